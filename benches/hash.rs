@@ -0,0 +1,23 @@
+//! Measures the throughput of the hash backends, run it with `cargo bench --bench hash`
+use std::time::Instant;
+
+use md5::Md5;
+use romst::hash::{HashBackend, RustCryptoBackend};
+use sha1::Sha1;
+
+fn main() {
+    let data = (0..64 * 1024 * 1024).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let megabytes = data.len() as f64 / (1024.0 * 1024.0);
+    let measure = |name: &str, hash: &mut dyn FnMut(&[u8]) -> String| {
+        let started = Instant::now();
+        let hash = hash(&data);
+        let elapsed = started.elapsed().as_secs_f64();
+        println!("{}: {:.0} MB/s ({})", name, megabytes / elapsed, hash);
+    };
+
+    let mut sha1 = RustCryptoBackend::<Sha1>::default();
+    measure("SHA1 (RustCrypto)", &mut |data| sha1.get_hash(data));
+    let mut md5 = RustCryptoBackend::<Md5>::default();
+    measure("MD5 (RustCrypto)", &mut |data| md5.get_hash(data));
+    measure("CRC32 (crc32fast)", &mut |data| format!("{:08x}", crc32fast::hash(data)));
+}
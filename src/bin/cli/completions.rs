@@ -0,0 +1,272 @@
+//! Shell completions generated from the definitions of the commands, so they always match the arguments of the build
+use anyhow::{Result, anyhow};
+use clap::{App, Arg, ArgSettings};
+use std::{io::{self, Write}, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(anyhow!("Non valid shell, can be either `bash`, `zsh` or `fish`"))
+        }
+    }
+}
+
+/// A command with the arguments that apply to it, the global arguments of the parents and the help included
+pub(super) struct CommandSpec<'a, 'help> {
+    pub path: Vec<&'a str>,
+    pub about: Option<&'a str>,
+    pub args: Vec<&'a Arg<'help>>,
+    pub subcommands: Vec<&'a App<'help>>,
+}
+
+impl<'a, 'help> CommandSpec<'a, 'help> {
+    /// The options and flags, the positional arguments are left out
+    pub fn options(&self) -> impl Iterator<Item = &&'a Arg<'help>> {
+        self.args.iter().filter(|arg| arg.get_long().is_some() || arg.get_short().is_some())
+    }
+
+    pub fn positionals(&self) -> impl Iterator<Item = &&'a Arg<'help>> {
+        self.args.iter().filter(|arg| arg.get_long().is_none() && arg.get_short().is_none())
+    }
+
+    fn name(&self, separator: &str) -> String {
+        self.path.join(separator)
+    }
+}
+
+/// Builds the app the same way it's built when parsing, so the global arguments and the help are added to every command,
+/// writing the help is the public way clap has to build it
+pub(super) fn build_app(app: &mut App) {
+    let _ = app.write_help(&mut io::sink());
+    for subcommand in app.get_subcommands_mut() {
+        build_app(subcommand);
+    }
+}
+
+/// All the commands of a built app, parents before their subcommands
+pub(super) fn get_commands<'a, 'help>(app: &'a App<'help>) -> Vec<CommandSpec<'a, 'help>> {
+    let mut commands = vec![];
+    add_commands(app, vec![], &mut commands);
+    commands
+}
+
+fn add_commands<'a, 'help>(app: &'a App<'help>, parent_path: Vec<&'a str>, commands: &mut Vec<CommandSpec<'a, 'help>>) {
+    let mut path = parent_path;
+    path.push(app.get_name());
+
+    let args = app.get_arguments().filter(|arg| !arg.is_set(ArgSettings::Hidden)).collect();
+    let subcommands = app.get_subcommands().collect::<Vec<_>>();
+    commands.push(CommandSpec { path: path.clone(), about: app.get_about(), args, subcommands: subcommands.clone() });
+    for subcommand in subcommands {
+        add_commands(subcommand, path.clone(), commands);
+    }
+}
+
+pub fn write_completions(f: &mut dyn Write, app: &mut App, shell: Shell) -> io::Result<()> {
+    build_app(app);
+    let commands = get_commands(app);
+    match shell {
+        Shell::Bash => write_bash(f, app.get_name(), &commands),
+        Shell::Zsh => write_zsh(f, app.get_name(), &commands),
+        Shell::Fish => write_fish(f, app.get_name(), &commands),
+    }
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.is_set(ArgSettings::TakesValue)
+}
+
+fn get_flags(arg: &Arg) -> Vec<String> {
+    let mut flags = vec![];
+    if let Some(short) = arg.get_short() {
+        flags.push(format!("-{}", short));
+    }
+    if let Some(long) = arg.get_long() {
+        flags.push(format!("--{}", long));
+    }
+    flags
+}
+
+fn write_bash(f: &mut dyn Write, bin_name: &str, commands: &[CommandSpec]) -> io::Result<()> {
+    let function = format!("_{}", bin_name.replace('-', "_"));
+    writeln!(f, "{}() {{", function)?;
+    writeln!(f, "    local cur prev cmd i")?;
+    writeln!(f, "    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(f, "    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+    writeln!(f, "    cmd=\"{}\"", bin_name)?;
+    writeln!(f)?;
+    writeln!(f, "    for (( i = 1; i < COMP_CWORD; i++ )); do")?;
+    writeln!(f, "        case \"${{cmd}}__${{COMP_WORDS[i]}}\" in")?;
+    let subcommand_paths = commands.iter().skip(1).map(|command| command.name("__")).collect::<Vec<_>>();
+    if !subcommand_paths.is_empty() {
+        writeln!(f, "            {})", subcommand_paths.join("|"))?;
+        writeln!(f, "                cmd=\"${{cmd}}__${{COMP_WORDS[i]}}\"")?;
+        writeln!(f, "                ;;")?;
+    }
+    writeln!(f, "        esac")?;
+    writeln!(f, "    done")?;
+    writeln!(f)?;
+    writeln!(f, "    case \"${{cmd}}\" in")?;
+    for command in commands {
+        writeln!(f, "        {})", command.name("__"))?;
+        writeln!(f, "            case \"${{prev}}\" in")?;
+        for arg in command.options().filter(|arg| takes_value(arg)) {
+            writeln!(f, "                {})", get_flags(arg).join("|"))?;
+            match arg.get_possible_values() {
+                Some(values) => writeln!(f, "                    COMPREPLY=( $(compgen -W \"{}\" -- \"${{cur}}\") )", values.join(" "))?,
+                None => writeln!(f, "                    COMPREPLY=( $(compgen -f -- \"${{cur}}\") )")?,
+            }
+            writeln!(f, "                    return 0")?;
+            writeln!(f, "                    ;;")?;
+        }
+        writeln!(f, "            esac")?;
+
+        let mut words = command.subcommands.iter().map(|subcommand| subcommand.get_name().to_string()).collect::<Vec<_>>();
+        for arg in command.positionals() {
+            words.extend(arg.get_possible_values().unwrap_or_default().iter().map(|value| value.to_string()));
+        }
+        words.extend(command.options().flat_map(|arg| get_flags(arg)));
+        writeln!(f, "            COMPREPLY=( $(compgen -W \"{}\" -- \"${{cur}}\") )", words.join(" "))?;
+        writeln!(f, "            ;;")?;
+    }
+    writeln!(f, "    esac")?;
+    writeln!(f, "}}")?;
+    writeln!(f)?;
+    writeln!(f, "complete -o filenames -F {} {}", function, bin_name)
+}
+
+fn zsh_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh_value(arg: &Arg) -> String {
+    match arg.get_possible_values() {
+        Some(values) => format!(":{}:({})", arg.get_name(), values.join(" ")),
+        None => format!(":{}:_files", arg.get_name()),
+    }
+}
+
+fn write_zsh(f: &mut dyn Write, bin_name: &str, commands: &[CommandSpec]) -> io::Result<()> {
+    writeln!(f, "#compdef {}", bin_name)?;
+    for command in commands {
+        writeln!(f)?;
+        writeln!(f, "_{}() {{", command.name("__"))?;
+        writeln!(f, "    local line state")?;
+        writeln!(f, "    _arguments -s -C \\")?;
+        for arg in command.options() {
+            let about = zsh_escape(arg.get_about().unwrap_or_default());
+            let value = if takes_value(arg) { zsh_value(arg) } else { String::new() };
+            let repeat = if arg.is_set(ArgSettings::MultipleOccurrences) || arg.is_set(ArgSettings::MultipleValues) { "*" } else { "" };
+            let flags = get_flags(arg);
+            if flags.len() > 1 {
+                writeln!(f, "        '{}({})'{{{}}}'[{}]{}' \\", repeat, flags.join(" "), flags.join(","), about, value)?;
+            } else {
+                writeln!(f, "        '{}{}[{}]{}' \\", repeat, flags[0], about, value)?;
+            }
+        }
+        for (position, arg) in command.positionals().enumerate() {
+            writeln!(f, "        '{}{}' \\", position + 1, zsh_value(arg))?;
+        }
+        if command.subcommands.is_empty() {
+            writeln!(f, "        && return 0")?;
+        } else {
+            writeln!(f, "        '1: :->command' \\")?;
+            writeln!(f, "        '*:: :->args' \\")?;
+            writeln!(f, "        && return 0")?;
+            writeln!(f)?;
+            writeln!(f, "    case $state in")?;
+            writeln!(f, "        command)")?;
+            writeln!(f, "            local -a commands")?;
+            writeln!(f, "            commands=(")?;
+            for subcommand in &command.subcommands {
+                writeln!(f, "                '{}:{}'", subcommand.get_name(), zsh_escape(subcommand.get_about().unwrap_or_default()))?;
+            }
+            writeln!(f, "            )")?;
+            writeln!(f, "            _describe -t commands 'command' commands")?;
+            writeln!(f, "            ;;")?;
+            writeln!(f, "        args)")?;
+            writeln!(f, "            case $line[1] in")?;
+            for subcommand in &command.subcommands {
+                writeln!(f, "                {}) _{}__{} ;;", subcommand.get_name(), command.name("__"), subcommand.get_name())?;
+            }
+            writeln!(f, "            esac")?;
+            writeln!(f, "            ;;")?;
+            writeln!(f, "    esac")?;
+        }
+        writeln!(f, "}}")?;
+    }
+    writeln!(f)?;
+    writeln!(f, "_{} \"$@\"", bin_name)
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn write_fish(f: &mut dyn Write, bin_name: &str, commands: &[CommandSpec]) -> io::Result<()> {
+    for command in commands {
+        // The root has no condition, the subcommands need their whole path and none of their own subcommands
+        let mut conditions = command.path.iter().skip(1)
+            .map(|name| format!("__fish_seen_subcommand_from {}", name))
+            .collect::<Vec<_>>();
+        if command.path.len() == 1 {
+            conditions.push("__fish_use_subcommand".to_string());
+        }
+        let condition = conditions.join("; and ");
+
+        for arg in command.options() {
+            let mut line = format!("complete -c {} -n '{}'", bin_name, condition);
+            if let Some(short) = arg.get_short() {
+                line.push_str(&format!(" -s {}", short));
+            }
+            if let Some(long) = arg.get_long() {
+                line.push_str(&format!(" -l {}", long));
+            }
+            if takes_value(arg) {
+                match arg.get_possible_values() {
+                    Some(values) => line.push_str(&format!(" -x -a '{}'", values.join(" "))),
+                    None => line.push_str(" -r -F"),
+                }
+            }
+            if let Some(about) = arg.get_about() {
+                line.push_str(&format!(" -d '{}'", fish_escape(about)));
+            }
+            writeln!(f, "{}", line)?;
+        }
+        for arg in command.positionals() {
+            if let Some(values) = arg.get_possible_values() {
+                writeln!(f, "complete -c {} -n '{}' -f -a '{}'", bin_name, condition, values.join(" "))?;
+            }
+        }
+
+        if !command.subcommands.is_empty() {
+            let names = command.subcommands.iter().map(|subcommand| subcommand.get_name()).collect::<Vec<_>>().join(" ");
+            let subcommand_condition = if command.path.len() == 1 {
+                condition.clone()
+            } else {
+                format!("{}; and not __fish_seen_subcommand_from {}", condition, names)
+            };
+            for subcommand in &command.subcommands {
+                let about = fish_escape(subcommand.get_about().unwrap_or_default());
+                writeln!(f, "complete -c {} -n '{}' -f -a '{}' -d '{}'", bin_name, subcommand_condition, subcommand.get_name(), about)?;
+            }
+        }
+    }
+    Ok(())
+}
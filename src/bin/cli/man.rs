@@ -0,0 +1,85 @@
+//! The man page in roff format, with a section for each command
+use clap::{App, Arg, ArgSettings};
+use std::io::{self, Write};
+
+use super::completions::{CommandSpec, build_app, get_commands};
+
+/// Escapes the text so roff doesn't read it as requests or escapes
+fn roff_escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+fn write_arg(f: &mut dyn Write, arg: &Arg) -> io::Result<()> {
+    let mut flags = vec![];
+    if let Some(short) = arg.get_short() {
+        flags.push(format!("\\fB\\-{}\\fR", short));
+    }
+    if let Some(long) = arg.get_long() {
+        flags.push(format!("\\fB\\-\\-{}\\fR", roff_escape(long)));
+    }
+    if flags.is_empty() {
+        flags.push(format!("\\fI<{}>\\fR", roff_escape(arg.get_name())));
+    } else if arg.is_set(ArgSettings::TakesValue) {
+        let last = flags.pop().unwrap_or_default();
+        flags.push(format!("{} \\fI<{}>\\fR", last, roff_escape(arg.get_name())));
+    }
+
+    writeln!(f, ".TP")?;
+    writeln!(f, "{}", flags.join(", "))?;
+    if let Some(about) = arg.get_about() {
+        writeln!(f, "{}", roff_escape(about))?;
+    }
+    if let Some(values) = arg.get_possible_values() {
+        writeln!(f, ".br")?;
+        writeln!(f, "Possible values: {}", roff_escape(&values.join(", ")))?;
+    }
+    Ok(())
+}
+
+fn write_command(f: &mut dyn Write, command: &CommandSpec) -> io::Result<()> {
+    writeln!(f, ".SS \"{}\"", roff_escape(&command.path.join(" ")))?;
+    if let Some(about) = command.about {
+        writeln!(f, "{}", roff_escape(about))?;
+    }
+    if !command.subcommands.is_empty() {
+        writeln!(f, ".PP")?;
+        let names = command.subcommands.iter().map(|subcommand| roff_escape(subcommand.get_name())).collect::<Vec<_>>();
+        writeln!(f, "Commands: {}", names.join(", "))?;
+    }
+    for arg in &command.args {
+        write_arg(f, arg)?;
+    }
+    Ok(())
+}
+
+pub fn write_man_page(f: &mut dyn Write, app: &mut App, version: &str) -> io::Result<()> {
+    build_app(app);
+    let name = app.get_name();
+    let commands = get_commands(app);
+
+    writeln!(f, ".TH {} 1 \"\" \"{} {}\"", roff_escape(&name.to_uppercase()), roff_escape(name), roff_escape(version))?;
+    writeln!(f, ".SH NAME")?;
+    match app.get_about() {
+        Some(about) => writeln!(f, "{} \\- {}", roff_escape(name), roff_escape(about))?,
+        None => writeln!(f, "{}", roff_escape(name))?,
+    }
+    writeln!(f, ".SH SYNOPSIS")?;
+    writeln!(f, "\\fB{}\\fR [OPTIONS] <COMMAND> [ARGS]", roff_escape(name))?;
+
+    if let Some((root, subcommands)) = commands.split_first() {
+        writeln!(f, ".SH OPTIONS")?;
+        for arg in &root.args {
+            write_arg(f, arg)?;
+        }
+        writeln!(f, ".SH COMMANDS")?;
+        for command in subcommands {
+            write_command(f, command)?;
+        }
+    }
+    Ok(())
+}
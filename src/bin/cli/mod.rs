@@ -0,0 +1,1006 @@
+//! The arguments of the command line, the completions and the man page are generated from them
+use anyhow::{Result, anyhow};
+use clap::Clap;
+use romst::{BiosSelection, EntryPaths, LooseFiles, RomsetMode, data::{filter::GameFilter, models::dat_info::Packing, sanitize::NamePolicy, reader::sqlite::{BrowseGrouping, SizeOrder}, writer::references::ReferenceFix, reporter::{miss_list::MissListFormat, sample_audit::AuditSample}}};
+use std::str::FromStr;
+
+pub mod completions;
+pub mod man;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    JsonPretty,
+    Plain,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "json-pretty" => Ok(OutputFormat::JsonPretty),
+            "plain" => Ok(OutputFormat::Plain),
+            _ => Err(anyhow!("Non valid output format, can be either `json`, `json-pretty` or `plain`"))
+        }
+    }
+}
+
+#[derive(Clap, Debug)]
+#[clap(name = "romst", version, author = "Nico H. <mail@nico2sh.com>")]
+pub struct Opts {
+    /// Only prints the results and errors, no progress is shown
+    #[clap(long, short, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Prints debug information
+    #[clap(long, short, global = true)]
+    pub verbose: bool,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Clap, Debug)]
+pub enum Command {
+    /// Loads the UI
+    Ui,
+    /// Import a DAT file into the database
+    Import(ImportArgs),
+    /// Manages the database
+    Db(DbArgs),
+    /// Gets information from roms and sets from the database
+    Info(InfoArgs),
+    /// Checks several files or a directory
+    Check(CheckArgs),
+    /// Checks a single file against a single set
+    CheckSet(CheckSetArgs),
+    /// Moves the spare roms and unknown files from a report out of the collection
+    Clean(CleanArgs),
+    /// Renames and copies the roms of the sets that can be fixed from a report, only zip archives are supported
+    Fix(FixArgs),
+    /// Copies the sets of a report into new zip archives in another directory, with the roms named as in the DAT
+    Rebuild(RebuildArgs),
+    /// Moves a collection to a new version of its DAT, rebuilding the sets into another directory as the new DAT expects
+    Migrate(MigrateArgs),
+    /// Copies a set with everything it needs in the mode, parent, bios, devices, disks and samples, into a folder or a zip
+    Bundle(BundleArgs),
+    /// Puts back the archives changed by a fix or a rebuild from their backup, undoing the run
+    Restore(RestoreArgs),
+    /// Manages the reports saved by the check command
+    Report(ReportArgs),
+    /// Writes the missing roms of a report in a format that other rom managers can import
+    Miss(MissArgs),
+    /// Writes the database back to DAT files
+    Export(ExportArgs),
+    /// Answers questions about a report, like which sets can be completed with the roms already in the collection
+    Analyze(AnalyzeArgs),
+    /// Manages a pool where each rom is stored once by its sha1
+    Pool(PoolArgs),
+    /// Tags the sets and attaches notes to them, the sets can be filtered by tag in other commands
+    Tag(TagArgs),
+    /// Writes the completions of the commands and their arguments for a shell
+    Completions(CompletionsArgs),
+    /// Writes the man page in roff format
+    Man,
+    /// Serves the import, check and info operations with JSON-RPC on a Unix socket
+    #[cfg(all(feature = "server", unix))]
+    Serve(ServeArgs),
+}
+
+/// The games to include and exclude, the rules are read from files
+#[derive(Clap, Debug)]
+pub struct FilterArgs {
+    /// A file with the games to include, a name or glob pattern per line, prefixed with `parent:` to match the parent, with `source:` to match the driver source file or with `category:` to match the category
+    #[clap(long)]
+    pub only_games: Option<String>,
+    /// A file with the games to exclude, in the same format as `--only-games`
+    #[clap(long)]
+    pub skip_games: Option<String>,
+    /// Only the sets with this tag, the rule files can also use `tag:` rules
+    #[clap(long)]
+    pub tag: Vec<String>,
+    /// Skips the sets with this tag
+    #[clap(long)]
+    pub skip_tag: Vec<String>,
+}
+
+impl FilterArgs {
+    pub fn get_game_filter(&self) -> Result<GameFilter> {
+        let mut game_filter = GameFilter::default();
+        if let Some(file) = &self.only_games {
+            game_filter.include_from_file(file)?;
+        }
+        if let Some(file) = &self.skip_games {
+            game_filter.exclude_from_file(file)?;
+        }
+        for tag in &self.tag {
+            game_filter.add_include(&format!("tag:{}", tag))?;
+        }
+        for tag in &self.skip_tag {
+            game_filter.add_exclude(&format!("tag:{}", tag))?;
+        }
+        Ok(game_filter)
+    }
+}
+
+#[derive(Clap, Debug)]
+pub struct ImportArgs {
+    /// Source DAT file, or a directory with DAT files. With several sources all of them are imported into the same database
+    #[clap(long, short)]
+    #[cfg_attr(not(feature = "net"), clap(setting = clap::ArgSettings::Required))]
+    #[cfg_attr(feature = "net", clap(required_unless_present = "url"))]
+    pub source: Vec<String>,
+    /// Downloads the DAT to import from this url, it can be gzipped or zipped
+    #[cfg(feature = "net")]
+    #[clap(long, short, conflicts_with = "source")]
+    pub url: Option<String>,
+    /// The expected sha1 of the downloaded file, the import fails if it doesn't match
+    #[cfg(feature = "net")]
+    #[clap(long, requires = "url")]
+    pub sha1: Option<String>,
+    /// Destination file. If not specified, uses the source file or directory name as reference, it's required with several sources
+    #[clap(long = "db", short = 'd')]
+    pub dest: Option<String>,
+    /// Overwrites the destination file if exists
+    #[clap(short = 'w')]
+    pub overwrite: bool,
+    /// Builds the rom index after importing, it speeds up checking large collections
+    #[clap(long, short = 'x')]
+    pub index: bool,
+    /// Also imports the driver status, number of players and screen orientation of the games
+    #[clap(long, short)]
+    pub extended_metadata: bool,
+    /// Number of threads to import, with 2 or more the entries are written while the DAT is parsed
+    #[clap(long, short, default_value = "1")]
+    pub threads: usize,
+    /// Fails the import if more than this number of entries can't be read, by default they are skipped and listed at the end
+    #[clap(long)]
+    pub max_errors: Option<u32>,
+    /// Compacts the database at the end, it reclaims the space of the games removed by the filter
+    #[clap(long)]
+    pub vacuum: bool,
+    /// Fixes the clones, bios and devices referencing games not in the DATs, creating empty games for them or removing the references
+    #[clap(long, possible_values = &["stub", "drop"])]
+    pub fix_references: Option<ReferenceFix>,
+    /// Only reads the DATs and lists their problems and the estimated database size, nothing is written
+    #[clap(long)]
+    #[cfg_attr(feature = "net", clap(conflicts_with = "url"))]
+    pub validate_only: bool,
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[derive(Clap, Debug)]
+pub struct DbArgs {
+    #[clap(subcommand)]
+    pub command: DbCommand,
+}
+
+#[derive(Clap, Debug)]
+pub enum DbCommand {
+    /// Builds the rom index for the database, it speeds up checking large collections
+    Index(DbIndexArgs),
+    /// Forces the romset mode a set is always checked with, like non-merged for bios sets. Without arguments lists the sets with a forced mode
+    SetMode(DbSetModeArgs),
+    /// Compacts the database and shows the space reclaimed
+    Vacuum(DbVacuumArgs),
+    /// Ignores roms that will never be found, like undumped roms, so their sets can be complete without them. Without roms lists the ignored ones
+    IgnoreRoms(DbIgnoreRomsArgs),
+    /// Lists the clones, bios and devices referencing games that aren't in the database, and fixes them if asked
+    Verify(DbVerifyArgs),
+    /// Packs the database into a compressed bundle, to take it to another machine
+    Export(DbExportArgs),
+    /// Unpacks the database of a bundle made with the export command, migrating it if it's from an older version
+    Import(DbImportArgs),
+}
+
+#[derive(Clap, Debug)]
+pub struct DbIndexArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct DbVacuumArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct DbVerifyArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Creates empty games for the ones referenced, or removes the references
+    #[clap(long, possible_values = &["stub", "drop"])]
+    pub fix: Option<ReferenceFix>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct DbExportArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// The bundle file. If not specified, uses the database name with the `romstdb` extension
+    #[clap(long, short)]
+    pub output: Option<String>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct DbImportArgs {
+    /// The bundle made with the export command
+    #[clap(long, short)]
+    pub source: String,
+    /// Destination file. If not specified, uses the bundle name with the `rst` extension
+    #[clap(long = "db", short = 'd')]
+    pub dest: Option<String>,
+    /// Overwrites the destination file if exists
+    #[clap(short = 'w')]
+    pub overwrite: bool,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct DbSetModeArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// The set to force the mode for
+    #[clap(long, short)]
+    pub game: Option<String>,
+    /// The mode the set is checked with
+    #[clap(long, short, possible_values = &["merged", "non-merged", "split"], requires = "game")]
+    pub mode: Option<RomsetMode>,
+    /// Removes the forced mode of the set, so it uses the mode of the check
+    #[clap(long, short, requires = "game", conflicts_with = "mode")]
+    pub clear: bool,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct DbIgnoreRomsArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Ignores the rom with this sha1 in every set
+    #[clap(long)]
+    pub sha1: Vec<String>,
+    /// Ignores a rom of a set only, written as `set/rom`
+    #[clap(long, short)]
+    pub rom: Vec<String>,
+    /// Takes the roms out of the ignore list instead
+    #[clap(long)]
+    pub remove: bool,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct InfoArgs {
+    #[clap(subcommand)]
+    pub command: InfoCommand,
+}
+
+#[derive(Clap, Debug)]
+pub enum InfoCommand {
+    /// Gets stats info from the database
+    Data(InfoDataArgs),
+    /// Gets information from the database for Romsets
+    Set(InfoSetArgs),
+    /// Shows the sets using a rom of a set, with the name the rom has in each of them
+    #[clap(name = "romusage")]
+    RomUsage(RomUsageArgs),
+    /// Shows the other sets with roms of a set, and which of its roms they have. A set sharing all its roms can be
+    /// built from the set
+    #[clap(name = "sharedroms")]
+    SharedRoms(SharedRomsArgs),
+    /// Shows which sets use a rom from the database, by its id
+    #[clap(name = "romid")]
+    RomId(RomIdArgs),
+    /// Counts the verified, good, bad and missing dumps of the database, or of some sets
+    Quality(QualityArgs),
+    /// Counts the sets grouped by year, manufacturer, driver or first letter
+    Browse(BrowseArgs),
+    /// Shows how many rom bytes each romset mode stores, and how much is saved by sharing the roms across sets
+    #[clap(name = "dedupe-stats")]
+    DedupeStats(InfoDataArgs),
+    /// Finds the roms of the database by the start of their hashes or by their size, with the sets using them
+    Find(InfoFindArgs),
+    /// Lists the sets storing the most rom bytes in a mode and the largest roms, to plan the storage
+    Sizes(InfoSizesArgs),
+}
+
+#[derive(Clap, Debug)]
+pub struct InfoSizesArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// The romset mode the sets are stored with
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split"], default_value = "non-merged")]
+    pub set_mode: RomsetMode,
+    /// Lists the largest or the smallest sets and roms
+    #[clap(long, short, possible_values = &["largest", "smallest"], default_value = "largest")]
+    pub order: SizeOrder,
+    /// How many sets and roms are listed
+    #[clap(long, short, default_value = "20")]
+    pub limit: u32,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct InfoFindArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// The roms with a sha1 starting with these hexadecimal digits
+    #[clap(long)]
+    pub sha1_prefix: Option<String>,
+    /// The roms with a md5 starting with these hexadecimal digits
+    #[clap(long)]
+    pub md5_prefix: Option<String>,
+    /// The roms with a crc starting with these hexadecimal digits
+    #[clap(long)]
+    pub crc_prefix: Option<String>,
+    /// The roms of this size in bytes or bigger
+    #[clap(long)]
+    pub size_min: Option<u32>,
+    /// The roms of this size in bytes or smaller
+    #[clap(long)]
+    pub size_max: Option<u32>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct InfoDataArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct InfoSetArgs {
+    /// A list of games to retrieve the information from
+    #[clap(long, short, required = true)]
+    pub games: Vec<String>,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Sets the romset mode
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split"], default_value = "non-merged")]
+    pub set_mode: RomsetMode,
+    /// A report created with the check command, it adds where each rom of the sets was found, use the mode of the check
+    #[clap(long, short)]
+    pub report: Option<String>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct RomUsageArgs {
+    /// The game to get the rom to search
+    #[clap(long, short)]
+    pub game: String,
+    /// The name of the rom in the set
+    #[clap(long, short)]
+    pub rom: String,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Sets the romset mode
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split"], default_value = "non-merged")]
+    pub set_mode: RomsetMode,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct SharedRomsArgs {
+    /// The set with the roms to search
+    #[clap(long, short)]
+    pub game: String,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Sets the romset mode
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split"], default_value = "non-merged")]
+    pub set_mode: RomsetMode,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct RomIdArgs {
+    /// The id of the rom in the database
+    #[clap(long, short)]
+    pub id: u32,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct QualityArgs {
+    /// A list of games to get the dump quality from
+    #[clap(long, short)]
+    pub games: Vec<String>,
+    /// Adds the sets with this tag to the games
+    #[clap(long)]
+    pub tag: Option<String>,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Sets the romset mode
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split"], default_value = "non-merged")]
+    pub set_mode: RomsetMode,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct BrowseArgs {
+    /// How to group the sets
+    #[clap(long, short, possible_values = &["year", "manufacturer", "driver", "letter"], default_value = "year")]
+    pub by: BrowseGrouping,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct CheckArgs {
+    /// A directory or list of files to check, it accepts glob patterns like `roms/*.zip`, and `@file` to read the paths from a file, one per line, or `-` to read them from the standard input
+    #[clap(long, short, required = true)]
+    pub source: Vec<String>,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short, required_unless_present = "dat")]
+    pub db: Option<String>,
+    /// Checks against a DAT file instead of a database, it's imported in memory for this check only
+    #[clap(long, conflicts_with_all = &["db", "summary-only"])]
+    pub dat: Option<String>,
+    /// Sets the romset mode, `auto` detects it from a sample of the files
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split", "auto"], default_value = "non-merged")]
+    pub set_mode: String,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+    /// Which bios variants are required, either `all`, `none` or the name of a bios
+    #[clap(long, short, default_value = "all")]
+    pub bios: BiosSelection,
+    /// Destination file for the report (if not specified, prints in text format on screen)
+    #[clap(long, short, conflicts_with = "format")]
+    pub report: Option<String>,
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+    /// How the paths of the entries inside the archives are compared with the DAT, `full` reports the roms in another folder to be renamed, `file-name` ignores the folders
+    #[clap(long, possible_values = &["full", "file-name"], default_value = "full")]
+    pub entry_paths: EntryPaths,
+    /// Extracts the archives to verify their structure and the crc of every entry, reporting the corrupt ones
+    #[clap(long)]
+    pub deep_verify: bool,
+    /// Accepts the zips named as a set whose directory lists the names, sizes and crcs of its roms without extracting them, only the rest are hashed. Faster to check again a verified collection, but corrupt data behind a right directory is not detected
+    #[clap(long, conflicts_with = "deep-verify")]
+    pub quick: bool,
+    /// How the loose files outside the archives are checked, `basename` groups the files with the same name, like a `.cue` with its `.bin` tracks, into a set, `dat` groups them by the game of the DAT with a rom of the same name
+    #[clap(long, possible_values = &["ignore", "basename", "dat"], default_value = "ignore")]
+    pub loose_files: LooseFiles,
+    /// A CSV with an `alias,set` line for each name a set is known by, or an ini with `alias=set` lines. The files named as an alias belong to the set and are reported to be renamed
+    #[clap(long)]
+    pub aliases: Option<String>,
+    /// Limits the read bandwidth of the scan in MB/s, so it doesn't saturate the disk
+    #[clap(long)]
+    pub throttle: Option<f64>,
+    /// Reads the files with the lowest IO priority, only when no other program uses the disk. Only supported in Linux
+    #[clap(long)]
+    pub nice_io: bool,
+    /// Takes the roms named as a rebuild with this name policy writes them as named as in the DAT
+    #[clap(long, possible_values = &["strict", "compatible", "passthrough"], default_value = "passthrough")]
+    pub names: NamePolicy,
+    /// Prints only the counts of sets by status and the sets broken since the last check, to run it unattended
+    #[clap(long, conflicts_with = "report")]
+    pub summary_only: bool,
+    /// Checks even if the DAT is meant to be used in another mode, the sets missing or to spare may be misleading
+    #[clap(long)]
+    pub force: bool,
+    /// Times a file failing with a transient IO error, like a network share not answering, is read again
+    #[clap(long, default_value = "2")]
+    pub retries: u32,
+    /// Milliseconds to wait before reading a file again the first time, it doubles on each retry
+    #[clap(long, default_value = "500")]
+    pub retry_backoff: u64,
+    /// Checks again only the files of this report that failed with a transient error, the source is the directory checked. The report updated is the result
+    #[clap(long, conflicts_with_all = &["dat", "summary-only"])]
+    pub retry_errors: Option<String>,
+    /// Hashes only this percentage of the zips named as a set, like `5%`, the rest are accepted by their directory as with `--quick`. The report estimates from the sample how many of the zips accepted would fail when hashed, for periodic audits of a verified collection
+    #[clap(long, conflicts_with_all = &["deep-verify", "retry-errors"])]
+    pub sample: Option<AuditSample>,
+    /// The seed the zips of the sample are picked with, the same seed picks the same zips of the same files again
+    #[clap(long, requires = "sample")]
+    pub sample_seed: Option<u64>,
+}
+
+#[derive(Clap, Debug)]
+pub struct CheckSetArgs {
+    /// The file to check
+    #[clap(long, short)]
+    pub source: String,
+    /// The set to check the file against
+    #[clap(long, short)]
+    pub game: String,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Sets the romset mode
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split"], default_value = "non-merged")]
+    pub set_mode: RomsetMode,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+    /// How the paths of the entries inside the archives are compared with the DAT, `full` reports the roms in another folder to be renamed, `file-name` ignores the folders
+    #[clap(long, possible_values = &["full", "file-name"], default_value = "full")]
+    pub entry_paths: EntryPaths,
+    /// Checks even if the DAT is meant to be used in another mode, the roms missing or to spare may be misleading
+    #[clap(long)]
+    pub force: bool,
+}
+
+#[derive(Clap, Debug)]
+pub struct CleanArgs {
+    /// The report file created with the check command
+    #[clap(long, short, required_unless_present = "undo")]
+    pub report: Option<String>,
+    /// The directory where the files are moved to
+    #[clap(long, short)]
+    pub target: String,
+    /// The directory with the files, if not specified, uses the directory from the report
+    #[clap(long, short)]
+    pub source: Option<String>,
+    /// Shows what would be moved, without moving anything
+    #[clap(long, short = 'n')]
+    pub dry_run: bool,
+    /// Restores the files moved to the target directory by a previous clean
+    #[clap(long, short, conflicts_with_all = &["report", "source", "dry-run"])]
+    pub undo: bool,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct FixArgs {
+    /// The report file created with the check command
+    #[clap(long, short)]
+    pub report: String,
+    /// Shows the operations and the disk space they need, without changing anything
+    #[clap(long, short = 'n')]
+    pub dry_run: bool,
+    /// Runs even if the free space of the disk is less than the estimate, the roms are counted uncompressed so it's
+    /// usually less
+    #[clap(long)]
+    pub skip_space_check: bool,
+    /// A list of the sets to do first, in order, a set per line or `tag:<tag>` for the sets with a tag. If the run is
+    /// interrupted with Ctrl+C, running it again continues with the remaining sets
+    #[clap(long)]
+    pub priority: Option<String>,
+    /// The ROMST database with the tags used in the priority list
+    #[clap(long, short)]
+    pub db: Option<String>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct RestoreArgs {
+    /// The manifest of the backup, in the folder next to the report named after the time of the run
+    #[clap(long, short)]
+    pub manifest: String,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct RebuildArgs {
+    /// The report file created with the check command
+    #[clap(long, short)]
+    pub report: String,
+    /// The directory where the sets are rebuilt
+    #[clap(long, short)]
+    pub target: String,
+    /// Shows the sets to rebuild with the estimated time and disk usage, without writing anything
+    #[clap(long, short = 'n')]
+    pub dry_run: bool,
+    /// Runs even if the free space of the disk is less than the estimate, the roms are counted uncompressed so it's
+    /// usually less
+    #[clap(long)]
+    pub skip_space_check: bool,
+    /// A list of the sets to do first, in order, a set per line or `tag:<tag>` for the sets with a tag. If the run is
+    /// interrupted with Ctrl+C, running it again continues with the remaining sets
+    #[clap(long)]
+    pub priority: Option<String>,
+    /// The ROMST database with the tags used in the priority list
+    #[clap(long, short)]
+    pub db: Option<String>,
+    /// The speed in megabytes per second used to estimate the time, 50 if not specified
+    #[clap(long)]
+    pub throughput: Option<f64>,
+    /// Writes the sets as zip archives or unpacked in folders, by default as the DAT expects, or zipped if it doesn't say
+    #[clap(long, possible_values = &["zip", "unzip"])]
+    pub packing: Option<Packing>,
+    /// How the rom names not valid in every filesystem are written, `compatible` replaces what Windows and FAT don't
+    /// allow, `strict` also the characters that aren't ASCII. The roms written with another name are listed
+    #[clap(long, possible_values = &["strict", "compatible", "passthrough"], default_value = "passthrough")]
+    pub names: NamePolicy,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct MigrateArgs {
+    /// The ROMST database of the DAT the collection was built for, it tells the sets renamed in the new DAT
+    #[clap(long)]
+    pub from: String,
+    /// The ROMST database of the new DAT
+    #[clap(long)]
+    pub to: String,
+    /// The directory with the collection
+    #[clap(long, short)]
+    pub source: String,
+    /// The directory where the migrated sets are rebuilt
+    #[clap(long, short)]
+    pub target: String,
+    /// Sets the romset mode
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split"], default_value = "non-merged")]
+    pub set_mode: RomsetMode,
+    /// Writes the sets as zip archives or unpacked in folders, by default as the new DAT expects, or zipped if it doesn't say
+    #[clap(long, possible_values = &["zip", "unzip"])]
+    pub packing: Option<Packing>,
+    /// Shows what each set needs without writing anything, with the json format the plan can be exported
+    #[clap(long, short = 'n')]
+    pub dry_run: bool,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct BundleArgs {
+    /// The name of the game to bundle
+    #[clap(long, short)]
+    pub game: String,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// The directory with the collection
+    #[clap(long, short)]
+    pub source: String,
+    /// The directory with the sample sets, `samples` in the source directory if not specified
+    #[clap(long)]
+    pub samples: Option<String>,
+    /// The directory where the bundle is written
+    #[clap(long, short)]
+    pub target: String,
+    /// Sets the romset mode
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split"], default_value = "non-merged")]
+    pub set_mode: RomsetMode,
+    /// Writes the bundle as a single zip, `<game>-bundle.zip`
+    #[clap(long, short)]
+    pub zip: bool,
+    /// Lists the files of the bundle and what's missing without writing anything
+    #[clap(long, short = 'n')]
+    pub dry_run: bool,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct MissArgs {
+    /// The report file created with the check command
+    #[clap(long, short)]
+    pub report: String,
+    /// `clrmamepro` writes a fix DAT, `romcenter` a csv with a row per rom
+    #[clap(name = "type", long = "type", short = 't', possible_values = &["clrmamepro", "romcenter"], default_value = "clrmamepro")]
+    pub list_format: MissListFormat,
+    /// The file to write the list to, if not specified, it's printed
+    #[clap(long, short)]
+    pub output: Option<String>,
+}
+
+#[derive(Clap, Debug)]
+pub struct ReportArgs {
+    #[clap(subcommand)]
+    pub command: ReportCommand,
+}
+
+#[derive(Clap, Debug)]
+pub enum ReportCommand {
+    /// Merges the reports of separate scans in a single one, a set gets the roms found by any of them
+    Merge(ReportMergeArgs),
+    /// Lists the sets of the database a report is missing, counting only the sets in the scope so checking part of a collection doesn't count the rest as missing. The scope can also be an explicit list with `--only-games`
+    Missing(ReportMissingArgs),
+}
+
+#[derive(Clap, Debug)]
+pub struct ReportMergeArgs {
+    /// The report files created with the check command, they must be checked with the same set mode
+    #[clap(required = true, min_values = 2)]
+    pub reports: Vec<String>,
+    /// The file to write the merged report to
+    #[clap(long, short)]
+    pub output: String,
+}
+
+#[derive(Clap, Debug)]
+pub struct ReportMissingArgs {
+    /// The report file created with the check command
+    pub report: String,
+    /// The ROMST database the report was checked against
+    #[clap(long, short)]
+    pub db: String,
+    /// Only the sets whose name starts with this prefix
+    #[clap(long)]
+    pub prefix: Vec<String>,
+    /// Only the sets of this driver source file, it can be a glob pattern like `*neogeo.cpp`
+    #[clap(long)]
+    pub driver: Vec<String>,
+    /// Only the sets of this category from the DAT, it can be a glob pattern like `Fighter/*`
+    #[clap(long)]
+    pub category: Vec<String>,
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "plain")]
+    pub format: OutputFormat,
+}
+
+impl ReportMissingArgs {
+    /// The prefixes, drivers and categories are included as any of the rules of `--only-games`
+    pub fn get_scope(&self) -> Result<GameFilter> {
+        let mut scope = self.filter.get_game_filter()?;
+        for prefix in &self.prefix {
+            scope.add_include(&format!("name:{}*", prefix))?;
+        }
+        for driver in &self.driver {
+            scope.add_include(&format!("source:{}", driver))?;
+        }
+        for category in &self.category {
+            scope.add_include(&format!("category:{}", category))?;
+        }
+        Ok(scope)
+    }
+}
+
+#[derive(Clap, Debug)]
+pub struct ExportArgs {
+    #[clap(subcommand)]
+    pub command: ExportCommand,
+}
+
+#[derive(Clap, Debug)]
+pub enum ExportCommand {
+    /// Writes the sets of the database as a DAT in the Logiqx XML format
+    Dat(ExportDatArgs),
+}
+
+#[derive(Clap, Debug)]
+pub struct ExportDatArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// The DAT file to write, or the directory for the DATs when they are split
+    #[clap(long, short)]
+    pub output: String,
+    /// Writes a smaller DAT for each driver source file or first letter of the sets instead of a single one
+    #[clap(long, possible_values = &["driver", "letter"])]
+    pub split_by: Option<BrowseGrouping>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct AnalyzeArgs {
+    #[clap(subcommand)]
+    pub command: AnalyzeCommand,
+}
+
+#[derive(Clap, Debug)]
+pub enum AnalyzeCommand {
+    /// Lists the sets that can be completed moving roms already in the collection, with all the moves together
+    Fixable(AnalyzeFixableArgs),
+}
+
+#[derive(Clap, Debug)]
+pub struct AnalyzeFixableArgs {
+    /// The report file created with the check command
+    #[clap(long, short)]
+    pub report: String,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct TagArgs {
+    #[clap(subcommand)]
+    pub command: TagCommand,
+}
+
+#[derive(Clap, Debug)]
+pub enum TagCommand {
+    /// Adds tags to a set, like `favorite`, `skip` or `broken-dump`
+    Add(TagAddArgs),
+    /// Removes tags from a set, or all of them if there are no tags
+    Remove(TagRemoveArgs),
+    /// Attaches a note to a set, without text the note is removed
+    Note(TagNoteArgs),
+    /// Lists the sets with tags or notes
+    List(TagListArgs),
+}
+
+#[derive(Clap, Debug)]
+pub struct TagAddArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// The set to tag
+    #[clap(long, short)]
+    pub game: String,
+    /// The tags to add
+    #[clap(long, short, required = true)]
+    pub tag: Vec<String>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct TagRemoveArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// The set to remove the tags from
+    #[clap(long, short)]
+    pub game: String,
+    /// The tags to remove, all of them if not specified
+    #[clap(long, short)]
+    pub tag: Vec<String>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct TagNoteArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// The set to attach the note to
+    #[clap(long, short)]
+    pub game: String,
+    /// The text of the note
+    #[clap(long, short)]
+    pub text: Option<String>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct TagListArgs {
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Only the sets with this tag
+    #[clap(long, short)]
+    pub tag: Option<String>,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct PoolArgs {
+    #[clap(subcommand)]
+    pub command: PoolCommand,
+}
+
+#[derive(Clap, Debug)]
+pub enum PoolCommand {
+    /// Adds the roms from files, zip files or directories to the pool
+    Add(PoolAddArgs),
+    /// Writes sets as zip files with the roms from the pool
+    Build(PoolBuildArgs),
+}
+
+#[derive(Clap, Debug)]
+pub struct PoolAddArgs {
+    /// The pool directory
+    #[clap(long, short)]
+    pub pool: String,
+    /// The files or directories to add
+    #[clap(long, short, required = true)]
+    pub source: Vec<String>,
+    /// Stores the roms compressed with zstd
+    #[clap(long, short = 'z')]
+    pub compress: bool,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Clap, Debug)]
+pub struct PoolBuildArgs {
+    /// The pool directory
+    #[clap(long, short)]
+    pub pool: String,
+    /// The sets to build
+    #[clap(long, short, required = true)]
+    pub games: Vec<String>,
+    /// The directory where the sets are written
+    #[clap(long, short)]
+    pub target: String,
+    /// The ROMST database to use. You can create one with the import command
+    #[clap(long, short)]
+    pub db: String,
+    /// Sets the romset mode
+    #[clap(long, short = 'm', possible_values = &["merged", "non-merged", "split"], default_value = "non-merged")]
+    pub set_mode: RomsetMode,
+    /// Choose the format for the output
+    #[clap(long, short, possible_values = &["json", "json-pretty", "plain"], default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[cfg(all(feature = "server", unix))]
+#[derive(Clap, Debug)]
+pub struct ServeArgs {
+    /// The path of the Unix socket to listen on
+    #[clap(long, short)]
+    pub socket: String,
+}
+
+#[derive(Clap, Debug)]
+pub struct CompletionsArgs {
+    /// The shell to write the completions for
+    #[clap(possible_values = &["bash", "zsh", "fish"])]
+    pub shell: completions::Shell,
+}
@@ -1,185 +1,102 @@
-use clap::{App, Arg, ArgMatches, crate_version};
-use anyhow::{Result, anyhow};
+use clap::{Clap, IntoApp};
 use console::Style;
 use env_logger::{Builder, Env, Target};
-use romst::{RomsetMode, Romst, sysout::{DatImporterReporterSysOut, ReportReporterSysOut}};
+use romst::{DEFAULT_MODE_DETECTION_SAMPLE, ImportOptions, data::{aliases::SetAliases, bundle::BundleOptions, portable::BUNDLE_EXTENSION, ignored_roms::IgnoredRoms, priority::SetPriority, reader::RomFilter, rebuilder::DEFAULT_REBUILD_THROUGHPUT, reporter::{CancellationToken, mode_detection::ModeMismatch}}, IoLimits, IoRetry, RomsetMode, Romst, ScanOptions, sysout::{DatImporterReporterSysOut, ReportFileReporterSysOut, ReportReporterSysOut, Verbosity}};
 use serde::Serialize;
-use std::{fmt::Display, path::Path, str::FromStr};
+use std::{fmt::Display, fs, io, path::Path};
 
+use cli::{AnalyzeCommand, AnalyzeFixableArgs, BundleArgs, ExportCommand, ExportDatArgs, ReportCommand, ReportMergeArgs, ReportMissingArgs, CheckArgs, CheckSetArgs, CleanArgs, Command, CompletionsArgs, DbCommand, DbExportArgs, DbIgnoreRomsArgs, DbImportArgs, DbIndexArgs, DbSetModeArgs, DbVacuumArgs, DbVerifyArgs, FixArgs, ImportArgs, MigrateArgs, RebuildArgs, RestoreArgs, InfoCommand, InfoDataArgs, InfoFindArgs, InfoSetArgs, InfoSizesArgs, MissArgs, Opts, OutputFormat, PoolAddArgs, PoolBuildArgs, PoolCommand, QualityArgs, RomIdArgs, RomUsageArgs, SharedRomsArgs, BrowseArgs, TagCommand};
+#[cfg(all(feature = "server", unix))]
+use cli::ServeArgs;
+
+mod cli;
 mod ui_cursive;
 
 const DB_EXTENSION: &str = "rst";
 
-enum OutputFormat {
-    Json,
-    JsonPretty,
-    Plain,
-}
-
-impl FromStr for OutputFormat {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        match s {
-            "json" => Ok(OutputFormat::Json),
-            "json-pretty" => Ok(OutputFormat::JsonPretty),
-            "plain" => Ok(OutputFormat::Plain),
-            _ => Err(anyhow!("Non valid ROM Set Mode, can be either `merged`, `split` or `non-merged`"))
-        }
-    }
-}
-
-impl Default for OutputFormat {
-    fn default() -> Self {
-        OutputFormat::Json
-    }
-}
-
-fn create_matches() -> ArgMatches {
-    let arg_db = Arg::new("db")
-        .about("The ROMST database to use. You can create one with the import command")
-        .long("db")
-        .short('d')
-        .takes_value(true)
-        .required(true);
-    let arg_set_mode = Arg::new("set-mode")
-        .about("Sets the romset mode")
-        .long("set-mode")
-        .short('m')
-        .possible_values(&["merge", "non-merged", "split"])
-        .default_value("non-merged")
-        .takes_value(true)
-        .required(false);
-    let arg_format = Arg::new("format")
-        .about("Choose the format for the output")
-        .long("format")
-        .short('f')
-        .possible_values(&["json", "json-pretty", "plain"])
-        .default_value("json")
-        .takes_value(true)
-        .required(false);
-
-    let matches = App::new("romst")
-        .version(crate_version!())
-        .author("Nico H. <mail@nico2sh.com>")
-        .subcommand(App::new("ui")
-            .about("Loads the UI"))
-        .subcommand(App::new("import")
-            .about("Import a DAT file into the database")
-            .arg(Arg::new("source")
-                .about("Source DAT file")
-                .long("source")
-                .short('s')
-                .about("Source DAT file")
-                .takes_value(true)
-                .required(true))
-            .arg(Arg::new("dest")
-                .long("db")
-                .short('d')
-                .about("Destination file. If not specified, uses the source file name as reference")
-                .takes_value(true)
-                .required(false))
-            .arg(Arg::new("overwrite")
-                .short('w')
-                .about("Overwrites the destination file if exists")
-                .takes_value(false)
-                .required(false)))
-        .subcommand(App::new("info")
-            .about("Gets information from roms and sets from the database")
-            .subcommand(App::new("data")
-                .about("Gets stats info from the database")
-                .arg(arg_db.clone())
-                .arg(arg_format.clone()))
-            .subcommand(App::new("set")
-                .about("Gets information from the database for Romsets")
-                .arg(Arg::new("games")
-                    .about("A list of games to retrieve the information from")
-                    .long("games")
-                    .short('g')
-                    .takes_value(true)
-                    .multiple(true)
-                    .required(true))
-                .arg(arg_db.clone())
-                .arg(arg_set_mode.clone())
-                .arg(arg_format.clone()))
-            .subcommand(App::new("romusage")
-                .about("Shows which sets a Rom is used")
-                .arg(Arg::new("game")
-                    .about("The game to get the rom to search")
-                    .long("game")
-                    .short('g')
-                    .takes_value(true)
-                    .required(true))
-                .arg(Arg::new("rom")
-                    .about("The romname to search, if empty, Romst will list all the roms present in the romset")
-                    .long("rom")
-                    .short('r')
-                    .takes_value(true)
-                    .required(false))
-                .arg(arg_db.clone())
-                .arg(arg_set_mode.clone()))
-                .arg(arg_format.clone()))
-        .subcommand(App::new("check")
-            .about("Checks several files or a directory")
-            .arg(Arg::new("source")
-                .about("A directory or list of files to check")
-                .long("source")
-                .short('s')
-                .takes_value(true)
-                .multiple(true)
-                .required(true))
-            .arg(arg_db.clone())
-            .arg(arg_set_mode.clone())
-            .arg(arg_format.clone())
-            .arg(Arg::new("report")
-                .about("Destination file for the report (if not specified, prints in text format on screen)")
-                .long("report")
-                .short('r')
-                .takes_value(true)
-                .required(false)
-                .conflicts_with("format")))
-        .get_matches();
-
-        matches
-}
-
 fn main() {
-    let mut builder = Builder::from_env(Env::default().default_filter_or("warn"));
+    let opts = Opts::parse();
+    // The flags are global, so they can be set either before or after the subcommand
+    let verbosity = Verbosity::from_flags(opts.quiet, opts.verbose);
+
+    let mut builder = Builder::from_env(Env::default().default_filter_or(verbosity.log_level().as_str()));
     builder.target(Target::Stdout);
     builder.init();
 
-    let matches = create_matches();
-
-    match matches.subcommand() {
-        Some(("ui", ui_matches)) => ui(ui_matches),
-        Some(("import", import_matches)) => import(import_matches),
-        Some(("info", info_matches)) => info(info_matches),
-        Some(("check", check_matches)) => check(check_matches),
-        Some(_) => {}
+    match opts.command {
+        Some(Command::Ui) => ui(),
+        Some(Command::Import(args)) => import(args, verbosity),
+        Some(Command::Info(args)) => info(args.command, verbosity),
+        Some(Command::Db(args)) => db(args.command, verbosity),
+        Some(Command::Check(args)) => check(args, verbosity),
+        Some(Command::CheckSet(args)) => check_set(args, verbosity),
+        Some(Command::Clean(args)) => clean(args, verbosity),
+        Some(Command::Fix(args)) => fix(args, verbosity),
+        Some(Command::Rebuild(args)) => rebuild(args, verbosity),
+        Some(Command::Migrate(args)) => migrate(args, verbosity),
+        Some(Command::Bundle(args)) => bundle(args, verbosity),
+        Some(Command::Restore(args)) => restore(args, verbosity),
+        Some(Command::Report(args)) => report(args.command, verbosity),
+        Some(Command::Miss(args)) => miss(args, verbosity),
+        Some(Command::Export(args)) => export(args.command, verbosity),
+        Some(Command::Analyze(args)) => analyze(args.command, verbosity),
+        Some(Command::Pool(args)) => pool(args.command, verbosity),
+        Some(Command::Tag(args)) => tag(args.command, verbosity),
+        Some(Command::Completions(args)) => completions(args),
+        Some(Command::Man) => man(),
+        #[cfg(all(feature = "server", unix))]
+        Some(Command::Serve(args)) => serve(args),
         None => {}
     }
 }
 
-fn print_from_format<T: Serialize + Display>(matches: &ArgMatches, obj: T) {
-    let format = match matches.value_of("format") {
-        Some(f) => str::parse::<OutputFormat>(f).unwrap_or_default(),
-        None => OutputFormat::default() 
+fn print_from_format<T: Serialize + Display>(format: OutputFormat, verbosity: Verbosity, obj: T) {
+    match format {
+        OutputFormat::Json => print_serialized(serde_json::to_string(&obj)),
+        OutputFormat::JsonPretty => print_serialized(serde_json::to_string_pretty(&obj)),
+        OutputFormat::Plain => {
+            // Quiet output is meant to be consumed by other tools, so no colors
+            if verbosity.is_quiet() {
+                console::set_colors_enabled(false);
+            }
+            println!("{}", obj)
+        }
     };
+}
 
-    match format {
-        OutputFormat::Json => {
-            let serialized = serde_json::to_string(&obj).unwrap();
-            println!("{}", serialized)
+/// Warns when the DAT is meant to be used in another mode, the command goes on
+fn warn_mode_mismatch(mismatch: anyhow::Result<Option<ModeMismatch>>) {
+    // If the mode of the DAT can't be read the command tells why
+    if let Ok(Some(mismatch)) = mismatch {
+        eprintln!("{} {}.", Style::new().yellow().bold().apply_to("WARNING"), mismatch);
+    }
+}
+
+/// Stops the check when the DAT is meant to be used in another mode, unless it's forced
+fn check_mode_mismatch(mismatch: anyhow::Result<Option<ModeMismatch>>, force: bool) -> bool {
+    match mismatch {
+        Ok(Some(mismatch)) if !force => {
+            println!("{} {}.\nUse `--force` to check anyway.", Style::new().red().apply_to("ERROR"), mismatch);
+            false
         }
-        OutputFormat::JsonPretty => {
-            let serialized = serde_json::to_string_pretty(&obj).unwrap();
-            println!("{}", serialized)
+        mismatch => {
+            warn_mode_mismatch(mismatch);
+            true
         }
-        OutputFormat::Plain => println!("{}", obj)
-    };
+    }
+}
+
+fn print_serialized(serialized: serde_json::Result<String>) {
+    match serialized {
+        Ok(serialized) => println!("{}", serialized),
+        Err(e) => {
+            println!("{} serializing the output, try the plain format.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
 }
 
-fn ui(_matches: &ArgMatches) {
+fn ui() {
     match ui_cursive::render() {
         Ok(_) => {}
         Err(e) => {
@@ -189,22 +106,140 @@ fn ui(_matches: &ArgMatches) {
     }
 }
 
-fn check(matches: &ArgMatches) {
-    let db = matches.value_of("db").unwrap();
-    let files = matches.values_of("source").unwrap().collect::<Vec<_>>();
-    let set_mode = match matches.value_of("set-mode") {
-        Some(mode) => str::parse::<RomsetMode>(mode).unwrap_or_default(),
-        None => RomsetMode::default() 
+fn check(args: CheckArgs, verbosity: Verbosity) {
+    // Without a DAT there's always a database, the summary needs one as it can't be used with a DAT
+    let db = args.db.as_deref().unwrap_or_default();
+    let reader = match (&args.dat, &args.db) {
+        (Some(dat), _) => Romst::get_data_reader_from_dat(dat),
+        (None, Some(db)) => Romst::get_data_reader(db),
+        (None, None) => {
+            println!("{} a database or a DAT file is needed to check the files.",
+                Style::new().red().apply_to("ERROR"));
+            return;
+        }
+    };
+    let reader = match reader {
+        Ok(reader) => reader,
+        Err(e) => {
+            println!("{} reading the sets to check.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+            return;
+        }
+    };
+    let files = args.source.iter().map(|file| file.as_str()).collect::<Vec<_>>();
+    let set_mode = match args.set_mode.as_str() {
+        "auto" => {
+            match Romst::detect_romset_mode(&reader, files.clone(), DEFAULT_MODE_DETECTION_SAMPLE) {
+                Ok(detection) => {
+                    if !verbosity.is_quiet() {
+                        // To stderr, so it doesn't mix with the report
+                        eprint!("{}", detection);
+                    }
+                    detection.mode
+                }
+                Err(e) => {
+                    println!("{} detecting the romset mode.\n{}",
+                        Style::new().red().apply_to("ERROR"), e);
+                    return;
+                }
+            }
+        }
+        mode => str::parse::<RomsetMode>(mode).unwrap_or_default(),
+    };
+    if !check_mode_mismatch(ModeMismatch::check(&reader, set_mode), args.force) {
+        return;
+    }
+
+    let game_filter = match args.filter.get_game_filter() {
+        Ok(game_filter) => game_filter,
+        Err(e) => {
+            println!("{} reading the games filter.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+            return;
+        }
     };
 
-    let reporter = Some(ReportReporterSysOut::new());
-    match Romst::get_report(db, files, set_mode, reporter) {
+    let set_aliases = match args.aliases.as_deref().map(SetAliases::from_file).transpose() {
+        Ok(set_aliases) => set_aliases.unwrap_or_default(),
+        Err(e) => {
+            println!("{} reading the set aliases.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+            return;
+        }
+    };
+
+    let cancellation = CancellationToken::default();
+    if let Err(e) = cancellation.cancel_on_ctrl_c() {
+        eprintln!("{} Ctrl+C can't interrupt the check, it exits right away.\n{}", Style::new().yellow().apply_to("WARNING"), e);
+    }
+
+    // The statuses of a check of only some sets would replace the ones of all the sets of the directory
+    let store_statuses = args.dat.is_none() && !args.summary_only && game_filter.is_empty();
+    let sample = match (args.sample, args.sample_seed) {
+        (Some(sample), Some(seed)) => Some(sample.with_seed(seed)),
+        (sample, _) => sample,
+    };
+    let options = ScanOptions::new(set_mode)
+        .bios(args.bios)
+        .game_filter(game_filter)
+        .entry_paths(args.entry_paths)
+        .deep_verify(args.deep_verify)
+        .quick_check(args.quick)
+        .io_limits(IoLimits::new(args.throttle, args.nice_io))
+        .loose_files(args.loose_files)
+        .set_aliases(set_aliases)
+        .name_policy(args.names)
+        .io_retry(IoRetry::new(args.retries, args.retry_backoff))
+        .sample(sample);
+    let reporter = Some(ReportReporterSysOut::with_verbosity(verbosity));
+    let result = match (&args.retry_errors, files.as_slice()) {
+        (Some(report_file), [directory]) if Path::new(directory).is_dir() => Romst::load_report(report_file)
+            .and_then(|report| Romst::retry_errors(reader, report, directory, options, cancellation, reporter)),
+        (Some(_), _) => {
+            println!("{} the source has to be the directory checked to retry the errors of the report.",
+                Style::new().red().apply_to("ERROR"));
+            return;
+        }
+        (None, _) => Romst::get_report(reader, files, options, cancellation, reporter),
+    };
+    match result {
         Ok(report) => {
-            if let Some(dest_file) = matches.value_of("report") {
-                match Romst::save_report(dest_file, report) {
+            if report.is_interrupted() {
+                // To stderr, so it doesn't mix with the report
+                eprintln!("{} the check was interrupted, {} files were not scanned and the report is partial",
+                    Style::new().yellow().apply_to("WARNING"), report.unscanned.len());
+            }
+            let retriable = report.get_retriable_files().len();
+            if retriable > 0 {
+                eprintln!("{} {} files couldn't be read because of transient errors, check them again with `--retry-errors`",
+                    Style::new().yellow().apply_to("WARNING"), retriable);
+            }
+            if let Some(file_checks) = report.get_file_checks().filter(|file_checks| file_checks.is_weak()) {
+                eprintln!("{} the roms were matched only by {}, a different rom with the same crc and size is taken as the right one",
+                    Style::new().yellow().apply_to("WARNING"), file_checks);
+            }
+            if store_statuses {
+                if let Err(e) = Romst::store_set_statuses(db, &report) {
+                    eprintln!("{} the statuses of the sets can't be stored in the database.\n{}",
+                        Style::new().yellow().apply_to("WARNING"), e);
+                }
+            }
+            if args.summary_only {
+                match Romst::summarize_report(db, report) {
+                    Ok(summary) => print_from_format(args.format, verbosity, summary),
+                    Err(e) => {
+                        println!("{} summarizing the report.\n{}",
+                            Style::new().red().apply_to("ERROR"), e);
+                    }
+                }
+            } else if let Some(dest_file) = &args.report {
+                let mut reporter = ReportFileReporterSysOut::with_verbosity(verbosity);
+                match Romst::save_report_with_progress(dest_file, report, Some(&mut reporter)) {
                     Ok(_) => {
-                        println!("{} report saved",
-                            Style::new().green().apply_to("SUCCESS"));
+                        if !verbosity.is_quiet() {
+                            println!("{} report saved",
+                                Style::new().green().apply_to("SUCCESS"));
+                        }
                     }
                     Err(e) => {
                         println!("{} saving a report.\n{}",
@@ -212,7 +247,7 @@ fn check(matches: &ArgMatches) {
                     }
                 }
             } else {
-                print_from_format(matches, report);
+                print_from_format(args.format, verbosity, report);
             }
         }
         Err(e) => {
@@ -222,22 +257,430 @@ fn check(matches: &ArgMatches) {
     }
 }
 
-fn import(matches: &ArgMatches) {
-    let file = matches.value_of("source").unwrap();
-    let output = match matches.value_of("dest") {
+fn check_set(args: CheckSetArgs, verbosity: Verbosity) {
+    let file = args.source.as_str();
+    if !check_mode_mismatch(Romst::get_mode_mismatch(args.db.as_str(), args.set_mode), args.force) {
+        return;
+    }
+
+    match Romst::check_set(args.db.as_str(), args.game.as_str(), file, ScanOptions::new(args.set_mode).entry_paths(args.entry_paths)) {
+        Ok(set_report) => print_from_format(args.format, verbosity, set_report),
+        Err(e) => {
+            println!("{} checking the file {}.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                Style::new().green().apply_to(file),
+                e);
+        }
+    }
+}
+
+fn clean(args: CleanArgs, verbosity: Verbosity) {
+    let target = args.target.as_str();
+
+    let result = if args.undo {
+        Romst::undo_clean(target)
+    } else {
+        let report = args.report.as_deref().unwrap();
+        let source = args.source.as_deref();
+        Romst::clean(report, source, target, args.dry_run)
+    };
+
+    match result {
+        Ok(plan) => {
+            print_from_format(args.format, verbosity, plan);
+        }
+        Err(e) => {
+            println!("{} cleaning the files.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+/// The priority list of the sets to fix or rebuild, no list keeps the order of the plan
+fn get_set_priority(priority: Option<&str>, db: Option<&str>) -> Option<SetPriority> {
+    match priority.map(|priority| Romst::get_set_priority(priority, db)).transpose() {
+        Ok(priority) => Some(priority.unwrap_or_default()),
+        Err(e) => {
+            println!("{} reading the priority list.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+            None
+        }
+    }
+}
+
+/// Stops the run after the set being done when Ctrl+C is pressed
+fn get_cancellation(dry_run: bool) -> CancellationToken {
+    let cancellation = CancellationToken::default();
+    if !dry_run {
+        if let Err(e) = cancellation.cancel_on_ctrl_c() {
+            eprintln!("{} Ctrl+C can't interrupt the run, it exits right away.\n{}", Style::new().yellow().apply_to("WARNING"), e);
+        }
+    }
+    cancellation
+}
+
+fn fix(args: FixArgs, verbosity: Verbosity) {
+    let priority = match get_set_priority(args.priority.as_deref(), args.db.as_deref()) {
+        Some(priority) => priority,
+        None => return,
+    };
+    let cancellation = get_cancellation(args.dry_run);
+    match Romst::fix(args.report.as_str(), &priority, args.dry_run, args.skip_space_check, &cancellation) {
+        Ok(plan) => {
+            print_from_format(args.format, verbosity, plan);
+        }
+        Err(e) => {
+            println!("{} fixing the sets.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn bundle(args: BundleArgs, verbosity: Verbosity) {
+    warn_mode_mismatch(Romst::get_mode_mismatch(args.db.as_str(), args.set_mode));
+    let options = BundleOptions {
+        rom_mode: args.set_mode,
+        source_directory: args.source,
+        samples_directory: args.samples,
+        target_directory: args.target,
+        zip: args.zip,
+    };
+    match Romst::bundle(args.db.as_str(), args.game.as_str(), options, args.dry_run) {
+        Ok(plan) => {
+            print_from_format(args.format, verbosity, plan);
+        }
+        Err(e) => {
+            println!("{} bundling the set.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn restore(args: RestoreArgs, verbosity: Verbosity) {
+    match Romst::restore_backup(args.manifest.as_str()) {
+        Ok(manifest) => {
+            print_from_format(args.format, verbosity, manifest);
+        }
+        Err(e) => {
+            println!("{} restoring the backup.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn rebuild(args: RebuildArgs, verbosity: Verbosity) {
+    let throughput = args.throughput.unwrap_or(DEFAULT_REBUILD_THROUGHPUT);
+    let priority = match get_set_priority(args.priority.as_deref(), args.db.as_deref()) {
+        Some(priority) => priority,
+        None => return,
+    };
+    let cancellation = get_cancellation(args.dry_run);
+    match Romst::rebuild(args.report.as_str(), args.target.as_str(), throughput, args.packing, args.names, &priority, args.dry_run, args.skip_space_check, &cancellation) {
+        Ok(plan) => {
+            print_from_format(args.format, verbosity, plan);
+        }
+        Err(e) => {
+            println!("{} rebuilding the sets.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn migrate(args: MigrateArgs, verbosity: Verbosity) {
+    let reader = match Romst::get_data_reader(args.to.as_str()) {
+        Ok(reader) => reader,
+        Err(e) => {
+            println!("{} reading the new database.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+            return;
+        }
+    };
+    warn_mode_mismatch(ModeMismatch::check(&reader, args.set_mode));
+    let reporter = Some(ReportReporterSysOut::with_verbosity(verbosity));
+    let report = match Romst::get_report(reader, vec![args.source.as_str()], ScanOptions::new(args.set_mode), CancellationToken::default(), reporter) {
+        Ok(report) => report,
+        Err(e) => {
+            println!("{} checking the collection against the new database.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+            return;
+        }
+    };
+    let cancellation = get_cancellation(args.dry_run);
+    match Romst::migrate(args.from.as_str(), &report, args.target.as_str(), args.packing, args.dry_run, &cancellation) {
+        Ok(plan) => {
+            print_from_format(args.format, verbosity, plan);
+        }
+        Err(e) => {
+            println!("{} migrating the sets.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn report(command: ReportCommand, verbosity: Verbosity) {
+    match command {
+        ReportCommand::Merge(args) => report_merge(args, verbosity),
+        ReportCommand::Missing(args) => report_missing(args, verbosity),
+    }
+}
+
+fn report_merge(args: ReportMergeArgs, verbosity: Verbosity) {
+    let mut reporter = ReportFileReporterSysOut::with_verbosity(verbosity);
+    match Romst::merge_reports(&args.reports, args.output, Some(&mut reporter)) {
+        Ok(_) => {
+            if !verbosity.is_quiet() {
+                println!("{} {} reports merged",
+                    Style::new().green().apply_to("SUCCESS"), args.reports.len());
+            }
+        }
+        Err(e) => {
+            println!("{} merging the reports.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn report_missing(args: ReportMissingArgs, verbosity: Verbosity) {
+    let scope = match args.get_scope() {
+        Ok(scope) => scope,
+        Err(e) => {
+            println!("{} reading the scope of the sets.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+            return;
+        }
+    };
+
+    match Romst::get_missing_sets(args.db.as_str(), args.report.as_str(), scope) {
+        Ok(missing_sets) => print_from_format(args.format, verbosity, missing_sets),
+        Err(e) => {
+            println!("{} listing the missing sets.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn miss(args: MissArgs, verbosity: Verbosity) {
+    let miss_list = match Romst::get_miss_list(args.report.as_str(), args.list_format) {
+        Ok(miss_list) => miss_list,
+        Err(e) => {
+            println!("{} creating the miss list.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+            return;
+        }
+    };
+
+    match &args.output {
+        Some(output) => {
+            match fs::write(output, miss_list) {
+                Ok(_) => {
+                    if !verbosity.is_quiet() {
+                        println!("{} miss list saved",
+                            Style::new().green().apply_to("SUCCESS"));
+                    }
+                }
+                Err(e) => {
+                    println!("{} saving the miss list.\n{}",
+                        Style::new().red().apply_to("ERROR"), e);
+                }
+            }
+        }
+        None => print!("{}", miss_list),
+    }
+}
+
+fn export(command: ExportCommand, verbosity: Verbosity) {
+    match command {
+        ExportCommand::Dat(args) => export_dat(args, verbosity),
+    }
+}
+
+fn export_dat(args: ExportDatArgs, verbosity: Verbosity) {
+    match Romst::export_dat(args.db.as_str(), args.output.as_str(), args.split_by) {
+        Ok(report) => {
+            print_from_format(args.format, verbosity, report);
+        }
+        Err(e) => {
+            println!("{} exporting the DAT.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn analyze(command: AnalyzeCommand, verbosity: Verbosity) {
+    match command {
+        AnalyzeCommand::Fixable(args) => analyze_fixable(args, verbosity),
+    }
+}
+
+fn analyze_fixable(args: AnalyzeFixableArgs, verbosity: Verbosity) {
+    match Romst::analyze_fixable(args.report.as_str()) {
+        Ok(analysis) => {
+            print_from_format(args.format, verbosity, analysis);
+        }
+        Err(e) => {
+            println!("{} analyzing the fixable sets.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn import(args: ImportArgs, verbosity: Verbosity) {
+    #[cfg(feature = "net")]
+    if let Some(url) = &args.url {
+        import_url(&args, verbosity, url);
+        return;
+    }
+
+    if args.validate_only {
+        validate_dats(&args, verbosity);
+        return;
+    }
+
+    if let [file] = args.source.as_slice() {
+        if !Path::new(file).is_dir() {
+            let output = match &args.dest {
+                Some(o) => {
+                    o.to_string()
+                }
+                None => {
+                    let path = Path::new(&file).with_extension(DB_EXTENSION);
+                    path.to_str().unwrap().to_string()
+                }
+            };
+            import_file(&args, verbosity, file, &output);
+            return;
+        }
+    }
+
+    let output = match (&args.dest, args.source.as_slice()) {
+        (Some(o), _) => o.to_string(),
+        (None, [dir]) => Path::new(dir).with_extension(DB_EXTENSION).to_string_lossy().to_string(),
+        (None, _) => {
+            println!("{} set the destination database with `--db` to import several DATs.",
+                Style::new().red().apply_to("ERROR"));
+            return;
+        }
+    };
+    import_files(&args, verbosity, &output);
+}
+
+fn validate_dats(args: &ImportArgs, verbosity: Verbosity) {
+    if !verbosity.is_quiet() {
+        for source in &args.source {
+            println!("Validating: {}", Style::new().bold().apply_to(source));
+        }
+    }
+
+    let reporter = DatImporterReporterSysOut::with_verbosity(verbosity);
+    match Romst::validate_dats(&args.source, Some(reporter)) {
+        Ok(validation) => {
+            print!("{}", validation);
+        }
+        Err(e) => {
+            println!("{} validating the DATs.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+        }
+    }
+}
+
+fn get_import_options(args: &ImportArgs) -> Option<ImportOptions> {
+    let game_filter = match args.filter.get_game_filter() {
+        Ok(game_filter) => game_filter,
+        Err(e) => {
+            println!("{} reading the games filter.\n{}",
+                Style::new().red().apply_to("ERROR"), e);
+            return None;
+        }
+    };
+    Some(ImportOptions {
+        overwrite: args.overwrite,
+        build_index: args.index,
+        threads: args.threads,
+        extended_metadata: args.extended_metadata,
+        max_errors: args.max_errors,
+        game_filter,
+        vacuum: args.vacuum,
+        fix_references: args.fix_references,
+    })
+}
+
+fn import_files(args: &ImportArgs, verbosity: Verbosity, output: &str) {
+    let options = match get_import_options(args) {
+        Some(options) => options,
+        None => return,
+    };
+
+    if !verbosity.is_quiet() {
+        for source in &args.source {
+            println!("Loading: {}", Style::new().bold().apply_to(source));
+        }
+        println!("Output: {}", Style::new().bold().apply_to(output));
+    }
+
+    let reporter = DatImporterReporterSysOut::with_verbosity(verbosity);
+    match Romst::import_dats(&args.source, output.to_string(), options, Some(reporter)) {
+        Ok(import_report) => {
+            if !verbosity.is_quiet() || import_report.has_issues() {
+                print!("{}", import_report);
+            }
+        }
+        Err(e) => {
+            println!("{} importing the DATs into {}.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            Style::new().green().apply_to(output),
+            e);
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+fn import_url(args: &ImportArgs, verbosity: Verbosity, url: &str) {
+    let output = match &args.dest {
         Some(o) => {
             o.to_string()
         }
         None => {
-            let path = Path::new(&file).with_extension(DB_EXTENSION);
+            let dat_name = romst::data::importer::remote::get_dat_name(url);
+            let path = Path::new(&dat_name).with_extension(DB_EXTENSION);
             path.to_str().unwrap().to_string()
         }
     };
-    let overwrite = matches.is_present("overwrite");
 
-    let reporter = DatImporterReporterSysOut::new();
-    match Romst::import_dat(file, &output, overwrite, Some(reporter)) {
-        Ok(_) => {}
+    if !verbosity.is_quiet() {
+        println!("Downloading: {}", Style::new().bold().apply_to(url));
+    }
+    // The downloaded file is removed when `remote_dat` is dropped
+    let remote_dat = match Romst::fetch_dat(url, args.sha1.as_deref()) {
+        Ok(remote_dat) => remote_dat,
+        Err(e) => {
+            println!("{} downloading the file {}.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                Style::new().green().apply_to(url),
+                e);
+            return;
+        }
+    };
+    import_file(args, verbosity, &remote_dat.get_path().to_string_lossy(), &output);
+}
+
+fn import_file(args: &ImportArgs, verbosity: Verbosity, file: &str, output: &str) {
+    let options = match get_import_options(args) {
+        Some(options) => options,
+        None => return,
+    };
+
+    if !verbosity.is_quiet() {
+        println!("Loading file: {}", Style::new().bold().apply_to(file));
+        println!("Output: {}", Style::new().bold().apply_to(output));
+    }
+
+    let reporter = DatImporterReporterSysOut::with_verbosity(verbosity);
+    match Romst::import_dat(file, output, options, Some(reporter)) {
+        Ok(import_report) => {
+            if !verbosity.is_quiet() || import_report.has_issues() {
+                print!("{}", import_report);
+            }
+        }
         Err(e) => { 
             println!("{} parsing the file {}.\n{}",
             Style::new().red().apply_to("ERROR"),
@@ -247,71 +690,402 @@ fn import(matches: &ArgMatches) {
     }
 }
 
-fn info(matches: &ArgMatches) {
-    match matches.subcommand() {
-        Some(("data", data_matches)) => info_data(data_matches),
-        Some(("set", set_matches)) => info_set(set_matches),
-        Some(("romusage", rom_usage_matches)) => rom_usage(rom_usage_matches),
-        Some(_) | None => {}
+fn db(command: DbCommand, verbosity: Verbosity) {
+    match command {
+        DbCommand::Index(args) => db_index(args, verbosity),
+        DbCommand::SetMode(args) => db_set_mode(args, verbosity),
+        DbCommand::Vacuum(args) => db_vacuum(args, verbosity),
+        DbCommand::IgnoreRoms(args) => db_ignore_roms(args, verbosity),
+        DbCommand::Verify(args) => db_verify(args, verbosity),
+        DbCommand::Export(args) => db_export(args, verbosity),
+        DbCommand::Import(args) => db_import(args, verbosity),
     }
 }
 
-fn info_data(matches: &ArgMatches) {
-    let db = matches.value_of("db").unwrap();
-    match Romst::get_db_info(db) {
+fn db_set_mode(args: DbSetModeArgs, verbosity: Verbosity) {
+    let db = args.db.as_str();
+    let result = match &args.game {
+        Some(game) => {
+            if args.mode.is_none() && !args.clear {
+                println!("{} set either a mode or clear the forced mode of the set.",
+                    Style::new().red().apply_to("ERROR"));
+                return;
+            }
+            Romst::set_mode_override(db, game.as_str(), args.mode)
+        }
+        None => Romst::get_mode_overrides(db),
+    };
+
+    match result {
+        Ok(overrides) => {
+            print_from_format(args.format, verbosity, overrides);
+        }
+        Err(e) => {
+            println!("{} forcing the romset mode.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                e);
+        }
+    }
+}
+
+fn db_index(args: DbIndexArgs, verbosity: Verbosity) {
+    match Romst::build_index(args.db.as_str()) {
         Ok(info) => {
-            print_from_format(matches, info);
+            print_from_format(args.format, verbosity, info);
         }
         Err(e) => {
-            println!("{} getting roms info.\n{}",
+            println!("{} building the rom index.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                e);
+        }
+    }
+}
+
+fn db_vacuum(args: DbVacuumArgs, verbosity: Verbosity) {
+    match Romst::vacuum_db(args.db.as_str()) {
+        Ok(report) => {
+            print_from_format(args.format, verbosity, report);
+        }
+        Err(e) => {
+            println!("{} compacting the database.\n{}",
                 Style::new().red().apply_to("ERROR"),
                 e);
         }
     }
 }
 
-fn info_set(matches: &ArgMatches) {
-    let db = matches.value_of("db").unwrap();
-    let games = matches.values_of("games").unwrap().collect::<Vec<_>>();
-    let set_mode = match matches.value_of("set-mode") {
-        Some(mode) => str::parse::<RomsetMode>(mode).unwrap_or_default(),
-        None => RomsetMode::default() 
+fn db_verify(args: DbVerifyArgs, verbosity: Verbosity) {
+    match Romst::verify_db(args.db.as_str(), args.fix) {
+        Ok(report) => {
+            print_from_format(args.format, verbosity, report);
+        }
+        Err(e) => {
+            println!("{} verifying the database.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                e);
+        }
+    }
+}
+
+fn db_export(args: DbExportArgs, verbosity: Verbosity) {
+    let output = match &args.output {
+        Some(o) => o.to_string(),
+        None => Path::new(&args.db).with_extension(BUNDLE_EXTENSION).to_string_lossy().to_string(),
     };
+    match Romst::export_db(args.db.as_str(), output.as_str()) {
+        Ok(report) => {
+            print_from_format(args.format, verbosity, report);
+        }
+        Err(e) => {
+            println!("{} exporting the database.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                e);
+        }
+    }
+}
 
-    match Romst::get_sets_info(db, games, set_mode) {
-        Ok(romsets) => {
-            print_from_format(matches, romsets);
+fn db_import(args: DbImportArgs, verbosity: Verbosity) {
+    let output = match &args.dest {
+        Some(o) => o.to_string(),
+        None => Path::new(&args.source).with_extension(DB_EXTENSION).to_string_lossy().to_string(),
+    };
+    match Romst::import_db(args.source.as_str(), output.as_str(), args.overwrite) {
+        Ok(report) => {
+            print_from_format(args.format, verbosity, report);
         }
-        Err(e) => { println!("{} getting game info.\n{}",
+        Err(e) => {
+            println!("{} importing the database bundle.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                e);
+        }
+    }
+}
+
+fn db_ignore_roms(args: DbIgnoreRomsArgs, verbosity: Verbosity) {
+    let db = args.db.as_str();
+    let mut ignored_roms = IgnoredRoms::default();
+    let parsed = args.sha1.iter().try_for_each(|sha1| ignored_roms.add_sha1(sha1))
+        .and_then(|_| args.rom.iter().try_for_each(|rom| ignored_roms.add_rom(rom)));
+    if let Err(e) = parsed {
+        println!("{} reading the roms to ignore.\n{}",
             Style::new().red().apply_to("ERROR"),
-            e); }
+            e);
+        return;
+    }
+
+    let result = if ignored_roms.is_empty() {
+        Romst::get_ignored_roms(db)
+    } else if args.remove {
+        Romst::remove_ignored_roms(db, &ignored_roms)
+    } else {
+        Romst::add_ignored_roms(db, &ignored_roms)
+    };
+
+    match result {
+        Ok(ignored_roms) => {
+            print_from_format(args.format, verbosity, ignored_roms);
+        }
+        Err(e) => {
+            println!("{} ignoring the roms.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                e);
+        }
+    }
+}
+
+fn info(command: InfoCommand, verbosity: Verbosity) {
+    match command {
+        InfoCommand::Data(args) => info_data(args, verbosity),
+        InfoCommand::Set(args) => info_set(args, verbosity),
+        InfoCommand::RomUsage(args) => rom_usage(args, verbosity),
+        InfoCommand::SharedRoms(args) => shared_roms(args, verbosity),
+        InfoCommand::RomId(args) => rom_id(args, verbosity),
+        InfoCommand::Quality(args) => quality(args, verbosity),
+        InfoCommand::Browse(args) => browse(args, verbosity),
+        InfoCommand::DedupeStats(args) => dedupe_stats(args, verbosity),
+        InfoCommand::Find(args) => find_roms(args, verbosity),
+        InfoCommand::Sizes(args) => sizes(args, verbosity),
     }
 }
 
-fn rom_usage(matches: &ArgMatches) {
-    let db = matches.value_of("db").unwrap();
-    let game = matches.value_of("game").unwrap();
-    let rom_name = matches.value_of("rom");
-    let set_mode = match matches.value_of("set-mode") {
-        Some(mode) => str::parse::<RomsetMode>(mode).unwrap_or_default(),
-        None => RomsetMode::default() 
+fn find_roms(args: InfoFindArgs, verbosity: Verbosity) {
+    let filter = RomFilter {
+        sha1_prefix: args.sha1_prefix,
+        md5_prefix: args.md5_prefix,
+        crc_prefix: args.crc_prefix,
+        size_min: args.size_min,
+        size_max: args.size_max,
     };
+    match Romst::find_roms(args.db.as_str(), filter) {
+        Ok(report) => {
+            print_from_format(args.format, verbosity, report);
+        }
+        Err(e) => {
+            println!("{} finding the roms.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                e);
+        }
+    }
+}
 
-    let execution = match rom_name {
-        Some(rom) => {
-            Romst::get_rom_usage(db, game, rom, set_mode)
+fn info_data(args: InfoDataArgs, verbosity: Verbosity) {
+    match Romst::get_db_info(args.db.as_str()) {
+        Ok(info) => {
+            print_from_format(args.format, verbosity, info);
+        }
+        Err(e) => {
+            println!("{} getting roms info.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                e);
         }
-        None => { 
-            Romst::get_romset_shared_roms(db, game, set_mode)
+    }
+}
+
+fn info_set(args: InfoSetArgs, verbosity: Verbosity) {
+    let games = args.games.iter().map(|game| game.as_str()).collect::<Vec<_>>();
+
+    warn_mode_mismatch(Romst::get_mode_mismatch(args.db.as_str(), args.set_mode));
+    match Romst::get_sets_info(args.db.as_str(), games, args.set_mode) {
+        Ok(mut romsets) => {
+            if let Some(report_file) = &args.report {
+                match Romst::load_report(report_file.as_str()) {
+                    Ok(report) => {
+                        if report.get_rom_mode() != args.set_mode {
+                            eprintln!("{} The report was checked in {} mode, the roms of the sets may not match.", Style::new().yellow().apply_to("WARNING"), report.get_rom_mode());
+                        }
+                        romsets.add_report(&report);
+                    }
+                    Err(e) => {
+                        println!("{} loading the report.\n{}",
+                            Style::new().red().apply_to("ERROR"), e);
+                        return;
+                    }
+                }
             }
-    };
+            print_from_format(args.format, verbosity, romsets);
+        }
+        Err(e) => { println!("{} getting game info.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            e); }
+    }
+}
+
+fn rom_usage(args: RomUsageArgs, verbosity: Verbosity) {
+    warn_mode_mismatch(Romst::get_mode_mismatch(args.db.as_str(), args.set_mode));
+    match Romst::get_rom_usage(args.db.as_str(), args.game.as_str(), args.rom.as_str(), args.set_mode) {
+        Ok(result) => {
+            print_from_format(args.format, verbosity, result);
+        }
+        Err(e) => { println!("{} getting roms info.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            e); }
+    }
+}
+
+fn shared_roms(args: SharedRomsArgs, verbosity: Verbosity) {
+    warn_mode_mismatch(Romst::get_mode_mismatch(args.db.as_str(), args.set_mode));
+    match Romst::get_romset_shared_roms(args.db.as_str(), args.game.as_str(), args.set_mode) {
+        Ok(result) => {
+            print_from_format(args.format, verbosity, result);
+        }
+        Err(e) => { println!("{} getting the shared roms.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            e); }
+    }
+}
 
-    match execution {
+fn rom_id(args: RomIdArgs, verbosity: Verbosity) {
+    match Romst::get_sets_using_rom_id(args.db.as_str(), args.id) {
         Ok(result) => {
-            print_from_format(matches, result);
+            print_from_format(args.format, verbosity, result);
         }
         Err(e) => { println!("{} getting roms info.\n{}",
             Style::new().red().apply_to("ERROR"),
             e); }
     }
-}
\ No newline at end of file
+}
+
+fn quality(args: QualityArgs, verbosity: Verbosity) {
+    let mut games = args.games.to_owned();
+    if let Some(tag) = &args.tag {
+        match Romst::get_user_data(args.db.as_str(), Some(tag)) {
+            Ok(user_data) => games.extend(user_data.sets.into_keys()),
+            Err(e) => {
+                println!("{} reading the tagged sets.\n{}", Style::new().red().apply_to("ERROR"), e);
+                return;
+            }
+        }
+    }
+    let games = games.iter().map(|game| game.as_str()).collect::<Vec<_>>();
+
+    warn_mode_mismatch(Romst::get_mode_mismatch(args.db.as_str(), args.set_mode));
+    match Romst::get_dump_quality(args.db.as_str(), games, args.set_mode) {
+        Ok(result) => {
+            print_from_format(args.format, verbosity, result);
+        }
+        Err(e) => { println!("{} getting the dump quality.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            e); }
+    }
+}
+
+fn browse(args: BrowseArgs, verbosity: Verbosity) {
+    match Romst::browse_sets(args.db.as_str(), args.by) {
+        Ok(result) => {
+            print_from_format(args.format, verbosity, result);
+        }
+        Err(e) => { println!("{} browsing the sets.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            e); }
+    }
+}
+
+fn dedupe_stats(args: InfoDataArgs, verbosity: Verbosity) {
+    match Romst::get_dedupe_stats(args.db.as_str()) {
+        Ok(result) => {
+            print_from_format(args.format, verbosity, result);
+        }
+        Err(e) => { println!("{} getting the dedupe stats.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            e); }
+    }
+}
+
+fn sizes(args: InfoSizesArgs, verbosity: Verbosity) {
+    match Romst::get_sizes(args.db.as_str(), args.set_mode, args.order, args.limit) {
+        Ok(result) => {
+            print_from_format(args.format, verbosity, result);
+        }
+        Err(e) => { println!("{} getting the sizes.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            e); }
+    }
+}
+
+fn tag(command: TagCommand, verbosity: Verbosity) {
+    let (result, format) = match command {
+        TagCommand::Add(args) => (Romst::add_set_tags(args.db.as_str(), args.game.as_str(), &args.tag), args.format),
+        TagCommand::Remove(args) => (Romst::remove_set_tags(args.db.as_str(), args.game.as_str(), &args.tag), args.format),
+        TagCommand::Note(args) => (Romst::set_set_note(args.db.as_str(), args.game.as_str(), args.text.as_deref()), args.format),
+        TagCommand::List(args) => (Romst::get_user_data(args.db.as_str(), args.tag.as_deref()), args.format),
+    };
+
+    match result {
+        Ok(user_data) => {
+            print_from_format(format, verbosity, user_data);
+        }
+        Err(e) => {
+            println!("{} tagging the set.\n{}",
+                Style::new().red().apply_to("ERROR"),
+                e);
+        }
+    }
+}
+
+fn pool(command: PoolCommand, verbosity: Verbosity) {
+    match command {
+        PoolCommand::Add(args) => pool_add(args, verbosity),
+        PoolCommand::Build(args) => pool_build(args, verbosity),
+    }
+}
+
+fn pool_add(args: PoolAddArgs, verbosity: Verbosity) {
+    let files = args.source.iter().map(|file| file.as_str()).collect::<Vec<_>>();
+
+    match Romst::pool_add(args.pool.as_str(), files, args.compress) {
+        Ok(report) => {
+            print_from_format(args.format, verbosity, report);
+        }
+        Err(e) => { println!("{} adding the files to the pool.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            e); }
+    }
+}
+
+fn pool_build(args: PoolBuildArgs, verbosity: Verbosity) {
+    let games = args.games.iter().map(|game| game.as_str()).collect::<Vec<_>>();
+
+    warn_mode_mismatch(Romst::get_mode_mismatch(args.db.as_str(), args.set_mode));
+    match Romst::pool_build(args.db.as_str(), args.pool.as_str(), games, args.set_mode, args.target.as_str()) {
+        Ok(report) => {
+            print_from_format(args.format, verbosity, report);
+        }
+        Err(e) => { println!("{} building the sets from the pool.\n{}",
+            Style::new().red().apply_to("ERROR"),
+            e); }
+    }
+}
+
+fn completions(args: CompletionsArgs) {
+    let mut app = Opts::into_app();
+    if let Err(e) = cli::completions::write_completions(&mut io::stdout(), &mut app, args.shell) {
+        println!("{} writing the completions.\n{}",
+            Style::new().red().apply_to("ERROR"), e);
+    }
+}
+
+fn man() {
+    let mut app = Opts::into_app();
+    if let Err(e) = cli::man::write_man_page(&mut io::stdout(), &mut app, env!("CARGO_PKG_VERSION")) {
+        println!("{} writing the man page.\n{}",
+            Style::new().red().apply_to("ERROR"), e);
+    }
+}
+
+#[cfg(all(feature = "server", unix))]
+fn serve(args: ServeArgs) {
+    let server = match romst::server::Server::bind(&args.socket) {
+        Ok(server) => server,
+        Err(e) => {
+            println!("{} listening on {}.\n{}",
+                Style::new().red().apply_to("ERROR"), args.socket, e);
+            return;
+        }
+    };
+
+    println!("Listening on {}", Style::new().green().apply_to(&args.socket));
+    if let Err(e) = server.serve() {
+        println!("{} serving the requests.\n{}",
+            Style::new().red().apply_to("ERROR"), e);
+    }
+}
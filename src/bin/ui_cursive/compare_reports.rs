@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+
+use cursive::{Cursive, align::HAlign, theme::{BaseColor, Color, Effect}, traits::{Nameable, Resizable, Scrollable}, utils::markup::StyledString, views::{Dialog, DummyView, EditView, LinearLayout, SelectView, TextView}};
+use romst::{data::reporter::{diff::{ReportDiff, SetDiff}, scan_report::SetStatus}, ui_core::{AppMessage, AppState}};
+
+use super::update_state;
+
+/// Asks for the reports to compare, the newer one can be left empty to compare with the loaded report
+pub fn compare_reports_dialog(s: &mut Cursive, state: Arc<Mutex<AppState>>) {
+    if s.find_name::<EditView>("before_report").is_some() || is_comparison_shown(s) {
+        return;
+    }
+
+    let content = LinearLayout::vertical()
+    .child(TextView::new("Older report"))
+    .child(EditView::new().with_name("before_report").fixed_width(50))
+    .child(DummyView)
+    .child(TextView::new("Newer report, empty for the loaded one"))
+    .child(EditView::new().with_name("after_report").fixed_width(50));
+
+    let compare_dialog = Dialog::around(content)
+    .title("Compare Reports")
+    .button("Compare", move |s| {
+        let before_file = s.call_on_name("before_report", |view: &mut EditView| view.get_content()).unwrap_or_default();
+        let after_file = s.call_on_name("after_report", |view: &mut EditView| view.get_content()).unwrap_or_default();
+        if before_file.trim().is_empty() {
+            return;
+        }
+        let after_file = Some(after_file.trim().to_string()).filter(|after_file| !after_file.is_empty());
+        s.pop_layer();
+        update_state(s, state.clone(), AppMessage::CompareReports(before_file.trim().to_string(), after_file), |s, state, result| {
+            match (result, state.get_comparison()) {
+                (Ok(_), Some(comparison)) => show_comparison(s, comparison),
+                (Ok(_), None) => {},
+                (Err(e), _) => {
+                    s.add_layer(Dialog::around(TextView::new(format!("Error comparing the reports\n\n{}", e)).h_align(HAlign::Center))
+                        .button("Close", |s| { s.pop_layer(); }));
+                }
+            }
+        });
+    }).button("Close", |s| {
+        s.pop_layer();
+    });
+    s.add_layer(compare_dialog);
+}
+
+/// Moves the selection to the next or the previous regressed set, skipping the sets that got better
+pub fn go_to_regression(s: &mut Cursive, state: Arc<Mutex<AppState>>, forward: bool) {
+    let state = state.lock().unwrap();
+    let comparison = match state.get_comparison() {
+        Some(comparison) => comparison,
+        None => return,
+    };
+    s.call_on_name("comparison_list", |view: &mut SelectView<usize>| {
+        let selected = view.selected_id();
+        let position = if forward { comparison.next_regression(selected) } else { comparison.previous_regression(selected) };
+        if let Some(position) = position {
+            view.set_selection(position);
+        }
+    });
+}
+
+fn is_comparison_shown(s: &mut Cursive) -> bool {
+    s.find_name::<SelectView<usize>>("comparison_list").is_some()
+}
+
+fn show_comparison(s: &mut Cursive, comparison: &ReportDiff) {
+    let mut header = StyledString::styled(format!("{} sets changed, ", comparison.sets.len()), Effect::Bold);
+    header.append(StyledString::styled(format!("{} regressed", comparison.get_regressions().len()), Color::Light(BaseColor::Red)));
+    header.append(format!("\nFrom {} to {}", comparison.before_date_time, comparison.after_date_time));
+
+    let mut sets = SelectView::<usize>::new().h_align(HAlign::Left);
+    sets.add_all(comparison.sets.iter().enumerate().map(|(position, set)| (get_set_diff_label(set), position)));
+    if let Some(position) = comparison.next_regression(None) {
+        sets = sets.selected(position);
+    }
+
+    let content = LinearLayout::vertical()
+    .child(TextView::new(header))
+    .child(DummyView)
+    .child(sets.with_name("comparison_list").scrollable());
+
+    s.add_layer(Dialog::around(content)
+        .title("Report Comparison")
+        .button("Close", |s| { s.pop_layer(); })
+        .full_screen());
+}
+
+fn get_set_diff_label(set: &SetDiff) -> StyledString {
+    let label = format!("{}: {} -> {}", set.name, get_status_label(set.before), get_status_label(set.after));
+    if set.is_regression() {
+        StyledString::styled(label, Color::Light(BaseColor::Red))
+    } else {
+        StyledString::plain(label)
+    }
+}
+
+fn get_status_label(status: Option<SetStatus>) -> String {
+    match status {
+        Some(status) => status.to_string(),
+        None => "Not found".to_string(),
+    }
+}
@@ -1,30 +1,34 @@
-use std::{fs, path::Path, thread};
+use std::sync::{Arc, Mutex};
 
 use cursive::{Cursive, View, align::HAlign, traits::{Nameable, Scrollable, Resizable}, views::{Dialog, DummyView, LinearLayout, Panel, SelectView, TextView}};
-use romst::Romst;
+use romst::ui_core::{AppMessage, AppState};
 use anyhow::Result;
 
-use super::list_sets::ListSets;
-
-const BASE_PATH: &str = "db";
+use super::{list_sets::{ListSets, start_set_details_loader}, update_state};
 
 pub struct SelectDB {
-
+    state: Arc<Mutex<AppState>>,
 }
 
 impl SelectDB {
-    pub fn new() -> Self {
-        Self { }
+    pub fn new(state: Arc<Mutex<AppState>>) -> Self {
+        Self { state }
     }
 
     pub fn load_view(&self) -> Result<impl View> {
-        let mut select_db = SelectView::new()
+        let state_select = Arc::clone(&self.state);
+        let state_submit = Arc::clone(&self.state);
+        let mut select_db = SelectView::<String>::new()
             .h_align(HAlign::Left)
             .autojump()
-            .on_select(on_select_db)
-            .on_submit(on_choose_db);
-        
-        let db_list = self.get_db_list()?;
+            .on_select(move |s, value| on_select_db(s, state_select.clone(), value))
+            .on_submit(move |s, value| on_choose_db(s, state_submit.clone(), value));
+
+        let db_list = {
+            let mut state = self.state.lock().unwrap();
+            state.update(AppMessage::RefreshDBs)?;
+            state.get_dbs().iter().map(|db| (db.name.to_owned(), db.path.to_owned())).collect::<Vec<_>>()
+        };
 
         select_db.add_all(db_list.clone());
 
@@ -48,74 +52,37 @@ impl SelectDB {
 
         Ok(dialog)
     }
-
-    fn get_db_list(&self) -> Result<Vec<(String, String)>> {
-        let db_path = Path::new(BASE_PATH);
-
-        if db_path.is_file() {
-            fs::remove_file(db_path)?;
-        };
-
-        if !db_path.exists() {
-            fs::create_dir(db_path)?;
-        };
-
-        let files = db_path.read_dir()?.into_iter().filter_map(|file| {
-            match file {
-                Ok(f) => { 
-                    let path = f.path();
-                    if path.is_file() {
-                        let file_name = f.file_name().to_str().map(|s| s.to_string() );
-                        let path_string = path.to_str().map(|s| s.to_string() );
-
-                        if let (Some(l), Some(r)) = (file_name, path_string) {
-                            Some((l, r))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                }
-                Err(_) => None
-            }
-        }).collect::<Vec<_>>();
-
-        Ok(files)
-    }
 }
 
-fn on_select_db(s: &mut Cursive, value: &String) {
-    let value = value.to_owned();
-    let cb_sink = s.cb_sink().clone();
-    thread::spawn(move || {
-        let db_info = Romst::get_db_info(value);
-        let content = match db_info {
-            Ok(info) => {
+fn on_select_db(s: &mut Cursive, state: Arc<Mutex<AppState>>, value: &str) {
+    update_state(s, state, AppMessage::HighlightDB(value.to_owned()), |s, state, result| {
+        let content = match (result, state.get_highlighted_db()) {
+            (Ok(_), Some(info)) => {
                 format!("{}", info)
             }
-            Err(e) => {
+            (Ok(_), None) => String::new(),
+            (Err(e), _) => {
                 format!("Error reading DB details.\n\n{}", e)
             }
         };
-        cb_sink.send(Box::new(move |s| {
-            s.call_on_name("db_details", |view: &mut TextView| {
-                view.set_content(content);
-            });
-        })).unwrap();
+        s.call_on_name("db_details", |view: &mut TextView| {
+            view.set_content(content);
+        });
     });
 }
 
-fn on_choose_db(s: &mut Cursive, value: &String) {
-    let mut browse_db = ListSets::new(value);
-    let view = browse_db.load_view();
+fn on_choose_db(s: &mut Cursive, state: Arc<Mutex<AppState>>, value: &str) {
+    let result = state.lock().unwrap().update(AppMessage::OpenDB(value.to_owned()));
+    let browse_db = ListSets::new(Arc::clone(&state));
+    let view = result.and_then(|_| browse_db.load_view());
     match view {
         Ok(v) => {
+            start_set_details_loader(s, state);
             s.pop_layer();
             s.add_layer(v);
         }
         Err(e) => {
-            let v = browse_db.load_error_dialog(e);
+            let v = browse_db.load_error_dialog(value, e);
             s.add_layer(v);
         }
     }
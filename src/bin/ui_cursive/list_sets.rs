@@ -1,35 +1,33 @@
 use std::{sync::{Arc, Mutex}, thread};
 
 use cursive::{Cursive, View, align::HAlign, theme::{Effect, Style}, traits::{Nameable, Scrollable, Resizable}, utils::markup::StyledString, views::{Button, Dialog, DummyView, EditView, LinearLayout, Panel, ResizedView, SelectView, TextView}};
-use romst::{RomsetMode, Romst, data::{models::{file::DataFile, set::GameSet}, reader::{DataReader, sqlite::DBReader}}};
+use romst::{data::{models::{file::DataFile, set::GameSet}, reader::sqlite::{BrowseGrouping, LastCheck, SetUserEntry}, units::ByteSize}, ui_core::{AppMessage, AppState, SetDetails, SetListItem, rom_usage::RomUsage, set_details::{SetDetailsLoader, SetDetailsRequest}}};
 
 use anyhow::Result;
 
-use super::utils::{get_style_bad_dump, get_style_no_dump, truncate_text};
+use super::{update_state, utils::{get_style_bad_dump, get_style_no_dump, truncate_text}};
 
 pub struct ListSets {
-    db_reader: Arc<Mutex<DBReader>>,
-    rom_mode: RomsetMode,
-    filter: Arc<Mutex<String>>,
+    state: Arc<Mutex<AppState>>,
 }
 
 impl ListSets {
-    pub fn new<S>(db_file: S) -> Self where S: Into<String> {
-        let db_reader = Romst::get_data_reader(db_file.into()).unwrap();
-        Self {
-            db_reader: Arc::new(Mutex::new(db_reader)),
-            rom_mode: RomsetMode::default(),
-            filter: Arc::new(Mutex::new(String::new()))
-        }
+    pub fn new(state: Arc<Mutex<AppState>>) -> Self {
+        Self { state }
     }
 
-    pub fn load_view(&mut self) -> Result<ResizedView<Dialog>> {
+    pub fn load_view(&self) -> Result<ResizedView<Dialog>> {
         let mut roms_header = StyledString::styled(" Roms |", Style::none());
         roms_header.append(get_style_no_dump(" No Dump "));
         roms_header.append(StyledString::styled("|", Style::none()));
         roms_header.append(get_style_bad_dump(" Bad Dump"));
 
-        let game_roms = SelectView::<DataFile>::new().h_align(HAlign::Left);
+        let state_rom = Arc::clone(&self.state);
+        let game_roms = SelectView::<DataFile>::new()
+        .h_align(HAlign::Left)
+        .on_submit(move |s, rom: &DataFile| {
+            on_submit_rom(s, state_rom.clone(), rom.to_owned());
+        });
         let game_details = LinearLayout::horizontal()
         .child(TextView::new("").with_name("game_details").full_width())
         .child(LinearLayout::vertical()
@@ -48,65 +46,78 @@ impl ListSets {
         .child(top_view)
         .child(center_view);
 
+        let title = {
+            let state = self.state.lock().unwrap();
+            state.get_db_handle().map(|db_handle| db_handle.get_title(state.get_rom_mode())).unwrap_or_else(|| "Select Set".to_string())
+        };
         let dialog = Dialog::around(view)
-        .title("Select Set")
+        .title(title)
         .full_screen();
 
         Ok(dialog)
     }
 
     pub fn get_sets_list(&self) -> Result<LinearLayout> {
-        let g_list = self.db_reader.lock().unwrap().get_game_list(self.rom_mode)?.iter()
-        .map(|item| {
-            (format!("{}", truncate_text(&item.0, 20)), item.0.clone())
-        }).collect::<Vec<_>>();
-
-        let mut select_game = SelectView::new()
+        let (items, filter_label, grouping_label) = {
+            let state = self.state.lock().unwrap();
+            (get_set_list_items(&state), get_filter_label(state.get_filter()), get_grouping_label(state.get_grouping()))
+        };
+        let mut select_game = SelectView::<SetListItem>::new()
             .h_align(HAlign::Left)
             .autojump();
-        select_game.add_all(g_list);
+        select_game.add_all(items);
 
-        let db = Arc::clone(&self.db_reader);
-        let db_select = Arc::clone(&db);
+        let state_select = Arc::clone(&self.state);
+        let state_submit = Arc::clone(&self.state);
         select_game = select_game
         .on_select(move |s, value| {
-            on_select_game(s, value.to_owned(), db_select.clone());
+            if let SetListItem::Set { name, .. } = value {
+                on_select_game(s, state_select.clone(), name.to_owned());
+            }
+        })
+        .on_submit(move |s, value| {
+            if let SetListItem::Group { name, .. } = value {
+                toggle_group(s, state_submit.clone(), name.to_owned());
+            }
         });
 
-        let db_filter = Arc::clone(&self.db_reader);
-        let filter = Arc::clone(&self.filter);
-        let rom_mode = self.rom_mode;
-        let filter_button = Button::new("Filter: [*None*]", move |s| {
-            filter_games_dialog(s, db_filter.clone(), rom_mode, filter.clone());
+        let state_filter = Arc::clone(&self.state);
+        let filter_button = Button::new(filter_label, move |s| {
+            filter_games_dialog(s, state_filter.clone());
         }).with_name("button_filter");
 
+        let state_grouping = Arc::clone(&self.state);
+        let grouping_button = Button::new(grouping_label, move |s| {
+            next_grouping(s, state_grouping.clone());
+        }).with_name("button_grouping");
+
         let sets_list = LinearLayout::vertical()
         .child(filter_button)
+        .child(grouping_button)
         .child(DummyView)
         .child(select_game.with_name("selection_list").scrollable());
 
         Ok(sets_list)
     }
 
-    pub fn load_error_dialog(&self, e: anyhow::Error) -> impl View {
+    pub fn load_error_dialog(&self, db_file: &str, e: anyhow::Error) -> impl View {
         Dialog::around(
-            TextView::new(format!("Error loading the DB {}\n\n{}", "db_file", e))
+            TextView::new(format!("Error loading the DB {}\n\n{}", db_file, e))
             .h_align(HAlign::Center)
         ).button("Close", |s| { s.pop_layer(); } )
     }
 
     fn get_top_view(&self) -> ResizedView<Panel<LinearLayout>> {
-        let db_rom_mode = Arc::clone(&self.db_reader);
-        let filter_rom_mode = Arc::clone(&self.filter);
-        let rom_mode = self.rom_mode;
+        let rom_mode = self.state.lock().unwrap().get_rom_mode();
+        let state_rom_mode = Arc::clone(&self.state);
         let layout = LinearLayout::horizontal()
-        .child(Button::new(format!("Rom Mode: {}", self.rom_mode), move |s| {
-            rom_mod_dialog(s, db_rom_mode.clone(), rom_mode, filter_rom_mode.clone());
+        .child(Button::new(format!("Rom Mode: {}", rom_mode), move |s| {
+            rom_mod_dialog(s, state_rom_mode.clone());
         }))
         .child(DummyView)
         .child(TextView::new("|"))
         .child(DummyView)
-        .child(Button::new("Scan Directory", |s| {
+        .child(Button::new("Scan Directory", |_s| {
 
         }));
 
@@ -114,22 +125,37 @@ impl ListSets {
     }
 }
 
-fn rom_mod_dialog(s: &mut Cursive, db_reader: Arc<Mutex<DBReader>>, rom_mode: RomsetMode, filter: Arc<Mutex<String>>) {
+fn rom_mod_dialog(_s: &mut Cursive, _state: Arc<Mutex<AppState>>) {
     
 }
 
-fn filter_games_dialog<'a>(s: &mut Cursive, db_reader: Arc<Mutex<DBReader>>, rom_mode: RomsetMode, filter: Arc<Mutex<String>>) {
-    let db_reader_button = Arc::clone(&db_reader);
-    let filter_button = Arc::clone(&filter);
+/// If the sets list is the view on screen
+fn is_sets_list_shown(s: &mut Cursive) -> bool {
+    s.find_name::<Button>("button_filter").is_some()
+}
+
+pub fn next_grouping(s: &mut Cursive, state: Arc<Mutex<AppState>>) {
+    if !is_sets_list_shown(s) {
+        return;
+    }
+    update_state(s, state, AppMessage::NextGrouping, |s, state, _result| {
+        render_set_list(s, state, None);
+    });
+}
+
+pub fn filter_games_dialog(s: &mut Cursive, state: Arc<Mutex<AppState>>) {
+    if !is_sets_list_shown(s) || s.find_name::<EditView>("filter_text").is_some() {
+        return;
+    }
+    let state_button = Arc::clone(&state);
     let current_filter = {
-        filter.lock().unwrap().to_owned()
+        state.lock().unwrap().get_filter().to_owned()
     };
     let filter_dialog = Dialog::new()
     .content(EditView::new()
         .content(current_filter)
         .on_submit(move |s, filter_to_set| {
-            *filter.lock().unwrap() = filter_to_set.to_string();
-            filter_set(s, db_reader.clone(), rom_mode, filter_to_set);
+            filter_set(s, state.clone(), filter_to_set);
             s.pop_layer();
         })
         .with_name("filter_text")
@@ -142,8 +168,7 @@ fn filter_games_dialog<'a>(s: &mut Cursive, db_reader: Arc<Mutex<DBReader>>, rom
             Some(filter_to_set) => filter_to_set.to_string(),
             None => String::new()
         };
-        *filter_button.lock().unwrap() = filter_to_set.to_string();
-        filter_set(s, db_reader_button.clone(), rom_mode, &filter_to_set);
+        filter_set(s, state_button.clone(), &filter_to_set);
         s.pop_layer();
     }).button("Close", |s| {
         s.pop_layer();
@@ -151,84 +176,222 @@ fn filter_games_dialog<'a>(s: &mut Cursive, db_reader: Arc<Mutex<DBReader>>, rom
     s.add_layer(filter_dialog);
 }
 
-fn filter_set(s: &mut Cursive, db_reader: Arc<Mutex<DBReader>>, rom_mode: RomsetMode, filter: &str) {
-    let cb_sink = s.cb_sink().clone();
-    let filter = filter.to_string();
-    thread::spawn(move || {
-        let set_list = db_reader.lock().unwrap().get_game_list(rom_mode).unwrap().iter()
-            .map(|item| {
-                (format!("{}", truncate_text(&item.0, 20)), item.0.clone())
-            }).collect::<Vec<_>>();
-        let filtered = set_list.iter().filter_map(|set| {
-            if set.0.contains(&filter) || set.1.contains(&filter) {
-                Some((format!("{}", truncate_text(&set.0, 20)), set.0.clone()))
-            } else {
-                None
-            }
-        }).collect::<Vec<_>>();
+/// Edits the tags, separated by commas, and the note of the selected set
+pub fn edit_tags_dialog(s: &mut Cursive, state: Arc<Mutex<AppState>>) {
+    if !is_sets_list_shown(s) || s.find_name::<EditView>("tags_text").is_some() {
+        return;
+    }
+    let (set_name, entry) = {
+        let state = state.lock().unwrap();
+        match state.get_selected_set() {
+            Some(set) => (set.game.name.to_owned(), state.get_set_user_entry(&set.game.name).cloned().unwrap_or_default()),
+            None => return,
+        }
+    };
 
-        cb_sink.send(Box::new(move |s| {
-            s.call_on_name("selection_list", |view: &mut SelectView<String>| {
-                view.clear();
-                view.add_all(filtered);
-            });
+    let tags = entry.tags.iter().cloned().collect::<Vec<_>>().join(", ");
+    let content = LinearLayout::vertical()
+    .child(TextView::new("Tags"))
+    .child(EditView::new().content(tags).with_name("tags_text").fixed_width(40))
+    .child(DummyView)
+    .child(TextView::new("Note"))
+    .child(EditView::new().content(entry.note.unwrap_or_default()).with_name("note_text").fixed_width(40));
+
+    let tags_dialog = Dialog::around(content)
+    .title(set_name.as_str())
+    .button("Save", move |s| {
+        let tags = s.call_on_name("tags_text", |view: &mut EditView| view.get_content()).unwrap_or_default();
+        let note = s.call_on_name("note_text", |view: &mut EditView| view.get_content()).unwrap_or_default();
+        let entry = SetUserEntry {
+            tags: tags.split(',').map(|tag| tag.trim()).filter(|tag| !tag.is_empty()).map(|tag| tag.to_string()).collect(),
+            note: Some(note.to_string()).filter(|note| !note.trim().is_empty()),
+        };
+        s.pop_layer();
+        update_state(s, state.clone(), AppMessage::SetUserEntry(set_name.clone(), entry), |s, state, result| {
+            render_set_details(s, state, result);
+        });
+    }).button("Close", |s| {
+        s.pop_layer();
+    });
+    s.add_layer(tags_dialog);
+}
 
-            let filter = if filter.is_empty() { "*None*" } else { filter.as_str() };
-            s.call_on_name("button_filter", |view: &mut Button| {
-                view.set_label(format!("Filter: [{}]", filter));
-            });
-        })).unwrap();
+fn filter_set(s: &mut Cursive, state: Arc<Mutex<AppState>>, filter: &str) {
+    update_state(s, state, AppMessage::SetFilter(filter.to_string()), |s, state, _result| {
+        render_set_list(s, state, None);
+    });
+}
+
+/// Expands the group loading its sets, or collapses it if it's already expanded
+fn toggle_group(s: &mut Cursive, state: Arc<Mutex<AppState>>, group: Option<String>) {
+    update_state(s, state, AppMessage::ToggleGroup(group.clone()), move |s, state, _result| {
+        render_set_list(s, state, Some(group));
+    });
+}
+
+/// Renders the sets list from the state, selecting the group if there is one
+fn render_set_list(s: &mut Cursive, state: &AppState, selected_group: Option<Option<String>>) {
+    let items = get_set_list_items(state);
+    s.call_on_name("selection_list", |view: &mut SelectView<SetListItem>| {
+        view.clear();
+        view.add_all(items);
+        if let Some(group) = selected_group {
+            if let Some(index) = state.get_set_list().iter().position(|item| item.is_group(&group)) {
+                view.set_selection(index);
+            }
+        }
     });
 
+    s.call_on_name("button_filter", |view: &mut Button| {
+        view.set_label(get_filter_label(state.get_filter()));
+    });
+    s.call_on_name("button_grouping", |view: &mut Button| {
+        view.set_label(get_grouping_label(state.get_grouping()));
+    });
+}
 
+fn get_set_list_items(state: &AppState) -> Vec<(StyledString, SetListItem)> {
+    state.get_set_list().iter().map(|item| (get_set_list_label(item), item.to_owned())).collect()
 }
 
-fn on_select_game(s: &mut Cursive, game_name: String, db_reader: Arc<Mutex<DBReader>>) {
+fn get_set_list_label(item: &SetListItem) -> StyledString {
+    match item {
+        SetListItem::Group { name, sets, expanded } => {
+            let marker = if *expanded { "-" } else { "+" };
+            let name = name.as_deref().unwrap_or("Unknown");
+            StyledString::styled(format!("{} {} ({})", marker, truncate_text(&name, 14), sets), Effect::Bold)
+        },
+        SetListItem::Set { name, grouped: true, .. } => StyledString::plain(format!("  {}", truncate_text(name, 18))),
+        SetListItem::Set { name, .. } => StyledString::plain(truncate_text(name, 20)),
+    }
+}
+
+fn get_filter_label(filter: &str) -> String {
+    let filter = if filter.is_empty() { "*None*" } else { filter };
+    format!("Filter: [{}]", filter)
+}
+
+fn get_grouping_label(grouping: Option<BrowseGrouping>) -> String {
+    match grouping {
+        Some(grouping) => format!("Group: [{}]", grouping),
+        None => "Group: [*None*]".to_string(),
+    }
+}
+
+fn on_select_game(s: &mut Cursive, state: Arc<Mutex<AppState>>, game_name: String) {
+    let request = {
+        let mut state = state.lock().unwrap();
+        let result = state.update(AppMessage::LoadingSet(game_name.clone()));
+        render_set_details(s, &state, result);
+        state.get_selected_db().map(|db_file| SetDetailsRequest { db_file: db_file.to_string(), set_name: game_name, rom_mode: state.get_rom_mode() })
+    };
+
+    if let Some(request) = request {
+        s.with_user_data(|loader: &mut SetDetailsLoader| loader.request(request));
+    }
+}
+
+/// Shows the details of the rom in a dialog, the sets using it are searched in a background thread
+fn on_submit_rom(s: &mut Cursive, state: Arc<Mutex<AppState>>, rom: DataFile) {
+    let (db_file, set_name, rom_mode) = {
+        let state = state.lock().unwrap();
+        match (state.get_selected_db(), state.get_selected_set()) {
+            (Some(db_file), Some(set)) => (db_file.to_string(), set.game.name.to_owned(), state.get_rom_mode()),
+            _ => return,
+        }
+    };
+
+    // The name ties the search to this dialog, it may be closed and another rom shown before it ends
+    let view_name = format!("rom_details_{}_{}", set_name, rom.name);
+    let mut loading = get_styled_from_rom(&rom);
+    loading.append("\n\nLoading...");
+    s.add_layer(Dialog::around(TextView::new(loading).with_name(view_name.as_str()).scrollable())
+        .title(rom.name.as_str())
+        .button("Close", |s| { s.pop_layer(); }));
+
     let cb_sink = s.cb_sink().clone();
     thread::spawn(move || {
-        let result = db_reader.lock().unwrap().get_set_info(game_name, RomsetMode::NonMerged);
-        match result {
-            Ok(gs) => {
-                cb_sink.send(Box::new(move |s| {
-                    s.call_on_name("game_details", |view: &mut TextView| {
-                        view.set_content(get_styled_from_game_set(&gs));
-                    });
-                    s.call_on_name("game_roms", |view: &mut SelectView<DataFile>| {
-                        let items = gs.roms.into_iter().map(|rom| {
-                            let rom_name = if let Some(status) = &rom.status {
-                                match status.as_str() {
-                                    "baddump" => {
-                                        get_style_bad_dump(&rom.name)
-                                    },
-                                    "nodump" => {
-                                        get_style_no_dump(&rom.name)
-                                    },
-                                    _ => {
-                                        StyledString::styled(format!("{} ({})", &rom.name, status), Style::none())
-                                    }
-                                }
-                            } else {
-                                StyledString::styled(&rom.name, Style::none())
-                            };
-                            (rom_name, rom)
-                        }).collect::<Vec<_>>();
-                        view.clear();
-                        view.add_all(items);
-                    });
-                })).unwrap();
-            }
-            Err(e) => {
-                cb_sink.send(Box::new(move |s| {
-                    s.call_on_name("game_details", |view: &mut TextView| {
-                        view.set_content(format!("Error\n\n{}", e));
-                    });
-                })).unwrap();
-            }
-        }
+        let result = RomUsage::load(&db_file, &set_name, rom, rom_mode);
+        let _ = cb_sink.send(Box::new(move |s| {
+            let content = match result {
+                Ok(rom_usage) => get_styled_from_rom_usage(&rom_usage),
+                Err(e) => StyledString::plain(format!("Error\n\n{}", e)),
+            };
+            s.call_on_name(view_name.as_str(), |view: &mut TextView| {
+                view.set_content(content);
+            });
+        }));
+    });
+}
+
+/// Starts the worker loading the details of the selected set, each loaded set is rendered in the UI thread
+pub fn start_set_details_loader(s: &mut Cursive, state: Arc<Mutex<AppState>>) {
+    let cb_sink = s.cb_sink().clone();
+    let loader = SetDetailsLoader::new(move |request, loaded| {
+        let state = Arc::clone(&state);
+        let _ = cb_sink.send(Box::new(move |s| {
+            let mut state = state.lock().unwrap();
+            let result = match loaded {
+                Ok(set_info) => state.update(AppMessage::SetLoaded(Box::new(set_info))),
+                Err(e) if matches!(state.get_set_details(), SetDetails::Loading(set_name) if *set_name == request.set_name) => Err(e),
+                Err(_) => Ok(()),
+            };
+            render_set_details(s, &state, result);
+        }));
     });
+    s.set_user_data(loader);
+}
+
+fn render_set_details(s: &mut Cursive, state: &AppState, result: Result<()>) {
+    match (result, state.get_set_details()) {
+        (Ok(_), SetDetails::Loaded(gs)) => {
+            s.call_on_name("game_details", |view: &mut TextView| {
+                view.set_content(get_styled_from_game_set(gs, state.get_set_user_entry(&gs.game.name), state.get_set_last_check(&gs.game.name)));
+            });
+            s.call_on_name("game_roms", |view: &mut SelectView<DataFile>| {
+                let items = gs.roms.iter().cloned().map(|rom| {
+                    let rom_name = if let Some(status) = &rom.status {
+                        match status.as_str() {
+                            "baddump" => {
+                                get_style_bad_dump(&rom.name)
+                            },
+                            "nodump" => {
+                                get_style_no_dump(&rom.name)
+                            },
+                            _ => {
+                                StyledString::styled(format!("{} ({})", &rom.name, status), Style::none())
+                            }
+                        }
+                    } else {
+                        StyledString::styled(&rom.name, Style::none())
+                    };
+                    (rom_name, rom)
+                }).collect::<Vec<_>>();
+                view.clear();
+                view.add_all(items);
+            });
+        }
+        (Ok(_), SetDetails::Loading(set_name)) => {
+            let mut styled = StyledString::styled("Name: ", Effect::Bold);
+            styled.append(set_name);
+            styled.append("\n\nLoading...");
+            s.call_on_name("game_details", |view: &mut TextView| {
+                view.set_content(styled);
+            });
+            s.call_on_name("game_roms", |view: &mut SelectView<DataFile>| {
+                view.clear();
+            });
+        }
+        (Ok(_), SetDetails::None) => {}
+        (Err(e), _) => {
+            s.call_on_name("game_details", |view: &mut TextView| {
+                view.set_content(format!("Error\n\n{}", e));
+            });
+        }
+    }
 }
 
-fn get_styled_from_game_set(game_set: &GameSet) -> StyledString {
+fn get_styled_from_game_set(game_set: &GameSet, user_entry: Option<&SetUserEntry>, last_check: Option<&LastCheck>) -> StyledString {
     let game = &game_set.game;
     let mut styled = StyledString::styled("Name: ", Effect::Bold);
     styled.append(&game.name);
@@ -256,6 +419,73 @@ fn get_styled_from_game_set(game_set: &GameSet) -> StyledString {
         styled.append(StyledString::styled("\nSource File: ", Effect::Bold));
         styled.append(source_file);
     }
+    if let Some(metadata) = &game.metadata {
+        if let Some(driver_status) = &metadata.driver_status {
+            styled.append(StyledString::styled("\nDriver Status: ", Effect::Bold));
+            styled.append(driver_status);
+        }
+        if let Some(players) = &metadata.players {
+            styled.append(StyledString::styled("\nPlayers: ", Effect::Bold));
+            styled.append(players.to_string());
+        }
+        if let Some(orientation) = &metadata.orientation {
+            styled.append(StyledString::styled("\nOrientation: ", Effect::Bold));
+            styled.append(orientation);
+        }
+    }
+    if let Some(user_entry) = user_entry {
+        if !user_entry.tags.is_empty() {
+            styled.append(StyledString::styled("\nTags: ", Effect::Bold));
+            styled.append(user_entry.tags.iter().cloned().collect::<Vec<_>>().join(", "));
+        }
+        if let Some(note) = &user_entry.note {
+            styled.append(StyledString::styled("\nNote: ", Effect::Bold));
+            styled.append(note);
+        }
+    }
+    if let Some(last_check) = last_check {
+        styled.append(StyledString::styled("\nLast Check: ", Effect::Bold));
+        styled.append(format!("{} ({})", last_check.status, last_check.checked_at));
+    }
+
+    styled
+}
+
+fn get_styled_from_rom(rom: &DataFile) -> StyledString {
+    let mut styled = StyledString::styled("Name: ", Effect::Bold);
+    styled.append(&rom.name);
+    let fields = [
+        ("Size", rom.info.size.map(|size| format!("{} ({} bytes)", ByteSize(size.into()), size))),
+        ("CRC", rom.info.crc.to_owned()),
+        ("SHA1", rom.info.sha1.to_owned()),
+        ("MD5", rom.info.md5.to_owned()),
+        ("Status", rom.status.to_owned()),
+        ("BIOS", rom.bios.to_owned()),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value {
+            styled.append(StyledString::styled(format!("\n{}: ", label), Effect::Bold));
+            styled.append(value);
+        }
+    }
+
+    styled
+}
+
+fn get_styled_from_rom_usage(rom_usage: &RomUsage) -> StyledString {
+    let mut styled = get_styled_from_rom(&rom_usage.rom);
+    styled.append("\n\n");
+    if rom_usage.used_by.is_empty() {
+        styled.append(format!("Not used by other sets than {}", rom_usage.set_name));
+        return styled;
+    }
+    styled.append(StyledString::styled("Also used by:", Effect::Bold));
+    for (set_name, rom_names) in &rom_usage.used_by {
+        styled.append(format!("\n- {}", set_name));
+        if rom_names.iter().any(|rom_name| *rom_name != rom_usage.rom.name) {
+            styled.append(format!(" (as {})", rom_names.join(", ")));
+        }
+    }
 
     styled
 }
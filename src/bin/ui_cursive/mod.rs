@@ -1,9 +1,13 @@
 mod utils;
 mod list_db;
 mod list_sets;
+mod compare_reports;
+
+use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
-use cursive::{Cursive, align::HAlign, event::Key, theme::{Color, PaletteColor, Theme}, views::*};
+use cursive::{Cursive, align::HAlign, event::{Event, Key as CursiveKey}, theme::{Color, PaletteColor, Theme}, traits::Nameable, views::*};
+use romst::ui_core::{AppMessage, AppState, DEFAULT_DB_DIRECTORY, keys::{DEFAULT_KEYBINDINGS_FILE, Key, KeyAction, KeyBindings}};
 
 use self::list_db::SelectDB;
 
@@ -12,9 +16,14 @@ pub fn render() -> Result<()> {
     let theme = custom_theme_from_cursive(&siv);
     siv.set_theme(theme);
 
-    siv.add_global_callback(Key::Esc,exit);
+    let state = Arc::new(Mutex::new(AppState::new(DEFAULT_DB_DIRECTORY)));
+    let (key_bindings, key_bindings_error) = match KeyBindings::load(DEFAULT_KEYBINDINGS_FILE) {
+        Ok(key_bindings) => (key_bindings, None),
+        Err(e) => (KeyBindings::default(), Some(e)),
+    };
+    add_key_callbacks(&mut siv, key_bindings, Arc::clone(&state));
 
-    let select_db = SelectDB::new();
+    let select_db = SelectDB::new(state);
 
     match select_db.load_view() {
         Ok(view) => {
@@ -28,11 +37,81 @@ pub fn render() -> Result<()> {
         }
     }
 
+    if let Some(e) = key_bindings_error {
+        siv.add_layer(Dialog::around(
+            TextView::new(format!("Error loading the key bindings, using the default ones\n\n{}", e))
+            .h_align(HAlign::Center)
+        ).button("Close", |s| { s.pop_layer(); }));
+    }
+
     siv.run();
 
     Ok(())
 }
 
+/// Updates the state in a background thread, and renders the result in the UI thread
+fn update_state<F>(s: &mut Cursive, state: Arc<Mutex<AppState>>, message: AppMessage, render: F) where F: FnOnce(&mut Cursive, &AppState, Result<()>) + Send + 'static {
+    let cb_sink = s.cb_sink().clone();
+    std::thread::spawn(move || {
+        let result = state.lock().unwrap().update(message);
+        cb_sink.send(Box::new(move |s| {
+            render(s, &state.lock().unwrap(), result);
+        })).unwrap();
+    });
+}
+
+fn add_key_callbacks(siv: &mut Cursive, key_bindings: KeyBindings, state: Arc<Mutex<AppState>>) {
+    let key_bindings = Arc::new(key_bindings);
+    for (action, keys) in key_bindings.get_bindings() {
+        for key in keys {
+            let state = Arc::clone(&state);
+            let key_bindings = Arc::clone(&key_bindings);
+            siv.add_global_callback(to_event(key), move |s| on_key_action(s, action, state.clone(), &key_bindings));
+        }
+    }
+}
+
+fn to_event(key: &Key) -> Event {
+    match key {
+        Key::Char(c) => Event::Char(*c),
+        Key::Ctrl(c) => Event::CtrlChar(*c),
+        Key::F(n) => Event::Key(CursiveKey::from_f(*n)),
+        Key::Esc => Event::Key(CursiveKey::Esc),
+        Key::Enter => Event::Key(CursiveKey::Enter),
+        Key::Tab => Event::Key(CursiveKey::Tab),
+        Key::Backspace => Event::Key(CursiveKey::Backspace),
+    }
+}
+
+fn on_key_action(s: &mut Cursive, action: KeyAction, state: Arc<Mutex<AppState>>, key_bindings: &KeyBindings) {
+    match action {
+        KeyAction::Quit => exit(s),
+        KeyAction::Help => help_dialog(s, key_bindings),
+        KeyAction::Filter => list_sets::filter_games_dialog(s, state),
+        KeyAction::NextGrouping => list_sets::next_grouping(s, state),
+        KeyAction::EditTags => list_sets::edit_tags_dialog(s, state),
+        KeyAction::CompareReports => compare_reports::compare_reports_dialog(s, state),
+        KeyAction::NextRegression => compare_reports::go_to_regression(s, state, true),
+        KeyAction::PreviousRegression => compare_reports::go_to_regression(s, state, false),
+    }
+}
+
+fn help_dialog(s: &mut Cursive, key_bindings: &KeyBindings) {
+    if s.find_name::<Dialog>("help_dialog").is_some() {
+        return;
+    }
+
+    let help = key_bindings.get_bindings().into_iter().map(|(action, keys)| {
+        let keys = keys.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(", ");
+        format!("{:>12}  {}", keys, action.get_description())
+    }).collect::<Vec<_>>().join("\n");
+
+    s.add_layer(Dialog::around(TextView::new(help))
+        .title("Key bindings")
+        .button("Close", |s| { s.pop_layer(); })
+        .with_name("help_dialog"));
+}
+
 fn custom_theme_from_cursive(siv: &Cursive) -> Theme {
     // We'll return the current theme with a small modification.
     let mut theme = siv.current_theme().clone();
@@ -44,4 +123,4 @@ fn custom_theme_from_cursive(siv: &Cursive) -> Theme {
 
 fn exit(s: &mut Cursive) {
     s.quit();
-}
\ No newline at end of file
+}
@@ -0,0 +1,127 @@
+//! Other names the sets are known by, like the names of FBNeo for the sets of a MAME DAT. A file named as an alias
+//! belongs to the set, and is reported to be renamed to the name of the DAT.
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{err, error::RomstError};
+
+use super::models;
+
+/// The sets each alias stands for, an alias stands for a single set but a set can have several aliases
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SetAliases {
+    sets: HashMap<String, String>,
+}
+
+impl SetAliases {
+    pub fn add_alias<S>(&mut self, alias: S, set_name: S) where S: Into<String> {
+        self.sets.insert(alias.into(), set_name.into());
+    }
+
+    /// Reads the aliases from a CSV with an `alias,set` line for each one, or from an ini with `alias=set` lines. Empty
+    /// lines, `#` and `;` comments and the `[section]` headers are skipped
+    pub fn from_file(file: impl AsRef<Path>) -> Result<Self> {
+        let file = file.as_ref();
+        let contents = match fs::read_to_string(file) {
+            Ok(contents) => contents,
+            Err(e) => return err!(RomstError::GenericError { message: format!("Error reading the aliases file `{}`: {}", file.to_string_lossy(), e) }),
+        };
+
+        let mut aliases = SetAliases::default();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') || line.starts_with('[') {
+                continue;
+            }
+            match line.split_once('=').or_else(|| line.split_once(',')) {
+                Some((alias, set_name)) if !alias.trim().is_empty() && !set_name.trim().is_empty() => {
+                    aliases.add_alias(alias.trim(), set_name.trim());
+                }
+                _ => return err!(RomstError::ParsingError { message: format!("Line {} of `{}` is not an `alias,set` pair: `{}`", number + 1, file.to_string_lossy(), line) }),
+            }
+        }
+
+        Ok(aliases)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// The set of the file, the set the name of the file is an alias of, or the name itself
+    pub fn get_set_from_file(&self, file: &str) -> String {
+        let set_name = models::get_set_from_file(file);
+        match self.sets.get(&set_name) {
+            Some(aliased) => aliased.to_owned(),
+            None => set_name,
+        }
+    }
+
+    /// Same as `models::does_file_belong_to_set`, but a file named as an alias of the set also belongs to it
+    pub fn does_file_belong_to_set(&self, file: &str, set: &str) -> bool {
+        models::does_file_belong_to_set(file, set) || self.get_alias_for_set(file, set).is_some()
+    }
+
+    /// The name the file needs to belong to the set without the alias, only if it's named as an alias of the set
+    pub fn get_rename_for_set(&self, file: &str, set: &str) -> Option<String> {
+        let alias = self.get_alias_for_set(file, set)?;
+        Some(format!("{}{}", set, file.strip_prefix(alias.as_str()).unwrap_or_default()))
+    }
+
+    /// The alias of the set the file is named as, if it's named as one
+    fn get_alias_for_set(&self, file: &str, set: &str) -> Option<String> {
+        let alias = models::get_set_from_file(file);
+        match self.sets.get(&alias) {
+            Some(aliased) if aliased == set && models::does_file_belong_to_set(file, &alias) => Some(alias),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+
+    #[test]
+    fn matches_the_files_named_as_an_alias() {
+        let mut aliases = SetAliases::default();
+        aliases.add_alias("sf2ua_fb", "sf2ua");
+
+        assert!(aliases.does_file_belong_to_set("sf2ua.zip", "sf2ua"));
+        assert!(aliases.does_file_belong_to_set("sf2ua_fb.zip", "sf2ua"));
+        assert!(aliases.does_file_belong_to_set("sf2ua_fb", "sf2ua"));
+        assert!(!aliases.does_file_belong_to_set("sf2ua_fb.zip", "sf2"));
+        assert!(!aliases.does_file_belong_to_set("sf2ua_fb.7z", "sf2ua"));
+
+        assert_eq!("sf2ua", aliases.get_set_from_file("sf2ua_fb.zip"));
+        assert_eq!("sf2", aliases.get_set_from_file("sf2.zip"));
+        assert_eq!(Some("sf2ua.zip".to_string()), aliases.get_rename_for_set("sf2ua_fb.zip", "sf2ua"));
+        assert_eq!(None, aliases.get_rename_for_set("sf2ua.zip", "sf2ua"));
+    }
+
+    #[test]
+    fn reads_the_aliases_from_csv_and_ini() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_aliases_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let csv = dir.join("aliases.csv");
+        fs::write(&csv, "# fbneo,mame\nsf2ua_fb, sf2ua\n\nmslugx_fb,mslugx\n")?;
+        let aliases = SetAliases::from_file(&csv)?;
+        assert_eq!("sf2ua", aliases.get_set_from_file("sf2ua_fb.zip"));
+        assert_eq!("mslugx", aliases.get_set_from_file("mslugx_fb.zip"));
+
+        let ini = dir.join("aliases.ini");
+        fs::write(&ini, "[aliases]\n; fbneo = mame\nsf2ua_fb = sf2ua\n")?;
+        assert_eq!("sf2ua", SetAliases::from_file(&ini)?.get_set_from_file("sf2ua_fb.zip"));
+
+        fs::write(&csv, "sf2ua_fb\n")?;
+        assert!(SetAliases::from_file(&csv).is_err());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use std::{collections::{BTreeMap, BTreeSet}, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use super::{fixer::{self, FixConflict, FixPlan}, reporter::scan_report::{RomLocatedAt, ScanReport, SetStatus}};
+
+/// An entry of an archive in the collection
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub archive: String,
+    pub entry: String,
+}
+
+impl ArchiveEntry {
+    pub fn new<S>(archive: S, entry: S) -> Self where S: Into<String> {
+        Self { archive: archive.into(), entry: entry.into() }
+    }
+}
+
+impl Display for ArchiveEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.archive, self.entry)
+    }
+}
+
+/// A rom already in the collection and the entries it's moved to, a rom needed by several sets is a single move
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RomMove {
+    pub source: ArchiveEntry,
+    pub targets: Vec<ArchiveEntry>,
+}
+
+impl Display for RomMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let targets = self.targets.iter().map(|target| target.to_string()).collect::<Vec<_>>();
+        writeln!(f, "- {} -> {}", self.source, targets.join(", "))
+    }
+}
+
+/// A rom renamed by the fix of its own archive that other archives copy with its current name, so the copies depend
+/// on which set is fixed first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceConflict {
+    pub source: ArchiveEntry,
+    pub renamed_to: Vec<String>,
+    pub copied_to: Vec<ArchiveEntry>,
+}
+
+impl Display for SourceConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let copied_to = self.copied_to.iter().map(|target| target.to_string()).collect::<Vec<_>>();
+        writeln!(f, "- {} is renamed to {} and copied to {}", self.source, self.renamed_to.join(", "), copied_to.join(", "))
+    }
+}
+
+/// The sets of a scan that can be completed with the roms already in the collection, with the moves of all of them
+/// together, and the targets and sources that can't be moved safely
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixableAnalysis {
+    root_directory: Option<String>,
+    pub sets: Vec<String>,
+    pub moves: Vec<RomMove>,
+    pub conflicts: Vec<FixConflict>,
+    pub source_conflicts: Vec<SourceConflict>,
+}
+
+impl Display for FixableAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(root_directory) = &self.root_directory {
+            writeln!(f, "Directory: {}", root_directory)?;
+        }
+        if self.sets.is_empty() {
+            writeln!(f, "No sets can be fixed")?;
+            return Ok(());
+        }
+        writeln!(f, "Fixable sets: {}", self.sets.len())?;
+        for set in &self.sets {
+            writeln!(f, "- {}", set)?;
+        }
+        writeln!(f, "Moves:")?;
+        for rom_move in &self.moves {
+            write!(f, "{}", rom_move)?;
+        }
+        if !self.conflicts.is_empty() {
+            writeln!(f, "Conflicts, not moved:")?;
+            for conflict in &self.conflicts {
+                write!(f, "{}", conflict)?;
+            }
+        }
+        if !self.source_conflicts.is_empty() {
+            writeln!(f, "Sources renamed while copied:")?;
+            for conflict in &self.source_conflicts {
+                write!(f, "{}", conflict)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FixableAnalysis {
+    /// Lists the fixeable sets of the report, the roms are taken from the same sources the fix plan uses
+    pub fn from_report(report: &ScanReport) -> Self {
+        let archives_by_set = fixer::get_archives_by_set(report);
+        let conflicts = FixPlan::from_report(report).conflicts;
+        let conflicting = conflicts.iter()
+            .map(|conflict| ArchiveEntry::new(conflict.archive.as_str(), conflict.target.as_str()))
+            .collect::<BTreeSet<_>>();
+
+        let sets = report.sets.iter()
+            .filter(|(_name, set)| set.is_complete() == SetStatus::FIXEABLE)
+            .collect::<BTreeMap<_, _>>();
+
+        let mut targets_by_source: BTreeMap<ArchiveEntry, BTreeSet<ArchiveEntry>> = BTreeMap::new();
+        for (set_name, set) in &sets {
+            let archive = fixer::get_set_archive(&archives_by_set, set_name);
+            for (rom, located_at) in &set.roms_available {
+                let source = match located_at {
+                    RomLocatedAt::InSet => continue,
+                    RomLocatedAt::InSetWrongName(name) => ArchiveEntry::new(archive.as_str(), name.as_str()),
+                    RomLocatedAt::InOthers(locations) => {
+                        match locations.iter().min_by_key(|location| (location.get_file(), location.get_with_name())) {
+                            Some(location) => ArchiveEntry::new(location.get_file(), location.get_with_name()),
+                            None => continue,
+                        }
+                    }
+                };
+                let target = ArchiveEntry::new(archive.as_str(), rom.name.as_str());
+                if !conflicting.contains(&target) {
+                    targets_by_source.entry(source).or_default().insert(target);
+                }
+            }
+        }
+
+        let mut source_conflicts = vec![];
+        for (source, targets) in &targets_by_source {
+            let (in_archive, in_others): (Vec<&ArchiveEntry>, Vec<&ArchiveEntry>) = targets.iter().partition(|target| target.archive == source.archive);
+            if !in_archive.is_empty() && !in_others.is_empty() {
+                source_conflicts.push(SourceConflict {
+                    source: source.to_owned(),
+                    renamed_to: in_archive.into_iter().map(|target| target.entry.to_string()).collect(),
+                    copied_to: in_others.into_iter().cloned().collect(),
+                });
+            }
+        }
+
+        let moves = targets_by_source.into_iter()
+            .map(|(source, targets)| RomMove { source, targets: targets.into_iter().collect() })
+            .collect();
+
+        Self {
+            root_directory: report.get_root_directory().map(|root_directory| root_directory.to_string()),
+            sets: sets.keys().map(|set_name| set_name.to_string()).collect(),
+            moves,
+            conflicts,
+            source_conflicts,
+        }
+    }
+
+    pub fn get_root_directory(&self) -> Option<&str> {
+        self.root_directory.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RomsetMode, data::{models::{archive::{ArchiveFormat, ArchiveInfo}, file::{DataFile, DataFileInfo, FileType}}, reporter::scan_report::RomLocation}};
+
+    use super::*;
+
+    fn rom(name: &str, crc: &str) -> DataFile {
+        let mut info = DataFileInfo::new(FileType::Rom);
+        info.crc = Some(crc.to_string());
+        DataFile::new(name, info)
+    }
+
+    fn archive(entries: usize) -> ArchiveInfo {
+        ArchiveInfo::new(ArchiveFormat::NonCanonical, entries, 0, String::new())
+    }
+
+    #[test]
+    fn aggregates_the_moves_of_all_the_sets() {
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::Split);
+        report.add_archive_info("game1.zip", archive(1));
+        report.add_archive_info("game2.zip", archive(1));
+        report.add_archive_info("game3.zip", archive(1));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "old.rom"), rom("a.rom", "1111"));
+        // The same rom of game3 completes game1 and game2
+        report.add_rom_for_set("game1", RomLocation::new("game3.zip", "shared.rom"), rom("b.rom", "2222"));
+        report.add_rom_for_set("game2", RomLocation::new("game3.zip", "shared.rom"), rom("b.rom", "2222"));
+        report.add_rom_for_set("game3", RomLocation::new("game3.zip", "shared.rom"), rom("shared.rom", "2222"));
+
+        let analysis = FixableAnalysis::from_report(&report);
+
+        assert_eq!(vec!["game1".to_string(), "game2".to_string()], analysis.sets);
+        assert_eq!(vec![
+            RomMove { source: ArchiveEntry::new("game1.zip", "old.rom"), targets: vec![ ArchiveEntry::new("game1.zip", "a.rom") ] },
+            RomMove { source: ArchiveEntry::new("game3.zip", "shared.rom"), targets: vec![ ArchiveEntry::new("game1.zip", "b.rom"), ArchiveEntry::new("game2.zip", "b.rom") ] },
+        ], analysis.moves);
+        assert!(analysis.conflicts.is_empty());
+        assert!(analysis.source_conflicts.is_empty());
+    }
+
+    #[test]
+    fn flags_the_conflicts() {
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::Split);
+        report.add_archive_info("game1.zip", archive(2));
+        report.add_archive_info("game2.zip", archive(0));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "x.rom"), rom("a.rom", "1111"));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "y.rom"), rom("a.rom", "2222"));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "old.rom"), rom("b.rom", "3333"));
+        report.add_rom_for_set("game2", RomLocation::new("game1.zip", "old.rom"), rom("c.rom", "3333"));
+
+        let analysis = FixableAnalysis::from_report(&report);
+
+        assert_eq!(vec![
+            FixConflict { archive: "game1.zip".to_string(), target: "a.rom".to_string(), sources: vec![ "x.rom".to_string(), "y.rom".to_string() ] },
+        ], analysis.conflicts);
+        assert_eq!(vec![
+            RomMove { source: ArchiveEntry::new("game1.zip", "old.rom"), targets: vec![ ArchiveEntry::new("game1.zip", "b.rom"), ArchiveEntry::new("game2.zip", "c.rom") ] },
+        ], analysis.moves);
+        assert_eq!(vec![
+            SourceConflict { source: ArchiveEntry::new("game1.zip", "old.rom"), renamed_to: vec![ "b.rom".to_string() ], copied_to: vec![ ArchiveEntry::new("game2.zip", "c.rom") ] },
+        ], analysis.source_conflicts);
+    }
+}
@@ -0,0 +1,204 @@
+//! Copies of the archives a fix or a rebuild is about to write again or replace, taken before their first change.
+//! The copies go into a dated folder with a manifest listing them, which is enough to put the archives back as they
+//! were. The manifest is written after each copy, so a run that failed halfway can be undone too.
+use std::{fmt::Display, fs::{self, File}, io::{BufReader, BufWriter}, path::{Path, PathBuf}};
+
+use anyhow::Result;
+use chrono::Utc;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{err, error::RomstError, filesystem::paths};
+
+use super::cleaner::get_tmp_path;
+
+/// The name of the manifest in the backup folder
+pub const BACKUP_MANIFEST_FILE: &str = "romst_backup_manifest.json";
+
+/// An archive changed by the run, it didn't exist if the run created it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackedUpArchive {
+    pub archive: String,
+    pub existed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub created_at: String,
+    /// The directory with the archives changed
+    pub directory: String,
+    /// The folder with the copies and the manifest
+    pub backup_directory: String,
+    pub archives: Vec<BackedUpArchive>,
+}
+
+impl Display for BackupManifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Directory: {}", self.directory)?;
+        writeln!(f, "Backup: {}", self.backup_directory)?;
+        if self.archives.is_empty() {
+            writeln!(f, "No archives backed up")?;
+        }
+        for archive in &self.archives {
+            if archive.existed {
+                writeln!(f, "- {}", archive.archive)?;
+            } else {
+                writeln!(f, "- {} (created by the run)", archive.archive)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Takes the copies of the archives of a directory. Without a folder nothing is copied, the default one is used to
+/// run a plan without backups
+#[derive(Debug, Default)]
+pub struct Backup {
+    backup_root: Option<PathBuf>,
+    manifest: Option<BackupManifest>,
+}
+
+impl Backup {
+    /// The backups go into a dated folder inside `backup_root`, created once the first archive is copied
+    pub fn new(backup_root: impl Into<PathBuf>) -> Self {
+        Self { backup_root: Some(backup_root.into()), manifest: None }
+    }
+
+    /// The backups of the operation, next to the report
+    pub fn get_backup_root(report_file: &str, operation: &str) -> String {
+        format!("{}.{}-backups", report_file, operation)
+    }
+
+    /// The manifest of the backups taken so far, if any
+    pub fn get_manifest_file(&self) -> Option<PathBuf> {
+        self.manifest.as_ref().map(|manifest| Path::new(&manifest.backup_directory).join(BACKUP_MANIFEST_FILE))
+    }
+
+    /// Copies the archive of the directory before it's changed, only the first time it's changed during the run
+    pub fn save(&mut self, directory: &Path, archive: &str) -> Result<()> {
+        let backup_root = match &self.backup_root {
+            Some(backup_root) => backup_root,
+            None => return Ok(()),
+        };
+        let manifest = match &mut self.manifest {
+            Some(manifest) => manifest,
+            None => self.manifest.insert(BackupManifest {
+                created_at: Utc::now().to_rfc3339(),
+                directory: directory.to_string_lossy().to_string(),
+                backup_directory: create_dated_directory(backup_root)?.to_string_lossy().to_string(),
+                archives: vec![],
+            }),
+        };
+        if manifest.archives.iter().any(|backed_up| backed_up.archive == archive) {
+            return Ok(());
+        }
+
+        let file = paths::join_key(directory, archive);
+        let existed = file.exists();
+        if existed {
+            let backup_file = paths::join_key(&manifest.backup_directory, archive);
+            info!("Backing up {} into {}", file.to_string_lossy(), backup_file.to_string_lossy());
+            if let Some(parent) = backup_file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if file.is_dir() {
+                paths::copy_directory(&file, &backup_file)?;
+            } else {
+                fs::copy(&file, &backup_file)?;
+            }
+        }
+        manifest.archives.push(BackedUpArchive { archive: archive.to_string(), existed });
+
+        let manifest_file = Path::new(&manifest.backup_directory).join(BACKUP_MANIFEST_FILE);
+        let tmp_file = get_tmp_path(&manifest_file);
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&tmp_file)?), &manifest)?;
+        fs::rename(tmp_file, manifest_file)?;
+        Ok(())
+    }
+
+    /// Puts back the archives of the manifest as they were before the run, the ones the run created are removed.
+    /// The backup is kept, so it can be restored again
+    pub fn restore(manifest_file: impl AsRef<Path>) -> Result<BackupManifest> {
+        let manifest_file = manifest_file.as_ref();
+        if !manifest_file.exists() {
+            return err!(RomstError::GenericError { message: format!("No backup manifest found at `{}`", manifest_file.to_string_lossy()) });
+        }
+        let manifest: BackupManifest = serde_json::from_reader(BufReader::new(File::open(manifest_file)?))?;
+
+        for backed_up in manifest.archives.iter().rev() {
+            let file = paths::join_key(&manifest.directory, &backed_up.archive);
+            remove_path(&file)?;
+            if backed_up.existed {
+                let backup_file = paths::join_key(&manifest.backup_directory, &backed_up.archive);
+                info!("Restoring {} from {}", file.to_string_lossy(), backup_file.to_string_lossy());
+                if backup_file.is_dir() {
+                    paths::copy_directory(&backup_file, &file)?;
+                } else {
+                    fs::copy(&backup_file, &file)?;
+                }
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// A new folder named after the current time, with a number after it if there's one already
+fn create_dated_directory(backup_root: &Path) -> Result<PathBuf> {
+    let name = Utc::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut directory = backup_root.join(&name);
+    let mut count = 1;
+    while directory.exists() {
+        directory = backup_root.join(format!("{}-{}", name, count));
+        count += 1;
+    }
+    fs::create_dir_all(&directory)?;
+    Ok(paths::normalize_path(directory))
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn restores_the_archives_changed() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_backup_{}", std::process::id()));
+        let roms = dir.join("roms");
+        fs::create_dir_all(&roms)?;
+        fs::write(roms.join("game1.zip"), "original")?;
+
+        let mut backup = Backup::new(dir.join("backups"));
+        assert!(backup.get_manifest_file().is_none());
+        backup.save(&roms, "game1.zip")?;
+        fs::write(roms.join("game1.zip"), "changed")?;
+        // Only the first copy is kept
+        backup.save(&roms, "game1.zip")?;
+        fs::write(roms.join("game1.zip"), "changed again")?;
+        backup.save(&roms, "game2.zip")?;
+        fs::write(roms.join("game2.zip"), "created")?;
+
+        let manifest = Backup::restore(backup.get_manifest_file().unwrap())?;
+        let game1 = fs::read_to_string(roms.join("game1.zip"));
+        let game2_exists = roms.join("game2.zip").exists();
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(vec![
+            BackedUpArchive { archive: "game1.zip".to_string(), existed: true },
+            BackedUpArchive { archive: "game2.zip".to_string(), existed: false },
+        ], manifest.archives);
+        assert_eq!("original", game1?);
+        assert!(!game2_exists);
+        Ok(())
+    }
+}
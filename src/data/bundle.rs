@@ -0,0 +1,331 @@
+//! Everything a set needs to run gathered in one place, to share it or to move it to another machine. Besides the
+//! archive of the set, the mode tells which other sets are needed: the parent of a clone in split, the bios in split
+//! and merged, and the devices and samples always. The files are found by their names in the collection, they aren't
+//! checked, a check of the bundle tells if they are right.
+use std::{collections::HashSet, fmt::Display, fs::{self, File}, io::{self, BufWriter}, path::{Path, PathBuf}};
+
+use anyhow::Result;
+use log::info;
+use serde::{Deserialize, Serialize};
+use zip::{CompressionMethod, ZipWriter, write::FileOptions};
+
+use crate::{RomsetMode, err, error::RomstError, filesystem::paths};
+
+use super::{cleaner::get_tmp_path, models::file::DumpStatus, reader::{DataReader, sqlite::DBReader}, units::ByteSize};
+
+/// The extensions of the archives a set can be in, a set can also be a folder
+const SET_EXTENSIONS: [&str; 3] = ["zip", "7z", "rar"];
+/// The folder of the samples, in the bundle and in the source directory if no other is given
+pub const SAMPLES_FOLDER: &str = "samples";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BundleReason {
+    Set,
+    Parent,
+    Bios,
+    Device,
+    Disk,
+    Samples,
+}
+
+impl Display for BundleReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleReason::Set => write!(f, "set"),
+            BundleReason::Parent => write!(f, "parent"),
+            BundleReason::Bios => write!(f, "bios"),
+            BundleReason::Device => write!(f, "device"),
+            BundleReason::Disk => write!(f, "disk"),
+            BundleReason::Samples => write!(f, "samples"),
+        }
+    }
+}
+
+/// A file or folder of the collection copied into the bundle, at the same path it has in the collection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleFile {
+    pub set_name: String,
+    pub reason: BundleReason,
+    /// The path in the bundle, with `/` between the folders
+    pub path: String,
+    pub source: String,
+    pub bytes: u64,
+}
+
+/// What the bundle needs and isn't in the collection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleMissing {
+    pub set_name: String,
+    pub reason: BundleReason,
+    pub path: String,
+}
+
+/// Where the sets are read from and how the bundle is written
+#[derive(Debug, Clone, Default)]
+pub struct BundleOptions {
+    pub rom_mode: RomsetMode,
+    pub source_directory: String,
+    /// The folder with the sample sets, `samples` in the source directory if not set
+    pub samples_directory: Option<String>,
+    pub target_directory: String,
+    /// Writes the bundle as a single `<game>-bundle.zip`, the files are stored as they are
+    pub zip: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundlePlan {
+    pub game_name: String,
+    pub rom_mode: RomsetMode,
+    pub source_directory: String,
+    pub target_directory: String,
+    pub files: Vec<BundleFile>,
+    pub missing: Vec<BundleMissing>,
+    pub bytes: u64,
+    /// The zip or the folder written, once the plan is executed
+    #[serde(default)]
+    pub bundle: Option<String>,
+}
+
+impl Display for BundlePlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Game: {} ({})", self.game_name, self.rom_mode)?;
+        writeln!(f, "Source dir: {}", self.source_directory)?;
+        writeln!(f, "Target dir: {}", self.target_directory)?;
+        for file in &self.files {
+            writeln!(f, "- {} ({} {}), {}", file.path, file.reason, file.set_name, ByteSize(file.bytes))?;
+        }
+        if !self.missing.is_empty() {
+            writeln!(f, "Not found in the collection:")?;
+            for missing in &self.missing {
+                writeln!(f, "- {} ({} {})", missing.path, missing.reason, missing.set_name)?;
+            }
+        }
+        writeln!(f, "Total: {}", ByteSize(self.bytes))?;
+        if let Some(bundle) = &self.bundle {
+            writeln!(f, "Bundle: {}", bundle)?;
+        }
+        Ok(())
+    }
+}
+
+impl BundlePlan {
+    /// Finds the files of the game and its dependencies in the source directory, the ones not there are listed as missing
+    pub fn new(reader: &DBReader, game_name: &str, options: &BundleOptions) -> Result<Self> {
+        let game = match reader.get_game(game_name) {
+            Some(game) => game,
+            None => return err!(RomstError::GenericError { message: format!("The game `{}` is not in the database", game_name) }),
+        };
+        let source_directory = Path::new(&options.source_directory);
+        let mut plan = Self {
+            game_name: game_name.to_string(),
+            rom_mode: options.rom_mode,
+            source_directory: options.source_directory.to_string(),
+            target_directory: options.target_directory.to_string(),
+            files: vec![],
+            missing: vec![],
+            bytes: 0,
+            bundle: None,
+        };
+
+        // In merged the clones are in the archive of their parent
+        let mut set_name = game.name.to_string();
+        if options.rom_mode == RomsetMode::Merged {
+            let mut seen = HashSet::new();
+            while let Some(parent) = reader.get_game(&set_name).and_then(|game| game.clone_of).filter(|parent| seen.insert(parent.to_string())) {
+                set_name = parent;
+            }
+        }
+        let mut sets = vec![(set_name.to_string(), BundleReason::Set)];
+        if options.rom_mode == RomsetMode::Split {
+            if let Some(parent) = &game.clone_of {
+                sets.push((parent.to_string(), BundleReason::Parent));
+            }
+        }
+        // The bios are the sets the roms come from besides the parent
+        if options.rom_mode != RomsetMode::NonMerged {
+            let mut rom_of = reader.get_game(&set_name).and_then(|game| game.rom_of);
+            while let Some(bios) = rom_of.filter(|bios| !sets.iter().any(|(name, _)| name == bios)) {
+                rom_of = reader.get_game(&bios).and_then(|game| game.rom_of);
+                if game.clone_of.as_ref() != Some(&bios) {
+                    sets.push((bios, BundleReason::Bios));
+                }
+            }
+        }
+        for device in reader.get_devices_for_game(game_name)?.dependencies {
+            sets.push((device, BundleReason::Device));
+        }
+
+        for (set_name, reason) in sets {
+            if reader.get_romset_roms(set_name.as_str(), options.rom_mode)?.is_empty() {
+                continue;
+            }
+            match find_set_file(source_directory, &set_name) {
+                Some(file_name) => plan.add_file(&set_name, reason, source_directory, &file_name)?,
+                None => plan.missing.push(BundleMissing { set_name: set_name.to_string(), reason, path: format!("{}.zip", set_name) }),
+            }
+        }
+
+        // The disks are in the folder named as the set
+        let mut disks = reader.get_romset_disks(game_name, RomsetMode::NonMerged)?;
+        disks.sort_by(|a, b| a.name.cmp(&b.name));
+        for disk in disks.into_iter().filter(|disk| disk.get_dump_status() != DumpStatus::NoDump) {
+            let disk_name = if disk.name.to_lowercase().ends_with(".chd") { disk.name.to_string() } else { format!("{}.chd", disk.name) };
+            let path = format!("{}/{}", set_name, disk_name);
+            if plan.files.iter().any(|file| file.path == set_name) {
+                // The folder of the set was copied whole
+                continue;
+            }
+            if paths::join_key(source_directory, &path).is_file() {
+                plan.add_file(game_name, BundleReason::Disk, source_directory, &path)?;
+            } else {
+                plan.missing.push(BundleMissing { set_name: game_name.to_string(), reason: BundleReason::Disk, path });
+            }
+        }
+
+        let sample_set = game.sample_of.clone().unwrap_or_else(|| game.name.to_string());
+        if !reader.get_samples(&sample_set)?.is_empty() {
+            let samples_directory = options.samples_directory.as_ref().map(PathBuf::from).unwrap_or_else(|| source_directory.join(SAMPLES_FOLDER));
+            match find_set_file(&samples_directory, &sample_set) {
+                Some(file_name) => {
+                    plan.add_file(&sample_set, BundleReason::Samples, &samples_directory, &file_name)?;
+                    if let Some(file) = plan.files.last_mut() {
+                        file.path = format!("{}/{}", SAMPLES_FOLDER, file_name);
+                    }
+                }
+                None => plan.missing.push(BundleMissing { set_name: sample_set.to_string(), reason: BundleReason::Samples, path: format!("{}/{}.zip", SAMPLES_FOLDER, sample_set) }),
+            }
+        }
+
+        Ok(plan)
+    }
+
+    fn add_file(&mut self, set_name: &str, reason: BundleReason, directory: &Path, path: &str) -> Result<()> {
+        let source = paths::join_key(directory, path);
+        let bytes = get_size(&source)?;
+        self.bytes += bytes;
+        self.files.push(BundleFile { set_name: set_name.to_string(), reason, path: path.to_string(), source: source.to_string_lossy().to_string(), bytes });
+        Ok(())
+    }
+
+    /// Copies the files into the target directory, or into a zip in it named after the game
+    pub fn execute(&mut self, zip: bool) -> Result<()> {
+        let target_directory = Path::new(&self.target_directory);
+        fs::create_dir_all(target_directory)?;
+
+        let bundle = if zip {
+            let bundle = target_directory.join(format!("{}-bundle.zip", self.game_name));
+            let tmp_path = get_tmp_path(&bundle);
+            let mut writer = ZipWriter::new(BufWriter::new(File::create(&tmp_path)?));
+            for file in &self.files {
+                info!("Adding {} to {}", file.source, bundle.to_string_lossy());
+                add_to_zip(&mut writer, Path::new(&file.source), &file.path)?;
+            }
+            writer.finish()?;
+            fs::rename(tmp_path, &bundle)?;
+            bundle
+        } else {
+            for file in &self.files {
+                let source = Path::new(&file.source);
+                let target = paths::join_key(target_directory, &file.path);
+                info!("Copying {} to {}", file.source, target.to_string_lossy());
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                if source.is_dir() {
+                    paths::copy_directory(source, &target)?;
+                } else {
+                    fs::copy(source, &target)?;
+                }
+            }
+            target_directory.to_path_buf()
+        };
+
+        self.bundle = Some(bundle.to_string_lossy().to_string());
+        Ok(())
+    }
+}
+
+/// The archive of the set, or its folder if there's no archive
+fn find_set_file(directory: &Path, set_name: &str) -> Option<String> {
+    SET_EXTENSIONS.iter()
+        .map(|extension| format!("{}.{}", set_name, extension))
+        .find(|file_name| paths::join_key(directory, file_name).is_file())
+        .or_else(|| Some(set_name.to_string()).filter(|_| paths::join_key(directory, set_name).is_dir()))
+}
+
+fn get_size(path: &Path) -> io::Result<u64> {
+    if path.is_dir() {
+        let mut size = 0;
+        for entry in fs::read_dir(path)? {
+            size += get_size(&entry?.path())?;
+        }
+        Ok(size)
+    } else {
+        Ok(fs::metadata(path)?.len())
+    }
+}
+
+fn add_to_zip(writer: &mut ZipWriter<BufWriter<File>>, source: &Path, path: &str) -> Result<()> {
+    if source.is_dir() {
+        let mut entries = fs::read_dir(source)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = paths::os_str_to_key(&entry.file_name());
+            add_to_zip(writer, &entry.path(), &format!("{}/{}", path, name))?;
+        }
+    } else {
+        // The archives are already compressed
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored).large_file(fs::metadata(source)?.len() >= u32::MAX as u64);
+        writer.start_file(path, options)?;
+        io::copy(&mut File::open(source)?, writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use zip::ZipArchive;
+
+    use crate::{ImportOptions, Romst, sysout::DatImporterReporterSysOut};
+
+    use super::*;
+
+    fn get_plan(dir: &Path, game_name: &str, rom_mode: RomsetMode) -> Result<BundlePlan> {
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        if !Path::new(&db_file).exists() {
+            Romst::import_dat("testdata/test.dat", &db_file, ImportOptions::default(), None::<DatImporterReporterSysOut>)?;
+        }
+        let reader = Romst::get_data_reader(&db_file)?;
+        let options = BundleOptions {
+            rom_mode,
+            source_directory: Path::new("testdata").join("split").to_string_lossy().to_string(),
+            target_directory: dir.join("bundle").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        BundlePlan::new(&reader, game_name, &options)
+    }
+
+    #[test]
+    fn gathers_the_set_with_its_dependencies() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_bundle_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let plan = get_plan(&dir, "game1a", RomsetMode::Split)?;
+        let found = plan.files.iter().map(|file| (file.path.as_str(), file.reason)).collect::<Vec<_>>();
+        assert_eq!(vec![("game1a.zip", BundleReason::Set), ("game1.zip", BundleReason::Parent), ("device1.zip", BundleReason::Device)], found);
+        assert!(plan.missing.is_empty());
+
+        let mut plan = get_plan(&dir, "game5", RomsetMode::Split)?;
+        assert_eq!(vec!["game5.zip", "game5/gm5-001.chd"], plan.files.iter().map(|file| file.path.as_str()).collect::<Vec<_>>());
+        plan.execute(true)?;
+        let bundle = ZipArchive::new(File::open(dir.join("bundle").join("game5-bundle.zip"))?);
+        let names = bundle.map(|bundle| bundle.file_names().map(|name| name.to_string()).collect::<HashSet<_>>());
+
+        fs::remove_dir_all(&dir)?;
+        assert_eq!(vec!["game5.zip", "game5/gm5-001.chd"].into_iter().map(|name| name.to_string()).collect::<HashSet<_>>(), names?);
+        Ok(())
+    }
+}
@@ -0,0 +1,509 @@
+use std::{collections::HashSet, fmt::Display, fs::{self, File, OpenOptions}, io::{BufReader, BufWriter}, path::{Path, PathBuf}};
+
+use anyhow::Result;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use zip::{ZipArchive, ZipWriter};
+
+use crate::{err, error::RomstError, filesystem::paths};
+
+use super::{models::{self, archive::ArchiveFormat}, reporter::scan_report::{FileKind, RomLocatedAt, ScanReport, SetReference}};
+
+/// Written in the target directory after a clean, it's used to undo it
+pub const CLEAN_MANIFEST_FILE: &str = "romst_clean_manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CleanAction {
+    /// The whole file is moved to the target directory
+    MoveFile(String),
+    /// The entries are removed from the archive, and stored in an archive with the same name in the target directory
+    RemoveEntries(String, Vec<String>),
+}
+
+impl CleanAction {
+    pub fn get_file(&self) -> &str {
+        match self {
+            CleanAction::MoveFile(file) => file,
+            CleanAction::RemoveEntries(file, _entries) => file,
+        }
+    }
+}
+
+impl Display for CleanAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CleanAction::MoveFile(file) => writeln!(f, "- Move {}", file),
+            CleanAction::RemoveEntries(file, entries) => {
+                writeln!(f, "- Remove from {}:", file)?;
+                for entry in entries {
+                    writeln!(f, "   - {}", entry)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The files to quarantine after a scan, spare roms and unknown files are moved out of the collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanPlan {
+    source_directory: String,
+    target_directory: String,
+    pub actions: Vec<CleanAction>,
+}
+
+impl Display for CleanPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Source dir: {}", self.source_directory)?;
+        writeln!(f, "Target dir: {}", self.target_directory)?;
+        if self.actions.is_empty() {
+            writeln!(f, "Nothing to clean")?;
+        }
+        for action in &self.actions {
+            write!(f, "{}", action)?;
+        }
+        Ok(())
+    }
+}
+
+impl CleanPlan {
+    /// Builds the plan from a scan report. If no source directory is given, uses the directory from the report.
+    /// Spare roms that are needed to fix another set are left where they are.
+    pub fn from_report<S>(report: &ScanReport, source_directory: Option<S>, target_directory: S) -> Result<Self> where S: AsRef<str> {
+        let source_directory = match source_directory {
+            Some(source_directory) => paths::normalize_directory(source_directory.as_ref()),
+            None => match report.get_root_directory() {
+                Some(root_directory) => paths::normalize_directory(root_directory),
+                None => return err!("The report doesn't have a scanned directory, a source directory is needed"),
+            }
+        };
+
+        let mut needed = HashSet::new();
+        for set in report.sets.values() {
+            for location in set.roms_available.values() {
+                if let RomLocatedAt::InOthers(locations) = location {
+                    for location in locations {
+                        needed.insert((location.get_file(), location.get_with_name()));
+                    }
+                }
+            }
+        }
+
+        if report.files.is_empty() && report.sets.values().any(|set| !set.roms_to_spare.is_empty() || !set.unknown.is_empty()) {
+            return err!(RomstError::GenericError { message: "The report was written before the files scanned were recorded, check the collection again to clean it".to_string() });
+        }
+
+        // Every file is cleaned on its own, a set may be in several files, like `game1.zip` and `game1.7z`
+        let mut actions = vec![];
+        for (file, scanned_file) in &report.files {
+            let set = match report.sets.get(&models::get_set_from_file(file)) {
+                Some(set) => set,
+                None => continue,
+            };
+
+            let entries = scanned_file.spare.iter()
+                .filter(|entry| !needed.contains(&(file.as_str(), entry.as_str())))
+                .cloned()
+                .collect::<Vec<_>>();
+            if entries.is_empty() {
+                continue;
+            }
+
+            let all_entries = scanned_file.entries == entries.len() && matches!(set.reference, SetReference::FileName(_));
+            match scanned_file.kind {
+                FileKind::Archive | FileKind::Folder if all_entries => actions.push(CleanAction::MoveFile(file.to_string())),
+                FileKind::Folder => {
+                    actions.extend(entries.iter().map(|entry| CleanAction::MoveFile(format!("{}/{}", file, entry))));
+                }
+                FileKind::Loose => actions.extend(entries.into_iter().map(CleanAction::MoveFile)),
+                FileKind::Archive => match report.archives.get(file) {
+                    Some(archive_info) if archive_info.format == ArchiveFormat::Rar => {
+                        warn!("The entries of the rar archive {} can't be removed, it's left as it is", file);
+                    }
+                    _ => actions.push(CleanAction::RemoveEntries(file.to_string(), entries)),
+                },
+            }
+        }
+
+        // Ignored files may be stored with the path they were found at, they are moved keeping their path in the scanned directory
+        for file in &report.ignored {
+            let path = Path::new(file);
+            let relative_path = if path.is_absolute() {
+                match paths::normalize_path(path).strip_prefix(&source_directory) {
+                    Ok(relative_path) => relative_path.to_string_lossy().to_string(),
+                    Err(_) => {
+                        warn!("The ignored file {} is not in {}, it's left where it is", file, source_directory);
+                        continue;
+                    }
+                }
+            } else {
+                file.to_string()
+            };
+            actions.push(CleanAction::MoveFile(relative_path));
+        }
+
+        Ok(Self { source_directory, target_directory: target_directory.as_ref().to_string(), actions })
+    }
+
+    /// Moves the files to the target directory, and writes the manifest to undo it after each file, so if an action
+    /// fails or the run is stopped, the manifest contains the actions done so far.
+    pub fn execute(&self) -> Result<()> {
+        let target_directory = Path::new(&self.target_directory);
+        let manifest_path = target_directory.join(CLEAN_MANIFEST_FILE);
+        if manifest_path.exists() {
+            return err!(RomstError::GenericError { message: format!("There is a previous clean in `{}`, undo it or remove the manifest first", self.target_directory) });
+        }
+        fs::create_dir_all(target_directory)?;
+
+        let mut done = CleanPlan { source_directory: self.source_directory.clone(), target_directory: self.target_directory.clone(), actions: vec![] };
+        for action in &self.actions {
+            self.execute_action(action)?;
+            done.actions.push(action.clone());
+            write_manifest(&manifest_path, &done)?;
+        }
+
+        Ok(())
+    }
+
+    fn execute_action(&self, action: &CleanAction) -> Result<()> {
+        let source = paths::join_key(&self.source_directory, action.get_file());
+        let target = paths::join_key(&self.target_directory, action.get_file());
+        if target.exists() {
+            return err!(RomstError::GenericError { message: format!("The file `{}` already exists", target.to_string_lossy()) });
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        match action {
+            CleanAction::MoveFile(_file) => move_file(&source, &target),
+            CleanAction::RemoveEntries(_file, entries) => {
+                info!("Removing {} entries from {}", entries.len(), source.to_string_lossy());
+                let tmp_path = get_tmp_path(&source);
+                let result = split_entries(&source, &tmp_path, &target, entries);
+                if result.is_err() {
+                    // The archive is left untouched, so nothing of the split is kept
+                    for partial in [&tmp_path, &target] {
+                        if partial.exists() {
+                            if let Err(remove_error) = fs::remove_file(partial) {
+                                error!("Error removing the partial file `{}`: {}", partial.to_string_lossy(), remove_error);
+                            }
+                        }
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    /// Restores the files moved by a previous clean, reading the manifest in the target directory
+    pub fn undo<S>(target_directory: S) -> Result<Self> where S: AsRef<str> {
+        let manifest_path = Path::new(target_directory.as_ref()).join(CLEAN_MANIFEST_FILE);
+        if !manifest_path.exists() {
+            return err!(RomstError::GenericError { message: format!("No clean manifest found in `{}`", target_directory.as_ref()) });
+        }
+        let manifest: CleanPlan = serde_json::from_reader(BufReader::new(File::open(&manifest_path)?))?;
+
+        // The manifest keeps only the actions left to undo, so an undo failing midway can be run again
+        let mut remaining = manifest.clone();
+        while let Some(action) = remaining.actions.pop() {
+            manifest.undo_action(&action)?;
+            if !remaining.actions.is_empty() {
+                write_manifest(&manifest_path, &remaining)?;
+            }
+        }
+        fs::remove_file(manifest_path)?;
+
+        Ok(manifest)
+    }
+
+    fn undo_action(&self, action: &CleanAction) -> Result<()> {
+        let source = paths::join_key(&self.source_directory, action.get_file());
+        let target = paths::join_key(&self.target_directory, action.get_file());
+        match action {
+            CleanAction::MoveFile(_file) => {
+                if source.exists() {
+                    return err!(RomstError::GenericError { message: format!("The file `{}` already exists", source.to_string_lossy()) });
+                }
+                if let Some(parent) = source.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                move_file(&target, &source)
+            }
+            CleanAction::RemoveEntries(file, _entries) => {
+                let mut removed = ZipArchive::new(BufReader::new(File::open(&target)?))?;
+                let existing = ZipArchive::new(BufReader::new(File::open(&source)?))?
+                    .file_names()
+                    .map(|name| name.to_string())
+                    .collect::<HashSet<_>>();
+                if let Some(name) = removed.file_names().find(|name| existing.contains(*name)) {
+                    return err!(RomstError::GenericError { message: format!("The entry `{}` already exists in `{}`", name, file) });
+                }
+
+                let archive_file = OpenOptions::new().read(true).write(true).open(&source)?;
+                let mut archive = ZipWriter::new_append(archive_file)?;
+                for i in 0..removed.len() {
+                    archive.raw_copy_file(removed.by_index_raw(i)?)?;
+                }
+                archive.finish()?;
+                fs::remove_file(target)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Writes the manifest to a temporary file first, so a run stopped while writing it keeps the previous one
+fn write_manifest(manifest_path: &Path, plan: &CleanPlan) -> Result<()> {
+    let tmp_path = get_tmp_path(manifest_path);
+    serde_json::to_writer_pretty(BufWriter::new(File::create(&tmp_path)?), plan)?;
+    fs::rename(tmp_path, manifest_path)?;
+    Ok(())
+}
+
+/// Copies the entries of the archive to the temporary file, except the ones removed that go to the target, and then
+/// replaces the archive with the temporary file
+fn split_entries(source: &Path, tmp_path: &Path, target: &Path, entries: &[String]) -> Result<()> {
+    let mut archive = ZipArchive::new(BufReader::new(File::open(source)?))?;
+    let mut kept = ZipWriter::new(BufWriter::new(File::create(tmp_path)?));
+    let mut removed = ZipWriter::new(BufWriter::new(File::create(target)?));
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if entries.iter().any(|name| name == entry.name()) {
+            removed.raw_copy_file(entry)?;
+        } else {
+            kept.raw_copy_file(entry)?;
+        }
+    }
+    kept.finish()?;
+    removed.finish()?;
+    fs::rename(tmp_path, source)?;
+    Ok(())
+}
+
+pub(super) fn get_tmp_path(file: &Path) -> PathBuf {
+    let mut tmp_file = file.as_os_str().to_owned();
+    tmp_file.push(".tmp");
+    PathBuf::from(tmp_file)
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    info!("Moving {} to {}", from.to_string_lossy(), to.to_string_lossy());
+    if fs::rename(from, to).is_err() {
+        // Renaming doesn't work across filesystems
+        warn!("Can't rename {}, copying it instead", from.to_string_lossy());
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::{RomsetMode, data::{models::{archive::{ArchiveFormat, ArchiveInfo}, file::{DataFile, DataFileInfo, FileType}}, reporter::scan_report::{FileKind, RomLocation}}};
+
+    use super::*;
+
+    fn rom(name: &str) -> DataFile {
+        DataFile::new(name, DataFileInfo::new(FileType::Rom))
+    }
+
+    fn archive(entries: usize) -> ArchiveInfo {
+        ArchiveInfo::new(ArchiveFormat::NonCanonical, entries, 0, String::new())
+    }
+
+    fn get_report(root_directory: &str) -> ScanReport {
+        let mut report = ScanReport::new(Some(root_directory.to_string()), RomsetMode::Split);
+        report.add_ignored(format!("{}/info.txt", root_directory));
+        // The reports read from a file may have the paths the files were found at
+        report.ignored.push(format!("{}/docs/manual.txt", root_directory));
+        report.ignored.push("/elsewhere/notes.txt".to_string());
+
+        report.set_in_file("game3.zip");
+        report.add_archive_info("game3.zip", archive(4));
+        report.add_scanned_file("game3.zip", FileKind::Archive, 4);
+        report.add_unknown_files(vec![ rom("leftover.rom") ], "game3.zip");
+
+        report.set_in_file("game2.zip");
+        report.add_archive_info("game2.zip", archive(3));
+        report.add_scanned_file("game2.zip", FileKind::Archive, 3);
+        report.add_roms_to_spare(vec![ rom("binary1.bin"), rom("binary2.bin") ], "game2.zip");
+        // binary1.bin is used to fix game1, so it stays
+        report.add_rom_for_set("game1", RomLocation::new("game2.zip", "binary1.bin"), rom("binary1.bin"));
+
+        report
+    }
+
+    fn copy_test_dir(name: &str) -> Result<PathBuf> {
+        let dir = env::temp_dir().join(format!("romst_clean_{}_{}", name, std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        let source = dir.join("source");
+        fs::create_dir_all(&source)?;
+        for entry in fs::read_dir(Path::new("testdata").join("wrong"))? {
+            let entry = entry?;
+            fs::copy(entry.path(), source.join(entry.file_name()))?;
+        }
+        fs::create_dir_all(source.join("docs"))?;
+        fs::write(source.join("docs").join("manual.txt"), "manual")?;
+        Ok(dir)
+    }
+
+    fn entries(file: &Path) -> Result<Vec<String>> {
+        let archive = ZipArchive::new(File::open(file)?)?;
+        let mut names = archive.file_names().map(|name| name.to_string()).collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    #[test]
+    fn plan_skips_needed_spare_roms() -> Result<()> {
+        let report = get_report("/roms");
+        let plan = CleanPlan::from_report(&report, None, "/spare")?;
+
+        assert_eq!(plan.source_directory, "/roms");
+        assert_eq!(plan.actions, vec![
+            CleanAction::RemoveEntries("game2.zip".to_string(), vec![ "binary2.bin".to_string() ]),
+            CleanAction::RemoveEntries("game3.zip".to_string(), vec![ "leftover.rom".to_string() ]),
+            CleanAction::MoveFile("info.txt".to_string()),
+            CleanAction::MoveFile("docs/manual.txt".to_string()),
+        ]);
+
+        let no_root = ScanReport::new(None, RomsetMode::Split);
+        assert!(CleanPlan::from_report(&no_root, None, "/spare").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn plan_cleans_every_file_of_a_set() -> Result<()> {
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::Split);
+        for (file, kind, entries) in [("game1.zip", FileKind::Archive, 2), ("game1", FileKind::Folder, 2), ("game1.rar", FileKind::Archive, 2)] {
+            report.set_in_file(file);
+            report.add_scanned_file(file, kind, entries);
+        }
+        report.add_archive_info("game1.rar", ArchiveInfo::new(ArchiveFormat::Rar, 2, 0, String::new()));
+        report.add_roms_to_spare(vec![ rom("zipped.bin") ], "game1.zip");
+        report.add_unknown_files(vec![ rom("extra.bin") ], "game1");
+        report.add_unknown_files(vec![ rom("unknown.bin") ], "game1.rar");
+        report.add_scanned_file("loose", FileKind::Loose, 2);
+        report.add_unknown_files(vec![ rom("loose.bin") ], "loose");
+
+        let plan = CleanPlan::from_report(&report, None, "/spare")?;
+        assert_eq!(plan.actions, vec![
+            // The entries of the rar can't be removed, so it's left as it is
+            CleanAction::MoveFile("game1/extra.bin".to_string()),
+            CleanAction::RemoveEntries("game1.zip".to_string(), vec![ "zipped.bin".to_string() ]),
+            CleanAction::MoveFile("loose.bin".to_string()),
+        ]);
+
+        // Without the files scanned, the spare roms can't be told apart
+        report.files.clear();
+        assert!(CleanPlan::from_report(&report, None, "/spare").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn clean_and_undo() -> Result<()> {
+        let dir = copy_test_dir("undo")?;
+        let source = dir.join("source");
+        let target = dir.join("spare");
+        let report = get_report(&source.to_string_lossy());
+        let plan = CleanPlan::from_report(&report, None, target.to_string_lossy().to_string())?;
+
+        plan.execute()?;
+        assert!(!source.join("info.txt").exists());
+        assert!(target.join("info.txt").exists());
+        assert!(target.join("docs").join("manual.txt").exists());
+        assert!(target.join(CLEAN_MANIFEST_FILE).exists());
+        assert_eq!(entries(&source.join("game3.zip"))?, vec![ "file1.rom", "file2.rom", "file3.rom" ]);
+        assert_eq!(entries(&target.join("game3.zip"))?, vec![ "leftover.rom" ]);
+        assert_eq!(entries(&source.join("game2.zip"))?, vec![ "binary1.bin", "wrongname.ban" ]);
+
+        // A second clean needs the first one to be undone
+        assert!(plan.execute().is_err());
+
+        CleanPlan::undo(target.to_string_lossy())?;
+        assert!(source.join("info.txt").exists());
+        assert!(source.join("docs").join("manual.txt").exists());
+        assert!(!target.join(CLEAN_MANIFEST_FILE).exists());
+        assert_eq!(entries(&source.join("game3.zip"))?, vec![ "file1.rom", "file2.rom", "file3.rom", "leftover.rom" ]);
+        assert_eq!(entries(&source.join("game2.zip"))?, vec![ "binary1.bin", "binary2.bin", "wrongname.ban" ]);
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn undo_failing_midway_can_be_run_again() -> Result<()> {
+        let dir = copy_test_dir("undo_again")?;
+        let source = dir.join("source");
+        let target = dir.join("spare");
+        let report = get_report(&source.to_string_lossy());
+        CleanPlan::from_report(&report, None, target.to_string_lossy().to_string())?.execute()?;
+
+        // The manual is restored before info.txt fails
+        fs::write(source.join("info.txt"), "new info")?;
+        assert!(CleanPlan::undo(target.to_string_lossy()).is_err());
+        assert!(source.join("docs").join("manual.txt").exists());
+
+        fs::remove_file(source.join("info.txt"))?;
+        CleanPlan::undo(target.to_string_lossy())?;
+        assert!(source.join("info.txt").exists());
+        assert_eq!(entries(&source.join("game2.zip"))?, vec![ "binary1.bin", "binary2.bin", "wrongname.ban" ]);
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_keeps_the_actions_done_before_a_failure() -> Result<()> {
+        let dir = copy_test_dir("stopped")?;
+        let source = dir.join("source");
+        let target = dir.join("spare");
+        let plan = CleanPlan {
+            source_directory: source.to_string_lossy().to_string(),
+            target_directory: target.to_string_lossy().to_string(),
+            actions: vec![ CleanAction::MoveFile("docs/manual.txt".to_string()), CleanAction::MoveFile("missing.txt".to_string()) ],
+        };
+        assert!(plan.execute().is_err());
+        assert!(target.join("docs").join("manual.txt").exists());
+
+        let undone = CleanPlan::undo(target.to_string_lossy())?;
+        assert_eq!(undone.actions, vec![ CleanAction::MoveFile("docs/manual.txt".to_string()) ]);
+        assert!(source.join("docs").join("manual.txt").exists());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn failed_removal_leaves_no_partial_files() -> Result<()> {
+        let dir = copy_test_dir("partial")?;
+        let source = dir.join("source");
+        let target = dir.join("spare");
+        // The central directory is still read, but not the header of the first entry
+        let archive_path = source.join("game3.zip");
+        let mut bytes = fs::read(&archive_path)?;
+        bytes[0] = 0;
+        fs::write(&archive_path, bytes)?;
+
+        let plan = CleanPlan {
+            source_directory: source.to_string_lossy().to_string(),
+            target_directory: target.to_string_lossy().to_string(),
+            actions: vec![ CleanAction::RemoveEntries("game3.zip".to_string(), vec![ "leftover.rom".to_string() ]) ],
+        };
+        assert!(plan.execute().is_err());
+        assert!(!target.join("game3.zip").exists());
+        assert!(!get_tmp_path(&archive_path).exists());
+        assert!(!target.join(CLEAN_MANIFEST_FILE).exists());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+//! The connections to the database files. The databases are written in WAL mode, so a set can be checked while the
+//! database is being written, and the connections wait for a while when it's locked before failing as busy.
+use std::{path::Path, time::Duration};
+
+use anyhow::Result;
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+
+use crate::error::RomstError;
+
+/// How long a connection waits for a lock held by another connection before failing as busy
+pub const DB_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens the database to write in it, creating it if it doesn't exist, in WAL mode
+pub fn open_read_write(db_path: impl AsRef<Path>) -> Result<Connection> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+    conn.busy_timeout(DB_BUSY_TIMEOUT)?;
+    // The journal mode is stored in the file, it's only changed the first time
+    conn.query_row("PRAGMA journal_mode = WAL;", [], |row| row.get::<_, String>(0))?;
+    Ok(conn)
+}
+
+pub fn open_read_only(db_path: impl AsRef<Path>) -> Result<Connection> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    conn.busy_timeout(DB_BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+/// Replaces the error with `RomstError::DatabaseBusy` if it comes from the database being locked by another connection
+pub fn retriable_if_busy(error: anyhow::Error, db_file: &str) -> anyhow::Error {
+    let busy = error.chain().any(|cause| matches!(cause.downcast_ref::<rusqlite::Error>(),
+        Some(rusqlite::Error::SqliteFailure(failure, _)) if failure.code == ErrorCode::DatabaseBusy || failure.code == ErrorCode::DatabaseLocked));
+    if busy {
+        RomstError::DatabaseBusy { path: db_file.to_string() }.into()
+    } else {
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+    use super::*;
+
+    #[test]
+    fn reads_while_writing_and_reports_busy_writes() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_connection_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst");
+
+        let writer = open_read_write(&db_file)?;
+        let journal_mode: String = writer.query_row("PRAGMA journal_mode;", [], |row| row.get(0))?;
+        assert_eq!("wal", journal_mode);
+        writer.execute_batch("CREATE TABLE roms (name TEXT); INSERT INTO roms VALUES ('rom1');")?;
+
+        writer.execute_batch("BEGIN IMMEDIATE; INSERT INTO roms VALUES ('rom2');")?;
+        let reader = open_read_only(&db_file)?;
+        let roms: u32 = reader.query_row("SELECT COUNT(*) FROM roms;", [], |row| row.get(0))?;
+        assert_eq!(1, roms);
+
+        let other_writer = open_read_write(&db_file)?;
+        other_writer.busy_timeout(Duration::from_millis(10))?;
+        let error = other_writer.execute_batch("INSERT INTO roms VALUES ('rom3');").map_err(anyhow::Error::from).unwrap_err();
+        let error = retriable_if_busy(error, "test.rst");
+        assert!(matches!(error.downcast_ref::<RomstError>(), Some(busy) if busy.is_retriable()));
+
+        writer.execute_batch("COMMIT;")?;
+        other_writer.execute_batch("INSERT INTO roms VALUES ('rom3');")?;
+        let roms: u32 = reader.query_row("SELECT COUNT(*) FROM roms;", [], |row| row.get(0))?;
+        assert_eq!(3, roms);
+
+        drop((writer, other_writer, reader));
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
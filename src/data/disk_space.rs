@@ -0,0 +1,81 @@
+//! The disk space a fix or a rebuild needs, checked against the free space of the disk before writing anything,
+//! so a run doesn't stop halfway with the disk full.
+use std::{fmt::Display, path::Path};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{data::units::ByteSize, err, error::RomstError, filesystem::paths};
+
+/// The sizes are of the roms uncompressed, the archives written keep the compression of the sources, so they are
+/// the most the run can use
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpaceEstimate {
+    /// The bytes written by all the operations, an archive written several times counts every time
+    pub bytes_written: u64,
+    /// The most the used space grows during the run
+    pub bytes_needed: u64,
+    /// The free space of the disk written to, unknown if it couldn't be read
+    pub bytes_available: Option<u64>,
+}
+
+impl Display for SpaceEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Bytes to write: at most {}", ByteSize(self.bytes_written))?;
+        match self.bytes_available {
+            Some(available) => writeln!(f, "Space needed: at most {}, {} free", ByteSize(self.bytes_needed), ByteSize(available)),
+            None => writeln!(f, "Space needed: at most {}, the free space is unknown", ByteSize(self.bytes_needed)),
+        }
+    }
+}
+
+impl SpaceEstimate {
+    pub fn new(bytes_written: u64, bytes_needed: u64) -> Self {
+        Self { bytes_written, bytes_needed, bytes_available: None }
+    }
+
+    /// Reads the free space of the disk of the directory, or of its closest parent if it doesn't exist yet
+    pub fn read_available(&mut self, directory: impl AsRef<Path>) {
+        self.bytes_available = paths::get_available_space(directory);
+    }
+
+    /// If the free space is unknown it's taken as enough
+    pub fn is_enough(&self) -> bool {
+        self.bytes_available.map(|available| available >= self.bytes_needed).unwrap_or(true)
+    }
+
+    /// Fails if the run may not fit in the free space
+    pub fn check(&self, directory: &str) -> Result<()> {
+        match self.bytes_available {
+            Some(available) if !self.is_enough() => err!(RomstError::GenericError {
+                message: format!("Not enough space in `{}`, it needs up to {} bytes and there are {} free. The roms are counted uncompressed, \
+                    skip the check if the archives are compressed enough to fit", directory, self.bytes_needed, available)
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fails_only_when_the_space_is_known_and_not_enough() {
+        let mut estimate = SpaceEstimate::new(300, 200);
+        assert!(estimate.check("/roms").is_ok());
+
+        estimate.bytes_available = Some(200);
+        assert!(estimate.check("/roms").is_ok());
+
+        estimate.bytes_available = Some(199);
+        assert!(estimate.check("/roms").is_err());
+    }
+
+    #[test]
+    fn reads_the_space_of_a_directory_not_created_yet() {
+        let mut estimate = SpaceEstimate::new(0, 0);
+        estimate.read_available(std::env::temp_dir().join("romst_not_created").join("target"));
+        assert_eq!(cfg!(target_os = "linux"), estimate.bytes_available.is_some());
+    }
+}
@@ -0,0 +1,273 @@
+use std::{collections::HashSet, fmt::Display, fs::{self, File}, io::{BufWriter, Write}, path::{Path, PathBuf}};
+
+use anyhow::Result;
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
+
+use crate::{RomsetMode, err, error::RomstError};
+
+use super::{models::{dat_info::{DatInfo, PACKING_KEY}, game::Game}, reader::{DataReader, sqlite::{BrowseGrouping, DBReader}}};
+
+/// The options of the DAT written as attributes of the `clrmamepro` element instead of header elements
+const CLRMAMEPRO_KEYS: [&str; 4] = [PACKING_KEY, "forcemerging", "forcenodump", "header"];
+/// The file of the sets without a value for the grouping
+const UNKNOWN_GROUP: &str = "unknown";
+
+/// A DAT file written by the export, with the number of sets in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedDat {
+    pub file: String,
+    pub sets: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DatExportReport {
+    pub files: Vec<ExportedDat>,
+}
+
+impl Display for DatExportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "DATs written: {}", self.files.len())?;
+        for exported in &self.files {
+            writeln!(f, "- {}: {} sets", exported.file, exported.sets)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the database as a DAT in the Logiqx XML format. Split by a grouping, the output is a directory with a DAT
+/// for each group, named after it. Split by letter, a clone can end in a different DAT than its parent
+pub fn export_dat(reader: &DBReader, output: &impl AsRef<Path>, split_by: Option<BrowseGrouping>) -> Result<DatExportReport> {
+    let output = output.as_ref();
+    let dat_info = reader.get_dat_info()?;
+    let mut report = DatExportReport::default();
+
+    match split_by {
+        None => {
+            let sets = reader.get_game_list(RomsetMode::NonMerged)?.into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+            write_dat(reader, output, &dat_info, None, &sets)?;
+            report.files.push(ExportedDat { file: output.to_string_lossy().to_string(), sets: sets.len() as u32 });
+        }
+        Some(grouping) => {
+            if output.is_file() {
+                return err!(RomstError::GenericError { message: format!("`{}` is a file, the split DATs are written to a directory", output.to_string_lossy()) });
+            }
+            fs::create_dir_all(output)?;
+            let mut file_names = HashSet::new();
+            for group in reader.get_sets_grouped_by(grouping, RomsetMode::NonMerged)? {
+                let sets = reader.get_sets_in_group(grouping, group.name.as_deref(), RomsetMode::NonMerged)?.into_iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>();
+                let file = output.join(get_unique_file_name(group.name.as_deref(), &mut file_names));
+                write_dat(reader, &file, &dat_info, Some(group.name.as_deref().unwrap_or(UNKNOWN_GROUP)), &sets)?;
+                report.files.push(ExportedDat { file: file.to_string_lossy().to_string(), sets: sets.len() as u32 });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// The group as a file name, the directories of a driver source file are part of the name so drivers with the same
+/// name don't end in the same file
+fn get_unique_file_name(group: Option<&str>, file_names: &mut HashSet<String>) -> String {
+    let name = match group {
+        Some(group) => {
+            let without_extension = PathBuf::from(group).with_extension("");
+            without_extension.to_string_lossy().chars()
+                .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+                .collect::<String>()
+                .trim_matches(['_', '.'])
+                .to_string()
+        }
+        None => UNKNOWN_GROUP.to_string(),
+    };
+    let name = if name.is_empty() { UNKNOWN_GROUP.to_string() } else { name };
+
+    let mut unique = name.to_owned();
+    let mut count = 1;
+    while !file_names.insert(unique.to_lowercase()) {
+        count += 1;
+        unique = format!("{}_{}", name, count);
+    }
+    format!("{}.dat", unique)
+}
+
+fn write_dat(reader: &DBReader, file: &Path, dat_info: &DatInfo, group: Option<&str>, sets: &[String]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(file)?);
+    writeln!(writer, "<?xml version=\"1.0\"?>")?;
+    writeln!(writer, "<datafile>")?;
+    write_header(&mut writer, dat_info, group)?;
+    for set_name in sets {
+        match reader.get_game(set_name.as_str()) {
+            Some(game) => write_game(&mut writer, reader, &game)?,
+            None => return err!(RomstError::GenericError { message: format!("Game {} not found", set_name) }),
+        }
+    }
+    writeln!(writer, "</datafile>")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_header(writer: &mut impl Write, dat_info: &DatInfo, group: Option<&str>) -> Result<()> {
+    let description = match group {
+        Some(group) => format!("{} ({})", dat_info.description, group),
+        None => dat_info.description.to_owned(),
+    };
+    writeln!(writer, "\t<header>")?;
+    writeln!(writer, "\t\t<name>{}</name>", escape_text(&dat_info.name))?;
+    writeln!(writer, "\t\t<description>{}</description>", escape_text(&description))?;
+    writeln!(writer, "\t\t<version>{}</version>", escape_text(&dat_info.version))?;
+    let (options, elements): (Vec<_>, Vec<_>) = dat_info.extra_data.iter()
+        .partition(|(key, _)| CLRMAMEPRO_KEYS.contains(&key.as_str()));
+    for (key, value) in elements {
+        writeln!(writer, "\t\t<{0}>{1}</{0}>", key, escape_text(value))?;
+    }
+    if !options.is_empty() {
+        write!(writer, "\t\t<clrmamepro")?;
+        for (key, value) in options {
+            write!(writer, " {}=\"{}\"", key, escape_text(value))?;
+        }
+        writeln!(writer, "/>")?;
+    }
+    writeln!(writer, "\t</header>")?;
+
+    Ok(())
+}
+
+fn write_game(writer: &mut impl Write, reader: &DBReader, game: &Game) -> Result<()> {
+    write!(writer, "\t<machine name=\"{}\"", escape_text(&game.name))?;
+    let attributes = [("sourcefile", &game.source_file), ("cloneof", &game.clone_of), ("romof", &game.rom_of), ("sampleof", &game.sample_of)];
+    for (attribute, value) in attributes {
+        if let Some(value) = value {
+            write!(writer, " {}=\"{}\"", attribute, escape_text(value))?;
+        }
+    }
+    writeln!(writer, ">")?;
+
+    let elements = [("description", &game.info_description), ("year", &game.info_year), ("manufacturer", &game.info_manufacturer)];
+    for (element, value) in elements {
+        if let Some(value) = value {
+            writeln!(writer, "\t\t<{0}>{1}</{0}>", element, escape_text(value))?;
+        }
+    }
+
+    let mut roms = reader.get_romset_roms(game.name.as_str(), RomsetMode::NonMerged)?.into_iter().map(|rom| rom.file).collect::<Vec<_>>();
+    roms.sort();
+    for rom in roms {
+        write!(writer, "\t\t<rom name=\"{}\"", escape_text(&rom.name))?;
+        if let Some(size) = rom.info.size {
+            write!(writer, " size=\"{}\"", size)?;
+        }
+        let attributes = [("crc", &rom.info.crc), ("md5", &rom.info.md5), ("sha1", &rom.info.sha1), ("status", &rom.status), ("bios", &rom.bios)];
+        for (attribute, value) in attributes {
+            if let Some(value) = value {
+                write!(writer, " {}=\"{}\"", attribute, escape_text(value))?;
+            }
+        }
+        for (attribute, value) in rom.extra_attrs.iter().flatten() {
+            write!(writer, " {}=\"{}\"", attribute, escape_text(value))?;
+        }
+        writeln!(writer, "/>")?;
+    }
+
+    let mut disks = reader.get_romset_disks(game.name.as_str(), RomsetMode::NonMerged)?;
+    disks.sort();
+    for disk in disks {
+        write!(writer, "\t\t<disk name=\"{}\"", escape_text(&disk.name))?;
+        let attributes = [("sha1", &disk.info.sha1), ("status", &disk.status)];
+        for (attribute, value) in attributes {
+            if let Some(value) = value {
+                write!(writer, " {}=\"{}\"", attribute, escape_text(value))?;
+            }
+        }
+        writeln!(writer, "/>")?;
+    }
+
+    if let Some(sample_of) = &game.sample_of {
+        for sample in reader.get_samples(sample_of)? {
+            writeln!(writer, "\t\t<sample name=\"{}\"/>", escape_text(&sample))?;
+        }
+    }
+    for device_ref in reader.get_device_refs(&game.name)? {
+        writeln!(writer, "\t\t<device_ref name=\"{}\"/>", escape_text(&device_ref))?;
+    }
+    writeln!(writer, "\t</machine>")?;
+
+    Ok(())
+}
+
+fn escape_text(value: &str) -> String {
+    String::from_utf8_lossy(&escape(value.as_bytes())).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::BufReader};
+    use rusqlite::{Connection, OpenFlags};
+    use crate::data::{importer::DatImporter, writer::sqlite::DBWriter};
+    use super::*;
+
+    fn import(dat_path: &impl AsRef<Path>) -> Result<DBReader> {
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        let writer = DBWriter::from_connection(&mut conn, 5);
+        let mut importer = DatImporter::<BufReader<File>, DBWriter>::from_path(dat_path, writer)?;
+        importer.load_dat()?;
+        Ok(DBReader::from_connection(conn))
+    }
+
+    #[test]
+    fn exports_a_dat_that_imports_back_the_same() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_export_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let reader = import(&Path::new("testdata").join("test.dat"))?;
+        let output = dir.join("export.dat");
+
+        let report = export_dat(&reader, &output, None)?;
+        assert_eq!(reader.get_db_info()?.games, report.files[0].sets);
+
+        let exported = import(&output)?;
+        assert_eq!(reader.get_dat_info()?.name, exported.get_dat_info()?.name);
+        assert_eq!(reader.get_packing()?, exported.get_packing()?);
+        for (set_name, _) in reader.get_game_list(RomsetMode::NonMerged)? {
+            let mut original = reader.get_game_set(set_name.as_str(), RomsetMode::NonMerged)?;
+            let mut imported = exported.get_game_set(set_name.as_str(), RomsetMode::NonMerged)?;
+            original.roms.sort();
+            imported.roms.sort();
+            assert_eq!(original.game, imported.game);
+            assert_eq!(original.roms, imported.roms);
+            assert_eq!(reader.get_device_refs(&set_name)?, exported.get_device_refs(&set_name)?);
+        }
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn splits_the_dat_by_driver() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_export_split_{}", std::process::id()));
+        let reader = import(&Path::new("testdata").join("test.dat"))?;
+
+        let report = export_dat(&reader, &dir, Some(BrowseGrouping::Driver))?;
+
+        let games = report.files.iter().map(|exported| exported.sets).sum::<u32>();
+        assert_eq!(reader.get_db_info()?.games, games);
+        let gamelib = report.files.iter().find(|exported| exported.file.ends_with("gamelib.dat")).unwrap();
+        assert_eq!(3, gamelib.sets);
+        assert_eq!(vec!["game0", "game1", "game1a"], import(&gamelib.file)?.get_game_list(RomsetMode::NonMerged)?.into_iter().map(|(name, _)| name).collect::<Vec<_>>());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn names_the_files_after_the_groups() {
+        let mut file_names = HashSet::new();
+        assert_eq!("src_mame_drivers_pacman.dat", get_unique_file_name(Some("src/mame/drivers/pacman.cpp"), &mut file_names));
+        assert_eq!("unknown.dat", get_unique_file_name(None, &mut file_names));
+        assert_eq!("unknown_2.dat", get_unique_file_name(Some("/"), &mut file_names));
+        assert_eq!("A.dat", get_unique_file_name(Some("A"), &mut file_names));
+        assert_eq!("a_2.dat", get_unique_file_name(Some("a"), &mut file_names));
+    }
+}
@@ -0,0 +1,236 @@
+//! Whitelists and blacklists of games, to import and check only part of a DAT. A rule is a game name or a glob pattern,
+//! and can be prefixed with `parent:` to match the parent of the clones, with `source:` to match the driver source file,
+//! with `category:` to match the category the DAT gives to the game, or with `tag:` to match the tags given to the sets.
+use std::{collections::{BTreeMap, BTreeSet}, convert::TryFrom, fs, path::Path, str::FromStr};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{err, error::RomstError, filesystem::glob};
+
+use super::models::game::Game;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilterField {
+    Name,
+    /// The parent of a clone, or the name if the game is not a clone
+    Parent,
+    Source,
+    /// The category from the directories of the DAT
+    Category,
+    /// Any of the tags of the set
+    Tag,
+}
+
+/// Stored as written, `field:pattern`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+struct FilterRule {
+    field: FilterField,
+    pattern: String,
+}
+
+impl FromStr for FilterRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (field, pattern) = match s.split_once(':') {
+            Some(("name", pattern)) => (FilterField::Name, pattern),
+            Some(("parent", pattern)) => (FilterField::Parent, pattern),
+            Some(("source", pattern)) => (FilterField::Source, pattern),
+            Some(("category", pattern)) => (FilterField::Category, pattern),
+            Some(("tag", pattern)) => (FilterField::Tag, pattern),
+            Some((field, _pattern)) => return err!(RomstError::ParsingError { message: format!("Unknown filter field `{}`, expected `name`, `parent`, `source`, `category` or `tag`", field) }),
+            None => (FilterField::Name, s),
+        };
+
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            return err!(RomstError::ParsingError { message: format!("Empty filter rule `{}`", s) });
+        }
+        Ok(FilterRule { field, pattern: pattern.to_lowercase() })
+    }
+}
+
+impl TryFrom<String> for FilterRule {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<FilterRule> for String {
+    fn from(rule: FilterRule) -> Self {
+        let field = match rule.field {
+            FilterField::Name => "name",
+            FilterField::Parent => "parent",
+            FilterField::Source => "source",
+            FilterField::Category => "category",
+            FilterField::Tag => "tag",
+        };
+        format!("{}:{}", field, rule.pattern)
+    }
+}
+
+impl FilterRule {
+    fn matches(&self, game: &Game, tags: Option<&BTreeSet<String>>) -> bool {
+        let value = match self.field {
+            FilterField::Name => Some(game.name.as_str()),
+            FilterField::Parent => Some(game.clone_of.as_deref().unwrap_or(&game.name)),
+            FilterField::Source => game.source_file.as_deref(),
+            FilterField::Category => game.category.as_deref(),
+            FilterField::Tag => return tags.map(|tags| tags.iter().any(|tag| glob::matches(&self.pattern, &tag.to_lowercase()))).unwrap_or(false),
+        };
+
+        match value {
+            Some(value) => glob::matches(&self.pattern, &value.to_lowercase()),
+            None => false,
+        }
+    }
+}
+
+/// Without include rules every game is included, the exclude rules are applied after them.
+/// The tag rules only match the sets once the tags are set with `set_tags`, the tags are not stored with the rules
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameFilter {
+    include: Vec<FilterRule>,
+    exclude: Vec<FilterRule>,
+    #[serde(skip)]
+    tags: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl GameFilter {
+    pub fn add_include(&mut self, rule: &str) -> Result<()> {
+        self.include.push(rule.parse()?);
+        Ok(())
+    }
+
+    pub fn add_exclude(&mut self, rule: &str) -> Result<()> {
+        self.exclude.push(rule.parse()?);
+        Ok(())
+    }
+
+    /// Reads the include rules from a file with a rule per line, empty lines and lines starting with `#` are skipped
+    pub fn include_from_file(&mut self, file: impl AsRef<Path>) -> Result<()> {
+        for rule in read_rules(file.as_ref())? {
+            self.add_include(&rule)?;
+        }
+        Ok(())
+    }
+
+    /// Same as `include_from_file`, for the exclude rules
+    pub fn exclude_from_file(&mut self, file: impl AsRef<Path>) -> Result<()> {
+        for rule in read_rules(file.as_ref())? {
+            self.add_exclude(&rule)?;
+        }
+        Ok(())
+    }
+
+    /// The tags of each set, read from the database the sets are checked against
+    pub fn set_tags(&mut self, tags: BTreeMap<String, BTreeSet<String>>) {
+        self.tags = tags;
+    }
+
+    /// If there are tag rules, so the tags need to be set
+    pub fn uses_tags(&self) -> bool {
+        self.include.iter().chain(self.exclude.iter()).any(|rule| rule.field == FilterField::Tag)
+    }
+
+    /// Without rules all the games are included
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, game: &Game) -> bool {
+        let tags = self.tags.get(&game.name);
+        let included = self.include.is_empty() || self.include.iter().any(|rule| rule.matches(game, tags));
+        included && !self.exclude.iter().any(|rule| rule.matches(game, tags))
+    }
+}
+
+fn read_rules(file: &Path) -> Result<Vec<String>> {
+    let contents = match fs::read_to_string(file) {
+        Ok(contents) => contents,
+        Err(e) => return err!(RomstError::GenericError { message: format!("Error reading the games list `{}`: {}", file.to_string_lossy(), e) }),
+    };
+
+    Ok(contents.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(name: &str, clone_of: Option<&str>, source_file: &str) -> Game {
+        let mut game = Game::new(name.to_string());
+        game.clone_of = clone_of.map(|clone_of| clone_of.to_string());
+        game.source_file = Some(source_file.to_string());
+        game
+    }
+
+    #[test]
+    fn filters_the_games() -> Result<()> {
+        let ssf2 = game("ssf2", None, "cps2.cpp");
+        let ssf2u = game("ssf2u", Some("ssf2"), "cps2.cpp");
+        let mslug = game("mslug", None, "neogeo.cpp");
+        let pacman = game("Pacman", None, "pacman.cpp");
+
+        assert!(GameFilter::default().matches(&pacman));
+
+        let mut filter = GameFilter::default();
+        filter.add_include("source:cps2.cpp")?;
+        filter.add_include("mslug*")?;
+        assert!(filter.matches(&ssf2u));
+        assert!(filter.matches(&mslug));
+        assert!(!filter.matches(&pacman));
+
+        filter.add_exclude("parent:ssf2")?;
+        assert!(!filter.matches(&ssf2));
+        assert!(!filter.matches(&ssf2u));
+        assert!(filter.matches(&mslug));
+
+        let mut filter = GameFilter::default();
+        filter.add_exclude("PAC*")?;
+        assert!(!filter.matches(&pacman));
+        assert!(filter.matches(&ssf2));
+
+        let mut filter = GameFilter::default();
+        let mut kof98 = game("kof98", None, "neogeo.cpp");
+        kof98.category = Some("Fighter/Versus".to_string());
+        filter.add_include("category:fighter/*")?;
+        assert!(filter.matches(&kof98));
+        assert!(!filter.matches(&mslug));
+
+        assert!(filter.add_include("driver:cps2.cpp").is_err());
+        assert!(filter.add_include("name: ").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn filters_the_games_by_tag() -> Result<()> {
+        let ssf2 = game("ssf2", None, "cps2.cpp");
+        let mslug = game("mslug", None, "neogeo.cpp");
+
+        let mut filter = GameFilter::default();
+        filter.add_include("tag:favorite")?;
+        filter.add_exclude("tag:broken-*")?;
+        assert!(filter.uses_tags());
+        assert!(!filter.matches(&ssf2));
+
+        let mut tags = BTreeMap::new();
+        tags.insert("ssf2".to_string(), vec!["Favorite".to_string()].into_iter().collect());
+        tags.insert("mslug".to_string(), vec!["favorite".to_string(), "broken-dump".to_string()].into_iter().collect());
+        filter.set_tags(tags);
+        assert!(filter.matches(&ssf2));
+        assert!(!filter.matches(&mslug));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,796 @@
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet, btree_map::Entry}, fmt::Display, fs::{self, File}, io::{BufReader, BufWriter}, path::Path};
+
+use anyhow::Result;
+use log::info;
+use serde::{Deserialize, Serialize};
+use zip::{ZipArchive, ZipWriter};
+
+use crate::{err, error::RomstError, filesystem::paths};
+
+use super::{backup::Backup, cleaner::get_tmp_path, disk_space::SpaceEstimate, journal::{ExecutionProgress, ResumeJournal}, models, priority::SetPriority, reporter::{CancellationToken, scan_report::{RomLocatedAt, ScanReport, SetReport, SetStatus}}};
+
+/// Suffix for the names used to move an entry out of the way
+const TMP_SUFFIX: &str = ".romst-tmp";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FixOperation {
+    /// Renames an entry of the archive
+    Rename { archive: String, from: String, to: String },
+    /// Copies an entry of the archive into a new entry in the same archive
+    Duplicate { archive: String, from: String, to: String },
+    /// Copies an entry from another archive
+    Copy { archive: String, from_archive: String, from: String, to: String },
+}
+
+impl FixOperation {
+    /// The archive modified by the operation
+    pub fn get_archive(&self) -> &str {
+        match self {
+            FixOperation::Rename { archive, .. } => archive,
+            FixOperation::Duplicate { archive, .. } => archive,
+            FixOperation::Copy { archive, .. } => archive,
+        }
+    }
+}
+
+impl Display for FixOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FixOperation::Rename { archive, from, to } => writeln!(f, "- Rename in {}: {} -> {}", archive, from, to),
+            FixOperation::Duplicate { archive, from, to } => writeln!(f, "- Copy in {}: {} -> {}", archive, from, to),
+            FixOperation::Copy { archive, from_archive, from, to } => writeln!(f, "- Copy {} from {} to {}: {}", from, from_archive, archive, to),
+        }
+    }
+}
+
+/// A target name that can't be written safely, either because more than one rom wants it or because it's taken by
+/// an entry that stays in the archive. None of the sources is moved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixConflict {
+    pub archive: String,
+    pub target: String,
+    pub sources: Vec<String>,
+}
+
+impl Display for FixConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "- {} in {} is wanted by {}", self.target, self.archive, self.sources.join(", "))
+    }
+}
+
+/// The ordered operations to fix the fixeable sets of a scan, the operations are sequenced so no entry is
+/// overwritten before it's moved, using temporary names when renames swap names.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixPlan {
+    root_directory: Option<String>,
+    pub operations: Vec<FixOperation>,
+    pub conflicts: Vec<FixConflict>,
+    /// Each operation writes its archive again, so the space needed is the largest archive on top of what they grow
+    #[serde(default)]
+    pub space: SpaceEstimate,
+    /// The sets fixed, once the plan is executed
+    #[serde(default)]
+    pub progress: Option<ExecutionProgress>,
+}
+
+impl Display for FixPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(root_directory) = &self.root_directory {
+            writeln!(f, "Directory: {}", root_directory)?;
+        }
+        if self.operations.is_empty() {
+            writeln!(f, "Nothing to fix")?;
+        }
+        for operation in &self.operations {
+            write!(f, "{}", operation)?;
+        }
+        if !self.operations.is_empty() {
+            write!(f, "{}", self.space)?;
+        }
+        if !self.conflicts.is_empty() {
+            writeln!(f, "Conflicts, not fixed:")?;
+            for conflict in &self.conflicts {
+                write!(f, "{}", conflict)?;
+            }
+        }
+        if let Some(progress) = &self.progress {
+            write!(f, "{}", progress)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum FixSource {
+    /// An entry of the set archive
+    InSet(String),
+    InOther(String, String),
+}
+
+impl Display for FixSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FixSource::InSet(name) => write!(f, "{}", name),
+            FixSource::InOther(archive, name) => write!(f, "{}/{}", archive, name),
+        }
+    }
+}
+
+impl FixPlan {
+    /// Builds the plan for the fixeable sets of the report, the roms with a wrong name are renamed and the roms found
+    /// in other archives are copied into the set archive.
+    pub fn from_report(report: &ScanReport) -> Self {
+        let archives_by_set = get_archives_by_set(report);
+        let sets = report.sets.iter()
+            .filter(|(_name, set)| set.is_complete() == SetStatus::FIXEABLE)
+            .collect::<BTreeMap<_, _>>();
+
+        let mut plan = FixPlan { root_directory: report.get_root_directory().map(paths::normalize_directory), operations: vec![], conflicts: vec![], space: SpaceEstimate::default(), progress: None };
+        let mut largest_archive = 0;
+        for (set_name, set) in sets {
+            let archive = get_set_archive(&archives_by_set, set_name);
+            let archive_size = report.archives.get(&archive).map(|info| info.total_size).unwrap_or_default();
+            let first = plan.operations.len();
+            plan.add_set(archive, set);
+
+            let operations = &plan.operations[first..];
+            if !operations.is_empty() {
+                let (written, size) = get_bytes_written(archive_size, set, operations);
+                plan.space.bytes_written += written;
+                plan.space.bytes_needed += size - archive_size;
+                largest_archive = largest_archive.max(size);
+            }
+        }
+        plan.space.bytes_needed += largest_archive;
+        plan.order_sets(|_set_name| 0);
+
+        plan
+    }
+
+    fn add_set(&mut self, archive: String, set: &SetReport) {
+        // The entries of the archive that aren't renamed
+        let mut staying = set.roms_to_spare.iter().chain(set.unknown.iter()).map(|rom| rom.name.to_string()).collect::<BTreeSet<_>>();
+        // The roms already right, a set with the same rom under two names may want them copied to the other name
+        let mut in_place = BTreeSet::new();
+        let mut wanted: BTreeMap<String, Vec<FixSource>> = BTreeMap::new();
+        for (rom, located_at) in &set.roms_available {
+            match located_at {
+                RomLocatedAt::InSet => {
+                    staying.insert(rom.name.to_string());
+                    in_place.insert(rom.name.to_string());
+                }
+                RomLocatedAt::InSetWrongName(name) => {
+                    wanted.entry(rom.name.to_string()).or_default().push(FixSource::InSet(name.to_string()));
+                }
+                RomLocatedAt::InOthers(locations) => {
+                    let location = locations.iter().min_by_key(|location| (location.get_file(), location.get_with_name()));
+                    if let Some(location) = location {
+                        wanted.entry(rom.name.to_string()).or_default().push(FixSource::InOther(location.get_file().to_string(), location.get_with_name().to_string()));
+                    }
+                }
+            }
+        }
+
+        // Two sources wanting the same name can't be solved, an entry that stays can't be overwritten, and the
+        // entries that stay because of a conflict may block other targets, so we repeat until nothing changes
+        let mut conflicts = BTreeMap::new();
+        loop {
+            let moving = wanted.values().flatten()
+                .filter_map(|source| match source { FixSource::InSet(name) => Some(name.as_str()), _ => None })
+                .collect::<BTreeSet<_>>();
+            let blocked = wanted.iter()
+                .filter(|(target, sources)| sources.len() > 1 || (staying.contains(*target) && !moving.contains(target.as_str())))
+                .map(|(target, _sources)| target.to_string())
+                .collect::<Vec<_>>();
+            if blocked.is_empty() {
+                break;
+            }
+
+            for target in blocked {
+                if let Some(mut sources) = wanted.remove(&target) {
+                    if staying.contains(&target) {
+                        sources.push(FixSource::InSet(target.to_string()));
+                    }
+                    for source in &sources {
+                        if let FixSource::InSet(name) = source {
+                            staying.insert(name.to_string());
+                        }
+                    }
+                    conflicts.insert(target, sources);
+                }
+            }
+        }
+        // An entry still wanted by a valid target isn't staying, even if a conflicting target wanted it too, unless
+        // it's already right and it's only copied
+        for source in wanted.values().flatten() {
+            if let FixSource::InSet(name) = source {
+                if !in_place.contains(name) {
+                    staying.remove(name);
+                }
+            }
+        }
+
+        for (target, mut sources) in conflicts {
+            sources.sort();
+            sources.dedup();
+            let sources = sources.iter().map(|source| source.to_string()).collect();
+            self.conflicts.push(FixConflict { archive: archive.to_string(), target, sources });
+        }
+
+        // The first target of an entry is a rename, the others are copies of it done before renaming it. An entry
+        // already right is only copied
+        let mut renames = BTreeMap::new();
+        let mut duplicates = vec![];
+        let mut copies = vec![];
+        for (target, sources) in wanted {
+            for source in sources {
+                match source {
+                    FixSource::InSet(name) if in_place.contains(&name) => duplicates.push((name, target.to_string())),
+                    FixSource::InSet(name) => {
+                        match renames.entry(name) {
+                            Entry::Occupied(entry) => duplicates.push((entry.key().to_string(), target.to_string())),
+                            Entry::Vacant(entry) => {
+                                entry.insert(target.to_string());
+                            }
+                        }
+                    }
+                    FixSource::InOther(from_archive, from) => copies.push((from_archive, from, target.to_string())),
+                }
+            }
+        }
+
+        let mut names = staying;
+        names.extend(renames.keys().cloned());
+        let mut pending_renames = vec![];
+        for (from, to) in duplicates {
+            if names.contains(&to) {
+                // The target is renamed later, so the copy waits with a temporary name
+                let tmp = get_tmp_name(&to, &names);
+                names.insert(tmp.to_string());
+                self.operations.push(FixOperation::Duplicate { archive: archive.to_string(), from, to: tmp.to_string() });
+                pending_renames.push((tmp, to));
+            } else {
+                names.insert(to.to_string());
+                self.operations.push(FixOperation::Duplicate { archive: archive.to_string(), from, to });
+            }
+        }
+
+        let mut renames = renames.into_iter().collect::<Vec<_>>();
+        while !renames.is_empty() {
+            match renames.iter().position(|(_from, to)| !names.contains(to)) {
+                Some(index) => {
+                    let (from, to) = renames.remove(index);
+                    names.remove(&from);
+                    names.insert(to.to_string());
+                    self.operations.push(FixOperation::Rename { archive: archive.to_string(), from, to });
+                }
+                None => {
+                    // All the targets are taken by entries still to rename, so they swap names, we move one
+                    // out of the way to break the cycle
+                    let tmp = get_tmp_name(&renames[0].0, &names);
+                    let from = std::mem::replace(&mut renames[0].0, tmp.to_string());
+                    names.remove(&from);
+                    names.insert(tmp.to_string());
+                    self.operations.push(FixOperation::Rename { archive: archive.to_string(), from, to: tmp });
+                }
+            }
+        }
+
+        for (from_archive, from, to) in copies {
+            self.operations.push(FixOperation::Copy { archive: archive.to_string(), from_archive, from, to });
+        }
+        for (from, to) in pending_renames {
+            self.operations.push(FixOperation::Rename { archive: archive.to_string(), from, to });
+        }
+    }
+
+    /// Orders the operations set by set, a set copying roms from the archive of another set goes before that set
+    /// renames them. The sets free to go are taken by their rank, a set holding back another one takes its rank if
+    /// it's better, and then in the order they had. Sets copying from each other can't both go first, so the copies
+    /// of the one going later read the names the roms have once the other set is fixed
+    fn order_sets<F>(&mut self, get_rank: F) where F: Fn(&str) -> usize {
+        let mut sets: Vec<(String, Vec<FixOperation>)> = vec![];
+        for operation in self.operations.drain(..) {
+            let set_name = models::get_set_from_file(operation.get_archive());
+            match sets.iter_mut().find(|(name, _operations)| *name == set_name) {
+                Some((_name, operations)) => operations.push(operation),
+                None => sets.push((set_name, vec![operation])),
+            }
+        }
+
+        // The sets that have to go before each set, as they copy from its archive
+        let mut readers: HashMap<String, HashSet<String>> = HashMap::new();
+        for (set_name, operations) in &sets {
+            for operation in operations {
+                if let FixOperation::Copy { from_archive, .. } = operation {
+                    let from_set = models::get_set_from_file(from_archive);
+                    if from_set != *set_name && sets.iter().any(|(name, _operations)| *name == from_set) {
+                        readers.entry(from_set).or_default().insert(set_name.to_string());
+                    }
+                }
+            }
+        }
+
+        let mut ranks = sets.iter().map(|(set_name, _operations)| (set_name.to_string(), get_rank(set_name))).collect::<HashMap<_, _>>();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (set_name, set_readers) in &readers {
+                let rank = ranks[set_name];
+                for reader in set_readers {
+                    if ranks[reader] > rank {
+                        ranks.insert(reader.to_string(), rank);
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        let mut pending = sets.into_iter().enumerate().map(|(position, (set_name, operations))| (ranks[&set_name], position, set_name, operations)).collect::<Vec<_>>();
+        let mut done = HashSet::new();
+        while !pending.is_empty() {
+            let is_free = |set_name: &String| readers.get(set_name).map(|readers| readers.iter().all(|reader| done.contains(reader))).unwrap_or(true);
+            let next = pending.iter().enumerate()
+                .filter(|(_index, (_rank, _position, set_name, _operations))| is_free(set_name))
+                .min_by_key(|(_index, (rank, position, _set_name, _operations))| (*rank, *position))
+                .or_else(|| pending.iter().enumerate().min_by_key(|(_index, (rank, position, _set_name, _operations))| (*rank, *position)))
+                .map(|(index, _set)| index);
+            if let Some(index) = next {
+                let (_rank, _position, set_name, operations) = pending.remove(index);
+                done.insert(set_name);
+                self.operations.extend(operations);
+            }
+        }
+
+        // The names the entries of each archive have after the operations done so far, by their original name
+        let mut current_names: HashMap<String, BTreeMap<String, String>> = HashMap::new();
+        for operation in self.operations.iter_mut() {
+            match operation {
+                FixOperation::Rename { archive, from, to } => {
+                    let names = current_names.entry(archive.to_string()).or_default();
+                    let original = names.iter().find(|(_original, current)| *current == from).map(|(original, _current)| original.to_string());
+                    names.insert(original.unwrap_or_else(|| from.to_string()), to.to_string());
+                }
+                FixOperation::Copy { archive, from_archive, from, .. } if models::get_set_from_file(from_archive) != models::get_set_from_file(archive) => {
+                    if let Some(current) = current_names.get(from_archive.as_str()).and_then(|names| names.get(from.as_str())) {
+                        *from = current.to_string();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn get_root_directory(&self) -> Option<&str> {
+        self.root_directory.as_deref()
+    }
+
+    /// Reads the free space of the disk of the directory to fix
+    pub fn read_available_space(&mut self) {
+        if let Some(root_directory) = &self.root_directory {
+            self.space.read_available(root_directory);
+        }
+    }
+
+    /// Moves the operations of the sets in the priority list first, in the order of the list, unless another set
+    /// copies roms from their archives, then that set goes before them
+    pub fn prioritize(&mut self, priority: &SetPriority) {
+        if !priority.is_empty() {
+            self.order_sets(|set_name| priority.get_rank(set_name));
+        }
+    }
+
+    /// Runs the operations in order on the zip archives of the root directory, stops at the first failure
+    pub fn execute(&self) -> Result<()> {
+        self.execute_resumable(&mut ResumeJournal::default(), &mut Backup::default(), &CancellationToken::default())?;
+        Ok(())
+    }
+
+    /// Runs the operations set by set, skipping the sets already in the journal and recording the ones done. Once
+    /// cancelled it stops after the set being fixed, and at the first failure. Each archive is backed up before its
+    /// first operation
+    pub fn execute_resumable(&self, journal: &mut ResumeJournal, backup: &mut Backup, cancellation: &CancellationToken) -> Result<ExecutionProgress> {
+        let root_directory = match &self.root_directory {
+            Some(root_directory) => Path::new(root_directory),
+            None => return err!("The plan doesn't have a directory to fix"),
+        };
+
+        let mut progress = ExecutionProgress::default();
+        let mut operations = self.operations.iter().peekable();
+        while let Some(first) = operations.peek() {
+            let set_name = models::get_set_from_file(first.get_archive());
+            let mut set_operations = vec![];
+            while let Some(operation) = operations.next_if(|operation| models::get_set_from_file(operation.get_archive()) == set_name) {
+                set_operations.push(operation);
+            }
+
+            if journal.is_completed(&set_name) {
+                progress.resumed += 1;
+                continue;
+            }
+            if cancellation.is_cancelled() {
+                progress.interrupted = true;
+                break;
+            }
+            for operation in set_operations {
+                backup.save(root_directory, operation.get_archive())?;
+                execute_operation(root_directory, operation)?;
+            }
+            journal.complete(&set_name)?;
+            progress.completed.push(set_name);
+        }
+        progress.backup_manifest = backup.get_manifest_file().map(|manifest_file| manifest_file.to_string_lossy().to_string());
+
+        Ok(progress)
+    }
+}
+
+fn execute_operation(root_directory: &Path, operation: &FixOperation) -> Result<()> {
+    let archive = paths::join_key(root_directory, operation.get_archive());
+    match operation {
+        FixOperation::Rename { from, to, .. } => {
+            info!("Renaming {} to {} in {}", from, to, archive.to_string_lossy());
+            rewrite_archive(&archive, Some((from, to)), None)
+        }
+        FixOperation::Duplicate { from, to, .. } => {
+            info!("Copying {} to {} in {}", from, to, archive.to_string_lossy());
+            rewrite_archive(&archive, None, Some((&archive, from, to)))
+        }
+        FixOperation::Copy { from_archive, from, to, .. } => {
+            let from_archive = paths::join_key(root_directory, from_archive);
+            info!("Copying {} from {} to {} in {}", from, from_archive.to_string_lossy(), to, archive.to_string_lossy());
+            rewrite_archive(&archive, None, Some((&from_archive, from, to)))
+        }
+    }
+}
+
+/// The bytes written by the operations on an archive, as each one writes the whole archive again, and the size of
+/// the archive at the end. The roms are counted uncompressed
+fn get_bytes_written(archive_size: u64, set: &SetReport, operations: &[FixOperation]) -> (u64, u64) {
+    let mut rom_sizes = HashMap::new();
+    for (rom, located_at) in &set.roms_available {
+        let size = rom.info.size.unwrap_or_default() as u64;
+        rom_sizes.insert(rom.name.as_str(), size);
+        if let RomLocatedAt::InSetWrongName(name) = located_at {
+            rom_sizes.insert(name.as_str(), size);
+        }
+    }
+
+    let mut size = archive_size;
+    let mut written = 0;
+    for operation in operations {
+        size += match operation {
+            FixOperation::Rename { .. } => 0,
+            FixOperation::Duplicate { from, .. } => rom_sizes.get(from.as_str()).copied().unwrap_or_default(),
+            FixOperation::Copy { to, .. } => rom_sizes.get(to.as_str()).copied().unwrap_or_default(),
+        };
+        written += size;
+    }
+
+    (written, size)
+}
+
+/// The archive of each set in the report, by the set name
+pub(crate) fn get_archives_by_set(report: &ScanReport) -> BTreeMap<String, String> {
+    let mut archives_by_set = BTreeMap::new();
+    for file in report.archives.keys() {
+        archives_by_set.entry(models::get_set_from_file(file)).or_insert_with(|| file.to_string());
+    }
+    archives_by_set
+}
+
+/// The archive of the set, a zip named as the set if it wasn't found
+pub(crate) fn get_set_archive(archives_by_set: &BTreeMap<String, String>, set_name: &str) -> String {
+    archives_by_set.get(set_name).cloned().unwrap_or_else(|| format!("{}.zip", set_name))
+}
+
+fn get_tmp_name(name: &str, names: &BTreeSet<String>) -> String {
+    let mut tmp = format!("{}{}", name, TMP_SUFFIX);
+    let mut count = 1;
+    while names.contains(&tmp) {
+        tmp = format!("{}{}{}", name, TMP_SUFFIX, count);
+        count += 1;
+    }
+    tmp
+}
+
+pub(super) fn check_zip(file: &Path) -> Result<()> {
+    match file.extension() {
+        Some(extension) if extension.eq_ignore_ascii_case("zip") => Ok(()),
+        _ => err!(RomstError::GenericError { message: format!("Only zip archives can be fixed, found `{}`", file.to_string_lossy()) }),
+    }
+}
+
+/// Writes the archive again renaming an entry and adding an entry copied from an archive, that can be the same one.
+/// If the archive doesn't exist it's created.
+fn rewrite_archive(file: &Path, rename: Option<(&str, &str)>, add: Option<(&Path, &str, &str)>) -> Result<()> {
+    check_zip(file)?;
+    let tmp_path = get_tmp_path(file);
+    let mut writer = ZipWriter::new(BufWriter::new(File::create(&tmp_path)?));
+
+    if file.exists() {
+        let mut archive = ZipArchive::new(BufReader::new(File::open(file)?))?;
+        let mut renamed = false;
+        for i in 0..archive.len() {
+            let entry = archive.by_index_raw(i)?;
+            match rename {
+                Some((from, to)) if entry.name() == from => {
+                    writer.raw_copy_file_rename(entry, to)?;
+                    renamed = true;
+                }
+                _ => writer.raw_copy_file(entry)?,
+            }
+        }
+        if let (Some((from, _to)), false) = (rename, renamed) {
+            fs::remove_file(&tmp_path)?;
+            return err!(RomstError::GenericError { message: format!("The entry `{}` is not in `{}`", from, file.to_string_lossy()) });
+        }
+    }
+
+    if let Some((source, from, to)) = add {
+        check_zip(source)?;
+        let mut source_archive = ZipArchive::new(BufReader::new(File::open(source)?))?;
+        let mut entry = None;
+        for i in 0..source_archive.len() {
+            if source_archive.by_index_raw(i)?.name() == from {
+                entry = Some(i);
+                break;
+            }
+        }
+        match entry {
+            Some(index) => writer.raw_copy_file_rename(source_archive.by_index_raw(index)?, to)?,
+            None => {
+                fs::remove_file(&tmp_path)?;
+                return err!(RomstError::GenericError { message: format!("The entry `{}` is not in `{}`", from, source.to_string_lossy()) });
+            }
+        }
+    }
+
+    writer.finish()?;
+    fs::rename(tmp_path, file)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::{RomsetMode, data::{models::{archive::{ArchiveFormat, ArchiveInfo}, file::{DataFile, DataFileInfo, FileType}}, reporter::scan_report::RomLocation}};
+
+    use super::*;
+
+    fn rom(name: &str, crc: &str) -> DataFile {
+        let mut info = DataFileInfo::new(FileType::Rom);
+        info.crc = Some(crc.to_string());
+        DataFile::new(name, info)
+    }
+
+    fn archive(entries: usize) -> ArchiveInfo {
+        ArchiveInfo::new(ArchiveFormat::NonCanonical, entries, 0, String::new())
+    }
+
+    fn rename(from: &str, to: &str) -> FixOperation {
+        FixOperation::Rename { archive: "game1.zip".to_string(), from: from.to_string(), to: to.to_string() }
+    }
+
+    #[test]
+    fn plans_swapped_names_with_temporary_names() {
+        let mut report = ScanReport::new(Some("roms".to_string()), RomsetMode::Split);
+        report.add_archive_info("game1.zip", archive(3));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "b.rom"), rom("a.rom", "1111"));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "a.rom"), rom("b.rom", "2222"));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "d.rom"), rom("c.rom", "3333"));
+
+        let plan = report.build_fix_plan();
+
+        assert!(plan.conflicts.is_empty());
+        assert_eq!(vec![
+            rename("d.rom", "c.rom"),
+            rename("a.rom", "a.rom.romst-tmp"),
+            rename("b.rom", "a.rom"),
+            rename("a.rom.romst-tmp", "b.rom"),
+        ], plan.operations);
+    }
+
+    #[test]
+    fn estimates_the_space_of_the_archives_written_again() {
+        let sized_rom = |name: &str, crc: &str, size: u32| {
+            let mut rom = rom(name, crc);
+            rom.info.size = Some(size);
+            rom
+        };
+        let mut report = ScanReport::new(Some("roms".to_string()), RomsetMode::Split);
+        report.add_archive_info("game1.zip", ArchiveInfo::new(ArchiveFormat::NonCanonical, 1, 100, String::new()));
+        report.add_archive_info("game2.zip", ArchiveInfo::new(ArchiveFormat::NonCanonical, 1, 500, String::new()));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "b.rom"), sized_rom("a.rom", "1111", 100));
+        report.add_rom_for_set("game1", RomLocation::new("game2.zip", "c.rom"), sized_rom("c.rom", "2222", 50));
+
+        let plan = report.build_fix_plan();
+
+        assert_eq!(2, plan.operations.len());
+        // The rename writes the 100 bytes of the archive, the copy writes them again with the 50 bytes of the rom
+        assert_eq!(250, plan.space.bytes_written);
+        assert_eq!(200, plan.space.bytes_needed);
+    }
+
+    #[test]
+    fn reports_targets_wanted_twice() {
+        let mut report = ScanReport::new(Some("roms".to_string()), RomsetMode::Split);
+        report.add_archive_info("game1.zip", archive(2));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "x.rom"), rom("a.rom", "1111"));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "y.rom"), rom("a.rom", "2222"));
+        report.add_rom_for_set("game1", RomLocation::new("game2.zip", "other.rom"), rom("c.rom", "3333"));
+        // The spare entry can't be overwritten
+        report.add_roms_to_spare(vec![ rom("d.rom", "9999") ], "game1.zip");
+        report.add_rom_for_set("game1", RomLocation::new("game2.zip", "d.rom"), rom("d.rom", "4444"));
+
+        let plan = report.build_fix_plan();
+
+        assert_eq!(vec![
+            FixConflict { archive: "game1.zip".to_string(), target: "a.rom".to_string(), sources: vec![ "x.rom".to_string(), "y.rom".to_string() ] },
+            FixConflict { archive: "game1.zip".to_string(), target: "d.rom".to_string(), sources: vec![ "d.rom".to_string(), "game2.zip/d.rom".to_string() ] },
+        ], plan.conflicts);
+        assert_eq!(vec![
+            FixOperation::Copy { archive: "game1.zip".to_string(), from_archive: "game2.zip".to_string(), from: "other.rom".to_string(), to: "c.rom".to_string() },
+        ], plan.operations);
+    }
+
+    #[test]
+    fn executes_the_plan() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_fix_{}", std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        for (file, entries) in [("game1.zip", vec![("a.rom", "b"), ("b.rom", "a")]), ("game2.zip", vec![("other.rom", "c")])] {
+            let mut writer = ZipWriter::new(File::create(dir.join(file))?);
+            for (name, content) in entries {
+                writer.start_file(name, zip::write::FileOptions::default())?;
+                std::io::Write::write_all(&mut writer, content.as_bytes())?;
+            }
+            writer.finish()?;
+        }
+
+        let mut report = ScanReport::new(Some(dir.to_string_lossy().to_string()), RomsetMode::Split);
+        report.add_archive_info("game1.zip", archive(2));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "b.rom"), rom("a.rom", "1111"));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "a.rom"), rom("b.rom", "2222"));
+        report.add_rom_for_set("game1", RomLocation::new("game2.zip", "other.rom"), rom("c.rom", "3333"));
+        let original = fs::read(dir.join("game1.zip"))?;
+        let mut backup = Backup::new(dir.join("backups"));
+        let progress = report.build_fix_plan().execute_resumable(&mut ResumeJournal::default(), &mut backup, &CancellationToken::default())?;
+        assert_eq!(backup.get_manifest_file().map(|file| file.to_string_lossy().to_string()), progress.backup_manifest);
+
+        let mut archive = ZipArchive::new(File::open(dir.join("game1.zip"))?)?;
+        let mut entries = vec![];
+        for name in ["a.rom", "b.rom", "c.rom"] {
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut archive.by_name(name)?, &mut content)?;
+            entries.push(content);
+        }
+        assert_eq!(vec!["a", "b", "c"], entries);
+        assert_eq!(3, archive.len());
+
+        Backup::restore(backup.get_manifest_file().unwrap())?;
+        assert_eq!(original, fs::read(dir.join("game1.zip"))?);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+    fn write_zip(file: &Path, entries: &[(&str, &str)]) -> Result<()> {
+        let mut writer = ZipWriter::new(File::create(file)?);
+        for (name, content) in entries {
+            writer.start_file(*name, zip::write::FileOptions::default())?;
+            std::io::Write::write_all(&mut writer, content.as_bytes())?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn read_entry(file: &Path, name: &str) -> Result<String> {
+        let mut archive = ZipArchive::new(File::open(file)?)?;
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name(name)?, &mut content)?;
+        Ok(content)
+    }
+
+    #[test]
+    fn copies_from_other_archives_before_renaming_them() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_fix_shared_{}", std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        write_zip(&dir.join("game1.zip"), &[("x.rom", "x")])?;
+        write_zip(&dir.join("game2.zip"), &[("y.rom", "y")])?;
+        write_zip(&dir.join("game3.zip"), &[("z.rom", "z")])?;
+
+        // game2 copies the rom game1 renames, game3 and game1 copy from each other
+        let mut report = ScanReport::new(Some(dir.to_string_lossy().to_string()), RomsetMode::Split);
+        report.add_archive_info("game1.zip", archive(1));
+        report.add_archive_info("game2.zip", archive(1));
+        report.add_archive_info("game3.zip", archive(1));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "x.rom"), rom("a.rom", "1111"));
+        report.add_rom_for_set("game1", RomLocation::new("game3.zip", "z.rom"), rom("c.rom", "3333"));
+        report.add_rom_for_set("game2", RomLocation::new("game2.zip", "y.rom"), rom("b.rom", "2222"));
+        report.add_rom_for_set("game2", RomLocation::new("game1.zip", "x.rom"), rom("a.rom", "1111"));
+        report.add_rom_for_set("game3", RomLocation::new("game3.zip", "z.rom"), rom("c.rom", "3333"));
+        report.add_rom_for_set("game3", RomLocation::new("game1.zip", "x.rom"), rom("a.rom", "1111"));
+
+        let plan = report.build_fix_plan();
+        let copy = |archive: &str, from_archive: &str, from: &str, to: &str| FixOperation::Copy { archive: archive.to_string(), from_archive: from_archive.to_string(), from: from.to_string(), to: to.to_string() };
+        let rename_in = |archive: &str, from: &str, to: &str| FixOperation::Rename { archive: archive.to_string(), from: from.to_string(), to: to.to_string() };
+        assert_eq!(vec![
+            rename_in("game2.zip", "y.rom", "b.rom"),
+            copy("game2.zip", "game1.zip", "x.rom", "a.rom"),
+            rename_in("game1.zip", "x.rom", "a.rom"),
+            copy("game1.zip", "game3.zip", "z.rom", "c.rom"),
+            // game1 went first, so its rom has the new name
+            rename_in("game3.zip", "z.rom", "c.rom"),
+            copy("game3.zip", "game1.zip", "a.rom", "a.rom"),
+        ], plan.operations);
+
+        plan.execute()?;
+        for file in ["game1.zip", "game2.zip", "game3.zip"] {
+            assert_eq!("x", read_entry(&dir.join(file), "a.rom")?);
+        }
+        assert_eq!("y", read_entry(&dir.join("game2.zip"), "b.rom")?);
+        assert_eq!("z", read_entry(&dir.join("game1.zip"), "c.rom")?);
+        assert_eq!("z", read_entry(&dir.join("game3.zip"), "c.rom")?);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn fixes_the_priority_sets_first_and_resumes() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_fix_priority_{}", std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        let mut report = ScanReport::new(Some(dir.to_string_lossy().to_string()), RomsetMode::Split);
+        for set_name in ["game1", "game2"] {
+            let archive_name = format!("{}.zip", set_name);
+            let mut writer = ZipWriter::new(File::create(dir.join(&archive_name))?);
+            writer.start_file("wrong.rom", zip::write::FileOptions::default())?;
+            writer.finish()?;
+            report.add_archive_info(&archive_name, archive(1));
+            report.add_rom_for_set(set_name, RomLocation::new(archive_name.as_str(), "wrong.rom"), rom("a.rom", "1111"));
+        }
+
+        let mut plan = report.build_fix_plan();
+        plan.prioritize(&SetPriority::new(vec!["game2"]));
+        assert_eq!("game2.zip", plan.operations[0].get_archive());
+
+        // game1 copies from game2, so it goes first even if game2 has the priority
+        let mut copying_report = ScanReport::new(Some(dir.to_string_lossy().to_string()), RomsetMode::Split);
+        copying_report.add_archive_info("game1.zip", archive(1));
+        copying_report.add_archive_info("game2.zip", archive(1));
+        copying_report.add_archive_info("game3.zip", archive(1));
+        copying_report.add_rom_for_set("game3", RomLocation::new("game3.zip", "wrong.rom"), rom("a.rom", "1111"));
+        copying_report.add_rom_for_set("game2", RomLocation::new("game2.zip", "wrong.rom"), rom("a.rom", "1111"));
+        copying_report.add_rom_for_set("game1", RomLocation::new("game2.zip", "wrong.rom"), rom("b.rom", "1111"));
+        let mut copying_plan = copying_report.build_fix_plan();
+        copying_plan.prioritize(&SetPriority::new(vec!["game2", "game3"]));
+        let archives = copying_plan.operations.iter().map(|operation| operation.get_archive()).collect::<Vec<_>>();
+        assert_eq!(vec!["game1.zip", "game2.zip", "game3.zip"], archives);
+
+        let cancellation = CancellationToken::default();
+        cancellation.cancel();
+        let progress = plan.execute_resumable(&mut ResumeJournal::default(), &mut Backup::default(), &cancellation)?;
+        assert!(progress.interrupted);
+        assert!(progress.completed.is_empty());
+
+        let mut journal = ResumeJournal::default();
+        journal.complete("game2")?;
+        let progress = plan.execute_resumable(&mut journal, &mut Backup::default(), &CancellationToken::default())?;
+        assert_eq!(vec!["game1".to_string()], progress.completed);
+        assert_eq!(1, progress.resumed);
+        assert!(ZipArchive::new(File::open(dir.join("game1.zip"))?)?.by_name("a.rom").is_ok());
+        assert!(ZipArchive::new(File::open(dir.join("game2.zip"))?)?.by_name("wrong.rom").is_ok());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
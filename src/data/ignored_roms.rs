@@ -0,0 +1,83 @@
+//! Roms that will never be found, like the undumped or lost roms of a set, so they are not required to complete it.
+//! A rom is ignored by its sha1 in every set that has it, or by the set and its name in that set only.
+use std::{collections::{BTreeMap, BTreeSet}, fmt::Display};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{err, error::RomstError};
+
+use super::{models::file::DataFile, reporter::formatter::{EnglishFormatter, ReportFormatter}};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IgnoredRoms {
+    /// The sha1s in lowercase
+    pub sha1s: BTreeSet<String>,
+    /// The names of the roms ignored in each set
+    pub roms: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Display for IgnoredRoms {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_ignored_roms(f, self)
+    }
+}
+
+impl IgnoredRoms {
+    pub fn add_sha1(&mut self, sha1: &str) -> Result<()> {
+        let sha1 = sha1.trim();
+        if sha1.len() != 40 || !sha1.chars().all(|c| c.is_ascii_hexdigit()) {
+            return err!(RomstError::ParsingError { message: format!("`{}` is not a sha1, it needs 40 hexadecimal characters", sha1) });
+        }
+        self.sha1s.insert(sha1.to_lowercase());
+        Ok(())
+    }
+
+    /// The rom is written as `set/rom`, the name of the rom can have more slashes if it's in a directory
+    pub fn add_rom(&mut self, set_rom: &str) -> Result<()> {
+        match set_rom.split_once('/') {
+            Some((set_name, rom_name)) if !set_name.trim().is_empty() && !rom_name.trim().is_empty() => {
+                self.roms.entry(set_name.trim().to_string()).or_default().insert(rom_name.trim().to_string());
+                Ok(())
+            }
+            _ => err!(RomstError::ParsingError { message: format!("`{}` is not a rom of a set, write it as `set/rom`", set_rom) }),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sha1s.is_empty() && self.roms.is_empty()
+    }
+
+    pub fn is_ignored(&self, set_name: &str, rom: &DataFile) -> bool {
+        let by_sha1 = rom.info.sha1.as_ref().map(|sha1| self.sha1s.contains(&sha1.to_lowercase())).unwrap_or(false);
+        by_sha1 || self.roms.get(set_name).map(|roms| roms.contains(&rom.name)).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::models::file::{DataFileInfo, FileType};
+    use super::*;
+
+    fn rom(name: &str, sha1: Option<&str>) -> DataFile {
+        let mut info = DataFileInfo::new(FileType::Rom);
+        info.sha1 = sha1.map(|sha1| sha1.to_string());
+        DataFile::new(name, info)
+    }
+
+    #[test]
+    fn ignores_the_roms_by_sha1_or_by_set() -> Result<()> {
+        let mut ignored_roms = IgnoredRoms::default();
+        ignored_roms.add_sha1("0123456789ABCDEF0123456789ABCDEF01234567")?;
+        ignored_roms.add_rom("game1/sound/rom2.rom")?;
+        assert!(ignored_roms.add_sha1("0123").is_err());
+        assert!(ignored_roms.add_rom("rom2.rom").is_err());
+
+        assert!(ignored_roms.is_ignored("game2", &rom("rom1.rom", Some("0123456789abcdef0123456789abcdef01234567"))));
+        assert!(ignored_roms.is_ignored("game1", &rom("sound/rom2.rom", None)));
+        assert!(!ignored_roms.is_ignored("game2", &rom("sound/rom2.rom", None)));
+        assert!(!ignored_roms.is_ignored("game1", &rom("rom1.rom", Some("1123456789abcdef0123456789abcdef01234567"))));
+
+        Ok(())
+    }
+}
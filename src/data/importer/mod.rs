@@ -1,50 +1,310 @@
+#[cfg(feature = "net")]
+pub mod remote;
+pub mod validation;
 
-use std::{fs::{self, File}, io::{BufRead, BufReader}, path::Path, str};
-use log::{debug, error, info};
+use std::{cell::RefCell, collections::BTreeMap, fmt::Display, fs::{self, File}, io::{BufRead, BufReader}, path::Path, rc::Rc, str, sync::{Arc, atomic::{AtomicU32, Ordering}, mpsc::{SyncSender, sync_channel}}, thread, time::{Duration, Instant}};
+use log::{debug, error, info, warn};
 use anyhow::Result;
-use quick_xml::{Reader, events::{attributes::Attributes, Event}};
-use crate::{data::writer::*, err, error::RomstError};
+use quick_xml::{Reader, events::{BytesStart, attributes::Attributes, Event}};
+use serde::{Deserialize, Serialize};
+use crate::{data::{reporter::formatter::{EnglishFormatter, ReportFormatter}, writer::{*, maintenance::VacuumReport, references::ReferencesReport}}, err, error::RomstError};
 
-use super::models::{disk::{GameDisk, GameDiskInfo}, file::DataFile, file::{DataFileInfo, FileType}, game::Game, dat_info::DatInfo};
+use super::models::{disk::{GameDisk, GameDiskInfo}, file::DataFile, file::{DataFileInfo, FileType}, game::{Game, GameMetadata}, dat_info::DatInfo};
 
-const _VERSION: &'static str = env!("CARGO_PKG_VERSION");
+const _VERSION: &str = env!("CARGO_PKG_VERSION");
+/// How many parsed entries can be waiting for the writer when importing in a separate thread
+const ENTRY_CHANNEL_SIZE: usize = 1024;
+/// How many issues are kept with their details in the import report, the rest are only counted
+const MAX_REPORTED_ISSUES: usize = 50;
+/// How many characters of the offending tag are kept in the import report
+const MAX_SNIPPET_LENGTH: usize = 120;
 
 pub struct DatImporter<R: BufRead, W: DataWriter> {
     reader: Reader<R>,
-    writer: W,
+    /// Only taken while the writer thread owns it
+    writer: Option<W>,
     reporter: Option<Box<dyn DatImporterReporter>>,
     total_bytes: u64,
+    entry_sender: Option<SyncSender<DatEntry>>,
+    parsed: u32,
+    written: Arc<AtomicU32>,
+    started: Instant,
+    extended_metadata: bool,
+    report: ImportReport,
+    max_errors: Option<u32>,
+    /// The names of the `dir` elements the reader is in
+    dirs: Vec<String>,
 }
 
 pub trait DatImporterReporter {
     fn set_total_bytes(&mut self, total_bytes: u64);
     fn update_position(&mut self, bytes: u64, new_entries: u32);
+    fn update_throughput(&mut self, throughput: ImportThroughput);
     fn start_finish(&self);
+    /// The progress of the finish step, as the parent updates done and the total
+    fn update_finish_progress(&mut self, done: u64, total: u64);
     fn finish(&self);
 }
 
+/// Reports the import of several DATs as a single one, the positions of each DAT come after the bytes of the DATs
+/// imported before it. Set the total bytes of all the DATs in the shared reporter before the import starts
+pub struct CombinedImportReporter<R: DatImporterReporter> {
+    inner: Rc<RefCell<R>>,
+    offset: u64,
+    last: bool,
+}
+
+impl<R: DatImporterReporter> CombinedImportReporter<R> {
+    pub fn new(inner: Rc<RefCell<R>>, offset: u64, last: bool) -> Self {
+        Self { inner, offset, last }
+    }
+}
+
+impl<R: DatImporterReporter> DatImporterReporter for CombinedImportReporter<R> {
+    fn set_total_bytes(&mut self, _total_bytes: u64) {}
+
+    fn update_position(&mut self, bytes: u64, new_entries: u32) {
+        self.inner.borrow_mut().update_position(self.offset + bytes, new_entries);
+    }
+
+    fn update_throughput(&mut self, throughput: ImportThroughput) {
+        self.inner.borrow_mut().update_throughput(throughput);
+    }
+
+    // Only the last DAT finishes the import
+    fn start_finish(&self) {
+        if self.last {
+            self.inner.borrow().start_finish();
+        }
+    }
+
+    fn update_finish_progress(&mut self, done: u64, total: u64) {
+        if self.last {
+            self.inner.borrow_mut().update_finish_progress(done, total);
+        }
+    }
+
+    fn finish(&self) {
+        if self.last {
+            self.inner.borrow().finish();
+        }
+    }
+}
+
+/// The entries parsed and written so far, when writing in a separate thread the writer may fall behind the parser
+#[derive(Debug, Clone, Copy)]
+pub struct ImportThroughput {
+    pub parsed: u32,
+    pub written: u32,
+    pub elapsed: Duration,
+}
+
+impl ImportThroughput {
+    /// Parsed entries per second
+    pub fn parse_rate(&self) -> f64 {
+        rate(self.parsed, self.elapsed)
+    }
+
+    /// Written entries per second
+    pub fn write_rate(&self) -> f64 {
+        rate(self.written, self.elapsed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportIssueLevel {
+    /// Some data of the entry couldn't be read, but the entry was imported
+    Warning,
+    /// The entry, or one of its elements, couldn't be read and was skipped
+    Error,
+}
+
+/// A problem found in the DAT, with the position in bytes and the start of the tag where it was found.
+/// Problems found when writing the entries have no position
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportIssue {
+    pub level: ImportIssueLevel,
+    pub position: Option<usize>,
+    pub message: String,
+    pub snippet: String,
+}
+
+/// The summary of an import, only the first issues are kept, but all of them are counted
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub entries: u32,
+    pub warnings: u32,
+    pub errors: u32,
+    pub issues: Vec<ImportIssue>,
+    /// The games removed after the import because they didn't match the filter
+    pub excluded: u32,
+    /// The sizes of the database when it's compacted after the import
+    #[serde(default)]
+    pub vacuum: Option<VacuumReport>,
+    /// The parents, bios and devices referenced but not imported, none if all of them are there
+    #[serde(default)]
+    pub references: Option<ReferencesReport>,
+}
+
+impl ImportReport {
+    fn add_issue(&mut self, issue: ImportIssue) {
+        match issue.level {
+            ImportIssueLevel::Warning => self.warnings += 1,
+            ImportIssueLevel::Error => self.errors += 1,
+        }
+        if self.issues.len() < MAX_REPORTED_ISSUES {
+            self.issues.push(issue);
+        }
+    }
+
+    pub fn has_issues(&self) -> bool {
+        self.warnings > 0 || self.errors > 0
+    }
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_import_report(f, self)
+    }
+}
+
+/// The report of each DAT imported into the same database, the filter and the compaction run once after all of them
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiImportReport {
+    pub dats: Vec<DatImportReport>,
+    /// The games removed after the import because they didn't match the filter
+    pub excluded: u32,
+    pub vacuum: Option<VacuumReport>,
+    #[serde(default)]
+    pub references: Option<ReferencesReport>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatImportReport {
+    pub source: String,
+    pub report: ImportReport,
+}
+
+impl MultiImportReport {
+    pub fn get_entries(&self) -> u32 {
+        self.dats.iter().map(|dat| dat.report.entries).sum()
+    }
+
+    pub fn has_issues(&self) -> bool {
+        self.dats.iter().any(|dat| dat.report.has_issues())
+    }
+}
+
+impl Display for MultiImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_multi_import_report(f, self)
+    }
+}
+
+fn get_snippet(start: &BytesStart) -> String {
+    let tag = String::from_utf8_lossy(start);
+    let mut snippet = tag.chars().take(MAX_SNIPPET_LENGTH).collect::<String>();
+    if snippet.len() < tag.len() {
+        snippet.push_str("...");
+    }
+    format!("<{}>", snippet)
+}
+
+fn rate(entries: u32, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 { entries as f64 / seconds } else { 0.0 }
+}
+
+/// An entry parsed from the DAT, ready to be written
+enum DatEntry {
+    Game { game: Box<Game>, roms: Vec<DataFile>, disks: Vec<GameDisk>, samples: Vec<String>, device_refs: Vec<String> },
+    DatInfo(DatInfo),
+}
+
+fn write_entry<W: DataWriter>(writer: &mut W, entry: DatEntry, written: &AtomicU32) -> Result<()> {
+    match entry {
+        DatEntry::Game { game, roms, disks, samples, device_refs } => {
+            writer.on_new_entry(*game, roms, disks, samples, device_refs)?;
+            written.fetch_add(1, Ordering::Relaxed);
+        },
+        DatEntry::DatInfo(dat_info) => writer.on_dat_info(dat_info)?,
+    }
+
+    Ok(())
+}
+
 impl<W: DataWriter> DatImporter<BufReader<File>, W> {
     pub fn from_path(path: &impl AsRef<Path>, writer: W) ->Result<Self> {
         let total_bytes = fs::metadata(path)?.len();
-        Ok(DatImporter {
-            reader: Reader::from_file(path).unwrap(),
-            writer,
-            reporter: None,
-            total_bytes
-        })
+        Ok(DatImporter::new(Reader::from_file(path)?, writer, total_bytes))
     }
 }
 
 impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
+    fn new(reader: Reader<R>, writer: W, total_bytes: u64) -> Self {
+        DatImporter {
+            reader,
+            writer: Some(writer),
+            reporter: None,
+            total_bytes,
+            entry_sender: None,
+            parsed: 0,
+            written: Arc::new(AtomicU32::new(0)),
+            started: Instant::now(),
+            extended_metadata: false,
+            report: ImportReport::default(),
+            max_errors: None,
+            dirs: vec![],
+        }
+    }
+
+    /// The import fails once more than `max_errors` entries couldn't be read, with `None` it never fails because of them
+    pub fn set_max_errors(&mut self, max_errors: Option<u32>) {
+        self.max_errors = max_errors;
+    }
+
+    /// Also reads the driver status, players and display orientation of the games
+    pub fn set_extended_metadata(&mut self, extended_metadata: bool) {
+        self.extended_metadata = extended_metadata;
+    }
+
     pub fn set_reporter<P>(&mut self, mut reporter: P) where P: DatImporterReporter + 'static {
         reporter.set_total_bytes(self.total_bytes);
         self.reporter = Some(Box::new(reporter));
     }
 
     fn report_new_entry(&mut self, new_entries: u32) {
+        self.parsed += new_entries;
         let buf_pos = self.buf_pos() as u64;
+        let throughput = self.get_throughput();
         if let Some(reporter) = self.reporter.as_mut() {
             reporter.update_position(buf_pos, new_entries);
+            reporter.update_throughput(throughput);
+        }
+    }
+
+    /// Adds the issue to the report, fails if there are more errors than allowed
+    fn add_issue(&mut self, level: ImportIssueLevel, message: String, start: &BytesStart) -> Result<()> {
+        let position = self.buf_pos();
+        match level {
+            ImportIssueLevel::Warning => warn!("{} at position {}", message, position),
+            ImportIssueLevel::Error => error!("{} at position {}", message, position),
+        }
+        self.report.add_issue(ImportIssue { level, position: Some(position), message: message.clone(), snippet: get_snippet(start) });
+
+        match self.max_errors {
+            Some(max_errors) if self.report.errors > max_errors => err!(RomstError::GenericError {
+                message: format!("Too many errors importing the DAT ({} warnings, {} errors), the last at position {}: {}", self.report.warnings, self.report.errors, position, message)
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn get_throughput(&self) -> ImportThroughput {
+        ImportThroughput {
+            parsed: self.parsed,
+            written: self.written.load(Ordering::Relaxed),
+            elapsed: self.started.elapsed(),
         }
     }
 
@@ -52,11 +312,79 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
         self.reader.buffer_position()
     }
 
-    pub fn load_dat(&mut self) -> Result<()> {
+    /// Gives back the writer once the DAT is loaded, so it can go on with the next DAT
+    pub fn into_writer(self) -> Option<W> {
+        self.writer
+    }
+
+    fn get_writer(&mut self) -> Result<&mut W> {
+        match self.writer.as_mut() {
+            Some(writer) => Ok(writer),
+            None => err!(RomstError::GenericError { message: "The DAT writer is not available".to_string() }),
+        }
+    }
+
+    /// Sends the entry to the writer thread if there is one, otherwise it's written right away
+    fn on_entry(&mut self, entry: DatEntry) -> Result<()> {
+        if let Some(sender) = &self.entry_sender {
+            if sender.send(entry).is_err() {
+                return err!(RomstError::GenericError { message: "The writer stopped before the import finished".to_string() });
+            }
+            Ok(())
+        } else {
+            let written = Arc::clone(&self.written);
+            write_entry(self.get_writer()?, entry, &written)
+        }
+    }
+
+    pub fn load_dat(&mut self) -> Result<ImportReport> {
         self.reader.trim_text(true);
 
-        self.writer.init()?;
+        self.get_writer()?.init()?;
+        self.started = Instant::now();
 
+        self.parse_dat()?;
+        self.finish_writer()
+    }
+
+    fn finish_writer(&mut self) -> Result<ImportReport> {
+        let throughput = self.get_throughput();
+        if let Some(ref mut reporter) = self.reporter {
+            reporter.update_throughput(throughput);
+            reporter.start_finish();
+        }
+        let reporter = &mut self.reporter;
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return err!(RomstError::GenericError { message: "The DAT writer is not available".to_string() }),
+        };
+        writer.finish_with_progress(&mut |done, total| {
+            if let Some(reporter) = reporter.as_mut() {
+                reporter.update_finish_progress(done, total);
+            }
+        })?;
+        for conflict in self.get_writer()?.take_rom_conflicts() {
+            let message = format!("Rom `{}` of `{}` has the same sha1 as the rom with id {}, but a different size or crc, stored as a conflict",
+                conflict.rom_name, conflict.game_name, conflict.conflicts_with);
+            warn!("{}", message);
+            let snippet = format!("{} <> {}", conflict.info, conflict.conflicts_with_info);
+            self.report.add_issue(ImportIssue { level: ImportIssueLevel::Warning, position: None, message, snippet });
+        }
+        for game_name in self.get_writer()?.take_skipped_games() {
+            let message = format!("Game `{}` is already in the database, skipped", game_name);
+            warn!("{}", message);
+            self.report.add_issue(ImportIssue { level: ImportIssueLevel::Warning, position: None, message, snippet: game_name });
+        }
+        if let Some(ref reporter) = self.reporter {
+            reporter.finish();
+        }
+
+        let mut report = std::mem::take(&mut self.report);
+        report.entries = self.parsed;
+        Ok(report)
+    }
+
+    fn parse_dat(&mut self) -> Result<()> {
         let mut buf = Vec::new();
         loop {
             match self.reader.read_event(&mut buf)? {
@@ -64,26 +392,17 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
                     if let Ok(name) = str::from_utf8(e.name()) {
                         match name.to_lowercase().trim() {
                             "datafile" => {
-                                self.read_datafile()?;
+                                self.read_datafile("datafile")?;
                             },
                             "mame" => {
-                                self.read_mame_header(e.attributes());
-                                self.read_datafile()?;
+                                self.read_mame_header(e)?;
+                                self.read_datafile("mame")?;
                             },
                             _ => {} 
                         }
                     }
                 },
-                Event::Eof => {
-                    if let Some(ref reporter) = self.reporter {
-                        reporter.start_finish();
-                    }
-                    self.writer.finish()?;
-                    if let Some(ref reporter) = self.reporter {
-                        reporter.finish();
-                    }
-                    break
-                }, 
+                Event::Eof => break,
                 _ => (),
             }
             // if we don't keep a borrow elsewhere, we can clear the buffer to keep memory usage low
@@ -93,26 +412,29 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
         Ok(())
     }
 
-    fn read_datafile(&mut self) -> Result<()> {
+    /// Reads the entries until the root tag, either `datafile` or `mame`, is closed. The entries can be nested in `dir`
+    /// elements, read recursively
+    fn read_datafile(&mut self, root_tag: &str) -> Result<()> {
         let mut buf = Vec::new();
         loop {
             match self.reader.read_event(&mut buf)? {
                 Event::Start(ref e) => {
                     if let Ok(name) = str::from_utf8(e.name()) {
                         match name.to_lowercase().trim() {
-                            "machine" | "game" => self.read_game_entry( String::from_utf8(e.name().to_vec())?, e.attributes())?,
+                            "machine" | "game" => self.read_game_entry(e)?,
                             "header" => self.read_dat_header()?,
+                            "dir" => self.read_dir(e)?,
                             tag_name => self.consume_tag(tag_name.to_string())?,
                         }
                     }
                 },
                 Event::End(e) => {
                     if let Ok(name) = str::from_utf8(e.name()){
-                        if name.to_lowercase().trim() == "datafile" {
+                        if name.to_lowercase().trim() == root_tag {
                             return Ok(());
                         } else {
                             return err!(RomstError::UnexpectedTagClose { 
-                                expected: String::from("datafile"),
+                                expected: String::from(root_tag),
                                 found: String::from_utf8(e.name().to_vec())?,
                                 position: self.buf_pos() });
                         }
@@ -125,6 +447,33 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
         }
     }
 
+    /// The games in the `dir` get its name, after the names of the parent `dir` elements, as category
+    fn read_dir(&mut self, start: &BytesStart) -> Result<()> {
+        let entry_type = String::from_utf8(start.name().to_vec())?;
+        let mut dir_name = None;
+        let result = process_attributes(start.attributes(), |key, value| {
+            if key.to_lowercase().trim() == "name" {
+                dir_name = Some(value.trim().to_string());
+            }
+        });
+        if let Err(e) = result {
+            self.add_issue(ImportIssueLevel::Warning, format!("Error reading the {}: {}", entry_type, e), start)?;
+        }
+
+        match dir_name.filter(|dir_name| !dir_name.is_empty()) {
+            Some(dir_name) => {
+                self.dirs.push(dir_name);
+                let result = self.read_datafile(&entry_type.to_lowercase());
+                self.dirs.pop();
+                result
+            }
+            None => {
+                self.add_issue(ImportIssueLevel::Warning, format!("The {} has no name, the entries are read without category", entry_type), start)?;
+                self.read_datafile(&entry_type.to_lowercase())
+            }
+        }
+    }
+
     // Reads through a tag moving the reader until it closes the tag
     fn consume_tag(&mut self, tag_name: String) -> Result<()> {
         let mut buf = Vec::new();
@@ -155,10 +504,10 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
 
     fn get_text(&mut self) -> Result<String> {
         let mut buf = Vec::new();
-        let text;
-        match self.reader.read_event(&mut buf)? {
+        
+        let text = match self.reader.read_event(&mut buf)? {
             Event::Text(t) => {
-                text = t.unescape_and_decode(&self.reader)?.trim().to_string()
+                t.unescape_and_decode(&self.reader)?.trim().to_string()
             },
             Event::End(_e) => {
                 return Ok(String::from(""));
@@ -167,19 +516,19 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
             _e => return err!(RomstError::UnexpectedXMLTag {
                 position: self.reader.buffer_position()
             }),
-        }
+        };
 
         match self.reader.read_event(&mut buf)? {
             Event::End(_e) => {
                 Ok(text)
             },
-            Event::Eof => return err!(RomstError::UnexpectedEOF),
-            _e => return err!(RomstError::UnexpectedXMLTag { position: self.reader.buffer_position() }),
+            Event::Eof => err!(RomstError::UnexpectedEOF),
+            _e => err!(RomstError::UnexpectedXMLTag { position: self.reader.buffer_position() }),
         }
     }
 
-    fn read_mame_header(&mut self, attributes: Attributes) {
-        process_attributes(attributes, |key, value| {
+    fn read_mame_header(&mut self, start: &BytesStart) -> Result<()> {
+        let result = process_attributes(start.attributes(), |key, value| {
             let val = value.trim();
             match key.to_lowercase().trim() {
                 "build" => {
@@ -197,6 +546,11 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
                 k => debug!("Unknown atribute parsing: {}", k),
             }
         });
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => self.add_issue(ImportIssueLevel::Warning, format!("Error reading the mame header: {}", e), start),
+        }
     }
 
     fn read_dat_header(&mut self) -> Result<()> {
@@ -238,11 +592,22 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
                         }
                     }
                     let dat_info = DatInfo::new(name, desc, ver, extras);
-                    self.writer.on_dat_info(dat_info)?;
+                    self.on_entry(DatEntry::DatInfo(dat_info))?;
                 },
                 Event::End(_) => break,
                 Event::Eof => panic!("Unexpected end of file"),
                 
+                // The clrmamepro element has the options of the DAT as attributes, like `forcepacking`
+                Event::Empty(ref e) if e.name().eq_ignore_ascii_case(b"clrmamepro") => {
+                    let mut extras = vec![];
+                    let result = process_attributes(e.attributes(), |key, value| {
+                        extras.push((key.to_lowercase(), value.to_string()));
+                    });
+                    if let Err(error) = result {
+                        self.add_issue(ImportIssueLevel::Warning, format!("Error reading the clrmamepro header: {}", error), e)?;
+                    }
+                    self.on_entry(DatEntry::DatInfo(DatInfo::new(String::new(), String::new(), String::new(), extras)))?;
+                }
                 Event::Empty(_) => {}
                 Event::Text(_) => {}
                 Event::Comment(_) => {}
@@ -256,14 +621,36 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
         Ok(())
     }
 
-    fn read_game_entry(&mut self, entry_type: String, attributes: Attributes) -> Result<()> {
-        let mut game = game_from_attributes(attributes)?;
+    /// Reads a game, a game that can't be read or elements of it that can't be read are added to the report and skipped
+    fn read_game_entry(&mut self, start: &BytesStart) -> Result<()> {
+        let entry_type = String::from_utf8(start.name().to_vec())?;
+        let mut game = match game_from_attributes(start.attributes()) {
+            Ok(game) => game,
+            Err(e) => {
+                self.add_issue(ImportIssueLevel::Error, format!("Skipping {}: {}", entry_type, e), start)?;
+                return self.consume_tag(entry_type);
+            }
+        };
+        if !self.dirs.is_empty() {
+            game.category = Some(self.dirs.join("/"));
+        }
+        let mut metadata = GameMetadata::default();
+        let extended_metadata = self.extended_metadata;
 
         let mut roms = vec![];
         let mut samples = vec![];
         let mut disks = vec![];
         let mut devices = vec![];
 
+        let mut add_element = |element| {
+            match element {
+                GameElement::Rom(rom) => roms.push(rom),
+                GameElement::Disk(disk) => disks.push(disk),
+                GameElement::Sample(sample) => samples.push(sample),
+                GameElement::Device(device) => devices.push(device),
+            }
+        };
+
         let mut buf = Vec::new();
         loop {
             match self.reader.read_event(&mut buf)? {
@@ -282,34 +669,35 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
                                 let manuf = self.get_text()?;
                                 game.info_manufacturer = Some(manuf);
                             },
-                            n => self.consume_tag(n.trim().to_string())?
+                            n => {
+                                if extended_metadata {
+                                    if let Err(error) = read_metadata_attributes(n, e.attributes(), &mut metadata) {
+                                        self.add_issue(ImportIssueLevel::Warning, format!("Error reading the metadata of `{}`: {}", game.name, error), e)?;
+                                    }
+                                }
+                                // Some DATs write the elements as `<rom ...></rom>`
+                                match game_element_from_attributes(n, e.attributes()) {
+                                    Ok(Some(element)) => add_element(element),
+                                    Ok(None) => {},
+                                    Err(error) => self.add_issue(ImportIssueLevel::Error, format!("Skipping {} of `{}`: {}", n, game.name, error), e)?,
+                                }
+                                self.consume_tag(n.trim().to_string())?
+                            }
                         }
                     }
                 },
                 Event::Empty(e) => {
                     if let Ok(name) = str::from_utf8(e.name()) {
-                        match name.to_lowercase().trim() {
-                            "rom" => {
-                                let rom = file_from_attributes(FileType::Rom, e.attributes())?;
-                                roms.push(rom);
-                            },
-                            "sample" => {
-                                let sample = device_ref(e.attributes());
-                                if let Some(sample_name) = sample {
-                                    samples.push(sample_name);
-                                }
-                            },
-                            "disk" => {
-                                let disk = disk_from_attributes(e.attributes())?;
-                                disks.push(disk);
-                            },
-                            "device_ref" => {
-                                let device = device_ref(e.attributes());
-                                if let Some(device_name) = device {
-                                    devices.push(device_name);
-                                }
-                            },
-                            _ => ()
+                        let name = name.to_lowercase();
+                        if extended_metadata {
+                            if let Err(error) = read_metadata_attributes(name.trim(), e.attributes(), &mut metadata) {
+                                self.add_issue(ImportIssueLevel::Warning, format!("Error reading the metadata of `{}`: {}", game.name, error), &e)?;
+                            }
+                        }
+                        match game_element_from_attributes(name.trim(), e.attributes()) {
+                            Ok(Some(element)) => add_element(element),
+                            Ok(None) => {},
+                            Err(error) => self.add_issue(ImportIssueLevel::Error, format!("Skipping {} of `{}`: {}", name.trim(), game.name, error), &e)?,
                         }
                     }
                 },
@@ -328,15 +716,108 @@ impl<R: BufRead, W: DataWriter> DatImporter<R, W> {
             buf.clear();
         }
 
-        self.writer.on_new_entry(game, roms, disks, samples, devices)?;
+        if extended_metadata {
+            game.metadata = Some(Box::new(metadata));
+        }
+
+        self.on_entry(DatEntry::Game { game: Box::new(game), roms, disks, samples, device_refs: devices })?;
         self.report_new_entry(1);
 
         Ok(())
     }
 }
 
+impl<R: BufRead, W: DataWriter + Send> DatImporter<R, W> {
+    /// Same as `load_dat`, but the entries are written in a separate thread while the DAT is parsed
+    pub fn load_dat_threaded(&mut self) -> Result<ImportReport> {
+        self.reader.trim_text(true);
+
+        let mut writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => return err!(RomstError::GenericError { message: "The DAT writer is not available".to_string() }),
+        };
+        writer.init()?;
+        self.started = Instant::now();
+
+        let written = Arc::clone(&self.written);
+        let (sender, receiver) = sync_channel::<DatEntry>(ENTRY_CHANNEL_SIZE);
+        let (parse_result, writer_result) = thread::scope(|scope| {
+            let writer_thread = scope.spawn(move || -> Result<W> {
+                for entry in receiver {
+                    write_entry(&mut writer, entry, &written)?;
+                }
+                Ok(writer)
+            });
+
+            self.entry_sender = Some(sender);
+            let parse_result = self.parse_dat();
+            // Closing the channel lets the writer finish
+            self.entry_sender = None;
+
+            let writer_result = writer_thread.join().unwrap_or_else(|_| {
+                err!(RomstError::GenericError { message: "The writer thread panicked".to_string() })
+            });
+            (parse_result, writer_result)
+        });
+
+        // If the writer failed, the parser only knows it couldn't send more entries
+        self.writer = Some(writer_result?);
+        parse_result?;
+        self.finish_writer()
+    }
+}
+
 // Helper functions
-fn process_attributes<F>(attributes: Attributes, mut f: F) where F: FnMut(&str, &str) {
+enum GameElement {
+    Rom(DataFile),
+    Disk(GameDisk),
+    Sample(String),
+    Device(String),
+}
+
+/// Parses the elements inside a game, either written as an empty element or as a start/end pair
+fn game_element_from_attributes(name: &str, attributes: Attributes) -> Result<Option<GameElement>> {
+    let element = match name {
+        "rom" => Some(GameElement::Rom(file_from_attributes(FileType::Rom, attributes)?)),
+        "disk" => Some(GameElement::Disk(disk_from_attributes(attributes)?)),
+        "sample" => device_ref(attributes)?.map(GameElement::Sample),
+        "device_ref" => device_ref(attributes)?.map(GameElement::Device),
+        _ => None
+    };
+
+    Ok(element)
+}
+
+/// Reads the machine metadata from the `driver`, `input` and `display` elements, only the first display is used
+fn read_metadata_attributes(name: &str, attributes: Attributes, metadata: &mut GameMetadata) -> Result<()> {
+    match name {
+        "driver" => process_attributes(attributes, |key, value| {
+            match key.to_lowercase().as_str() {
+                "status" => metadata.driver_status = Some(value.to_lowercase()),
+                "emulation" => metadata.emulation_status = Some(value.to_lowercase()),
+                k => debug!("Unknown atribute parsing: {}", k),
+            }
+        }),
+        "input" => process_attributes(attributes, |key, value| {
+            match key.to_lowercase().as_str() {
+                "players" => metadata.players = value.parse::<u32>().ok(),
+                k => debug!("Unknown atribute parsing: {}", k),
+            }
+        }),
+        "display" if metadata.display_type.is_none() && metadata.orientation.is_none() => process_attributes(attributes, |key, value| {
+            match key.to_lowercase().as_str() {
+                "type" => metadata.display_type = Some(value.to_lowercase()),
+                "rotate" => metadata.orientation = GameMetadata::orientation_from_rotation(value),
+                k => debug!("Unknown atribute parsing: {}", k),
+            }
+        }),
+        _ => Ok(())
+    }
+}
+
+/// Calls `f` with every attribute that can be read, fails at the end if any of them couldn't
+fn process_attributes<F>(attributes: Attributes, mut f: F) -> Result<()> where F: FnMut(&str, &str) {
+    let mut errors = vec![];
     attributes.for_each(|a| {
         match a {
             Ok(a) => {
@@ -345,51 +826,63 @@ fn process_attributes<F>(attributes: Attributes, mut f: F) where F: FnMut(&str,
 
                 match (key, value) {
                     (Ok(k), Ok(v)) => f(k.trim(), v.trim()),
-                    (Err(e), Ok(_)) | (Ok(_), Err(e)) => error!("Error reading attributes: {}", e),
-                    (Err(e1), Err(e2)) => error!("Error reading attributes: {}, {}", e1, e2),
+                    (Err(e), Ok(_)) | (Ok(_), Err(e)) => errors.push(e.to_string()),
+                    (Err(e1), Err(e2)) => errors.push(format!("{}, {}", e1, e2)),
                 }
             },
-            Err(e) => {
-                error!("Error reading attributes: {}", e)
-            }
+            Err(e) => errors.push(e.to_string()),
         }
     });
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        err!(RomstError::ParsingError { message: format!("Error reading attributes: {}", errors.join("; ")) })
+    }
 }
 
-fn device_ref(attributes: Attributes) -> Option<String> {
+fn device_ref(attributes: Attributes) -> Result<Option<String>> {
     let mut device_name = None;
     process_attributes(attributes, |key, value| {
         match key.to_lowercase().as_str() {
             "name" => device_name = Some(String::from(value)),
             k => debug!("Unknown atribute parsing: {}", k),
         }
-    });
+    })?;
 
-    device_name
+    Ok(device_name)
 }
 
 fn file_from_attributes(file_type: FileType, attributes: Attributes) -> Result<DataFile> {
     let mut data_file_info = DataFileInfo::new(file_type);
     let mut file_name = None;
     let mut status = None;
+    let mut bios = None;
+    let mut extra_attrs = BTreeMap::new();
 
     process_attributes(attributes, |key, value| {
         match key.to_lowercase().as_str() {
             "name" => file_name = Some(value.to_string()),
+            "bios" => bios = Some(String::from(value)),
             "sha1" => data_file_info.sha1 = Some(String::from(value)),
             "md5" => data_file_info.md5 = Some(String::from(value)),
             "crc" => data_file_info.crc = Some(String::from(value).to_lowercase()),
             "size" => data_file_info.size = value.parse::<u32>().ok(),
-            "serial" => debug!("Ignoring serial attribute from file"),
             "status" => status = Some(String::from(value)),
-            k => debug!("Unknown atribute parsing: {}", k),
+            "merge" => debug!("Ignoring merge attribute, the roms shared with the parent are found when importing"),
+            // Kept as they are, so they are not lost, e.g. `region`, `offset`, `date` or `serial`
+            _ => { extra_attrs.insert(key.to_string(), value.to_string()); },
         }
-    });
+    })?;
 
     if let Some(name) = file_name {
-        Ok(DataFile::new_with_status(name, data_file_info, status))
+        let mut data_file = DataFile::new_with_status(name, data_file_info, status);
+        data_file.bios = bios;
+        if !extra_attrs.is_empty() {
+            data_file.extra_attrs = Some(extra_attrs);
+        }
+        Ok(data_file)
     } else {
-        error!("Found file without name, not adding");
         err!(RomstError::ParsingError { message: "File without name".to_string() })
     }
 }
@@ -406,14 +899,13 @@ fn disk_from_attributes(attributes: Attributes) -> Result<GameDisk> {
             "status" => disk_info.status = Some(String::from(value).to_lowercase()),
             k => debug!("Unknown atribute parsing: {}", k),
         }
-    });
+    })?;
 
     if let Some(name) = disk_name {
         let mut disk = GameDisk::new(name);
         disk.info = disk_info;
         Ok(disk)
     } else {
-        error!("Found disk without name, not adding");
         err!(RomstError::ParsingError { message: "Disk without name".to_string() })
     }
 }
@@ -430,9 +922,9 @@ fn game_from_attributes(attributes: Attributes) -> Result<Game> {
             "sampleof" => game.sample_of = Some(String::from(value)),
             k => debug!("Unknown atribute parsing: {}", k),
         }
-    });
+    })?;
 
-    if game.name == "" {
+    if game.name.is_empty() {
         return err!(RomstError::ParsingError { message: String::from("Missing name attribute for Game") });
     }
 
@@ -445,9 +937,19 @@ mod tests {
 
     use super::*;
 
+    pub struct GameContents {
+        pub roms: Vec<DataFile>,
+        pub disks: Vec<GameDisk>,
+        pub samples: Vec<String>,
+        pub device_refs: Vec<String>,
+    }
+
     pub struct MemoryWriter {
         pub initialized: Rc<RefCell<bool>>,
         pub games: Rc<RefCell<Vec<String>>>,
+        pub contents: Rc<RefCell<Vec<GameContents>>>,
+        pub metadata: Rc<RefCell<Vec<Option<Box<GameMetadata>>>>>,
+        pub categories: Rc<RefCell<Vec<Option<String>>>>,
     }
 
     impl MemoryWriter {
@@ -455,6 +957,9 @@ mod tests {
             MemoryWriter {
                 initialized: Rc::new(RefCell::new(false)),
                 games: Rc::new(RefCell::new(vec![])),
+                contents: Rc::new(RefCell::new(vec![])),
+                metadata: Rc::new(RefCell::new(vec![])),
+                categories: Rc::new(RefCell::new(vec![])),
             }
         }
     }
@@ -469,8 +974,11 @@ mod tests {
             Ok(())
         }
 
-        fn on_new_entry(&mut self, game: Game, _roms: Vec<DataFile>, _disks: Vec<GameDisk>, _samples: Vec<String>, _device_refs: Vec<String>) -> Result<()> {
+        fn on_new_entry(&mut self, game: Game, roms: Vec<DataFile>, disks: Vec<GameDisk>, samples: Vec<String>, device_refs: Vec<String>) -> Result<()> {
+            self.metadata.borrow_mut().push(game.metadata);
+            self.categories.borrow_mut().push(game.category);
             self.games.borrow_mut().push(game.name);
+            self.contents.borrow_mut().push(GameContents { roms, disks, samples, device_refs });
 
             Ok(())
         }
@@ -497,4 +1005,264 @@ mod tests {
         
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn load_from_str(xml: &str) -> Result<Vec<GameContents>> {
+        let writer = MemoryWriter::new();
+        let contents = Rc::clone(&writer.contents);
+        let mut importer = DatImporter::new(Reader::from_str(xml), writer, 0);
+        importer.load_dat()?;
+
+        Ok(contents.replace(vec![]))
+    }
+
+    #[test]
+    fn threaded_import_writes_all_entries() -> Result<()> {
+        use rusqlite::{Connection, OpenFlags};
+        use crate::data::{reader::{DataReader, sqlite::DBReader}, writer::sqlite::DBWriter};
+
+        let path = Path::new("testdata").join("test.dat");
+        let mut games = vec![];
+        for threaded in [false, true] {
+            let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+            let writer = DBWriter::from_connection(&mut conn, 5);
+            let mut importer = DatImporter::from_path(&path, writer)?;
+            if threaded {
+                importer.load_dat_threaded()?;
+            } else {
+                importer.load_dat()?;
+            }
+            let throughput = importer.get_throughput();
+            assert_eq!(throughput.parsed, throughput.written);
+
+            let reader = DBReader::from_connection(conn);
+            assert_eq!(reader.get_db_info()?.games, throughput.written);
+            games.push(reader.get_game_list(crate::RomsetMode::NonMerged)?);
+        }
+        assert_eq!(games[0], games[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_the_games_nested_in_dirs() -> Result<()> {
+        let writer = MemoryWriter::new();
+        let games = Rc::clone(&writer.games);
+        let categories = Rc::clone(&writer.categories);
+
+        let path = Path::new("testdata").join("nested.dat");
+        let mut importer = DatImporter::from_path(&path, writer)?;
+        let report = importer.load_dat()?;
+
+        assert_eq!(vec!["game1", "game2", "game3", "game4"], *games.borrow());
+        assert_eq!(vec![
+            None,
+            Some("Homebrew".to_string()),
+            Some("Homebrew/Demos".to_string()),
+            Some("Prototypes".to_string()),
+        ], *categories.borrow());
+        assert_eq!(4, report.entries);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_extended_metadata() -> Result<()> {
+        let xml = r#"<mame>
+            <machine name="game1">
+                <description>Game 1</description>
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f"/>
+                <display tag="screen" type="raster" rotate="270" width="224" height="256"/>
+                <display tag="screen2" type="lcd" rotate="0"/>
+                <input players="2" coins="1">
+                    <control type="joy" ways="8"/>
+                </input>
+                <driver status="imperfect" emulation="good" savestate="supported"/>
+            </machine>
+        </mame>"#;
+
+        for extended_metadata in [false, true] {
+            let writer = MemoryWriter::new();
+            let metadata = Rc::clone(&writer.metadata);
+            let mut importer = DatImporter::new(Reader::from_str(xml), writer, 0);
+            importer.set_extended_metadata(extended_metadata);
+            importer.load_dat()?;
+
+            let metadata = metadata.replace(vec![]);
+            if extended_metadata {
+                assert_eq!(metadata, vec![Some(Box::new(GameMetadata {
+                    driver_status: Some("imperfect".to_string()),
+                    emulation_status: Some("good".to_string()),
+                    players: Some(2),
+                    orientation: Some("vertical".to_string()),
+                    display_type: Some("raster".to_string()),
+                }))]);
+            } else {
+                assert_eq!(metadata, vec![None]);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_empty_and_non_empty_elements() -> Result<()> {
+        let empty = r#"<datafile>
+            <game name="game1">
+                <description>Game 1</description>
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f"/>
+                <rom name="rom2.rom" size="2048" crc="1f2b3c4d"/>
+                <disk name="disk1" sha1="0123456789abcdef0123456789abcdef01234567"/>
+                <sample name="sample1"/>
+                <device_ref name="device1"/>
+            </game>
+        </datafile>"#;
+        let non_empty = r#"<datafile>
+            <game name="game1">
+                <description>Game 1</description>
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f"></rom>
+                <rom name="rom2.rom" size="2048" crc="1f2b3c4d"></rom>
+                <disk name="disk1" sha1="0123456789abcdef0123456789abcdef01234567"></disk>
+                <sample name="sample1"></sample>
+                <device_ref name="device1"></device_ref>
+            </game>
+        </datafile>"#;
+
+        for xml in [empty, non_empty] {
+            let contents = load_from_str(xml)?;
+            assert_eq!(contents.len(), 1);
+            let game = &contents[0];
+            assert_eq!(game.roms.iter().map(|rom| rom.name.as_str()).collect::<Vec<_>>(), vec!["rom1.rom", "rom2.rom"]);
+            assert_eq!(game.roms[0].info.crc, Some("8a1e7c8f".to_string()));
+            assert_eq!(game.disks.len(), 1);
+            assert_eq!(game.samples, vec!["sample1"]);
+            assert_eq!(game.device_refs, vec!["device1"]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_extra_rom_attributes() -> Result<()> {
+        let xml = r#"<mame>
+            <machine name="game1">
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f" region="maincpu" offset="0"/>
+                <rom name="rom2.rom" size="2048" crc="1f2b3c4d"/>
+            </machine>
+        </mame>"#;
+
+        let contents = load_from_str(xml)?;
+        let roms = &contents[0].roms;
+        assert_eq!(Some("maincpu"), roms[0].get_extra_attr("region"));
+        assert_eq!(Some("0"), roms[0].get_extra_attr("offset"));
+        assert_eq!(2, roms[0].extra_attrs.as_ref().unwrap().len());
+        assert_eq!(None, roms[1].extra_attrs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_and_skips_unreadable_entries() -> Result<()> {
+        let xml = r#"<datafile>
+            <game name="game1">
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f"/>
+                <rom size="1024" crc="1f2b3c4d"/>
+            </game>
+            <game cloneof="game1">
+                <rom name="rom2.rom" size="2048" crc="8a1e7c8f"/>
+            </game>
+            <game name="game2">
+                <disk sha1="0123456789abcdef0123456789abcdef01234567"></disk>
+            </game>
+        </datafile>"#;
+
+        let writer = MemoryWriter::new();
+        let games = Rc::clone(&writer.games);
+        let contents = Rc::clone(&writer.contents);
+        let mut importer = DatImporter::new(Reader::from_str(xml), writer, 0);
+        let report = importer.load_dat()?;
+
+        assert_eq!(*games.borrow(), vec!["game1", "game2"]);
+        assert_eq!(contents.borrow()[0].roms.len(), 1);
+        assert_eq!(contents.borrow()[1].disks.len(), 0);
+        assert_eq!(report.entries, 2);
+        assert_eq!(report.errors, 3);
+        assert_eq!(report.warnings, 0);
+        assert!(report.issues[0].message.contains("game1"));
+        assert_eq!(report.issues[0].snippet, r#"<rom size="1024" crc="1f2b3c4d">"#);
+        assert!(report.issues[1].snippet.starts_with("<game cloneof"));
+
+        let mut importer = DatImporter::new(Reader::from_str(xml), MemoryWriter::new(), 0);
+        importer.set_max_errors(Some(2));
+        assert!(importer.load_dat().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_roms_with_conflicting_hashes() -> Result<()> {
+        use rusqlite::{Connection, OpenFlags};
+        use crate::data::writer::sqlite::{DBWriter, ROM_CONFLICTS_TABLE};
+
+        let xml = r#"<datafile>
+            <game name="game1">
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f" sha1="0123456789abcdef0123456789abcdef01234567"/>
+            </game>
+            <game name="game2">
+                <rom name="rom2.rom" size="1024" crc="8a1e7c8f" sha1="0123456789abcdef0123456789abcdef01234567"/>
+                <rom name="rom3.rom" crc="8a1e7c8f" sha1="0123456789abcdef0123456789abcdef01234567"/>
+            </game>
+        </datafile>"#;
+
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        let writer = DBWriter::from_connection(&mut conn, 1);
+        let mut importer = DatImporter::new(Reader::from_str(xml), writer, 0);
+        let report = importer.load_dat()?;
+
+        assert_eq!(report.warnings, 1);
+        assert_eq!(report.issues[0].position, None);
+        assert!(report.issues[0].message.contains("rom2.rom"));
+
+        let conflicts: Vec<(String, u32)> = conn.prepare(&format!("SELECT name, size FROM {};", ROM_CONFLICTS_TABLE))?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(conflicts, vec![("rom2.rom".to_string(), 1024)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_finish_progress() -> Result<()> {
+        use rusqlite::{Connection, OpenFlags};
+        use crate::data::writer::sqlite::DBWriter;
+
+        struct FinishReporter {
+            progress: Rc<RefCell<Vec<(u64, u64)>>>,
+        }
+
+        impl DatImporterReporter for FinishReporter {
+            fn set_total_bytes(&mut self, _total_bytes: u64) {}
+            fn update_position(&mut self, _bytes: u64, _new_entries: u32) {}
+            fn update_throughput(&mut self, _throughput: ImportThroughput) {}
+            fn start_finish(&self) {}
+            fn update_finish_progress(&mut self, done: u64, total: u64) {
+                self.progress.borrow_mut().push((done, total));
+            }
+            fn finish(&self) {}
+        }
+
+        let progress = Rc::new(RefCell::new(vec![]));
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        let writer = DBWriter::from_connection(&mut conn, 5);
+        let mut importer = DatImporter::from_path(&Path::new("testdata").join("test.dat"), writer)?;
+        importer.set_reporter(FinishReporter { progress: Rc::clone(&progress) });
+        importer.load_dat()?;
+
+        let progress = progress.borrow();
+        let (_, total) = progress[0];
+        assert!(total > 0);
+        assert_eq!(Some(&(0, total)), progress.first());
+        assert_eq!(Some(&(total, total)), progress.last());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use std::{env, ffi::OsString, fs, io::{Cursor, Read}, path::{Path, PathBuf}, process::{self, Command}, sync::atomic::{AtomicU32, Ordering}};
+
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use log::{debug, warn};
+use zip::ZipArchive;
+
+use crate::{err, error::RomstError, filesystem::sha1::SHA1Hasher};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZIP_MAGIC: [u8; 4] = [b'P', b'K', 0x03, 0x04];
+/// The protocols a DAT is downloaded with, also when following a redirect
+const ALLOWED_SCHEMES: [&str; 2] = ["http", "https"];
+
+static DOWNLOAD_COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// A DAT downloaded to a temporary directory, the directory is removed when dropped
+#[derive(Debug)]
+pub struct RemoteDat {
+    directory: PathBuf,
+    path: PathBuf,
+}
+
+impl RemoteDat {
+    /// Downloads the DAT from the url, it can be a plain DAT, gzipped, or inside a zip.
+    /// If `expected_sha1` is set, it's checked against the sha1 of the downloaded file, before decompressing it.
+    /// The download uses the `curl` command, only with http or https
+    pub fn fetch(url: &str, expected_sha1: Option<&str>) -> Result<Self> {
+        RemoteDat::fetch_with(url, expected_sha1, download_with_curl)
+    }
+
+    fn fetch_with<D>(url: &str, expected_sha1: Option<&str>, download_file: D) -> Result<Self> where D: FnOnce(&str, &Path) -> Result<()> {
+        check_url(url)?;
+        let directory = env::temp_dir().join(format!("romst_dat_{}_{}", process::id(), DOWNLOAD_COUNT.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&directory)?;
+        let remote_dat = RemoteDat { path: directory.join(get_dat_name(url)), directory };
+
+        let download = remote_dat.directory.join("download");
+        debug!("Downloading {} to {}", url, download.to_string_lossy());
+        download_file(url, &download)?;
+
+        let bytes = fs::read(&download)?;
+        if let Some(expected_sha1) = expected_sha1 {
+            let sha1 = SHA1Hasher::new().get_hash(&bytes);
+            if sha1 != expected_sha1.trim().to_lowercase() {
+                return err!(RomstError::GenericError { message: format!("The sha1 of `{}` is {}, expected {}", url, sha1, expected_sha1) });
+            }
+        }
+
+        fs::write(&remote_dat.path, decode_dat(bytes)?)?;
+
+        Ok(remote_dat)
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RemoteDat {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.directory) {
+            warn!("Error removing the downloaded DAT at `{}`: {}", self.directory.to_string_lossy(), e);
+        }
+    }
+}
+
+/// Only urls with the allowed schemes are downloaded, `curl` would read other protocols like `file://` too
+fn check_url(url: &str) -> Result<()> {
+    let scheme = url.split_once("://").map(|(scheme, _rest)| scheme.to_lowercase());
+    match scheme {
+        Some(scheme) if ALLOWED_SCHEMES.contains(&scheme.as_str()) => Ok(()),
+        _ => err!(RomstError::GenericError { message: format!("Wrong url `{}`, only {} urls can be downloaded", url, ALLOWED_SCHEMES.join(" and ")) }),
+    }
+}
+
+/// The url is given with `--url`, so it's never taken as an option
+fn get_curl_args(url: &str, download: &Path) -> Vec<OsString> {
+    let protocols = format!("={}", ALLOWED_SCHEMES.join(","));
+    let mut args = ["--fail", "--silent", "--show-error", "--location", "--proto", &protocols, "--proto-redir", &protocols]
+        .iter()
+        .map(OsString::from)
+        .collect::<Vec<_>>();
+    args.extend([OsString::from("--output"), download.as_os_str().to_owned(), OsString::from("--url"), OsString::from(url)]);
+    args
+}
+
+fn download_with_curl(url: &str, download: &Path) -> Result<()> {
+    let output = match Command::new("curl").args(get_curl_args(url, download)).output() {
+        Ok(output) => output,
+        Err(e) => return err!(RomstError::GenericError { message: format!("Couldn't run `curl` to download the DAT: {}", e) }),
+    };
+    if !output.status.success() {
+        return err!(RomstError::GenericError { message: format!("Error downloading `{}`: {}", url, String::from_utf8_lossy(&output.stderr).trim()) });
+    }
+    Ok(())
+}
+
+/// The name of the DAT file from the last part of the url, without the compression extension
+pub fn get_dat_name(url: &str) -> String {
+    let name = url.split(['?', '#']).next().unwrap_or(url)
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let name = name.trim_end_matches(".gz").trim_end_matches(".zip");
+    if name.is_empty() {
+        "remote.dat".to_string()
+    } else if Path::new(name).extension().is_none() {
+        format!("{}.dat", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Decompresses the DAT if it's gzipped or zipped, a zip must contain a single file or a single `.dat` or `.xml`
+fn decode_dat(bytes: Vec<u8>) -> Result<Vec<u8>> {
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut dat = vec![];
+        GzDecoder::new(bytes.as_slice()).read_to_end(&mut dat)?;
+        Ok(dat)
+    } else if bytes.starts_with(&ZIP_MAGIC) {
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let names = archive.file_names().map(|name| name.to_string()).collect::<Vec<_>>();
+        let dat_names = names.iter().filter(|name| {
+            let name = name.to_lowercase();
+            name.ends_with(".dat") || name.ends_with(".xml")
+        }).cloned().collect::<Vec<_>>();
+        let name = match (names.as_slice(), dat_names.as_slice()) {
+            ([name], _) | (_, [name]) => name.to_string(),
+            _ => return err!(RomstError::GenericError { message: format!("Expected a single DAT in the zip, found: {}", names.join(", ")) }),
+        };
+        let mut dat = vec![];
+        archive.by_name(&name)?.read_to_end(&mut dat)?;
+        Ok(dat)
+    } else {
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use flate2::{Compression, write::GzEncoder};
+    use zip::{ZipWriter, write::FileOptions};
+    use super::*;
+
+    #[test]
+    fn gets_dat_names() {
+        assert_eq!("mame.dat", get_dat_name("https://example.com/dats/mame.dat"));
+        assert_eq!("mame.dat", get_dat_name("https://example.com/dats/mame.dat.gz?version=1"));
+        assert_eq!("mame.xml", get_dat_name("https://example.com/mame.xml.zip"));
+        assert_eq!("latest.dat", get_dat_name("https://example.com/latest"));
+    }
+
+    #[test]
+    fn decodes_compressed_dats() -> Result<()> {
+        let dat = fs::read(Path::new("testdata").join("test.dat"))?;
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&dat)?;
+        assert_eq!(dat, decode_dat(encoder.finish()?)?);
+
+        let mut zip_writer = ZipWriter::new(Cursor::new(vec![]));
+        zip_writer.start_file("readme.txt", FileOptions::default())?;
+        zip_writer.write_all(b"readme")?;
+        zip_writer.start_file("test.dat", FileOptions::default())?;
+        zip_writer.write_all(&dat)?;
+        assert_eq!(dat, decode_dat(zip_writer.finish()?.into_inner())?);
+
+        assert_eq!(dat, decode_dat(dat.clone())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fetches_and_checks_the_sha1() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let url = "https://example.com/dats/test.dat";
+        let sha1 = SHA1Hasher::new().get_hash(&fs::read(&path)?);
+        // The download is served from the test data instead of running curl
+        let copy_test_dat = |_url: &str, download: &Path| -> Result<()> {
+            fs::copy(&path, download)?;
+            Ok(())
+        };
+
+        let remote_dat = RemoteDat::fetch_with(url, Some(&sha1), copy_test_dat)?;
+        assert_eq!(fs::read(&path)?, fs::read(remote_dat.get_path())?);
+        let directory = remote_dat.directory.to_owned();
+        drop(remote_dat);
+        assert!(!directory.exists());
+
+        assert!(RemoteDat::fetch_with(url, Some("0000000000000000000000000000000000000000"), copy_test_dat).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn downloads_only_http_urls() {
+        assert!(check_url("http://example.com/test.dat").is_ok());
+        assert!(check_url("HTTPS://example.com/test.dat").is_ok());
+        assert!(check_url("file:///etc/passwd").is_err());
+        assert!(check_url("ftp://example.com/test.dat").is_err());
+        assert!(check_url("-o/tmp/test.dat").is_err());
+        assert!(RemoteDat::fetch("file:///etc/passwd", None).is_err());
+
+        let args = get_curl_args("https://example.com/-x.dat", Path::new("download"));
+        let url_position = args.iter().position(|arg| arg == "https://example.com/-x.dat").unwrap();
+        assert_eq!("--url", args[url_position - 1]);
+        assert_eq!(url_position, args.len() - 1);
+    }
+}
@@ -0,0 +1,192 @@
+//! Reads the DATs without writing them, to find their problems and how big the database would be before a long import.
+use std::{collections::{BTreeSet, HashSet}, fmt::Display};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{models::{dat_info::DatInfo, disk::GameDisk, file::DataFile, game::Game}, units::ByteSize, writer::DataWriter};
+
+use super::{DatImportReport, ImportIssueLevel};
+
+/// How many roms without hashes are listed, the rest are only counted
+const MAX_LISTED_ROMS: usize = 50;
+/// The average bytes each row takes in the database with its indexes, measured on imported databases
+const GAME_ROW_BYTES: u64 = 60;
+const ROM_ROW_BYTES: u64 = 180;
+const GAME_ROM_ROW_BYTES: u64 = 240;
+
+/// The sha1, md5, crc and size that tell a rom apart
+type RomKey = (Option<String>, Option<String>, Option<String>, Option<u32>);
+
+/// Counts the entries of the DATs instead of writing them, the same writer goes through all the DATs so the sets
+/// repeated in different DATs are found
+#[derive(Debug, Default)]
+pub struct ValidationWriter {
+    names: HashSet<String>,
+    validation: DatValidation,
+    unique_roms: HashSet<RomKey>,
+    unique_disks: HashSet<Option<String>>,
+}
+
+impl ValidationWriter {
+    /// The validation of all the DATs read, with the parse reports of each one
+    pub fn into_validation(self, dats: Vec<DatImportReport>) -> DatValidation {
+        let mut validation = self.validation;
+        validation.unique_roms = self.unique_roms.len() as u64;
+        validation.estimated_db_bytes = validation.games as u64 * GAME_ROW_BYTES
+            + (validation.unique_roms + self.unique_disks.len() as u64) * ROM_ROW_BYTES
+            + (validation.roms + validation.disks) * GAME_ROM_ROW_BYTES;
+        validation.dats = dats;
+        validation
+    }
+}
+
+impl DataWriter for ValidationWriter {
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_new_entry(&mut self, game: Game, roms: Vec<DataFile>, disks: Vec<GameDisk>, samples: Vec<String>, _device_refs: Vec<String>) -> Result<()> {
+        let validation = &mut self.validation;
+        validation.games += 1;
+        if !self.names.insert(game.name.to_owned()) {
+            validation.duplicated_sets.insert(game.name.to_owned());
+        }
+
+        validation.roms += roms.len() as u64;
+        for rom in roms {
+            let nodump = matches!(&rom.status, Some(status) if status.eq_ignore_ascii_case("nodump"));
+            if !nodump && rom.info.sha1.is_none() && rom.info.md5.is_none() && rom.info.crc.is_none() {
+                validation.roms_without_hashes += 1;
+                if validation.unhashed_roms.len() < MAX_LISTED_ROMS {
+                    validation.unhashed_roms.push(format!("{}: {}", game.name, rom.name));
+                }
+            }
+            self.unique_roms.insert((rom.info.sha1, rom.info.md5, rom.info.crc, rom.info.size));
+        }
+        validation.disks += disks.len() as u64;
+        self.unique_disks.extend(disks.into_iter().map(|disk| disk.info.sha1));
+        validation.samples += samples.len() as u64;
+
+        Ok(())
+    }
+
+    fn on_dat_info(&mut self, _dat_info: DatInfo) -> Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// What an import of the DATs would find, nothing is written
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatValidation {
+    /// The entries and the malformed ones of each DAT
+    pub dats: Vec<DatImportReport>,
+    pub games: u32,
+    pub roms: u64,
+    pub unique_roms: u64,
+    pub disks: u64,
+    pub samples: u64,
+    /// The sets found more than once, only the first one would be imported
+    pub duplicated_sets: BTreeSet<String>,
+    pub roms_without_hashes: u32,
+    /// The first roms without hashes, as `set: rom`
+    pub unhashed_roms: Vec<String>,
+    /// A rough estimate from the average size of the rows, the filter and the metadata change it
+    pub estimated_db_bytes: u64,
+}
+
+impl DatValidation {
+    pub fn has_issues(&self) -> bool {
+        self.dats.iter().any(|dat| dat.report.has_issues()) || !self.duplicated_sets.is_empty() || self.roms_without_hashes > 0
+    }
+}
+
+impl Display for DatValidation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for dat in &self.dats {
+            writeln!(f, "DAT file: {}", dat.source)?;
+            writeln!(f, "Entries: {}, Warnings: {}, Errors: {}", dat.report.entries, dat.report.warnings, dat.report.errors)?;
+            for issue in &dat.report.issues {
+                let level = match issue.level {
+                    ImportIssueLevel::Warning => "Warning",
+                    ImportIssueLevel::Error => "Error",
+                };
+                match issue.position {
+                    Some(position) => writeln!(f, "- [{}] Position {}: {}", level, position, issue.message)?,
+                    None => writeln!(f, "- [{}] {}", level, issue.message)?,
+                }
+                writeln!(f, "  {}", issue.snippet)?;
+            }
+        }
+        writeln!(f, "Games: {}", self.games)?;
+        writeln!(f, "Roms: {}, {} different", self.roms, self.unique_roms)?;
+        writeln!(f, "Disks: {}", self.disks)?;
+        writeln!(f, "Samples: {}", self.samples)?;
+        if !self.duplicated_sets.is_empty() {
+            writeln!(f, "Duplicated sets: {}", self.duplicated_sets.iter().cloned().collect::<Vec<_>>().join(", "))?;
+        }
+        if self.roms_without_hashes > 0 {
+            writeln!(f, "Roms without hashes: {}", self.roms_without_hashes)?;
+            for rom in &self.unhashed_roms {
+                writeln!(f, "- {}", rom)?;
+            }
+        }
+        writeln!(f, "Estimated database size: {}", ByteSize(self.estimated_db_bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quick_xml::Reader;
+    use crate::data::importer::DatImporter;
+    use super::*;
+
+    fn validate(writer: ValidationWriter, xml: &str) -> Result<(ValidationWriter, DatImportReport)> {
+        let mut importer = DatImporter::new(Reader::from_str(xml), writer, 0);
+        let report = importer.load_dat()?;
+        let writer = importer.into_writer().unwrap();
+        Ok((writer, DatImportReport { source: "test.dat".to_string(), report }))
+    }
+
+    #[test]
+    fn finds_the_problems_of_the_dats() -> Result<()> {
+        let first = r#"<datafile>
+            <game name="game1">
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f"/>
+                <rom name="rom2.rom" size="1024"/>
+                <rom name="rom3.rom" size="1024" status="nodump"/>
+            </game>
+            <game name="game2">
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f"/>
+                <rom size="1024" crc="1f2b3c4d"/>
+            </game>
+        </datafile>"#;
+        let second = r#"<datafile>
+            <game name="game2">
+                <rom name="rom4.rom" size="512" crc="2f2b3c4d"/>
+                <disk name="disk1" sha1="0123456789abcdef0123456789abcdef01234567"/>
+            </game>
+        </datafile>"#;
+
+        let (writer, first_report) = validate(ValidationWriter::default(), first)?;
+        let (writer, second_report) = validate(writer, second)?;
+        let validation = writer.into_validation(vec![first_report, second_report]);
+
+        assert_eq!(1, validation.dats[0].report.errors);
+        assert_eq!(3, validation.games);
+        assert_eq!(5, validation.roms);
+        assert_eq!(3, validation.unique_roms);
+        assert_eq!(1, validation.disks);
+        assert_eq!(vec!["game2"], validation.duplicated_sets.iter().collect::<Vec<_>>());
+        assert_eq!(1, validation.roms_without_hashes);
+        assert_eq!(vec!["game1: rom2.rom"], validation.unhashed_roms);
+        assert_eq!(3 * GAME_ROW_BYTES + 4 * ROM_ROW_BYTES + 6 * GAME_ROM_ROW_BYTES, validation.estimated_db_bytes);
+        assert!(validation.has_issues());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! The sets a fix or a rebuild already went through. The journal is written next to the report after each set, so a run
+//! that was interrupted or failed resumes where it stopped instead of going through the same sets again.
+use std::{collections::BTreeSet, fmt::Display, fs::{self, File}, io::{BufReader, BufWriter}, path::PathBuf};
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::cleaner::get_tmp_path;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResumeJournal {
+    /// The date of the report the sets were done from, the journal of another check is discarded
+    report_date: String,
+    completed: BTreeSet<String>,
+    /// Without a file the journal is only kept in memory
+    #[serde(skip)]
+    file: Option<PathBuf>,
+}
+
+impl ResumeJournal {
+    /// Reads the journal left by a previous run from the same report, or starts a new one
+    pub fn open(file: impl Into<PathBuf>, report_date: &str) -> Result<Self> {
+        let file = file.into();
+        if file.exists() {
+            match serde_json::from_reader::<_, ResumeJournal>(BufReader::new(File::open(&file)?)) {
+                Ok(journal) if journal.report_date == report_date => return Ok(Self { file: Some(file), ..journal }),
+                Ok(_) => warn!("The journal `{}` is from another check, starting from the first set", file.to_string_lossy()),
+                Err(e) => warn!("The journal `{}` can't be read, starting from the first set: {}", file.to_string_lossy(), e),
+            }
+        }
+
+        Ok(Self { report_date: report_date.to_string(), completed: BTreeSet::new(), file: Some(file) })
+    }
+
+    /// The journal of the operation, next to the report
+    pub fn get_journal_file(report_file: &str, operation: &str) -> String {
+        format!("{}.{}-journal", report_file, operation)
+    }
+
+    pub fn is_completed(&self, set_name: &str) -> bool {
+        self.completed.contains(set_name)
+    }
+
+    /// Records the set as done, writing the journal again
+    pub fn complete(&mut self, set_name: &str) -> Result<()> {
+        self.completed.insert(set_name.to_string());
+        if let Some(file) = &self.file {
+            let tmp_file = get_tmp_path(file);
+            serde_json::to_writer(BufWriter::new(File::create(&tmp_file)?), self)?;
+            fs::rename(tmp_file, file)?;
+        }
+        Ok(())
+    }
+}
+
+/// The sets done in a run, and the ones skipped because a previous run did them
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionProgress {
+    pub completed: Vec<String>,
+    pub resumed: usize,
+    /// The run was stopped before going through all the sets, run it again to continue
+    pub interrupted: bool,
+    /// The manifest of the copies of the archives changed, to restore them
+    #[serde(default)]
+    pub backup_manifest: Option<String>,
+}
+
+impl Display for ExecutionProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Sets done: {}", self.completed.len())?;
+        if self.resumed > 0 {
+            writeln!(f, "Sets done in a previous run: {}", self.resumed)?;
+        }
+        if self.interrupted {
+            writeln!(f, "Interrupted, run it again to continue with the remaining sets")?;
+        }
+        if let Some(backup_manifest) = &self.backup_manifest {
+            writeln!(f, "Backup of the archives changed: {}", backup_manifest)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+
+    #[test]
+    fn resumes_from_the_journal_of_the_same_report() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_journal_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let file = dir.join("report.fix-journal");
+
+        let mut journal = ResumeJournal::open(&file, "2026-01-01")?;
+        journal.complete("game1")?;
+        assert!(ResumeJournal::open(&file, "2026-01-01")?.is_completed("game1"));
+        assert!(!ResumeJournal::open(&file, "2026-01-02")?.is_completed("game1"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+//! Moving a collection to a new version of its DAT. The collection is checked against the new DAT, each set is told
+//! apart by what its archive needs to satisfy it, using the old DAT to find the sets that were renamed, and then the
+//! sets are rebuilt into a new directory.
+use std::{collections::BTreeSet, fmt::Display};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{backup::Backup, journal::ResumeJournal, models::{self, dat_info::Packing}, rebuilder::{DEFAULT_REBUILD_THROUGHPUT, RebuildPlan, SetRebuild}, reporter::{CancellationToken, scan_report::{ScanReport, SetReference, SetReport}}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationAction {
+    /// The archive already satisfies the new DAT
+    Keep,
+    /// Only the name of the archive or the names of some roms change
+    Rename,
+    /// Roms are added from other archives or removed from the archive
+    Update,
+    /// Roms are missing from the collection, the set can't be rebuilt
+    Incomplete,
+}
+
+impl Display for MigrationAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationAction::Keep => write!(f, "keep"),
+            MigrationAction::Rename => write!(f, "rename"),
+            MigrationAction::Update => write!(f, "update"),
+            MigrationAction::Incomplete => write!(f, "incomplete"),
+        }
+    }
+}
+
+/// A rom renamed inside the archive of the set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RomRename {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetMigration {
+    pub set_name: String,
+    pub action: MigrationAction,
+    /// The set of the old DAT the archive is named after, when the set was renamed in the new one
+    pub renamed_from: Option<String>,
+    pub renamed_roms: Vec<RomRename>,
+    /// The roms copied from other archives
+    pub added: Vec<String>,
+    /// The files of the archive the new DAT doesn't have in the set
+    pub removed: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+impl Display for SetMigration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "- {}: {}", self.set_name, self.action)?;
+        if let Some(renamed_from) = &self.renamed_from {
+            write!(f, ", renamed from {}", renamed_from)?;
+        }
+        if !self.renamed_roms.is_empty() {
+            let renamed_roms = self.renamed_roms.iter().map(|rename| format!("{} -> {}", rename.from, rename.to)).collect::<Vec<_>>();
+            write!(f, ", renames {}", renamed_roms.join(", "))?;
+        }
+        if !self.added.is_empty() {
+            write!(f, ", adds {}", self.added.join(", "))?;
+        }
+        if !self.removed.is_empty() {
+            write!(f, ", removes {}", self.removed.join(", "))?;
+        }
+        if !self.missing.is_empty() {
+            write!(f, ", missing {}", self.missing.join(", "))?;
+        }
+        writeln!(f)
+    }
+}
+
+/// What each set of the collection needs to satisfy the new DAT, with the rebuild that writes the migrated sets
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MigrationPlan {
+    pub sets: Vec<SetMigration>,
+    /// The sets of the old DAT that are not in the new one, their archives are left out of the target directory
+    pub dropped: Vec<String>,
+    pub rebuild: RebuildPlan,
+}
+
+impl Display for MigrationPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = |action: MigrationAction| self.sets.iter().filter(|set| set.action == action).count();
+        writeln!(f, "Sets: {} kept, {} renamed, {} updated, {} incomplete",
+            count(MigrationAction::Keep), count(MigrationAction::Rename), count(MigrationAction::Update), count(MigrationAction::Incomplete))?;
+        for set in self.sets.iter().filter(|set| set.action != MigrationAction::Keep) {
+            write!(f, "{}", set)?;
+        }
+        if !self.dropped.is_empty() {
+            writeln!(f, "Dropped from the new DAT: {}", self.dropped.join(", "))?;
+        }
+        write!(f, "{}", self.rebuild)
+    }
+}
+
+impl MigrationPlan {
+    /// Builds the plan from a check of the collection against the new DAT, `old_sets` are the sets of the old DAT. The
+    /// sets are rebuilt into the target directory as the rebuild command does
+    pub fn from_report<S>(report: &ScanReport, old_sets: &BTreeSet<String>, target_directory: S, packing: Option<Packing>) -> Self where S: AsRef<str> {
+        let mut report_sets = report.sets.iter().collect::<Vec<_>>();
+        report_sets.sort_by(|a, b| a.0.cmp(b.0));
+        // The archives of files not matching a set of the new DAT, the ones of the old DAT may be a renamed set
+        let unmatched = report_sets.iter()
+            .filter(|(_, set)| set.in_file && matches!(set.reference, SetReference::FileName(_)))
+            .map(|(set_name, set)| (set_name.as_str(), *set))
+            .collect::<Vec<_>>();
+
+        // Only the sets of the new DAT are rebuilt
+        let mut new_report = ScanReport::new(report.get_root_directory().map(|dir| dir.to_string()), report.get_rom_mode());
+        new_report.set_packing(report.get_packing());
+        new_report.archives = report.archives.clone();
+        new_report.sets = report.sets.iter()
+            .filter(|(_, set)| matches!(set.reference, SetReference::Game(_)))
+            .map(|(set_name, set)| (set_name.to_owned(), set.clone()))
+            .collect();
+        let rebuild = RebuildPlan::from_report(&new_report, target_directory, DEFAULT_REBUILD_THROUGHPUT, packing);
+
+        let mut renamed = BTreeSet::new();
+        let sets = rebuild.sets.iter().map(|set_rebuild| {
+            let set = &report.sets[&set_rebuild.set_name];
+            let renamed_from = get_renamed_from(set_rebuild, set, old_sets, &unmatched);
+            if let Some(renamed_from) = &renamed_from {
+                renamed.insert(renamed_from.to_string());
+            }
+            plan_set(set_rebuild, set, renamed_from.and_then(|renamed_from| unmatched.iter().find(|(set_name, _)| *set_name == renamed_from)))
+        }).collect();
+
+        let dropped = unmatched.iter()
+            .filter(|(set_name, _)| old_sets.contains(*set_name) && !renamed.contains(*set_name))
+            .map(|(set_name, _)| set_name.to_string())
+            .collect();
+
+        Self { sets, dropped, rebuild }
+    }
+
+    /// Rebuilds the sets that can be rebuilt into the target directory, once cancelled it stops after the set being
+    /// rebuilt
+    pub fn execute(&mut self, cancellation: &CancellationToken) -> Result<()> {
+        self.rebuild.progress = Some(self.rebuild.execute_resumable(&mut ResumeJournal::default(), &mut Backup::default(), cancellation)?);
+        Ok(())
+    }
+}
+
+/// The old set the roms come from, if the set is new, doesn't have an archive and all its roms are in the archive of a
+/// set of the old DAT that's not in the new one
+fn get_renamed_from(set_rebuild: &SetRebuild, set: &SetReport, old_sets: &BTreeSet<String>, unmatched: &[(&str, &SetReport)]) -> Option<String> {
+    if set.in_file || old_sets.contains(&set_rebuild.set_name) {
+        return None;
+    }
+    let mut sources = set_rebuild.sources.iter().map(|source| models::get_set_from_file(source));
+    match (sources.next(), sources.next()) {
+        (Some(source), None) if old_sets.contains(&source) && unmatched.iter().any(|(set_name, _)| *set_name == source) => Some(source),
+        _ => None,
+    }
+}
+
+fn plan_set(set_rebuild: &SetRebuild, set: &SetReport, renamed_from: Option<&(&str, &SetReport)>) -> SetMigration {
+    // The archive of the set, either named after the set or after its old name
+    let own_set = renamed_from.map(|(set_name, _)| set_name.to_string()).unwrap_or_else(|| set_rebuild.set_name.to_string());
+    let is_own_archive = |archive: &str| models::get_set_from_file(archive) == own_set;
+
+    let renamed_roms = set_rebuild.entries.iter()
+        .filter(|entry| is_own_archive(&entry.from_archive) && entry.is_rename())
+        .map(|entry| RomRename { from: entry.from.to_string(), to: entry.to.to_string() })
+        .collect::<Vec<_>>();
+    let added = set_rebuild.entries.iter()
+        .filter(|entry| !is_own_archive(&entry.from_archive))
+        .map(|entry| entry.to.to_string())
+        .collect::<Vec<_>>();
+
+    let own_report = renamed_from.map(|(_, set)| *set).unwrap_or(set);
+    let kept = set_rebuild.entries.iter().filter(|entry| is_own_archive(&entry.from_archive)).map(|entry| entry.from.as_str()).collect::<BTreeSet<_>>();
+    let removed = own_report.roms_to_spare.iter().chain(own_report.unknown.iter())
+        .map(|file| file.name.as_str())
+        .filter(|name| !kept.contains(name))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+
+    let action = if !set_rebuild.is_achievable() {
+        MigrationAction::Incomplete
+    } else if !added.is_empty() || !removed.is_empty() {
+        MigrationAction::Update
+    } else if renamed_from.is_some() || !renamed_roms.is_empty() {
+        MigrationAction::Rename
+    } else {
+        MigrationAction::Keep
+    };
+
+    SetMigration {
+        set_name: set_rebuild.set_name.to_string(),
+        action,
+        renamed_from: renamed_from.map(|(set_name, _)| set_name.to_string()),
+        renamed_roms,
+        added,
+        removed,
+        missing: set_rebuild.missing.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path};
+    use crate::{ImportOptions, RomsetMode, Romst, ScanOptions, sysout::{DatImporterReporterSysOut, ReportReporterSysOut}};
+    use super::*;
+
+    #[test]
+    fn tells_what_each_set_needs_for_the_new_dat() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_migration_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        // The new DAT renames game3 and a rom of game2
+        let new_dat = dir.join("new.dat");
+        let dat = fs::read_to_string("testdata/test.dat")?;
+        fs::write(&new_dat, dat.replace("<machine name=\"game3\"", "<machine name=\"game3b\"").replace("rom name=\"binary3.bin\"", "rom name=\"binary3.rom\""))?;
+        let old_db = dir.join("old.rst").to_string_lossy().to_string();
+        let new_db = dir.join("new.rst").to_string_lossy().to_string();
+        Romst::import_dat("testdata/test.dat", &old_db, ImportOptions { overwrite: true, ..Default::default() }, None::<DatImporterReporterSysOut>)?;
+        Romst::import_dat(new_dat.to_string_lossy().as_ref(), &new_db, ImportOptions { overwrite: true, ..Default::default() }, None::<DatImporterReporterSysOut>)?;
+
+        let reader = Romst::get_data_reader(&new_db)?;
+        let report = Romst::get_report(reader, vec!["testdata/split"], ScanOptions::new(RomsetMode::Split), CancellationToken::default(), None::<ReportReporterSysOut>)?;
+        let target = dir.join("target").to_string_lossy().to_string();
+        let plan = Romst::migrate(old_db.as_str(), &report, target.as_str(), None, true, &CancellationToken::default())?;
+
+        let get_set = |set_name: &str| plan.sets.iter().find(|set| set.set_name == set_name).unwrap();
+        assert_eq!(MigrationAction::Keep, get_set("game1a").action);
+        let game2 = get_set("game2");
+        assert_eq!(MigrationAction::Rename, game2.action);
+        assert_eq!(vec![RomRename { from: "binary3.bin".to_string(), to: "binary3.rom".to_string() }], game2.renamed_roms);
+        let game3b = get_set("game3b");
+        assert_eq!((MigrationAction::Rename, Some("game3".to_string())), (game3b.action, game3b.renamed_from.clone()));
+        assert!(plan.dropped.is_empty());
+        assert!(!Path::new(&target).exists());
+
+        let plan = Romst::migrate(old_db.as_str(), &report, target.as_str(), None, false, &CancellationToken::default())?;
+        assert!(plan.rebuild.progress.unwrap().completed.contains(&"game3b".to_string()));
+        assert!(Path::new(&target).join("game3b.zip").exists());
+        assert!(!Path::new(&target).join("game3.zip").exists());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,6 +1,28 @@
 pub mod models;
 
 pub mod importer;
+pub mod exporter;
 pub mod writer;
 pub mod reader;
-pub mod reporter;
\ No newline at end of file
+pub mod reporter;
+pub mod cleaner;
+pub mod filter;
+pub mod aliases;
+pub mod ignored_roms;
+pub mod fixer;
+pub mod analysis;
+pub mod rebuilder;
+pub mod migration;
+pub mod priority;
+pub mod journal;
+pub mod backup;
+pub mod portable;
+pub mod disk_space;
+pub mod bundle;
+pub mod sanitize;
+pub mod units;
+pub mod pool;
+pub mod connection;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
@@ -0,0 +1,98 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::units::ByteSize;
+
+/// The flavour of a zip file, canonical formats have a known layout that doesn't need to be rebuilt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    TorrentZip,
+    RvZstd,
+    NonCanonical,
+    /// A rar archive, it's only read, so it needs to be rezipped
+    Rar,
+}
+
+impl ArchiveFormat {
+    pub fn is_canonical(&self) -> bool {
+        !matches!(self, ArchiveFormat::NonCanonical)
+    }
+}
+
+impl Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveFormat::TorrentZip => write!(f, "TorrentZip"),
+            ArchiveFormat::RvZstd => write!(f, "RVZSTD"),
+            ArchiveFormat::NonCanonical => write!(f, "Non canonical"),
+            ArchiveFormat::Rar => write!(f, "RAR"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveInfo {
+    pub format: ArchiveFormat,
+    pub entries: usize,
+    pub total_size: u64,
+    pub comment: String,
+    /// The modification times of the oldest and newest entries, as `YYYY-MM-DD HH:MM:SS`, none for the rar archives
+    /// and the reports written before they were recorded
+    #[serde(default)]
+    pub oldest_entry: Option<String>,
+    #[serde(default)]
+    pub newest_entry: Option<String>,
+}
+
+impl ArchiveInfo {
+    pub fn new(format: ArchiveFormat, entries: usize, total_size: u64, comment: String) -> Self { Self { format, entries, total_size, comment, oldest_entry: None, newest_entry: None } }
+
+    /// Keeps the oldest and newest of the modification times of the entries
+    pub fn with_entry_times<I>(mut self, times: I) -> Self where I: IntoIterator<Item = String> {
+        for time in times {
+            if self.oldest_entry.as_ref().map(|oldest| time < *oldest).unwrap_or(true) {
+                self.oldest_entry = Some(time.clone());
+            }
+            if self.newest_entry.as_ref().map(|newest| time > *newest).unwrap_or(true) {
+                self.newest_entry = Some(time);
+            }
+        }
+        self
+    }
+}
+
+/// A problem found when verifying the structure of an archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchiveIssue {
+    /// The data of the entry doesn't match the crc stored in its header
+    CrcMismatch { entry: String, expected: String, found: String },
+    /// The entry can't be extracted
+    UnreadableEntry { entry: String, message: String },
+    /// The central directory can't be read, the entries with a local header may still be salvaged
+    BrokenCentralDirectory { salvageable_entries: usize },
+}
+
+impl Display for ArchiveIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveIssue::CrcMismatch { entry, expected, found } => write!(f, "{}: crc {} expected, found {}", entry, expected, found),
+            ArchiveIssue::UnreadableEntry { entry, message } => write!(f, "{}: can't be read, {}", entry, message),
+            ArchiveIssue::BrokenCentralDirectory { salvageable_entries } => write!(f, "broken central directory, {} entries can be salvaged", salvageable_entries),
+        }
+    }
+}
+
+impl Display for ArchiveInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] entries: {}, size: {}", self.format, self.entries, ByteSize(self.total_size))?;
+        if !self.comment.is_empty() {
+            write!(f, ", comment: {}", self.comment)?;
+        }
+        if let (Some(oldest), Some(newest)) = (&self.oldest_entry, &self.newest_entry) {
+            write!(f, ", modified: {} to {}", oldest, newest)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
 use serde::{Serialize, Deserialize};
 
+use crate::{RomsetMode, err, error::RomstError};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DatInfo {
     pub name: String,
@@ -12,6 +14,63 @@ pub struct DatInfo {
 
 impl DatInfo {
     pub fn new(name: String, description: String, version: String, extra_data: Vec<(String, String)>) -> Self { Self { name, description, version, extra_data } }
+
+    /// The packing the DAT expects from the `forcepacking` option of its clrmamepro header
+    pub fn get_packing(&self) -> Option<Packing> {
+        self.extra_data.iter()
+            .find(|(key, _value)| key == PACKING_KEY)
+            .and_then(|(_key, value)| value.parse().ok())
+    }
+
+    /// The mode the DAT is meant to be used in from the `forcemerging` option of its clrmamepro header
+    pub fn get_merging(&self) -> Option<RomsetMode> {
+        self.extra_data.iter()
+            .find(|(key, _value)| key == MERGING_KEY)
+            .and_then(|(_key, value)| parse_merging(value))
+    }
+}
+
+/// The key of the expected packing in the info of the DAT
+pub const PACKING_KEY: &str = "forcepacking";
+/// The key of the expected mode in the info of the DAT
+pub const MERGING_KEY: &str = "forcemerging";
+
+/// The mode of a `forcemerging` value, `none` is non-merged and `full` is merged
+pub fn parse_merging(value: &str) -> Option<RomsetMode> {
+    match value.to_lowercase().trim() {
+        "none" => Some(RomsetMode::NonMerged),
+        "split" => Some(RomsetMode::Split),
+        "full" | "merged" => Some(RomsetMode::Merged),
+        _ => None,
+    }
+}
+
+/// How the sets are stored, zip archives or unpacked in folders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Packing {
+    Zip,
+    Unzip,
+}
+
+impl FromStr for Packing {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "zip" => Ok(Packing::Zip),
+            "unzip" => Ok(Packing::Unzip),
+            _ => err!(RomstError::GenericError { message: format!("Unknown packing `{}`, can be either `zip` or `unzip`", s) }),
+        }
+    }
+}
+
+impl Display for Packing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Packing::Zip => write!(f, "zip"),
+            Packing::Unzip => write!(f, "unzip"),
+        }
+    }
 }
 
 impl Display for DatInfo {
@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, fmt::{self, Display}, hash::Hash};
+use std::{cmp::Ordering, collections::BTreeMap, fmt::{self, Display}, hash::Hash, path::Path};
 use std::cmp::Ord;
 use serde::{Deserialize, Serialize};
 
@@ -10,6 +10,7 @@ use crate::{error::RomstError, err, filesystem};
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FileType {
     Rom,
+    Disk,
     Sample
 }
 
@@ -17,16 +18,33 @@ impl Display for FileType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             FileType::Rom => write!(f, "ROM"),
+            FileType::Disk => write!(f, "Disk"),
             FileType::Sample => write!(f, "Sample"),
         }
     }
 }
 
+impl FileType {
+    /// Guesses the type from the extension, `.chd` files are disks and `.wav` files are samples
+    pub fn from_file_name(file_name: &str) -> Self {
+        let extension = Path::new(file_name).extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("chd") => FileType::Disk,
+            Some("wav") => FileType::Sample,
+            _ => FileType::Rom,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DataFile {
     pub name: String,
     pub info: DataFileInfo,
     pub status: Option<String>,
+    pub bios: Option<String>,
+    /// The attributes from the DAT not read by romst, like `region`, `offset` or `date`, kept as they are
+    #[serde(default)]
+    pub extra_attrs: Option<BTreeMap<String, String>>,
 }
 
 impl Ord for DataFile {
@@ -54,6 +72,9 @@ impl Display for DataFile {
         if let Some(status) = &self.status {
             write!(f, " ({})", status)?;
         };
+        if let Some(bios) = &self.bios {
+            write!(f, " [bios: {}]", bios)?;
+        };
 
         Ok(())
     }
@@ -64,7 +85,9 @@ impl DataFile {
         Self {
             name: name.into(),
             info: file_info,
-            status: None
+            status: None,
+            bios: None,
+            extra_attrs: None,
         }
     }
 
@@ -72,7 +95,9 @@ impl DataFile {
         Self {
             name: name.into(),
             info: file_info,
-            status
+            status,
+            bios: None,
+            extra_attrs: None,
         }
     }
 
@@ -84,6 +109,76 @@ impl DataFile {
             self.info.deep_compare(&other.info, file_checks)
         }
     }
+
+    pub fn get_dump_status(&self) -> DumpStatus {
+        DumpStatus::from_status(self.status.as_deref())
+    }
+
+    pub fn get_extra_attr(&self, key: &str) -> Option<&str> {
+        self.extra_attrs.as_ref().and_then(|extra_attrs| extra_attrs.get(key)).map(String::as_str)
+    }
+
+    pub fn add_extra_attr<K, V>(&mut self, key: K, value: V) where K: Into<String>, V: Into<String> {
+        self.extra_attrs.get_or_insert_with(BTreeMap::new).insert(key.into(), value.into());
+    }
+}
+
+/// The quality of the dump from the `status` attribute, files without status are good dumps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DumpStatus {
+    Good,
+    Verified,
+    BadDump,
+    NoDump,
+}
+
+impl DumpStatus {
+    pub fn from_status(status: Option<&str>) -> Self {
+        match status.map(|status| status.trim().to_lowercase()).as_deref() {
+            Some("verified") => DumpStatus::Verified,
+            Some("baddump") => DumpStatus::BadDump,
+            Some("nodump") => DumpStatus::NoDump,
+            _ => DumpStatus::Good,
+        }
+    }
+}
+
+/// Number of files for each dump status
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpQuality {
+    pub verified: u32,
+    pub good: u32,
+    pub bad_dump: u32,
+    pub no_dump: u32,
+}
+
+impl DumpQuality {
+    pub fn from_files<'a>(files: impl IntoIterator<Item = &'a DataFile>) -> Self {
+        let mut dump_quality = DumpQuality::default();
+        for file in files {
+            dump_quality.add(file.get_dump_status(), 1);
+        }
+        dump_quality
+    }
+
+    pub fn add(&mut self, dump_status: DumpStatus, count: u32) {
+        match dump_status {
+            DumpStatus::Good => self.good += count,
+            DumpStatus::Verified => self.verified += count,
+            DumpStatus::BadDump => self.bad_dump += count,
+            DumpStatus::NoDump => self.no_dump += count,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.verified + self.good + self.bad_dump + self.no_dump
+    }
+}
+
+impl Display for DumpQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "verified: {}, good: {}, bad dumps: {}, no dumps: {}", self.verified, self.good, self.bad_dump, self.no_dump)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -188,19 +283,19 @@ impl Ord for DataFileInfo {
     fn cmp(&self, other: &Self) -> Ordering {
         // We are good just with the sha1
         if let (Some(self_sha1), Some(other_sha1)) = (self.sha1.as_ref(), other.sha1.as_ref()) {
-            return self_sha1.cmp(&other_sha1);
+            return self_sha1.cmp(other_sha1);
         }
         // MD5 in case of emergency
         if let (Some(self_md5), Some(other_md5)) = (self.md5.as_ref(), other.md5.as_ref()) {
-            return self_md5.cmp(&other_md5);
+            return self_md5.cmp(other_md5);
         }
         // uh-ooohh
         if let (Some(self_crc), Some(other_crc)) = (self.crc.as_ref(), other.crc.as_ref()) {
-            return self_crc.cmp(&other_crc);
+            return self_crc.cmp(other_crc);
         }
         // last resource
         if let (Some(self_size), Some(other_size)) = (self.size.as_ref(), other.size.as_ref()) {
-            return self_size.cmp(&other_size);
+            return self_size.cmp(other_size);
         }
 
         self.file_type.cmp(&other.file_type)
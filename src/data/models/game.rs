@@ -11,6 +11,56 @@ pub struct Game {
     pub info_description: Option<String>,
     pub info_year: Option<String>,
     pub info_manufacturer: Option<String>,
+    /// The `dir` elements of the DAT the game is nested in, joined with `/`
+    pub category: Option<String>,
+    /// Only available when the DAT is imported with the extended metadata
+    pub metadata: Option<Box<GameMetadata>>,
+}
+
+/// Machine information from the `driver`, `input` and `display` elements of the DAT
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct GameMetadata {
+    /// Either `good`, `imperfect` or `preliminary`
+    pub driver_status: Option<String>,
+    pub emulation_status: Option<String>,
+    pub players: Option<u32>,
+    /// `horizontal` or `vertical`, from the rotation of the first display
+    pub orientation: Option<String>,
+    pub display_type: Option<String>,
+}
+
+impl GameMetadata {
+    pub fn orientation_from_rotation(rotation: &str) -> Option<String> {
+        match rotation.trim() {
+            "0" | "180" => Some("horizontal".to_string()),
+            "90" | "270" => Some("vertical".to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut metadata = vec![];
+
+        if let Some(driver_status) = &self.driver_status {
+            metadata.push(format!("Driver: {}", driver_status));
+        }
+        if let Some(emulation_status) = &self.emulation_status {
+            metadata.push(format!("Emulation: {}", emulation_status));
+        }
+        if let Some(players) = &self.players {
+            metadata.push(format!("Players: {}", players));
+        }
+        if let Some(orientation) = &self.orientation {
+            metadata.push(format!("Orientation: {}", orientation));
+        }
+        if let Some(display_type) = &self.display_type {
+            metadata.push(format!("Display: {}", display_type));
+        }
+
+        write!(f, "{}", metadata.join(", "))
+    }
 }
 
 impl Game {
@@ -23,7 +73,9 @@ impl Game {
             sample_of: None,
             info_description: None,
             info_year: None,
-            info_manufacturer: None
+            info_manufacturer: None,
+            category: None,
+            metadata: None,
         }
     }
 }
@@ -44,6 +96,9 @@ impl fmt::Display for Game {
         if let Some(sample_of) = &self.sample_of {
             game_data.push(format!("Sample of: {}", sample_of))
         }
+        if let Some(category) = &self.category {
+            game_data.push(format!("Category: {}", category))
+        }
 
         let name_and_desc = match self.info_description {
             Some(ref desc) => { format!("[{}] {}", self.name, desc) }
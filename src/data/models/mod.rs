@@ -5,17 +5,27 @@ pub mod set;
 pub mod game;
 pub mod file;
 pub mod disk;
+pub mod archive;
 
+/// The set of an archive is its name without the extension. Folders and groups of loose files are named as the set, so
+/// a name with dots like `Dr. Mario (v1.1)` is kept whole, only an alphanumeric extension is removed
 pub fn get_set_from_file(file: &str) -> String {
     let file_path = Path::new(file);
-    if let Some(set_name) = file_path.file_stem() {
-        set_name.to_string_lossy().to_string()
-    } else {
-        file.to_string()
+    let has_extension = file_path.extension()
+        .map(|extension| extension.to_string_lossy().chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or(false);
+    match file_path.file_stem() {
+        Some(set_name) if has_extension => set_name.to_string_lossy().to_string(),
+        _ => file_path.file_name().map(|file_name| file_name.to_string_lossy().to_string()).unwrap_or_else(|| file.to_string()),
     }
 }
 
+/// A zip or rar archive belongs to the set with its name, and a folder or a group of loose files to the set named as them
 pub fn does_file_belong_to_set(file: &str, set: &str) -> bool {
+    if file == set {
+        return true;
+    }
+
     let file_path = Path::new(file);
     if is_extension_for_file_set(&file_path) {
         if let Some(set_name) = file_path.file_stem() {
@@ -30,7 +40,7 @@ pub fn does_file_belong_to_set(file: &str, set: &str) -> bool {
 
 fn is_extension_for_file_set(file: &impl AsRef<Path>) -> bool {
     if let Some(extension) = file.as_ref().extension() {
-        return extension.eq("zip");
+        return extension.eq("zip") || extension.eq_ignore_ascii_case("rar");
     }
 
     false
@@ -38,15 +48,27 @@ fn is_extension_for_file_set(file: &impl AsRef<Path>) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use super::does_file_belong_to_set;
+    use super::{does_file_belong_to_set, get_set_from_file};
 
     #[test]
     pub fn should_identify_a_set() {
         assert!(does_file_belong_to_set("set.zip", "set"))
     }
 
+    #[test]
+    pub fn should_identify_a_rar_set() {
+        assert!(does_file_belong_to_set("set.rar", "set"))
+    }
+
     #[test]
     pub fn should_identify_a_non_set() {
         assert!(!does_file_belong_to_set("file.zip", "set"))
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn should_identify_a_folder_set() {
+        assert!(does_file_belong_to_set("Dr. Mario (v1.1)", "Dr. Mario (v1.1)"));
+        assert_eq!("Dr. Mario (v1.1)", get_set_from_file("Dr. Mario (v1.1)"));
+        assert_eq!("Dr. Mario (v1.1)", get_set_from_file("Dr. Mario (v1.1).zip"));
+    }
+}
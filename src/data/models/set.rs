@@ -1,9 +1,9 @@
 use std::{fmt::Display, writeln};
 
-use super::{file::DataFile, game::Game};
+use super::{file::{DataFile, DumpQuality}, game::Game};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameSet {
     pub game: Game,
     pub roms: Vec<DataFile>,
@@ -14,12 +14,17 @@ pub struct GameSet {
 
 impl GameSet {
     pub fn new(game: Game, roms: Vec<DataFile>, samples: Vec<DataFile>, disks: Vec<DataFile>, device_refs: Vec<String>) -> Self { Self { game, roms, samples, disks, device_refs } }
+
+    pub fn get_dump_quality(&self) -> DumpQuality {
+        DumpQuality::from_files(&self.roms)
+    }
 }
 
 impl Display for GameSet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "{}", self.game)?;
         if !self.roms.is_empty() {
+            writeln!(f, "Dump quality: {}", self.get_dump_quality())?;
             writeln!(f, "Roms:")?;
             for rom in self.roms.as_slice() {
                 writeln!(f, "    - {}", rom)?;
@@ -0,0 +1,334 @@
+use std::{collections::BTreeMap, fmt::Display, fs::{self, File}, io::{BufReader, BufWriter, Read, Write}, path::{Path, PathBuf}};
+
+use anyhow::Result;
+use log::{debug, warn};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use zip::{CompressionMethod, ZipArchive, ZipWriter, write::FileOptions};
+
+use crate::{RomsetMode, err, error::RomstError, filesystem::{md5::MD5Hasher, sha1::SHA1Hasher}};
+
+use super::{connection, models::file::{DataFile, DumpStatus}, reader::DataReader};
+
+/// The index of the pool, stored in the pool directory
+pub const POOL_INDEX_FILE: &str = "romst_pool.db";
+const COMPRESSED_EXTENSION: &str = "zst";
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// A directory where every unique rom is stored once under its sha1, e.g. `8b/b3/8bb3a81b...`.
+/// The roms can be stored compressed with zstd, and the index keeps the other hashes to find them.
+pub struct RomPool {
+    directory: PathBuf,
+    conn: Connection,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PoolAddReport {
+    pub added: u32,
+    pub already_in_pool: u32,
+    pub errors: Vec<String>,
+}
+
+impl Display for PoolAddReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Roms added to the pool: {}", self.added)?;
+        writeln!(f, "Roms already in the pool: {}", self.already_in_pool)?;
+        if !self.errors.is_empty() {
+            writeln!(f, "Errors:")?;
+            for error in &self.errors {
+                writeln!(f, "- {}", error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The sets written from the pool, the sets with missing roms are written with the roms available
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PoolBuildReport {
+    pub built: Vec<String>,
+    pub missing_roms: BTreeMap<String, Vec<String>>,
+    pub errors: Vec<String>,
+}
+
+impl Display for PoolBuildReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Sets built: {}", self.built.len())?;
+        for set in &self.built {
+            writeln!(f, "- {}", set)?;
+        }
+        if !self.missing_roms.is_empty() {
+            writeln!(f, "Roms not in the pool:")?;
+            for (set, roms) in &self.missing_roms {
+                writeln!(f, "- {}: {}", set, roms.join(", "))?;
+            }
+        }
+        if !self.errors.is_empty() {
+            writeln!(f, "Errors:")?;
+            for error in &self.errors {
+                writeln!(f, "- {}", error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RomPool {
+    /// Opens the pool in the directory, creating it if it doesn't exist
+    pub fn open(directory: &impl AsRef<Path>) -> Result<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+        let conn = connection::open_read_write(directory.join(POOL_INDEX_FILE))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS pool_roms (
+                sha1        TEXT PRIMARY KEY,
+                md5         TEXT,
+                crc         TEXT,
+                size        INTEGER,
+                compressed  INTEGER);",
+            params![])?;
+        conn.execute("CREATE INDEX IF NOT EXISTS pool_roms_crc ON pool_roms(crc, size);", params![])?;
+
+        Ok(Self { directory, conn })
+    }
+
+    /// Adds the roms in the files to the pool, zip files are added entry by entry and directories with all their contents
+    pub fn add_files(&mut self, file_paths: Vec<impl AsRef<Path>>, compress: bool) -> Result<PoolAddReport> {
+        let mut report = PoolAddReport::default();
+        for file_path in file_paths {
+            let file_path = file_path.as_ref();
+            if let Err(e) = self.add_file(file_path, compress, &mut report) {
+                warn!("Error adding {} to the pool: {}", file_path.to_string_lossy(), e);
+                report.errors.push(format!("{}: {}", file_path.to_string_lossy(), e));
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn add_file(&mut self, file_path: &Path, compress: bool, report: &mut PoolAddReport) -> Result<()> {
+        if file_path.is_dir() {
+            let mut paths = file_path.read_dir()?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<_>>();
+            paths.sort();
+            for path in paths {
+                self.add_file(&path, compress, report)?;
+            }
+            return Ok(());
+        }
+
+        let is_zip = file_path.extension().map(|extension| extension.to_string_lossy().to_lowercase() == "zip").unwrap_or(false);
+        if is_zip {
+            let mut archive = ZipArchive::new(BufReader::new(File::open(file_path)?))?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i)?;
+                if entry.is_dir() {
+                    continue;
+                }
+                let mut bytes = vec![];
+                entry.read_to_end(&mut bytes)?;
+                self.add_rom(&bytes, compress, report)?;
+            }
+        } else {
+            let bytes = fs::read(file_path)?;
+            self.add_rom(&bytes, compress, report)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_rom(&mut self, bytes: &[u8], compress: bool, report: &mut PoolAddReport) -> Result<()> {
+        let sha1 = SHA1Hasher::new().get_hash(bytes);
+        if self.contains(&sha1)? {
+            report.already_in_pool += 1;
+            return Ok(());
+        }
+
+        let rom_path = self.get_rom_path(&sha1, compress);
+        if let Some(parent) = rom_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Written to a temporary file first, so the pool never has half written roms
+        let tmp_path = rom_path.with_extension("tmp");
+        if compress {
+            let mut encoder = zstd::Encoder::new(BufWriter::new(File::create(&tmp_path)?), COMPRESSION_LEVEL)?;
+            encoder.write_all(bytes)?;
+            encoder.finish()?.flush()?;
+        } else {
+            fs::write(&tmp_path, bytes)?;
+        }
+        fs::rename(&tmp_path, &rom_path)?;
+
+        let md5 = MD5Hasher::new().get_hash(bytes);
+        let crc = format!("{:08x}", crc32fast::hash(bytes));
+        self.conn.execute("INSERT INTO pool_roms (sha1, md5, crc, size, compressed) VALUES (?1, ?2, ?3, ?4, ?5);",
+            params![sha1, md5, crc, bytes.len() as u32, compress])?;
+        debug!("Added rom {} to the pool", sha1);
+        report.added += 1;
+
+        Ok(())
+    }
+
+    fn contains(&self, sha1: &str) -> Result<bool> {
+        let found = self.conn.query_row("SELECT sha1 FROM pool_roms WHERE sha1 = ?1;", params![sha1], |row| row.get::<_, String>(0)).optional()?;
+        Ok(found.is_some())
+    }
+
+    fn get_rom_path(&self, sha1: &str, compressed: bool) -> PathBuf {
+        let file_name = if compressed { format!("{}.{}", sha1, COMPRESSED_EXTENSION) } else { sha1.to_string() };
+        self.directory.join(&sha1[0..2]).join(&sha1[2..4]).join(file_name)
+    }
+
+    pub fn get_roms_count(&self) -> Result<u32> {
+        Ok(self.conn.query_row("SELECT COUNT(*) FROM pool_roms;", params![], |row| row.get(0))?)
+    }
+
+    /// The sha1 of the rom in the pool, searched by sha1, or by crc and size if the rom has no sha1
+    pub fn find_rom(&self, rom: &DataFile) -> Result<Option<String>> {
+        if let Some(sha1) = &rom.info.sha1 {
+            let sha1 = sha1.to_lowercase();
+            return Ok(if self.contains(&sha1)? { Some(sha1) } else { None });
+        }
+
+        match (&rom.info.crc, rom.info.size) {
+            (Some(crc), Some(size)) => {
+                Ok(self.conn.query_row("SELECT sha1 FROM pool_roms WHERE crc = ?1 AND size = ?2;", params![crc.to_lowercase(), size], |row| row.get(0)).optional()?)
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads the rom decompressed
+    pub fn read_rom(&self, sha1: &str) -> Result<Vec<u8>> {
+        let compressed: Option<bool> = self.conn.query_row("SELECT compressed FROM pool_roms WHERE sha1 = ?1;", params![sha1], |row| row.get(0)).optional()?;
+        match compressed {
+            Some(true) => Ok(zstd::decode_all(BufReader::new(File::open(self.get_rom_path(sha1, true))?))?),
+            Some(false) => Ok(fs::read(self.get_rom_path(sha1, false))?),
+            None => err!(RomstError::GenericError { message: format!("The rom {} is not in the pool", sha1) }),
+        }
+    }
+
+    /// Writes each set as a zip in the target directory with the roms of the set in the mode, taken from the pool.
+    /// The existing files in the target directory are not overwritten
+    pub fn build_sets<R: DataReader>(&self, data_reader: &R, set_names: Vec<impl AsRef<str>>, rom_mode: RomsetMode, target_directory: &impl AsRef<Path>) -> Result<PoolBuildReport> {
+        fs::create_dir_all(target_directory)?;
+        let mut report = PoolBuildReport::default();
+        for set_name in set_names {
+            let set_name = set_name.as_ref();
+            if let Err(e) = self.build_set(data_reader, set_name, rom_mode, target_directory.as_ref(), &mut report) {
+                warn!("Error building the set {}: {}", set_name, e);
+                report.errors.push(format!("{}: {}", set_name, e));
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn build_set<R: DataReader>(&self, data_reader: &R, set_name: &str, rom_mode: RomsetMode, target_directory: &Path, report: &mut PoolBuildReport) -> Result<()> {
+        let target_path = target_directory.join(format!("{}.zip", set_name));
+        if target_path.exists() {
+            return err!(RomstError::GenericError { message: format!("`{}` already exists", target_path.to_string_lossy()) });
+        }
+
+        // The same rom may be listed more than once in merged sets
+        let roms = data_reader.get_romset_roms(set_name, rom_mode)?.into_iter()
+            .map(|rom| rom.file)
+            .filter(|rom| rom.get_dump_status() != DumpStatus::NoDump)
+            .map(|rom| (rom.name.to_owned(), rom))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut found = vec![];
+        let mut missing = vec![];
+        for (name, rom) in roms {
+            match self.find_rom(&rom)? {
+                Some(sha1) => found.push((name, sha1)),
+                None => missing.push(name),
+            }
+        }
+        if found.is_empty() {
+            report.missing_roms.insert(set_name.to_string(), missing);
+            return Ok(());
+        }
+
+        let tmp_path = target_path.with_extension("tmp");
+        let mut zip_writer = ZipWriter::new(BufWriter::new(File::create(&tmp_path)?));
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, sha1) in found {
+            zip_writer.start_file(name, options)?;
+            zip_writer.write_all(&self.read_rom(&sha1)?)?;
+        }
+        zip_writer.finish()?.flush()?;
+        fs::rename(&tmp_path, &target_path)?;
+
+        report.built.push(set_name.to_string());
+        if !missing.is_empty() {
+            report.missing_roms.insert(set_name.to_string(), missing);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::OpenFlags;
+    use std::{env, io::BufReader};
+    use crate::data::{importer::DatImporter, reader::sqlite::DBReader, writer::sqlite::DBWriter};
+    use super::*;
+
+    fn get_data_reader() -> Result<DBReader> {
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        let writer = DBWriter::from_connection(&mut conn, 100);
+        let mut importer = DatImporter::<BufReader<File>, DBWriter>::from_path(&Path::new("testdata").join("test.dat"), writer)?;
+        importer.load_dat()?;
+
+        Ok(DBReader::from_connection(conn))
+    }
+
+    #[test]
+    fn add_to_pool_and_build_sets() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_pool_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let result = (|| -> Result<()> {
+            let split = Path::new("testdata").join("split");
+            let mut pool = RomPool::open(&dir.join("pool"))?;
+            let report = pool.add_files(vec![split.join("game1.zip"), split.join("game1a.zip")], true)?;
+            assert_eq!(6, report.added);
+            assert!(report.errors.is_empty());
+
+            // The same roms again are not added twice
+            let report = pool.add_files(vec![split.join("game1.zip")], false)?;
+            assert_eq!(0, report.added);
+            assert_eq!(4, report.already_in_pool);
+            assert_eq!(6, pool.get_roms_count()?);
+
+            let data_reader = get_data_reader()?;
+            let target = dir.join("target");
+            let report = pool.build_sets(&data_reader, vec!["game1a"], RomsetMode::NonMerged, &target)?;
+            assert_eq!(vec!["game1a"], report.built);
+            assert!(report.missing_roms.is_empty());
+
+            // The built set has the same roms as the non merged set
+            let expected = data_reader.get_romset_roms("game1a", RomsetMode::NonMerged)?;
+            let mut archive = ZipArchive::new(BufReader::new(File::open(target.join("game1a.zip"))?))?;
+            assert_eq!(expected.len(), archive.len());
+            for rom in expected {
+                let mut entry = archive.by_name(&rom.file.name)?;
+                let mut bytes = vec![];
+                entry.read_to_end(&mut bytes)?;
+                assert_eq!(rom.file.info.sha1, Some(SHA1Hasher::new().get_hash(&bytes)));
+            }
+
+            // Existing files are not overwritten
+            let report = pool.build_sets(&data_reader, vec!["game1a"], RomsetMode::NonMerged, &target)?;
+            assert!(report.built.is_empty());
+            assert_eq!(1, report.errors.len());
+
+            Ok(())
+        })();
+        fs::remove_dir_all(&dir)?;
+
+        result
+    }
+}
@@ -0,0 +1,277 @@
+//! Databases packed to be taken to another machine. The bundle is a zip with a compacted copy of the database, the
+//! SQLite file format is the same in every platform, and a manifest telling what's inside and the schema it was
+//! written with. Importing it checks the copy is whole and brings its schema up to the current version.
+use std::{fmt::Display, fs::{self, File}, io::{self, BufReader, BufWriter, Write}, path::Path};
+
+use anyhow::Result;
+use chrono::Utc;
+use log::info;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use zip::{CompressionMethod, ZipArchive, ZipWriter, write::FileOptions};
+
+use crate::{err, error::RomstError};
+
+use super::{cleaner::get_tmp_path, connection, reader::sqlite::DBReader, units::{ByteSize, Count}, writer::sqlite::{SCHEMA_VERSION, migrate_schema}};
+
+/// The version of the layout of the bundle, changes when the files inside do
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+/// The extension the bundles get when no other is given
+pub const BUNDLE_EXTENSION: &str = "romstdb";
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "database.sqlite";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    /// The schema of the database inside, 0 if it's older than the schema versions
+    pub schema_version: u32,
+    /// The version of romst that wrote the bundle
+    pub romst_version: String,
+    pub created_at: String,
+    pub dat_name: String,
+    pub dat_version: String,
+    pub games: u32,
+    pub roms: u32,
+}
+
+impl Display for BundleManifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "DAT: {} ({})", self.dat_name, self.dat_version)?;
+        writeln!(f, "Created at: {} by romst {}", self.created_at, self.romst_version)?;
+        writeln!(f, "Schema version: {}", self.schema_version)?;
+        writeln!(f, "Games: {}", Count(self.games as u64))?;
+        writeln!(f, "Roms: {}", Count(self.roms as u64))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleExportReport {
+    pub bundle_file: String,
+    /// The size of the bundle, in bytes
+    pub size: u64,
+    pub manifest: BundleManifest,
+}
+
+impl Display for BundleExportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Bundle: {} ({})", self.bundle_file, ByteSize(self.size))?;
+        self.manifest.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleImportReport {
+    pub db_file: String,
+    pub manifest: BundleManifest,
+    /// The schema version of the database once imported, higher than the one in the manifest if it was migrated
+    pub schema_version: u32,
+}
+
+impl Display for BundleImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Database: {}", self.db_file)?;
+        self.manifest.fmt(f)?;
+        if self.schema_version != self.manifest.schema_version {
+            writeln!(f, "Migrated to schema version: {}", self.schema_version)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the bundle of the database, the database has to be fully imported
+pub fn export_bundle(db_file: &str, bundle_file: &str) -> Result<BundleExportReport> {
+    let reader = DBReader::from_path(db_file)?;
+    if !reader.is_import_complete()? {
+        return err!(RomstError::GenericError { message: format!("The database `{}` was not fully imported, import the DAT file again.", db_file) });
+    }
+    let dat_info = reader.get_dat_info()?;
+    let manifest = BundleManifest {
+        format_version: BUNDLE_FORMAT_VERSION,
+        schema_version: reader.get_schema_version()?.unwrap_or(0),
+        romst_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now().to_rfc3339(),
+        dat_name: dat_info.name,
+        dat_version: dat_info.version,
+        games: reader.get_games_count()?,
+        roms: reader.get_roms_count()?,
+    };
+    drop(reader);
+
+    // The copy is taken in a single transaction, so a database being written is still copied consistently
+    let bundle_path = Path::new(bundle_file);
+    let copy_file = get_tmp_path(&bundle_path.with_extension("sqlite"));
+    remove_if_exists(&copy_file)?;
+    let conn = connection::open_read_only(db_file)?;
+    conn.execute("VACUUM INTO ?1;", params![copy_file.to_string_lossy()])?;
+    drop(conn);
+
+    let tmp_file = get_tmp_path(bundle_path);
+    let written = write_bundle(&tmp_file, &copy_file, &manifest);
+    fs::remove_file(&copy_file)?;
+    written?;
+    fs::rename(&tmp_file, bundle_path)?;
+    info!("Database `{}` exported into `{}`", db_file, bundle_file);
+
+    Ok(BundleExportReport { bundle_file: bundle_file.to_string(), size: fs::metadata(bundle_path)?.len(), manifest })
+}
+
+fn write_bundle(bundle_file: &Path, copy_file: &Path, manifest: &BundleManifest) -> Result<()> {
+    let mut writer = ZipWriter::new(BufWriter::new(File::create(bundle_file)?));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    writer.start_file(MANIFEST_ENTRY, options)?;
+    serde_json::to_writer_pretty(&mut writer, manifest)?;
+    writer.start_file(DATABASE_ENTRY, options.large_file(true))?;
+    io::copy(&mut BufReader::new(File::open(copy_file)?), &mut writer)?;
+    writer.finish()?.flush()?;
+    Ok(())
+}
+
+/// Reads the manifest of the bundle, without unpacking the database
+pub fn read_manifest(bundle_file: &str) -> Result<BundleManifest> {
+    let mut archive = open_bundle(bundle_file)?;
+    let manifest = read_manifest_from(&mut archive, bundle_file)?;
+    Ok(manifest)
+}
+
+fn open_bundle(bundle_file: &str) -> Result<ZipArchive<BufReader<File>>> {
+    if !Path::new(bundle_file).exists() {
+        return err!(RomstError::GenericError { message: format!("No bundle found at `{}`", bundle_file) });
+    }
+    ZipArchive::new(BufReader::new(File::open(bundle_file)?))
+        .or_else(|e| err!(RomstError::GenericError { message: format!("`{}` is not a database bundle: {}", bundle_file, e) }))
+}
+
+fn read_manifest_from(archive: &mut ZipArchive<BufReader<File>>, bundle_file: &str) -> Result<BundleManifest> {
+    let manifest: BundleManifest = match archive.by_name(MANIFEST_ENTRY) {
+        Ok(entry) => serde_json::from_reader(entry)?,
+        Err(_) => return err!(RomstError::GenericError { message: format!("The bundle `{}` has no manifest", bundle_file) }),
+    };
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        return err!(RomstError::GenericError { message: format!("The bundle `{}` has the format version {}, newer than the {} this version of romst supports",
+            bundle_file, manifest.format_version, BUNDLE_FORMAT_VERSION) });
+    }
+    if manifest.schema_version > SCHEMA_VERSION {
+        return err!(RomstError::GenericError { message: format!("The database in `{}` has the schema version {}, newer than the {} this version of romst supports",
+            bundle_file, manifest.schema_version, SCHEMA_VERSION) });
+    }
+    Ok(manifest)
+}
+
+/// Unpacks the database of the bundle into `db_file`, migrating its schema if it's older. The database is only
+/// replaced once the copy is checked
+pub fn import_bundle(bundle_file: &str, db_file: &str, overwrite: bool) -> Result<BundleImportReport> {
+    let db_path = Path::new(db_file);
+    if db_path.exists() && !overwrite {
+        return err!(RomstError::GenericError { message: format!("The database `{}` already exists, use overwrite to replace it", db_file) });
+    }
+    let mut archive = open_bundle(bundle_file)?;
+    let manifest = read_manifest_from(&mut archive, bundle_file)?;
+
+    let tmp_file = get_tmp_path(db_path);
+    remove_if_exists(&tmp_file)?;
+    let unpacked = unpack_database(&mut archive, bundle_file, &tmp_file, &manifest);
+    if unpacked.is_err() {
+        remove_if_exists(&tmp_file)?;
+    }
+    unpacked?;
+
+    for suffix in &["-wal", "-shm"] {
+        let mut sidecar = db_path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        remove_if_exists(Path::new(&sidecar))?;
+    }
+    fs::rename(&tmp_file, db_path)?;
+    info!("Bundle `{}` imported into `{}`", bundle_file, db_file);
+
+    Ok(BundleImportReport { db_file: db_file.to_string(), manifest, schema_version: SCHEMA_VERSION })
+}
+
+fn unpack_database(archive: &mut ZipArchive<BufReader<File>>, bundle_file: &str, db_file: &Path, manifest: &BundleManifest) -> Result<()> {
+    {
+        let mut entry = match archive.by_name(DATABASE_ENTRY) {
+            Ok(entry) => entry,
+            Err(_) => return err!(RomstError::GenericError { message: format!("The bundle `{}` has no database", bundle_file) }),
+        };
+        let mut writer = BufWriter::new(File::create(db_file)?);
+        io::copy(&mut entry, &mut writer)?;
+        writer.flush()?;
+    }
+
+    let mut conn = connection::open_read_write(db_file)?;
+    let integrity: String = conn.query_row("PRAGMA integrity_check;", params![], |row| row.get(0))?;
+    if integrity != "ok" {
+        return err!(RomstError::GenericError { message: format!("The database in `{}` is damaged: {}", bundle_file, integrity) });
+    }
+    let games: u32 = conn.query_row("SELECT COUNT(*) FROM games;", params![], |row| row.get(0))?;
+    let roms: u32 = conn.query_row("SELECT COUNT(*) FROM roms;", params![], |row| row.get(0))?;
+    if games != manifest.games || roms != manifest.roms {
+        return err!(RomstError::GenericError { message: format!("The database in `{}` has {} games and {} roms, the manifest says {} games and {} roms",
+            bundle_file, games, roms, manifest.games, manifest.roms) });
+    }
+    let from_version = migrate_schema(&mut conn)?;
+    if from_version != SCHEMA_VERSION {
+        info!("Database migrated from schema version {} to {}", from_version, SCHEMA_VERSION);
+    }
+    // Back to a single file before it's moved, the journal stays behind otherwise
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE);", params![], |_| Ok(()))?;
+    conn.close().map_err(|(_, e)| e)?;
+    Ok(())
+}
+
+fn remove_if_exists(file: &Path) -> Result<()> {
+    if file.exists() {
+        fs::remove_file(file)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use rusqlite::Connection;
+
+    use crate::{ImportOptions, Romst, data::reader::DataReader, sysout::DatImporterReporterSysOut};
+
+    use super::*;
+
+    #[test]
+    fn exports_and_imports_the_database() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_portable_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        let bundle_file = dir.join("test.romstdb").to_string_lossy().to_string();
+        let imported_file = dir.join("imported.rst").to_string_lossy().to_string();
+        Romst::import_dat("testdata/test.dat", &db_file, ImportOptions { overwrite: true, ..Default::default() }, None::<DatImporterReporterSysOut>)?;
+        Romst::add_set_tags(db_file.as_str(), "game1", &["favorite".to_string()])?;
+
+        let export = export_bundle(&db_file, &bundle_file)?;
+        assert_eq!(SCHEMA_VERSION, export.manifest.schema_version);
+        assert_eq!(export.manifest, read_manifest(&bundle_file)?);
+
+        let import = import_bundle(&bundle_file, &imported_file, false)?;
+        assert_eq!(export.manifest.games, import.manifest.games);
+        let reader = Romst::get_data_reader(&imported_file)?;
+        assert_eq!(Romst::get_data_reader(&db_file)?.get_game_list(crate::RomsetMode::Merged)?, reader.get_game_list(crate::RomsetMode::Merged)?);
+        drop(reader);
+        assert!(Romst::get_user_data(&imported_file, Some("favorite"))?.sets.contains_key("game1"));
+        // It's not replaced without overwrite
+        assert!(import_bundle(&bundle_file, &imported_file, false).is_err());
+        import_bundle(&bundle_file, &imported_file, true)?;
+
+        // The databases older than the schema versions are migrated
+        let conn = Connection::open(&db_file)?;
+        conn.execute_batch("DROP TABLE schema_info;")?;
+        drop(conn);
+        let old_export = export_bundle(&db_file, &bundle_file)?;
+        assert_eq!(0, old_export.manifest.schema_version);
+        let import = import_bundle(&bundle_file, &imported_file, true)?;
+        assert_eq!(SCHEMA_VERSION, import.schema_version);
+        assert_eq!(Some(SCHEMA_VERSION), DBReader::from_path(&imported_file)?.get_schema_version()?);
+        assert!(Romst::get_data_reader(&imported_file)?.is_import_complete()?);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! The sets a fix or a rebuild goes through first, so the ones that matter most are done even if the run is
+//! interrupted before the rest.
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::Result;
+
+use crate::{err, error::RomstError};
+
+use super::reader::sqlite::SetUserData;
+
+/// A line of the list starting with it stands for all the sets with the tag
+pub const TAG_PREFIX: &str = "tag:";
+
+#[derive(Debug, Default, Clone)]
+pub struct SetPriority {
+    ranks: HashMap<String, usize>,
+}
+
+impl SetPriority {
+    /// The sets in order, a set listed twice keeps its first position
+    pub fn new<S>(sets: impl IntoIterator<Item = S>) -> Self where S: Into<String> {
+        let mut ranks = HashMap::new();
+        for set_name in sets {
+            let rank = ranks.len();
+            ranks.entry(set_name.into()).or_insert(rank);
+        }
+        Self { ranks }
+    }
+
+    /// Reads the list with a set per line, or `tag:<tag>` for the sets with the tag in the order of their names, the
+    /// tags are looked up in the user data. Empty lines and `#` comments are skipped
+    pub fn from_file(file: impl AsRef<Path>, user_data: Option<&SetUserData>) -> Result<Self> {
+        let file = file.as_ref();
+        let contents = match fs::read_to_string(file) {
+            Ok(contents) => contents,
+            Err(e) => return err!(RomstError::GenericError { message: format!("Error reading the priority list `{}`: {}", file.to_string_lossy(), e) }),
+        };
+
+        let mut sets = vec![];
+        for line in contents.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            match line.strip_prefix(TAG_PREFIX) {
+                Some(tag) => match user_data {
+                    Some(user_data) => {
+                        let tag = tag.trim();
+                        sets.extend(user_data.sets.iter().filter(|(_, entry)| entry.tags.contains(tag)).map(|(set_name, _)| set_name.to_owned()));
+                    }
+                    None => return err!(RomstError::GenericError { message: format!("The tag `{}` needs a database to find its sets", tag.trim()) }),
+                },
+                None => sets.push(line.to_string()),
+            }
+        }
+
+        Ok(Self::new(sets))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranks.is_empty()
+    }
+
+    /// The position of the set in the list, the sets not in it go after all the listed ones
+    pub fn get_rank(&self, set_name: &str) -> usize {
+        self.ranks.get(set_name).copied().unwrap_or(self.ranks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use super::*;
+
+    #[test]
+    fn reads_the_sets_and_the_tags_in_order() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_priority_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let file = dir.join("favorites.txt");
+        fs::write(&file, "# favorites\nsf2\n\ntag:best\nsf2\n")?;
+
+        let mut user_data = SetUserData::default();
+        for set_name in ["mslug", "dkong"] {
+            user_data.sets.entry(set_name.to_string()).or_default().tags.insert("best".to_string());
+        }
+        let priority = SetPriority::from_file(&file, Some(&user_data))?;
+        assert_eq!(0, priority.get_rank("sf2"));
+        assert_eq!(1, priority.get_rank("dkong"));
+        assert_eq!(2, priority.get_rank("mslug"));
+        assert_eq!(3, priority.get_rank("pacman"));
+
+        assert!(SetPriority::from_file(&file, None).is_err());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+//! A reader keeping the last sets asked, for the consumers that ask for the same sets again and again, like the UIs
+//! going back and forth through the sets list. Only the info of the sets is kept, everything else goes to the reader
+//! wrapped, which is still there for the queries the trait doesn't have.
+use std::{cell::RefCell, collections::{HashMap, VecDeque}};
+
+use anyhow::Result;
+
+use crate::{RomsetMode, data::{filter::GameFilter, ignored_roms::IgnoredRoms, models::{dat_info::Packing, file::DataFile, game::Game, set::GameSet}, reporter::{scan_report::ScanReport, summary::MissingSets}}};
+
+use super::{DataReader, DbDataEntry, FileCheckSearch, FoundRom, RomFilter, RomSearch, SetDependencies};
+
+/// The sets kept if no other capacity is given
+pub const DEFAULT_CACHE_CAPACITY: usize = 64;
+
+/// The info of the sets by game and mode, the least recently used goes first when it's full
+#[derive(Debug)]
+struct SetInfoCache {
+    capacity: usize,
+    // The most recently used at the back
+    sets: VecDeque<((String, RomsetMode), GameSet)>,
+}
+
+impl SetInfoCache {
+    fn get(&mut self, game_name: &str, rom_mode: RomsetMode) -> Option<GameSet> {
+        let position = self.sets.iter().position(|((name, mode), _)| name == game_name && *mode == rom_mode)?;
+        let entry = self.sets.remove(position)?;
+        let game_set = entry.1.clone();
+        self.sets.push_back(entry);
+        Some(game_set)
+    }
+
+    fn insert(&mut self, game_name: &str, rom_mode: RomsetMode, game_set: GameSet) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.sets.len() >= self.capacity {
+            self.sets.pop_front();
+        }
+        self.sets.push_back(((game_name.to_string(), rom_mode), game_set));
+    }
+}
+
+#[derive(Debug)]
+pub struct CachedDataReader<R: DataReader> {
+    reader: R,
+    cache: RefCell<SetInfoCache>,
+}
+
+impl<R: DataReader> CachedDataReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_capacity(reader, DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Keeps up to `capacity` sets, with 0 nothing is kept
+    pub fn with_capacity(reader: R, capacity: usize) -> Self {
+        Self { reader, cache: RefCell::new(SetInfoCache { capacity, sets: VecDeque::new() }) }
+    }
+
+    pub fn get_reader(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_reader(self) -> R {
+        self.reader
+    }
+
+    /// Forgets the sets kept, for when the database changes
+    pub fn clear(&self) {
+        self.cache.borrow_mut().sets.clear();
+    }
+
+    /// The number of sets kept
+    pub fn len(&self) -> usize {
+        self.cache.borrow().sets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<R: DataReader> DataReader for CachedDataReader<R> {
+    fn get_game_list(&self, rom_mode: RomsetMode) -> Result<Vec<(String, String)>> {
+        self.reader.get_game_list(rom_mode)
+    }
+
+    fn get_game<S>(&self, game_name: S) -> Option<Game> where S: AsRef<str> + rusqlite::ToSql {
+        self.reader.get_game(game_name)
+    }
+
+    fn get_romset_roms<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<Vec<DbDataEntry<DataFile>>> where S: AsRef<str> + rusqlite::ToSql {
+        self.reader.get_romset_roms(game_name, rom_mode)
+    }
+
+    fn get_romsets_roms(&self, game_names: &[String], rom_mode: RomsetMode) -> Result<HashMap<String, Vec<DbDataEntry<DataFile>>>> {
+        self.reader.get_romsets_roms(game_names, rom_mode)
+    }
+
+    fn get_game_set<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<GameSet> where S: AsRef<str> + rusqlite::ToSql {
+        self.reader.get_game_set(game_name, rom_mode)
+    }
+
+    fn get_set_info<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<GameSet> where S: AsRef<str> {
+        let game_name = game_name.as_ref();
+        if let Some(game_set) = self.cache.borrow_mut().get(game_name, rom_mode) {
+            return Ok(game_set);
+        }
+        // Errors aren't kept, the set may be there once the database is imported again
+        let game_set = self.reader.get_set_info(game_name, rom_mode)?;
+        self.cache.borrow_mut().insert(game_name, rom_mode, game_set.clone());
+        Ok(game_set)
+    }
+
+    fn get_romset_disks<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<Vec<DataFile>> where S: AsRef<str> {
+        self.reader.get_romset_disks(game_name, rom_mode)
+    }
+
+    fn get_games_with_rom_name(&self, rom_name: &str) -> Result<Vec<String>> {
+        self.reader.get_games_with_rom_name(rom_name)
+    }
+
+    fn get_packing(&self) -> Result<Option<Packing>> {
+        self.reader.get_packing()
+    }
+
+    fn get_dat_mode(&self) -> Result<Option<RomsetMode>> {
+        self.reader.get_dat_mode()
+    }
+
+    fn find_roms(&self, filter: &RomFilter) -> Result<Vec<FoundRom>> {
+        self.reader.find_roms(filter)
+    }
+
+    fn get_games_in_scope(&self, scope: &GameFilter) -> Result<Vec<Game>> {
+        self.reader.get_games_in_scope(scope)
+    }
+
+    fn get_missing_sets(&self, report: &ScanReport, scope: &GameFilter) -> Result<MissingSets> {
+        self.reader.get_missing_sets(report, scope)
+    }
+
+    fn get_ignored_roms(&self) -> Result<IgnoredRoms> {
+        self.reader.get_ignored_roms()
+    }
+
+    fn get_mode_override<S>(&self, game_name: S) -> Result<Option<RomsetMode>> where S: AsRef<str> {
+        self.reader.get_mode_override(game_name)
+    }
+
+    fn get_rom_usage<S>(&self, game_name: S, rom_name: S, rom_mode: RomsetMode) -> Result<RomSearch> where S: AsRef<str> + rusqlite::ToSql {
+        self.reader.get_rom_usage(game_name, rom_name, rom_mode)
+    }
+
+    fn get_romset_shared_roms<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<RomSearch> where S: AsRef<str> + rusqlite::ToSql {
+        self.reader.get_romset_shared_roms(game_name, rom_mode)
+    }
+
+    fn get_sets_using_rom_id(&self, rom_id: u32) -> Result<RomSearch> {
+        self.reader.get_sets_using_rom_id(rom_id)
+    }
+
+    fn get_romsets_from_roms(&self, roms: Vec<DataFile>, rom_mode: RomsetMode) -> Result<RomSearch> {
+        self.reader.get_romsets_from_roms(roms, rom_mode)
+    }
+
+    fn get_romset_dependencies<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<SetDependencies> where S: AsRef<str> {
+        self.reader.get_romset_dependencies(game_name, rom_mode)
+    }
+
+    fn get_devices_for_game<S>(&self, game_name: S) -> Result<SetDependencies> where S: AsRef<str> + rusqlite::ToSql {
+        self.reader.get_devices_for_game(game_name)
+    }
+
+    fn get_file_checks(&self) -> Result<FileCheckSearch> {
+        self.reader.get_file_checks()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::{ImportOptions, Romst, sysout::DatImporterReporterSysOut};
+
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_sets_asked() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_cached_reader_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        Romst::import_dat("testdata/test.dat", &db_file, ImportOptions::default(), None::<DatImporterReporterSysOut>)?;
+        let reader = CachedDataReader::with_capacity(Romst::get_data_reader(&db_file)?, 2);
+
+        let game1 = reader.get_set_info("game1", RomsetMode::Merged)?;
+        assert_eq!(game1.roms.len(), reader.get_set_info("game1", RomsetMode::Merged)?.roms.len());
+        assert_eq!(1, reader.len());
+        // The same set in another mode is another entry
+        reader.get_set_info("game1", RomsetMode::NonMerged)?;
+        assert_eq!(2, reader.len());
+        // game1 in merged is the least recently used
+        reader.get_set_info("game1", RomsetMode::NonMerged)?;
+        reader.get_set_info("game2", RomsetMode::Merged)?;
+        assert_eq!(2, reader.len());
+        assert!(reader.cache.borrow_mut().get("game1", RomsetMode::Merged).is_none());
+        assert!(reader.cache.borrow_mut().get("game1", RomsetMode::NonMerged).is_some());
+
+        assert!(reader.get_set_info("unknown", RomsetMode::Merged).is_err());
+        assert_eq!(2, reader.len());
+        reader.clear();
+        assert!(reader.is_empty());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
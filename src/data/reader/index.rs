@@ -0,0 +1,531 @@
+use std::{collections::BTreeSet, fmt::Display, fs, ops::Deref, path::Path};
+
+use anyhow::Result;
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+
+use crate::{data::{cleaner::get_tmp_path, models::file::{DataFile, DataFileInfo, FileType}}, err, error::RomstError, filesystem::hash::to_hex};
+
+const INDEX_MAGIC: &[u8; 8] = b"RSTIDX3\0";
+const HEADER_SIZE: usize = 8 + 8 + 4 * 4;
+const ROM_RECORD_SIZE: usize = 4 + 1 + 4 + 4 + 20 + 16;
+
+const HAS_CRC: u8 = 1;
+const HAS_SHA1: u8 = 1 << 1;
+const HAS_MD5: u8 = 1 << 2;
+const HAS_SIZE: u8 = 1 << 3;
+const OTHER_CRC: u8 = 1 << 4;
+const OTHER_SHA1: u8 = 1 << 5;
+const OTHER_MD5: u8 = 1 << 6;
+
+/// A hash of the database that is not lowercase hexadecimal, the database compares hashes as text so it never equals
+/// the hash of a file
+const OTHER_HASH: &str = "-";
+
+/// An index of the roms hashes, to match the files with the roms without querying the database.
+/// The file is made of fixed size records, the roms sorted by id and the lookup tables sorted by hash, so it's mapped in
+/// memory and searched in place with a binary search.
+///
+/// Layout, all numbers little endian:
+/// - Header: magic, fingerprint of the database (u64), number of roms, crc records, sha1 records and md5 records (u32)
+/// - rom records: id (u32), flags of the values present (u8), crc (u32), size (u32), sha1 (20 bytes), md5 (16 bytes)
+/// - crc records: crc (u32), rom record (u32)
+/// - sha1 records: first 8 bytes of the sha1 (u64), rom record (u32)
+/// - md5 records: first 8 bytes of the md5 (u64), rom record (u32)
+#[derive(Debug, PartialEq, Eq)]
+pub struct RomIndex {
+    data: IndexData,
+    fingerprint: u64,
+    roms: u32,
+    crc: Table,
+    sha1: Table,
+    md5: Table,
+}
+
+/// The bytes of the index, built in memory or mapped from the file
+#[derive(Debug)]
+enum IndexData {
+    Owned(Vec<u8>),
+    #[cfg(target_os = "linux")]
+    Mapped(Mapping),
+}
+
+impl IndexData {
+    #[cfg(target_os = "linux")]
+    fn load(path: &Path) -> Result<Self> {
+        let file = fs::File::open(path)?;
+        // An empty file can't be mapped
+        if file.metadata()?.len() == 0 {
+            return Ok(IndexData::Owned(vec![]));
+        }
+        Ok(IndexData::Mapped(Mapping::map(&file)?))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn load(path: &Path) -> Result<Self> {
+        Ok(IndexData::Owned(fs::read(path)?))
+    }
+}
+
+impl Deref for IndexData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            IndexData::Owned(data) => data,
+            #[cfg(target_os = "linux")]
+            IndexData::Mapped(mapping) => mapping,
+        }
+    }
+}
+
+impl PartialEq for IndexData {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for IndexData {}
+
+/// A read only, private mapping of a whole file
+#[cfg(target_os = "linux")]
+struct Mapping {
+    address: *mut libc::c_void,
+    len: usize,
+}
+
+#[cfg(target_os = "linux")]
+impl Mapping {
+    fn map(file: &fs::File) -> Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = file.metadata()?.len() as usize;
+        let address = unsafe { libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0) };
+        if address == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Self { address, len })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // The mapping lives as long as self, and the index files are replaced by a rename, never written in place
+        unsafe { std::slice::from_raw_parts(self.address as *const u8, self.len) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.address, self.len) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::fmt::Debug for Mapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mapping").field("len", &self.len).finish()
+    }
+}
+
+// The mapping is only read
+#[cfg(target_os = "linux")]
+unsafe impl Send for Mapping {}
+#[cfg(target_os = "linux")]
+unsafe impl Sync for Mapping {}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Table {
+    start: usize,
+    len: usize,
+    key_size: usize,
+}
+
+impl Table {
+    fn record_size(&self) -> usize {
+        self.key_size + 4
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.len * self.record_size()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RomIndexInfo {
+    pub roms: u32,
+    pub crc_entries: usize,
+    pub sha1_entries: usize,
+    pub md5_entries: usize,
+}
+
+impl Display for RomIndexInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Rom index")?;
+        writeln!(f, "- Roms: {}", self.roms)?;
+        writeln!(f, "- CRC entries: {}", self.crc_entries)?;
+        writeln!(f, "- SHA1 entries: {}", self.sha1_entries)?;
+        writeln!(f, "- MD5 entries: {}", self.md5_entries)
+    }
+}
+
+/// A hash as stored in the index, only the lowercase hexadecimal hashes are stored as bytes
+enum Hash<const N: usize> {
+    Missing,
+    Canonical([u8; N]),
+    Other,
+}
+
+impl<const N: usize> Hash<N> {
+    fn parse(hash: Option<&str>) -> Self {
+        let hash = match hash {
+            Some(hash) if hash.len() == N * 2 => hash,
+            Some(_) => return Hash::Other,
+            None => return Hash::Missing,
+        };
+        let mut bytes = [0; N];
+        for (byte, digits) in bytes.iter_mut().zip(hash.as_bytes().chunks(2)) {
+            match (hex_value(digits[0]), hex_value(digits[1])) {
+                (Some(high), Some(low)) => *byte = high << 4 | low,
+                _ => return Hash::Other,
+            }
+        }
+        Hash::Canonical(bytes)
+    }
+
+    /// The hash to search with, `None` if it can't be searched in the index
+    fn searchable(self) -> Option<Option<[u8; N]>> {
+        match self {
+            Hash::Missing => Some(None),
+            Hash::Canonical(bytes) => Some(Some(bytes)),
+            Hash::Other => None,
+        }
+    }
+
+    /// Stores the hash in the record, returns the flag for it
+    fn store(&self, record: &mut [u8], canonical: u8, other: u8) -> u8 {
+        match self {
+            Hash::Missing => 0,
+            Hash::Canonical(bytes) => {
+                record.copy_from_slice(bytes);
+                canonical
+            },
+            Hash::Other => other,
+        }
+    }
+}
+
+/// Identifies the database the index is built from, by the date of its last import and its roms, so an index is
+/// outdated once the DAT is imported again even if the count of roms is the same
+pub fn db_fingerprint(last_import: Option<&str>, roms: u32) -> u64 {
+    // FNV-1a, stable across builds unlike the hasher of the standard library
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in last_import.unwrap_or_default().as_bytes().iter().chain(roms.to_le_bytes().iter()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+impl RomIndex {
+    pub fn build(conn: &Connection, fingerprint: u64) -> Result<Self> {
+        let mut stmt = conn.prepare("SELECT id, crc, sha1, md5, size FROM roms ORDER BY id;")?;
+        let rows = stmt.query_map(params![], |row| {
+            let id: u32 = row.get(0)?;
+            let crc: Option<String> = row.get(1)?;
+            let sha1: Option<String> = row.get(2)?;
+            let md5: Option<String> = row.get(3)?;
+            let size: Option<u32> = row.get(4)?;
+            Ok((id, crc, sha1, md5, size))
+        })?;
+
+        let mut records = vec![];
+        let mut crc_records = vec![];
+        let mut sha1_records = vec![];
+        let mut md5_records = vec![];
+        for (position, row) in rows.enumerate() {
+            let (id, crc, sha1, md5, size) = row?;
+            let position = position as u32;
+            let crc = Hash::<4>::parse(crc.as_deref());
+            let sha1 = Hash::<20>::parse(sha1.as_deref());
+            let md5 = Hash::<16>::parse(md5.as_deref());
+
+            let mut record = [0; ROM_RECORD_SIZE];
+            record[0..4].copy_from_slice(&id.to_le_bytes());
+            let mut flags = crc.store(&mut record[5..9], HAS_CRC, OTHER_CRC)
+                | sha1.store(&mut record[13..33], HAS_SHA1, OTHER_SHA1)
+                | md5.store(&mut record[33..49], HAS_MD5, OTHER_MD5);
+            if let Some(size) = size {
+                record[9..13].copy_from_slice(&size.to_le_bytes());
+                flags |= HAS_SIZE;
+            }
+            record[4] = flags;
+            records.extend_from_slice(&record);
+
+            if let Hash::Canonical(crc) = crc {
+                crc_records.push((u32::from_be_bytes(crc) as u64, position));
+            }
+            if let Hash::Canonical(sha1) = sha1 {
+                sha1_records.push((get_prefix(&sha1), position));
+            }
+            if let Hash::Canonical(md5) = md5 {
+                md5_records.push((get_prefix(&md5), position));
+            }
+        }
+
+        let roms = (records.len() / ROM_RECORD_SIZE) as u32;
+        let mut data = Vec::with_capacity(HEADER_SIZE + records.len() + crc_records.len() * 8 + (sha1_records.len() + md5_records.len()) * 12);
+        data.extend_from_slice(INDEX_MAGIC);
+        data.extend_from_slice(&fingerprint.to_le_bytes());
+        for number in [roms, crc_records.len() as u32, sha1_records.len() as u32, md5_records.len() as u32] {
+            data.extend_from_slice(&number.to_le_bytes());
+        }
+        data.append(&mut records);
+        for (table, key_size) in [(&mut crc_records, 4), (&mut sha1_records, 8), (&mut md5_records, 8)] {
+            table.sort_unstable();
+            for (key, position) in table.iter() {
+                data.extend_from_slice(&key.to_le_bytes()[0..key_size]);
+                data.extend_from_slice(&position.to_le_bytes());
+            }
+        }
+
+        match RomIndex::from_data(IndexData::Owned(data)) {
+            Some(index) => Ok(index),
+            None => err!(RomstError::GenericError { message: "Error building the rom index".to_string() }),
+        }
+    }
+
+    fn from_data(data: IndexData) -> Option<Self> {
+        if data.len() < HEADER_SIZE || &data[0..8] != INDEX_MAGIC {
+            return None;
+        }
+        let fingerprint = read_u64(&data, 8);
+        let roms = read_u32(&data, 16);
+        let crc = Table { start: HEADER_SIZE + roms as usize * ROM_RECORD_SIZE, len: read_u32(&data, 20) as usize, key_size: 4 };
+        let sha1 = Table { start: crc.end(), len: read_u32(&data, 24) as usize, key_size: 8 };
+        let md5 = Table { start: sha1.end(), len: read_u32(&data, 28) as usize, key_size: 8 };
+        if data.len() != md5.end() {
+            return None;
+        }
+
+        Some(Self { data, fingerprint, roms, crc, sha1, md5 })
+    }
+
+    pub fn get_roms(&self) -> u32 {
+        self.roms
+    }
+
+    pub fn get_fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn get_info(&self) -> RomIndexInfo {
+        RomIndexInfo { roms: self.roms, crc_entries: self.crc.len, sha1_entries: self.sha1.len, md5_entries: self.md5.len }
+    }
+
+    /// Writes the index to a temporary file first and then replaces the old one, as it may be mapped by another process
+    pub fn write(&self, path: &impl AsRef<Path>) -> Result<()> {
+        let tmp_path = get_tmp_path(path.as_ref());
+        fs::write(&tmp_path, &*self.data)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Maps the index file in memory, so only the pages of the records searched are read
+    pub fn load(path: &impl AsRef<Path>) -> Result<Self> {
+        match RomIndex::from_data(IndexData::load(path.as_ref())?) {
+            Some(index) => Ok(index),
+            None => err!(RomstError::ParsingError { message: format!("`{}` is not a rom index of this version, or it's corrupted", path.as_ref().to_string_lossy()) }),
+        }
+    }
+
+    /// The roms of the database sharing a hash with the file, with their values as the database has them, so they are
+    /// matched with the file without a query. `None` if the index can't decide, when a hash of the file is not lowercase
+    /// hexadecimal, as the database compares the hashes as text
+    pub fn find_candidates(&self, file: &DataFile) -> Option<Vec<(u32, DataFileInfo)>> {
+        let crc = Hash::<4>::parse(file.info.crc.as_deref()).searchable()?;
+        let sha1 = Hash::<20>::parse(file.info.sha1.as_deref()).searchable()?;
+        let md5 = Hash::<16>::parse(file.info.md5.as_deref()).searchable()?;
+
+        let mut positions = BTreeSet::new();
+        if let Some(crc) = crc {
+            positions.extend(self.find_in(&self.crc, u32::from_be_bytes(crc) as u64));
+        }
+        if let Some(sha1) = sha1 {
+            positions.extend(self.find_in(&self.sha1, get_prefix(&sha1)).filter(|position| self.get_record(*position)[13..33] == sha1));
+        }
+        if let Some(md5) = md5 {
+            positions.extend(self.find_in(&self.md5, get_prefix(&md5)).filter(|position| self.get_record(*position)[33..49] == md5));
+        }
+
+        Some(positions.into_iter().map(|position| self.get_rom(position)).collect())
+    }
+
+    /// The positions of the rom records with the key in the table
+    fn find_in<'a>(&'a self, table: &'a Table, key: u64) -> impl Iterator<Item = u32> + 'a {
+        let key_at = move |i: usize| {
+            let position = table.start + i * table.record_size();
+            if table.key_size == 4 { read_u32(&self.data, position) as u64 } else { read_u64(&self.data, position) }
+        };
+        let (mut low, mut high) = (0, table.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if key_at(middle) < key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        (low..table.len).take_while(move |i| key_at(*i) == key)
+            .map(move |i| read_u32(&self.data, table.start + i * table.record_size() + table.key_size))
+            .filter(move |position| *position < self.roms)
+    }
+
+    fn get_record(&self, position: u32) -> &[u8] {
+        let start = HEADER_SIZE + position as usize * ROM_RECORD_SIZE;
+        &self.data[start..start + ROM_RECORD_SIZE]
+    }
+
+    fn get_rom(&self, position: u32) -> (u32, DataFileInfo) {
+        let record = self.get_record(position);
+        let flags = record[4];
+        let mut info = DataFileInfo::new(FileType::Rom);
+        info.crc = get_hash(flags, HAS_CRC, OTHER_CRC, &record[5..9]);
+        info.size = if flags & HAS_SIZE != 0 { Some(read_u32(record, 9)) } else { None };
+        info.sha1 = get_hash(flags, HAS_SHA1, OTHER_SHA1, &record[13..33]);
+        info.md5 = get_hash(flags, HAS_MD5, OTHER_MD5, &record[33..49]);
+        (read_u32(record, 0), info)
+    }
+}
+
+fn get_hash(flags: u8, canonical: u8, other: u8, bytes: &[u8]) -> Option<String> {
+    if flags & canonical != 0 {
+        Some(to_hex(bytes))
+    } else if flags & other != 0 {
+        Some(OTHER_HASH.to_string())
+    } else {
+        None
+    }
+}
+
+fn get_prefix(hash: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&hash[0..8]);
+    u64::from_be_bytes(bytes)
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        _ => None,
+    }
+}
+
+fn read_u32(data: &[u8], position: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[position..position + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(data: &[u8], position: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[position..position + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs::File, io::BufReader};
+    use rusqlite::OpenFlags;
+    use crate::{ImportOptions, Romst, data::{importer::DatImporter, models::file::{DataFileInfo, FileType}, writer::sqlite::DBWriter}, sysout::DatImporterReporterSysOut};
+    use super::*;
+
+    fn get_db_connection(dat_path: &impl AsRef<Path>) -> Result<Connection> {
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        let writer = DBWriter::from_connection(&mut conn, 5);
+        let mut importer = DatImporter::<BufReader<File>, DBWriter>::from_path(dat_path, writer)?;
+        importer.load_dat()?;
+
+        Ok(conn)
+    }
+
+    #[test]
+    fn finds_candidates_and_round_trips() -> Result<()> {
+        let conn = get_db_connection(&Path::new("testdata").join("test.dat"))?;
+        let index = RomIndex::build(&conn, 0)?;
+
+        let ids = |file: &DataFile| index.find_candidates(file).map(|candidates| candidates.into_iter().map(|(id, _info)| id).collect::<Vec<_>>());
+
+        let mut rom = DataFile::new("rom1", DataFileInfo::new(FileType::Rom));
+        rom.info.crc = Some("1b736d41".to_string());
+        assert_eq!(ids(&rom), Some(vec![5]));
+        let (_id, info) = &index.find_candidates(&rom).unwrap_or_default()[0];
+        assert_eq!(info.crc.as_deref(), Some("1b736d41"));
+
+        let mut rom = DataFile::new("rom1", DataFileInfo::new(FileType::Rom));
+        rom.info.sha1 = Some("8bb3a81b9fa2de5163f0ffc634a998c455bcca25".to_string());
+        assert_eq!(ids(&rom), Some(vec![2]));
+
+        // Only the first bytes of the sha1 are in the lookup table, the rest must match too
+        let mut same_prefix = rom.clone();
+        same_prefix.info.sha1 = Some("8bb3a81b9fa2de51000000000000000000000000".to_string());
+        assert_eq!(ids(&same_prefix), Some(vec![]));
+
+        let mut unknown = DataFile::new("unknown", DataFileInfo::new(FileType::Rom));
+        unknown.info.crc = Some("00000000".to_string());
+        assert_eq!(ids(&unknown), Some(vec![]));
+
+        // The database compares the hashes as text, the index can't decide for a hash in another form
+        let mut uppercase = DataFile::new("uppercase", DataFileInfo::new(FileType::Rom));
+        uppercase.info.crc = Some("1B736D41".to_string());
+        assert_eq!(ids(&uppercase), None);
+
+        let path = env::temp_dir().join(format!("romst_index_{}.idx", std::process::id()));
+        index.write(&path)?;
+        let loaded = RomIndex::load(&path)?;
+        assert_eq!(loaded, index);
+        assert_eq!(index.find_candidates(&rom), loaded.find_candidates(&rom));
+
+        // Writing the index again doesn't change the one already loaded
+        let other = RomIndex::build(&conn, 1)?;
+        other.write(&path)?;
+        assert_eq!(loaded, index);
+        assert_eq!(RomIndex::load(&path)?, other);
+        fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn index_is_replaced_only_by_a_successful_import() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_index_import_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        let index_file = Romst::get_index_file(&db_file);
+        let options = || ImportOptions { overwrite: true, ..Default::default() };
+        Romst::import_dat("testdata/test.dat", &db_file, options(), None::<DatImporterReporterSysOut>)?;
+        Romst::build_index(&db_file)?;
+        let first_index = RomIndex::load(&index_file)?;
+        assert_eq!(first_index.get_fingerprint(), Romst::get_data_reader(&db_file)?.get_db_fingerprint()?);
+
+        // A failed import leaves the database and its index as they were
+        let broken_dat = dir.join("broken.dat");
+        fs::write(&broken_dat, "<datafile><game name=\"game1\"><rom name=")?;
+        assert!(Romst::import_dat(broken_dat.to_string_lossy().to_string(), db_file.clone(), options(), None::<DatImporterReporterSysOut>).is_err());
+        assert_eq!(first_index, RomIndex::load(&index_file)?);
+
+        // The same DAT imported again has the same roms, but the index is outdated
+        Romst::import_dat("testdata/test.dat", &db_file, options(), None::<DatImporterReporterSysOut>)?;
+        assert!(!Path::new(&index_file).exists());
+        first_index.write(&index_file)?;
+        assert_ne!(first_index.get_fingerprint(), Romst::get_data_reader(&db_file)?.get_db_fingerprint()?);
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -1,12 +1,13 @@
 pub mod sqlite;
+pub mod index;
+pub mod cached;
 
-use std::{collections::{HashMap, HashSet}, fmt::Display, ops::Deref, rc::Rc};
+use std::{collections::{BTreeMap, HashMap, HashSet, VecDeque}, fmt::Display, ops::Deref, rc::Rc};
 
 use crate::{RomsetMode, err, error::RomstError, filesystem::FileChecks};
-use super::models::{file::DataFile, game::Game, set::GameSet};
+use super::{filter::GameFilter, ignored_roms::IgnoredRoms, models::{dat_info::Packing, file::{DataFile, DataFileInfo}, game::Game, set::GameSet}, reporter::{formatter::{EnglishFormatter, ReportFormatter}, scan_report::ScanReport, summary::MissingSets}};
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
-use console::Style;
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct DbDataEntry<T> {
     pub id: u32,
@@ -24,9 +25,47 @@ impl <T> Display for DbDataEntry<T> where T: Display {
     }
 }
 
+/// How a rom was matched with a rom of the database, from the weakest to the strongest evidence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MatchEvidence {
+    /// Only the crc and the size agree, collisions are possible
+    CrcAndSize,
+    Md5,
+    Sha1,
+    /// The sha1, md5, crc and size are known by both and all of them agree
+    AllHashes,
+}
+
+/// The strongest evidence the rom is the rom of the database. There's none if a hash or the size known by both
+/// contradicts it, or if they don't share enough to tell
+pub fn get_match_evidence(db_rom: &DataFileInfo, rom: &DataFileInfo) -> Option<MatchEvidence> {
+    fn agrees<T: PartialEq>(db_value: &Option<T>, value: &Option<T>) -> Option<bool> {
+        match (db_value, value) {
+            (Some(db_value), Some(value)) => Some(db_value == value),
+            _ => None,
+        }
+    }
+
+    let sha1 = agrees(&db_rom.sha1, &rom.sha1);
+    let md5 = agrees(&db_rom.md5, &rom.md5);
+    let crc = agrees(&db_rom.crc, &rom.crc);
+    let size = agrees(&db_rom.size, &rom.size);
+    if [sha1, md5, crc, size].contains(&Some(false)) {
+        return None;
+    }
+
+    match (sha1, md5, crc, size) {
+        (Some(true), Some(true), Some(true), Some(true)) => Some(MatchEvidence::AllHashes),
+        (Some(true), _, _, _) => Some(MatchEvidence::Sha1),
+        (None, Some(true), _, _) => Some(MatchEvidence::Md5),
+        (None, None, Some(true), Some(true)) => Some(MatchEvidence::CrcAndSize),
+        _ => None,
+    }
+}
+
 #[derive(Debug)]
 pub struct SetDependencies {
-    set_name: String,
+    pub set_name: String,
     pub dependencies: Vec<String>
 }
 
@@ -37,6 +76,23 @@ impl SetDependencies {
             dependencies: vec![],
         }
     }
+
+    /// Follows the device references from the set, as devices can reference other devices. Each device is listed
+    /// once, in the order they are found, so a chain referencing back to a device already seen ends there
+    pub fn resolve_devices<S, F>(set_name: S, device_refs: F) -> Self where S: Into<String>, F: Fn(&str) -> Vec<String> {
+        let mut set_dependencies = SetDependencies::new(set_name);
+        let mut seen = HashSet::new();
+        seen.insert(set_dependencies.set_name.to_string());
+        let mut pending = VecDeque::from(device_refs(&set_dependencies.set_name));
+        while let Some(device) = pending.pop_front() {
+            if seen.insert(device.to_string()) {
+                pending.extend(device_refs(&device));
+                set_dependencies.dependencies.push(device);
+            }
+        }
+
+        set_dependencies
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -101,6 +157,8 @@ impl Default for RomSearch {
     }
 }
 
+/// The roms of the file found in a set. A rom is kept by its id and the name it has in the file, so a file with a single
+/// copy of a rom the set has under two names only provides the name it has
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SetContent {
     roms_included: HashSet<Rc<DbDataEntry<DataFile>>>
@@ -117,29 +175,9 @@ impl SetContent {
     }
 }
 
-impl  Display for RomSearch {
+impl Display for RomSearch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if !self.set_results.is_empty() {
-            for game_roms in &self.set_results {
-                writeln!(f, "Set: {}", Style::new().green().bold().apply_to(game_roms.0))?;
-                let set_content = game_roms.1;
-                if !set_content.roms_included.is_empty() {
-                    writeln!(f, "  {}:", Style::new().cyan().apply_to("Roms"))?;
-                    for rom in &set_content.roms_included {
-                        writeln!(f, "   - {}", rom)?;
-                    }
-                }
-            }
-        }
-
-        if !self.unknowns.is_empty() {
-            writeln!(f, "  {}:", Style::new().red().apply_to("Unkown files"))?;
-            for unknown in &self.unknowns {
-                writeln!(f, "   - {}", unknown)?;
-            }
-        }
-
-        Ok(())
+        EnglishFormatter.write_rom_search(f, self)
     }
 }
 
@@ -166,11 +204,103 @@ impl FileCheckSearch {
     }
 }
 
+/// The predicates to find roms by, all of them have to match. A hash prefix matches the hashes starting with it,
+/// ignoring the case, and the size range includes both ends
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RomFilter {
+    pub sha1_prefix: Option<String>,
+    pub md5_prefix: Option<String>,
+    pub crc_prefix: Option<String>,
+    pub size_min: Option<u32>,
+    pub size_max: Option<u32>,
+}
+
+impl RomFilter {
+    /// Without predicates every rom matches
+    pub fn is_empty(&self) -> bool {
+        self.sha1_prefix.is_none() && self.md5_prefix.is_none() && self.crc_prefix.is_none() && self.size_min.is_none() && self.size_max.is_none()
+    }
+
+    /// The prefixes are only hexadecimal digits, and the size range is not reversed
+    pub fn validate(&self) -> Result<()> {
+        for prefix in [&self.sha1_prefix, &self.md5_prefix, &self.crc_prefix].iter().filter_map(|prefix| prefix.as_deref()) {
+            if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+                return err!(RomstError::ParsingError { message: format!("The hash prefix `{}` is not hexadecimal", prefix) });
+            }
+        }
+        if let (Some(size_min), Some(size_max)) = (self.size_min, self.size_max) {
+            if size_min > size_max {
+                return err!(RomstError::ParsingError { message: format!("The minimum size {} is bigger than the maximum size {}", size_min, size_max) });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A rom of the database matching a filter, with the names it has in each set using it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FoundRom {
+    pub id: u32,
+    pub info: DataFileInfo,
+    pub used_by: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RomFindReport {
+    pub filter: RomFilter,
+    pub roms: Vec<FoundRom>,
+}
+
+impl Display for RomFindReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_rom_find_report(f, self)
+    }
+}
+
+/// The other sets that have roms of a set, with the names of those roms in the set. A set sharing all the roms of
+/// another can be built from it, one sharing some of them can be started with it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedRoms {
+    pub set_name: String,
+    pub shared_with: BTreeMap<String, Vec<String>>,
+}
+
+impl SharedRoms {
+    /// From the search of the sets with the roms of the set, the set itself is left out
+    pub fn from_search<S>(set_name: S, rom_search: &RomSearch) -> Self where S: AsRef<str> {
+        let shared_with = rom_search.set_results.iter()
+            .filter(|(other_set, _content)| other_set.as_str() != set_name.as_ref())
+            .map(|(other_set, content)| {
+                let mut rom_names = content.get_roms_included().into_iter().map(|rom| rom.file.name.to_string()).collect::<Vec<_>>();
+                rom_names.sort();
+                rom_names.dedup();
+                (other_set.to_string(), rom_names)
+            }).collect();
+
+        Self { set_name: set_name.as_ref().to_string(), shared_with }
+    }
+}
+
+impl Display for SharedRoms {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_shared_roms(f, self)
+    }
+}
+
 pub trait DataReader {
     fn get_game_list(&self, rom_mode: RomsetMode) -> Result<Vec<(String, String)>>;
     fn get_game<S>(&self, game_name: S) -> Option<Game> where S: AsRef<str> + rusqlite::ToSql;
     /// Returns all the roms for a specific romset
     fn get_romset_roms<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<Vec<DbDataEntry<DataFile>>> where S: AsRef<str> + rusqlite::ToSql;
+    /// Returns the roms for each of the romsets, readers backed by a database fetch them at once.
+    /// Every name requested is in the result, sets without roms or not found have an empty list
+    fn get_romsets_roms(&self, game_names: &[String], rom_mode: RomsetMode) -> Result<HashMap<String, Vec<DbDataEntry<DataFile>>>> {
+        let mut romsets_roms = HashMap::new();
+        for game_name in game_names {
+            romsets_roms.insert(game_name.to_owned(), self.get_romset_roms(game_name.as_str(), rom_mode)?);
+        }
+        Ok(romsets_roms)
+    }
     fn get_game_set<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<GameSet> where S: AsRef<str> + rusqlite::ToSql {
         match self.get_game(&game_name) {
             Some(game) => {
@@ -191,17 +321,58 @@ pub trait DataReader {
         let device_refs = self.get_devices_for_game(game_name.as_ref())?;
         match self.get_game(game_name.as_ref()) {
             Some(game) => {
-                return Ok(GameSet::new(game, roms, vec![], vec![], device_refs.dependencies));
+                Ok(GameSet::new(game, roms, vec![], vec![], device_refs.dependencies))
             }
             None => err!(RomstError::GenericError{ message: format!("Game {} not found", game_name.as_ref()) })
         }
     }
+    /// The disks of a set, in merged mode with the disks of its clones. Readers without disks return none
+    fn get_romset_disks<S>(&self, _game_name: S, _rom_mode: RomsetMode) -> Result<Vec<DataFile>> where S: AsRef<str> {
+        Ok(vec![])
+    }
+    /// The games with a rom of this name, sorted. Readers that can't look them up return none
+    fn get_games_with_rom_name(&self, _rom_name: &str) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+    /// The packing the DAT expects for the sets. Readers without the info of the DAT return none
+    fn get_packing(&self) -> Result<Option<Packing>> {
+        Ok(None)
+    }
+    /// The mode the DAT is meant to be used in. Readers without the info of the DAT return none
+    fn get_dat_mode(&self) -> Result<Option<RomsetMode>> {
+        Ok(None)
+    }
+    /// The roms matching the filter, sorted by their id. Readers that can't search the roms return none
+    fn find_roms(&self, _filter: &RomFilter) -> Result<Vec<FoundRom>> {
+        Ok(vec![])
+    }
+    /// The games matching the scope, with their driver and category, sorted by name. Readers that can't list the games return none
+    fn get_games_in_scope(&self, _scope: &GameFilter) -> Result<Vec<Game>> {
+        Ok(vec![])
+    }
+    /// The sets of the scope the report didn't find, with the statuses of the ones found. The sets checked outside the
+    /// scope are left out, so checking a single directory doesn't count the rest of the DAT as missing
+    fn get_missing_sets(&self, report: &ScanReport, scope: &GameFilter) -> Result<MissingSets> {
+        Ok(MissingSets::new(report, self.get_games_in_scope(scope)?))
+    }
+    /// The roms not required to complete their sets, as they will never be found
+    fn get_ignored_roms(&self) -> Result<IgnoredRoms> {
+        Ok(IgnoredRoms::default())
+    }
+    /// The mode the set is always checked with, whatever the mode of the check is
+    fn get_mode_override<S>(&self, _game_name: S) -> Result<Option<RomsetMode>> where S: AsRef<str> {
+        Ok(None)
+    }
     /// Finds where this rom is included, in other games. Returns the games and the name used for that rom
     fn get_rom_usage<S>(&self, game_name: S, rom_name: S, rom_mode: RomsetMode) -> Result<RomSearch> where S: AsRef<str> + rusqlite::ToSql;
     /// Gets all romsets that include roms in the searched game
     /// This is useful to know what new (incomplete though) sets can be generated from the current one
     fn get_romset_shared_roms<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<RomSearch> where S: AsRef<str> + rusqlite::ToSql;
 
+    /// Finds all the sets referencing a rom from the database by its id, with the name the rom has in each set.
+    /// Parents and clones are listed separately
+    fn get_sets_using_rom_id(&self, rom_id: u32) -> Result<RomSearch>;
+
     /// Finds all romsets associated with the roms sent
     fn get_romsets_from_roms(&self, roms: Vec<DataFile>, rom_mode: RomsetMode) -> Result<RomSearch>;
 
@@ -209,15 +380,12 @@ pub trait DataReader {
         let mut result = self.get_devices_for_game(game_name.as_ref())?;
 
         // If we are in split mode, we add the parent as a dependency
-        match rom_mode {
-            RomsetMode::Split => {
-                if let Some(game) = self.get_game(game_name.as_ref()) {
-                    if let Some(clone_of) = game.clone_of {
-                        result.dependencies.push(clone_of);
-                    }
+        if let RomsetMode::Split = rom_mode {
+            if let Some(game) = self.get_game(game_name.as_ref()) {
+                if let Some(clone_of) = game.clone_of {
+                    result.dependencies.push(clone_of);
                 }
             }
-            _ => {}
         }
 
         Ok(result)
@@ -230,7 +398,9 @@ pub trait DataReader {
 
 #[cfg(test)]
 mod tests {
-    use super::{DbDataEntry, FileCheckSearch, RomSearch};
+    use std::collections::HashMap;
+
+    use super::{DbDataEntry, FileCheckSearch, MatchEvidence, RomSearch, SetDependencies, get_match_evidence};
     use crate::{data::models::file::{DataFile, DataFileInfo, FileType}, filesystem::FileChecks};
 
     #[test]
@@ -275,6 +445,22 @@ mod tests {
         assert!(file_checks.contains(FileChecks::CRC));
     }
 
+    #[test]
+    fn resolves_the_device_chain_once() {
+        let device_refs: HashMap<&str, Vec<&str>> = vec![
+            ("game1", vec!["cpu", "screen"]),
+            ("cpu", vec!["bus", "screen"]),
+            ("bus", vec!["cpu", "game1"]),
+        ].into_iter().collect();
+
+        let set_dependencies = SetDependencies::resolve_devices("game1", |game_name| {
+            device_refs.get(game_name).map(|devices| devices.iter().map(|device| device.to_string()).collect()).unwrap_or_default()
+        });
+
+        assert_eq!("game1", set_dependencies.set_name);
+        assert_eq!(vec!["cpu", "screen", "bus"], set_dependencies.dependencies);
+    }
+
     #[test]
     fn should_check_without_sha1_or_md5() {
         let file_check_search = FileCheckSearch {
@@ -336,4 +522,28 @@ mod tests {
         assert!(available_2.iter().find(|f| { if let Some(crc) = &f.file.info.crc { crc.eq(&"dc20b010".to_string()) } else { false } }).is_some());
         assert!(available_2.iter().find(|f| { if let Some(crc) = &f.file.info.crc { crc.eq(&"fbe0d501".to_string()) } else { false } }).is_some());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn scores_the_matches_by_the_hashes_shared() {
+        let rom = |sha1: Option<&str>, md5: Option<&str>, crc: Option<&str>, size: Option<u32>| {
+            let mut info = DataFileInfo::new(FileType::Rom);
+            info.sha1 = sha1.map(|sha1| sha1.to_string());
+            info.md5 = md5.map(|md5| md5.to_string());
+            info.crc = crc.map(|crc| crc.to_string());
+            info.size = size;
+            info
+        };
+        let searched = rom(Some("aaaa"), Some("bbbb"), Some("cccc"), Some(1024));
+
+        assert_eq!(Some(MatchEvidence::AllHashes), get_match_evidence(&searched, &searched));
+        assert_eq!(Some(MatchEvidence::Sha1), get_match_evidence(&rom(Some("aaaa"), None, None, Some(1024)), &searched));
+        assert_eq!(Some(MatchEvidence::Md5), get_match_evidence(&rom(None, Some("bbbb"), None, None), &searched));
+        assert_eq!(Some(MatchEvidence::CrcAndSize), get_match_evidence(&rom(None, None, Some("cccc"), Some(1024)), &searched));
+        // Not enough to tell
+        assert_eq!(None, get_match_evidence(&rom(None, None, Some("cccc"), None), &searched));
+        // A hash known by both contradicts the others
+        assert_eq!(None, get_match_evidence(&rom(Some("aaaa"), Some("dddd"), None, None), &searched));
+        assert_eq!(None, get_match_evidence(&rom(None, Some("bbbb"), None, Some(2048)), &searched));
+        assert!(MatchEvidence::AllHashes > MatchEvidence::Sha1 && MatchEvidence::Md5 > MatchEvidence::CrcAndSize);
+    }
+}
@@ -1,29 +1,36 @@
-use std::{collections::HashSet, fmt::Display, iter::FromIterator};
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, fmt::Display, iter::FromIterator, path::Path, str::FromStr};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use log::{debug, error, warn};
-use rusqlite::{Connection, Row, ToSql, params};
+use rusqlite::{Connection, OptionalExtension, Row, ToSql, params};
 use serde::{Deserialize, Serialize};
 
-use crate::{data::models::dat_info::DatInfo};
-use crate::{RomsetMode, data::models::{disk::GameDisk, file::{DataFile, DataFileInfo, FileType}, game::Game}};
+use crate::data::{connection, filter::GameFilter, ignored_roms::IgnoredRoms, models::dat_info::{DatInfo, MERGING_KEY, PACKING_KEY, Packing, parse_merging}, reporter::formatter::{EnglishFormatter, ReportFormatter}, reporter::scan_report::SetStatus, writer::{maintenance, sqlite::{CHECK_STATUS_TABLE, GAME_CATEGORIES_TABLE, IMPORT_DATE_KEY, IMPORT_STATUS_COMPLETE, IMPORT_STATUS_KEY, MODE_OVERRIDES_TABLE, IGNORED_ROMS_TABLE, SCHEMA_VERSION_KEY, SET_NOTES_TABLE, SET_TAGS_TABLE}}};
+use crate::{RomsetMode, filesystem::paths, data::models::{disk::GameDisk, file::{DataFile, DataFileInfo, DumpQuality, DumpStatus, FileType}, game::{Game, GameMetadata}}};
 
-use super::{DataReader, DbDataEntry, FileCheckSearch, RomSearch, SetDependencies};
+use super::{DataReader, DbDataEntry, FileCheckSearch, FoundRom, MatchEvidence, RomFilter, RomSearch, SetDependencies, get_match_evidence, index::{self, RomIndex}};
 
 #[derive(Debug)]
 pub struct SearchEntryIds<T> {
     pub found: Vec<DbDataEntry<T>>,
+    /// How each of the found entries was matched, in the same order. Only the roms record it
+    pub evidence: Vec<MatchEvidence>,
     pub not_found: Vec<T>,
     pub ignored: Vec<T>
 }
 
 impl <T> SearchEntryIds<T> {
-    fn new() -> Self { Self { found: vec![], not_found: vec![], ignored: vec![] } }
+    fn new() -> Self { Self { found: vec![], evidence: vec![], not_found: vec![], ignored: vec![] } }
 
     fn add_found(&mut self, id: u32, file: T) {
         self.found.push(DbDataEntry::new(id, file));
     }
 
+    fn add_found_with(&mut self, id: u32, file: T, evidence: MatchEvidence) {
+        self.add_found(id, file);
+        self.evidence.push(evidence);
+    }
+
     fn add_not_found(&mut self, entry: T) {
         self.not_found.push(entry);
     }
@@ -37,21 +44,364 @@ pub struct DBReport {
     pub roms_in_games: u32,
     pub samples: u32,
     pub device_refs: u32,
+    /// Of the roms in games, as a rom may have a different status in each game
+    pub dump_quality: DumpQuality,
+    pub stats: DBStats,
 }
 
 impl DBReport {
-    pub fn new(dat_info: DatInfo) -> Self { Self { dat_info, games: 0, roms: 0, roms_in_games: 0, samples: 0, device_refs: 0 } }
+    pub fn new(dat_info: DatInfo) -> Self { Self { dat_info, games: 0, roms: 0, roms_in_games: 0, samples: 0, device_refs: 0, dump_quality: DumpQuality::default(), stats: DBStats::default() } }
+}
+
+/// An open database with the metadata the UIs show about it, read once when it's opened
+#[derive(Debug)]
+pub struct DbHandle {
+    pub path: String,
+    pub dat_name: String,
+    pub dat_version: String,
+    /// Databases created before the schema version was stored don't have it
+    pub schema_version: Option<u32>,
+    pub games: u32,
+    pub roms: u32,
+    reader: DBReader,
+}
+
+impl DbHandle {
+    pub fn new<S>(path: S, reader: DBReader) -> Result<Self> where S: Into<String> {
+        let dat_info = reader.get_dat_info()?;
+        let schema_version = reader.get_schema_version()?;
+        let games = reader.get_games_count()?;
+        let roms = reader.get_roms_count()?;
+        Ok(Self { path: path.into(), dat_name: dat_info.name, dat_version: dat_info.version, schema_version, games, roms, reader })
+    }
+
+    pub fn get_reader(&self) -> &DBReader {
+        &self.reader
+    }
+
+    pub fn into_reader(self) -> DBReader {
+        self.reader
+    }
+
+    /// A one line summary for the UI headers, like `MAME 0.263 (Split) - 40000 sets`
+    pub fn get_title(&self, rom_mode: RomsetMode) -> String {
+        format!("{} {} ({}) - {} sets", self.dat_name, self.dat_version, rom_mode, self.games)
+    }
+}
+
+/// How the database is stored, to follow its growth between imports
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DBStats {
+    pub file_size: u64,
+    /// Databases imported before the date was stored don't have it
+    pub last_import: Option<String>,
+    pub tables: Vec<TableStats>,
+    pub indexes: Vec<IndexStats>,
+}
+
+/// The size is approximate, the pages used by the table including their free space. There's no size if SQLite can't
+/// report it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableStats {
+    pub name: String,
+    pub rows: u64,
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexStats {
+    pub name: String,
+    pub table: String,
+    pub size: Option<u64>,
 }
 
 impl Display for DBReport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.dat_info)?;
-        writeln!(f, "{}", "Database info")?;
-        writeln!(f, "- Games: {}", self.games)?;
-        writeln!(f, "- Roms: {}", self.roms)?;
-        writeln!(f, "- Roms in Games: {}", self.roms_in_games)?;
-        writeln!(f, "- Samples: {}", self.samples)?;
-        writeln!(f, "- Device References: {}", self.device_refs)
+        EnglishFormatter.write_db_report(f, self)
+    }
+}
+
+/// The rom bytes a layout stores, the duplicated bytes are the roms stored more than once and the saved bytes what the
+/// layout saves compared to non merged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModeDedupe {
+    pub rom_mode: RomsetMode,
+    pub bytes: u64,
+    pub duplicated: u64,
+    pub saved: u64,
+}
+
+/// How many rom bytes are shared across sets, as the bytes of each distinct rom and what each romset mode stores.
+/// The roms with no dump are not counted as there is nothing to store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupeStats {
+    pub unique_bytes: u64,
+    pub modes: Vec<ModeDedupe>,
+}
+
+impl Display for DedupeStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_dedupe_stats(f, self)
+    }
+}
+
+/// Which end of the sizes is listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeOrder {
+    #[default]
+    Largest,
+    Smallest,
+}
+
+impl SizeOrder {
+    fn get_direction(&self) -> &'static str {
+        match self {
+            SizeOrder::Largest => "DESC",
+            SizeOrder::Smallest => "ASC",
+        }
+    }
+}
+
+impl Display for SizeOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizeOrder::Largest => write!(f, "Largest"),
+            SizeOrder::Smallest => write!(f, "Smallest"),
+        }
+    }
+}
+
+impl FromStr for SizeOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "largest" => Ok(SizeOrder::Largest),
+            "smallest" => Ok(SizeOrder::Smallest),
+            _ => Err(anyhow!("Non valid order, can be either `largest` or `smallest`"))
+        }
+    }
+}
+
+/// The roms stored in the archive of a set for a mode, and their bytes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetSize {
+    pub name: String,
+    pub description: Option<String>,
+    pub roms: u32,
+    pub bytes: u64,
+}
+
+/// A rom of the database, with the first set using it and how many sets use it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RomSize {
+    pub rom_id: u32,
+    pub name: String,
+    pub set_name: String,
+    pub sets: u32,
+    pub bytes: u64,
+}
+
+/// The sets and roms taking the most, or the least, space. The DATs don't have the size of the disks, so they
+/// aren't listed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizesReport {
+    pub rom_mode: RomsetMode,
+    pub order: SizeOrder,
+    pub sets: Vec<SetSize>,
+    pub roms: Vec<RomSize>,
+}
+
+impl Display for SizesReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_sizes_report(f, self)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetDumpQuality {
+    pub name: String,
+    pub dump_quality: DumpQuality,
+}
+
+/// The dump quality of the whole database, and of the requested sets
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DumpQualityReport {
+    pub database: DumpQuality,
+    pub sets: Vec<SetDumpQuality>,
+}
+
+impl Display for DumpQualityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_dump_quality_report(f, self)
+    }
+}
+
+/// The sets that are always checked with a specific mode
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SetModeOverrides {
+    pub overrides: BTreeMap<String, RomsetMode>,
+}
+
+impl Display for SetModeOverrides {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_mode_overrides(f, self)
+    }
+}
+
+/// The tags and the note the user attached to a set
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetUserEntry {
+    pub tags: BTreeSet<String>,
+    pub note: Option<String>,
+}
+
+/// The sets with tags or a note
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SetUserData {
+    pub sets: BTreeMap<String, SetUserEntry>,
+}
+
+impl SetUserData {
+    /// Keeps only the sets with the tag
+    pub fn retain_tag(&mut self, tag: &str) {
+        self.sets.retain(|_, entry| entry.tags.contains(tag));
+    }
+
+    /// The tags of each set, to filter the sets by tag
+    pub fn get_tags(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.sets.iter()
+            .filter(|(_, entry)| !entry.tags.is_empty())
+            .map(|(set_name, entry)| (set_name.to_owned(), entry.tags.clone()))
+            .collect()
+    }
+}
+
+impl Display for SetUserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_user_data(f, self)
+    }
+}
+
+/// The status of a set in the last check of a directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastCheck {
+    pub root_directory: String,
+    pub status: SetStatus,
+    pub checked_at: String,
+    /// When the directory was modified at the time of the check, in milliseconds since the epoch
+    pub directory_modified: Option<i64>,
+}
+
+/// The statuses of the sets in the last checks, the most recent check first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SetLastChecks {
+    pub sets: BTreeMap<String, Vec<LastCheck>>,
+}
+
+impl SetLastChecks {
+    /// Discards the checks of the directories modified since, as files may have been added or removed
+    pub fn retain_current(&mut self) {
+        let mut modified = HashMap::new();
+        self.sets.retain(|_, checks| {
+            checks.retain(|check| *modified.entry(check.root_directory.clone())
+                .or_insert_with(|| paths::get_modified_millis(&check.root_directory)) == check.directory_modified);
+            !checks.is_empty()
+        });
+    }
+
+    /// The most recent check of the set
+    pub fn get_last_check(&self, set_name: &str) -> Option<&LastCheck> {
+        self.sets.get(set_name).and_then(|checks| checks.first())
+    }
+}
+
+/// How the sets are grouped when browsing the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BrowseGrouping {
+    Year,
+    Manufacturer,
+    /// The source file of the set
+    Driver,
+    /// The first letter of the set name
+    Letter,
+}
+
+impl BrowseGrouping {
+    fn get_column(&self) -> &'static str {
+        match self {
+            BrowseGrouping::Year => "info_year",
+            BrowseGrouping::Manufacturer => "info_manuf",
+            BrowseGrouping::Driver => "source_file",
+            BrowseGrouping::Letter => "UPPER(SUBSTR(name, 1, 1))",
+        }
+    }
+}
+
+impl Display for BrowseGrouping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrowseGrouping::Year => write!(f, "Year"),
+            BrowseGrouping::Manufacturer => write!(f, "Manufacturer"),
+            BrowseGrouping::Driver => write!(f, "Driver"),
+            BrowseGrouping::Letter => write!(f, "Letter"),
+        }
+    }
+}
+
+impl FromStr for BrowseGrouping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "year" => Ok(BrowseGrouping::Year),
+            "manufacturer" => Ok(BrowseGrouping::Manufacturer),
+            "driver" => Ok(BrowseGrouping::Driver),
+            "letter" => Ok(BrowseGrouping::Letter),
+            _ => Err(anyhow!("Non valid grouping, can be either `year`, `manufacturer`, `driver` or `letter`"))
+        }
+    }
+}
+
+/// Number of sets with the same value, `name` is `None` for the sets without it
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrowseGroup {
+    pub name: Option<String>,
+    pub sets: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BrowseReport {
+    pub grouping: BrowseGrouping,
+    pub groups: Vec<BrowseGroup>,
+}
+
+impl Display for BrowseReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Sets by {}", self.grouping.to_string().to_lowercase())?;
+        for group in &self.groups {
+            writeln!(f, "- {}: {}", group.name.as_deref().unwrap_or("Unknown"), group.sets)?;
+        }
+        Ok(())
+    }
+}
+
+/// The files stored in each archive for a mode, as the `archive`, `name` and `rom_id` columns, the roms with no dump
+/// are left out
+fn get_archive_files_query(rom_mode: RomsetMode) -> &'static str {
+    match rom_mode {
+        RomsetMode::NonMerged => "SELECT game_name AS archive, name, rom_id FROM game_roms WHERE status IS NOT 'nodump'",
+        RomsetMode::Split => "SELECT game_name AS archive, name, rom_id FROM game_roms WHERE status IS NOT 'nodump' AND parent IS NULL",
+        RomsetMode::Merged => "SELECT DISTINCT COALESCE(games.rom_of, games.name) AS archive, game_roms.name, game_roms.rom_id
+            FROM game_roms JOIN games ON game_roms.game_name = games.name WHERE game_roms.status IS NOT 'nodump'",
+    }
+}
+
+fn get_mode_filter(rom_mode: RomsetMode, prefix: &str) -> String {
+    match rom_mode {
+        RomsetMode::Merged => format!("{} rom_of IS NULL", prefix),
+        _ => String::new(),
     }
 }
 
@@ -72,9 +422,15 @@ Game info_desc = row.get(12)?;
 Game info_year = row.get(13)?;
 Game info_manuf = row.get(14)?;
 Rom id = row.get(15)?;
+Rom bios = row.get(16)?;
+Rom extra_attrs = row.get(17)?;
 */
-const GAMES_ROMS_QUERY: &str = "SELECT DISTINCT game_roms.game_name, game_roms.name as rom_name, roms.sha1, roms.md5, roms.crc, roms.size, game_roms.status, game_roms.parent, games.clone_of, games.rom_of, games.source_file, games.sample_of, games.info_desc, games.info_year, games.info_manuf, roms.id
+const GAMES_ROMS_QUERY: &str = "SELECT DISTINCT game_roms.game_name, game_roms.name as rom_name, roms.sha1, roms.md5, roms.crc, roms.size, game_roms.status, game_roms.parent, games.clone_of, games.rom_of, games.source_file, games.sample_of, games.info_desc, games.info_year, games.info_manuf, roms.id, game_roms.bios, game_roms.extra_attrs
                 FROM game_roms JOIN roms ON game_roms.rom_id = roms.id JOIN games ON game_roms.game_name = games.name";
+/// How many set names are sent in a single query, below the SQLite limit of parameters
+const MAX_NAMES_PER_QUERY: usize = 500;
+/// How many rom ids are sent in a single query, a merged set can have tens of thousands of roms
+const MAX_IDS_PER_QUERY: usize = 500;
 fn process_games_rom_row(row: &Row) -> Result<(Game, DbDataEntry<DataFile>, Option<String>), rusqlite::Error> {
     let mut game = Game::new(row.get(0)?);
     game.clone_of = row.get(8)?;
@@ -94,6 +450,8 @@ fn process_games_rom_row(row: &Row) -> Result<(Game, DbDataEntry<DataFile>, Opti
     let rom_name: String = row.get(1)?;
     let mut data_file = DataFile::new(rom_name, data_file_info);
     data_file.status = row.get(6)?;
+    data_file.bios = row.get(16)?;
+    data_file.extra_attrs = get_extra_attrs(row, 17)?;
 
     let rom_id = row.get(15)?;
     let db_entry = DbDataEntry::new(rom_id, data_file);
@@ -113,8 +471,10 @@ Rom size = row.get(5)?;
 Rom status = row.get(6)?;
 Rom parent = row.get(7)?;
 Rom id = row.get(8)?;
+Rom bios = row.get(9)?;
+Rom extra_attrs = row.get(10)?;
 */
-const _ROMS_QUERY: &str = "SELECT DISTINCT game_roms.game_name, game_roms.name as rom_name, roms.sha1, roms.md5, roms.crc, roms.size, game_roms.status, game_roms.parent, roms.id
+const _ROMS_QUERY: &str = "SELECT DISTINCT game_roms.game_name, game_roms.name as rom_name, roms.sha1, roms.md5, roms.crc, roms.size, game_roms.status, game_roms.parent, roms.id, game_roms.bios, game_roms.extra_attrs
                 FROM game_roms JOIN roms ON game_roms.rom_id = roms.id";
 fn _process_rom_row(row: &Row) -> Result<(String, DbDataEntry<DataFile>, Option<String>), rusqlite::Error> {
     let game: String = row.get(0)?;
@@ -128,6 +488,8 @@ fn _process_rom_row(row: &Row) -> Result<(String, DbDataEntry<DataFile>, Option<
     let rom_name: String = row.get(1)?;
     let mut data_file = DataFile::new(rom_name, data_file_info);
     data_file.status = row.get(6)?;
+    data_file.bios = row.get(9)?;
+    data_file.extra_attrs = get_extra_attrs(row, 10)?;
 
     let rom_id = row.get(8)?;
     let db_entry = DbDataEntry::new(rom_id, data_file);
@@ -137,14 +499,99 @@ fn _process_rom_row(row: &Row) -> Result<(String, DbDataEntry<DataFile>, Option<
     Ok((game, db_entry, rom_parent))
 }
 
+/// The extra attributes of the roms are stored as a JSON object
+fn get_extra_attrs(row: &Row, idx: usize) -> Result<Option<BTreeMap<String, String>>, rusqlite::Error> {
+    let extra_attrs: Option<String> = row.get(idx)?;
+    extra_attrs.map(|extra_attrs| serde_json::from_str(&extra_attrs)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))))
+        .transpose()
+}
+
 #[derive(Debug)]
 pub struct DBReader {
     conn: Connection,
+    index: Option<RomIndex>,
+    path: Option<String>,
 }
 
 impl DBReader{
     pub fn from_connection(conn: Connection) -> Self {
-        Self { conn }
+        Self { conn, index: None, path: None }
+    }
+
+    /// Opens the database in read only mode, the reader owns the connection so it can be kept for as long as needed
+    pub fn from_path(db_file: impl AsRef<Path>) -> Result<Self> {
+        let db_path = db_file.as_ref();
+        if !db_path.exists() {
+            return Err(anyhow!("No Database found at `{}`", db_path.to_string_lossy()));
+        }
+        let conn = connection::open_read_only(db_path)?;
+        Ok(Self { path: Some(db_path.to_string_lossy().to_string()), ..Self::from_connection(conn) })
+    }
+
+    /// The file of the database, readers from a connection, like the ones of a DAT imported in memory, have none
+    pub fn get_path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Uses the index to discard the roms not in the database before querying it
+    pub fn set_index(&mut self, index: RomIndex) {
+        self.index = Some(index);
+    }
+
+    pub fn build_index(&self) -> Result<RomIndex> {
+        RomIndex::build(&self.conn, self.get_db_fingerprint()?)
+    }
+
+    /// The fingerprint an index built from this database has
+    pub fn get_db_fingerprint(&self) -> Result<u64> {
+        Ok(index::db_fingerprint(self.get_last_import()?.as_deref(), self.get_roms_count()?))
+    }
+
+    pub fn get_roms_count(&self) -> Result<u32> {
+        let count = self.conn.query_row("SELECT COUNT(*) FROM roms;", params![], |row| {
+            row.get(0)
+        })?;
+        Ok(count)
+    }
+
+    pub fn get_games_count(&self) -> Result<u32> {
+        let count = self.conn.query_row("SELECT COUNT(*) FROM games;", params![], |row| {
+            row.get(0)
+        })?;
+        Ok(count)
+    }
+
+    /// The version of the schema the database was created with, `None` if it's older than the `schema_info` table
+    pub fn get_schema_version(&self) -> Result<Option<u32>> {
+        let has_schema_info: u32 = self.conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name = 'schema_info';", params![], |row| {
+            row.get(0)
+        })?;
+        if has_schema_info == 0 {
+            return Ok(None);
+        }
+        let schema_version: Option<String> = self.conn.query_row("SELECT value FROM schema_info WHERE key = ?1;", params![SCHEMA_VERSION_KEY], |row| row.get(0))
+            .optional()?;
+        Ok(schema_version.and_then(|version| version.parse().ok()))
+    }
+
+    /// Checks the import of the database finished, databases created before the `schema_info` table existed are considered complete
+    pub fn is_import_complete(&self) -> Result<bool> {
+        let has_schema_info: u32 = self.conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name = 'schema_info';", params![], |row| {
+            row.get(0)
+        })?;
+        if has_schema_info == 0 {
+            return Ok(true);
+        }
+
+        let status: Result<String, rusqlite::Error> = self.conn.query_row("SELECT value FROM schema_info WHERE key = ?1;", params![IMPORT_STATUS_KEY], |row| {
+            row.get(0)
+        });
+        match status {
+            Ok(status) => Ok(status == IMPORT_STATUS_COMPLETE),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
     }
 
     pub fn get_dat_info(&self) -> Result<DatInfo> {
@@ -172,87 +619,363 @@ impl DBReader{
         Ok(dat_info)
     }
 
+    /// The samples of a sample set, the games name it with `sampleof`
+    pub fn get_samples(&self, sample_set: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached("SELECT sample FROM samples WHERE sample_set = ?1 ORDER BY sample;")?;
+        let samples = stmt.query_map(params![sample_set], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+        Ok(samples)
+    }
+
+    /// The devices the game references itself, without the devices they reference in turn
+    pub fn get_device_refs(&self, game_name: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached("SELECT device_ref FROM devices WHERE game_name = ?1 ORDER BY device_ref;")?;
+        let device_refs = stmt.query_map(params![game_name], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+        Ok(device_refs)
+    }
+
     pub fn get_db_info(&self) -> Result<DBReport> {
         let dat_info = self.get_dat_info()?;
         let mut db_report = DBReport::new(dat_info);
 
         let mut stmt = self.conn.prepare("SELECT COUNT(*) FROM games;")?;
         let games: u32 = stmt.query_row(params![], |row| {
-            Ok(row.get(0)?)
+            row.get(0)
         })?;
         db_report.games = games;
 
         let mut stmt = self.conn.prepare("SELECT COUNT(*) FROM roms;")?;
         let roms: u32 = stmt.query_row(params![], |row| {
-            Ok(row.get(0)?)
+            row.get(0)
         })?;
         db_report.roms = roms;
 
         let mut stmt = self.conn.prepare("SELECT COUNT(*) FROM game_roms;")?;
         let roms_in_games: u32 = stmt.query_row(params![], |row| {
-            Ok(row.get(0)?)
+            row.get(0)
         })?;
         db_report.roms_in_games = roms_in_games;
 
         let mut stmt = self.conn.prepare("SELECT COUNT(*) FROM samples;")?;
         let samples: u32 = stmt.query_row(params![], |row| {
-            Ok(row.get(0)?)
+            row.get(0)
         })?;
         db_report.samples = samples;
 
         let mut stmt = self.conn.prepare("SELECT COUNT(*) FROM devices;")?;
         let device_refs: u32 = stmt.query_row(params![], |row| {
-            Ok(row.get(0)?)
+            row.get(0)
         })?;
         db_report.device_refs = device_refs;
+        db_report.dump_quality = self.get_dump_quality()?;
+        db_report.stats = self.get_db_stats()?;
 
         Ok(db_report)
     }
 
-    fn find_sets_for_roms(&self, db_roms: Vec<DbDataEntry<DataFile>>, rom_mode: RomsetMode) -> Result<RomSearch> {
-        let mut params = vec![];
-        let mut ids_cond = String::new();
-        let mut i: u32 = 0;
-        db_roms.iter().for_each(|db_rom| {
-            if i != 0 {
-                ids_cond.push_str(", ");
+    /// The size of the database and of its tables and indexes, the sizes come from the `dbstat` table when SQLite has it
+    pub fn get_db_stats(&self) -> Result<DBStats> {
+        let sizes = self.get_object_sizes().unwrap_or_else(|e| {
+            warn!("The size of the tables is not available: {}", e);
+            HashMap::new()
+        });
+        let mut stats = DBStats { file_size: maintenance::get_database_size(&self.conn)?, last_import: self.get_last_import()?, ..Default::default() };
+
+        let mut stmt = self.conn.prepare("SELECT type, name, tbl_name FROM sqlite_master
+            WHERE type IN ('table', 'index') AND name NOT LIKE 'sqlite_%' ORDER BY name;")?;
+        let objects = stmt.query_map(params![], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (object_type, name, table) in objects {
+            let size = sizes.get(&name).copied();
+            if object_type == "table" {
+                let rows: u64 = self.conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\";", name), params![], |row| row.get(0))?;
+                stats.tables.push(TableStats { name, rows, size });
+            } else {
+                stats.indexes.push(IndexStats { name, table, size });
             }
-            i += 1;                     
-            ids_cond.push_str("?");
-            ids_cond.push_str(&i.to_string());
-            params.push(&db_rom.id);
+        }
+
+        Ok(stats)
+    }
+
+    fn get_object_sizes(&self) -> Result<HashMap<String, u64>> {
+        let mut stmt = self.conn.prepare("SELECT name, SUM(pgsize) FROM dbstat GROUP BY name;")?;
+        let sizes = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<HashMap<String, u64>, _>>()?;
+        Ok(sizes)
+    }
+
+    fn get_last_import(&self) -> Result<Option<String>> {
+        let has_schema_info: u32 = self.conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name = 'schema_info';", params![], |row| {
+            row.get(0)
+        })?;
+        if has_schema_info == 0 {
+            return Ok(None);
+        }
+        let last_import = self.conn.query_row("SELECT value FROM schema_info WHERE key = ?1;", params![IMPORT_DATE_KEY], |row| row.get(0))
+            .optional()?;
+        Ok(last_import)
+    }
+
+    /// Counts the roms in games by their status
+    pub fn get_dump_quality(&self) -> Result<DumpQuality> {
+        let mut stmt = self.conn.prepare("SELECT status, COUNT(*) FROM game_roms GROUP BY status;")?;
+        let mut rows = stmt.query(params![])?;
+        let mut dump_quality = DumpQuality::default();
+        while let Some(row) = rows.next()? {
+            let status: Option<String> = row.get(0)?;
+            let count: u32 = row.get(1)?;
+            dump_quality.add(DumpStatus::from_status(status.as_deref()), count);
+        }
+
+        Ok(dump_quality)
+    }
+
+    /// The extended metadata of the game, if the DAT was imported with it
+    pub fn get_game_metadata<S>(&self, game_name: S) -> Result<Option<Box<GameMetadata>>> where S: AsRef<str> {
+        let mut stmt = self.conn.prepare("SELECT driver_status, emulation_status, players, orientation, display_type
+            FROM game_metadata WHERE game_name = ?1;")?;
+        let result = stmt.query_row(params![game_name.as_ref()], |row| {
+            Ok(GameMetadata {
+                driver_status: row.get(0)?,
+                emulation_status: row.get(1)?,
+                players: row.get(2)?,
+                orientation: row.get(3)?,
+                display_type: row.get(4)?,
+            })
         });
 
-        // We do a query with all the roms we received, the result will return all sets associated with these roms
-        let query = GAMES_ROMS_QUERY.to_string() + " WHERE game_roms.rom_id IN (" + &ids_cond + ") ORDER BY game_roms.game_name;";
+        match result {
+            Ok(metadata) => Ok(Some(Box::new(metadata))),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Databases imported before the categories existed don't have the table
+    pub fn get_game_category<S>(&self, game_name: S) -> Result<Option<String>> where S: AsRef<str> {
+        if !self.has_table(GAME_CATEGORIES_TABLE)? {
+            return Ok(None);
+        }
+
+        let category = self.conn.query_row(&format!("SELECT category FROM {} WHERE game_name = ?1;", GAME_CATEGORIES_TABLE), params![game_name.as_ref()], |row| {
+            row.get(0)
+        }).optional()?;
+        Ok(category)
+    }
+
+    pub fn get_mode_overrides(&self) -> Result<SetModeOverrides> {
+        let mut overrides = SetModeOverrides::default();
+        if !self.has_table(MODE_OVERRIDES_TABLE)? {
+            return Ok(overrides);
+        }
+
+        let mut stmt = self.conn.prepare(&format!("SELECT game_name, mode FROM {};", MODE_OVERRIDES_TABLE))?;
+        let rows = stmt.query_map(params![], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (game_name, mode) = row?;
+            overrides.overrides.insert(game_name, RomsetMode::from_str(&mode)?);
+        }
+
+        Ok(overrides)
+    }
+
+    /// The tags and notes of the sets, databases imported before they existed have none
+    pub fn get_user_data(&self) -> Result<SetUserData> {
+        let mut user_data = SetUserData::default();
+        if self.has_table(SET_TAGS_TABLE)? {
+            let mut stmt = self.conn.prepare(&format!("SELECT game_name, tag FROM {};", SET_TAGS_TABLE))?;
+            let rows = stmt.query_map(params![], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for row in rows {
+                let (game_name, tag) = row?;
+                user_data.sets.entry(game_name).or_default().tags.insert(tag);
+            }
+        }
+        if self.has_table(SET_NOTES_TABLE)? {
+            let mut stmt = self.conn.prepare(&format!("SELECT game_name, note FROM {};", SET_NOTES_TABLE))?;
+            let rows = stmt.query_map(params![], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for row in rows {
+                let (game_name, note) = row?;
+                user_data.sets.entry(game_name).or_default().note = Some(note);
+            }
+        }
+
+        Ok(user_data)
+    }
+
+    /// The sets that were complete in the last check of the directory
+    pub fn get_last_complete_sets(&self, root_directory: &str) -> Result<BTreeSet<String>> {
+        if !self.has_table(CHECK_STATUS_TABLE)? {
+            return Ok(BTreeSet::new());
+        }
+
+        let mut stmt = self.conn.prepare(&format!("SELECT game_name FROM {} WHERE root_directory = ?1 AND status = ?2;", CHECK_STATUS_TABLE))?;
+        let rows = stmt.query_map(params![root_directory, SetStatus::COMPLETE.get_key()], |row| row.get::<_, String>(0))?;
+        let mut sets = BTreeSet::new();
+        for row in rows {
+            sets.insert(row?);
+        }
+
+        Ok(sets)
+    }
+
+    /// The statuses of the sets in the last check of each directory, including the ones of directories modified since
+    pub fn get_last_checks(&self) -> Result<SetLastChecks> {
+        let mut last_checks = SetLastChecks::default();
+        if !self.has_table(CHECK_STATUS_TABLE)? {
+            return Ok(last_checks);
+        }
+        // The statuses stored before the check time was kept don't have the columns, they are discarded
+        let columns: u32 = self.conn.query_row(&format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = 'checked_at';", CHECK_STATUS_TABLE), params![], |row| row.get(0))?;
+        if columns == 0 {
+            return Ok(last_checks);
+        }
+
+        let mut stmt = self.conn.prepare(&format!("SELECT game_name, root_directory, status, checked_at, directory_modified FROM {}
+            WHERE checked_at IS NOT NULL ORDER BY game_name, checked_at DESC;", CHECK_STATUS_TABLE))?;
+        let rows = stmt.query_map(params![], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?, row.get::<_, Option<i64>>(4)?))
+        })?;
+        for row in rows {
+            let (game_name, root_directory, status, checked_at, directory_modified) = row?;
+            if let Some(status) = SetStatus::from_key(&status) {
+                last_checks.sets.entry(game_name).or_default().push(LastCheck { root_directory, status, checked_at, directory_modified });
+            }
+        }
+
+        Ok(last_checks)
+    }
+
+    fn has_table(&self, table_name: &str) -> Result<bool> {
+        let tables: u32 = self.conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name = ?1;", params![table_name], |row| {
+            row.get(0)
+        })?;
+        Ok(tables > 0)
+    }
+
+    /// The rom bytes of each romset mode, a file is counted once per archive it's stored in. In merged mode the clones
+    /// are in the archive of the set they take the roms from, the same as in `get_romset_roms`
+    pub fn get_dedupe_stats(&self) -> Result<DedupeStats> {
+        let unique_bytes: u64 = self.conn.query_row("SELECT COALESCE(SUM(size), 0) FROM roms
+            WHERE id IN (SELECT rom_id FROM game_roms WHERE status IS NOT 'nodump');", params![], |row| row.get(0))?;
+
+        let mut modes: Vec<ModeDedupe> = vec![];
+        for rom_mode in [RomsetMode::NonMerged, RomsetMode::Split, RomsetMode::Merged] {
+            let files = get_archive_files_query(rom_mode);
+            let bytes: u64 = self.conn.query_row(&format!("SELECT COALESCE(SUM(roms.size), 0) FROM ({}) AS files JOIN roms ON files.rom_id = roms.id;", files),
+                params![], |row| row.get(0))?;
+            let non_merged = modes.first().map(|mode| mode.bytes).unwrap_or(bytes);
+            modes.push(ModeDedupe { rom_mode, bytes, duplicated: bytes.saturating_sub(unique_bytes), saved: non_merged.saturating_sub(bytes) });
+        }
+
+        Ok(DedupeStats { unique_bytes, modes })
+    }
 
+    /// The sets storing the most, or the least, rom bytes in the mode, and the roms with the most, or the least, bytes.
+    /// The sets are the archives of `get_dedupe_stats`, a rom shared by several sets is listed once
+    pub fn get_sizes(&self, rom_mode: RomsetMode, order: SizeOrder, limit: u32) -> Result<SizesReport> {
+        let query = format!("SELECT files.archive, games.info_desc, COUNT(*), COALESCE(SUM(roms.size), 0) AS bytes
+            FROM ({}) AS files JOIN roms ON files.rom_id = roms.id LEFT JOIN games ON files.archive = games.name
+            GROUP BY files.archive ORDER BY bytes {}, files.archive LIMIT ?1;", get_archive_files_query(rom_mode), order.get_direction());
+        let mut stmt = self.conn.prepare(&query)?;
+        let sets = stmt.query_map(params![limit], |row| {
+            Ok(SetSize { name: row.get(0)?, description: row.get(1)?, roms: row.get(2)?, bytes: row.get(3)? })
+        })?.collect::<Result<Vec<_>, _>>()?;
+
+        let query = format!("SELECT roms.id, MIN(game_roms.game_name || '/' || game_roms.name), COUNT(DISTINCT game_roms.game_name), roms.size
+            FROM roms JOIN game_roms ON game_roms.rom_id = roms.id WHERE game_roms.status IS NOT 'nodump'
+            GROUP BY roms.id ORDER BY roms.size {}, roms.id LIMIT ?1;", order.get_direction());
+        let mut stmt = self.conn.prepare(&query)?;
+        let roms = stmt.query_map(params![limit], |row| {
+            let location: String = row.get(1)?;
+            let (set_name, name) = location.split_once('/').unwrap_or(("", location.as_str()));
+            Ok(RomSize { rom_id: row.get(0)?, name: name.to_string(), set_name: set_name.to_string(), sets: row.get(2)?, bytes: row.get(3)? })
+        })?.collect::<Result<Vec<_>, _>>()?;
+
+        Ok(SizesReport { rom_mode, order, sets, roms })
+    }
+
+    pub fn get_sets_by_year(&self) -> Result<Vec<BrowseGroup>> {
+        self.get_sets_grouped_by(BrowseGrouping::Year, RomsetMode::NonMerged)
+    }
+
+    pub fn get_sets_by_manufacturer(&self) -> Result<Vec<BrowseGroup>> {
+        self.get_sets_grouped_by(BrowseGrouping::Manufacturer, RomsetMode::NonMerged)
+    }
+
+    pub fn get_sets_by_driver(&self) -> Result<Vec<BrowseGroup>> {
+        self.get_sets_grouped_by(BrowseGrouping::Driver, RomsetMode::NonMerged)
+    }
+
+    /// Counts the sets for each value of the grouping, sorted by the value.
+    /// In merged mode only the parents are counted, the same as in `get_game_list`
+    pub fn get_sets_grouped_by(&self, grouping: BrowseGrouping, rom_mode: RomsetMode) -> Result<Vec<BrowseGroup>> {
+        let column = grouping.get_column();
+        let mut stmt = self.conn.prepare(format!("SELECT {0}, COUNT(*) FROM games{1} GROUP BY {0} ORDER BY {0};", column, get_mode_filter(rom_mode, " WHERE")).as_str())?;
+        let groups = stmt.query_map(params![], |row| {
+            Ok(BrowseGroup { name: row.get(0)?, sets: row.get(1)? })
+        })?.collect::<Result<Vec<_>, _>>()?;
+
+        Ok(groups)
+    }
+
+    /// The sets, with their description, of a single group from `get_sets_grouped_by`, so the whole list doesn't need to be loaded
+    pub fn get_sets_in_group(&self, grouping: BrowseGrouping, group: Option<&str>, rom_mode: RomsetMode) -> Result<Vec<(String, String)>> {
+        let column = grouping.get_column();
+        let condition = match group {
+            Some(_) => format!("{} = ?1", column),
+            None => format!("{} IS NULL", column),
+        };
+        let query = format!("SELECT name, info_desc FROM games WHERE {}{} ORDER BY name;", condition, get_mode_filter(rom_mode, " AND"));
+        let mut stmt = self.conn.prepare(&query)?;
+        let rows = match group {
+            Some(group) => stmt.query_map(params![group], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<Vec<_>, _>>()?,
+            None => stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<Vec<_>, _>>()?,
+        };
+
+        Ok(rows)
+    }
+
+    fn find_sets_for_roms(&self, db_roms: Vec<DbDataEntry<DataFile>>, rom_mode: RomsetMode) -> Result<RomSearch> {
+        // The same rom can be in the file more than once with different names
+        let mut names_by_id: HashMap<u32, Vec<String>> = HashMap::new();
+        for db_rom in &db_roms {
+            names_by_id.entry(db_rom.id).or_default().push(db_rom.file.name.clone());
+        }
+        let mut ids = names_by_id.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        // The query returns all the sets associated with the roms, in chunks so it stays below the SQLite limit of parameters
         type QueryResult = (Game, DbDataEntry<DataFile>, Option<String>);
-        let mut roms_stmt = self.conn.prepare(&query)?;
-        let roms_rows = roms_stmt.query_map::<QueryResult, _, _>(rusqlite::params_from_iter(params.iter()), |row| {
-            process_games_rom_row(row)
-        })?.filter_map(|result| {
-            // We filter the erros
-            result.ok()
-        }).flat_map(|results| {
-            // Since we can have more than one rom id with different name, we create a vec with each name
-            // Most of the times it will be only one
-            let rom_id: u32 = results.1.id;
-            db_roms.iter().filter_map(|db_rom| {
-                if rom_id == db_rom.id {
-                    Some(db_rom.file.name.clone())
-                } else {
-                    None
+        let mut roms_rows = vec![];
+        for ids_chunk in ids.chunks(MAX_IDS_PER_QUERY) {
+            let ids_cond = (1..=ids_chunk.len()).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", ");
+            let query = GAMES_ROMS_QUERY.to_string() + " WHERE game_roms.rom_id IN (" + &ids_cond + ") ORDER BY game_roms.game_name;";
+
+            let mut roms_stmt = self.conn.prepare(&query)?;
+            let chunk_rows = roms_stmt.query_map::<QueryResult, _, _>(rusqlite::params_from_iter(ids_chunk.iter()), |row| {
+                process_games_rom_row(row)
+            })?.filter_map(|result| {
+                // We filter the erros
+                result.ok()
+            });
+
+            for (game, db_rom, game_parent) in chunk_rows {
+                // Since we can have more than one rom id with different name, we add an entry for each name
+                // Most of the times it will be only one
+                let rom_id = db_rom.id;
+                for file_name in names_by_id.get(&rom_id).into_iter().flatten() {
+                    let mut data_file = db_rom.file.clone();
+                    data_file.name = file_name.clone();
+                    roms_rows.push((game.clone(), DbDataEntry::new(rom_id, data_file), game_parent.clone()));
                 }
-            }).map(|file_name| {
-                let game = results.0.clone();
-                let mut data_file = results.1.file.clone();
-                data_file.name = file_name;
-
-                (game,
-                DbDataEntry::new(rom_id, data_file),
-                results.2.clone())
-            }).collect::<Vec<_>>()
-        }).collect::<Vec<_>>();
+            }
+        }
 
         let mut result = RomSearch::new();
         for item in roms_rows {
@@ -272,7 +995,7 @@ impl DBReader{
                     result.add_file_for_set(game.name, rom);
                 }
                 RomsetMode::Split => {
-                    if game_parent == None {
+                    if game_parent.is_none() {
                         result.add_file_for_set(game.name, rom);
                     }
                 }
@@ -283,6 +1006,13 @@ impl DBReader{
     }
 
     pub fn get_ids_from_files(conn: &Connection, files: Vec<DataFile>) -> Result<SearchEntryIds<DataFile>> {
+        DBReader::get_ids_from_files_with_index(conn, files, None)
+    }
+
+    /// If there is an index the roms sharing a hash with the searched one are taken from it, and the database is only
+    /// queried when the index can't decide. They are scored by `get_match_evidence`, the strongest match wins, and if
+    /// several roms match as strongly the rom is ignored
+    pub fn get_ids_from_files_with_index(conn: &Connection, files: Vec<DataFile>, index: Option<&RomIndex>) -> Result<SearchEntryIds<DataFile>> {
         let mut result = SearchEntryIds::new();
         for rom_file in files {
             let rom = &rom_file.info;
@@ -293,59 +1023,38 @@ impl DBReader{
                     result.ignored.push(rom_file);
                 },
                 _ => {
-                    let mut params: Vec<(&str, &dyn ToSql)> = vec![];
-                    let mut statement_where = vec![];
-                    let mut has_hash = false;
-
-                    if let Some(ref sha1) = rom.sha1 {
-                        has_hash = true;
-                        params.push((":sha1", sha1));
-                        statement_where.push("(sha1 = :sha1 OR sha1 IS NULL)");
-                    }
-                    if let Some(ref md5) = rom.md5 {
-                        has_hash = true;
-                        params.push((":md5", md5));
-                        statement_where.push("(md5 = :md5 OR md5 IS NULL)");
-                    }
-
-                    if !has_hash {
+                    if rom.sha1.is_none() && rom.md5.is_none() && rom.crc.is_none() {
                         warn!("Rom `{}` has no hash value, it could match any other rom, should be ignored", rom_file);
                         result.not_found.push(rom_file);
-                    } else {
-                        if let Some(ref crc) = rom.crc {
-                            params.push((":crc", crc));
-                            statement_where.push("(crc = :crc OR crc IS NULL)");
-                        }
-                        if let Some(ref size) = rom.size {
-                            params.push((":size", size));
-                            statement_where.push("(size = :size OR size IS NULL)");
-                        }
-
-                        // Minimum fields to find, has to have at least md5 or sha1
-                        statement_where.push("(sha1 IS NOT NULL OR md5 IS NOT NULL)");
-
-                        let statement = "SELECT id FROM roms WHERE ".to_string() +
-                            &statement_where.join(" AND ") + ";";
-                        
-                        let mut rom_stmt = conn.prepare_cached(&statement)?;
-                        let query_rom_result: Vec<u32> = rom_stmt.query_map(params.as_slice(), |row| {
-                            Ok(row.get(0)?)
-                        })?.filter_map(|row| row.ok() ).collect();
+                        continue;
+                    }
 
-                        match query_rom_result.len() {
-                            0 => {
-                                debug!("No ROM found in DB: {}", rom);
-                                result.add_not_found(rom_file);
-                            },
-                            1 => {
-                                debug!("Found ROM in DB: {}", rom);
-                                result.add_found(query_rom_result[0], rom_file);
-                            },
-                            n => {
-                                // TODO: There is a corner case which is, if the search has a sha1, and the DB has a md5 it may match as both with match against the null value
-                                warn!("Found more than one rom ({}) on the query, ROM: {}", n, rom_file);
-                                result.ignored.push(rom_file);
+                    let candidates = match index.and_then(|index| index.find_candidates(&rom_file)) {
+                        Some(candidates) => candidates,
+                        None => DBReader::get_roms_sharing_hash(conn, rom)?,
+                    };
+
+                    let matches = candidates.iter()
+                        .filter_map(|(id, db_rom)| get_match_evidence(db_rom, rom).map(|evidence| (*id, evidence)))
+                        .collect::<Vec<_>>();
+                    let strongest = matches.iter().map(|(_id, evidence)| *evidence).max();
+                    let best = matches.into_iter().filter(|(_id, evidence)| Some(*evidence) == strongest).collect::<Vec<_>>();
+
+                    match best.as_slice() {
+                        [] => {
+                            if !candidates.is_empty() {
+                                debug!("Rejected {} ROMs sharing a hash but contradicting another: {}", candidates.len(), rom);
                             }
+                            debug!("No ROM found in DB: {}", rom);
+                            result.add_not_found(rom_file);
+                        },
+                        [(id, evidence)] => {
+                            debug!("Found ROM in DB by {:?}: {}", evidence, rom);
+                            result.add_found_with(*id, rom_file, *evidence);
+                        },
+                        best => {
+                            warn!("Found more than one rom ({}) matching by {:?}, ROM: {}", best.len(), best[0].1, rom_file);
+                            result.ignored.push(rom_file);
                         }
                     }
                 }
@@ -355,6 +1064,21 @@ impl DBReader{
         Ok(result)
     }
 
+    fn get_roms_sharing_hash(conn: &Connection, rom: &DataFileInfo) -> Result<Vec<(u32, DataFileInfo)>> {
+        // A missing hash is bound as NULL, which never equals any value
+        let mut rom_stmt = conn.prepare_cached("SELECT id, sha1, md5, crc, size FROM roms WHERE sha1 = ?1 OR md5 = ?2 OR crc = ?3;")?;
+        let candidates = rom_stmt.query_map(params![rom.sha1, rom.md5, rom.crc], |row| {
+            let mut db_rom = DataFileInfo::new(FileType::Rom);
+            db_rom.sha1 = row.get(1)?;
+            db_rom.md5 = row.get(2)?;
+            db_rom.crc = row.get(3)?;
+            db_rom.size = row.get(4)?;
+            Ok((row.get::<_, u32>(0)?, db_rom))
+        })?.filter_map(|row| row.ok()).collect();
+
+        Ok(candidates)
+    }
+
     pub fn get_ids_from_disks(conn: &Connection, files: Vec<GameDisk>) -> Result<SearchEntryIds<GameDisk>> {
         let mut result = SearchEntryIds::new();
         for file in files {
@@ -386,7 +1110,7 @@ impl DBReader{
                         
                         let mut rom_stmt = conn.prepare_cached(&statement)?;
                         let query_rom_result: Vec<u32> = rom_stmt.query_map(params.as_slice(), |row| {
-                            Ok(row.get(0)?)
+                            row.get(0)
                         })?.filter_map(|row| row.ok() ).collect();
 
                         match query_rom_result.len() {
@@ -447,13 +1171,29 @@ impl DataReader for DBReader {
                     sample_of: row.get(4)?,
                     info_description: row.get(5)?,
                     info_year: row.get(6)?,
-                    info_manufacturer: row.get(7)?
+                    info_manufacturer: row.get(7)?,
+                    category: None,
+                    metadata: None,
                 }
             )
         });
 
         match game_result {
-            Ok(game) => {
+            Ok(mut game) => {
+                game.metadata = match self.get_game_metadata(&game.name) {
+                    Ok(metadata) => metadata,
+                    Err(e) => {
+                        debug!("No extended metadata for the game {}: {}", game.name, e);
+                        None
+                    }
+                };
+                game.category = match self.get_game_category(&game.name) {
+                    Ok(category) => category,
+                    Err(e) => {
+                        error!("Error reading the category of the game {}: {}", game.name, e);
+                        None
+                    }
+                };
                 Some(game)
             },
             Err(rusqlite::Error::QueryReturnedNoRows) => {
@@ -466,30 +1206,233 @@ impl DataReader for DBReader {
         }
     }
 
-    fn get_romset_roms<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<Vec<DbDataEntry<DataFile>>> where S: AsRef<str> + rusqlite::ToSql {
-        let mut query = GAMES_ROMS_QUERY.to_string();
+    fn get_games_in_scope(&self, scope: &GameFilter) -> Result<Vec<Game>> {
+        let query = if self.has_table(GAME_CATEGORIES_TABLE)? {
+            format!("SELECT name, clone_of, rom_of, source_file, category FROM games LEFT JOIN {} ON game_name = name ORDER BY name;", GAME_CATEGORIES_TABLE)
+        } else {
+            "SELECT name, clone_of, rom_of, source_file, NULL FROM games ORDER BY name;".to_string()
+        };
+        let mut stmt = self.conn.prepare(&query)?;
+        let mut rows = stmt.query(params![])?;
+        let mut games = vec![];
+        while let Some(row) = rows.next()? {
+            let mut game = Game::new(row.get(0)?);
+            game.clone_of = row.get(1)?;
+            game.rom_of = row.get(2)?;
+            game.source_file = row.get(3)?;
+            game.category = row.get(4)?;
+            if scope.matches(&game) {
+                games.push(game);
+            }
+        }
+        Ok(games)
+    }
+
+    /// Databases imported before the ignore list existed don't have the table
+    fn get_ignored_roms(&self) -> Result<IgnoredRoms> {
+        let mut ignored_roms = IgnoredRoms::default();
+        if !self.has_table(IGNORED_ROMS_TABLE)? {
+            return Ok(ignored_roms);
+        }
+
+        let mut stmt = self.conn.prepare(&format!("SELECT sha1, game_name, rom_name FROM {};", IGNORED_ROMS_TABLE))?;
+        let rows = stmt.query_map(params![], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?;
+        for row in rows {
+            match row? {
+                (sha1, _, _) if !sha1.is_empty() => {
+                    ignored_roms.sha1s.insert(sha1);
+                }
+                (_, game_name, rom_name) => {
+                    ignored_roms.roms.entry(game_name).or_default().insert(rom_name);
+                }
+            }
+        }
+
+        Ok(ignored_roms)
+    }
+
+    fn get_packing(&self) -> Result<Option<Packing>> {
+        let packing: Option<String> = self.conn.query_row("SELECT value FROM info WHERE key = ?1;", params![PACKING_KEY], |row| {
+            row.get(0)
+        }).optional()?;
+        Ok(packing.and_then(|packing| packing.parse().ok()))
+    }
+
+    fn get_dat_mode(&self) -> Result<Option<RomsetMode>> {
+        let merging: Option<String> = self.conn.query_row("SELECT value FROM info WHERE key = ?1;", params![MERGING_KEY], |row| {
+            row.get(0)
+        }).optional()?;
+        Ok(merging.and_then(|merging| parse_merging(&merging)))
+    }
+
+    fn get_mode_override<S>(&self, game_name: S) -> Result<Option<RomsetMode>> where S: AsRef<str> {
+        if !self.has_table(MODE_OVERRIDES_TABLE)? {
+            return Ok(None);
+        }
+
+        let mode: Option<String> = self.conn.query_row(&format!("SELECT mode FROM {} WHERE game_name = ?1;", MODE_OVERRIDES_TABLE), params![game_name.as_ref()], |row| {
+            row.get(0)
+        }).optional()?;
+        match mode {
+            Some(mode) => Ok(Some(RomsetMode::from_str(&mode)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn get_romset_roms<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<Vec<DbDataEntry<DataFile>>> where S: AsRef<str> + rusqlite::ToSql {
+        let mut query = GAMES_ROMS_QUERY.to_string();
+        match rom_mode {
+            RomsetMode::Merged => {
+                query.push_str(" WHERE (game_roms.game_name = ?1 OR games.rom_of = ?1);");
+            }
+            RomsetMode::NonMerged => {
+                query.push_str(" WHERE game_roms.game_name = ?1");
+            }
+            RomsetMode::Split => {
+                query.push_str(" WHERE (game_roms.game_name = ?1 AND game_roms.parent IS NULL);");
+            }
+        }
+
+        let mut roms_stmt = self.conn.prepare(&query)?;
+        let mut roms_rows = roms_stmt.query_map(params![ game_name ], |row| {
+            let r = process_games_rom_row(row)?;
+            Ok(r.1)
+        })?
+        .filter_map(|row| row.ok())
+        .collect::<Vec<_>>();
+
+        let roms: HashSet<DbDataEntry<DataFile>> = roms_rows.drain(..).collect();
+        Ok(Vec::from_iter(roms))
+    }
+
+    fn get_games_with_rom_name(&self, rom_name: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached("SELECT DISTINCT game_name FROM game_roms WHERE name = ?1 ORDER BY game_name;")?;
+        let games = stmt.query_map(params![rom_name], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+        Ok(games)
+    }
+
+    fn find_roms(&self, filter: &RomFilter) -> Result<Vec<FoundRom>> {
+        filter.validate()?;
+        let mut params: Vec<(&str, &dyn ToSql)> = vec![];
+        let mut filter_where = vec![];
+        let sha1_prefix = filter.sha1_prefix.as_ref().map(|prefix| format!("{}%", prefix.to_lowercase()));
+        let md5_prefix = filter.md5_prefix.as_ref().map(|prefix| format!("{}%", prefix.to_lowercase()));
+        let crc_prefix = filter.crc_prefix.as_ref().map(|prefix| format!("{}%", prefix.to_lowercase()));
+        if let Some(ref sha1_prefix) = sha1_prefix {
+            params.push((":sha1", sha1_prefix));
+            filter_where.push("roms.sha1 LIKE :sha1");
+        }
+        if let Some(ref md5_prefix) = md5_prefix {
+            params.push((":md5", md5_prefix));
+            filter_where.push("roms.md5 LIKE :md5");
+        }
+        if let Some(ref crc_prefix) = crc_prefix {
+            params.push((":crc", crc_prefix));
+            filter_where.push("roms.crc LIKE :crc");
+        }
+        if let Some(ref size_min) = filter.size_min {
+            params.push((":size_min", size_min));
+            filter_where.push("roms.size >= :size_min");
+        }
+        if let Some(ref size_max) = filter.size_max {
+            params.push((":size_max", size_max));
+            filter_where.push("roms.size <= :size_max");
+        }
+
+        let mut query = "SELECT roms.id, roms.sha1, roms.md5, roms.crc, roms.size, game_roms.game_name, game_roms.name
+            FROM roms LEFT JOIN game_roms ON game_roms.rom_id = roms.id".to_string();
+        if !filter_where.is_empty() {
+            query.push_str(&format!(" WHERE {}", filter_where.join(" AND ")));
+        }
+        query.push_str(" ORDER BY roms.id, game_roms.game_name, game_roms.name;");
+
+        let mut stmt = self.conn.prepare(&query)?;
+        let mut rows = stmt.query(params.as_slice())?;
+        let mut roms: Vec<FoundRom> = vec![];
+        while let Some(row) = rows.next()? {
+            let id: u32 = row.get(0)?;
+            if roms.last().map(|rom| rom.id) != Some(id) {
+                let mut info = DataFileInfo::new(FileType::Rom);
+                info.sha1 = row.get(1)?;
+                info.md5 = row.get(2)?;
+                info.crc = row.get(3)?;
+                info.size = row.get(4)?;
+                roms.push(FoundRom { id, info, used_by: BTreeMap::new() });
+            }
+            let game_name: Option<String> = row.get(5)?;
+            let rom_name: Option<String> = row.get(6)?;
+            if let (Some(rom), Some(game_name), Some(rom_name)) = (roms.last_mut(), game_name, rom_name) {
+                rom.used_by.entry(game_name).or_default().push(rom_name);
+            }
+        }
+
+        Ok(roms)
+    }
+
+    fn get_romset_disks<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<Vec<DataFile>> where S: AsRef<str> {
+        let mut query = "SELECT DISTINCT game_disks.name, disks.sha1, disks.region, disks.status
+            FROM game_disks JOIN disks ON game_disks.disk_id = disks.id JOIN games ON game_disks.game_name = games.name".to_string();
         match rom_mode {
-            RomsetMode::Merged => {
-                query.push_str(" WHERE (game_roms.game_name = ?1 OR games.rom_of = ?1);");
-            }
-            RomsetMode::NonMerged => {
-                query.push_str(" WHERE game_roms.game_name = ?1");
-            }
-            RomsetMode::Split => {
-                query.push_str(" WHERE (game_roms.game_name = ?1 AND game_roms.parent IS NULL);");
-            }
+            RomsetMode::Merged => query.push_str(" WHERE (game_disks.game_name = ?1 OR games.rom_of = ?1);"),
+            RomsetMode::NonMerged | RomsetMode::Split => query.push_str(" WHERE game_disks.game_name = ?1;"),
         }
 
-        let mut roms_stmt = self.conn.prepare(&query)?;
-        let mut roms_rows = roms_stmt.query_map(params![ game_name ], |row| {
-            let r = process_games_rom_row(row)?;
-            Ok(r.1)
+        let mut disks_stmt = self.conn.prepare(&query)?;
+        let disks = disks_stmt.query_map(params![ game_name.as_ref() ], |row| {
+            let mut info = DataFileInfo::new(FileType::Disk);
+            info.sha1 = row.get(1)?;
+            let status: Option<String> = row.get(3)?;
+            Ok(DataFile::new_with_status(row.get::<_, String>(0)?, info, status))
         })?
         .filter_map(|row| row.ok())
-        .collect::<Vec<_>>();
+        .collect::<HashSet<_>>();
 
-        let roms: HashSet<DbDataEntry<DataFile>> = roms_rows.drain(..).collect();
-        Ok(Vec::from_iter(roms))
+        Ok(Vec::from_iter(disks))
+    }
+
+    fn get_romsets_roms(&self, game_names: &[String], rom_mode: RomsetMode) -> Result<HashMap<String, Vec<DbDataEntry<DataFile>>>> {
+        let mut romsets_roms: HashMap<String, HashSet<DbDataEntry<DataFile>>> = game_names.iter()
+            .map(|game_name| (game_name.to_owned(), HashSet::new()))
+            .collect();
+
+        for names in game_names.chunks(MAX_NAMES_PER_QUERY) {
+            let names_cond = (1..=names.len()).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", ");
+            let mut query = GAMES_ROMS_QUERY.to_string();
+            match rom_mode {
+                RomsetMode::Merged => {
+                    query.push_str(&format!(" WHERE (game_roms.game_name IN ({0}) OR games.rom_of IN ({0}));", names_cond));
+                }
+                RomsetMode::NonMerged => {
+                    query.push_str(&format!(" WHERE game_roms.game_name IN ({});", names_cond));
+                }
+                RomsetMode::Split => {
+                    query.push_str(&format!(" WHERE (game_roms.game_name IN ({}) AND game_roms.parent IS NULL);", names_cond));
+                }
+            }
+
+            let mut roms_stmt = self.conn.prepare(&query)?;
+            let roms_rows = roms_stmt.query_map(rusqlite::params_from_iter(names.iter()), |row| {
+                let r = process_games_rom_row(row)?;
+                Ok((r.0, r.1))
+            })?
+            .filter_map(|row| row.ok());
+
+            for (game, rom) in roms_rows {
+                // In merged mode the roms of the clones also belong to the parent set
+                if let (RomsetMode::Merged, Some(parent)) = (rom_mode, &game.rom_of) {
+                    if let Some(roms) = romsets_roms.get_mut(parent) {
+                        roms.insert(rom.clone());
+                    }
+                }
+                if let Some(roms) = romsets_roms.get_mut(&game.name) {
+                    roms.insert(rom);
+                }
+            }
+        }
+
+        Ok(romsets_roms.into_iter().map(|(game_name, roms)| (game_name, Vec::from_iter(roms))).collect())
     }
 
     fn get_rom_usage<S>(&self, game_name: S, rom_name: S, rom_mode: RomsetMode) -> Result<RomSearch> where S: AsRef<str> + rusqlite::ToSql {
@@ -517,20 +1460,42 @@ impl DataReader for DBReader {
     }
 
     fn get_romsets_from_roms(&self, roms: Vec<DataFile>, rom_mode: RomsetMode) -> Result<RomSearch> {
-        let mut search_rom_ids_result = DBReader::get_ids_from_files(&self.conn, roms)?;
+        let mut search_rom_ids_result = DBReader::get_ids_from_files_with_index(&self.conn, roms, self.index.as_ref())?;
 
         let mut rom_search = self.find_sets_for_roms(search_rom_ids_result.found, rom_mode)?;
         rom_search.unknowns.append(search_rom_ids_result.not_found.as_mut());
         Ok(rom_search)
     }
 
+    fn get_sets_using_rom_id(&self, rom_id: u32) -> Result<RomSearch> {
+        let query = GAMES_ROMS_QUERY.to_string() + " WHERE game_roms.rom_id = ?1 ORDER BY game_roms.game_name;";
+        let mut stmt = self.conn.prepare(&query)?;
+        let rows = stmt.query_map(params![rom_id], |row| {
+            process_games_rom_row(row)
+        })?.filter_map(|row| row.ok());
+
+        let mut result = RomSearch::new();
+        for (game, rom, _parent) in rows {
+            result.add_file_for_set(game.name, rom);
+        }
+
+        Ok(result)
+    }
+
     fn get_devices_for_game<S>(&self, game_name: S) -> Result<SetDependencies> where S: AsRef<str> + rusqlite::ToSql {
-        let mut search_stmt = self.conn.prepare("SELECT devices.device_ref FROM devices
-            JOIN game_roms ON devices.device_ref = game_roms.game_name
-            WHERE devices.game_name = ?1 GROUP BY devices.device_ref;")?;
+        // The devices referenced by other devices are followed too, UNION only adds the devices not found yet so
+        // the references going back to a device already found end there
+        let mut search_stmt = self.conn.prepare("WITH RECURSIVE device_chain(device_ref) AS (
+                SELECT device_ref FROM devices WHERE game_name = ?1
+                UNION
+                SELECT devices.device_ref FROM devices JOIN device_chain ON devices.game_name = device_chain.device_ref
+            )
+            SELECT device_chain.device_ref FROM device_chain
+            JOIN game_roms ON device_chain.device_ref = game_roms.game_name
+            WHERE device_chain.device_ref != ?1 GROUP BY device_chain.device_ref;")?;
 
         let result = search_stmt.query_map(params![game_name], |row| {
-            Ok(row.get(0)?)
+            row.get(0)
         })?.filter_map(|row| row.ok());
 
         let mut set_dependencies = SetDependencies::new(game_name.as_ref());
@@ -555,9 +1520,9 @@ impl DataReader for DBReader {
 
 #[cfg(test)]
 mod tests {
-    use std::{io::BufReader, fs::File, path::Path};
-    use rusqlite::{Connection, OpenFlags};
-    use crate::data::{importer::DatImporter, models::{disk::GameDiskInfo, file::FileType}, reader::sqlite::DBReader, writer::{sqlite::DBWriter}};
+    use rusqlite::OpenFlags;
+    use std::{env, fs, io::BufReader, fs::File};
+    use crate::data::{importer::DatImporter, models::{disk::GameDiskInfo, file::FileType}, reader::{SharedRoms, sqlite::DBReader}, reporter::{mode_detection::ModeMismatch, scan_report::ScanReport}, writer::{DataWriter, sqlite::DBWriter}};
     use super::*;
 
     fn get_db_connection<'a, 'b>(dat_path: &'b impl AsRef<Path>) -> Result<Connection> {
@@ -569,6 +1534,26 @@ mod tests {
         Ok(conn)
     }
 
+    #[test]
+    fn opens_the_database_from_a_path() -> Result<()> {
+        let db_file = env::temp_dir().join(format!("romst_reader_{}.rst", std::process::id()));
+        let _ = fs::remove_file(&db_file);
+        assert!(DBReader::from_path(&db_file).is_err());
+
+        let mut conn = Connection::open(&db_file)?;
+        let writer = DBWriter::from_connection(&mut conn, 5);
+        let mut importer = DatImporter::<BufReader<File>, DBWriter>::from_path(&Path::new("testdata").join("test.dat"), writer)?;
+        importer.load_dat()?;
+        drop(importer);
+        drop(conn);
+
+        let game = DBReader::from_path(&db_file).map(|data_reader| data_reader.get_game("game1"));
+        fs::remove_file(&db_file)?;
+        assert_eq!(Some("game1".to_string()), game?.map(|game| game.name));
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_sets() -> Result<()> {
         let path = Path::new("testdata").join("test.dat");
@@ -606,6 +1591,172 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn lists_the_games_in_scope() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let mut scope = GameFilter::default();
+        scope.add_include("source:gamelib.cpp")?;
+        let games = data_reader.get_games_in_scope(&scope)?;
+        assert_eq!(vec!["game0", "game1", "game1a"], games.iter().map(|game| game.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(Some("game1".to_string()), games[2].clone_of);
+
+        let report = ScanReport::new(None, RomsetMode::Merged);
+        let missing_sets = data_reader.get_missing_sets(&report, &scope)?;
+        assert_eq!(2, missing_sets.in_scope);
+        assert_eq!(vec!["game0".to_string(), "game1".to_string()], missing_sets.not_found);
+
+        Ok(())
+    }
+
+    #[test]
+    fn gets_the_roms_of_many_sets_at_once() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let game_names = data_reader.get_game_list(RomsetMode::NonMerged)?.into_iter()
+            .map(|(name, _)| name)
+            .chain(std::iter::once("unknown".to_string()))
+            .collect::<Vec<_>>();
+        for rom_mode in [RomsetMode::Merged, RomsetMode::NonMerged, RomsetMode::Split] {
+            let mut romsets_roms = data_reader.get_romsets_roms(&game_names, rom_mode)?;
+            assert_eq!(game_names.len(), romsets_roms.len());
+            for game_name in &game_names {
+                let mut expected = data_reader.get_romset_roms(game_name.as_str(), rom_mode)?;
+                let mut roms = romsets_roms.remove(game_name).unwrap();
+                expected.sort_by(|a, b| a.file.name.cmp(&b.file.name));
+                roms.sort_by(|a, b| a.file.name.cmp(&b.file.name));
+                assert_eq!(expected, roms, "{} in {}", game_name, rom_mode);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn counts_dump_quality() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let db_report = data_reader.get_db_info()?;
+        assert_eq!(db_report.roms_in_games, db_report.dump_quality.total());
+        assert_eq!(2, db_report.dump_quality.no_dump);
+        assert_eq!(0, db_report.dump_quality.bad_dump);
+
+        let game_set = data_reader.get_game_set("game0", RomsetMode::NonMerged)?;
+        assert_eq!(1, game_set.get_dump_quality().no_dump);
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_game_with_metadata() -> Result<()> {
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        let mut writer = DBWriter::from_connection(&mut conn, 5);
+        writer.init()?;
+        let mut game = Game::new("game1".to_string());
+        game.metadata = Some(Box::new(GameMetadata { driver_status: Some("good".to_string()), players: Some(2), ..Default::default() }));
+        writer.on_new_entry(game, vec![], vec![], vec![], vec![])?;
+        writer.on_new_entry(Game::new("game2".to_string()), vec![], vec![], vec![], vec![])?;
+        writer.finish()?;
+
+        let data_reader = DBReader::from_connection(conn);
+        let metadata = data_reader.get_game("game1").and_then(|game| game.metadata);
+        assert_eq!(metadata.as_ref().and_then(|metadata| metadata.driver_status.as_deref()), Some("good"));
+        assert_eq!(metadata.and_then(|metadata| metadata.players), Some(2));
+        assert_eq!(data_reader.get_game("game2").map(|game| game.metadata), Some(None));
+
+        Ok(())
+    }
+
+    #[test]
+    fn counts_the_rom_bytes_of_each_mode() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let stats = data_reader.get_dedupe_stats()?;
+
+        // The same bytes the sets have one by one
+        for mode in &stats.modes {
+            let mut bytes = 0;
+            for (game_name, _) in data_reader.get_game_list(mode.rom_mode)? {
+                bytes += data_reader.get_romset_roms(game_name.as_str(), mode.rom_mode)?.iter()
+                    .filter(|rom| rom.file.get_dump_status() != DumpStatus::NoDump)
+                    .map(|rom| rom.file.info.size.unwrap_or_default() as u64)
+                    .sum::<u64>();
+            }
+            assert_eq!(bytes, mode.bytes, "{}", mode.rom_mode);
+            assert_eq!(mode.bytes - stats.unique_bytes, mode.duplicated);
+        }
+        let non_merged = stats.modes[0].bytes;
+        assert_eq!(0, stats.modes[0].saved);
+        assert!(stats.modes[1].saved > 0);
+        assert_eq!(non_merged - stats.modes[2].bytes, stats.modes[2].saved);
+        assert!(stats.modes[2].bytes <= stats.modes[1].bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lists_the_largest_sets_and_roms() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let sizes = data_reader.get_sizes(RomsetMode::NonMerged, SizeOrder::Largest, 2)?;
+        assert_eq!(vec![("game1a", 6, 75776), ("game1", 4, 71680)],
+            sizes.sets.iter().map(|set| (set.name.as_str(), set.roms, set.bytes)).collect::<Vec<_>>());
+        assert_eq!(("game1", "binfil1.bin", 2, 65536), (sizes.roms[0].set_name.as_str(), sizes.roms[0].name.as_str(), sizes.roms[0].sets, sizes.roms[0].bytes));
+
+        // The clone only stores the roms it doesn't share with the parent
+        let sizes = data_reader.get_sizes(RomsetMode::Split, SizeOrder::Largest, 10)?;
+        assert_eq!(Some(4096), sizes.sets.iter().find(|set| set.name == "game1a").map(|set| set.bytes));
+        let sizes = data_reader.get_sizes(RomsetMode::Merged, SizeOrder::Largest, 10)?;
+        assert!(sizes.sets.iter().all(|set| set.name != "game1a"));
+
+        let sizes = data_reader.get_sizes(RomsetMode::NonMerged, SizeOrder::Smallest, 1)?;
+        assert_eq!(1, sizes.sets.len());
+        assert_eq!(512, sizes.roms[0].bytes);
+        Ok(())
+    }
+
+    #[test]
+    fn groups_sets_for_browsing() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let years = data_reader.get_sets_by_year()?;
+        assert!(years.contains(&BrowseGroup { name: Some("1984".to_string()), sets: 2 }));
+        assert!(years.contains(&BrowseGroup { name: Some("1999".to_string()), sets: 3 }));
+
+        let manufacturers = data_reader.get_sets_by_manufacturer()?;
+        assert!(manufacturers.contains(&BrowseGroup { name: Some("Lagos".to_string()), sets: 2 }));
+
+        let drivers = data_reader.get_sets_by_driver()?;
+        assert!(drivers.contains(&BrowseGroup { name: Some("gamelib.cpp".to_string()), sets: 3 }));
+        let total: u32 = drivers.iter().map(|group| group.sets).sum();
+        assert_eq!(data_reader.get_db_info()?.games, total);
+
+        let letters = data_reader.get_sets_grouped_by(BrowseGrouping::Letter, RomsetMode::Merged)?;
+        let parents = data_reader.get_game_list(RomsetMode::Merged)?.len() as u32;
+        assert_eq!(parents, letters.iter().map(|group| group.sets).sum::<u32>());
+
+        let sets = data_reader.get_sets_in_group(BrowseGrouping::Driver, Some("gamelib.cpp"), RomsetMode::NonMerged)?;
+        assert_eq!(vec!["game0", "game1", "game1a"], sets.iter().map(|set| set.0.as_str()).collect::<Vec<_>>());
+        let sets = data_reader.get_sets_in_group(BrowseGrouping::Driver, Some("gamelib.cpp"), RomsetMode::Merged)?;
+        assert_eq!(vec!["game0", "game1"], sets.iter().map(|set| set.0.as_str()).collect::<Vec<_>>());
+        let sets = data_reader.get_sets_in_group(BrowseGrouping::Driver, None, RomsetMode::NonMerged)?;
+        assert_eq!(drivers.iter().find(|group| group.name.is_none()).map(|group| group.sets), Some(sets.len() as u32));
+
+        Ok(())
+    }
+
     #[test]
     fn test_rom_ids_retrieval_with_repeated_roms() -> Result<()> {
         let path = Path::new("testdata").join("test.dat");
@@ -693,6 +1844,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn finds_the_sets_of_many_roms() -> Result<()> {
+        const ROMS: u32 = 40_000;
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        DBWriter::from_connection(&mut conn, 5).init()?;
+        let tx = conn.transaction()?;
+        tx.execute("INSERT INTO games (name) VALUES ('huge');", params![])?;
+        for id in 0..ROMS {
+            tx.execute("INSERT INTO roms (id, sha1, crc, size) VALUES (?1, ?2, ?3, 1024);", params![id, format!("{:040x}", id), format!("{:08x}", id)])?;
+            tx.execute("INSERT INTO game_roms (game_name, rom_id, name) VALUES ('huge', ?1, ?2);", params![id, format!("rom{}.bin", id)])?;
+        }
+        tx.commit()?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let db_roms = (0..ROMS).map(|id| DbDataEntry::new(id, DataFile::new(format!("rom{}.bin", id), DataFileInfo::new(FileType::Rom)))).collect();
+        let rom_sets = data_reader.find_sets_for_roms(db_roms, RomsetMode::NonMerged)?;
+
+        assert_eq!(ROMS as usize, rom_sets.get_roms_available_for_set("huge").len());
+        assert_eq!(1, rom_sets.set_results.len());
+
+        Ok(())
+    }
+
     #[test]
     fn find_rom_id_from_sha1() -> Result<()> {
         let path = Path::new("testdata").join("test.dat");
@@ -777,6 +1951,232 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn prefers_the_strongest_match_over_partial_hashes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch("CREATE TABLE roms (id INTEGER PRIMARY KEY, sha1 TEXT, md5 TEXT, crc TEXT, size INT);
+            INSERT INTO roms (id, sha1, md5, crc, size) VALUES (0, NULL, 'md5a', 'crca', 1024);
+            INSERT INTO roms (id, sha1, md5, crc, size) VALUES (1, 'sha1a', NULL, 'crca', 1024);
+            INSERT INTO roms (id, sha1, md5, crc, size) VALUES (2, 'sha1b', 'md5b', 'crcb', 1024);")?;
+
+        // Used to match both the first and second rows, through the hashes they don't have
+        let mut with_sha1 = DataFile::new("rom1", DataFileInfo::new(FileType::Rom));
+        with_sha1.info.sha1 = Some("sha1a".to_string());
+        with_sha1.info.crc = Some("crca".to_string());
+        with_sha1.info.size = Some(1024);
+        // The sha1 agrees with the third row, but the crc doesn't
+        let mut contradicting = DataFile::new("rom2", DataFileInfo::new(FileType::Rom));
+        contradicting.info.sha1 = Some("sha1b".to_string());
+        contradicting.info.crc = Some("crcx".to_string());
+
+        let result = DBReader::get_ids_from_files(&conn, vec![with_sha1, contradicting])?;
+
+        assert_eq!(1, result.found.len());
+        assert_eq!(1, result.found[0].id);
+        assert_eq!(vec![MatchEvidence::Sha1], result.evidence);
+        assert_eq!("rom2", result.not_found[0].name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn find_rom_ids_with_index() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let index = RomIndex::build(&conn, 0)?;
+
+        let mut rom1 = DataFile::new("rom1", DataFileInfo::new(FileType::Rom));
+        rom1.info.sha1 = Some("8273bfebe84dd41a5d237add8f9d03ac9bb0ef54".to_string());
+        rom1.info.crc = Some("1b736d41".to_string());
+        let mut rom2 = DataFile::new("rom2", DataFileInfo::new(FileType::Rom));
+        rom2.info.sha1 = Some("0000000000000000000000000000000000000000".to_string());
+        rom2.info.crc = Some("00000000".to_string());
+        let result = DBReader::get_ids_from_files_with_index(&conn, vec![ rom1, rom2 ], Some(&index))?;
+
+        assert_eq!(result.found.len(), 1);
+        assert_eq!(result.found[0].id, 5);
+        assert_eq!(result.not_found.len(), 1);
+        assert_eq!(result.not_found[0].name, "rom2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn index_matches_as_the_database() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let index = RomIndex::build(&conn, 0)?;
+
+        let mut stmt = conn.prepare("SELECT id, sha1, md5, crc FROM roms;")?;
+        let roms = stmt.query_map(params![], |row| {
+            let mut rom = DataFile::new(row.get::<_, u32>(0)?.to_string(), DataFileInfo::new(FileType::Rom));
+            rom.info.sha1 = row.get(1)?;
+            rom.info.md5 = row.get(2)?;
+            rom.info.crc = row.get(3)?;
+            Ok(rom)
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+        // Each hash on its own too, with the same rom searched by a single hash
+        let mut files = roms.clone();
+        for rom in &roms {
+            for keep in 0..3 {
+                let mut file = rom.clone();
+                if keep != 0 { file.info.sha1 = None; }
+                if keep != 1 { file.info.md5 = None; }
+                if keep != 2 { file.info.crc = None; }
+                files.push(file);
+            }
+        }
+
+        for file in files {
+            let from_index = index.find_candidates(&file).map(|mut candidates| { candidates.sort_by_key(|(id, _info)| *id); candidates });
+            let mut from_db = DBReader::get_roms_sharing_hash(&conn, &file.info)?;
+            from_db.sort_by_key(|(id, _info)| *id);
+            assert_eq!(from_index, Some(from_db), "{}", file);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_sets_using_rom_id() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        // binfil1.bin, used by game1 and its clone game1a
+        let rom_search = data_reader.get_sets_using_rom_id(5)?;
+        assert_eq!(2, rom_search.set_results.len());
+        let game1 = rom_search.get_roms_available_for_set("game1");
+        assert_eq!(1, game1.len());
+        assert_eq!("binfil1.bin", game1[0].file.name);
+        assert_eq!(1, rom_search.get_roms_available_for_set("game1a").len());
+
+        assert!(data_reader.get_sets_using_rom_id(1000)?.set_results.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn lists_the_sets_sharing_roms() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let rom_search = data_reader.get_romset_shared_roms("game1", RomsetMode::NonMerged)?;
+        let shared_roms = SharedRoms::from_search("game1", &rom_search);
+        // The set itself is left out, and its clone has all its roms in non-merged
+        assert_eq!(vec!["game1a"], shared_roms.shared_with.keys().collect::<Vec<_>>());
+        assert_eq!(vec!["binfil1.bin", "rom1.trom", "rom2.trom", "rom3.trom"], shared_roms.shared_with["game1a"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn finds_the_roms_by_hash_prefix_and_size() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let filter = RomFilter { sha1_prefix: Some("8273BF".to_string()), ..Default::default() };
+        let roms = data_reader.find_roms(&filter)?;
+        assert_eq!(1, roms.len());
+        assert_eq!(Some(65536), roms[0].info.size);
+        assert_eq!(Some(&vec!["binfil1.bin".to_string()]), roms[0].used_by.get("game1"));
+        assert!(roms[0].used_by.contains_key("game1a"));
+
+        let filter = RomFilter { size_min: Some(4096), size_max: Some(65536), ..Default::default() };
+        assert!(data_reader.find_roms(&filter)?.iter().all(|rom| matches!(rom.info.size, Some(size) if (4096..=65536).contains(&size))));
+        let filter = RomFilter { sha1_prefix: Some("8273bf".to_string()), size_max: Some(2048), ..Default::default() };
+        assert!(data_reader.find_roms(&filter)?.is_empty());
+
+        assert!(data_reader.find_roms(&RomFilter { crc_prefix: Some("1b%".to_string()), ..Default::default() }).is_err());
+        assert!(data_reader.find_roms(&RomFilter { size_min: Some(10), size_max: Some(1), ..Default::default() }).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_is_flagged_complete() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        assert!(data_reader.is_import_complete()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn partial_import_is_not_complete() -> Result<()> {
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        {
+            let writer = DBWriter::from_connection(&mut conn, 5);
+            writer.init()?;
+        }
+        let data_reader = DBReader::from_connection(conn);
+
+        assert!(!data_reader.is_import_complete()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_the_extra_rom_attributes() -> Result<()> {
+        let xml = r#"<datafile>
+            <game name="game1">
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f" region="maincpu" offset="1000"/>
+                <rom name="rom2.rom" size="2048" crc="1f2b3c4d"/>
+            </game>
+        </datafile>"#;
+        let dat_file = env::temp_dir().join(format!("romst_extra_attrs_{}.dat", std::process::id()));
+        fs::write(&dat_file, xml)?;
+        let conn = get_db_connection(&dat_file);
+        fs::remove_file(&dat_file)?;
+        let data_reader = DBReader::from_connection(conn?);
+
+        let roms = data_reader.get_romset_roms("game1", RomsetMode::NonMerged)?;
+        let rom1 = roms.iter().find(|rom| rom.file.name == "rom1.rom").unwrap();
+        assert_eq!(Some("maincpu"), rom1.file.get_extra_attr("region"));
+        assert_eq!(Some("1000"), rom1.file.get_extra_attr("offset"));
+        let rom2 = roms.iter().find(|rom| rom.file.name == "rom2.rom").unwrap();
+        assert_eq!(None, rom2.file.extra_attrs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_the_packing_of_the_dat() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let data_reader = DBReader::from_connection(get_db_connection(&path)?);
+        assert_eq!(None, data_reader.get_packing()?);
+
+        let xml = r#"<datafile>
+            <header>
+                <name>Unpacked</name>
+                <clrmamepro forcemerging="split" forcepacking="unzip"/>
+            </header>
+            <game name="game1">
+                <rom name="rom1.rom" size="2048" crc="8a1e7c8f"/>
+            </game>
+        </datafile>"#;
+        let dat_file = env::temp_dir().join(format!("romst_packing_{}.dat", std::process::id()));
+        fs::write(&dat_file, xml)?;
+        let conn = get_db_connection(&dat_file);
+        fs::remove_file(&dat_file)?;
+        let data_reader = DBReader::from_connection(conn?);
+
+        assert_eq!(Some(Packing::Unzip), data_reader.get_packing()?);
+        assert_eq!(Some(RomsetMode::Split), data_reader.get_dat_mode()?);
+        assert_eq!(None, ModeMismatch::check(&data_reader, RomsetMode::Split)?);
+        assert_eq!(Some(ModeMismatch { dat_mode: RomsetMode::Split, rom_mode: RomsetMode::Merged }), ModeMismatch::check(&data_reader, RomsetMode::Merged)?);
+        let dat_info = data_reader.get_dat_info()?;
+        assert_eq!(Some(Packing::Unzip), dat_info.get_packing());
+        assert!(dat_info.extra_data.contains(&("forcemerging".to_string(), "split".to_string())));
+
+        Ok(())
+    }
+
     #[test]
     fn get_devices_dependencies() -> Result<()> {
         let path = Path::new("testdata").join("test.dat");
@@ -789,4 +2189,73 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn resolves_the_devices_referenced_by_devices() -> Result<()> {
+        let xml = r#"<datafile>
+            <machine name="cpu" isdevice="yes">
+                <rom name="cpu.rom" size="1024" crc="11111111"/>
+                <device_ref name="board"/>
+            </machine>
+            <machine name="board" isdevice="yes">
+                <device_ref name="sound"/>
+                <device_ref name="cpu"/>
+            </machine>
+            <machine name="sound" isdevice="yes">
+                <rom name="sound.rom" size="1024" crc="22222222"/>
+                <device_ref name="cpu"/>
+            </machine>
+            <machine name="game1">
+                <rom name="rom1.rom" size="2048" crc="33333333"/>
+                <device_ref name="cpu"/>
+                <device_ref name="game1"/>
+            </machine>
+        </datafile>"#;
+        let dat_file = env::temp_dir().join(format!("romst_device_chain_{}.dat", std::process::id()));
+        fs::write(&dat_file, xml)?;
+        let conn = get_db_connection(&dat_file);
+        fs::remove_file(&dat_file)?;
+        let data_reader = DBReader::from_connection(conn?);
+
+        // The board has no roms, but the sound device it references does
+        assert_eq!(vec!["cpu".to_string(), "sound".to_string()], data_reader.get_devices_for_game("game1")?.dependencies);
+        assert_eq!(vec!["sound".to_string()], data_reader.get_devices_for_game("cpu")?.dependencies);
+
+        Ok(())
+    }
+
+    #[test]
+    fn forces_set_modes() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+
+        crate::data::writer::sqlite::write_mode_override(&conn, "game1", Some(RomsetMode::Split))?;
+        crate::data::writer::sqlite::write_mode_override(&conn, "game2", Some(RomsetMode::Merged))?;
+        crate::data::writer::sqlite::write_mode_override(&conn, "game2", None)?;
+        assert!(crate::data::writer::sqlite::write_mode_override(&conn, "nogame", Some(RomsetMode::Split)).is_err());
+
+        let data_reader = DBReader::from_connection(conn);
+        assert!(matches!(data_reader.get_mode_override("game1")?, Some(RomsetMode::Split)));
+        assert!(data_reader.get_mode_override("game2")?.is_none());
+        assert_eq!(vec!["game1"], data_reader.get_mode_overrides()?.overrides.keys().collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_storage_of_the_database() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let stats = data_reader.get_db_stats()?;
+        assert!(stats.file_size > 0);
+        assert!(stats.last_import.is_some());
+        let games = stats.tables.iter().find(|table| table.name == "games").unwrap();
+        assert_eq!(10, games.rows);
+        assert!(games.size.unwrap_or_default() > 0);
+        assert!(stats.indexes.iter().any(|index| index.name == "roms_sha1" && index.table == "roms"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,527 @@
+use std::{collections::{BTreeMap, BTreeSet}, fmt::Display, fs::{self, File}, io::{self, BufReader, BufWriter}, path::Path, time::Duration};
+
+use anyhow::Result;
+use log::info;
+use serde::{Deserialize, Serialize};
+use zip::{ZipArchive, ZipWriter, result::ZipError};
+
+use crate::{err, error::RomstError, filesystem::paths};
+
+use super::{backup::Backup, sanitize::NamePolicy, cleaner::get_tmp_path, disk_space::SpaceEstimate, fixer::check_zip, units::ByteSize, journal::{ExecutionProgress, ResumeJournal}, models::{self, dat_info::Packing}, priority::SetPriority, reporter::{CancellationToken, scan_report::{RomLocatedAt, ScanReport, SetReport}}};
+
+/// The read and write speed assumed to estimate how long a rebuild takes, in megabytes per second
+pub const DEFAULT_REBUILD_THROUGHPUT: f64 = 50.0;
+
+/// A rom copied into the rebuilt archive, renamed when the name in the source is not the one from the DAT
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebuildEntry {
+    pub from_archive: String,
+    pub from: String,
+    pub to: String,
+    pub size: u64,
+    /// The name of the rom in the DAT, when it's written with another name valid in the filesystem
+    #[serde(default)]
+    pub dat_name: Option<String>,
+}
+
+impl RebuildEntry {
+    pub fn is_rename(&self) -> bool {
+        self.from != self.to
+    }
+}
+
+/// How a set is rebuilt into its own archive in the target directory. It can't be rebuilt if a rom is missing, or if
+/// a rom is only in a source that isn't a zip archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetRebuild {
+    pub set_name: String,
+    pub archive: String,
+    pub entries: Vec<RebuildEntry>,
+    /// The archives the roms are read from
+    pub sources: BTreeSet<String>,
+    pub missing: Vec<String>,
+    pub unsupported: BTreeSet<String>,
+}
+
+impl SetRebuild {
+    pub fn is_achievable(&self) -> bool {
+        self.missing.is_empty() && self.unsupported.is_empty()
+    }
+
+    pub fn get_bytes(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+
+    pub fn get_renames(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.is_rename()).count()
+    }
+
+    /// The roms written with another name than the one in the DAT
+    pub fn get_sanitized(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.dat_name.is_some()).count()
+    }
+}
+
+impl Display for SetRebuild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_achievable() {
+            writeln!(f, "- {}: {} roms from {} archives, {} renamed, {}", self.archive, self.entries.len(), self.sources.len(), self.get_renames(), ByteSize(self.get_bytes()))?;
+            for entry in &self.entries {
+                if let Some(dat_name) = &entry.dat_name {
+                    writeln!(f, "  `{}` written as `{}`", dat_name, entry.to)?;
+                }
+            }
+            Ok(())
+        } else {
+            write!(f, "- {}: can't be rebuilt", self.archive)?;
+            if !self.missing.is_empty() {
+                write!(f, ", missing {}", self.missing.join(", "))?;
+            }
+            if !self.unsupported.is_empty() {
+                write!(f, ", not zip archives {}", self.unsupported.iter().cloned().collect::<Vec<_>>().join(", "))?;
+            }
+            writeln!(f)
+        }
+    }
+}
+
+/// The totals of the sets that can be rebuilt. The disk usage is the size of the roms uncompressed, the rebuilt
+/// archives keep the compression of the sources, so they usually take less
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RebuildTotals {
+    pub sets: usize,
+    pub achievable: usize,
+    pub sources: usize,
+    pub roms: usize,
+    pub renames: usize,
+    /// The roms written with another name than the one in the DAT
+    #[serde(default)]
+    pub sanitized: usize,
+    pub bytes: u64,
+    pub estimated_time: Duration,
+}
+
+impl Display for RebuildTotals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Sets: {} can be rebuilt out of {}", self.achievable, self.sets)?;
+        writeln!(f, "Archives to read: {}", self.sources)?;
+        writeln!(f, "Roms to copy: {}, {} renamed", self.roms, self.renames)?;
+        if self.sanitized > 0 {
+            writeln!(f, "Names changed for the filesystem: {}", self.sanitized)?;
+        }
+        writeln!(f, "Disk usage: at most {}", ByteSize(self.bytes))?;
+        writeln!(f, "Estimated time: {}s", self.estimated_time.as_secs())
+    }
+}
+
+/// Rebuilds the sets of a scan into new zip archives in a target directory, or folders if the sets are unpacked, one
+/// per set with the roms named as in the DAT, leaving the scanned files untouched. Only the roms are rebuilt, the
+/// disks stay where they are.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebuildPlan {
+    root_directory: Option<String>,
+    target_directory: String,
+    packing: Packing,
+    #[serde(default)]
+    name_policy: NamePolicy,
+    pub sets: Vec<SetRebuild>,
+    pub totals: RebuildTotals,
+    /// The archives are written once in the target directory
+    #[serde(default)]
+    pub space: SpaceEstimate,
+    /// The sets rebuilt, once the plan is executed
+    #[serde(default)]
+    pub progress: Option<ExecutionProgress>,
+}
+
+impl Display for RebuildPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(root_directory) = &self.root_directory {
+            writeln!(f, "Source dir: {}", root_directory)?;
+        }
+        writeln!(f, "Target dir: {}", self.target_directory)?;
+        writeln!(f, "Packing: {}", self.packing)?;
+        writeln!(f, "Names: {}", self.name_policy)?;
+        if self.sets.is_empty() {
+            writeln!(f, "Nothing to rebuild")?;
+        }
+        for set in &self.sets {
+            write!(f, "{}", set)?;
+        }
+        write!(f, "{}", self.totals)?;
+        write!(f, "{}", self.space)?;
+        if let Some(progress) = &self.progress {
+            write!(f, "{}", progress)?;
+        }
+        Ok(())
+    }
+}
+
+impl RebuildPlan {
+    /// Builds the plan for all the sets of the report, the roms are read from the set archive when they are there, or
+    /// from the first other archive that has them. The time is estimated reading and writing at `throughput` megabytes per second.
+    /// Without a packing, the sets are packed as the DAT of the report expects, in zip archives if it doesn't say
+    pub fn from_report<S>(report: &ScanReport, target_directory: S, throughput: f64, packing: Option<Packing>) -> Self where S: AsRef<str> {
+        let packing = packing.or_else(|| report.get_packing()).unwrap_or(Packing::Zip);
+        let mut archives_by_set = BTreeMap::new();
+        for file in report.archives.keys() {
+            archives_by_set.entry(models::get_set_from_file(file)).or_insert_with(|| file.to_string());
+        }
+
+        let sets = report.sets.iter().collect::<BTreeMap<_, _>>();
+        let sets = sets.into_iter().map(|(set_name, set)| {
+            let set_archive = archives_by_set.get(set_name).cloned().unwrap_or_else(|| format!("{}.zip", set_name));
+            plan_set(set_name, &set_archive, set, packing)
+        }).collect::<Vec<_>>();
+
+        let mut totals = RebuildTotals { sets: sets.len(), ..Default::default() };
+        let mut sources = BTreeSet::new();
+        for set in sets.iter().filter(|set| set.is_achievable()) {
+            totals.achievable += 1;
+            totals.roms += set.entries.len();
+            totals.renames += set.get_renames();
+            totals.bytes += set.get_bytes();
+            sources.extend(set.sources.iter());
+        }
+        totals.sources = sources.len();
+        // Each byte is read from the source and written to the target
+        let bytes_per_second = (throughput * 1024.0 * 1024.0).max(1.0);
+        totals.estimated_time = Duration::from_secs_f64(2.0 * totals.bytes as f64 / bytes_per_second);
+
+        let space = SpaceEstimate::new(totals.bytes, totals.bytes);
+        Self { root_directory: report.get_root_directory().map(|dir| dir.to_string()), target_directory: target_directory.as_ref().to_string(), packing, name_policy: NamePolicy::default(), sets, totals, space, progress: None }
+    }
+
+    pub fn get_root_directory(&self) -> Option<&str> {
+        self.root_directory.as_deref()
+    }
+
+    pub fn get_target_directory(&self) -> &str {
+        &self.target_directory
+    }
+
+    pub fn get_packing(&self) -> Packing {
+        self.packing
+    }
+
+    /// Reads the free space of the disk of the target directory
+    pub fn read_available_space(&mut self) {
+        self.space.read_available(&self.target_directory);
+    }
+
+    pub fn get_name_policy(&self) -> NamePolicy {
+        self.name_policy
+    }
+
+    /// Changes the names of the roms written into names valid for the policy, the entries changed keep the name of the DAT
+    pub fn sanitize_names(&mut self, name_policy: NamePolicy) {
+        self.name_policy = name_policy;
+        self.totals.renames = 0;
+        self.totals.sanitized = 0;
+        for set in self.sets.iter_mut() {
+            let dat_names = set.entries.iter().map(|entry| entry.dat_name.clone().unwrap_or_else(|| entry.to.to_string())).collect::<Vec<_>>();
+            for (entry, (written, dat_name)) in set.entries.iter_mut().zip(name_policy.sanitize_all(&dat_names).into_iter().zip(dat_names)) {
+                entry.dat_name = Some(dat_name).filter(|dat_name| *dat_name != written);
+                entry.to = written;
+            }
+            if set.is_achievable() {
+                self.totals.renames += set.get_renames();
+                self.totals.sanitized += set.get_sanitized();
+            }
+        }
+    }
+
+    /// Moves the sets in the priority list first, in the order of the list
+    pub fn prioritize(&mut self, priority: &SetPriority) {
+        if !priority.is_empty() {
+            self.sets.sort_by_key(|set| priority.get_rank(&set.set_name));
+        }
+    }
+
+    /// Writes the archives of the sets that can be rebuilt, stops at the first failure. An archive already in the
+    /// target directory is replaced
+    pub fn execute(&self) -> Result<()> {
+        self.execute_resumable(&mut ResumeJournal::default(), &mut Backup::default(), &CancellationToken::default())?;
+        Ok(())
+    }
+
+    /// Same as `execute`, but skips the sets already in the journal and records the ones rebuilt. Once cancelled it
+    /// stops after the set being rebuilt. An archive already in the target directory is backed up before it's replaced
+    pub fn execute_resumable(&self, journal: &mut ResumeJournal, backup: &mut Backup, cancellation: &CancellationToken) -> Result<ExecutionProgress> {
+        let root_directory = match &self.root_directory {
+            Some(root_directory) => Path::new(root_directory),
+            None => return err!("The plan doesn't have a directory to read the roms from"),
+        };
+        let target_directory = Path::new(&self.target_directory);
+        fs::create_dir_all(target_directory)?;
+
+        let mut progress = ExecutionProgress::default();
+        for set in self.sets.iter().filter(|set| set.is_achievable()) {
+            if journal.is_completed(&set.set_name) {
+                progress.resumed += 1;
+                continue;
+            }
+            if cancellation.is_cancelled() {
+                progress.interrupted = true;
+                break;
+            }
+            let archive = paths::join_key(target_directory, &set.archive);
+            info!("Rebuilding {}", archive.to_string_lossy());
+            if archive.exists() {
+                backup.save(target_directory, &set.archive)?;
+            }
+            match self.packing {
+                Packing::Zip => write_archive(root_directory, &archive, &set.entries)?,
+                Packing::Unzip => write_directory(root_directory, &archive, &set.entries)?,
+            }
+            journal.complete(&set.set_name)?;
+            progress.completed.push(set.set_name.to_string());
+        }
+        progress.backup_manifest = backup.get_manifest_file().map(|manifest_file| manifest_file.to_string_lossy().to_string());
+
+        Ok(progress)
+    }
+}
+
+fn plan_set(set_name: &str, set_archive: &str, set: &SetReport, packing: Packing) -> SetRebuild {
+    let mut roms = set.roms_available.iter().collect::<Vec<_>>();
+    roms.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+
+    let mut entries = vec![];
+    for (rom, located_at) in roms {
+        let (from_archive, from) = match located_at {
+            RomLocatedAt::InSet => (set_archive.to_string(), rom.name.to_string()),
+            RomLocatedAt::InSetWrongName(name) => (set_archive.to_string(), name.to_string()),
+            RomLocatedAt::InOthers(locations) => {
+                match locations.iter().min_by_key(|location| (location.get_file(), location.get_with_name())) {
+                    Some(location) => (location.get_file().to_string(), location.get_with_name().to_string()),
+                    None => continue,
+                }
+            }
+        };
+        let size = rom.info.size.unwrap_or_default() as u64;
+        entries.push(RebuildEntry { from_archive, from, to: rom.name.to_string(), size, dat_name: None });
+    }
+
+    let sources = entries.iter().map(|entry| entry.from_archive.to_string()).collect::<BTreeSet<_>>();
+    let unsupported = sources.iter().filter(|source| check_zip(Path::new(source)).is_err()).cloned().collect();
+    let mut missing = set.roms_missing.iter().map(|rom| rom.name.to_string()).collect::<Vec<_>>();
+    missing.sort();
+
+    let archive = match packing {
+        Packing::Zip => format!("{}.zip", set_name),
+        Packing::Unzip => set_name.to_string(),
+    };
+
+    SetRebuild { set_name: set_name.to_string(), archive, entries, sources, missing, unsupported }
+}
+
+/// Writes the archive copying the entries from their sources without recompressing them
+fn write_archive(root_directory: &Path, file: &Path, entries: &[RebuildEntry]) -> Result<()> {
+    let tmp_path = get_tmp_path(file);
+    let mut writer = ZipWriter::new(BufWriter::new(File::create(&tmp_path)?));
+
+    let mut sources = BTreeMap::new();
+    for entry in entries {
+        if !sources.contains_key(&entry.from_archive) {
+            let source = ZipArchive::new(BufReader::new(File::open(paths::join_key(root_directory, &entry.from_archive))?))?;
+            sources.insert(entry.from_archive.to_string(), source);
+        }
+        let source = sources.get_mut(&entry.from_archive).unwrap();
+        let index = (0..source.len()).find(|i| matches!(source.by_index_raw(*i), Ok(file) if file.name() == entry.from));
+        match index {
+            Some(index) => writer.raw_copy_file_rename(source.by_index_raw(index)?, &entry.to)?,
+            None => {
+                drop(writer);
+                fs::remove_file(&tmp_path)?;
+                return err!(RomstError::GenericError { message: format!("The entry `{}` is not in `{}`", entry.from, entry.from_archive) });
+            }
+        }
+    }
+
+    writer.finish()?;
+    fs::rename(tmp_path, file)?;
+    Ok(())
+}
+
+/// Writes the roms unpacked in a folder, extracting the entries from their sources. A folder already there is replaced
+fn write_directory(root_directory: &Path, directory: &Path, entries: &[RebuildEntry]) -> Result<()> {
+    let tmp_path = get_tmp_path(directory);
+    fs::create_dir_all(&tmp_path)?;
+
+    let mut sources = BTreeMap::new();
+    for entry in entries {
+        if !sources.contains_key(&entry.from_archive) {
+            let source = ZipArchive::new(BufReader::new(File::open(paths::join_key(root_directory, &entry.from_archive))?))?;
+            sources.insert(entry.from_archive.to_string(), source);
+        }
+        let source = sources.get_mut(&entry.from_archive).unwrap();
+        let mut file = match source.by_name(&entry.from) {
+            Ok(file) => file,
+            Err(ZipError::FileNotFound) => {
+                fs::remove_dir_all(&tmp_path)?;
+                return err!(RomstError::GenericError { message: format!("The entry `{}` is not in `{}`", entry.from, entry.from_archive) });
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let target = tmp_path.join(&entry.to);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut file, &mut BufWriter::new(File::create(target)?))?;
+    }
+
+    if directory.exists() {
+        fs::remove_dir_all(directory)?;
+    }
+    fs::rename(tmp_path, directory)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::{Read, Write}};
+
+    use crate::{RomsetMode, data::{models::{archive::{ArchiveFormat, ArchiveInfo}, file::{DataFile, DataFileInfo, FileType}}, reporter::scan_report::RomLocation}};
+
+    use super::*;
+
+    fn rom(name: &str, crc: &str, size: u32) -> DataFile {
+        let mut info = DataFileInfo::new(FileType::Rom);
+        info.crc = Some(crc.to_string());
+        info.size = Some(size);
+        DataFile::new(name, info)
+    }
+
+    #[test]
+    fn plans_and_rebuilds_the_sets() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_rebuild_{}", std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        let source_dir = dir.join("roms");
+        fs::create_dir_all(&source_dir)?;
+        for (file, entries) in [("game1.zip", vec![("a.rom", "aa"), ("wrong.rom", "bbb")]), ("game2.zip", vec![("other.rom", "cccc")])] {
+            let mut writer = ZipWriter::new(File::create(source_dir.join(file))?);
+            for (name, content) in entries {
+                writer.start_file(name, zip::write::FileOptions::default())?;
+                writer.write_all(content.as_bytes())?;
+            }
+            writer.finish()?;
+        }
+
+        let mut report = ScanReport::new(Some(source_dir.to_string_lossy().to_string()), RomsetMode::Split);
+        report.add_archive_info("game1.zip", ArchiveInfo::new(ArchiveFormat::NonCanonical, 2, 0, String::new()));
+        report.add_archive_info("game2.zip", ArchiveInfo::new(ArchiveFormat::NonCanonical, 1, 0, String::new()));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "a.rom"), rom("a.rom", "1111", 2));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "wrong.rom"), rom("b.rom", "2222", 3));
+        report.add_rom_for_set("game1", RomLocation::new("game2.zip", "other.rom"), rom("c.rom", "3333", 4));
+        report.add_rom_for_set("game3", RomLocation::new("game2.zip", "other.rom"), rom("c.rom", "3333", 4));
+        report.add_missing_roms_for_set("game3", vec![rom("d.rom", "4444", 5)]);
+
+        let target_dir = dir.join("rebuilt").to_string_lossy().to_string();
+        let plan = RebuildPlan::from_report(&report, &target_dir, 1.0, None);
+
+        let game1 = plan.sets.iter().find(|set| set.set_name == "game1").unwrap();
+        assert!(game1.is_achievable());
+        assert_eq!(vec!["game1.zip", "game2.zip"], game1.sources.iter().collect::<Vec<_>>());
+        assert_eq!(2, game1.get_renames());
+        let game3 = plan.sets.iter().find(|set| set.set_name == "game3").unwrap();
+        assert!(!game3.is_achievable());
+        assert_eq!(vec!["d.rom".to_string()], game3.missing);
+        assert_eq!(2, plan.totals.sets);
+        assert_eq!(1, plan.totals.achievable);
+        assert_eq!(9, plan.totals.bytes);
+        assert!(plan.totals.estimated_time > Duration::from_secs(0));
+
+        plan.execute()?;
+
+        let mut archive = ZipArchive::new(File::open(dir.join("rebuilt").join("game1.zip"))?)?;
+        let mut contents = vec![];
+        for name in ["a.rom", "b.rom", "c.rom"] {
+            let mut content = String::new();
+            archive.by_name(name)?.read_to_string(&mut content)?;
+            contents.push(content);
+        }
+        assert_eq!(vec!["aa", "bbb", "cccc"], contents);
+        assert!(!dir.join("rebuilt").join("game3.zip").exists());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn rebuilds_the_sets_unpacked_as_the_dat_expects() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_rebuild_unzip_{}", std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        let source_dir = dir.join("roms");
+        fs::create_dir_all(&source_dir)?;
+        let mut writer = ZipWriter::new(File::create(source_dir.join("game1.zip"))?);
+        for (name, content) in [("a.rom", "aa"), ("wrong.rom", "bbb")] {
+            writer.start_file(name, zip::write::FileOptions::default())?;
+            writer.write_all(content.as_bytes())?;
+        }
+        writer.finish()?;
+
+        let mut report = ScanReport::new(Some(source_dir.to_string_lossy().to_string()), RomsetMode::Split);
+        report.set_packing(Some(Packing::Unzip));
+        report.add_archive_info("game1.zip", ArchiveInfo::new(ArchiveFormat::NonCanonical, 2, 0, String::new()));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "a.rom"), rom("a.rom", "1111", 2));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "wrong.rom"), rom("sub/b.rom", "2222", 3));
+
+        let target_dir = dir.join("rebuilt").to_string_lossy().to_string();
+        assert_eq!(Packing::Zip, RebuildPlan::from_report(&report, &target_dir, 1.0, Some(Packing::Zip)).get_packing());
+        let plan = RebuildPlan::from_report(&report, &target_dir, 1.0, None);
+        assert_eq!(Packing::Unzip, plan.get_packing());
+        assert_eq!("game1", plan.sets[0].archive);
+
+        plan.execute()?;
+
+        let set_dir = dir.join("rebuilt").join("game1");
+        assert_eq!("aa", fs::read_to_string(set_dir.join("a.rom"))?);
+        assert_eq!("bbb", fs::read_to_string(set_dir.join("sub").join("b.rom"))?);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn writes_the_names_valid_for_the_policy() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_rebuild_names_{}", std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        let source_dir = dir.join("roms");
+        fs::create_dir_all(&source_dir)?;
+        let mut writer = ZipWriter::new(File::create(source_dir.join("game1.zip"))?);
+        for (name, content) in [("a:b.rom", "aa"), ("a?b.rom", "bbb")] {
+            writer.start_file(name, zip::write::FileOptions::default())?;
+            writer.write_all(content.as_bytes())?;
+        }
+        writer.finish()?;
+
+        let mut report = ScanReport::new(Some(source_dir.to_string_lossy().to_string()), RomsetMode::Split);
+        report.add_archive_info("game1.zip", ArchiveInfo::new(ArchiveFormat::NonCanonical, 2, 0, String::new()));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "a:b.rom"), rom("a:b.rom", "1111", 2));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "a?b.rom"), rom("a?b.rom", "2222", 3));
+
+        let mut plan = RebuildPlan::from_report(&report, dir.join("rebuilt").to_string_lossy(), 1.0, None);
+        assert_eq!(0, plan.totals.renames);
+        plan.sanitize_names(NamePolicy::Compatible);
+        let written = plan.sets[0].entries.iter().map(|entry| (entry.to.as_str(), entry.dat_name.as_deref())).collect::<Vec<_>>();
+        assert_eq!(vec![("a_b.rom", Some("a:b.rom")), ("a_b (2).rom", Some("a?b.rom"))], written);
+        assert_eq!(2, plan.totals.sanitized);
+        assert_eq!(2, plan.totals.renames);
+
+        plan.execute()?;
+        let mut archive = ZipArchive::new(File::open(dir.join("rebuilt").join("game1.zip"))?)?;
+        let mut content = String::new();
+        archive.by_name("a_b (2).rom")?.read_to_string(&mut content)?;
+        assert_eq!("bbb", content);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! The sets whose status changed between two reports of the same collection, a set is regressed when it's worse than
+//! it was, like a complete set that is now fixeable or wasn't found.
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::models;
+
+use super::scan_report::{ScanReport, SetStatus};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetDiff {
+    pub name: String,
+    /// The status in the older report, none if the set wasn't found
+    pub before: Option<SetStatus>,
+    pub after: Option<SetStatus>,
+}
+
+impl SetDiff {
+    pub fn is_regression(&self) -> bool {
+        get_rank(self.after) > get_rank(self.before)
+    }
+}
+
+/// How bad a status is, a set not found is worse than an incomplete one
+fn get_rank(status: Option<SetStatus>) -> u8 {
+    match status {
+        Some(SetStatus::COMPLETE) => 0,
+        Some(SetStatus::FIXEABLE) => 1,
+        Some(SetStatus::INCOMPLETE) => 2,
+        None => 3,
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReportDiff {
+    pub before_date_time: String,
+    pub after_date_time: String,
+    /// The sets whose status changed, in the order of their names
+    pub sets: Vec<SetDiff>,
+}
+
+impl ReportDiff {
+    /// Compares the statuses of the sets, the sets of the files the newer report didn't scan are left out as their
+    /// status is not known
+    pub fn new(before: &ScanReport, after: &ScanReport) -> Self {
+        let before_statuses = before.get_set_statuses();
+        let after_statuses = after.get_set_statuses();
+        let unscanned_sets = after.unscanned.iter().map(|file| models::get_set_from_file(file)).collect::<BTreeSet<_>>();
+
+        let names = before_statuses.keys().chain(after_statuses.keys()).collect::<BTreeSet<_>>();
+        let sets = names.into_iter()
+            .filter(|name| !unscanned_sets.contains(*name))
+            .map(|name| SetDiff { name: name.to_owned(), before: before_statuses.get(name).copied(), after: after_statuses.get(name).copied() })
+            .filter(|set| set.before != set.after)
+            .collect();
+
+        Self {
+            before_date_time: before.get_date_time_string().to_string(),
+            after_date_time: after.get_date_time_string().to_string(),
+            sets,
+        }
+    }
+
+    pub fn get_regressions(&self) -> Vec<&SetDiff> {
+        self.sets.iter().filter(|set| set.is_regression()).collect()
+    }
+
+    /// The position of the first regression after `index`, wrapping around to the first one
+    pub fn next_regression(&self, index: Option<usize>) -> Option<usize> {
+        let start = index.map(|index| index + 1).unwrap_or(0);
+        let positions = self.get_regression_positions();
+        positions.iter().find(|position| **position >= start).or_else(|| positions.first()).copied()
+    }
+
+    /// The position of the last regression before `index`, wrapping around to the last one
+    pub fn previous_regression(&self, index: Option<usize>) -> Option<usize> {
+        let positions = self.get_regression_positions();
+        let before = index.and_then(|index| positions.iter().rev().find(|position| **position < index));
+        before.or_else(|| positions.last()).copied()
+    }
+
+    fn get_regression_positions(&self) -> Vec<usize> {
+        self.sets.iter().enumerate().filter(|(_, set)| set.is_regression()).map(|(position, _)| position).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RomsetMode, data::models::{file::{DataFile, DataFileInfo, FileType}, game::Game}};
+    use super::*;
+
+    /// A report with the sets either complete or missing a rom
+    fn get_report(statuses: &[(&str, bool)]) -> ScanReport {
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::NonMerged);
+        for (name, complete) in statuses {
+            report.reference_with_game(Game::new(name.to_string()));
+            if !complete {
+                report.add_missing_rom_for_set(*name, DataFile::new("rom1", DataFileInfo::new(FileType::Rom)));
+            }
+        }
+        report
+    }
+
+    #[test]
+    fn finds_the_regressed_sets() {
+        let before = get_report(&[("game1", true), ("game2", false), ("game3", true), ("game4", true), ("game6", true)]);
+        let mut after = get_report(&[("game1", false), ("game2", true), ("game4", true), ("game5", true)]);
+        after.add_unscanned("game6.zip");
+
+        let diff = ReportDiff::new(&before, &after);
+        let changed = diff.sets.iter().map(|set| (set.name.as_str(), set.is_regression())).collect::<Vec<_>>();
+        assert_eq!(vec![("game1", true), ("game2", false), ("game3", true), ("game5", false)], changed);
+        assert_eq!(2, diff.get_regressions().len());
+
+        assert_eq!(Some(0), diff.next_regression(None));
+        assert_eq!(Some(2), diff.next_regression(Some(0)));
+        assert_eq!(Some(0), diff.next_regression(Some(2)));
+        assert_eq!(Some(2), diff.previous_regression(None));
+        assert_eq!(Some(0), diff.previous_regression(Some(2)));
+        assert_eq!(Some(2), diff.previous_regression(Some(0)));
+        assert_eq!(None, ReportDiff::default().next_regression(None));
+    }
+}
@@ -0,0 +1,805 @@
+use std::{collections::BTreeMap, fmt::{self, Write}};
+
+use console::Style;
+
+use crate::data::{units::{ByteSize, Count}, ignored_roms::IgnoredRoms, importer::{ImportIssueLevel, ImportReport, MultiImportReport}, models::file::{DataFile, DumpQuality}, reader::{RomFindReport, RomSearch, SharedRoms, sqlite::{DBReport, DBStats, DedupeStats, DumpQualityReport, LastCheck, SetModeOverrides, SetUserData, SizesReport}}};
+
+use super::{ownership::SetOwnership, sample_audit::SampleAuditReport, scan_report::{FileErrorKind, RomLocatedAt, ScanReport, SetReference, SetReport, SetStatus}, summary::{CheckSummary, MissingSets}};
+
+/// All the fixed pieces of text used when rendering a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportText {
+    ScannedDir,
+    DateOfReport,
+    Mode,
+    MatchedBy,
+    WeakMatching,
+    Ignored,
+    NotScanned,
+    FileErrors,
+    TransientError,
+    PermanentError,
+    SampleAudit,
+    Seed,
+    ArchivesScreened,
+    ArchivesSampled,
+    DirectoryMatching,
+    SampleMismatches,
+    MismatchRate,
+    Confidence,
+    ExpectedMismatches,
+    AtMost,
+    NoEstimate,
+    SplitArchives,
+    WrongPacking,
+    AliasRenames,
+    Set,
+    FileName,
+    Status,
+    InFile,
+    DependsOn,
+    RomsAvailable,
+    RenameFrom,
+    LocatedAt,
+    LocatedAs,
+    RomsUnneeded,
+    RomsMissing,
+    RomsToSpare,
+    DisksAvailable,
+    DisksMissing,
+    StatusComplete,
+    StatusFixeable,
+    StatusIncomplete,
+    Roms,
+    UnknownFiles,
+    DatabaseInfo,
+    Games,
+    RomsInGames,
+    Samples,
+    DeviceReferences,
+    NonCanonicalArchives,
+    CorruptArchives,
+    DumpQuality,
+    DatabaseStorage,
+    FileSize,
+    LastImport,
+    Unknown,
+    Tables,
+    Indexes,
+    Rows,
+    DumpsVerified,
+    DumpsGood,
+    BadDumps,
+    NoDumps,
+    ModeOverrides,
+    NoModeOverrides,
+    RomBytesByMode,
+    UniqueRoms,
+    Duplicated,
+    Saved,
+    SetsBySize,
+    RomsBySize,
+    SetCount,
+    RomCount,
+    TaggedSets,
+    NoTaggedSets,
+    Tags,
+    Note,
+    ImportedEntries,
+    ImportedDats,
+    DatFile,
+    ExcludedGames,
+    ReclaimedSpace,
+    ImportWarnings,
+    ImportErrors,
+    ImportWarning,
+    ImportError,
+    Position,
+    IssuesNotShown,
+    CheckSummary,
+    NewlyBroken,
+    NoneNewlyBroken,
+    RomsFound,
+    NoRomsFound,
+    NotSetCount,
+    SharedWith,
+    NoSharedRoms,
+    LastChecks,
+    SeveralDirectories,
+    SetsInScope,
+    NotScannedSets,
+    SetsNotFound,
+    NoSetsNotFound,
+    RomsIgnored,
+    IgnoredRoms,
+    NoIgnoredRoms,
+    IgnoredEverywhere,
+    CheckedAt,
+    NotInCheck,
+    RomsInCollection,
+    RomInSet,
+    RomToRename,
+    RomInOtherFile,
+    RomMissing,
+    RomIgnored,
+    RomUnneeded,
+    RomNotInReport,
+}
+
+impl From<&SetStatus> for ReportText {
+    fn from(status: &SetStatus) -> Self {
+        match status {
+            SetStatus::COMPLETE => ReportText::StatusComplete,
+            SetStatus::FIXEABLE => ReportText::StatusFixeable,
+            SetStatus::INCOMPLETE => ReportText::StatusIncomplete,
+        }
+    }
+}
+
+/// Renders the reports as text.
+/// `text` gives the labels, override it to localize the output, along with `size` and `count` for the numbers. The
+/// `write_*` methods do the traversal of the report data, override them to change the layout.
+pub trait ReportFormatter {
+    fn size(&self, bytes: u64) -> String {
+        ByteSize(bytes).to_string()
+    }
+
+    fn count(&self, count: u64) -> String {
+        Count(count).to_string()
+    }
+
+    fn text(&self, text: ReportText) -> String {
+        english_text(text).to_string()
+    }
+
+    fn write_scan_report(&self, f: &mut dyn Write, report: &ScanReport) -> fmt::Result {
+        if let Some(path) = report.get_root_directory() {
+            writeln!(f, "{}: {}", self.text(ReportText::ScannedDir), path)?;
+        }
+        writeln!(f, "{}: {}", self.text(ReportText::DateOfReport), report.get_date_time_string())?;
+        writeln!(f, "{}: {}", self.text(ReportText::Mode), report.get_rom_mode())?;
+        if let Some(file_checks) = report.get_file_checks() {
+            writeln!(f, "{}: {}", self.text(ReportText::MatchedBy), file_checks)?;
+        }
+        if report.is_weak_matching() {
+            writeln!(f, "{}", Style::new().yellow().apply_to(self.text(ReportText::WeakMatching)))?;
+        }
+        let ignored_roms = report.get_ignored_roms_count();
+        if ignored_roms > 0 {
+            writeln!(f, "{}: {}", self.text(ReportText::RomsIgnored), ignored_roms)?;
+        }
+        writeln!(f)?;
+        if report.is_interrupted() {
+            writeln!(f, "{}:", self.text(ReportText::NotScanned))?;
+            for file in &report.unscanned {
+                writeln!(f, "- {}", file)?;
+            }
+            writeln!(f)?;
+        }
+        if !report.errors.is_empty() {
+            writeln!(f, "{}:", self.text(ReportText::FileErrors))?;
+            for (file, error) in &report.errors {
+                let kind = match error.kind {
+                    FileErrorKind::Transient => ReportText::TransientError,
+                    FileErrorKind::Permanent => ReportText::PermanentError,
+                };
+                writeln!(f, "- {} [{}]: {}", file, self.text(kind), error.message)?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(sample_audit) = &report.sample_audit {
+            self.write_sample_audit(f, sample_audit)?;
+            writeln!(f)?;
+        }
+        if !report.ignored.is_empty() {
+            writeln!(f, "{}:", self.text(ReportText::Ignored))?;
+            for file in &report.ignored {
+                writeln!(f, "- {}", file)?;
+            }
+            writeln!(f)?;
+        }
+        if !report.split_archives.is_empty() {
+            writeln!(f, "{}:", self.text(ReportText::SplitArchives))?;
+            for (file, parts) in report.split_archives.iter().collect::<BTreeMap<_, _>>() {
+                writeln!(f, "- {} ({})", file, parts.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        if let (Some(packing), false) = (report.get_packing(), report.wrong_packing.is_empty()) {
+            writeln!(f, "{} ({}):", self.text(ReportText::WrongPacking), packing)?;
+            for file in &report.wrong_packing {
+                writeln!(f, "- {}", file)?;
+            }
+            writeln!(f)?;
+        }
+        if !report.alias_renames.is_empty() {
+            writeln!(f, "{}:", self.text(ReportText::AliasRenames))?;
+            for (file, rename) in &report.alias_renames {
+                writeln!(f, "- {} -> {}", file, rename)?;
+            }
+            writeln!(f)?;
+        }
+
+        for set in report.sets.values() {
+            self.write_set_report(f, set)?;
+            writeln!(f)?;
+        }
+
+        let non_canonical = report.get_non_canonical_archives();
+        if !non_canonical.is_empty() {
+            writeln!(f, "{}:", self.text(ReportText::NonCanonicalArchives))?;
+            for (file, archive_info) in non_canonical {
+                writeln!(f, "- {} {}", file, archive_info)?;
+            }
+        }
+
+        if !report.corrupt.is_empty() {
+            writeln!(f, "{}:", self.text(ReportText::CorruptArchives))?;
+            for (file, issues) in report.corrupt.iter().collect::<BTreeMap<_, _>>() {
+                writeln!(f, "- {}", file)?;
+                for issue in issues {
+                    writeln!(f, "   - {}", issue)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_set_reference(&self, f: &mut dyn Write, reference: &SetReference) -> fmt::Result {
+        match reference {
+            SetReference::FileName(name) => {
+                writeln!(f, "{}: {}", self.text(ReportText::FileName), name)
+            }
+            SetReference::Game(game) => {
+                writeln!(f, "{}", game)
+            }
+        }
+    }
+
+    fn write_set_report(&self, f: &mut dyn Write, set: &SetReport) -> fmt::Result {
+        write!(f, "{} - ", self.text(ReportText::Set))?;
+        self.write_set_reference(f, &set.reference)?;
+        let file_status = if set.in_file {
+            format!(" [{}]", self.text(ReportText::InFile))
+        } else {
+            String::new()
+        };
+        writeln!(f, "{}: {}{}", self.text(ReportText::Status), self.text((&set.is_complete()).into()), file_status)?;
+        let dump_quality = set.get_dump_quality();
+        if dump_quality.total() > 0 {
+            write!(f, "{}: ", self.text(ReportText::DumpQuality))?;
+            self.write_dump_quality(f, &dump_quality)?;
+        }
+
+        if !set.device_dependencies.is_empty() {
+            writeln!(f, "{}:", self.text(ReportText::DependsOn))?;
+            for dep in &set.device_dependencies {
+                writeln!(f, " - {}", dep)?;
+            }
+        }
+        if !set.roms_available.is_empty() {
+            writeln!(f, "{}", self.text(ReportText::RomsAvailable))?;
+            for (rom, location) in &set.roms_available {
+                self.write_rom_available(f, rom, location)?;
+            }
+        }
+        self.write_rom_list(f, ReportText::RomsUnneeded, set.roms_unneeded.iter().collect())?;
+        self.write_rom_list(f, ReportText::RomsMissing, set.roms_missing.iter().collect())?;
+        self.write_rom_list(f, ReportText::RomsIgnored, set.roms_ignored.iter().collect())?;
+        if !set.disks_available.is_empty() {
+            writeln!(f, "{}", self.text(ReportText::DisksAvailable))?;
+            for (disk, location) in &set.disks_available {
+                self.write_rom_available(f, disk, location)?;
+            }
+        }
+        self.write_rom_list(f, ReportText::DisksMissing, set.disks_missing.iter().collect())?;
+        self.write_rom_list(f, ReportText::RomsToSpare, set.roms_to_spare.iter().collect())
+    }
+
+    fn write_rom_available(&self, f: &mut dyn Write, rom: &DataFile, location: &RomLocatedAt) -> fmt::Result {
+        match location {
+            RomLocatedAt::InSet => writeln!(f, " - {}", rom.name),
+            RomLocatedAt::InSetWrongName(name) => writeln!(f, " - {} [{}: {}]", rom.name, self.text(ReportText::RenameFrom), name),
+            RomLocatedAt::InOthers(locations) => {
+                let location_list = locations.iter().map(|location| {
+                    format!("{} {} {}", location.get_file(), self.text(ReportText::LocatedAs), location.get_with_name())
+                }).collect::<Vec<_>>();
+                writeln!(f, " - {} [{}: {}]", rom.name, self.text(ReportText::LocatedAt), location_list.join(", "))
+            }
+        }
+    }
+
+    fn write_rom_list(&self, f: &mut dyn Write, title: ReportText, roms: Vec<&DataFile>) -> fmt::Result {
+        if !roms.is_empty() {
+            writeln!(f, "{}", self.text(title))?;
+            for rom in roms {
+                writeln!(f, " - {}", rom.name)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_rom_search(&self, f: &mut dyn Write, rom_search: &RomSearch) -> fmt::Result {
+        for (set_name, set_content) in &rom_search.set_results {
+            writeln!(f, "{}: {}", self.text(ReportText::Set), Style::new().green().bold().apply_to(set_name))?;
+            let roms = set_content.get_roms_included();
+            if !roms.is_empty() {
+                writeln!(f, "  {}:", Style::new().cyan().apply_to(self.text(ReportText::Roms)))?;
+                for rom in roms {
+                    writeln!(f, "   - {}", rom)?;
+                }
+            }
+        }
+
+        if !rom_search.unknowns.is_empty() {
+            writeln!(f, "  {}:", Style::new().red().apply_to(self.text(ReportText::UnknownFiles)))?;
+            for unknown in &rom_search.unknowns {
+                writeln!(f, "   - {}", unknown)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_rom_find_report(&self, f: &mut dyn Write, report: &RomFindReport) -> fmt::Result {
+        if report.roms.is_empty() {
+            return writeln!(f, "{}", self.text(ReportText::NoRomsFound));
+        }
+        writeln!(f, "{}: {}", self.text(ReportText::RomsFound), report.roms.len())?;
+        for rom in &report.roms {
+            writeln!(f, "- {}", rom.info)?;
+            if rom.used_by.is_empty() {
+                writeln!(f, "   {}", self.text(ReportText::NotSetCount))?;
+            }
+            for (set_name, rom_names) in &rom.used_by {
+                writeln!(f, "   - {}: {}", Style::new().green().apply_to(set_name), rom_names.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_shared_roms(&self, f: &mut dyn Write, shared_roms: &SharedRoms) -> fmt::Result {
+        writeln!(f, "{}: {}", self.text(ReportText::Set), Style::new().green().bold().apply_to(&shared_roms.set_name))?;
+        if shared_roms.shared_with.is_empty() {
+            return writeln!(f, "  {}", self.text(ReportText::NoSharedRoms));
+        }
+        writeln!(f, "  {}:", Style::new().cyan().apply_to(self.text(ReportText::SharedWith)))?;
+        for (set_name, rom_names) in &shared_roms.shared_with {
+            writeln!(f, "   - {}: {}", Style::new().green().apply_to(set_name), rom_names.join(", "))?;
+        }
+        Ok(())
+    }
+
+    fn write_set_ownership(&self, f: &mut dyn Write, ownership: &SetOwnership) -> fmt::Result {
+        let status = match &ownership.status {
+            Some(status) => self.text(status.into()),
+            None => self.text(ReportText::NotInCheck),
+        };
+        writeln!(f, "{}: {} ({} {})", self.text(ReportText::Status), status, self.text(ReportText::CheckedAt), ownership.checked_at)?;
+        writeln!(f, "{}: {} / {}", self.text(ReportText::RomsInCollection), ownership.roms_found, ownership.roms.len())?;
+        for rom in &ownership.roms {
+            match &rom.found_as {
+                Some(found_as) => writeln!(f, " - {}: {} {}", rom.name, self.text((&rom.ownership).into()), found_as)?,
+                None => writeln!(f, " - {}: {}", rom.name, self.text((&rom.ownership).into()))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_db_report(&self, f: &mut dyn Write, db_report: &DBReport) -> fmt::Result {
+        write!(f, "{}", db_report.dat_info)?;
+        writeln!(f, "{}", self.text(ReportText::DatabaseInfo))?;
+        writeln!(f, "- {}: {}", self.text(ReportText::Games), self.count(db_report.games.into()))?;
+        writeln!(f, "- {}: {}", self.text(ReportText::Roms), self.count(db_report.roms.into()))?;
+        writeln!(f, "- {}: {}", self.text(ReportText::RomsInGames), self.count(db_report.roms_in_games.into()))?;
+        writeln!(f, "- {}: {}", self.text(ReportText::Samples), self.count(db_report.samples.into()))?;
+        writeln!(f, "- {}: {}", self.text(ReportText::DeviceReferences), self.count(db_report.device_refs.into()))?;
+        write!(f, "- {}: ", self.text(ReportText::DumpQuality))?;
+        self.write_dump_quality(f, &db_report.dump_quality)?;
+        self.write_db_stats(f, &db_report.stats)
+    }
+
+    fn write_db_stats(&self, f: &mut dyn Write, stats: &DBStats) -> fmt::Result {
+        let size = |size: Option<u64>| match size {
+            Some(size) => self.size(size),
+            None => self.text(ReportText::Unknown),
+        };
+        writeln!(f, "{}", self.text(ReportText::DatabaseStorage))?;
+        writeln!(f, "- {}: {}", self.text(ReportText::FileSize), self.size(stats.file_size))?;
+        writeln!(f, "- {}: {}", self.text(ReportText::LastImport), stats.last_import.clone().unwrap_or_else(|| self.text(ReportText::Unknown)))?;
+        writeln!(f, "- {}:", self.text(ReportText::Tables))?;
+        for table in &stats.tables {
+            writeln!(f, "  - {}: {} {}, {}", table.name, self.count(table.rows), self.text(ReportText::Rows), size(table.size))?;
+        }
+        writeln!(f, "- {}:", self.text(ReportText::Indexes))?;
+        for index in &stats.indexes {
+            writeln!(f, "  - {} ({}): {}", index.name, index.table, size(index.size))?;
+        }
+        Ok(())
+    }
+
+    fn write_dump_quality(&self, f: &mut dyn Write, dump_quality: &DumpQuality) -> fmt::Result {
+        writeln!(f, "{}: {} / {}: {} / {}: {} / {}: {}",
+            self.text(ReportText::DumpsVerified), dump_quality.verified,
+            self.text(ReportText::DumpsGood), dump_quality.good,
+            self.text(ReportText::BadDumps), dump_quality.bad_dump,
+            self.text(ReportText::NoDumps), dump_quality.no_dump)
+    }
+
+    fn write_dump_quality_report(&self, f: &mut dyn Write, report: &DumpQualityReport) -> fmt::Result {
+        write!(f, "{}: ", self.text(ReportText::DumpQuality))?;
+        self.write_dump_quality(f, &report.database)?;
+        for set in &report.sets {
+            write!(f, "- {}: ", set.name)?;
+            self.write_dump_quality(f, &set.dump_quality)?;
+        }
+        Ok(())
+    }
+
+    fn write_dedupe_stats(&self, f: &mut dyn Write, stats: &DedupeStats) -> fmt::Result {
+        writeln!(f, "{}", self.text(ReportText::RomBytesByMode))?;
+        writeln!(f, "- {}: {}", self.text(ReportText::UniqueRoms), self.size(stats.unique_bytes))?;
+        for mode in &stats.modes {
+            writeln!(f, "- {}: {}, {} {}, {} {}", mode.rom_mode, self.size(mode.bytes),
+                self.size(mode.duplicated), self.text(ReportText::Duplicated), self.size(mode.saved), self.text(ReportText::Saved))?;
+        }
+        Ok(())
+    }
+
+    fn write_sizes_report(&self, f: &mut dyn Write, report: &SizesReport) -> fmt::Result {
+        writeln!(f, "{} {} ({}: {})", report.order, self.text(ReportText::SetsBySize), self.text(ReportText::Mode), report.rom_mode)?;
+        for set in &report.sets {
+            writeln!(f, "- {}: {}, {} {}", set.name, self.size(set.bytes), set.roms, self.text(ReportText::RomCount))?;
+        }
+        writeln!(f, "{} {}", report.order, self.text(ReportText::RomsBySize))?;
+        for rom in &report.roms {
+            writeln!(f, "- {}/{}: {}, {} {}", rom.set_name, rom.name, self.size(rom.bytes), rom.sets, self.text(ReportText::SetCount))?;
+        }
+        Ok(())
+    }
+
+    fn write_mode_overrides(&self, f: &mut dyn Write, overrides: &SetModeOverrides) -> fmt::Result {
+        if overrides.overrides.is_empty() {
+            return writeln!(f, "{}", self.text(ReportText::NoModeOverrides));
+        }
+        writeln!(f, "{}:", self.text(ReportText::ModeOverrides))?;
+        for (game_name, rom_mode) in &overrides.overrides {
+            writeln!(f, "- {}: {}", game_name, rom_mode)?;
+        }
+        Ok(())
+    }
+
+    fn write_ignored_roms(&self, f: &mut dyn Write, ignored_roms: &IgnoredRoms) -> fmt::Result {
+        if ignored_roms.is_empty() {
+            return writeln!(f, "{}", self.text(ReportText::NoIgnoredRoms));
+        }
+        writeln!(f, "{}:", self.text(ReportText::IgnoredRoms))?;
+        for sha1 in &ignored_roms.sha1s {
+            writeln!(f, "- {} ({})", sha1, self.text(ReportText::IgnoredEverywhere))?;
+        }
+        for (set_name, roms) in &ignored_roms.roms {
+            for rom_name in roms {
+                writeln!(f, "- {}/{}", set_name, rom_name)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_user_data(&self, f: &mut dyn Write, user_data: &SetUserData) -> fmt::Result {
+        if user_data.sets.is_empty() {
+            return writeln!(f, "{}", self.text(ReportText::NoTaggedSets));
+        }
+        writeln!(f, "{}:", self.text(ReportText::TaggedSets))?;
+        for (game_name, entry) in &user_data.sets {
+            writeln!(f, "- {}", game_name)?;
+            if !entry.tags.is_empty() {
+                writeln!(f, "  {}: {}", self.text(ReportText::Tags), entry.tags.iter().cloned().collect::<Vec<_>>().join(", "))?;
+            }
+            if let Some(note) = &entry.note {
+                writeln!(f, "  {}: {}", self.text(ReportText::Note), note)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_last_checks(&self, f: &mut dyn Write, last_checks: &[LastCheck]) -> fmt::Result {
+        if last_checks.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "{}:", self.text(ReportText::LastChecks))?;
+        for last_check in last_checks {
+            let root_directory = if last_check.root_directory.is_empty() { self.text(ReportText::SeveralDirectories) } else { last_check.root_directory.to_owned() };
+            writeln!(f, "- {}: {} ({})", root_directory, self.text((&last_check.status).into()), last_check.checked_at)?;
+        }
+        Ok(())
+    }
+
+    fn write_check_summary(&self, f: &mut dyn Write, summary: &CheckSummary) -> fmt::Result {
+        writeln!(f, "{} ({})", self.text(ReportText::CheckSummary), summary.date_time)?;
+        if let Some(root_directory) = &summary.root_directory {
+            writeln!(f, "{}: {}", self.text(ReportText::ScannedDir), root_directory)?;
+        }
+        writeln!(f, "{}: {} / {}: {} / {}: {}",
+            self.text(ReportText::StatusComplete), summary.complete,
+            self.text(ReportText::StatusFixeable), summary.fixeable,
+            self.text(ReportText::StatusIncomplete), summary.incomplete)?;
+        writeln!(f, "{}: {} / {}: {}",
+            self.text(ReportText::Ignored), summary.ignored,
+            self.text(ReportText::CorruptArchives), summary.corrupt)?;
+        if summary.roms_ignored > 0 {
+            writeln!(f, "{}: {}", self.text(ReportText::RomsIgnored), summary.roms_ignored)?;
+        }
+        if summary.unscanned > 0 {
+            writeln!(f, "{}: {}", self.text(ReportText::NotScanned), summary.unscanned)?;
+        }
+        if summary.newly_broken.is_empty() {
+            writeln!(f, "{}", self.text(ReportText::NoneNewlyBroken))?;
+        } else {
+            writeln!(f, "{}:", self.text(ReportText::NewlyBroken))?;
+            for set_name in &summary.newly_broken {
+                writeln!(f, "- {}", set_name)?;
+            }
+        }
+        if let Some(sample_audit) = &summary.sample_audit {
+            writeln!(f)?;
+            self.write_sample_audit(f, sample_audit)?;
+        }
+        Ok(())
+    }
+
+    fn write_sample_audit(&self, f: &mut dyn Write, sample_audit: &SampleAuditReport) -> fmt::Result {
+        writeln!(f, "{}: {}% ({}: {})", self.text(ReportText::SampleAudit), sample_audit.sample.ratio * 100.0,
+            self.text(ReportText::Seed), sample_audit.sample.seed)?;
+        writeln!(f, "{}: {}", self.text(ReportText::ArchivesScreened), self.count(sample_audit.screened as u64))?;
+        writeln!(f, "{}: {} ({}: {})", self.text(ReportText::ArchivesSampled), self.count(sample_audit.sampled as u64),
+            self.text(ReportText::DirectoryMatching), self.count(sample_audit.sampled_screenable as u64))?;
+        writeln!(f, "{}: {}", self.text(ReportText::SampleMismatches), self.count(sample_audit.mismatches.len() as u64))?;
+        for file in &sample_audit.mismatches {
+            writeln!(f, "- {}", file)?;
+        }
+        match &sample_audit.estimate {
+            Some(estimate) => {
+                writeln!(f, "{}: {:.2}% ({:.2}% - {:.2}%, {})", self.text(ReportText::MismatchRate),
+                    estimate.rate * 100.0, estimate.rate_lower * 100.0, estimate.rate_upper * 100.0, self.text(ReportText::Confidence))?;
+                writeln!(f, "{}: {} ({} {})", self.text(ReportText::ExpectedMismatches), self.count(estimate.screened_expected),
+                    self.text(ReportText::AtMost), self.count(estimate.screened_at_most))
+            },
+            None => writeln!(f, "{}", self.text(ReportText::NoEstimate)),
+        }
+    }
+
+    fn write_missing_sets(&self, f: &mut dyn Write, missing_sets: &MissingSets) -> fmt::Result {
+        writeln!(f, "{}: {}", self.text(ReportText::DateOfReport), missing_sets.date_time)?;
+        writeln!(f, "{}: {}", self.text(ReportText::SetsInScope), missing_sets.in_scope)?;
+        writeln!(f, "{}: {} / {}: {} / {}: {}",
+            self.text(ReportText::StatusComplete), missing_sets.complete,
+            self.text(ReportText::StatusFixeable), missing_sets.fixeable,
+            self.text(ReportText::StatusIncomplete), missing_sets.incomplete)?;
+        if missing_sets.unscanned > 0 {
+            writeln!(f, "{}: {}", self.text(ReportText::NotScannedSets), missing_sets.unscanned)?;
+        }
+        if missing_sets.not_found.is_empty() {
+            return writeln!(f, "{}", self.text(ReportText::NoSetsNotFound));
+        }
+        writeln!(f, "{}: {}", self.text(ReportText::SetsNotFound), missing_sets.not_found.len())?;
+        for set_name in &missing_sets.not_found {
+            writeln!(f, "- {}", set_name)?;
+        }
+        Ok(())
+    }
+
+    fn write_import_report(&self, f: &mut dyn Write, report: &ImportReport) -> fmt::Result {
+        writeln!(f, "{}: {}", self.text(ReportText::ImportedEntries), report.entries)?;
+        if report.excluded > 0 {
+            writeln!(f, "{}: {}", self.text(ReportText::ExcludedGames), report.excluded)?;
+        }
+        if let Some(vacuum) = &report.vacuum {
+            writeln!(f, "{}: {}", self.text(ReportText::ReclaimedSpace), self.size(vacuum.get_reclaimed()))?;
+        }
+        if let Some(references) = &report.references {
+            write!(f, "{}", references)?;
+        }
+        writeln!(f, "{}: {}, {}: {}", self.text(ReportText::ImportWarnings), report.warnings, self.text(ReportText::ImportErrors), report.errors)?;
+        for issue in &report.issues {
+            let level = match issue.level {
+                ImportIssueLevel::Warning => Style::new().yellow().apply_to(self.text(ReportText::ImportWarning)),
+                ImportIssueLevel::Error => Style::new().red().apply_to(self.text(ReportText::ImportError)),
+            };
+            match issue.position {
+                Some(position) => writeln!(f, "- [{}] {} {}: {}", level, self.text(ReportText::Position), position, issue.message)?,
+                None => writeln!(f, "- [{}] {}", level, issue.message)?,
+            }
+            writeln!(f, "  {}", issue.snippet)?;
+        }
+        let not_shown = (report.warnings + report.errors) as usize - report.issues.len();
+        if not_shown > 0 {
+            writeln!(f, "{}: {}", self.text(ReportText::IssuesNotShown), not_shown)?;
+        }
+        Ok(())
+    }
+
+    fn write_multi_import_report(&self, f: &mut dyn Write, report: &MultiImportReport) -> fmt::Result {
+        writeln!(f, "{}: {}", self.text(ReportText::ImportedDats), report.dats.len())?;
+        for dat in &report.dats {
+            writeln!(f)?;
+            writeln!(f, "{}: {}", self.text(ReportText::DatFile), Style::new().bold().apply_to(&dat.source))?;
+            self.write_import_report(f, &dat.report)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{}: {}", self.text(ReportText::ImportedEntries), report.get_entries())?;
+        if report.excluded > 0 {
+            writeln!(f, "{}: {}", self.text(ReportText::ExcludedGames), report.excluded)?;
+        }
+        if let Some(vacuum) = &report.vacuum {
+            writeln!(f, "{}: {}", self.text(ReportText::ReclaimedSpace), self.size(vacuum.get_reclaimed()))?;
+        }
+        if let Some(references) = &report.references {
+            write!(f, "{}", references)?;
+        }
+        Ok(())
+    }
+}
+
+/// The formatter used by the `Display` implementations of the reports
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EnglishFormatter;
+
+impl ReportFormatter for EnglishFormatter {}
+
+fn english_text(text: ReportText) -> &'static str {
+    match text {
+        ReportText::ScannedDir => "Scanned dir",
+        ReportText::DateOfReport => "Date of the report",
+        ReportText::Mode => "Mode",
+        ReportText::MatchedBy => "Matched by",
+        ReportText::WeakMatching => "WARNING: the roms were matched only by their crc and size, a different rom with the same ones is taken as the right one",
+        ReportText::Ignored => "Ignored",
+        ReportText::NotScanned => "Not scanned, the check was interrupted",
+        ReportText::FileErrors => "Files that couldn't be read",
+        ReportText::TransientError => "transient, retry it",
+        ReportText::SampleAudit => "Sample audit, zips hashed",
+        ReportText::Seed => "seed",
+        ReportText::ArchivesScreened => "Zips accepted by their directory",
+        ReportText::ArchivesSampled => "Zips sampled",
+        ReportText::DirectoryMatching => "with their directory matching the DAT",
+        ReportText::SampleMismatches => "Zips sampled not matching their directory",
+        ReportText::MismatchRate => "Mismatch rate",
+        ReportText::Confidence => "95% confidence",
+        ReportText::ExpectedMismatches => "Zips accepted expected not to match",
+        ReportText::AtMost => "at most",
+        ReportText::NoEstimate => "No estimate, no zip sampled has its directory matching the DAT",
+        ReportText::PermanentError => "permanent",
+        ReportText::SplitArchives => "Split archives (need to be joined)",
+        ReportText::WrongPacking => "Not packed as the DAT expects",
+        ReportText::AliasRenames => "Named as an alias of the set (rename)",
+        ReportText::Set => "Set",
+        ReportText::FileName => "File name",
+        ReportText::Status => "Status",
+        ReportText::InFile => "in file",
+        ReportText::DependsOn => "Depends on",
+        ReportText::RomsAvailable => "Roms Available",
+        ReportText::RenameFrom => "rename from",
+        ReportText::LocatedAt => "located at",
+        ReportText::LocatedAs => "as",
+        ReportText::RomsUnneeded => "Roms Unneeded (e.g. Bad Dumps)",
+        ReportText::RomsMissing => "Roms Missing",
+        ReportText::RomsToSpare => "Roms to Spare",
+        ReportText::DisksAvailable => "Disks Available",
+        ReportText::DisksMissing => "Disks Missing",
+        ReportText::StatusComplete => "Complete",
+        ReportText::StatusFixeable => "Fixeable",
+        ReportText::StatusIncomplete => "Incomplete",
+        ReportText::Roms => "Roms",
+        ReportText::UnknownFiles => "Unkown files",
+        ReportText::DatabaseInfo => "Database info",
+        ReportText::Games => "Games",
+        ReportText::RomsInGames => "Roms in Games",
+        ReportText::Samples => "Samples",
+        ReportText::DeviceReferences => "Device References",
+        ReportText::NonCanonicalArchives => "Non canonical archives (need to be rezipped)",
+        ReportText::CorruptArchives => "Corrupt archives",
+        ReportText::DumpQuality => "Dump quality",
+        ReportText::DatabaseStorage => "Database storage",
+        ReportText::FileSize => "File size",
+        ReportText::LastImport => "Last import",
+        ReportText::Unknown => "unknown",
+        ReportText::Tables => "Tables",
+        ReportText::Indexes => "Indexes",
+        ReportText::Rows => "rows",
+        ReportText::DumpsVerified => "Verified",
+        ReportText::DumpsGood => "Good",
+        ReportText::BadDumps => "Bad dumps",
+        ReportText::NoDumps => "No dumps",
+        ReportText::ModeOverrides => "Sets with a forced mode",
+        ReportText::NoModeOverrides => "No sets with a forced mode",
+        ReportText::RomBytesByMode => "Rom bytes by set mode",
+        ReportText::UniqueRoms => "Unique roms",
+        ReportText::Duplicated => "duplicated",
+        ReportText::Saved => "saved",
+        ReportText::SetsBySize => "sets by rom bytes",
+        ReportText::RomsBySize => "roms by bytes",
+        ReportText::SetCount => "sets",
+        ReportText::RomCount => "roms",
+        ReportText::TaggedSets => "Sets with tags or notes",
+        ReportText::NoTaggedSets => "No sets with tags or notes",
+        ReportText::Tags => "Tags",
+        ReportText::Note => "Note",
+        ReportText::ImportedEntries => "Imported entries",
+        ReportText::ImportedDats => "Imported DATs",
+        ReportText::DatFile => "DAT file",
+        ReportText::ExcludedGames => "Games excluded by the filter",
+        ReportText::ReclaimedSpace => "Space reclaimed compacting the database",
+        ReportText::ImportWarnings => "Warnings",
+        ReportText::ImportErrors => "Errors",
+        ReportText::ImportWarning => "Warning",
+        ReportText::ImportError => "Error",
+        ReportText::Position => "position",
+        ReportText::IssuesNotShown => "Issues not shown",
+        ReportText::CheckSummary => "Check summary",
+        ReportText::NewlyBroken => "Broken since the last check",
+        ReportText::NoneNewlyBroken => "No sets broken since the last check",
+        ReportText::RomsFound => "Roms found",
+        ReportText::NoRomsFound => "No roms found",
+        ReportText::NotSetCount => "Not used by any set",
+        ReportText::SharedWith => "Roms shared with",
+        ReportText::NoSharedRoms => "No other set has its roms",
+        ReportText::LastChecks => "Last checks",
+        ReportText::SeveralDirectories => "several directories",
+        ReportText::SetsInScope => "Sets in scope",
+        ReportText::NotScannedSets => "Not scanned",
+        ReportText::SetsNotFound => "Sets not found",
+        ReportText::NoSetsNotFound => "All the sets in scope were found",
+        ReportText::RomsIgnored => "Roms ignored",
+        ReportText::IgnoredRoms => "Ignored roms",
+        ReportText::NoIgnoredRoms => "No roms are ignored",
+        ReportText::IgnoredEverywhere => "in any set",
+        ReportText::CheckedAt => "Checked at",
+        ReportText::NotInCheck => "Not found",
+        ReportText::RomsInCollection => "Roms in the collection",
+        ReportText::RomInSet => "in the set",
+        ReportText::RomToRename => "in the set, to rename from",
+        ReportText::RomInOtherFile => "in another file",
+        ReportText::RomMissing => "missing",
+        ReportText::RomIgnored => "missing, ignored",
+        ReportText::RomUnneeded => "not needed",
+        ReportText::RomNotInReport => "not in the check",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RomsetMode;
+    use super::*;
+
+    struct SpanishFormatter;
+
+    impl ReportFormatter for SpanishFormatter {
+        fn text(&self, text: ReportText) -> String {
+            match text {
+                ReportText::Mode => "Modo".to_string(),
+                ReportText::DateOfReport => "Fecha del reporte".to_string(),
+                t => english_text(t).to_string(),
+            }
+        }
+    }
+
+    #[test]
+    fn display_uses_english_formatter() {
+        let report = ScanReport::new(None, RomsetMode::Split);
+        let mut output = String::new();
+        EnglishFormatter.write_scan_report(&mut output, &report).unwrap();
+
+        assert_eq!(output, format!("{}", report));
+        assert!(output.contains("Mode: Split"));
+    }
+
+    #[test]
+    fn formatter_replaces_texts() {
+        let report = ScanReport::new(None, RomsetMode::Split);
+        let mut output = String::new();
+        SpanishFormatter.write_scan_report(&mut output, &report).unwrap();
+
+        assert!(output.contains("Modo: Split"));
+        assert!(output.contains("Fecha del reporte: "));
+    }
+}
@@ -0,0 +1,188 @@
+use std::{fmt::{self, Write}, str::FromStr};
+
+use anyhow::Result;
+
+use crate::{data::models::file::DataFile, err, error::RomstError};
+
+use super::scan_report::{ScanReport, SetReference, SetReport};
+
+/// Formats of the missing roms lists that other rom managers can import
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissListFormat {
+    /// A fix DAT in the clrmamepro DAT format
+    ClrMamePro,
+    /// A csv file with a row per missing rom, as RomCenter miss lists
+    RomCenter,
+}
+
+impl FromStr for MissListFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "clrmamepro" | "cmp" => Ok(MissListFormat::ClrMamePro),
+            "romcenter" | "rc" => Ok(MissListFormat::RomCenter),
+            _ => err!(RomstError::GenericError { message: format!("Unknown miss list format `{}`, can be either `clrmamepro` or `romcenter`", s) }),
+        }
+    }
+}
+
+impl fmt::Display for MissListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissListFormat::ClrMamePro => write!(f, "clrmamepro"),
+            MissListFormat::RomCenter => write!(f, "romcenter"),
+        }
+    }
+}
+
+/// Writes the missing roms of the report, the sets and roms are sorted by name and sets without missing roms are left out
+pub fn write_miss_list(f: &mut dyn Write, report: &ScanReport, format: MissListFormat) -> fmt::Result {
+    let sets = get_sets_with_missing_roms(report);
+    match format {
+        MissListFormat::ClrMamePro => write_clrmamepro(f, report, sets),
+        MissListFormat::RomCenter => write_romcenter(f, sets),
+    }
+}
+
+fn get_sets_with_missing_roms(report: &ScanReport) -> Vec<(&SetReport, Vec<&DataFile>)> {
+    let mut sets = report.sets.values()
+        .filter(|set| !set.roms_missing.is_empty())
+        .map(|set| {
+            let mut roms = set.roms_missing.iter().collect::<Vec<_>>();
+            roms.sort();
+            (set, roms)
+        })
+        .collect::<Vec<_>>();
+    sets.sort_by(|a, b| a.0.reference.get_name().cmp(b.0.reference.get_name()));
+    sets
+}
+
+fn get_description(set: &SetReport) -> Option<&str> {
+    match &set.reference {
+        SetReference::Game(game) => game.info_description.as_deref(),
+        SetReference::FileName(_) => None,
+    }
+}
+
+fn write_clrmamepro(f: &mut dyn Write, report: &ScanReport, sets: Vec<(&SetReport, Vec<&DataFile>)>) -> fmt::Result {
+    writeln!(f, "clrmamepro (")?;
+    writeln!(f, "\tname \"romst fix list\"")?;
+    writeln!(f, "\tdescription \"Missing roms from the scan of {}\"", report.get_date_time_string())?;
+    writeln!(f, ")")?;
+
+    for (set, roms) in sets {
+        writeln!(f)?;
+        writeln!(f, "game (")?;
+        writeln!(f, "\tname {}", quote_clrmamepro(set.reference.get_name()))?;
+        if let Some(description) = get_description(set) {
+            writeln!(f, "\tdescription {}", quote_clrmamepro(description))?;
+        }
+        for rom in roms {
+            write!(f, "\trom ( name {}", quote_clrmamepro(&rom.name))?;
+            if let Some(size) = rom.info.size {
+                write!(f, " size {}", size)?;
+            }
+            if let Some(crc) = &rom.info.crc {
+                write!(f, " crc {}", crc)?;
+            }
+            if let Some(md5) = &rom.info.md5 {
+                write!(f, " md5 {}", md5)?;
+            }
+            if let Some(sha1) = &rom.info.sha1 {
+                write!(f, " sha1 {}", sha1)?;
+            }
+            writeln!(f, " )")?;
+        }
+        writeln!(f, ")")?;
+    }
+
+    Ok(())
+}
+
+/// Names with spaces or parenthesis need quotes in the clrmamepro format
+fn quote_clrmamepro(value: &str) -> String {
+    if value.is_empty() || value.contains([' ', '\t', '(', ')', '"']) {
+        format!("\"{}\"", value.replace('"', "'"))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_romcenter(f: &mut dyn Write, sets: Vec<(&SetReport, Vec<&DataFile>)>) -> fmt::Result {
+    writeln!(f, "Game,Description,Rom,Size,CRC32,MD5,SHA1")?;
+    for (set, roms) in sets {
+        let description = get_description(set).unwrap_or_default();
+        for rom in roms {
+            let size = rom.info.size.map(|size| size.to_string()).unwrap_or_default();
+            let fields = [set.reference.get_name(), description, &rom.name, &size,
+                rom.info.crc.as_deref().unwrap_or_default(), rom.info.md5.as_deref().unwrap_or_default(), rom.info.sha1.as_deref().unwrap_or_default()];
+            writeln!(f, "{}", fields.iter().map(|field| quote_csv(field)).collect::<Vec<_>>().join(","))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn quote_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RomsetMode, data::models::{file::{DataFileInfo, FileType}, game::Game}};
+    use super::*;
+
+    fn get_rom(name: &str, crc: &str, size: u32) -> DataFile {
+        let mut info = DataFileInfo::new(FileType::Rom);
+        info.crc = Some(crc.to_string());
+        info.size = Some(size);
+        DataFile::new(name, info)
+    }
+
+    fn get_report() -> ScanReport {
+        let mut report = ScanReport::new(None, RomsetMode::NonMerged);
+        let mut game = Game::new("game2".to_string());
+        game.info_description = Some("Game 2, \"the sequel\"".to_string());
+        report.reference_with_game(game);
+        report.add_missing_roms_for_set("game2", vec![get_rom("rom b.bin", "22222222", 2048), get_rom("rom_a.bin", "11111111", 1024)]);
+        report.add_missing_rom_for_set("game1", get_rom("rom1.bin", "33333333", 512));
+        report.set_in_file("game3.zip");
+        report
+    }
+
+    #[test]
+    fn writes_clrmamepro_fix_list() -> Result<()> {
+        let mut output = String::new();
+        write_miss_list(&mut output, &get_report(), MissListFormat::ClrMamePro)?;
+
+        let games = output.split("\n\n").skip(1).collect::<Vec<_>>();
+        assert_eq!(2, games.len());
+        assert_eq!("game (\n\tname game1\n\trom ( name rom1.bin size 512 crc 33333333 )\n)", games[0]);
+        assert_eq!("game (\n\tname game2\n\tdescription \"Game 2, 'the sequel'\"\n\trom ( name \"rom b.bin\" size 2048 crc 22222222 )\n\trom ( name rom_a.bin size 1024 crc 11111111 )\n)\n", games[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn writes_romcenter_csv() -> Result<()> {
+        let mut output = String::new();
+        write_miss_list(&mut output, &get_report(), MissListFormat::RomCenter)?;
+
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(vec![
+            "Game,Description,Rom,Size,CRC32,MD5,SHA1",
+            "game1,,rom1.bin,512,33333333,,",
+            "game2,\"Game 2, \"\"the sequel\"\"\",rom b.bin,2048,22222222,,",
+            "game2,\"Game 2, \"\"the sequel\"\"\",rom_a.bin,1024,11111111,,",
+        ], lines);
+        assert!("romcenter".parse::<MissListFormat>().is_ok());
+        assert!("mame".parse::<MissListFormat>().is_err());
+
+        Ok(())
+    }
+}
@@ -1,23 +1,72 @@
 pub mod scan_report;
+pub mod formatter;
+pub mod mode_detection;
+pub mod miss_list;
+pub mod summary;
+pub mod diff;
+pub mod report_file;
+pub mod ownership;
+pub mod sample_audit;
 
-use std::{fs, path::{Path, PathBuf}};
-use crate::{RomsetMode, err, error::RomstIOError, filesystem::FileReader};
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, fs, io::{self, BufRead}, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, Ordering}}, thread};
+use crate::{EntryPaths, IoRetry, LooseFiles, RomsetMode, ScanOptions, err, error::{RomstError, RomstIOError}, filesystem::{self, FileChecks, FileReader, glob, paths, throttle::{self, Throttle}}};
 
 
-use super::{models::{self, set::GameSet}, reader::DataReader};
+use super::{models::{self, archive::{ArchiveInfo, ArchiveIssue}, dat_info::Packing, file::{DataFile, FileType}, game::Game, set::GameSet}, reader::DataReader};
 use anyhow::Result;
 use crossbeam::sync::WaitGroup;
 
-use scan_report::{RomLocation, ScanReport};
-use tokio::sync::mpsc::{Receiver, channel};
-use log::error;
+use mode_detection::ModeDetection;
+use sample_audit::{AuditOutcome, SampleAuditReport};
+use scan_report::{FileError, FileErrorKind, FileKind, RomLocation, ScanReport, SetReport, SetStatus};
+use tokio::sync::mpsc::{Receiver, UnboundedSender, channel};
+use log::{error, warn};
 
 
-type RR = Option<Box<dyn ReportReporter>>;
-    
+type RR = Option<Box<dyn ReportReporter + Send>>;
+type SL = Option<Box<dyn SetReportListener + Send>>;
+/// The loose files grouped by the name of the set they are assembled into
+type LooseSets = BTreeMap<String, Vec<PathBuf>>;
+
 pub struct Reporter<R: DataReader> {
     data_reader: R,
-    reporter: RR
+    reporter: RR,
+    set_listener: SL,
+    options: ScanOptions,
+    cancellation: CancellationToken,
+}
+
+/// Stops a scan, the files being read are finished and the rest are reported as not scanned
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Cancels the scan when Ctrl+C is pressed, a second Ctrl+C exits right away
+    pub fn cancel_on_ctrl_c(&self) -> Result<()> {
+        let token = self.clone();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        thread::spawn(move || runtime.block_on(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                warn!("Interrupted, finishing the files being read, press Ctrl+C again to exit now");
+                token.cancel();
+            }
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }));
+
+        Ok(())
+    }
 }
 
 pub trait ReportReporter {
@@ -27,38 +76,168 @@ pub trait ReportReporter {
     fn update_report_directory(&mut self, new_files: usize);
     fn update_report_ignored(&mut self, new_files: usize);
     fn update_report_file_error(&mut self, new_files: usize);
+    /// A set is found for the first time in the scan
+    fn set_matched(&mut self, set_name: &str);
+    /// The status of a set changed with the last file processed, `previous` is the status reported before, if any
+    fn set_completed(&mut self, set_name: &str, status: SetStatus, previous: Option<SetStatus>);
     fn finish(&mut self);
 }
 
+/// Receives the set reports during a scan, each time a file that updates a set is processed.
+/// The same set can be received several times, the last one received is the most up to date.
+pub trait SetReportListener {
+    fn set_report_updated(&mut self, file_name: &str, set_report: &SetReport);
+}
+
+impl<F> SetReportListener for F where F: FnMut(&str, &SetReport) {
+    fn set_report_updated(&mut self, file_name: &str, set_report: &SetReport) {
+        self(file_name, set_report)
+    }
+}
+
+/// Keeps the last report received of each set, the sets of the report of a check are collected from the stream with it
+#[derive(Debug, Default)]
+pub struct SetReportCollector {
+    sets: HashMap<String, SetReport>,
+}
+
+impl SetReportCollector {
+    pub fn into_sets(self) -> HashMap<String, SetReport> {
+        self.sets
+    }
+}
+
+impl SetReportListener for SetReportCollector {
+    fn set_report_updated(&mut self, _file_name: &str, set_report: &SetReport) {
+        self.sets.insert(set_report.reference.get_name().to_string(), set_report.clone());
+    }
+}
+
+/// A set report sent through a channel, wrap the receiver in a `tokio_stream::wrappers::UnboundedReceiverStream` to get a Stream
+#[derive(Debug, Clone)]
+pub struct SetReportUpdate {
+    pub file_name: String,
+    pub set_report: SetReport,
+}
+
+impl SetReportListener for UnboundedSender<SetReportUpdate> {
+    fn set_report_updated(&mut self, file_name: &str, set_report: &SetReport) {
+        let update = SetReportUpdate { file_name: file_name.to_string(), set_report: set_report.clone() };
+        if let Err(e) = self.send(update) {
+            error!("ERROR sending the set report: {}", e);
+        }
+    }
+}
+
 enum ReportMessageContent {
-    GameSetBuilt(GameSet),
+    GameSetBuilt(Box<GameSet>, FileKind, Option<ArchiveInfo>),
     FoundNotValid,
-    FoundError,
+    FoundError(FileError),
+    /// The parts of a zip split in several files, they can't be read until they are joined
+    FoundSplitArchive(Vec<String>),
+    /// The scan was cancelled before the file was read
+    NotScanned,
     Done
 }
 
 struct ReportMessage {
     file_name: String, 
-    content: ReportMessageContent
+    content: ReportMessageContent,
+    archive_issues: Vec<ArchiveIssue>,
+    /// Only for the zips named as a set when the check is a sample audit
+    audit: Option<AuditOutcome>,
 }
 
 impl ReportMessage {
-    fn new(file_name: String, content: ReportMessageContent) -> Self { Self { file_name, content } }
+    fn new(file_name: String, content: ReportMessageContent) -> Self { Self { file_name, content, archive_issues: vec![], audit: None } }
+
+    fn with_archive_issues(mut self, archive_issues: Vec<ArchiveIssue>) -> Self {
+        self.archive_issues = archive_issues;
+        self
+    }
+
+    fn with_audit(mut self, audit: Option<AuditOutcome>) -> Self {
+        self.audit = audit;
+        self
+    }
 }
 
 impl<R: DataReader> Reporter<R> {
-    pub fn new(data_reader: R) -> Self { Self { data_reader, reporter: None } }
+    pub fn new(data_reader: R) -> Self { Self { data_reader, reporter: None, set_listener: None, options: ScanOptions::default(), cancellation: CancellationToken::default() } }
+
+    /// Cancelling the token stops the scan, the report only has the files read until then
+    pub fn set_cancellation_token(&mut self, cancellation: CancellationToken) {
+        self.cancellation = cancellation;
+    }
 
-    pub fn add_reporter<P>(&mut self, reporter: P) where P: ReportReporter + 'static {
+    pub fn add_reporter<P>(&mut self, reporter: P) where P: ReportReporter + Send + 'static {
         self.reporter = Some(Box::new(reporter));
     }
 
-    pub async fn check(&mut self, file_paths: Vec<impl AsRef<Path>>, rom_mode: RomsetMode) -> Result<ScanReport> {
+    pub fn add_set_listener<L>(&mut self, listener: L) where L: SetReportListener + Send + 'static {
+        self.set_listener = Some(Box::new(listener));
+    }
+
+    /// Checks a single archive against a single set, comparing the roms in the archive with the roms of the set only.
+    /// The roms in the archive that are not in the set are reported to spare
+    pub fn check_set(&mut self, set_name: &str, file_path: &impl AsRef<Path>, options: ScanOptions) -> Result<SetReport> {
+        self.options = options;
+        let rom_mode = self.options.rom_mode;
+        let game = match self.data_reader.get_game(set_name) {
+            Some(game) => game,
+            None => return err!(RomstError::GenericError { message: format!("Game {} not found", set_name) }),
+        };
+        let file_path = file_path.as_ref();
+        let file_name = match file_path.file_name() {
+            Some(file_name) => paths::os_str_to_key(file_name),
+            None => return err!(RomstError::GenericError { message: format!("`{}` is not a file", file_path.to_string_lossy()) }),
+        };
+
+        let file_checks = self.data_reader.get_file_checks()?.get_file_checks();
+        let file_roms = FileReader::new().build_game_set(&file_path, file_checks)?.roms;
+        let set_mode = self.data_reader.get_mode_override(set_name)?.unwrap_or(rom_mode);
+        let set_roms = self.data_reader.get_romset_roms(set_name, set_mode)?;
+
+        let mut scan_report = ScanReport::new(None, set_mode);
+        scan_report.set_set_aliases(self.options.set_aliases.clone());
+        scan_report.set_ignored_roms(self.data_reader.get_ignored_roms()?);
+        scan_report.reference_with_game(game);
+        scan_report.add_dependencies(set_name, self.data_reader.get_devices_for_game(set_name)?.dependencies);
+        if self.options.set_aliases.does_file_belong_to_set(&file_name, set_name) {
+            scan_report.set_in_file(&file_name);
+        }
+
+        let shared_file_name = scan_report.intern_file_name(&file_name);
+        let same = |a: &DataFile, b: &DataFile| a.info.deep_compare(&b.info, file_checks).unwrap_or(false);
+        for set_rom in &set_roms {
+            let is_dump = !matches!(&set_rom.file.status, Some(status) if status.to_lowercase() == "nodump");
+            match file_roms.iter().find(|file_rom| is_dump && same(file_rom, &set_rom.file)) {
+                Some(file_rom) => {
+                    let location = self.get_rom_location(&shared_file_name, set_name, &file_rom.name, &set_rom.file.name);
+                    scan_report.add_rom_for_set(set_name, location, set_rom.file.to_owned());
+                }
+                None if self.options.bios.is_required(&set_rom.file) => scan_report.add_missing_rom_for_set(set_name, set_rom.file.to_owned()),
+                None => {}
+            }
+        }
+
+        let mut set_report = scan_report.sets.remove(set_name).unwrap_or_else(|| SetReport::new(set_name));
+        set_report.roms_to_spare.extend(file_roms.into_iter().filter(|file_rom| {
+            !set_roms.iter().any(|set_rom| same(file_rom, &set_rom.file))
+        }));
+
+        Ok(set_report)
+    }
+
+    /// The paths can be glob patterns, like `roms/*.zip`, or a file with a path per line if prefixed with `@`
+    pub async fn check(&mut self, file_paths: Vec<impl AsRef<Path>>, options: ScanOptions) -> Result<ScanReport> {
+        self.options = options;
+        let rom_mode = self.options.rom_mode;
+        let file_paths = expand_sources(file_paths)?;
         if file_paths.len() == 1 {
-            if let Some(path) = file_paths.get(0) {
-                let p = path.as_ref();
-                if p.is_dir() {
-                    return self.check_directory(&p.to_path_buf(), rom_mode).await
+            if let Some(path) = file_paths.first() {
+                if path.is_dir() {
+                    return self.check_directory(path, rom_mode).await
                 }
             }
         }
@@ -69,7 +248,7 @@ impl<R: DataReader> Reporter<R> {
     async fn check_directory(&mut self, file_path: &impl AsRef<Path>, rom_mode: RomsetMode) -> Result<ScanReport> {
         let path = file_path.as_ref();
         if path.is_dir() {
-            let contents = path.read_dir()?.into_iter().filter_map(|dir_entry| {
+            let contents = path.read_dir()?.filter_map(|dir_entry| {
                 if let Ok(ref entry) = dir_entry {
                     let path = entry.path();
                     Some(path)
@@ -84,48 +263,118 @@ impl<R: DataReader> Reporter<R> {
     }
 
     /// Returns a Receiver that will receive a message with the file reports.
-    async fn send_sets_from_files(&mut self, file_paths: Vec<impl AsRef<Path>>) -> Result<Receiver<ReportMessage>> {
+    async fn send_sets_from_files(&mut self, file_paths: Vec<impl AsRef<Path>>, rom_mode: RomsetMode) -> Result<Receiver<ReportMessage>> {
+        let (file_paths, split_archives) = group_split_archives(file_paths);
+        let (file_paths, loose_sets) = self.group_loose_files(file_paths)?;
+        // An audit screens the zips not sampled as a quick check does
+        let quick_check_roms = if self.options.quick_check || self.options.sample.is_some() { self.get_quick_check_roms(&file_paths, rom_mode)? } else { HashMap::new() };
+        let sampled = Arc::new(match self.options.sample {
+            Some(sample) => sample.select(&quick_check_roms.keys().collect::<Vec<_>>()),
+            None => HashSet::new(),
+        });
+        let auditing = self.options.sample.is_some();
+        let quick_check_roms = Arc::new(quick_check_roms);
+        let total_files = file_paths.len() + split_archives.len() + loose_sets.len();
         if let Some(reporter) = self.reporter.as_mut() {
-            reporter.set_total_files(file_paths.len());
+            reporter.set_total_files(total_files);
         }
 
-        let (tx, receiver) = channel::<ReportMessage>(file_paths.len());
+        let (tx, receiver) = channel::<ReportMessage>(total_files);
         let wg = WaitGroup::new();
 
         let file_checks = self.data_reader.get_file_checks()?.get_file_checks();
+        let deep_verify = self.options.deep_verify;
+        let entry_paths = self.options.entry_paths;
+        let throttle = self.options.io_limits.get_bytes_per_second().map(Throttle::new);
+        let low_priority = self.options.io_limits.low_priority;
+        let io_retry = self.options.io_retry;
+        let cancellation = self.cancellation.clone();
+        if low_priority && !throttle::is_io_priority_supported() {
+            warn!("Lowering the IO priority is not supported in this system, the files are read with the normal priority");
+        }
 
         file_paths.into_iter()
             .for_each(|fp| {
-                let path = fp.as_ref();
-                if path.is_file() {
+                let path = fp.as_path();
+                if path.is_file() || filesystem::is_disk_folder(&path) {
                     let sender = tx.clone();
                     let p = path.to_path_buf();
                     let wg = wg.clone();
+                    let throttle = throttle.clone();
+                    let cancellation = cancellation.clone();
+                    let quick_check_roms = Arc::clone(&quick_check_roms);
+                    let sampled = Arc::clone(&sampled);
 
                     tokio::spawn(async move {
                         let file_name = match p.file_name() {
-                            Some(file) => {
-                                file.to_owned().into_string().unwrap_or_else(|os_string| {
-                                    os_string.to_string_lossy().to_string()
-                                })
-                            }
+                            Some(file) => paths::os_str_to_key(file),
                             None => { "UNKNOWN FILE".to_string() }
                         };
+                        if cancellation.is_cancelled() {
+                            if let Err(error) = sender.send(ReportMessage::new(file_name, ReportMessageContent::NotScanned)).await {
+                                error!("ERROR: {}", error);
+                            }
+                            drop(wg);
+                            return;
+                        }
 
-                        let mut file_reader = FileReader::new();
-                        let result = match file_reader.build_game_set(&p, file_checks) {
+                        let mut file_reader = new_file_reader(throttle, low_priority);
+                        let archive_issues = if deep_verify {
+                            file_reader.verify_archive(&p).unwrap_or_else(|e| {
+                                error!("ERROR verifying the archive: {}", e);
+                                vec![]
+                            })
+                        } else {
+                            vec![]
+                        };
+                        let screened = quick_check_roms.get(&p).and_then(|set_roms| {
+                            file_reader.read_zip_directory(&p).ok().and_then(|entries| screen_archive(entries, set_roms, entry_paths))
+                        });
+                        // The zips sampled are hashed whatever their directory has
+                        let is_sampled = sampled.contains(&p);
+                        let (screened, sampled_roms) = if is_sampled { (None, screened) } else { (screened, None) };
+                        let is_screened = screened.is_some();
+                        let game_set = match screened {
+                            Some(roms) => Ok(GameSet::new(Game::new(models::get_set_from_file(&file_name)), roms, vec![], vec![], vec![])),
+                            None => read_with_retries(io_retry, &file_name, || file_reader.build_game_set(&p, file_checks)).await,
+                        };
+                        let audit = if is_sampled {
+                            Some(AuditOutcome::from_sampled(sampled_roms.as_deref(), game_set.as_ref().ok().map(|game_set| game_set.roms.as_slice()), file_checks))
+                        } else if auditing && is_screened {
+                            Some(AuditOutcome::Screened)
+                        } else {
+                            None
+                        };
+                        let result = match game_set {
                             Ok(game_set) => {
+                                let archive_info = if p.is_dir() {
+                                    None
+                                } else {
+                                    match file_reader.get_archive_info(&p) {
+                                        Ok(archive_info) => Some(archive_info),
+                                        Err(e) => {
+                                            error!("ERROR reading the archive info: {}", e);
+                                            None
+                                        }
+                                    }
+                                };
+                                let kind = if p.is_dir() { FileKind::Folder } else { FileKind::Archive };
+                                sender.send(ReportMessage::new(file_name,
+                                    ReportMessageContent::GameSetBuilt(Box::new(game_set), kind, archive_info)).with_archive_issues(archive_issues).with_audit(audit)).await
+                            },
+                            Err(_) if filesystem::is_split_archive(&p).unwrap_or(false) => {
+                                let parts = vec![file_name.clone()];
                                 sender.send(ReportMessage::new(file_name,
-                                    ReportMessageContent::GameSetBuilt(game_set))).await
+                                    ReportMessageContent::FoundSplitArchive(parts)).with_archive_issues(archive_issues)).await
                             },
                             Err(RomstIOError::NotValidFileError(file_name, _file_type )) => {
                                 sender.send(ReportMessage::new(file_name,
-                                    ReportMessageContent::FoundNotValid)).await
+                                    ReportMessageContent::FoundNotValid).with_archive_issues(archive_issues).with_audit(audit)).await
                             },
                             Err(e) => {
                                 error!("ERROR: {}", e);
                                 sender.send(ReportMessage::new(file_name,
-                                    ReportMessageContent::FoundError)).await
+                                    ReportMessageContent::FoundError(get_file_error(e))).with_archive_issues(archive_issues).with_audit(audit)).await
                             }
                         };
 
@@ -139,6 +388,41 @@ impl<R: DataReader> Reporter<R> {
                     reporter.update_report_directory(1);
                 };
             });
+        for (archive, parts) in split_archives {
+            let sender = tx.clone();
+            let wg = wg.clone();
+            let file_name = paths::get_file_name_key(&archive);
+            tokio::spawn(async move {
+                if let Err(error) = sender.send(ReportMessage::new(file_name, ReportMessageContent::FoundSplitArchive(parts))).await {
+                    error!("ERROR: {}", error);
+                }
+                drop(wg);
+            });
+        }
+        for (set_name, paths) in loose_sets {
+            let sender = tx.clone();
+            let wg = wg.clone();
+            let throttle = throttle.clone();
+            let cancellation = cancellation.clone();
+            tokio::spawn(async move {
+                let mut file_reader = new_file_reader(throttle, low_priority);
+                let content = if cancellation.is_cancelled() {
+                    ReportMessageContent::NotScanned
+                } else {
+                    match read_with_retries(io_retry, &set_name, || file_reader.build_loose_game_set(&set_name, &paths, file_checks)).await {
+                        Ok(game_set) => ReportMessageContent::GameSetBuilt(Box::new(game_set), FileKind::Loose, None),
+                        Err(e) => {
+                            error!("ERROR: {}", e);
+                            ReportMessageContent::FoundError(get_file_error(e))
+                        }
+                    }
+                };
+                if let Err(error) = sender.send(ReportMessage::new(set_name, content)).await {
+                    error!("ERROR: {}", error);
+                }
+                drop(wg);
+            });
+        }
         let sender = tx;
         tokio::spawn(async move {
             wg.wait();
@@ -151,35 +435,152 @@ impl<R: DataReader> Reporter<R> {
         Ok(receiver)
     }
 
-    async fn check_files(&mut self, source_directory: Option<impl AsRef<Path>>, file_paths: Vec<impl AsRef<Path>>, rom_mode: RomsetMode) -> Result<ScanReport> {
-        let mut rx = self.send_sets_from_files(file_paths).await?;
+    /// The roms of the set each zip is named as, to screen the zips with a quick check. The zips not named as a set of
+    /// the DAT are left out, they are always hashed
+    fn get_quick_check_roms(&self, file_paths: &[PathBuf], rom_mode: RomsetMode) -> Result<HashMap<PathBuf, Vec<DataFile>>> {
+        let mut paths_by_set: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+        for path in file_paths.iter().filter(|path| path.is_file()) {
+            let file_name = paths::get_file_name_key(path);
+            let set_name = self.options.set_aliases.get_set_from_file(&file_name);
+            if self.options.set_aliases.does_file_belong_to_set(&file_name, &set_name) && file_name.to_lowercase().ends_with(".zip") {
+                paths_by_set.entry(set_name).or_default().push(path);
+            }
+        }
+
+        let mut sets_by_mode: BTreeMap<&str, (RomsetMode, Vec<String>)> = BTreeMap::new();
+        for set_name in paths_by_set.keys() {
+            if self.data_reader.get_game(set_name).is_none() {
+                continue;
+            }
+            let set_mode = self.data_reader.get_mode_override(set_name)?.unwrap_or(rom_mode);
+            sets_by_mode.entry(set_mode.get_key()).or_insert_with(|| (set_mode, vec![])).1.push(set_name.to_owned());
+        }
 
-        let source_dir = match source_directory {
-            Some(path) => {
-                let absolute = fs::canonicalize(path.as_ref())?;
-                Some(absolute.to_string_lossy().to_string())
+        let mut quick_check_roms = HashMap::new();
+        for (set_mode, set_names) in sets_by_mode.into_values() {
+            for (set_name, set_roms) in self.data_reader.get_romsets_roms(&set_names, set_mode)? {
+                let set_roms = set_roms.into_iter()
+                    .map(|set_rom| set_rom.file)
+                    .filter(|rom| !matches!(&rom.status, Some(status) if status.to_lowercase() == "nodump"))
+                    .collect::<Vec<_>>();
+                for path in paths_by_set.remove(&set_name).unwrap_or_default() {
+                    quick_check_roms.insert(path.to_owned(), set_roms.clone());
+                }
             }
-            None => {
-                None
+        }
+
+        Ok(quick_check_roms)
+    }
+
+    /// Takes out the loose files, grouped by the set they are assembled into as set in `loose_files`
+    fn group_loose_files(&self, file_paths: Vec<PathBuf>) -> Result<(Vec<PathBuf>, LooseSets)> {
+        let mut loose_sets = LooseSets::new();
+        if self.options.loose_files == LooseFiles::Ignore {
+            return Ok((file_paths, loose_sets));
+        }
+
+        let mut others = vec![];
+        for path in file_paths {
+            if !is_loose_file(&path) {
+                others.push(path);
+                continue;
             }
-        };
+            let basename = get_loose_file_basename(&path);
+            let set_name = match self.options.loose_files {
+                LooseFiles::Dat => {
+                    let file_name = paths::get_file_name_key(&path);
+                    let games = self.data_reader.get_games_with_rom_name(&file_name)?;
+                    match games.as_slice() {
+                        [game] if !games.contains(&basename) => game.to_owned(),
+                        _ => basename,
+                    }
+                },
+                _ => basename,
+            };
+            loose_sets.entry(set_name).or_default().push(path);
+        }
+        for paths in loose_sets.values_mut() {
+            paths.sort();
+        }
+
+        Ok((others, loose_sets))
+    }
+
+    async fn check_files(&mut self, source_directory: Option<impl AsRef<Path>>, file_paths: Vec<impl AsRef<Path>>, rom_mode: RomsetMode) -> Result<ScanReport> {
+        let mut rx = self.send_sets_from_files(file_paths, rom_mode).await?;
+
+        let source_dir = source_directory.map(paths::normalize_directory);
 
         let mut scan_report = ScanReport::new(source_dir, rom_mode);
+        scan_report.set_set_aliases(self.options.set_aliases.clone());
+        scan_report.set_ignored_roms(self.data_reader.get_ignored_roms()?);
+        let packing = self.data_reader.get_packing()?;
+        scan_report.set_packing(packing);
+        let file_checks = self.data_reader.get_file_checks()?.get_file_checks();
+        // The zips accepted by the quick check are matched by the crcs and sizes of their directory
+        scan_report.set_file_checks(if self.options.quick_check || self.options.sample.is_some() { file_checks & (FileChecks::CRC | FileChecks::SIZE) } else { file_checks });
+        if let Some(sample) = self.options.sample {
+            scan_report.sample_audit = Some(SampleAuditReport::new(sample));
+        }
+        // The last status reported of each set
+        let mut set_statuses: HashMap<String, SetStatus> = HashMap::new();
+        let mut collector = SetReportCollector::default();
 
         while let Some(message) = rx.recv().await {
             let file_name = message.file_name;
-            if !file_name.eq("") {
+            if !file_name.is_empty() {
                 if let Some(reporter) = self.reporter.as_mut() {
                     reporter.update_report_new_file(file_name.as_str());
                 };
             }
+            if !message.archive_issues.is_empty() {
+                scan_report.add_corrupt_archive(file_name.as_str(), message.archive_issues);
+            }
+            if let (Some(audit), Some(sample_audit)) = (message.audit, scan_report.sample_audit.as_mut()) {
+                sample_audit.add_outcome(file_name.as_str(), audit);
+            }
             match message.content {
-                ReportMessageContent::GameSetBuilt(file_game_set) => {
-                    match self.add_set_report(&mut scan_report, file_name, file_game_set, rom_mode).await {
-                        Ok(_) => {
+                ReportMessageContent::GameSetBuilt(file_game_set, kind, archive_info) => {
+                    // The disks are always in folders, so a folder with only disks is fine whatever the packing
+                    let has_roms = !file_game_set.roms.is_empty() || !file_game_set.samples.is_empty();
+                    if has_roms && packing.map(|packing| (packing == Packing::Zip) != archive_info.is_some()).unwrap_or(false) {
+                        scan_report.add_wrong_packing(file_name.as_str());
+                    }
+                    if let Some(archive_info) = archive_info {
+                        scan_report.add_archive_info(file_name.as_str(), archive_info);
+                    }
+                    let entries = file_game_set.roms.len() + file_game_set.samples.len() + file_game_set.disks.len();
+                    scan_report.add_scanned_file(file_name.as_str(), kind, entries);
+                    match self.add_set_report(&mut scan_report, file_name.clone(), *file_game_set, rom_mode).await {
+                        Ok(updated_sets) => {
                             if let Some(reporter) = self.reporter.as_mut() {
                                 reporter.update_report_new_added_file(1);
-                            };
+                            }
+                            // The stream of the sets updated by the file, the report is collected from it
+                            let game_filter = &self.options.game_filter;
+                            for set_name in &updated_sets {
+                                let set_report = match scan_report.sets.get(set_name) {
+                                    Some(set_report) => set_report,
+                                    None => continue,
+                                };
+                                collector.set_report_updated(&file_name, set_report);
+                                if !set_report.is_included(game_filter) {
+                                    continue;
+                                }
+                                if let Some(reporter) = self.reporter.as_mut() {
+                                    let status = set_report.is_complete();
+                                    let previous = set_statuses.insert(set_name.to_owned(), status);
+                                    if previous.is_none() {
+                                        reporter.set_matched(set_name);
+                                    }
+                                    if previous != Some(status) {
+                                        reporter.set_completed(set_name, status, previous);
+                                    }
+                                }
+                                if let Some(listener) = self.set_listener.as_mut() {
+                                    listener.set_report_updated(&file_name, set_report);
+                                }
+                            }
                         }
                         Err(_) => {
                             if let Some(reporter) = self.reporter.as_mut() {
@@ -194,64 +595,130 @@ impl<R: DataReader> Reporter<R> {
                         reporter.update_report_ignored(1);
                     };
                 }
-                ReportMessageContent::FoundError => {
+                ReportMessageContent::FoundError(file_error) => {
+                    scan_report.add_error(file_name, file_error);
                     if let Some(reporter) = self.reporter.as_mut() {
                         reporter.update_report_file_error(1);
                     };
                 },
+                ReportMessageContent::FoundSplitArchive(parts) => {
+                    scan_report.add_split_archive(file_name, parts);
+                    if let Some(reporter) = self.reporter.as_mut() {
+                        reporter.update_report_ignored(1);
+                    };
+                },
+                ReportMessageContent::NotScanned => {
+                    scan_report.add_unscanned(file_name);
+                },
                 ReportMessageContent::Done => {
                     break;
                 }
             }
         };
+        scan_report.unscanned.sort();
+        scan_report.wrong_packing.sort();
 
         if let Some(reporter) = self.reporter.as_mut() {
             reporter.finish();
         }
+        scan_report.sets = collector.into_sets();
+        scan_report.retain_sets(&self.options.game_filter);
         Ok(scan_report)
     }
 
-    async fn add_set_report(&mut self, scan_report: &mut ScanReport, file_name: String, file_game_set: GameSet, rom_mode: RomsetMode) -> Result<()> {
+    /// Where a rom of the set was found. If the entry is in the file of the set and matches the name of the rom, with
+    /// the paths compared as set in `entry_paths`, or named as the name policy writes the rom, it's located with the
+    /// name of the rom, so it's not renamed
+    fn get_rom_location(&self, file_name: &Arc<str>, set_name: &str, entry_name: &str, rom_name: &str) -> RomLocation {
+        let is_rom_name = self.options.entry_paths.is_same_name(entry_name, rom_name)
+            || self.options.name_policy.is_written_name(&entry_name.trim().replace('\\', "/"), rom_name);
+        if self.options.set_aliases.does_file_belong_to_set(file_name, set_name) && is_rom_name {
+            RomLocation::in_shared_file(Arc::clone(file_name), rom_name)
+        } else {
+            RomLocation::in_shared_file(Arc::clone(file_name), entry_name)
+        }
+    }
+
+    /// References the set with its game the first time it's found, its disks are missing until its folder is found
+    fn reference_set(&self, scan_report: &mut ScanReport, set_name: &str, rom_mode: RomsetMode) -> Result<()> {
+        if let Some(game) = self.data_reader.get_game(set_name) {
+            scan_report.reference_with_game(game);
+            let set_mode = self.data_reader.get_mode_override(set_name)?.unwrap_or(rom_mode);
+            scan_report.add_missing_disks_for_set(set_name, self.data_reader.get_romset_disks(set_name, set_mode)?);
+        }
+        Ok(())
+    }
+
+    /// The disks in a folder are matched by their sha1 with the disks of the set named as the folder only
+    fn add_disks_report(&self, scan_report: &mut ScanReport, folder_name: &str, disks: Vec<DataFile>, rom_mode: RomsetMode) -> Result<()> {
+        let set_name = self.options.set_aliases.get_set_from_file(folder_name);
+        if !scan_report.has_reference_with_game(&set_name) {
+            self.reference_set(scan_report, &set_name, rom_mode)?;
+        }
+
+        let set_mode = self.data_reader.get_mode_override(&set_name)?.unwrap_or(rom_mode);
+        let set_disks = self.data_reader.get_romset_disks(&set_name, set_mode)?;
+        let mut unknown = vec![];
+        for disk in disks {
+            match set_disks.iter().find(|set_disk| set_disk.info.sha1.is_some() && set_disk.info.sha1 == disk.info.sha1) {
+                Some(set_disk) => scan_report.add_disk_for_set(&set_name, set_disk.to_owned(), &disk.name),
+                None => unknown.push(disk),
+            }
+        }
+        scan_report.add_unknown_files(unknown, folder_name);
+
+        Ok(())
+    }
+
+    /// Returns the names of the sets updated with the file
+    async fn add_set_report(&mut self, scan_report: &mut ScanReport, file_name: String, file_game_set: GameSet, rom_mode: RomsetMode) -> Result<BTreeSet<String>> {
+        let GameSet { roms, disks, .. } = file_game_set;
         // We fetch all the sets that can be get from these roms
-        let rom_search = self.data_reader.get_romsets_from_roms(file_game_set.roms, rom_mode)?;
+        let rom_search = self.data_reader.get_romsets_from_roms(roms, rom_mode)?;
 
         scan_report.set_in_file(&file_name);
+        let shared_file_name = scan_report.intern_file_name(&file_name);
+
+        // We fetch all roms for the sets at once, grouped by mode as some sets are always checked with the same mode
+        let mut sets_by_mode: BTreeMap<&str, (RomsetMode, Vec<String>)> = BTreeMap::new();
+        for set_name in rom_search.set_results.keys() {
+            let set_mode = self.data_reader.get_mode_override(set_name)?.unwrap_or(rom_mode);
+            sets_by_mode.entry(set_mode.get_key()).or_insert_with(|| (set_mode, vec![])).1.push(set_name.to_owned());
+        }
+        let mut db_sets_roms = HashMap::new();
+        for (set_mode, set_names) in sets_by_mode.into_values() {
+            db_sets_roms.extend(self.data_reader.get_romsets_roms(&set_names, set_mode)?);
+        }
 
         let mut matched_file_name_with_set = false;
         for entry in &rom_search.set_results {
             let set_name = entry.0;
             let roms = entry.1;
 
-            // We fetch all roms for the set we are analyzing
-            let db_game_roms = self.data_reader.get_romset_roms(&set_name, rom_mode)?;
-            let db_roms = db_game_roms;
+            let db_roms = db_sets_roms.remove(set_name).unwrap_or_default();
 
-            let deps = self.data_reader.get_devices_for_game(&set_name)?;
-            scan_report.add_dependencies(&set_name, deps.dependencies);
+            let deps = self.data_reader.get_devices_for_game(set_name)?;
+            scan_report.add_dependencies(set_name, deps.dependencies);
 
-            if !scan_report.has_reference_with_game(&set_name) {
-                if let Some(game) = self.data_reader.get_game(&set_name) {
-                    scan_report.reference_with_game(game);
-                }
+            if !scan_report.has_reference_with_game(set_name) {
+                self.reference_set(scan_report, set_name, rom_mode)?;
             }
 
             roms.get_roms_included().into_iter().for_each(|rom| {
                 // We look for coincidences in the database for the roms found for that set
                 db_roms.iter().for_each(|set_rom| {
                     if rom.id == set_rom.id {
-                        let file_name_c = file_name.clone();
-                        let rom_name = rom.file.name.clone();
-                        let location = RomLocation::new(file_name_c, rom_name);
-                        scan_report.add_rom_for_set(set_name.to_owned(), location, set_rom.file.to_owned());
-                    } else {
-                        scan_report.add_missing_rom_for_set(set_name.to_owned(), set_rom.file.to_owned());
+                        let location = self.get_rom_location(&shared_file_name, set_name, &rom.file.name, &set_rom.file.name);
+                        scan_report.add_rom_for_set(set_name, location, set_rom.file.to_owned());
+                    } else if self.options.bios.is_required(&set_rom.file) {
+                        scan_report.add_missing_rom_for_set(set_name, set_rom.file.to_owned());
                     };
                 });
             });
 
-            if models::does_file_belong_to_set(&file_name, set_name.as_str()) {
+            if self.options.set_aliases.does_file_belong_to_set(&file_name, set_name.as_str()) {
                 matched_file_name_with_set = true;
-                scan_report.add_roms_to_spare(rom_search.get_roms_to_spare_for_set(&set_name), &file_name);
+                scan_report.add_roms_to_spare(rom_search.get_roms_to_spare_for_set(set_name), &file_name);
             }
         };
 
@@ -260,17 +727,207 @@ impl<R: DataReader> Reporter<R> {
             scan_report.add_roms_to_spare(spare, &file_name);
         }
 
-        scan_report.add_unknown_files(rom_search.unknowns, file_name);
+        let mut updated_sets = rom_search.set_results.keys().cloned().collect::<BTreeSet<_>>();
+        updated_sets.insert(self.options.set_aliases.get_set_from_file(&file_name));
+        scan_report.add_unknown_files(rom_search.unknowns, &file_name);
+        if !disks.is_empty() {
+            self.add_disks_report(scan_report, &file_name, disks, rom_mode)?;
+        }
 
-        Ok(())
+        Ok(updated_sets)
+    }
+}
+
+/// Samples the files to find which romset mode fits them best, the reader is only borrowed so it can be used for the check
+pub fn detect_romset_mode<R: DataReader>(data_reader: &R, file_paths: Vec<impl AsRef<Path>>, sample_size: usize) -> Result<ModeDetection> {
+    mode_detection::detect_romset_mode(data_reader, expand_directory(expand_sources(file_paths)?)?, sample_size)
+}
+
+/// Expands the glob patterns, the `@` files and `-` for the standard input, these two have a path or pattern per line, ignoring
+/// empty lines and `#` comments. Existing paths are never taken as patterns, and the paths are normalized so a file given
+/// twice, even as `./roms/x.zip` and `roms/x.zip`, is only kept once
+fn expand_sources(file_paths: Vec<impl AsRef<Path>>) -> Result<Vec<PathBuf>> {
+    let mut sources = vec![];
+    for path in &file_paths {
+        let path = path.as_ref().to_string_lossy();
+        if path == "-" {
+            sources.extend(read_path_list(io::stdin().lock())?);
+            continue;
+        }
+        match path.strip_prefix('@') {
+            Some(list_file) => {
+                let list = match fs::File::open(list_file) {
+                    Ok(list) => read_path_list(io::BufReader::new(list))?,
+                    Err(e) => return err!(RomstError::GenericError { message: format!("Error reading the file list `{}`: {}", list_file, e) }),
+                };
+                sources.extend(list);
+            },
+            None => sources.push(path.to_string()),
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut expanded = vec![];
+    for source in sources {
+        // Names like `Game [USA].zip` are common, so a path that exists is used as it is
+        let found = if glob::is_glob(&source) && !Path::new(&source).exists() {
+            let found = glob::glob_paths(&source)?;
+            if found.is_empty() {
+                warn!("No files match `{}`", source);
+            }
+            found
+        } else {
+            vec![PathBuf::from(source)]
+        };
+        for path in found.into_iter().map(paths::normalize_path) {
+            if seen.insert(path.clone()) {
+                expanded.push(path);
+            }
+        }
+    }
+
+    Ok(expanded)
+}
+
+fn read_path_list(list: impl BufRead) -> Result<Vec<String>> {
+    let mut paths = vec![];
+    for line in list.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            paths.push(line.to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// A single directory is replaced by its contents, as `check` does
+fn expand_directory(file_paths: Vec<impl AsRef<Path>>) -> Result<Vec<PathBuf>> {
+    if let [path] = file_paths.as_slice() {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let contents = path.read_dir()?
+                .filter_map(|dir_entry| dir_entry.ok().map(|entry| entry.path()))
+                .collect();
+            return Ok(contents);
+        }
+    }
+
+    Ok(file_paths.iter().map(|path| path.as_ref().to_path_buf()).collect())
+}
+
+/// A file reader for a scan worker, with the IO priority of the worker lowered if needed
+/// The roms of the set if the entries of its zip are exactly them, with the same names, sizes and crcs. The entries are
+/// trusted to have the data the DAT expects, so the roms are taken from the DAT with the names of the entries
+fn screen_archive(entries: Vec<DataFile>, set_roms: &[DataFile], entry_paths: EntryPaths) -> Option<Vec<DataFile>> {
+    if set_roms.is_empty() || entries.len() != set_roms.len() {
+        return None;
+    }
+
+    let mut entries = entries;
+    let mut roms = vec![];
+    for set_rom in set_roms {
+        let crc = set_rom.info.crc.as_deref()?;
+        let position = entries.iter().position(|entry| {
+            entry_paths.is_same_name(&entry.name, &set_rom.name)
+                && entry.info.size.is_some() && entry.info.size == set_rom.info.size
+                && entry.info.crc.as_deref().map(|entry_crc| entry_crc.eq_ignore_ascii_case(crc)).unwrap_or(false)
+        })?;
+        let entry = entries.swap_remove(position);
+        roms.push(DataFile::new(entry.name, set_rom.info.to_owned()));
+    }
+
+    Some(roms)
+}
+
+/// Reads the file again while it fails with a transient IO error, waiting longer before each retry
+async fn read_with_retries<T, F>(io_retry: IoRetry, file_name: &str, mut read: F) -> Result<T, RomstIOError> where F: FnMut() -> Result<T, RomstIOError> {
+    let mut retry = 0;
+    loop {
+        match read() {
+            Err(e) if e.is_transient() && retry < io_retry.retries => {
+                let backoff = io_retry.get_backoff(retry);
+                warn!("Error reading `{}`, reading it again in {} ms: {:#}", file_name, backoff.as_millis(), anyhow::Error::from(e));
+                tokio::time::sleep(backoff).await;
+                retry += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn get_file_error(error: RomstIOError) -> FileError {
+    let kind = if error.is_transient() { FileErrorKind::Transient } else { FileErrorKind::Permanent };
+    FileError { kind, message: format!("{:#}", anyhow::Error::from(error)) }
+}
+
+fn new_file_reader(throttle: Option<Throttle>, low_priority: bool) -> FileReader {
+    if low_priority {
+        if let Err(e) = throttle::lower_io_priority() {
+            warn!("Can't lower the IO priority: {}", e);
+        }
+    }
+    let mut file_reader = FileReader::new();
+    if let Some(throttle) = throttle {
+        file_reader.set_throttle(throttle);
+    }
+    file_reader
+}
+
+/// A raw file outside an archive, that is not a disk or the part of a split zip
+fn is_loose_file(path: &Path) -> bool {
+    let file_name = paths::get_file_name_key(path);
+    let is_archive = path.extension()
+        .map(|extension| ["zip", "7z", "rar"].contains(&extension.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false);
+    path.is_file() && !is_archive && filesystem::get_split_archive_of_part(&path).is_none()
+        && FileType::from_file_name(&file_name) != FileType::Disk
+}
+
+/// The name of the set of a loose file, without the extension and the ` (Track N)` suffix of the multi-track images
+fn get_loose_file_basename(path: &Path) -> String {
+    let stem = path.file_stem().map(paths::os_str_to_key).unwrap_or_default();
+    let stem = stem.trim();
+    match stem.strip_suffix(')').and_then(|stem| stem.rsplit_once(" (Track ")) {
+        Some((basename, number)) if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) => basename.to_string(),
+        _ => stem.to_string(),
     }
 }
 
+/// Takes out the parts of the split zips, grouped by the zip they belong to, with the names of their files sorted
+fn group_split_archives(file_paths: Vec<impl AsRef<Path>>) -> (Vec<PathBuf>, BTreeMap<PathBuf, Vec<String>>) {
+    let file_name = |path: &Path| paths::get_file_name_key(path);
+
+    let mut split_archives: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    let mut others = vec![];
+    for path in &file_paths {
+        let path = path.as_ref();
+        match filesystem::get_split_archive_of_part(&path) {
+            Some(archive) => split_archives.entry(archive).or_default().push(file_name(path)),
+            None => others.push(path.to_path_buf()),
+        }
+    }
+
+    // The last part is the zip itself
+    others.retain(|path| match split_archives.get_mut(path) {
+        Some(parts) => {
+            parts.push(file_name(path));
+            false
+        }
+        None => true,
+    });
+    for parts in split_archives.values_mut() {
+        parts.sort();
+    }
+
+    (others, split_archives)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{cell::RefCell, path::Path, rc::Rc};
+    use std::{path::Path, sync::{Arc, Mutex}};
     use rusqlite::{Connection, OpenFlags};
-    use crate::data::{importer::DatImporter, reader::sqlite::DBReader, writer::sqlite::DBWriter};
+    use crate::{BiosSelection, data::{aliases::SetAliases, fixer::FixOperation, importer::DatImporter, reader::sqlite::DBReader, reporter::scan_report::{RomLocatedAt, SetStatus}, writer::sqlite::DBWriter}};
     use super::*;
 
     fn get_db_connection<'a, 'b>(dat_path: &'b impl AsRef<Path>) -> Result<Connection> {
@@ -286,7 +943,7 @@ mod tests {
     }
 
     struct TestReportReporter {
-        inner: Rc<RefCell<InnerReportReporter>>
+        inner: Arc<Mutex<InnerReportReporter>>
     }
 
     struct InnerReportReporter {
@@ -297,45 +954,57 @@ mod tests {
         ignored: usize,
         error: usize,
         finished: bool,
-        files: Vec<String>
+        files: Vec<String>,
+        sets_matched: Vec<String>,
+        set_statuses: HashMap<String, SetStatus>,
     }
 
     impl TestReportReporter {
         fn new() -> Self { Self { 
-            inner: Rc::new(RefCell::new(InnerReportReporter {
-                total_files: 0, current_files: 0, new_files: 0, directories: 0, ignored: 0, error: 0, finished: false, files: vec![] }
+            inner: Arc::new(Mutex::new(InnerReportReporter {
+                total_files: 0, current_files: 0, new_files: 0, directories: 0, ignored: 0, error: 0, finished: false, files: vec![], sets_matched: vec![], set_statuses: HashMap::new() }
              )) }
         }
     }
 
     impl ReportReporter for TestReportReporter {
         fn set_total_files(&mut self, total_files: usize) {
-            self.inner.borrow_mut().total_files += total_files;
+            self.inner.lock().unwrap().total_files += total_files;
         }
 
         fn update_report_new_file(&mut self, new_file: &str) {
-            self.inner.borrow_mut().current_files += 1;
-            self.inner.borrow_mut().files.push(new_file.to_string());
+            self.inner.lock().unwrap().current_files += 1;
+            self.inner.lock().unwrap().files.push(new_file.to_string());
         }
 
         fn update_report_new_added_file(&mut self, new_files: usize) {
-            self.inner.borrow_mut().new_files += new_files;
+            self.inner.lock().unwrap().new_files += new_files;
         }
 
         fn update_report_directory(&mut self, new_files: usize) {
-            self.inner.borrow_mut().directories += new_files;
+            self.inner.lock().unwrap().directories += new_files;
         }
 
         fn update_report_ignored(&mut self, new_files: usize) {
-            self.inner.borrow_mut().ignored += new_files;
+            self.inner.lock().unwrap().ignored += new_files;
         }
 
         fn update_report_file_error(&mut self, new_files: usize) {
-            self.inner.borrow_mut().error += new_files;
+            self.inner.lock().unwrap().error += new_files;
+        }
+
+        fn set_matched(&mut self, set_name: &str) {
+            self.inner.lock().unwrap().sets_matched.push(set_name.to_string());
+        }
+
+        fn set_completed(&mut self, set_name: &str, status: SetStatus, previous: Option<SetStatus>) {
+            let reported = self.inner.lock().unwrap().set_statuses.insert(set_name.to_string(), status);
+            assert_eq!(reported, previous);
+            assert_ne!(Some(status), previous);
         }
 
         fn finish(&mut self) {
-            self.inner.borrow_mut().finished = true;
+            self.inner.lock().unwrap().finished = true;
         }
     }
 
@@ -384,21 +1053,24 @@ mod tests {
 
         let mut reporter = Reporter::new(data_reader);
         let report_reporter = TestReportReporter::new();
-        let inner = Rc::clone(&report_reporter.inner);
+        let inner = Arc::clone(&report_reporter.inner);
 
         reporter.add_reporter(report_reporter);
 
         let game_path = Path::new("testdata").join("split");
-        let report = reporter.check(vec![ game_path ], RomsetMode::Merged).await?;
-
-        assert_eq!(inner.borrow().total_files, 8);
-        assert_eq!(inner.borrow().current_files, 7);
-        assert_eq!(inner.borrow().new_files, 7);
-        assert_eq!(inner.borrow().directories, 1);
-        assert_eq!(inner.borrow().ignored, 0);
-        assert_eq!(inner.borrow().error, 0);
-        assert!(inner.borrow().finished);
+        let report = reporter.check(vec![ game_path ], ScanOptions::new(RomsetMode::Merged)).await?;
+
+        assert_eq!(inner.lock().unwrap().total_files, 8);
+        assert_eq!(inner.lock().unwrap().current_files, 8);
+        assert_eq!(inner.lock().unwrap().new_files, 8);
+        assert_eq!(inner.lock().unwrap().directories, 0);
+        assert_eq!(inner.lock().unwrap().ignored, 0);
+        assert_eq!(inner.lock().unwrap().error, 0);
+        assert!(inner.lock().unwrap().finished);
         assert_eq!(report.sets.len(), 7);
+        assert_eq!(inner.lock().unwrap().sets_matched.len(), 7);
+        let statuses = report.sets.iter().map(|(set_name, set_report)| (set_name.to_owned(), set_report.is_complete())).collect::<HashMap<_, _>>();
+        assert_eq!(inner.lock().unwrap().set_statuses, statuses);
         tests::assert_file_report(&report, "device1.zip", "device1", 1, 0, 0, 0, 0, 0);
         tests::assert_file_report(&report, "game1.zip", "game1", 4, 0, 2, 0, 0, 0);
         tests::assert_file_report(&report, "game1a.zip", "game1a", 0, 0, 0, 0, 2, 0);
@@ -406,6 +1078,70 @@ mod tests {
         tests::assert_file_report(&report, "game3.zip", "game3", 3, 0, 0, 0, 0, 0);
         tests::assert_file_report(&report, "game4.zip", "game4", 4, 0, 0, 0,0, 0);
         tests::assert_file_report(&report, "game5.zip", "game5", 3, 0, 0, 0, 0, 0);
+        // The disk is in the `game5` folder next to the zip
+        let game5 = &report.sets["game5"];
+        assert_eq!(1, game5.disks_available.len());
+        assert!(game5.disks_missing.is_empty());
+        assert_eq!(SetStatus::COMPLETE, game5.is_complete());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_the_missing_disks_and_the_folders_with_the_wrong_names() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+        let mut reporter = Reporter::new(data_reader);
+
+        let report = reporter.check(vec![ Path::new("testdata").join("split").join("game5.zip") ], ScanOptions::new(RomsetMode::Merged)).await?;
+        let game5 = &report.sets["game5"];
+        assert!(game5.disks_available.is_empty());
+        assert_eq!(1, game5.disks_missing.len());
+        assert_eq!(SetStatus::INCOMPLETE, game5.is_complete());
+
+        let dir = std::env::temp_dir().join(format!("romst_disk_folder_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("game5"))?;
+        fs::copy(Path::new("testdata").join("split").join("game5.zip"), dir.join("game5.zip"))?;
+        fs::copy(Path::new("testdata").join("split").join("game5").join("gm5-001.chd"), dir.join("game5").join("disk.chd"))?;
+        let report = reporter.check(vec![ &dir ], ScanOptions::new(RomsetMode::Merged)).await;
+        fs::remove_dir_all(&dir)?;
+
+        let report = report?;
+        let game5 = &report.sets["game5"];
+        assert!(game5.disks_missing.is_empty());
+        assert_eq!(Some(&RomLocatedAt::InSetWrongName("disk.chd".to_string())), game5.disks_available.values().next());
+        assert_eq!(SetStatus::FIXEABLE, game5.is_complete());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn streams_set_reports() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let mut reporter = Reporter::new(data_reader);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        reporter.add_set_listener(tx);
+
+        let game_path = Path::new("testdata").join("split");
+        let report = reporter.check(vec![ game_path ], ScanOptions::new(RomsetMode::Merged)).await?;
+        drop(reporter);
+
+        let mut last_updates = std::collections::HashMap::new();
+        while let Some(update) = rx.recv().await {
+            last_updates.insert(update.set_report.reference.get_name().to_string(), update);
+        }
+
+        assert_eq!(last_updates.len(), report.sets.len());
+        for (set_name, set_report) in &report.sets {
+            let update = &last_updates[set_name];
+            assert_eq!(update.set_report.roms_available, set_report.roms_available);
+            assert_eq!(update.set_report.roms_missing, set_report.roms_missing);
+        }
 
         Ok(())
     }
@@ -418,22 +1154,24 @@ mod tests {
 
         let mut reporter = Reporter::new(data_reader);
         let report_reporter = TestReportReporter::new();
-        let inner = Rc::clone(&report_reporter.inner);
+        let inner = Arc::clone(&report_reporter.inner);
 
         reporter.add_reporter(report_reporter);
 
         let game_path = Path::new("testdata").join("single");
-        let report = reporter.check(vec![ game_path ], RomsetMode::Merged).await?;
-
-        assert_eq!(inner.borrow().total_files, 1);
-        assert_eq!(inner.borrow().current_files, 1);
-        assert_eq!(inner.borrow().new_files, 1);
-        assert_eq!(inner.borrow().directories, 0);
-        assert_eq!(inner.borrow().ignored, 0);
-        assert_eq!(inner.borrow().error, 0);
-        assert!(inner.borrow().finished);
+        let report = reporter.check(vec![ game_path ], ScanOptions::new(RomsetMode::Merged)).await?;
+
+        assert_eq!(inner.lock().unwrap().total_files, 1);
+        assert_eq!(inner.lock().unwrap().current_files, 1);
+        assert_eq!(inner.lock().unwrap().new_files, 1);
+        assert_eq!(inner.lock().unwrap().directories, 0);
+        assert_eq!(inner.lock().unwrap().ignored, 0);
+        assert_eq!(inner.lock().unwrap().error, 0);
+        assert!(inner.lock().unwrap().finished);
         assert_eq!(report.sets.len(), 1);
         tests::assert_file_report(&report, "game4.zip", "game4", 4, 0, 0, 0, 0, 0);
+        assert_eq!(report.archives.len(), 1);
+        assert_eq!(report.get_non_canonical_archives().len(), 1);
 
         Ok(())
     }
@@ -446,20 +1184,20 @@ mod tests {
 
         let mut reporter = Reporter::new(data_reader);
         let report_reporter = TestReportReporter::new();
-        let inner = Rc::clone(&report_reporter.inner);
+        let inner = Arc::clone(&report_reporter.inner);
 
         reporter.add_reporter(report_reporter);
 
         let game_path = Path::new("testdata").join("wrong");
-        let report = reporter.check(vec![ &game_path ], RomsetMode::Split).await?;
-
-        assert_eq!(inner.borrow().total_files, 4);
-        assert_eq!(inner.borrow().current_files, 4);
-        assert_eq!(inner.borrow().new_files, 3);
-        assert_eq!(inner.borrow().directories, 0);
-        assert_eq!(inner.borrow().ignored, 1);
-        assert_eq!(inner.borrow().error, 0);
-        assert!(inner.borrow().finished);
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::Split)).await?;
+
+        assert_eq!(inner.lock().unwrap().total_files, 4);
+        assert_eq!(inner.lock().unwrap().current_files, 4);
+        assert_eq!(inner.lock().unwrap().new_files, 3);
+        assert_eq!(inner.lock().unwrap().directories, 0);
+        assert_eq!(inner.lock().unwrap().ignored, 1);
+        assert_eq!(inner.lock().unwrap().error, 0);
+        assert!(inner.lock().unwrap().finished);
         assert_eq!(report.sets.len(), 3);
         tests::assert_file_report(&report, "game1.zip", "game1", 3, 1, 0, 0, 0, 0);
         tests::assert_file_report(&report, "game2.zip", "game2", 2, 0, 1, 1, 0, 0);
@@ -467,4 +1205,419 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn reports_split_archives_as_one_file() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+
+        let mut reporter = Reporter::new(data_reader);
+        let report_reporter = TestReportReporter::new();
+        let inner = Arc::clone(&report_reporter.inner);
+        reporter.add_reporter(report_reporter);
+
+        let dir = std::env::temp_dir().join(format!("romst_split_archives_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        // The last part of a split zip, its central directory starts in the first part
+        let last_part = [0x50, 0x4b, 0x05, 0x06, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        fs::write(dir.join("game1.z01"), [0x50, 0x4b, 0x07, 0x08])?;
+        fs::write(dir.join("game1.z02"), [0])?;
+        fs::write(dir.join("game1.zip"), last_part)?;
+        fs::write(dir.join("game2.zip"), last_part)?;
+        fs::copy(Path::new("testdata").join("split").join("game4.zip"), dir.join("game4.zip"))?;
+
+        let report = reporter.check(vec![ &dir ], ScanOptions::new(RomsetMode::Merged)).await?;
+
+        assert_eq!(inner.lock().unwrap().total_files, 3);
+        assert_eq!(inner.lock().unwrap().current_files, 3);
+        assert_eq!(inner.lock().unwrap().ignored, 2);
+        assert_eq!(inner.lock().unwrap().error, 0);
+        assert_eq!(Some(&vec!["game1.z01".to_string(), "game1.z02".to_string(), "game1.zip".to_string()]), report.split_archives.get("game1.zip"));
+        assert_eq!(Some(&vec!["game2.zip".to_string()]), report.split_archives.get("game2.zip"));
+        assert!(report.ignored.is_empty());
+        tests::assert_file_report(&report, "game4.zip", "game4", 4, 0, 0, 0, 0, 0);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn groups_the_loose_files_into_sets() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let data_reader = DBReader::from_connection(conn);
+        let mut reporter = Reporter::new(data_reader);
+
+        let dir = std::env::temp_dir().join(format!("romst_loose_files_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let mut archive = zip::ZipArchive::new(fs::File::open(Path::new("testdata").join("split").join("game4.zip"))?)?;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let mut file = fs::File::create(dir.join(entry.name()))?;
+            io::copy(&mut entry, &mut file)?;
+        }
+
+        let ignored = reporter.check(vec![ &dir ], ScanOptions::new(RomsetMode::Merged)).await;
+        let grouped = reporter.check(vec![ &dir ], ScanOptions::new(RomsetMode::Merged).loose_files(LooseFiles::Dat)).await;
+        fs::remove_dir_all(&dir)?;
+
+        let ignored = ignored?;
+        assert!(ignored.sets.is_empty());
+        assert_eq!(4, ignored.ignored.len());
+        let grouped = grouped?;
+        assert_eq!(1, grouped.sets.len());
+        tests::assert_file_report(&grouped, "game4", "game4", 4, 0, 0, 0, 0, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancelled_check_reports_unscanned_files() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let mut reporter = Reporter::new(DBReader::from_connection(conn));
+        let cancellation = CancellationToken::default();
+        reporter.set_cancellation_token(cancellation.clone());
+        cancellation.cancel();
+
+        let game_path = Path::new("testdata").join("split").join("game1.zip");
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::Split)).await?;
+        assert!(report.is_interrupted());
+        assert!(report.sets.is_empty());
+        assert_eq!(vec!["game1.zip".to_string()], report.unscanned);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn retries_the_transient_errors() {
+        let failing = |kind: io::ErrorKind, failures: u32| {
+            let mut reads = 0;
+            move || {
+                reads += 1;
+                if reads <= failures { Err(RomstIOError::Io { source: io::Error::from(kind) }) } else { Ok(reads) }
+            }
+        };
+
+        let read = read_with_retries(IoRetry::new(2, 1), "game1.zip", failing(io::ErrorKind::TimedOut, 2)).await;
+        assert_eq!(3, read.unwrap());
+        let read = read_with_retries(IoRetry::new(1, 1), "game1.zip", failing(io::ErrorKind::TimedOut, 2)).await;
+        assert!(matches!(read, Err(ref e) if e.is_transient()));
+        assert_eq!(FileErrorKind::Transient, get_file_error(read.unwrap_err()).kind);
+        // The permanent errors are not read again
+        let read = read_with_retries(IoRetry::new(2, 1), "game1.zip", failing(io::ErrorKind::InvalidData, 1)).await;
+        assert_eq!(FileErrorKind::Permanent, get_file_error(read.unwrap_err()).kind);
+    }
+
+    #[test]
+    fn gets_the_basename_of_the_loose_files() {
+        assert_eq!("Game (USA)", get_loose_file_basename(Path::new("Game (USA).cue")));
+        assert_eq!("Game (USA)", get_loose_file_basename(Path::new("Game (USA) (Track 02).bin")));
+        assert_eq!("Game (Track A)", get_loose_file_basename(Path::new("Game (Track A).bin")));
+    }
+
+    #[tokio::test]
+    async fn bios_selection_filters_missing_roms() -> Result<()> {
+        use crate::data::{models::file::{DataFile, DataFileInfo, FileType}, testing::{FixtureBuilder, GameFixture}};
+
+        let mut bios_info = DataFileInfo::new(FileType::Rom);
+        bios_info.sha1 = Some("a4a3d9ddfbfc8d9c0a2e1a0c6a3c3a0c2ee5e4f1".to_string());
+        let mut bios_rom = DataFile::new("euro.rom", bios_info);
+        bios_rom.bios = Some("europe".to_string());
+
+        let get_reader = || FixtureBuilder::new()
+            .game(GameFixture::new("game4")
+                .rom("rrham.rom", "b47a81a4bce8d7abd8f940b0acb5674776b4ae03", "7182d83b", 2048)
+                .rom("rhum1.rom", "0d0410009c5bd3802b0021c8f29edc997a83c88c", "4bec6e65", 2048)
+                .rom("rhum2.rom", "0d0410009c5bd3802b0021c8f29edc997a83c88c", "4bec6e65", 2048)
+                .rom("rhin1.rom", "5bef439d1d775e0ff3a17189478e87b3dd5d0e49", "0d46fa2d", 2048)
+                .data_file(bios_rom.clone()))
+            .build();
+
+        let game_path = Path::new("testdata").join("single");
+
+        let mut reporter = Reporter::new(get_reader()?);
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::NonMerged)).await?;
+        assert_eq!(1, report.sets.get("game4").unwrap().roms_missing.len());
+
+        let mut reporter = Reporter::new(get_reader()?);
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::NonMerged).bios(BiosSelection::NoBios)).await?;
+        assert_eq!(0, report.sets.get("game4").unwrap().roms_missing.len());
+
+        let mut reporter = Reporter::new(get_reader()?);
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::NonMerged).bios(BiosSelection::Variant("japan".to_string()))).await?;
+        assert_eq!(0, report.sets.get("game4").unwrap().roms_missing.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn expands_globs_and_file_lists() -> Result<()> {
+        let list_file = std::env::temp_dir().join(format!("romst_file_list_{}.txt", std::process::id()));
+        fs::write(&list_file, "# split sets\ntestdata/split/game1.zip\n\n  testdata/split/game5  \ntestdata/wrong/*.txt\n")?;
+
+        let sources = vec!["testdata/split/game[12]*.zip".to_string(), format!("@{}", list_file.to_string_lossy())];
+        let paths = expand_sources(sources)?;
+        fs::remove_file(&list_file)?;
+
+        let expected = vec!["testdata/split/game1.zip", "testdata/split/game1a.zip", "testdata/split/game2.zip", "testdata/split/game5", "testdata/wrong/info.txt"];
+        assert_eq!(expected.into_iter().map(paths::normalize_path).collect::<Vec<_>>(), paths);
+
+        assert!(expand_sources(vec!["@testdata/none.txt"]).is_err());
+
+        let list = read_path_list(io::Cursor::new("testdata/split/game2.zip\r\n# comment\n\ntestdata/split/game1.zip"))?;
+        assert_eq!(vec!["testdata/split/game2.zip", "testdata/split/game1.zip"], list);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checks_a_file_given_with_different_paths_once() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let mut reporter = Reporter::new(DBReader::from_connection(conn));
+        let report_reporter = TestReportReporter::new();
+        let inner = Arc::clone(&report_reporter.inner);
+        reporter.add_reporter(report_reporter);
+
+        let absolute = std::env::current_dir()?.join("testdata").join("split").join("game1.zip");
+        let sources = vec![ PathBuf::from("testdata/split/game1.zip"), PathBuf::from("./testdata/split/game1.zip"), absolute ];
+        let report = reporter.check(sources, ScanOptions::new(RomsetMode::Split)).await?;
+
+        assert_eq!(inner.lock().unwrap().total_files, 1);
+        assert_eq!(vec!["game1.zip"], report.archives.keys().collect::<Vec<_>>());
+        assert_eq!(SetStatus::COMPLETE, report.sets["game1"].is_complete());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn flags_the_files_not_packed_as_the_dat_expects() -> Result<()> {
+        let dat = fs::read_to_string(Path::new("testdata").join("test.dat"))?.replace("<clrmamepro/>", r#"<clrmamepro forcepacking="unzip"/>"#);
+        let dat_file = std::env::temp_dir().join(format!("romst_unzip_{}.dat", std::process::id()));
+        fs::write(&dat_file, dat)?;
+        let conn = get_db_connection(&dat_file);
+        fs::remove_file(&dat_file)?;
+        let mut reporter = Reporter::new(DBReader::from_connection(conn?));
+
+        let report = reporter.check(vec![ Path::new("testdata").join("split") ], ScanOptions::new(RomsetMode::Split)).await?;
+
+        assert_eq!(Some(Packing::Unzip), report.get_packing());
+        // The folder of game5 only has its disk
+        let expected = vec!["device1.zip", "game1.zip", "game1a.zip", "game2.zip", "game3.zip", "game4.zip", "game5.zip"];
+        assert_eq!(expected, report.wrong_packing);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn accepts_the_zips_from_their_directory_with_a_quick_check() -> Result<()> {
+        use crate::data::testing::{FixtureBuilder, GameFixture};
+
+        // The sha1 of rom1.trom is not the one of the file, only a check extracting the zip finds it out
+        let get_reader = |extra_rom: bool| {
+            let mut game = GameFixture::new("game1")
+                .rom("rom1.trom", "0000000000000000000000000000000000000000", "1d460eee", 2048)
+                .rom("rom2.trom", "bea1c74957e8f659d51fbac19ed8d2418a741927", "b4069ce5", 2048)
+                .rom("rom3.trom", "2dda409cbca971007a9089ba0e7c9cd37533c58c", "c284e56d", 2048)
+                .rom("binfil1.bin", "8273bfebe84dd41a5d237add8f9d03ac9bb0ef54", "1b736d41", 65536);
+            if extra_rom {
+                game = game.rom("rom4.trom", "dbb660bbff3c7e37fd0397ccbac593c0f71da6d4", "c6b7767e", 2048);
+            }
+            FixtureBuilder::new().game(game).build()
+        };
+        let game_path = Path::new("testdata").join("split").join("game1.zip");
+
+        let mut reporter = Reporter::new(get_reader(false)?);
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::NonMerged).quick_check(true)).await?;
+        assert_eq!(SetStatus::COMPLETE, report.sets["game1"].is_complete());
+        assert_eq!(Some(FileChecks::CRC | FileChecks::SIZE), report.get_file_checks());
+        assert!(report.is_weak_matching());
+
+        let mut reporter = Reporter::new(get_reader(false)?);
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::NonMerged)).await?;
+        assert_eq!(SetStatus::INCOMPLETE, report.sets["game1"].is_complete());
+        assert!(!report.is_weak_matching());
+
+        // The directory doesn't match the set, so the zip is extracted and hashed
+        let mut reporter = Reporter::new(get_reader(true)?);
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::NonMerged).quick_check(true)).await?;
+        assert_eq!(2, report.sets["game1"].roms_missing.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hashes_the_zips_sampled_in_an_audit() -> Result<()> {
+        use crate::data::testing::{FixtureBuilder, GameFixture};
+        use sample_audit::AuditSample;
+
+        // The directory of the zip matches the set, but the sha1 of rom1.trom is not the one of the file
+        let reader = FixtureBuilder::new().game(GameFixture::new("game1")
+            .rom("rom1.trom", "0000000000000000000000000000000000000000", "1d460eee", 2048)
+            .rom("rom2.trom", "bea1c74957e8f659d51fbac19ed8d2418a741927", "b4069ce5", 2048)
+            .rom("rom3.trom", "2dda409cbca971007a9089ba0e7c9cd37533c58c", "c284e56d", 2048)
+            .rom("binfil1.bin", "8273bfebe84dd41a5d237add8f9d03ac9bb0ef54", "1b736d41", 65536)).build()?;
+        let game_path = Path::new("testdata").join("split").join("game1.zip");
+
+        let mut reporter = Reporter::new(reader);
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::NonMerged).sample(Some(AuditSample::new(0.05)))).await?;
+        assert_eq!(SetStatus::INCOMPLETE, report.sets["game1"].is_complete());
+        let sample_audit = report.sample_audit.as_ref().unwrap();
+        assert_eq!((0, 1, 1), (sample_audit.screened, sample_audit.sampled, sample_audit.sampled_screenable));
+        assert_eq!(vec!["game1.zip".to_string()], sample_audit.mismatches);
+        assert_eq!(Some(1.0), sample_audit.estimate.map(|estimate| estimate.rate));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn matches_the_files_named_as_an_alias() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("romst_alias_check_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::copy(Path::new("testdata").join("split").join("game1.zip"), dir.join("game1_fb.zip"))?;
+
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let mut reporter = Reporter::new(DBReader::from_connection(conn));
+        let mut set_aliases = SetAliases::default();
+        set_aliases.add_alias("game1_fb", "game1");
+
+        let report = reporter.check(vec![ &dir ], ScanOptions::new(RomsetMode::Split).set_aliases(set_aliases)).await?;
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(SetStatus::COMPLETE, report.sets["game1"].is_complete());
+        assert!(report.sets["game1"].in_file);
+        assert!(!report.sets.contains_key("game1_fb"));
+        assert_eq!(Some(&"game1.zip".to_string()), report.alias_renames.get("game1_fb.zip"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn checks_a_single_set() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let mut reporter = Reporter::new(DBReader::from_connection(conn));
+
+        let game_path = Path::new("testdata").join("split").join("game1.zip");
+        let set_report = reporter.check_set("game1", &game_path, ScanOptions::new(RomsetMode::NonMerged))?;
+        assert!(set_report.in_file);
+        assert_eq!(SetStatus::COMPLETE, set_report.is_complete());
+        assert!(set_report.roms_to_spare.is_empty());
+
+        let other_path = Path::new("testdata").join("split").join("game2.zip");
+        let set_report = reporter.check_set("game1", &other_path, ScanOptions::new(RomsetMode::NonMerged))?;
+        assert!(!set_report.in_file);
+        assert_eq!(SetStatus::INCOMPLETE, set_report.is_complete());
+        assert!(!set_report.roms_to_spare.is_empty());
+
+        assert!(reporter.check_set("unknown", &game_path, ScanOptions::new(RomsetMode::NonMerged)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn compares_the_entry_paths() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let conn = get_db_connection(&path)?;
+        let mut reporter = Reporter::new(DBReader::from_connection(conn));
+
+        // The same roms, inside a folder
+        let dir = std::env::temp_dir().join(format!("romst_entry_paths_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let game_path = dir.join("game1.zip");
+        let mut source = zip::ZipArchive::new(fs::File::open(Path::new("testdata").join("split").join("game1.zip"))?)?;
+        let mut writer = zip::ZipWriter::new(fs::File::create(&game_path)?);
+        writer.add_directory("cpu/", zip::write::FileOptions::default())?;
+        for i in 0..source.len() {
+            let entry = source.by_index_raw(i)?;
+            let name = format!("cpu/{}", entry.name());
+            writer.raw_copy_file_rename(entry, name)?;
+        }
+        writer.finish()?;
+
+        let set_report = reporter.check_set("game1", &game_path, ScanOptions::new(RomsetMode::NonMerged))?;
+        assert_eq!(SetStatus::FIXEABLE, set_report.is_complete());
+        assert!(set_report.roms_available.values().all(|located_at| matches!(located_at, RomLocatedAt::InSetWrongName(name) if name.starts_with("cpu/"))));
+        assert!(set_report.unknown.is_empty());
+
+        let set_report = reporter.check_set("game1", &game_path, ScanOptions::new(RomsetMode::NonMerged).entry_paths(EntryPaths::FileName))?;
+        assert_eq!(SetStatus::COMPLETE, set_report.is_complete());
+
+        assert!(EntryPaths::FullPath.is_same_name("cpu\\rom.bin", "cpu/rom.bin"));
+        assert!(!EntryPaths::FullPath.is_same_name("rom.bin", "cpu/rom.bin"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn uses_the_forced_set_mode() -> Result<()> {
+        let path = Path::new("testdata").join("test.dat");
+        let game_path = Path::new("testdata").join("split").join("game1.zip");
+
+        let conn = get_db_connection(&path)?;
+        let mut reporter = Reporter::new(DBReader::from_connection(conn));
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::Merged)).await?;
+        let merged_missing = report.sets.get("game1").unwrap().roms_missing.len();
+
+        let conn = get_db_connection(&path)?;
+        crate::data::writer::sqlite::write_mode_override(&conn, "game1", Some(RomsetMode::Split))?;
+        let mut reporter = Reporter::new(DBReader::from_connection(conn));
+        let report = reporter.check(vec![ &game_path ], ScanOptions::new(RomsetMode::Merged)).await?;
+        let set_report = report.sets.get("game1").unwrap();
+
+        assert!(merged_missing > 0);
+        assert_eq!(0, set_report.roms_missing.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn needs_each_name_of_a_duplicated_rom() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("romst_duplicated_rom_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        // game4 has the same rom as rhum1.rom and rhum2.rom
+        let write_game4 = |names: &[&str]| -> Result<()> {
+            let mut source = zip::ZipArchive::new(fs::File::open(Path::new("testdata").join("split").join("game4.zip"))?)?;
+            let mut writer = zip::ZipWriter::new(fs::File::create(dir.join("game4.zip"))?);
+            for name in names {
+                writer.raw_copy_file(source.by_name(name)?)?;
+            }
+            writer.finish()?;
+            Ok(())
+        };
+        let path = Path::new("testdata").join("test.dat");
+
+        write_game4(&["rrham.rom", "rhum1.rom", "rhin1.rom"])?;
+        let mut reporter = Reporter::new(DBReader::from_connection(get_db_connection(&path)?));
+        let single_copy = reporter.check(vec![ &dir ], ScanOptions::new(RomsetMode::NonMerged)).await;
+
+        write_game4(&["rrham.rom", "rhin1.rom"])?;
+        let mut reporter = Reporter::new(DBReader::from_connection(get_db_connection(&path)?));
+        let no_copy = reporter.check(vec![ &dir ], ScanOptions::new(RomsetMode::NonMerged)).await;
+        fs::remove_dir_all(&dir)?;
+
+        // The single copy is only in the set as rhum1.rom, rhum2.rom has to be copied from it
+        let single_copy = single_copy?;
+        let set = &single_copy.sets["game4"];
+        let located_at = |name: &str| set.roms_available.iter().find(|(rom, _located_at)| rom.name == name).map(|(_rom, located_at)| located_at.clone());
+        assert_eq!(Some(RomLocatedAt::InSet), located_at("rhum1.rom"));
+        assert_eq!(Some(RomLocatedAt::InSetWrongName("rhum1.rom".to_string())), located_at("rhum2.rom"));
+        assert_eq!(SetStatus::FIXEABLE, set.is_complete());
+        // Renaming it would lose rhum1.rom
+        assert_eq!(vec![FixOperation::Duplicate { archive: "game4.zip".to_string(), from: "rhum1.rom".to_string(), to: "rhum2.rom".to_string() }],
+            single_copy.build_fix_plan().operations);
+
+        let no_copy = no_copy?;
+        let set = &no_copy.sets["game4"];
+        assert_eq!(vec!["rhum1.rom", "rhum2.rom"], set.roms_missing.iter().map(|rom| rom.name.as_str()).collect::<BTreeSet<_>>().into_iter().collect::<Vec<_>>());
+        assert_eq!(SetStatus::INCOMPLETE, set.is_complete());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use std::{fmt::Display, path::{Path, PathBuf}};
+
+use anyhow::Result;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{RomsetMode, data::{models::{self, file::DataFile}, reader::DataReader}, filesystem::{FileChecks, FileReader}};
+
+/// The modes in order of preference when several fit the same
+const MODES: [RomsetMode; 3] = [RomsetMode::NonMerged, RomsetMode::Split, RomsetMode::Merged];
+
+/// The result of comparing the sampled archives against the roms each mode expects
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModeDetection {
+    pub mode: RomsetMode,
+    /// Ratio of the sampled archives where the mode is the best fit
+    pub confidence: f32,
+    pub sampled: usize,
+    /// Average fit of each mode, 1 means the archives match exactly the roms of the mode
+    pub scores: Vec<(RomsetMode, f32)>,
+}
+
+impl Display for ModeDetection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Detected mode: {} ({:.0}% confidence, {} archives sampled)", self.mode, self.confidence * 100.0, self.sampled)?;
+        for (mode, score) in &self.scores {
+            writeln!(f, "- {}: {:.2}", mode, score)?;
+        }
+        Ok(())
+    }
+}
+
+/// The mode asked is not the one the DAT is meant to be used in, the sets are put together in a way the DAT doesn't
+/// expect, so what's reported as missing or to spare can be misleading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModeMismatch {
+    pub dat_mode: RomsetMode,
+    pub rom_mode: RomsetMode,
+}
+
+impl Display for ModeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The DAT is meant to be used in {} mode, the results in {} mode may be misleading", self.dat_mode, self.rom_mode)
+    }
+}
+
+impl ModeMismatch {
+    /// The mismatch if the DAT says its mode and it's not the one asked
+    pub fn check<R: DataReader>(data_reader: &R, rom_mode: RomsetMode) -> Result<Option<Self>> {
+        Ok(data_reader.get_dat_mode()?
+            .filter(|dat_mode| *dat_mode != rom_mode)
+            .map(|dat_mode| Self { dat_mode, rom_mode }))
+    }
+}
+
+/// Samples evenly up to `sample_size` files, and picks the mode with the best average fit
+pub fn detect_romset_mode<R: DataReader>(data_reader: &R, file_paths: Vec<PathBuf>, sample_size: usize) -> Result<ModeDetection> {
+    let mut files = file_paths.into_iter().filter(|path| path.is_file()).collect::<Vec<_>>();
+    files.sort();
+    let step = (files.len() / sample_size.max(1)).max(1);
+
+    let file_checks = data_reader.get_file_checks()?.get_file_checks();
+    let mut file_reader = FileReader::new();
+    let mut archive_scores = vec![];
+    for file in files.iter().step_by(step).take(sample_size) {
+        match score_archive(data_reader, &mut file_reader, file, file_checks) {
+            Ok(Some(scores)) => archive_scores.push(scores),
+            Ok(None) => debug!("Archive {} is not a set in the database", file.to_string_lossy()),
+            Err(e) => warn!("Error sampling {}: {}", file.to_string_lossy(), e),
+        }
+    }
+
+    let sampled = archive_scores.len();
+    let averages = MODES.iter().enumerate().map(|(i, mode)| {
+        let total: f32 = archive_scores.iter().map(|scores| scores[i]).sum();
+        (*mode, if sampled == 0 { 0.0 } else { total / sampled as f32 })
+    }).collect::<Vec<_>>();
+
+    let mut best = 0;
+    for (i, (_mode, score)) in averages.iter().enumerate() {
+        if *score > averages[best].1 {
+            best = i;
+        }
+    }
+
+    let best_fits = archive_scores.iter().filter(|scores| {
+        scores.iter().all(|score| *score <= scores[best])
+    }).count();
+    let confidence = if sampled == 0 { 0.0 } else { best_fits as f32 / sampled as f32 };
+
+    Ok(ModeDetection { mode: MODES[best], confidence, sampled, scores: averages })
+}
+
+/// The fit of the archive for each mode, in the same order as `MODES`
+fn score_archive<R: DataReader>(data_reader: &R, file_reader: &mut FileReader, file: &Path, file_checks: FileChecks) -> Result<Option<Vec<f32>>> {
+    let set_name = models::get_set_from_file(&file.to_string_lossy());
+    if data_reader.get_game(set_name.as_str()).is_none() {
+        return Ok(None);
+    }
+
+    let archive_roms = file_reader.build_game_set(&file, file_checks)?.roms;
+    let mut scores = vec![];
+    for mode in MODES {
+        let expected = data_reader.get_romset_roms(set_name.as_str(), mode)?.into_iter()
+            .map(|rom| rom.file)
+            .filter(|rom| !matches!(&rom.status, Some(status) if status.to_lowercase() == "nodump"))
+            .collect::<Vec<_>>();
+        scores.push(fit(&expected, &archive_roms, file_checks));
+    }
+
+    Ok(Some(scores))
+}
+
+fn fit(expected: &[DataFile], found: &[DataFile], file_checks: FileChecks) -> f32 {
+    let total = expected.len() + found.len();
+    if total == 0 {
+        return 1.0;
+    }
+
+    let same = |a: &DataFile, b: &DataFile| a.info.deep_compare(&b.info, file_checks).unwrap_or(false);
+    let expected_found = expected.iter().filter(|rom| found.iter().any(|other| same(rom, other))).count();
+    let found_expected = found.iter().filter(|rom| expected.iter().any(|other| same(rom, other))).count();
+
+    (expected_found + found_expected) as f32 / total as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{reader::sqlite::DBReader, writer::sqlite::DBWriter, importer::DatImporter};
+    use rusqlite::{Connection, OpenFlags};
+    use super::*;
+
+    fn get_data_reader() -> Result<DBReader> {
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        let writer = DBWriter::from_connection(&mut conn, 100);
+        let mut importer = DatImporter::from_path(&Path::new("testdata").join("test.dat"), writer)?;
+        importer.load_dat()?;
+
+        Ok(DBReader::from_connection(conn))
+    }
+
+    #[test]
+    fn detects_split_collection() -> Result<()> {
+        let data_reader = get_data_reader()?;
+        let files = Path::new("testdata").join("split").read_dir()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<_>>();
+
+        let detection = detect_romset_mode(&data_reader, files, 100)?;
+
+        assert!(matches!(detection.mode, RomsetMode::Split));
+        assert!(detection.sampled > 0);
+        assert!(detection.confidence > 0.5);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+//! The roms of a set as the DAT has them, next to what a check found of each one, so the info of a set tells what's
+//! in the collection without looking up the report.
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::models::set::GameSet;
+
+use super::{formatter::{EnglishFormatter, ReportFormatter, ReportText}, scan_report::{RomLocatedAt, ScanReport, SetStatus}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RomOwnership {
+    InSet,
+    /// In the set with another name
+    ToRename,
+    /// Only in the files of other sets
+    InOtherFile,
+    Missing,
+    /// Missing, but it's in the ignore list
+    Ignored,
+    /// A bad dump the set doesn't need
+    Unneeded,
+    /// The check has the set but not the rom, usually because it was done with another mode
+    NotInReport,
+}
+
+impl From<&RomOwnership> for ReportText {
+    fn from(ownership: &RomOwnership) -> Self {
+        match ownership {
+            RomOwnership::InSet => ReportText::RomInSet,
+            RomOwnership::ToRename => ReportText::RomToRename,
+            RomOwnership::InOtherFile => ReportText::RomInOtherFile,
+            RomOwnership::Missing => ReportText::RomMissing,
+            RomOwnership::Ignored => ReportText::RomIgnored,
+            RomOwnership::Unneeded => ReportText::RomUnneeded,
+            RomOwnership::NotInReport => ReportText::RomNotInReport,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnedRom {
+    pub name: String,
+    pub ownership: RomOwnership,
+    /// Where the rom is when it's not in the set with its name, the name it has or the file and the name
+    pub found_as: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetOwnership {
+    pub set_name: String,
+    /// The date of the check
+    pub checked_at: String,
+    /// The status of the set in the check, none if the check didn't find it
+    pub status: Option<SetStatus>,
+    pub roms: Vec<OwnedRom>,
+    /// The roms found, in the set or in other files
+    pub roms_found: usize,
+}
+
+impl Display for SetOwnership {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_set_ownership(f, self)
+    }
+}
+
+impl SetOwnership {
+    /// The roms of the set in the order of the DAT, with where the check found them
+    pub fn new(game_set: &GameSet, report: &ScanReport) -> Self {
+        let set_name = game_set.game.name.to_string();
+        let set_report = report.sets.get(&set_name);
+        let roms = game_set.roms.iter().map(|rom| {
+            let (ownership, found_as) = match set_report {
+                Some(set_report) => match set_report.roms_available.iter().find(|(available, _)| available.name == rom.name) {
+                    Some((_, RomLocatedAt::InSet)) => (RomOwnership::InSet, None),
+                    Some((_, RomLocatedAt::InSetWrongName(name))) => (RomOwnership::ToRename, Some(name.to_string())),
+                    Some((_, RomLocatedAt::InOthers(locations))) => {
+                        let location = locations.iter().min_by_key(|location| (location.get_file(), location.get_with_name()));
+                        (RomOwnership::InOtherFile, location.map(|location| format!("{}/{}", location.get_file(), location.get_with_name())))
+                    },
+                    None if set_report.roms_missing.iter().any(|missing| missing.name == rom.name) => (RomOwnership::Missing, None),
+                    None if set_report.roms_ignored.iter().any(|ignored| ignored.name == rom.name) => (RomOwnership::Ignored, None),
+                    None if set_report.roms_unneeded.iter().any(|unneeded| unneeded.name == rom.name) => (RomOwnership::Unneeded, None),
+                    None => (RomOwnership::NotInReport, None),
+                },
+                None => (RomOwnership::Missing, None),
+            };
+            OwnedRom { name: rom.name.to_string(), ownership, found_as }
+        }).collect::<Vec<_>>();
+        let roms_found = roms.iter().filter(|rom| matches!(rom.ownership, RomOwnership::InSet | RomOwnership::ToRename | RomOwnership::InOtherFile)).count();
+
+        Self {
+            set_name,
+            checked_at: report.get_date_time_string().to_string(),
+            status: set_report.map(|set_report| set_report.is_complete()),
+            roms,
+            roms_found,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RomsetMode, data::{models::{file::{DataFile, DataFileInfo, FileType}, game::Game}, reporter::scan_report::RomLocation}};
+
+    use super::*;
+
+    fn rom(name: &str, crc: &str) -> DataFile {
+        let mut info = DataFileInfo::new(FileType::Rom);
+        info.crc = Some(crc.to_string());
+        DataFile::new(name, info)
+    }
+
+    #[test]
+    fn combines_the_set_with_the_check() {
+        let game_set = GameSet::new(Game::new("game1".to_string()), vec![rom("a.rom", "1111"), rom("b.rom", "2222"), rom("c.rom", "3333"), rom("d.rom", "4444")], vec![], vec![], vec![]);
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::NonMerged);
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "a.rom"), rom("a.rom", "1111"));
+        report.add_rom_for_set("game1", RomLocation::new("game1.zip", "wrong.rom"), rom("b.rom", "2222"));
+        report.add_rom_for_set("game1", RomLocation::new("game2.zip", "c.rom"), rom("c.rom", "3333"));
+        report.add_missing_roms_for_set("game1", vec![rom("d.rom", "4444")]);
+
+        let ownership = SetOwnership::new(&game_set, &report);
+        let roms = ownership.roms.iter().map(|rom| (rom.name.as_str(), rom.ownership, rom.found_as.as_deref())).collect::<Vec<_>>();
+        assert_eq!(vec![
+            ("a.rom", RomOwnership::InSet, None),
+            ("b.rom", RomOwnership::ToRename, Some("wrong.rom")),
+            ("c.rom", RomOwnership::InOtherFile, Some("game2.zip/c.rom")),
+            ("d.rom", RomOwnership::Missing, None),
+        ], roms);
+        assert_eq!(3, ownership.roms_found);
+        assert_eq!(Some(SetStatus::INCOMPLETE), ownership.status);
+
+        let ownership = SetOwnership::new(&GameSet::new(Game::new("game2".to_string()), vec![rom("e.rom", "5555")], vec![], vec![], vec![]), &report);
+        assert_eq!(None, ownership.status);
+        assert_eq!(RomOwnership::Missing, ownership.roms[0].ownership);
+    }
+}
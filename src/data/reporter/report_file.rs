@@ -0,0 +1,182 @@
+//! Reads and writes the reports, compressed with zstd after a magic header. The reports saved before the compression
+//! have no header and are read as they are.
+use std::{fs::{self, File}, io::{self, BufReader, BufWriter, Cursor, Read, Write}, path::Path};
+
+use anyhow::Result;
+
+use super::scan_report::ScanReport;
+
+/// Written before the compressed report, the last byte is the version of the file format
+pub const REPORT_MAGIC: &[u8; 8] = b"ROMSTRP\x01";
+const COMPRESSION_LEVEL: i32 = 3;
+/// How many bytes are read or written between the updates of the progress
+const PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// Gets the progress of a report being saved or loaded, in bytes of the report when saving and of the file when loading
+pub trait ReportFileReporter {
+    fn start(&mut self, total_bytes: u64, saving: bool);
+    fn update_position(&mut self, bytes: u64);
+    fn finish(&mut self);
+}
+
+/// Counts the bytes going through, updating the reporter every `PROGRESS_STEP` bytes
+struct ProgressCounter<'a, T> {
+    inner: T,
+    bytes: u64,
+    reported: u64,
+    reporter: Option<&'a mut dyn ReportFileReporter>,
+}
+
+impl<'a, T> ProgressCounter<'a, T> {
+    fn new(inner: T, reporter: Option<&'a mut dyn ReportFileReporter>) -> Self {
+        Self { inner, bytes: 0, reported: 0, reporter }
+    }
+
+    fn add_bytes(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+        if self.bytes - self.reported >= PROGRESS_STEP {
+            self.reported = self.bytes;
+            if let Some(reporter) = self.reporter.as_mut() {
+                reporter.update_position(self.bytes);
+            }
+        }
+    }
+}
+
+impl<'a, T: Write> Write for ProgressCounter<'a, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.add_bytes(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a, T: Read> Read for ProgressCounter<'a, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.add_bytes(read);
+        Ok(read)
+    }
+}
+
+pub fn write_report(output_file: &Path, report: &ScanReport, mut reporter: Option<&mut dyn ReportFileReporter>) -> Result<()> {
+    if let Some(reporter) = reporter.as_mut() {
+        reporter.start(bincode::serialized_size(report)?, true);
+    }
+
+    let mut file = BufWriter::new(File::create(output_file)?);
+    file.write_all(REPORT_MAGIC)?;
+    let encoder = zstd::Encoder::new(file, COMPRESSION_LEVEL)?;
+    // Buffered before the counter, bincode writes the fields one by one
+    let mut writer = BufWriter::new(ProgressCounter::new(encoder, reporter));
+    bincode::serialize_into(&mut writer, report)?;
+
+    let counter = writer.into_inner().map_err(|e| e.into_error())?;
+    if let Some(reporter) = counter.reporter {
+        reporter.finish();
+    }
+    counter.inner.finish()?.flush()?;
+    Ok(())
+}
+
+pub fn read_report(report_file: &Path, mut reporter: Option<&mut dyn ReportFileReporter>) -> Result<ScanReport> {
+    if let Some(reporter) = reporter.as_mut() {
+        reporter.start(fs::metadata(report_file)?.len(), false);
+    }
+
+    let mut file = ProgressCounter::new(File::open(report_file)?, reporter);
+    let mut header = vec![];
+    (&mut file).take(REPORT_MAGIC.len() as u64).read_to_end(&mut header)?;
+    let report = if header == REPORT_MAGIC {
+        let mut decoder = zstd::Decoder::new(&mut file)?;
+        bincode::deserialize_from(BufReader::new(&mut decoder))?
+    } else {
+        bincode::deserialize_from(BufReader::new(Cursor::new(header).chain(&mut file)))?
+    };
+
+    if let Some(reporter) = file.reporter {
+        reporter.finish();
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::{RomsetMode, data::models::{file::{DataFile, DataFileInfo, FileType}, game::Game}};
+    use super::*;
+
+    #[derive(Default)]
+    struct TestReporter {
+        total_bytes: u64,
+        bytes: u64,
+        finished: bool,
+    }
+
+    impl ReportFileReporter for TestReporter {
+        fn start(&mut self, total_bytes: u64, _saving: bool) {
+            self.total_bytes = total_bytes;
+        }
+
+        fn update_position(&mut self, bytes: u64) {
+            self.bytes = bytes;
+        }
+
+        fn finish(&mut self) {
+            self.finished = true;
+        }
+    }
+
+    fn get_report() -> ScanReport {
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::NonMerged);
+        for i in 0..20000 {
+            let name = format!("game{}", i);
+            report.reference_with_game(Game::new(name.to_owned()));
+            report.add_missing_rom_for_set(&name, DataFile::new(format!("rom{}.bin", i).as_str(), DataFileInfo::new(FileType::Rom)));
+        }
+        report
+    }
+
+    #[test]
+    fn saves_the_report_compressed() -> Result<()> {
+        let report_file = env::temp_dir().join(format!("romst_report_file_{}.rst", std::process::id()));
+        let report = get_report();
+
+        let mut reporter = TestReporter::default();
+        write_report(&report_file, &report, Some(&mut reporter))?;
+        assert!(reporter.finished);
+        assert_eq!(bincode::serialized_size(&report)?, reporter.total_bytes);
+        assert!(reporter.bytes >= PROGRESS_STEP);
+
+        let contents = fs::read(&report_file)?;
+        assert!(contents.starts_with(REPORT_MAGIC));
+        assert!((contents.len() as u64) < reporter.total_bytes);
+
+        let mut reporter = TestReporter::default();
+        let loaded = read_report(&report_file, Some(&mut reporter));
+        fs::remove_file(&report_file)?;
+        assert_eq!(report.get_set_statuses(), loaded?.get_set_statuses());
+        assert_eq!(contents.len() as u64, reporter.total_bytes);
+        assert!(reporter.finished);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_the_reports_without_compression() -> Result<()> {
+        let report_file = env::temp_dir().join(format!("romst_report_plain_{}.rst", std::process::id()));
+        let report = get_report();
+        fs::write(&report_file, bincode::serialize(&report)?)?;
+
+        let loaded = read_report(&report_file, None);
+        fs::remove_file(&report_file)?;
+        assert_eq!(report.get_set_statuses(), loaded?.get_set_statuses());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+//! Audits of a collection already verified that only hash a random sample of the zips named as a set, the rest are
+//! screened by their directory like in a quick check. A zip sampled with a directory matching the DAT but with roms
+//! that don't match it once hashed would have been accepted by the screening, so the ratio of them in the sample is
+//! extrapolated to the zips screened, with the bounds it has with a 95% confidence.
+use std::{collections::HashSet, fmt::Display, path::PathBuf, str::FromStr, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{data::models::file::DataFile, err, error::RomstError, filesystem::FileChecks};
+
+use super::formatter::{EnglishFormatter, ReportFormatter};
+
+/// The z score of a 95% confidence
+const CONFIDENCE_Z: f64 = 1.96;
+
+/// The share of the zips hashed, and the seed they are picked with, the same seed picks the same zips again
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AuditSample {
+    /// From 0 to 1
+    pub ratio: f64,
+    pub seed: u64,
+}
+
+impl AuditSample {
+    /// A sample picked with a seed taken from the clock
+    pub fn new(ratio: f64) -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or_default();
+        Self { ratio, seed }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Picks the zips hashed, at least one if there are any
+    pub fn select(&self, candidates: &[&PathBuf]) -> HashSet<PathBuf> {
+        let mut candidates = candidates.to_vec();
+        candidates.sort();
+        let sample_size = ((candidates.len() as f64 * self.ratio).ceil() as usize).clamp(candidates.len().min(1), candidates.len());
+
+        // The first ones of a partial shuffle
+        let mut state = self.seed;
+        for i in 0..sample_size {
+            let j = i + (split_mix(&mut state) % (candidates.len() - i) as u64) as usize;
+            candidates.swap(i, j);
+        }
+        candidates.into_iter().take(sample_size).cloned().collect()
+    }
+}
+
+impl FromStr for AuditSample {
+    type Err = anyhow::Error;
+
+    /// A percentage, like `5%`, the `%` is optional
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let percentage = s.trim().trim_end_matches('%').trim().parse::<f64>().ok().filter(|percentage| *percentage > 0.0 && *percentage <= 100.0);
+        match percentage {
+            Some(percentage) => Ok(AuditSample::new(percentage / 100.0)),
+            None => err!(RomstError::GenericError { message: format!("Wrong sample `{}`, it has to be a percentage over 0 and up to 100, like `5%`", s) }),
+        }
+    }
+}
+
+/// SplitMix64, enough to pick the zips evenly
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// What the audit did with a zip named as a set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOutcome {
+    /// Not sampled, accepted by its directory
+    Screened,
+    /// Sampled, the roms hashed match the directory
+    Matched,
+    /// Sampled, the directory matches the DAT but the roms hashed don't, or couldn't be read
+    Mismatched,
+    /// Sampled, the directory doesn't match the DAT, so the screening would have hashed it too
+    Rejected,
+}
+
+impl AuditOutcome {
+    /// The outcome of a zip sampled, from the roms its directory was screened with and the roms hashed
+    pub fn from_sampled(screened: Option<&[DataFile]>, hashed: Option<&[DataFile]>, file_checks: FileChecks) -> Self {
+        match (screened, hashed) {
+            (None, _) => AuditOutcome::Rejected,
+            (Some(screened), Some(hashed)) if screened.iter().all(|screened_rom| hashed.iter().any(|hashed_rom| {
+                hashed_rom.name == screened_rom.name && hashed_rom.info.deep_compare(&screened_rom.info, file_checks).unwrap_or(false)
+            })) => AuditOutcome::Matched,
+            (Some(_), _) => AuditOutcome::Mismatched,
+        }
+    }
+}
+
+/// The share of the zips screened expected to have a mismatch
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MismatchEstimate {
+    /// Of the zips sampled with a directory matching the DAT
+    pub rate: f64,
+    /// The bounds of the rate with a 95% confidence
+    pub rate_lower: f64,
+    pub rate_upper: f64,
+    /// The zips screened expected to have a mismatch, and the most there would be with a 95% confidence
+    pub screened_expected: u64,
+    pub screened_at_most: u64,
+}
+
+impl MismatchEstimate {
+    /// The Wilson score interval of the rate, it holds with small samples and no mismatches found
+    fn new(mismatches: usize, sampled: usize, screened: usize) -> Option<Self> {
+        if sampled == 0 {
+            return None;
+        }
+        let n = sampled as f64;
+        let rate = mismatches as f64 / n;
+        let z2 = CONFIDENCE_Z * CONFIDENCE_Z;
+        let denominator = 1.0 + z2 / n;
+        let center = (rate + z2 / (2.0 * n)) / denominator;
+        let half_width = CONFIDENCE_Z * (rate * (1.0 - rate) / n + z2 / (4.0 * n * n)).sqrt() / denominator;
+        let rate_lower = (center - half_width).max(0.0);
+        let rate_upper = (center + half_width).min(1.0);
+
+        Some(Self {
+            rate,
+            rate_lower,
+            rate_upper,
+            screened_expected: (rate * screened as f64).round() as u64,
+            screened_at_most: (rate_upper * screened as f64).ceil() as u64,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleAuditReport {
+    pub sample: AuditSample,
+    /// The zips accepted by their directory without hashing them
+    pub screened: usize,
+    /// The zips hashed for the audit
+    pub sampled: usize,
+    /// The zips sampled with a directory matching the DAT, the estimate is made from them
+    pub sampled_screenable: usize,
+    /// The zips sampled with a directory matching the DAT but not their roms
+    pub mismatches: Vec<String>,
+    /// None until a zip sampled has a directory matching the DAT
+    pub estimate: Option<MismatchEstimate>,
+}
+
+impl Display for SampleAuditReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_sample_audit(f, self)
+    }
+}
+
+impl SampleAuditReport {
+    pub fn new(sample: AuditSample) -> Self {
+        Self { sample, screened: 0, sampled: 0, sampled_screenable: 0, mismatches: vec![], estimate: None }
+    }
+
+    pub fn add_outcome<S>(&mut self, file: S, outcome: AuditOutcome) where S: Into<String> {
+        match outcome {
+            AuditOutcome::Screened => self.screened += 1,
+            AuditOutcome::Matched => {
+                self.sampled += 1;
+                self.sampled_screenable += 1;
+            },
+            AuditOutcome::Mismatched => {
+                self.sampled += 1;
+                self.sampled_screenable += 1;
+                self.mismatches.push(file.into());
+                self.mismatches.sort();
+            },
+            AuditOutcome::Rejected => self.sampled += 1,
+        }
+        self.estimate = MismatchEstimate::new(self.mismatches.len(), self.sampled_screenable, self.screened);
+    }
+
+    /// Adds the zips of the audit of other files, the sample of this one is kept
+    pub fn merge(&mut self, other: SampleAuditReport) {
+        self.screened += other.screened;
+        self.sampled += other.sampled;
+        self.sampled_screenable += other.sampled_screenable;
+        self.mismatches.extend(other.mismatches);
+        self.mismatches.sort();
+        self.mismatches.dedup();
+        self.estimate = MismatchEstimate::new(self.mismatches.len(), self.sampled_screenable, self.screened);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::models::file::{DataFileInfo, FileType};
+
+    use super::*;
+
+    fn rom(name: &str, crc: &str) -> DataFile {
+        let mut info = DataFileInfo::new(FileType::Rom);
+        info.crc = Some(crc.to_string());
+        info.size = Some(1024);
+        DataFile::new(name, info)
+    }
+
+    #[test]
+    fn picks_the_same_sample_with_the_same_seed() -> anyhow::Result<()> {
+        let paths = (0..200).map(|i| PathBuf::from(format!("game{}.zip", i))).collect::<Vec<_>>();
+        let candidates = paths.iter().collect::<Vec<_>>();
+        let sample = "5%".parse::<AuditSample>()?.with_seed(42);
+        let selected = sample.select(&candidates);
+        assert_eq!(10, selected.len());
+        assert_eq!(selected, sample.select(&candidates.iter().rev().cloned().collect::<Vec<_>>()));
+        assert_ne!(selected, sample.with_seed(43).select(&candidates));
+        assert_eq!(1, sample.select(&candidates[..3]).len());
+        assert!(sample.select(&[]).is_empty());
+        assert!("0%".parse::<AuditSample>().is_err());
+        assert!("150".parse::<AuditSample>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn estimates_the_mismatches_of_the_zips_screened() {
+        let screened = vec![rom("a.rom", "1111"), rom("b.rom", "2222")];
+        let hashed = vec![rom("a.rom", "1111"), rom("b.rom", "2222")];
+        let corrupt = vec![rom("a.rom", "1111"), rom("b.rom", "3333")];
+        assert_eq!(AuditOutcome::Matched, AuditOutcome::from_sampled(Some(&screened), Some(&hashed), FileChecks::CRC));
+        assert_eq!(AuditOutcome::Mismatched, AuditOutcome::from_sampled(Some(&screened), Some(&corrupt), FileChecks::CRC));
+        assert_eq!(AuditOutcome::Mismatched, AuditOutcome::from_sampled(Some(&screened), None, FileChecks::CRC));
+        assert_eq!(AuditOutcome::Rejected, AuditOutcome::from_sampled(None, Some(&hashed), FileChecks::CRC));
+
+        let mut report = SampleAuditReport::new(AuditSample::new(0.1));
+        for i in 0..900 {
+            report.add_outcome(format!("screened{}.zip", i), AuditOutcome::Screened);
+        }
+        for i in 0..99 {
+            report.add_outcome(format!("sampled{}.zip", i), AuditOutcome::Matched);
+        }
+        report.add_outcome("rejected.zip", AuditOutcome::Rejected);
+        let estimate = report.estimate.unwrap();
+        assert_eq!(0.0, estimate.rate);
+        assert_eq!(0, estimate.screened_expected);
+        // About the rule of three, 3 / 99 of the zips screened
+        assert_eq!(34, estimate.screened_at_most);
+
+        report.add_outcome("corrupt.zip", AuditOutcome::Mismatched);
+        let estimate = report.estimate.unwrap();
+        assert_eq!(vec!["corrupt.zip".to_string()], report.mismatches);
+        assert_eq!(101, report.sampled);
+        assert_eq!(0.01, estimate.rate);
+        assert_eq!(9, estimate.screened_expected);
+        assert!(estimate.rate_lower > 0.0 && estimate.rate_lower < 0.01 && estimate.rate_upper > 0.05);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::{HashMap, HashSet, hash_map::Entry}, fmt::Display};
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet, hash_map::Entry}, fmt::Display, sync::Arc};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use anyhow::Result;
@@ -6,38 +6,117 @@ use anyhow::anyhow;
 
 use log::debug;
 
-use crate::{RomsetMode, data::models::{self, file::DataFile, game::Game}};
+use crate::{RomsetMode, filesystem::{FileChecks, paths}, data::{aliases::SetAliases, filter::GameFilter, ignored_roms::IgnoredRoms, fixer::FixPlan, rebuilder::RebuildPlan, models::{archive::{ArchiveInfo, ArchiveIssue}, dat_info::Packing, file::{DataFile, DumpQuality}, game::Game}}};
+
+use super::{formatter::{EnglishFormatter, ReportFormatter}, sample_audit::SampleAuditReport};
+
+/// The version of the serialized reports, it changes when a field is renamed, removed or changes its meaning
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Whether reading the file again may work, a transient error is retried by the scan workers and again with a check
+/// retrying the errors of a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileErrorKind {
+    Transient,
+    Permanent,
+}
+
+/// A file that couldn't be read, with the reason
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileError {
+    pub kind: FileErrorKind,
+    pub message: String,
+}
+
+/// How the entries of a scanned file are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    /// A zip or a rar archive
+    Archive,
+    /// A folder, its entries are the files in it
+    Folder,
+    /// Loose files assembled into a set, named as the set, its entries are the files in the scanned directory
+    Loose,
+}
+
+/// What the scan found in a file, so it can be cleaned apart from the other files of its set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScannedFile {
+    pub kind: FileKind,
+    pub entries: usize,
+    /// The entries with spare roms or unknown files
+    pub spare: BTreeSet<String>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ScanReport {
+    schema_version: u32,
     root_directory: Option<String>,
     date_time: String,
     rom_mode: RomsetMode,
     pub sets: HashMap<String, SetReport>,
     pub ignored: Vec<String>,
+    pub archives: HashMap<String, ArchiveInfo>,
+    /// Only filled when the archives are verified
+    #[serde(default)]
+    pub corrupt: HashMap<String, Vec<ArchiveIssue>>,
+    /// The zips split in several files, with the files of their parts, they need to be joined to be checked
+    #[serde(default)]
+    pub split_archives: HashMap<String, Vec<String>>,
+    /// The files not read because the check was interrupted, the report is partial if there are any
+    #[serde(default)]
+    pub unscanned: Vec<String>,
+    /// The files that couldn't be read, after the retries of the transient errors
+    #[serde(default)]
+    pub errors: BTreeMap<String, FileError>,
+    /// Only when the check hashed a sample of the zips, with the mismatches expected in the ones screened
+    #[serde(default)]
+    pub sample_audit: Option<SampleAuditReport>,
+    /// The packing the DAT expects, if it sets one
+    #[serde(default)]
+    packing: Option<Packing>,
+    /// The archives and folders not packed as the DAT expects
+    #[serde(default)]
+    pub wrong_packing: Vec<String>,
+    /// The files named as an alias of their set, with the name of the set they need to be renamed to
+    #[serde(default)]
+    pub alias_renames: BTreeMap<String, String>,
+    /// The files read, with their spare and unknown entries, empty in the reports written before they were recorded
+    #[serde(default)]
+    pub files: BTreeMap<String, ScannedFile>,
+    /// The hashes the roms were matched by, unknown in the reports written before they were recorded
+    #[serde(default)]
+    file_checks: Option<FileChecks>,
+    #[serde(skip)]
+    file_names: NameInterner,
+    #[serde(skip)]
+    set_aliases: SetAliases,
+    #[serde(skip)]
+    ignored_roms: IgnoredRoms,
 }
 
-impl Display for ScanReport {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(path) = &self.root_directory {
-            writeln!(f, "Scanned dir: {}", path)?;
-        }
-        writeln!(f, "Date of the report: {}", self.date_time)?;
-        writeln!(f, "Mode: {}", self.rom_mode)?;
-        writeln!(f)?;
-        if !self.ignored.is_empty() {
-            writeln!(f, "Ignored:")?;
-            for file in &self.ignored {
-                writeln!(f, "- {}", file)?;
-            }
-            writeln!(f)?;
-        }
+/// Keeps a single copy of each file name, shared by the locations of all the roms found in the file
+#[derive(Debug, Default)]
+struct NameInterner {
+    names: HashSet<Arc<str>>,
+}
 
-        for set in &self.sets {
-            let s = set.1; 
-            writeln!(f, "{}", s)?;
+impl NameInterner {
+    fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(interned) = self.names.get(name) {
+            return Arc::clone(interned);
         }
-        Ok(())
+        let interned: Arc<str> = Arc::from(name);
+        self.names.insert(Arc::clone(&interned));
+        interned
+    }
+}
+
+impl Display for ScanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_scan_report(f, self)
     }
 }
 
@@ -46,10 +125,25 @@ impl ScanReport {
         let now = Utc::now();
         
         Self {
-            root_directory,
+            schema_version: REPORT_SCHEMA_VERSION,
+            root_directory: root_directory.map(paths::normalize_directory),
             date_time: now.to_rfc3339(),
             rom_mode, sets: HashMap::new(),
-            ignored: vec![]
+            ignored: vec![],
+            archives: HashMap::new(),
+            corrupt: HashMap::new(),
+            split_archives: HashMap::new(),
+            unscanned: vec![],
+            errors: BTreeMap::new(),
+            sample_audit: None,
+            packing: None,
+            wrong_packing: vec![],
+            alias_renames: BTreeMap::new(),
+            files: BTreeMap::new(),
+            file_checks: None,
+            file_names: NameInterner::default(),
+            set_aliases: SetAliases::default(),
+            ignored_roms: IgnoredRoms::default(),
         }
     }
 
@@ -59,29 +153,180 @@ impl ScanReport {
             .map(|t| t.with_timezone(&Utc))
     }
 
+    pub fn get_schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    pub fn get_date_time_string(&self) -> &str {
+        &self.date_time
+    }
+
+    pub fn get_root_directory(&self) -> Option<&str> {
+        self.root_directory.as_deref()
+    }
+
+    /// Removes the sets of the games not matching the filter, sets not referencing a game are kept
+    pub fn retain_sets(&mut self, game_filter: &GameFilter) {
+        self.sets.retain(|_name, set| set.is_included(game_filter));
+    }
+
+    /// The operations to fix the fixeable sets, renaming and copying roms into the set archives
+    /// The status of each set referencing a game, the sets made only of the roms of a file are left out
+    pub fn get_set_statuses(&self) -> BTreeMap<String, SetStatus> {
+        self.sets.values()
+            .filter_map(|set| match &set.reference {
+                SetReference::Game(game) => Some((game.name.clone(), set.is_complete())),
+                SetReference::FileName(_name) => None,
+            })
+            .collect()
+    }
+
+    pub fn build_fix_plan(&self) -> FixPlan {
+        FixPlan::from_report(self)
+    }
+
+    pub fn build_rebuild_plan<S>(&self, target_directory: S, throughput: f64, packing: Option<Packing>) -> RebuildPlan where S: AsRef<str> {
+        RebuildPlan::from_report(self, target_directory, throughput, packing)
+    }
+
+    pub fn get_rom_mode(&self) -> RomsetMode {
+        self.rom_mode
+    }
+
+    pub fn get_packing(&self) -> Option<Packing> {
+        self.packing
+    }
+
+    pub fn set_packing(&mut self, packing: Option<Packing>) {
+        self.packing = packing;
+    }
+
+    pub fn get_file_checks(&self) -> Option<FileChecks> {
+        self.file_checks
+    }
+
+    pub fn set_file_checks(&mut self, file_checks: FileChecks) {
+        self.file_checks = Some(file_checks);
+    }
+
+    /// The roms were matched only by their crc and size, so a different rom with the same ones is taken as the right one
+    pub fn is_weak_matching(&self) -> bool {
+        self.file_checks.map(|file_checks| file_checks.is_weak()).unwrap_or(false)
+    }
+
+    /// The files named as an alias of a set belong to it
+    pub fn set_set_aliases(&mut self, set_aliases: SetAliases) {
+        self.set_aliases = set_aliases;
+    }
+
+    /// The roms in the list are not required, when missing they are reported as ignored
+    pub fn set_ignored_roms(&mut self, ignored_roms: IgnoredRoms) {
+        self.ignored_roms = ignored_roms;
+    }
+
+    /// The roms missing in all the sets that are ignored
+    pub fn get_ignored_roms_count(&self) -> usize {
+        self.sets.values().map(|set| set.roms_ignored.len()).sum()
+    }
+
+    pub fn add_wrong_packing<S>(&mut self, file: S) where S: Into<String> {
+        self.wrong_packing.push(paths::normalize_file_key(&file.into()));
+    }
+
     pub fn add_ignored<S>(&mut self, file: S) where S: Into<String> {
-        self.ignored.push(file.into());
+        self.ignored.push(paths::normalize_file_key(&file.into()));
+    }
+
+    pub fn add_unscanned<S>(&mut self, file: S) where S: Into<String> {
+        self.unscanned.push(paths::normalize_file_key(&file.into()));
+    }
+
+    /// If the check was interrupted before reading all the files
+    pub fn is_interrupted(&self) -> bool {
+        !self.unscanned.is_empty()
+    }
+
+    pub fn add_error<S>(&mut self, file: S, error: FileError) where S: Into<String> {
+        self.errors.insert(paths::normalize_file_key(&file.into()), error);
+    }
+
+    /// The files that failed with a transient error, they can be checked again with `merge_retried`
+    pub fn get_retriable_files(&self) -> Vec<&str> {
+        self.errors.iter().filter(|(_, error)| error.kind == FileErrorKind::Transient).map(|(file, _)| file.as_str()).collect()
+    }
+
+    /// Adds the check of the files that failed with a transient error, the files failing again keep the new error. The
+    /// root directory is kept, as the files retried are the ones of this report
+    pub fn merge_retried(&mut self, retried: ScanReport) -> Result<()> {
+        for file in self.get_retriable_files().into_iter().map(|file| file.to_string()).collect::<Vec<_>>() {
+            self.errors.remove(&file);
+        }
+        let root_directory = self.root_directory.take();
+        let result = self.merge(retried);
+        self.root_directory = root_directory;
+        result
+    }
+
+    /// A file read by the scan, before its roms are added to the sets
+    pub fn add_scanned_file<S>(&mut self, file: S, kind: FileKind, entries: usize) where S: Into<String> {
+        self.files.insert(paths::normalize_file_key(&file.into()), ScannedFile { kind, entries, spare: BTreeSet::new() });
+    }
+
+    pub fn add_archive_info<S>(&mut self, file: S, archive_info: ArchiveInfo) where S: Into<String> {
+        self.archives.insert(paths::normalize_file_key(&file.into()), archive_info);
+    }
+
+    /// The archives that are not in a canonical format, and need to be rezipped
+    pub fn add_split_archive<S>(&mut self, file: S, parts: Vec<String>) where S: Into<String> {
+        self.split_archives.entry(paths::normalize_file_key(&file.into())).or_default().extend(parts);
+    }
+
+    pub fn add_corrupt_archive<S>(&mut self, file: S, issues: Vec<ArchiveIssue>) where S: Into<String> {
+        self.corrupt.entry(paths::normalize_file_key(&file.into())).or_default().extend(issues);
+    }
+
+    pub fn get_non_canonical_archives(&self) -> Vec<(&String, &ArchiveInfo)> {
+        let mut archives = self.archives.iter()
+            .filter(|(_file, archive_info)| !archive_info.format.is_canonical())
+            .collect::<Vec<_>>();
+        archives.sort_by(|a, b| a.0.cmp(b.0));
+        archives
+    }
+
+    /// The name of the file shared with the locations already in the report, to build the locations of the roms in the file
+    pub fn intern_file_name(&mut self, file: &str) -> Arc<str> {
+        // Only the keys are interned, so a name already there doesn't need to be normalized again
+        match self.file_names.names.get(file) {
+            Some(interned) => Arc::clone(interned),
+            None => self.file_names.intern(&paths::normalize_file_key(file)),
+        }
     }
 
     pub fn add_rom_for_set<S>(&mut self, set_name: S, location: RomLocation, rom: DataFile) where S: AsRef<str> {
+        let location = RomLocation { file: self.intern_file_name(&location.file), ..location };
+        let in_set = self.set_aliases.does_file_belong_to_set(&location.file, set_name.as_ref());
         let set = self.sets.entry(set_name.as_ref().to_owned()).or_insert_with(|| SetReport::new(set_name.as_ref()));
         match &rom.status {
             Some(status) if status.to_lowercase() == "nodump" => {
                 set.roms_unneeded.insert(rom);
             }
             _ => {
-                set.add_set_rom(location, rom);
+                set.add_located_rom(location, rom, in_set);
             }
         }
     }
 
     pub fn add_missing_roms_for_set<I, S>(&mut self, set_name: S, roms: I) where I: IntoIterator<Item = DataFile>, S: AsRef<str> {
+        let ignored_roms = &self.ignored_roms;
         let set = self.sets.entry(set_name.as_ref().to_owned()).or_insert_with(|| SetReport::new(set_name.as_ref()));
         roms.into_iter().for_each(|rom| {
             match &rom.status {
                 Some(status) if status.to_lowercase() == "nodump" => {
                     set.roms_unneeded.insert(rom);
                 }
+                _ if ignored_roms.is_ignored(set_name.as_ref(), &rom) => {
+                    set.roms_ignored.insert(rom);
+                }
                 _ => {
                     set.add_missing_rom(rom);
                 }
@@ -90,34 +335,68 @@ impl ScanReport {
     }
 
     pub fn add_missing_rom_for_set<S>(&mut self, set_name: S, rom: DataFile) where S: AsRef<str> {
+        let ignored_roms = &self.ignored_roms;
         let set = self.sets.entry(set_name.as_ref().to_owned()).or_insert_with(|| SetReport::new(set_name.as_ref()));
         match &rom.status {
             Some(status) if status.to_lowercase() == "nodump" => {
                 set.roms_unneeded.insert(rom);
             }
+            _ if ignored_roms.is_ignored(set_name.as_ref(), &rom) => {
+                set.roms_ignored.insert(rom);
+            }
             _ => {
                 set.add_missing_rom(rom);
             }
         }
     }
 
+    /// A disk of the set found in its folder, with the name of the file in the folder
+    pub fn add_disk_for_set<S>(&mut self, set_name: S, disk: DataFile, file_name: &str) where S: AsRef<str> {
+        let set = self.sets.entry(set_name.as_ref().to_owned()).or_insert_with(|| SetReport::new(set_name.as_ref()));
+        set.add_set_disk(disk, file_name);
+    }
+
+    /// The disks without a dump are not expected, so they are never missing
+    pub fn add_missing_disks_for_set<I, S>(&mut self, set_name: S, disks: I) where I: IntoIterator<Item = DataFile>, S: AsRef<str> {
+        let set = self.sets.entry(set_name.as_ref().to_owned()).or_insert_with(|| SetReport::new(set_name.as_ref()));
+        disks.into_iter()
+            .filter(|disk| !matches!(&disk.status, Some(status) if status.to_lowercase() == "nodump"))
+            .for_each(|disk| {
+                if !set.disks_available.contains_key(&disk) {
+                    set.disks_missing.insert(disk);
+                }
+            });
+    }
+
+    /// The file is the one of its set, a file named as an alias of the set is reported to be renamed
     pub fn set_in_file<S>(&mut self, source_file: S) where S: AsRef<str> {
-        let set_name = models::get_set_from_file(source_file.as_ref());
+        let set_name = self.set_aliases.get_set_from_file(source_file.as_ref());
+        if let Some(rename) = self.set_aliases.get_rename_for_set(source_file.as_ref(), &set_name) {
+            self.alias_renames.insert(paths::normalize_file_key(source_file.as_ref()), rename);
+        }
         self.sets.entry(set_name.clone()).or_insert_with(|| SetReport::new(set_name)).in_file = true;
     }
 
     pub fn add_unknown_files<I, S>(&mut self, files: I, source_file: S) where I: IntoIterator<Item = DataFile>, S: AsRef<str> {
-        let set_name = models::get_set_from_file(source_file.as_ref());
+        let set_name = self.set_aliases.get_set_from_file(source_file.as_ref());
         let set = self.sets.entry(set_name.clone()).or_insert_with(|| SetReport::new(set_name));
+        let mut scanned_file = self.files.get_mut(&paths::normalize_file_key(source_file.as_ref()));
         for file in files {
+            if let Some(scanned_file) = scanned_file.as_mut() {
+                scanned_file.spare.insert(file.name.clone());
+            }
             set.unknown.push(file);
         }
     }
 
     pub fn add_roms_to_spare<I, S>(&mut self, files: I, source_file: S) where I: IntoIterator<Item = DataFile>, S: AsRef<str> {
-        let set_name = models::get_set_from_file(source_file.as_ref());
+        let set_name = self.set_aliases.get_set_from_file(source_file.as_ref());
         let set = self.sets.entry(set_name.clone()).or_insert_with(|| SetReport::new(set_name));
+        let mut scanned_file = self.files.get_mut(&paths::normalize_file_key(source_file.as_ref()));
         files.into_iter().for_each(|rom| {
+            if let Some(scanned_file) = scanned_file.as_mut() {
+                scanned_file.spare.insert(rom.name.clone());
+            }
             set.roms_to_spare.insert(rom);
         });
     }
@@ -132,11 +411,7 @@ impl ScanReport {
         let set = self.sets.get(set_name.as_ref());
         match set {
             Some(set) => {
-                if let SetReference::Game(_) = set.reference {
-                    true
-                } else {
-                    false
-                }
+                matches!(set.reference, SetReference::Game(_))
             }
             None => false
         }
@@ -144,11 +419,77 @@ impl ScanReport {
 
     pub fn add_dependencies<S>(&mut self, set_name: S, dependencies: Vec<String>) where S: AsRef<str> {
         let set = self.sets.entry(set_name.as_ref().to_owned()).or_insert_with(|| SetReport::new(set_name.as_ref()));
-        set.device_dependencies.extend(dependencies.into_iter());
+        set.device_dependencies.extend(dependencies);
+    }
+
+    /// Adds the scan of another report, both need the same romset mode. A set in both has the roms found by any of
+    /// them, so it gets the best status of the two, and the roms found in other files keep the locations of both.
+    /// The date is the latest one, the root directory is kept only if both scanned the same, and for a file in both
+    /// reports the archive info of `other` replaces this one
+    pub fn merge(&mut self, other: ScanReport) -> Result<()> {
+        if self.rom_mode != other.rom_mode {
+            return Err(anyhow!("The reports can't be merged, one is checked as {} and the other as {}", self.rom_mode, other.rom_mode));
+        }
+
+        if self.root_directory != other.root_directory {
+            self.root_directory = None;
+        }
+        if other.get_date_time().ok() > self.get_date_time().ok() {
+            self.date_time = other.date_time;
+        }
+        self.packing = self.packing.or(other.packing);
+        // The merged report is as trustworthy as the weakest of the two
+        self.file_checks = match (self.file_checks, other.file_checks) {
+            (Some(file_checks), Some(other_checks)) => Some(file_checks & other_checks),
+            _ => None,
+        };
+
+        for (set_name, mut other_set) in other.sets {
+            for located_at in other_set.roms_available.values_mut() {
+                if let RomLocatedAt::InOthers(locations) = located_at {
+                    for location in locations.iter_mut() {
+                        location.file = self.intern_file_name(&location.file);
+                    }
+                }
+            }
+            match self.sets.entry(set_name) {
+                Entry::Occupied(mut entry) => entry.get_mut().merge(other_set),
+                Entry::Vacant(entry) => {
+                    entry.insert(other_set);
+                }
+            }
+        }
+
+        merge_files(&mut self.ignored, other.ignored);
+        merge_files(&mut self.unscanned, other.unscanned);
+        self.errors.extend(other.errors);
+        self.sample_audit = match (self.sample_audit.take(), other.sample_audit) {
+            (Some(mut sample_audit), Some(other_audit)) => {
+                sample_audit.merge(other_audit);
+                Some(sample_audit)
+            },
+            (sample_audit, other_audit) => sample_audit.or(other_audit),
+        };
+        merge_files(&mut self.wrong_packing, other.wrong_packing);
+        self.alias_renames.extend(other.alias_renames);
+        self.archives.extend(other.archives);
+        self.files.extend(other.files);
+        self.corrupt.extend(other.corrupt);
+        self.split_archives.extend(other.split_archives);
+
+        Ok(())
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Adds the files not in the list yet, sorted
+fn merge_files(files: &mut Vec<String>, other: Vec<String>) {
+    files.extend(other);
+    files.sort();
+    files.dedup();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "SetReportSchema", from = "SetReportSchema")]
 pub struct SetReport {
     pub reference: SetReference,
     pub in_file: bool,
@@ -157,11 +498,76 @@ pub struct SetReport {
     pub roms_unneeded: HashSet<DataFile>, // BadDumps
     pub roms_to_spare: HashSet<DataFile>,
     pub device_dependencies: HashSet<String>,
-    pub unknown: Vec<DataFile>
+    pub unknown: Vec<DataFile>,
+    /// The disks found in the folder of the set
+    pub disks_available: HashMap<DataFile, RomLocatedAt>,
+    pub disks_missing: HashSet<DataFile>,
+    /// The roms not found that are in the ignore list, they are not required to complete the set
+    pub roms_ignored: HashSet<DataFile>,
+}
+
+/// How a set is serialized, with its status so the consumers don't need to work it out from the roms
+#[derive(Serialize, Deserialize)]
+struct SetReportSchema {
+    reference: SetReference,
+    status: SetStatus,
+    in_file: bool,
+    #[serde(with = "as_entries")]
+    roms_available: HashMap<DataFile, RomLocatedAt>,
+    roms_missing: HashSet<DataFile>,
+    roms_unneeded: HashSet<DataFile>,
+    roms_to_spare: HashSet<DataFile>,
+    device_dependencies: HashSet<String>,
+    unknown: Vec<DataFile>,
+    #[serde(default, with = "as_entries")]
+    disks_available: HashMap<DataFile, RomLocatedAt>,
+    #[serde(default)]
+    disks_missing: HashSet<DataFile>,
+    #[serde(default)]
+    roms_ignored: HashSet<DataFile>,
+}
+
+impl From<SetReport> for SetReportSchema {
+    fn from(set: SetReport) -> Self {
+        Self {
+            status: set.is_complete(),
+            reference: set.reference,
+            in_file: set.in_file,
+            roms_available: set.roms_available,
+            roms_missing: set.roms_missing,
+            roms_unneeded: set.roms_unneeded,
+            roms_to_spare: set.roms_to_spare,
+            device_dependencies: set.device_dependencies,
+            unknown: set.unknown,
+            disks_available: set.disks_available,
+            disks_missing: set.disks_missing,
+            roms_ignored: set.roms_ignored,
+        }
+    }
+}
+
+// The status is worked out again from the roms
+impl From<SetReportSchema> for SetReport {
+    fn from(set: SetReportSchema) -> Self {
+        Self {
+            reference: set.reference,
+            in_file: set.in_file,
+            roms_available: set.roms_available,
+            roms_missing: set.roms_missing,
+            roms_unneeded: set.roms_unneeded,
+            roms_to_spare: set.roms_to_spare,
+            device_dependencies: set.device_dependencies,
+            unknown: set.unknown,
+            disks_available: set.disks_available,
+            disks_missing: set.disks_missing,
+            roms_ignored: set.roms_ignored,
+        }
+    }
 }
 
 // A set may be associated with a game based on its name, or just contain roms if there are no matches
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SetReference {
     FileName(String),
     Game(Game)
@@ -182,104 +588,66 @@ impl SetReference {
 
 impl Display for SetReference {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            SetReference::FileName(name) => {
-                writeln!(f, "File name: {}", name)
-            }
-            SetReference::Game(game) => {
-                writeln!(f, "{}", game)
-            }
-        }
+        EnglishFormatter.write_set_reference(f, self)
     }
 }
 
 impl Display for SetReport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Set - {}", self.reference)?;
-        let file_status = if self.in_file {
-            " [in file]"
-        } else {
-            ""
-        };
-        writeln!(f, "Status: {}{}", self.is_complete(), file_status)?;
-
-        if !self.device_dependencies.is_empty() {
-            writeln!(f, "Depends on:")?;
-            for dep in &self.device_dependencies {
-                writeln!(f, " - {}", dep)?;
-            }
-        }
-        if !self.roms_available.is_empty() {
-            writeln!(f, "Roms Available")?;
-            for available in &self.roms_available {
-                let rom = available.0;
-                let location = available.1;
-                match location {
-                    RomLocatedAt::InSet => { writeln!(f, " - {}", rom.name)?; }
-                    RomLocatedAt::InSetWrongName(name) => { writeln!(f, " - {} [rename from: {}]", rom.name, name)?; }
-                    RomLocatedAt::InOthers(locations) => {
-                        let mut location_list = vec![];
-                        for location in locations {
-                            location_list.push(format!("{} as {}", location.file, location.with_name));
-                        }
-                        writeln!(f, " - {} [located at: {}]", rom.name, location_list.join(", "))?; 
-                    }
-                }
-            }
-        }
-        if !self.roms_unneeded.is_empty() {
-            writeln!(f, "Roms Unneeded (e.g. Bad Dumps)")?;
-            for unneeded in &self.roms_unneeded {
-                writeln!(f, " - {}", unneeded.name)?;
-            }
-        }
-        if !self.roms_missing.is_empty() {
-            writeln!(f, "Roms Missing")?;
-            for missing in &self.roms_missing {
-                writeln!(f, " - {}", missing.name)?;
-            }
-        }
-        if !self.roms_to_spare.is_empty() {
-            writeln!(f, "Roms to Spare")?;
-            for to_spare in &self.roms_to_spare {
-                writeln!(f, " - {}", to_spare.name)?;
-            }
-        }
-        Ok(())
+        EnglishFormatter.write_set_report(f, self)
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RomLocatedAt {
     InSet,
     InSetWrongName(String),
     InOthers(Vec<RomLocation>)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum SetStatus {
+    #[serde(rename = "complete")]
     COMPLETE,
+    #[serde(rename = "fixeable")]
     FIXEABLE,
+    #[serde(rename = "incomplete")]
     INCOMPLETE
 }
 
-impl Display for SetStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl SetStatus {
+    /// The name stored in the database
+    pub fn get_key(&self) -> &'static str {
         match self {
-            SetStatus::COMPLETE => {
-                write!(f, "Complete")
-            }
-            SetStatus::FIXEABLE => {
-                write!(f, "Fixeable")
-            }
-            SetStatus::INCOMPLETE => {
-                write!(f, "Incomplete")
-            }
+            SetStatus::COMPLETE => "complete",
+            SetStatus::FIXEABLE => "fixeable",
+            SetStatus::INCOMPLETE => "incomplete",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "complete" => Some(SetStatus::COMPLETE),
+            "fixeable" => Some(SetStatus::FIXEABLE),
+            "incomplete" => Some(SetStatus::INCOMPLETE),
+            _ => None,
         }
     }
 }
 
+impl Display for SetStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", EnglishFormatter.text(self.into()))
+    }
+}
+
 impl SetReport {
+    /// The dump quality of the roms of the set, either available, missing or unneeded
+    pub fn get_dump_quality(&self) -> DumpQuality {
+        DumpQuality::from_files(self.roms_available.keys().chain(self.roms_missing.iter()).chain(self.roms_unneeded.iter()).chain(self.roms_ignored.iter()))
+    }
+
     pub fn new<S>(name: S) -> Self where S: Into<String> {
         Self {
             reference: SetReference::FileName(name.into()),
@@ -289,7 +657,18 @@ impl SetReport {
             roms_unneeded: HashSet::new(),
             roms_to_spare: HashSet::new(),
             device_dependencies: HashSet::new(),
-            unknown: vec![]
+            unknown: vec![],
+            disks_available: HashMap::new(),
+            disks_missing: HashSet::new(),
+            roms_ignored: HashSet::new(),
+        }
+    }
+
+    /// If the game of the set matches the filter, a set not referencing a game is always included
+    pub fn is_included(&self, game_filter: &GameFilter) -> bool {
+        match &self.reference {
+            SetReference::Game(game) => game_filter.matches(game),
+            SetReference::FileName(_name) => true,
         }
     }
 
@@ -298,11 +677,11 @@ impl SetReport {
     }
 
     pub fn is_complete(&self) -> SetStatus {
-        if self.roms_missing.is_empty() {
-            let mut available = self.roms_available.len();
+        if self.roms_missing.is_empty() && self.disks_missing.is_empty() {
+            let mut available = self.roms_available.len() + self.disks_available.len();
 
-            for set_rom in &self.roms_available {
-                if set_rom.1.eq(&RomLocatedAt::InSet) {
+            for location in self.roms_available.values().chain(self.disks_available.values()) {
+                if location.eq(&RomLocatedAt::InSet) {
                     available -= 1;
                 }
             }
@@ -317,12 +696,48 @@ impl SetReport {
         }
     }
 
+    /// Adds what another scan found of the same set, a rom found in either is available and the location in the set
+    /// wins over the others
+    fn merge(&mut self, other: SetReport) {
+        if let (SetReference::FileName(_), SetReference::Game(_)) = (&self.reference, &other.reference) {
+            self.reference = other.reference;
+        }
+        self.in_file |= other.in_file;
+
+        merge_located(&mut self.roms_available, other.roms_available);
+        merge_located(&mut self.disks_available, other.disks_available);
+        let roms_available = &self.roms_available;
+        self.roms_missing.extend(other.roms_missing);
+        self.roms_missing.retain(|rom| !roms_available.contains_key(rom));
+        let disks_available = &self.disks_available;
+        self.disks_missing.extend(other.disks_missing);
+        self.disks_missing.retain(|disk| !disks_available.contains_key(disk));
+        self.roms_ignored.extend(other.roms_ignored);
+        self.roms_ignored.retain(|rom| !roms_available.contains_key(rom));
+
+        self.roms_unneeded.extend(other.roms_unneeded);
+        self.roms_to_spare.extend(other.roms_to_spare);
+        self.device_dependencies.extend(other.device_dependencies);
+        for file in other.unknown {
+            if !self.unknown.contains(&file) {
+                self.unknown.push(file);
+            }
+        }
+    }
+
+    #[cfg(test)]
     fn add_set_rom(&mut self, location: RomLocation, rom: DataFile) {
+        let in_set = crate::data::models::does_file_belong_to_set(&location.file, self.reference.get_name());
+        self.add_located_rom(location, rom, in_set);
+    }
+
+    /// Adds a rom found in a file, `in_set` if the file is the one of this set
+    fn add_located_rom(&mut self, location: RomLocation, rom: DataFile, in_set: bool) {
         if self.roms_missing.remove(&rom) {
             debug!("Removed from set {} the file as missing {}", self.reference, &rom);
         }
+        self.roms_ignored.remove(&rom);
 
-        let in_set = models::does_file_belong_to_set(location.file.as_str(), self.reference.get_name());
         let rom_name = rom.name.clone();
         match self.roms_available.entry(rom) {
             Entry::Occupied(mut entry) => {
@@ -368,6 +783,26 @@ impl SetReport {
         }
     }
 
+    /// The disk is in the set if the file has its name, the `.chd` extension is optional in the DATs
+    fn add_set_disk(&mut self, disk: DataFile, file_name: &str) {
+        self.disks_missing.remove(&disk);
+        let located = if disk_stem(file_name) == disk_stem(&disk.name) {
+            RomLocatedAt::InSet
+        } else {
+            RomLocatedAt::InSetWrongName(file_name.to_string())
+        };
+        match self.disks_available.entry(disk) {
+            Entry::Occupied(mut entry) => {
+                if located == RomLocatedAt::InSet {
+                    entry.insert(located);
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(located);
+            }
+        }
+    }
+
     fn add_missing_rom(&mut self, file: DataFile) {
         match &file.status {
             Some(status) if status.to_lowercase() == "nodump" => {
@@ -385,24 +820,98 @@ impl SetReport {
     }
 
     fn find_in_available(&self, file: &DataFile) -> bool {
-        self.roms_available.get(file).is_some()
+        self.roms_available.contains_key(file)
     }
 
     fn find_in_missing(&self, file: &DataFile) -> bool {
-        self.roms_missing.get(file).is_some()
+        self.roms_missing.contains(file)
+    }
+}
+
+/// The locations of the files found by both scans, in the set with its name first, then in the set with another name,
+/// and in other files with the locations of both
+fn merge_located(available: &mut HashMap<DataFile, RomLocatedAt>, other: HashMap<DataFile, RomLocatedAt>) {
+    for (file, other_located_at) in other {
+        match available.entry(file) {
+            Entry::Occupied(mut entry) => {
+                match (entry.get_mut(), other_located_at) {
+                    (RomLocatedAt::InSet, _) => {}
+                    (_, RomLocatedAt::InSet) => {
+                        entry.insert(RomLocatedAt::InSet);
+                    }
+                    (RomLocatedAt::InSetWrongName(_), _) => {}
+                    (_, other_located_at @ RomLocatedAt::InSetWrongName(_)) => {
+                        entry.insert(other_located_at);
+                    }
+                    (RomLocatedAt::InOthers(locations), RomLocatedAt::InOthers(other_locations)) => {
+                        for location in other_locations {
+                            if !locations.contains(&location) {
+                                locations.push(location);
+                            }
+                        }
+                    }
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(other_located_at);
+            }
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+fn disk_stem(name: &str) -> &str {
+    let name = name.trim();
+    name.strip_suffix(".chd").unwrap_or(name)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RomLocation {
-    file: String,
+    /// Many roms are in the same file, so its name is shared
+    file: Arc<str>,
     with_name: String,
 }
 
 impl RomLocation {
-    pub fn new<S>(file: S, with_name: S) -> Self where S: Into<String> { Self { file: file.into(), with_name: with_name.into() } }
+    pub fn new<S>(file: S, with_name: S) -> Self where S: Into<String> { Self { file: Arc::from(file.into()), with_name: with_name.into() } }
+
+    pub fn in_shared_file<S>(file: Arc<str>, with_name: S) -> Self where S: Into<String> { Self { file, with_name: with_name.into() } }
+
+    pub fn get_file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn get_with_name(&self) -> &str {
+        &self.with_name
+    }
 }
 
+/// Maps keyed by a file are written as a list of objects with the file and where it's located, as formats like JSON
+/// only take strings as keys
+mod as_entries {
+    use std::{collections::HashMap, hash::Hash};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    struct EntryRef<'a, K, V> {
+        file: &'a K,
+        located_at: &'a V,
+    }
+
+    #[derive(Deserialize)]
+    struct Entry<K, V> {
+        file: K,
+        located_at: V,
+    }
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error> where K: Serialize, V: Serialize, S: Serializer {
+        serializer.collect_seq(map.iter().map(|(file, located_at)| EntryRef { file, located_at }))
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error> where K: Deserialize<'de> + Eq + Hash, V: Deserialize<'de>, D: Deserializer<'de> {
+        let entries = Vec::<Entry<K, V>>::deserialize(deserializer)?;
+        Ok(entries.into_iter().map(|entry| (entry.file, entry.located_at)).collect())
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -410,6 +919,20 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn shares_the_file_names_of_the_locations() {
+        let mut scan_report = ScanReport::new(None, RomsetMode::Split);
+        scan_report.add_rom_for_set("set1", RomLocation::new("other.zip", "file1"), DataFile::new("file1", get_sample_rom("1234")));
+        scan_report.add_rom_for_set("set2", RomLocation::new("other.zip", "file2"), DataFile::new("file2", get_sample_rom("5678")));
+
+        let get_file = |set_name: &str| match scan_report.sets[set_name].roms_available.values().next() {
+            Some(RomLocatedAt::InOthers(locations)) => Arc::clone(&locations[0].file),
+            _ => panic!("No location for {}", set_name),
+        };
+        assert!(Arc::ptr_eq(&get_file("set1"), &get_file("set2")));
+        assert!(Arc::ptr_eq(&get_file("set1"), &scan_report.intern_file_name("other.zip")));
+    }
+
     #[test]
     fn has_complete_set() {
         let mut set = SetReport::new("set1");
@@ -548,6 +1071,174 @@ mod tests {
         assert_eq!(SetStatus::COMPLETE, completeness);
     }
 
+    #[test]
+    fn ignored_roms_are_not_needed_to_complete_the_set() -> Result<()> {
+        let mut ignored_roms = IgnoredRoms::default();
+        ignored_roms.add_rom("set1/file2")?;
+        let mut scan_report = ScanReport::new(None, RomsetMode::Split);
+        scan_report.set_ignored_roms(ignored_roms);
+
+        scan_report.add_rom_for_set("set1", RomLocation::new("set1.zip", "file1"),
+            DataFile::new("file1", get_sample_rom("1234")));
+        scan_report.add_missing_rom_for_set("set1", DataFile::new("file2", get_sample_rom("5678")));
+        scan_report.add_missing_rom_for_set("set2", DataFile::new("file2", get_sample_rom("5678")));
+
+        let set = scan_report.sets.get("set1").unwrap();
+        assert_eq!(SetStatus::COMPLETE, set.is_complete());
+        assert_eq!(1, set.roms_ignored.len());
+        assert_eq!(SetStatus::INCOMPLETE, scan_report.sets.get("set2").unwrap().is_complete());
+        assert_eq!(1, scan_report.get_ignored_roms_count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn serializes_with_a_stable_schema() -> Result<()> {
+        let mut scan_report = ScanReport::new(None, RomsetMode::Split);
+        scan_report.add_rom_for_set("set1", RomLocation::new("set1.zip", "wrong"),
+            DataFile::new("file1", get_sample_rom("1234")));
+        scan_report.set_file_checks(FileChecks::SHA1 | FileChecks::CRC | FileChecks::SIZE);
+
+        let json = serde_json::to_value(&scan_report)?;
+        assert_eq!(REPORT_SCHEMA_VERSION, json["schema_version"]);
+        assert_eq!(serde_json::json!(["sha1", "crc", "size"]), json["file_checks"]);
+        let set = &json["sets"]["set1"];
+        assert_eq!("fixeable", set["status"]);
+        assert_eq!("set1", set["reference"]["file_name"]);
+        assert_eq!("file1", set["roms_available"][0]["file"]["name"]);
+        assert_eq!("wrong", set["roms_available"][0]["located_at"]["in_set_wrong_name"]);
+
+        let encoded = bincode::serialize(&scan_report)?;
+        let decoded: ScanReport = bincode::deserialize(&encoded)?;
+        let set = decoded.sets.get("set1").unwrap();
+        assert_eq!(Some(&RomLocatedAt::InSetWrongName("wrong".to_string())), set.roms_available.values().next());
+        assert_eq!(SetStatus::FIXEABLE, set.is_complete());
+        assert_eq!(scan_report.get_file_checks(), decoded.get_file_checks());
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_weakest_file_checks_when_merging() -> Result<()> {
+        let mut report = ScanReport::new(None, RomsetMode::Split);
+        report.set_file_checks(FileChecks::ALL);
+        assert!(!report.is_weak_matching());
+        let mut other = ScanReport::new(None, RomsetMode::Split);
+        other.set_file_checks(FileChecks::CRC | FileChecks::SIZE);
+
+        report.merge(other)?;
+        assert_eq!(Some(FileChecks::CRC | FileChecks::SIZE), report.get_file_checks());
+        assert!(report.is_weak_matching());
+        assert!(format!("{}", report).contains("Matched by: crc, size"));
+
+        // The reports written before the checks were recorded don't know them
+        let mut json = serde_json::to_value(&report)?;
+        json.as_object_mut().unwrap().remove("file_checks");
+        let old: ScanReport = serde_json::from_value(json)?;
+        assert_eq!(None, old.get_file_checks());
+        assert!(!old.is_weak_matching());
+        report.merge(old)?;
+        assert_eq!(None, report.get_file_checks());
+
+        Ok(())
+    }
+
+    #[test]
+    fn merges_the_sets_found_in_each_scan() -> Result<()> {
+        let file1 = DataFile::new("file1", get_sample_rom("1234"));
+        let file2 = DataFile::new("file2", get_sample_rom("5678"));
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::Split);
+        report.add_rom_for_set("set1", RomLocation::new("set1.zip", "file1"), file1.clone());
+        report.add_missing_rom_for_set("set1", file2.clone());
+        report.add_ignored("notes.txt");
+        let mut other = ScanReport::new(Some("/roms".to_string()), RomsetMode::Split);
+        other.add_rom_for_set("set1", RomLocation::new("set1.zip", "file2"), file2.clone());
+        other.add_missing_rom_for_set("set1", file1);
+        other.reference_with_game(Game::new("set2".to_string()));
+        other.add_missing_rom_for_set("set2", file2);
+        other.add_ignored("notes.txt");
+
+        report.merge(other)?;
+
+        assert_eq!(SetStatus::COMPLETE, report.sets["set1"].is_complete());
+        assert!(report.sets["set1"].roms_missing.is_empty());
+        assert!(report.has_reference_with_game("set2"));
+        assert_eq!(SetStatus::INCOMPLETE, report.sets["set2"].is_complete());
+        assert_eq!(Some("/roms"), report.get_root_directory());
+        assert_eq!(vec!["notes.txt".to_string()], report.ignored);
+
+        Ok(())
+    }
+
+    #[test]
+    fn merges_the_locations_of_a_rom() -> Result<()> {
+        let file1 = DataFile::new("file1", get_sample_rom("1234"));
+        let file2 = DataFile::new("file2", get_sample_rom("5678"));
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::Split);
+        report.add_rom_for_set("set1", RomLocation::new("a.zip", "x"), file1.clone());
+        report.add_rom_for_set("set1", RomLocation::new("a.zip", "y"), file2.clone());
+        let mut other = ScanReport::new(Some("/other".to_string()), RomsetMode::Split);
+        other.add_rom_for_set("set1", RomLocation::new("b.zip", "x"), file1.clone());
+        other.add_rom_for_set("set1", RomLocation::new("set1.zip", "z"), file2.clone());
+
+        report.merge(other)?;
+
+        let set = &report.sets["set1"];
+        assert_eq!(RomLocatedAt::InOthers(vec![RomLocation::new("a.zip", "x"), RomLocation::new("b.zip", "x")]), set.roms_available[&file1]);
+        assert_eq!(RomLocatedAt::InSetWrongName("z".to_string()), set.roms_available[&file2]);
+        assert_eq!(SetStatus::FIXEABLE, set.is_complete());
+        // Scans of different directories don't have a single root
+        assert_eq!(None, report.get_root_directory());
+
+        Ok(())
+    }
+
+    #[test]
+    fn replaces_the_errors_retried() -> Result<()> {
+        let file1 = DataFile::new("file1", get_sample_rom("1234"));
+        let transient = |message: &str| FileError { kind: FileErrorKind::Transient, message: message.to_string() };
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::Split);
+        report.add_error("set1.zip", transient("timed out"));
+        report.add_error("set2.zip", transient("timed out"));
+        report.add_error("bad.zip", FileError { kind: FileErrorKind::Permanent, message: "invalid archive".to_string() });
+        assert_eq!(vec!["set1.zip", "set2.zip"], report.get_retriable_files());
+
+        let mut retried = ScanReport::new(None, RomsetMode::Split);
+        retried.add_rom_for_set("set1", RomLocation::new("set1.zip", "file1"), file1);
+        retried.add_error("set2.zip", transient("host is down"));
+        report.merge_retried(retried)?;
+
+        assert!(report.sets.contains_key("set1"));
+        assert_eq!(vec!["bad.zip", "set2.zip"], report.errors.keys().collect::<Vec<_>>());
+        assert_eq!("host is down", report.errors["set2.zip"].message);
+        assert_eq!(Some("/roms"), report.get_root_directory());
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_merges_reports_of_the_same_mode() {
+        let mut report = ScanReport::new(None, RomsetMode::Split);
+        let other = ScanReport::new(None, RomsetMode::Merged);
+        assert!(report.merge(other).is_err());
+    }
+
+    #[test]
+    fn keeps_the_latest_date() -> Result<()> {
+        let mut report = ScanReport::new(None, RomsetMode::Split);
+        report.date_time = "2021-01-01T00:00:00+00:00".to_string();
+        let mut other = ScanReport::new(None, RomsetMode::Split);
+        other.date_time = "2022-01-01T00:00:00+00:00".to_string();
+        report.merge(other)?;
+        assert_eq!("2022-01-01T00:00:00+00:00", report.get_date_time_string());
+
+        let mut older = ScanReport::new(None, RomsetMode::Split);
+        older.date_time = "2020-01-01T00:00:00+00:00".to_string();
+        report.merge(older)?;
+        assert_eq!("2022-01-01T00:00:00+00:00", report.get_date_time_string());
+
+        Ok(())
+    }
+
     fn get_sample_rom<S>(sha1: S) -> DataFileInfo where S: Into<String>{
         let mut rom = DataFileInfo::new(FileType::Rom);
         rom.sha1 = Some(sha1.into());
@@ -0,0 +1,160 @@
+use std::{collections::{BTreeMap, BTreeSet}, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{RomsetMode, data::models::{self, game::Game}};
+
+use super::{formatter::{EnglishFormatter, ReportFormatter}, sample_audit::SampleAuditReport, scan_report::{ScanReport, SetStatus}};
+
+/// A compact result of a check, meant for unattended runs where only the counts and what changed since the last run matter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckSummary {
+    pub root_directory: Option<String>,
+    pub date_time: String,
+    pub complete: u32,
+    pub fixeable: u32,
+    pub incomplete: u32,
+    pub ignored: u32,
+    pub corrupt: u32,
+    /// The files not read because the check was interrupted
+    #[serde(default)]
+    pub unscanned: u32,
+    /// The missing roms that are in the ignore list, so they don't make their sets incomplete
+    #[serde(default)]
+    pub roms_ignored: u32,
+    /// Sets complete in the last check that are not anymore, either because they are not complete or were not found
+    pub newly_broken: Vec<String>,
+    /// Only when the check hashed a sample of the zips
+    #[serde(default)]
+    pub sample_audit: Option<SampleAuditReport>,
+}
+
+impl Display for CheckSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_check_summary(f, self)
+    }
+}
+
+impl CheckSummary {
+    pub fn new(report: &ScanReport, statuses: &BTreeMap<String, SetStatus>, last_complete: &BTreeSet<String>) -> Self {
+        let count = |status: SetStatus| statuses.values().filter(|set_status| **set_status == status).count() as u32;
+        // The sets of the files not scanned are not known to be broken
+        let unscanned_sets = report.unscanned.iter().map(|file| models::get_set_from_file(file)).collect::<BTreeSet<_>>();
+        let newly_broken = last_complete.iter()
+            .filter(|set_name| statuses.get(*set_name) != Some(&SetStatus::COMPLETE) && !unscanned_sets.contains(*set_name))
+            .cloned()
+            .collect();
+
+        Self {
+            root_directory: report.get_root_directory().map(|root_directory| root_directory.to_string()),
+            date_time: report.get_date_time_string().to_string(),
+            complete: count(SetStatus::COMPLETE),
+            fixeable: count(SetStatus::FIXEABLE),
+            incomplete: count(SetStatus::INCOMPLETE),
+            ignored: report.ignored.len() as u32,
+            corrupt: report.corrupt.len() as u32,
+            unscanned: report.unscanned.len() as u32,
+            roms_ignored: report.get_ignored_roms_count() as u32,
+            newly_broken,
+            sample_audit: report.sample_audit.clone(),
+        }
+    }
+}
+
+/// The statuses of the sets a check is expected to find, the sets outside the scope are neither counted nor missing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissingSets {
+    pub date_time: String,
+    /// The sets in the scope, in merged mode without the clones as they are in the files of their parents
+    pub in_scope: u32,
+    pub complete: u32,
+    pub fixeable: u32,
+    pub incomplete: u32,
+    /// The sets of the files not read because the check was interrupted, they are not known to be missing
+    pub unscanned: u32,
+    /// The sets of the scope without any of their files found, sorted by name
+    pub not_found: Vec<String>,
+}
+
+impl Display for MissingSets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        EnglishFormatter.write_missing_sets(f, self)
+    }
+}
+
+impl MissingSets {
+    pub fn new(report: &ScanReport, games: Vec<Game>) -> Self {
+        let statuses = report.get_set_statuses();
+        let unscanned_sets = report.unscanned.iter().map(|file| models::get_set_from_file(file)).collect::<BTreeSet<_>>();
+        let merged = report.get_rom_mode() == RomsetMode::Merged;
+
+        let mut missing_sets = Self {
+            date_time: report.get_date_time_string().to_string(),
+            in_scope: 0,
+            complete: 0,
+            fixeable: 0,
+            incomplete: 0,
+            unscanned: 0,
+            not_found: vec![],
+        };
+        for game in games.into_iter().filter(|game| !merged || game.clone_of.is_none()) {
+            missing_sets.in_scope += 1;
+            match statuses.get(&game.name) {
+                Some(SetStatus::COMPLETE) => missing_sets.complete += 1,
+                Some(SetStatus::FIXEABLE) => missing_sets.fixeable += 1,
+                Some(SetStatus::INCOMPLETE) => missing_sets.incomplete += 1,
+                None if unscanned_sets.contains(&game.name) => missing_sets.unscanned += 1,
+                None => missing_sets.not_found.push(game.name),
+            }
+        }
+        missing_sets.not_found.sort();
+        missing_sets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_the_statuses() {
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::NonMerged);
+        report.add_ignored("/roms/readme.txt");
+        let statuses: BTreeMap<String, SetStatus> = vec![
+            ("game1".to_string(), SetStatus::COMPLETE),
+            ("game2".to_string(), SetStatus::FIXEABLE),
+            ("game3".to_string(), SetStatus::INCOMPLETE),
+            ("game4".to_string(), SetStatus::COMPLETE),
+        ].into_iter().collect();
+        let last_complete: BTreeSet<String> = vec!["game1", "game2", "game5"].into_iter().map(|name| name.to_string()).collect();
+
+        let summary = CheckSummary::new(&report, &statuses, &last_complete);
+
+        assert_eq!(Some("/roms".to_string()), summary.root_directory);
+        assert_eq!((2, 1, 1, 1, 0), (summary.complete, summary.fixeable, summary.incomplete, summary.ignored, summary.corrupt));
+        assert_eq!(vec!["game2".to_string(), "game5".to_string()], summary.newly_broken);
+
+        report.add_unscanned("game5.zip");
+        let summary = CheckSummary::new(&report, &statuses, &last_complete);
+        assert_eq!(1, summary.unscanned);
+        assert_eq!(vec!["game2".to_string()], summary.newly_broken);
+    }
+
+    #[test]
+    fn counts_only_the_sets_in_scope() {
+        let mut report = ScanReport::new(Some("/roms".to_string()), RomsetMode::Merged);
+        report.reference_with_game(Game::new("mslug".to_string()));
+        report.reference_with_game(Game::new("kof98".to_string()));
+        report.add_missing_rom_for_set("kof98", models::file::DataFile::new("rom1", models::file::DataFileInfo::new(models::file::FileType::Rom)));
+        report.add_unscanned("/roms/mslug3.zip");
+
+        let mut kof98a = Game::new("kof98a".to_string());
+        kof98a.clone_of = Some("kof98".to_string());
+        let games = vec!["mslug2", "mslug", "kof98", "mslug3", "garou"].into_iter().map(|name| Game::new(name.to_string())).chain(vec![kof98a]).collect();
+        let missing_sets = MissingSets::new(&report, games);
+
+        assert_eq!(5, missing_sets.in_scope);
+        assert_eq!((1, 0, 1, 1), (missing_sets.complete, missing_sets.fixeable, missing_sets.incomplete, missing_sets.unscanned));
+        assert_eq!(vec!["garou".to_string(), "mslug2".to_string()], missing_sets.not_found);
+    }
+}
@@ -0,0 +1,167 @@
+//! The rom names of a DAT written as files, some have characters Windows and FAT filesystems don't allow, like `:`
+//! or `?`, or are names reserved there, like `CON`. The policy changes them into names valid everywhere, and a check
+//! with the same policy takes the changed names as right.
+use std::{collections::HashSet, fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{err, error::RomstError};
+
+/// Characters not allowed in Windows and FAT file names, besides the control characters
+const ILLEGAL_CHARACTERS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+/// Names Windows reserves for devices, with any extension
+const RESERVED_NAMES: [&str; 22] = ["CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"];
+const REPLACEMENT: char = '_';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum NamePolicy {
+    /// Only ASCII characters valid in Windows, for the most limited filesystems and devices
+    Strict,
+    /// The characters and names Windows and FAT don't allow are replaced, and names only different in case are told apart
+    Compatible,
+    /// The names are written as they are in the DAT
+    #[default]
+    Passthrough,
+}
+
+impl Display for NamePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NamePolicy::Strict => write!(f, "strict"),
+            NamePolicy::Compatible => write!(f, "compatible"),
+            NamePolicy::Passthrough => write!(f, "passthrough"),
+        }
+    }
+}
+
+impl FromStr for NamePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "strict" => Ok(NamePolicy::Strict),
+            "compatible" => Ok(NamePolicy::Compatible),
+            "passthrough" => Ok(NamePolicy::Passthrough),
+            _ => err!(RomstError::GenericError { message: format!("Unknown name policy `{}`, can be either `strict`, `compatible` or `passthrough`", s) }),
+        }
+    }
+}
+
+impl NamePolicy {
+    /// The name valid for the policy, the folders are kept and separated with `/`
+    pub fn sanitize(&self, name: &str) -> String {
+        match self {
+            NamePolicy::Passthrough => name.to_string(),
+            _ => name.split(['/', '\\']).map(|part| self.sanitize_part(part)).collect::<Vec<_>>().join("/"),
+        }
+    }
+
+    fn sanitize_part(&self, part: &str) -> String {
+        let mut part = part.chars().map(|c| {
+            if c.is_control() || ILLEGAL_CHARACTERS.contains(&c) || (*self == NamePolicy::Strict && !c.is_ascii()) {
+                REPLACEMENT
+            } else {
+                c
+            }
+        }).collect::<String>();
+        // Windows drops the dots and spaces at the end
+        while part.ends_with(['.', ' ']) {
+            part.pop();
+            part.push(REPLACEMENT);
+        }
+        if part.is_empty() {
+            return REPLACEMENT.to_string();
+        }
+        let stem_len = part.find('.').unwrap_or(part.len());
+        if RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(part[..stem_len].trim_end())) {
+            part.insert(stem_len, REPLACEMENT);
+        }
+        part
+    }
+
+    /// The names of the roms of a set as they are written, in the same order. The names that collide once changed, or
+    /// that only differ in case, get a number after them, the names that don't change are kept
+    pub fn sanitize_all<S>(&self, names: &[S]) -> Vec<String> where S: AsRef<str> {
+        if *self == NamePolicy::Passthrough {
+            return names.iter().map(|name| name.as_ref().to_string()).collect();
+        }
+        let sanitized = names.iter().map(|name| self.sanitize(name.as_ref())).collect::<Vec<_>>();
+        let mut used = HashSet::new();
+        let mut result = names.iter().zip(sanitized.iter()).map(|(name, sanitized)| {
+            Some(sanitized.to_string()).filter(|sanitized| sanitized == name.as_ref() && used.insert(sanitized.to_lowercase()))
+        }).collect::<Vec<_>>();
+        for (written, sanitized) in result.iter_mut().zip(sanitized.iter()) {
+            if written.is_none() {
+                let mut candidate = sanitized.to_string();
+                let mut count = 2;
+                while !used.insert(candidate.to_lowercase()) {
+                    candidate = add_number(sanitized, count);
+                    count += 1;
+                }
+                *written = Some(candidate);
+            }
+        }
+        result.into_iter().flatten().collect()
+    }
+
+    /// If the name can be the one written for the rom by this policy, with or without the number added on a collision
+    pub fn is_written_name(&self, written_name: &str, rom_name: &str) -> bool {
+        if *self == NamePolicy::Passthrough {
+            return false;
+        }
+        let sanitized = self.sanitize(rom_name);
+        written_name == sanitized || remove_number(written_name).filter(|name| *name == sanitized).is_some()
+    }
+}
+
+/// `folder/name (2).ext` for `folder/name.ext`
+fn add_number(name: &str, count: u32) -> String {
+    let file_start = name.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match name[file_start..].rfind('.').filter(|i| *i > 0) {
+        Some(dot) => format!("{} ({}){}", &name[..file_start + dot], count, &name[file_start + dot..]),
+        None => format!("{} ({})", name, count),
+    }
+}
+
+/// The name without the number `add_number` adds, if it has one
+fn remove_number(name: &str) -> Option<String> {
+    let file_start = name.rfind('/').map(|i| i + 1).unwrap_or(0);
+    let stem_end = name[file_start..].rfind('.').filter(|i| *i > 0).map(|i| file_start + i).unwrap_or(name.len());
+    let stem = name[..stem_end].strip_suffix(')')?;
+    let open = stem.rfind(" (")?;
+    let number = &stem[open + 2..];
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("{}{}", &stem[..open], &name[stem_end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_the_names() {
+        assert_eq!("a_b_.bin", NamePolicy::Compatible.sanitize("a:b?.bin"));
+        assert_eq!("cpu/rom_", NamePolicy::Compatible.sanitize("cpu\\rom."));
+        assert_eq!("CON_.bin", NamePolicy::Compatible.sanitize("CON.bin"));
+        assert_eq!("console.bin", NamePolicy::Compatible.sanitize("console.bin"));
+        assert_eq!("Pokémon.gb", NamePolicy::Compatible.sanitize("Pokémon.gb"));
+        assert_eq!("Pok_mon.gb", NamePolicy::Strict.sanitize("Pokémon.gb"));
+        assert_eq!("a:b.bin", NamePolicy::Passthrough.sanitize("a:b.bin"));
+    }
+
+    #[test]
+    fn tells_apart_the_names_colliding() {
+        let names = ["a?b.bin", "a_b.bin", "A:B.bin", "c.bin"];
+        let written = NamePolicy::Compatible.sanitize_all(&names);
+        assert_eq!(vec!["a_b (2).bin", "a_b.bin", "A_B (3).bin", "c.bin"], written);
+        for (written, name) in written.iter().zip(names.iter()) {
+            assert!(*written == *name || NamePolicy::Compatible.is_written_name(written, name));
+        }
+        assert!(!NamePolicy::Compatible.is_written_name("a_b (2).bin", "c.bin"));
+        assert!(!NamePolicy::Passthrough.is_written_name("a_b.bin", "a:b.bin"));
+        assert_eq!(names.to_vec(), NamePolicy::Passthrough.sanitize_all(&names));
+    }
+}
@@ -0,0 +1,349 @@
+//! In-memory implementation of a `DataReader` to test the scan and report logic without a database.
+//! Enable the `testing` feature to use it outside of this crate.
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use anyhow::Result;
+
+use crate::{RomsetMode, err, error::RomstError};
+
+use super::{models::{file::{DataFile, DataFileInfo, FileType}, game::Game}, reader::{DataReader, DbDataEntry, FileCheckSearch, RomSearch, SetDependencies, get_match_evidence}};
+
+#[derive(Debug, Clone)]
+struct MockGameRom {
+    game_name: String,
+    rom_id: u32,
+    name: String,
+    status: Option<String>,
+    bios: Option<String>,
+    extra_attrs: Option<BTreeMap<String, String>>,
+    parent: Option<String>,
+}
+
+/// A `DataReader` that keeps all the data in memory, build it with a `FixtureBuilder`
+#[derive(Debug, Default)]
+pub struct MockDataReader {
+    games: BTreeMap<String, Game>,
+    roms: Vec<DataFileInfo>,
+    game_roms: Vec<MockGameRom>,
+    devices: HashMap<String, Vec<String>>,
+}
+
+impl MockDataReader {
+    fn get_rom(&self, game_rom: &MockGameRom) -> DbDataEntry<DataFile> {
+        let info = self.roms[game_rom.rom_id as usize].clone();
+        let mut data_file = DataFile::new_with_status(game_rom.name.clone(), info, game_rom.status.clone());
+        data_file.bios = game_rom.bios.clone();
+        data_file.extra_attrs = game_rom.extra_attrs.clone();
+        DbDataEntry::new(game_rom.rom_id, data_file)
+    }
+
+    /// Same rules as `DBReader::get_ids_from_files`, returns the found roms and the ones not found
+    fn get_ids_from_files(&self, files: Vec<DataFile>) -> (Vec<DbDataEntry<DataFile>>, Vec<DataFile>) {
+        let mut found = vec![];
+        let mut not_found = vec![];
+        for rom_file in files {
+            match &rom_file.status {
+                Some(status) if status.to_lowercase() == "nodump" => {}
+                _ => {
+                    let matches = self.roms.iter().enumerate().filter_map(|(id, db_rom)| {
+                        get_match_evidence(db_rom, &rom_file.info).map(|evidence| (id as u32, evidence))
+                    }).collect::<Vec<_>>();
+                    let strongest = matches.iter().map(|(_id, evidence)| *evidence).max();
+                    let ids = matches.into_iter().filter(|(_id, evidence)| Some(*evidence) == strongest).map(|(id, _evidence)| id).collect::<Vec<_>>();
+                    match ids.len() {
+                        0 => not_found.push(rom_file),
+                        1 => found.push(DbDataEntry::new(ids[0], rom_file)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        (found, not_found)
+    }
+
+    fn find_sets_for_roms(&self, db_roms: Vec<DbDataEntry<DataFile>>, rom_mode: RomsetMode) -> RomSearch {
+        let mut game_roms = self.game_roms.iter().collect::<Vec<_>>();
+        game_roms.sort_by(|a, b| a.game_name.cmp(&b.game_name));
+
+        let mut result = RomSearch::new();
+        for game_rom in game_roms {
+            let game = match self.games.get(&game_rom.game_name) {
+                Some(game) => game,
+                None => continue,
+            };
+            let db_rom = self.get_rom(game_rom);
+            for searched in db_roms.iter().filter(|searched| searched.id == game_rom.rom_id) {
+                let mut data_file = db_rom.file.clone();
+                data_file.name = searched.file.name.clone();
+                let rom = DbDataEntry::new(db_rom.id, data_file);
+
+                match rom_mode {
+                    RomsetMode::Merged => {
+                        let set_name = game.rom_of.as_ref().unwrap_or(&game.name);
+                        result.add_file_for_set(set_name.to_owned(), rom);
+                    }
+                    RomsetMode::NonMerged => {
+                        result.add_file_for_set(game.name.to_owned(), rom);
+                    }
+                    RomsetMode::Split => {
+                        if game_rom.parent.is_none() {
+                            result.add_file_for_set(game.name.to_owned(), rom);
+                        }
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl DataReader for MockDataReader {
+    fn get_game_list(&self, rom_mode: RomsetMode) -> Result<Vec<(String, String)>> {
+        let list = self.games.values().filter(|game| {
+            match rom_mode {
+                RomsetMode::Merged => game.rom_of.is_none(),
+                _ => true,
+            }
+        }).map(|game| {
+            (game.name.to_owned(), game.info_description.to_owned().unwrap_or_default())
+        }).collect();
+
+        Ok(list)
+    }
+
+    fn get_games_with_rom_name(&self, rom_name: &str) -> Result<Vec<String>> {
+        let games = self.game_roms.iter()
+            .filter(|game_rom| game_rom.name == rom_name)
+            .map(|game_rom| game_rom.game_name.clone())
+            .collect::<BTreeSet<_>>();
+        Ok(games.into_iter().collect())
+    }
+
+    fn get_game<S>(&self, game_name: S) -> Option<Game> where S: AsRef<str> + rusqlite::ToSql {
+        self.games.get(game_name.as_ref()).cloned()
+    }
+
+    fn get_romset_roms<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<Vec<DbDataEntry<DataFile>>> where S: AsRef<str> + rusqlite::ToSql {
+        let game_name = game_name.as_ref();
+        let roms: HashSet<DbDataEntry<DataFile>> = self.game_roms.iter().filter(|game_rom| {
+            match rom_mode {
+                RomsetMode::Merged => {
+                    game_rom.game_name == game_name || self.games.get(&game_rom.game_name)
+                        .map(|game| game.rom_of.as_deref() == Some(game_name))
+                        .unwrap_or(false)
+                }
+                RomsetMode::NonMerged => game_rom.game_name == game_name,
+                RomsetMode::Split => game_rom.game_name == game_name && game_rom.parent.is_none(),
+            }
+        }).map(|game_rom| self.get_rom(game_rom)).collect();
+
+        Ok(roms.into_iter().collect())
+    }
+
+    fn get_rom_usage<S>(&self, game_name: S, rom_name: S, rom_mode: RomsetMode) -> Result<RomSearch> where S: AsRef<str> + rusqlite::ToSql {
+        let roms = self.get_romset_roms(game_name, rom_mode)?.into_iter().filter(|rom| {
+            rom.file.name.eq(rom_name.as_ref())
+        }).collect();
+
+        Ok(self.find_sets_for_roms(roms, rom_mode))
+    }
+
+    fn get_romset_shared_roms<S>(&self, game_name: S, rom_mode: RomsetMode) -> Result<RomSearch> where S: AsRef<str> + rusqlite::ToSql {
+        let game_roms = self.get_romset_roms(game_name, rom_mode)?;
+
+        Ok(self.find_sets_for_roms(game_roms, rom_mode))
+    }
+
+    fn get_romsets_from_roms(&self, roms: Vec<DataFile>, rom_mode: RomsetMode) -> Result<RomSearch> {
+        let (found, mut not_found) = self.get_ids_from_files(roms);
+
+        let mut rom_search = self.find_sets_for_roms(found, rom_mode);
+        rom_search.unknowns.append(&mut not_found);
+        Ok(rom_search)
+    }
+
+    fn get_sets_using_rom_id(&self, rom_id: u32) -> Result<RomSearch> {
+        let mut result = RomSearch::new();
+        for game_rom in self.game_roms.iter().filter(|game_rom| game_rom.rom_id == rom_id) {
+            result.add_file_for_set(game_rom.game_name.clone(), self.get_rom(game_rom));
+        }
+
+        Ok(result)
+    }
+
+    fn get_devices_for_game<S>(&self, game_name: S) -> Result<SetDependencies> where S: AsRef<str> + rusqlite::ToSql {
+        let mut set_dependencies = SetDependencies::resolve_devices(game_name.as_ref(), |game_name| {
+            self.devices.get(game_name).cloned().unwrap_or_default()
+        });
+        // Only devices with roms are a dependency
+        set_dependencies.dependencies.retain(|device| {
+            self.game_roms.iter().any(|game_rom| &game_rom.game_name == device)
+        });
+
+        Ok(set_dependencies)
+    }
+
+    fn get_file_checks(&self) -> Result<FileCheckSearch> {
+        Ok(FileCheckSearch {
+            sha1: self.roms.iter().filter(|rom| rom.sha1.is_some()).count() as u32,
+            md5: self.roms.iter().filter(|rom| rom.md5.is_some()).count() as u32,
+            crc: self.roms.iter().filter(|rom| rom.crc.is_some()).count() as u32,
+        })
+    }
+}
+
+/// Declares a game for a `FixtureBuilder`
+#[derive(Debug, Clone)]
+pub struct GameFixture {
+    game: Game,
+    roms: Vec<DataFile>,
+    devices: Vec<String>,
+}
+
+impl GameFixture {
+    pub fn new<S>(name: S) -> Self where S: Into<String> {
+        Self { game: Game::new(name.into()), roms: vec![], devices: vec![] }
+    }
+
+    /// Marks the game as a clone of `parent`, sets both `cloneof` and `romof`
+    pub fn clone_of<S>(mut self, parent: S) -> Self where S: Into<String> {
+        let parent = parent.into();
+        self.game.clone_of = Some(parent.clone());
+        self.game.rom_of = Some(parent);
+        self
+    }
+
+    pub fn description<S>(mut self, description: S) -> Self where S: Into<String> {
+        self.game.info_description = Some(description.into());
+        self
+    }
+
+    pub fn rom<S>(mut self, name: S, sha1: S, crc: S, size: u32) -> Self where S: Into<String> {
+        let mut info = DataFileInfo::new(FileType::Rom);
+        info.sha1 = Some(sha1.into());
+        info.crc = Some(crc.into());
+        info.size = Some(size);
+        self.roms.push(DataFile::new(name, info));
+        self
+    }
+
+    pub fn data_file(mut self, data_file: DataFile) -> Self {
+        self.roms.push(data_file);
+        self
+    }
+
+    pub fn device<S>(mut self, device: S) -> Self where S: Into<String> {
+        self.devices.push(device.into());
+        self
+    }
+}
+
+/// Builds a `MockDataReader` declaring games, roms and parents programmatically.
+/// Roms with the same info share the same id, as they do in the database.
+#[derive(Debug, Default)]
+pub struct FixtureBuilder {
+    games: Vec<GameFixture>,
+}
+
+impl FixtureBuilder {
+    pub fn new() -> Self {
+        Self { games: vec![] }
+    }
+
+    pub fn game(mut self, game: GameFixture) -> Self {
+        self.games.push(game);
+        self
+    }
+
+    pub fn build(self) -> Result<MockDataReader> {
+        let mut reader = MockDataReader::default();
+        let mut rom_ids: HashMap<DataFileInfo, u32> = HashMap::new();
+
+        for fixture in self.games {
+            let game_name = fixture.game.name.clone();
+            if reader.games.contains_key(&game_name) {
+                return err!(RomstError::GenericError { message: format!("Game {} declared twice", game_name) });
+            }
+            for rom in fixture.roms {
+                let next_id = reader.roms.len() as u32;
+                let id = *rom_ids.entry(rom.info.clone()).or_insert_with(|| {
+                    reader.roms.push(rom.info.clone());
+                    next_id
+                });
+                reader.game_roms.push(MockGameRom { game_name: game_name.clone(), rom_id: id, name: rom.name, status: rom.status, bios: rom.bios, extra_attrs: rom.extra_attrs, parent: None });
+            }
+            reader.devices.insert(game_name.clone(), fixture.devices);
+            reader.games.insert(game_name, fixture.game);
+        }
+
+        // Same as the DBWriter does when finishing, roms also present in the parent are marked
+        let parent_roms = reader.game_roms.iter().map(|game_rom| (game_rom.game_name.clone(), game_rom.rom_id)).collect::<HashSet<_>>();
+        for game_rom in reader.game_roms.iter_mut() {
+            if let Some(parent) = reader.games.get(&game_rom.game_name).and_then(|game| game.rom_of.clone()) {
+                if parent_roms.contains(&(parent.clone(), game_rom.rom_id)) {
+                    game_rom.parent = Some(parent);
+                }
+            }
+        }
+
+        Ok(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{ScanOptions, data::reporter::Reporter};
+
+    use super::*;
+
+    fn get_fixture() -> Result<MockDataReader> {
+        FixtureBuilder::new()
+            .game(GameFixture::new("device1")
+                .rom("devrom1.bin", "802e076afc412be12db3cb8c79523f65d612a6cf", "dc20b010", 8192))
+            .game(GameFixture::new("game1")
+                .rom("rom1.trom", "8bb3a81b9fa2de5163f0ffc634a998c455bcca25", "1d460eee", 2048)
+                .rom("rom2.trom", "bea1c74957e8f659d51fbac19ed8d2418a741927", "b4069ce5", 2048)
+                .device("device1"))
+            .game(GameFixture::new("game1a").clone_of("game1")
+                .rom("rom1.trom", "8bb3a81b9fa2de5163f0ffc634a998c455bcca25", "1d460eee", 2048)
+                .rom("rom4.trom", "dbb660bbff3c7e37fd0397ccbac593c0f71da6d4", "c6b7767e", 2048))
+            .game(GameFixture::new("game4")
+                .rom("rrham.rom", "b47a81a4bce8d7abd8f940b0acb5674776b4ae03", "7182d83b", 2048)
+                .rom("rhum1.rom", "0d0410009c5bd3802b0021c8f29edc997a83c88c", "4bec6e65", 2048)
+                .rom("rhum2.rom", "0d0410009c5bd3802b0021c8f29edc997a83c88c", "4bec6e65", 2048)
+                .rom("rhin1.rom", "5bef439d1d775e0ff3a17189478e87b3dd5d0e49", "0d46fa2d", 2048))
+            .build()
+    }
+
+    #[test]
+    fn gets_romset_roms_by_mode() -> Result<()> {
+        let reader = get_fixture()?;
+
+        assert_eq!(3, reader.get_romset_roms("game1", RomsetMode::Merged)?.len());
+        assert_eq!(2, reader.get_romset_roms("game1a", RomsetMode::NonMerged)?.len());
+        assert_eq!(1, reader.get_romset_roms("game1a", RomsetMode::Split)?.len());
+        assert_eq!(vec!["device1".to_string()], reader.get_devices_for_game("game1")?.dependencies);
+        assert_eq!(3, reader.get_game_list(RomsetMode::Merged)?.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reporter_works_with_mock() -> Result<()> {
+        let mut reporter = Reporter::new(get_fixture()?);
+
+        let game_path = Path::new("testdata").join("single");
+        let report = reporter.check(vec![ game_path ], ScanOptions::new(RomsetMode::NonMerged)).await?;
+
+        assert_eq!(1, report.sets.len());
+        let set = report.sets.get("game4").unwrap();
+        assert_eq!(4, set.roms_available.len());
+        assert!(set.roms_missing.is_empty());
+
+        Ok(())
+    }
+}
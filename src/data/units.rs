@@ -0,0 +1,66 @@
+//! The sizes and counts as they are printed in the plain output, the sizes in binary units like `1.5 MiB` and the
+//! counts with their thousands separated. The serialized reports keep the raw values.
+use std::fmt::Display;
+
+const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// A size in bytes, printed with the largest unit it has at least one of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteSize(pub u64);
+
+impl Display for ByteSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 < 1024 {
+            return write!(f, "{} bytes", self.0);
+        }
+        let mut value = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        // `1023.96 KiB` rounds to `1024.0 KiB`, better as `1 MiB`
+        if format!("{:.1}", value) == "1024.0" && unit < UNITS.len() - 1 {
+            value = 1.0;
+            unit += 1;
+        }
+        let value = format!("{:.1}", value);
+        write!(f, "{} {}", value.trim_end_matches(".0"), UNITS[unit])
+    }
+}
+
+/// A count with a comma between each group of thousands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Count(pub u64);
+
+impl Display for Count {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = self.0.to_string();
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        write!(f, "{}", grouped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prints_the_sizes_and_counts() {
+        assert_eq!("512 bytes", ByteSize(512).to_string());
+        assert_eq!("64 KiB", ByteSize(65536).to_string());
+        assert_eq!("1.5 MiB", ByteSize(1024 * 1024 * 3 / 2).to_string());
+        assert_eq!("1 MiB", ByteSize(1024 * 1024 - 1).to_string());
+        assert_eq!("2.3 GiB", ByteSize(2_469_606_195).to_string());
+        assert_eq!("0", Count(0).to_string());
+        assert_eq!("999", Count(999).to_string());
+        assert_eq!("1,000", Count(1000).to_string());
+        assert_eq!("12,345,678", Count(12_345_678).to_string());
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+
+use crate::data::units::ByteSize;
+
+/// The size of the database before and after compacting it, in bytes
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VacuumReport {
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+impl VacuumReport {
+    pub fn get_reclaimed(&self) -> u64 {
+        self.size_before.saturating_sub(self.size_after)
+    }
+}
+
+impl Display for VacuumReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Size before: {}", ByteSize(self.size_before))?;
+        writeln!(f, "Size after: {}", ByteSize(self.size_after))?;
+        writeln!(f, "Reclaimed: {}", ByteSize(self.get_reclaimed()))
+    }
+}
+
+/// Rewrites the database without the pages left free by the deleted rows, and refreshes the statistics the queries
+/// are planned with
+pub fn vacuum(conn: &Connection) -> Result<VacuumReport> {
+    let size_before = get_database_size(conn)?;
+    conn.execute_batch("VACUUM; PRAGMA optimize;")?;
+    let size_after = get_database_size(conn)?;
+
+    Ok(VacuumReport { size_before, size_after })
+}
+
+/// The pages of the database, what the file takes on disk without the journal
+pub(crate) fn get_database_size(conn: &Connection) -> Result<u64> {
+    let page_count: u64 = conn.query_row("PRAGMA page_count;", params![], |row| row.get(0))?;
+    let page_size: u64 = conn.query_row("PRAGMA page_size;", params![], |row| row.get(0))?;
+    Ok(page_count * page_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reclaims_the_space_of_deleted_rows() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch("CREATE TABLE roms (id INTEGER PRIMARY KEY, sha1 TEXT);")?;
+        for id in 0..2000 {
+            conn.execute("INSERT INTO roms (id, sha1) VALUES (?1, ?2);", params![id, format!("{:040x}", id)])?;
+        }
+        conn.execute("DELETE FROM roms WHERE id >= 100;", params![])?;
+
+        let report = vacuum(&conn)?;
+
+        assert!(report.size_after < report.size_before);
+        assert_eq!(report.size_before - report.size_after, report.get_reclaimed());
+        Ok(())
+    }
+}
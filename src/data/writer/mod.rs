@@ -1,12 +1,39 @@
 pub mod sqlite;
+pub mod maintenance;
+pub mod references;
 
 use anyhow::Result;
 
 use super::models::{dat_info::DatInfo, disk::GameDisk, file::*, game::Game};
 
+/// A rom sharing the sha1 with a rom written before, but with a different size or crc, usually a malformed DAT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomConflict {
+    pub game_name: String,
+    pub rom_name: String,
+    pub rom_id: u32,
+    pub info: DataFileInfo,
+    pub conflicts_with: u32,
+    pub conflicts_with_info: DataFileInfo,
+}
+
 pub trait DataWriter {
     fn init(&self) -> Result<()>;
     fn on_new_entry(&mut self, game: Game, roms: Vec<DataFile>, disks: Vec<GameDisk>, samples: Vec<String>, device_refs: Vec<String>) -> Result<()>;
     fn on_dat_info(&mut self, dat_info: DatInfo) -> Result<()>;
     fn finish(&mut self) -> Result<()>;
+    /// Finishes the import reporting the progress of the last updates as the items done and the total, writers
+    /// without a long finish just finish
+    fn finish_with_progress(&mut self, _on_progress: &mut dyn FnMut(u64, u64)) -> Result<()> {
+        self.finish()
+    }
+    /// The conflicts found since the last call, writers that don't check for them return none
+    fn take_rom_conflicts(&mut self) -> Vec<RomConflict> {
+        vec![]
+    }
+    /// The games not written because the database already had one with the same name, writers that don't add to an
+    /// existing database return none
+    fn take_skipped_games(&mut self) -> Vec<String> {
+        vec![]
+    }
 }
\ No newline at end of file
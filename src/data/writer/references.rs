@@ -0,0 +1,202 @@
+//! The parents, bios and devices the games point to that aren't in the database. Trimmed DATs sometimes keep clones
+//! of parents they don't have, and the sets in split and merged modes are resolved without them silently, so they are
+//! listed after importing, and can be fixed creating empty games for them or forgetting the references.
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::Result;
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+
+use crate::{err, error::RomstError};
+
+/// The description of the games created for the missing references
+const STUB_DESCRIPTION: &str = "Not in the DAT, created for the games referencing it";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceKind {
+    CloneOf,
+    RomOf,
+    Device,
+}
+
+impl Display for ReferenceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReferenceKind::CloneOf => write!(f, "clone of"),
+            ReferenceKind::RomOf => write!(f, "rom of"),
+            ReferenceKind::Device => write!(f, "device"),
+        }
+    }
+}
+
+/// A game pointing to another one that isn't in the database
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DanglingReference {
+    pub game_name: String,
+    pub kind: ReferenceKind,
+    pub missing: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceFix {
+    /// Creates an empty game for each missing one, the clones keep their parent
+    CreateStubs,
+    /// Removes the references, the clones are taken as parents
+    Drop,
+}
+
+impl Display for ReferenceFix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReferenceFix::CreateStubs => write!(f, "stub"),
+            ReferenceFix::Drop => write!(f, "drop"),
+        }
+    }
+}
+
+impl FromStr for ReferenceFix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "stub" => Ok(ReferenceFix::CreateStubs),
+            "drop" => Ok(ReferenceFix::Drop),
+            _ => err!(RomstError::GenericError { message: format!("Unknown fix `{}`, can be either `stub` or `drop`", s) }),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReferencesReport {
+    /// The references found before fixing them
+    pub dangling: Vec<DanglingReference>,
+    pub fix: Option<ReferenceFix>,
+    /// The games created with the stubs fix
+    pub stubs: Vec<String>,
+}
+
+impl ReferencesReport {
+    pub fn is_empty(&self) -> bool {
+        self.dangling.is_empty()
+    }
+}
+
+impl Display for ReferencesReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Dangling references: {}", self.dangling.len())?;
+        for reference in &self.dangling {
+            writeln!(f, "- {}: {} `{}`", reference.game_name, reference.kind, reference.missing)?;
+        }
+        match self.fix {
+            Some(ReferenceFix::CreateStubs) => writeln!(f, "Games created: {}", self.stubs.len()),
+            Some(ReferenceFix::Drop) => writeln!(f, "References removed: {}", self.dangling.len()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The clones, bios and device references pointing to games that aren't in the database, sorted by game
+pub fn find_dangling_references(conn: &Connection) -> Result<Vec<DanglingReference>> {
+    let queries = [
+        (ReferenceKind::CloneOf, "SELECT name, clone_of FROM games WHERE clone_of IS NOT NULL AND clone_of NOT IN (SELECT name FROM games);"),
+        (ReferenceKind::RomOf, "SELECT name, rom_of FROM games WHERE rom_of IS NOT NULL AND rom_of NOT IN (SELECT name FROM games);"),
+        (ReferenceKind::Device, "SELECT game_name, device_ref FROM devices WHERE device_ref NOT IN (SELECT name FROM games);"),
+    ];
+
+    let mut dangling = vec![];
+    for (kind, query) in queries {
+        let mut stmt = conn.prepare(query)?;
+        let mut rows = stmt.query(params![])?;
+        while let Some(row) = rows.next()? {
+            dangling.push(DanglingReference { game_name: row.get(0)?, kind, missing: row.get(1)? });
+        }
+    }
+    dangling.sort();
+    Ok(dangling)
+}
+
+/// Lists the dangling references, and fixes them if asked
+pub fn verify_references(conn: &mut Connection, fix: Option<ReferenceFix>) -> Result<ReferencesReport> {
+    let dangling = find_dangling_references(conn)?;
+    let mut report = ReferencesReport { dangling, fix, stubs: vec![] };
+    if report.is_empty() {
+        return Ok(report);
+    }
+
+    let tx = conn.transaction()?;
+    match fix {
+        Some(ReferenceFix::CreateStubs) => {
+            let mut stubs = report.dangling.iter().map(|reference| reference.missing.to_string()).collect::<Vec<_>>();
+            stubs.sort();
+            stubs.dedup();
+            {
+                let mut stmt = tx.prepare("INSERT INTO games (name, info_desc) VALUES (?1, ?2);")?;
+                for stub in &stubs {
+                    stmt.execute(params![stub, STUB_DESCRIPTION])?;
+                }
+            }
+            report.stubs = stubs;
+        },
+        Some(ReferenceFix::Drop) => {
+            tx.execute("UPDATE games SET clone_of = NULL WHERE clone_of NOT IN (SELECT name FROM games);", params![])?;
+            tx.execute("UPDATE games SET rom_of = NULL WHERE rom_of NOT IN (SELECT name FROM games);", params![])?;
+            tx.execute("DELETE FROM devices WHERE device_ref NOT IN (SELECT name FROM games);", params![])?;
+        },
+        None => {},
+    }
+    tx.commit()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::{ImportOptions, Romst, data::reader::DataReader, sysout::DatImporterReporterSysOut};
+
+    use super::*;
+
+    fn import_with_orphan(db_file: &str) -> Result<Connection> {
+        Romst::import_dat("testdata/test.dat", db_file, ImportOptions { overwrite: true, ..Default::default() }, None::<DatImporterReporterSysOut>)?;
+        let conn = Connection::open(db_file)?;
+        conn.execute("INSERT INTO games (name, clone_of, rom_of) VALUES ('orphan', 'noparent', 'noparent');", params![])?;
+        conn.execute("INSERT INTO devices (game_name, device_ref) VALUES ('orphan', 'nodevice');", params![])?;
+        Ok(conn)
+    }
+
+    #[test]
+    fn fixes_the_dangling_references() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_references_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+
+        let mut conn = import_with_orphan(&db_file)?;
+        let report = verify_references(&mut conn, None)?;
+        assert_eq!(vec![
+            DanglingReference { game_name: "orphan".to_string(), kind: ReferenceKind::CloneOf, missing: "noparent".to_string() },
+            DanglingReference { game_name: "orphan".to_string(), kind: ReferenceKind::RomOf, missing: "noparent".to_string() },
+            DanglingReference { game_name: "orphan".to_string(), kind: ReferenceKind::Device, missing: "nodevice".to_string() },
+        ], report.dangling);
+        assert_eq!(3, find_dangling_references(&conn)?.len());
+
+        let report = verify_references(&mut conn, Some(ReferenceFix::CreateStubs))?;
+        assert_eq!(vec!["nodevice".to_string(), "noparent".to_string()], report.stubs);
+        assert!(find_dangling_references(&conn)?.is_empty());
+        let reader = Romst::get_data_reader(&db_file)?;
+        assert_eq!(Some("noparent".to_string()), reader.get_game("orphan").and_then(|game| game.clone_of));
+        drop(reader);
+        drop(conn);
+
+        let mut conn = import_with_orphan(&db_file)?;
+        verify_references(&mut conn, Some(ReferenceFix::Drop))?;
+        assert!(find_dangling_references(&conn)?.is_empty());
+        let reader = Romst::get_data_reader(&db_file)?;
+        assert_eq!(None, reader.get_game("orphan").and_then(|game| game.clone_of));
+        assert!(reader.get_game("noparent").is_none());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,11 +1,28 @@
-use std::{collections::{HashMap, HashSet}, iter::FromIterator, rc::Rc};
+use std::{collections::{BTreeMap, HashMap, HashSet}, iter::FromIterator, sync::Arc};
 
 use anyhow::Result;
+use chrono::Utc;
 use log::{debug, error};
-use rusqlite::{Connection, params};
-
-use crate::{data::{models::{disk::{GameDisk, GameDiskInfo}, file::{DataFile, DataFileInfo}, game::Game}, reader::sqlite::DBReader}};
-use super::DataWriter;
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+
+use crate::{RomsetMode, data::{filter::GameFilter, ignored_roms::IgnoredRoms, models::{disk::{GameDisk, GameDiskInfo}, file::{DataFile, DataFileInfo}, game::Game}, reader::sqlite::DBReader, reporter::scan_report::SetStatus}, err, error::RomstError};
+use super::{DataWriter, RomConflict};
+
+pub const SCHEMA_VERSION: u32 = 1;
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+pub const IMPORT_STATUS_KEY: &str = "import_status";
+pub const IMPORT_STATUS_IN_PROGRESS: &str = "in_progress";
+pub const IMPORT_STATUS_COMPLETE: &str = "complete";
+pub const IMPORT_DATE_KEY: &str = "import_date";
+pub const MODE_OVERRIDES_TABLE: &str = "set_mode_overrides";
+pub const ROM_CONFLICTS_TABLE: &str = "rom_conflicts";
+pub const CHECK_STATUS_TABLE: &str = "check_status";
+pub const GAME_CATEGORIES_TABLE: &str = "game_categories";
+pub const SET_TAGS_TABLE: &str = "set_tags";
+pub const SET_NOTES_TABLE: &str = "set_notes";
+pub const IGNORED_ROMS_TABLE: &str = "ignored_roms";
+/// The parent updates committed in each transaction when finishing an import
+const FINISH_CHUNK_SIZE: usize = 5000;
 
 #[derive(Debug)]
 pub struct IdsCounter {
@@ -13,6 +30,12 @@ pub struct IdsCounter {
     disk: u32,
 }
 
+impl Default for IdsCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IdsCounter {
     pub fn new() -> Self { Self { rom: 0, disk: 0 } }
 
@@ -33,12 +56,15 @@ pub struct DBWriter<'d> {
     conn: &'d mut Connection,
     buffer: Buffer,
     buffer_size: u16,
+    rom_conflicts: Vec<RomConflict>,
+    append: bool,
+    skipped_games: Vec<String>,
 }
 
 #[derive(Debug)]
 struct Buffer {
     ids: IdsCounter,
-    games: HashMap<String, Rc<Game>>,
+    games: HashMap<String, Arc<Game>>,
 
     roms: HashMap<DataFileInfo, u32>,
     game_roms: HashMap<String, Vec<GameFileBufferItem>>,
@@ -52,15 +78,19 @@ struct Buffer {
 struct GameFileBufferItem {
     name: String,
     id: u32,
-    status: Option<String>
+    status: Option<String>,
+    bios: Option<String>,
+    extra_attrs: Option<String>,
 }
 
 impl GameFileBufferItem {
     fn from_data_file(rom_id: u32, data_file: DataFile) -> Self {
-        Self { name: data_file.name, id: rom_id, status: data_file.status } 
+        // Stored as JSON, there are only a few roms with extra attributes
+        let extra_attrs = data_file.extra_attrs.and_then(|extra_attrs| serde_json::to_string(&extra_attrs).ok());
+        Self { name: data_file.name, id: rom_id, status: data_file.status, bios: data_file.bios, extra_attrs }
     }
     fn from_disk_file(disk_id: u32, disk_file: GameDisk) -> Self {
-        Self { name: disk_file.name, id: disk_id, status: disk_file.info.status }
+        Self { name: disk_file.name, id: disk_id, status: disk_file.info.status, bios: None, extra_attrs: None }
     }
 }
 
@@ -82,7 +112,7 @@ impl Buffer {
         self.games.len() + self.samples.len() + self.roms.len() + self.game_roms.len()
     }
 
-    fn add_game(&mut self, game_name: String, game: Rc<Game>) {
+    fn add_game(&mut self, game_name: String, game: Arc<Game>) {
         self.games.insert(game_name, game);
     }
 
@@ -109,7 +139,7 @@ impl Buffer {
     }
 
     fn add_sample_pack(&mut self, sample_pack: String, samples: Vec<String>) {
-        self.samples.entry(sample_pack).or_insert_with(HashSet::new).extend(samples);
+        self.samples.entry(sample_pack).or_default().extend(samples);
     }
 
     fn add_disks(&mut self, disks: Vec<GameDisk>) -> Vec<(u32, GameDisk)> {
@@ -143,13 +173,30 @@ impl Buffer {
 
 impl <'d> DBWriter<'d> {
     pub fn from_connection(conn: &'d mut Connection, buffer_size: u16) -> Self {
-        Self { conn, buffer: Buffer::new(), buffer_size }
+        Self { conn, buffer: Buffer::new(), buffer_size, rom_conflicts: vec![], append: false, skipped_games: vec![] }
+    }
+
+    /// A writer adding the entries of another DAT to a database already imported, the ids continue after the ones
+    /// written and the games already in the database are skipped
+    pub fn appending(conn: &'d mut Connection, buffer_size: u16) -> Result<Self> {
+        let rom: u32 = conn.query_row("SELECT IFNULL(MAX(id) + 1, 0) FROM roms;", params![], |row| row.get(0))?;
+        let disk: u32 = conn.query_row("SELECT IFNULL(MAX(id) + 1, 0) FROM disks;", params![], |row| row.get(0))?;
+        let mut writer = Self::from_connection(conn, buffer_size);
+        writer.buffer.ids = IdsCounter { rom, disk };
+        writer.append = true;
+        Ok(writer)
+    }
+
+    fn is_game_written(&self, game_name: &str) -> Result<bool> {
+        let found = self.conn.query_row("SELECT 1 FROM games WHERE name = ?1;", params![game_name], |_| Ok(()))
+            .optional()?;
+        Ok(found.is_some())
     }
 
     fn remove_table_if_exist(&self, table_name: &str) -> Result<()> {
         let sql = "SELECT name FROM sqlite_master WHERE type='table' AND name = ?1;";
         let result: Result<String, rusqlite::Error>  = self.conn.query_row(sql, params![ table_name ], |row| {
-            Ok(row.get(0)?)
+            row.get(0)
         });
 
         match result {
@@ -165,6 +212,7 @@ impl <'d> DBWriter<'d> {
     }
 
     fn create_schema(&self) -> Result<()> {
+        self.create_table_schema_info()?;
         self.create_table_info()?;
         self.create_table_roms()?;
         self.create_table_games()?;
@@ -173,6 +221,26 @@ impl <'d> DBWriter<'d> {
         self.create_table_disks()?;
         self.create_table_game_disks()?;
         self.create_table_samples()?;
+        self.create_table_game_metadata()?;
+        self.create_table_game_categories()?;
+        self.create_table_mode_overrides()?;
+        self.create_table_user_data()?;
+        self.create_table_rom_conflicts()?;
+
+        Ok(())
+    }
+
+    fn create_table_schema_info(&self) -> Result<()> {
+        self.remove_table_if_exist("schema_info")?;
+        self.conn.execute(
+            "CREATE TABLE schema_info (
+                key     TEXT PRIMARY KEY,
+                value   TEXT);",
+            params![])?;
+        self.conn.execute("INSERT INTO schema_info (key, value) VALUES (?1, ?2);",
+            params![SCHEMA_VERSION_KEY, SCHEMA_VERSION.to_string()])?;
+        self.conn.execute("INSERT INTO schema_info (key, value) VALUES (?1, ?2);",
+            params![IMPORT_STATUS_KEY, IMPORT_STATUS_IN_PROGRESS])?;
 
         Ok(())
     }
@@ -246,6 +314,8 @@ impl <'d> DBWriter<'d> {
                 name        TEXT,
                 status      TEXT,
                 parent      TEXT,
+                bios        TEXT,
+                extra_attrs TEXT,
                 PRIMARY KEY (game_name, rom_id, name));",
             params![])?;
         debug!("Creating Games/ROMs indexes");
@@ -329,6 +399,67 @@ impl <'d> DBWriter<'d> {
         Ok(())
     }
 
+    fn create_table_game_metadata(&self) -> Result<()> {
+        debug!("Creating game metadata table");
+        self.remove_table_if_exist("game_metadata")?;
+        // Only filled when importing with the extended metadata
+        self.conn.execute(
+            "CREATE TABLE game_metadata (
+                game_name           TEXT PRIMARY KEY,
+                driver_status       TEXT,
+                emulation_status    TEXT,
+                players             INTEGER,
+                orientation         TEXT,
+                display_type        TEXT);",
+            params![])?;
+
+        Ok(())
+    }
+
+    fn create_table_game_categories(&self) -> Result<()> {
+        debug!("Creating game categories table");
+        self.remove_table_if_exist(GAME_CATEGORIES_TABLE)?;
+        // Only filled for the games nested in `dir` elements
+        self.conn.execute(
+            &format!("CREATE TABLE {} (
+                game_name   TEXT PRIMARY KEY,
+                category    TEXT NOT NULL);", GAME_CATEGORIES_TABLE),
+            params![])?;
+
+        Ok(())
+    }
+
+    fn create_table_mode_overrides(&self) -> Result<()> {
+        debug!("Creating set mode overrides table");
+        self.remove_table_if_exist(MODE_OVERRIDES_TABLE)?;
+        create_table_mode_overrides_if_not_exists(self.conn)
+    }
+
+    fn create_table_user_data(&self) -> Result<()> {
+        debug!("Creating set tags, notes and ignored roms tables");
+        self.remove_table_if_exist(SET_TAGS_TABLE)?;
+        self.remove_table_if_exist(SET_NOTES_TABLE)?;
+        self.remove_table_if_exist(IGNORED_ROMS_TABLE)?;
+        create_tables_user_data_if_not_exists(self.conn)
+    }
+
+    fn create_table_rom_conflicts(&self) -> Result<()> {
+        debug!("Creating rom conflicts table");
+        self.remove_table_if_exist(ROM_CONFLICTS_TABLE)?;
+        self.conn.execute(
+            &format!("CREATE TABLE {} (
+                rom_id          INTEGER,
+                game_name       TEXT,
+                name            TEXT,
+                sha1            TEXT,
+                crc             TEXT,
+                size            INT,
+                conflicts_with  INTEGER);", ROM_CONFLICTS_TABLE),
+            params![])?;
+
+        Ok(())
+    }
+
     fn get_rom_ids(&mut self, roms: Vec<DataFile>) -> Result<Vec<GameFileBufferItem>> {
         // We search the database
         let rom_ids = DBReader::get_ids_from_files(self.conn, roms)?;
@@ -407,12 +538,45 @@ impl <'d> DBWriter<'d> {
                     Ok(_) => {}
                     Err(e) => { error!("Error inserting row in the games db: {}", e) }
                 }
+
+            if let Some(category) = &game.category {
+                let result = tx.execute(&format!("INSERT INTO {} (game_name, category) VALUES (?1, ?2);", GAME_CATEGORIES_TABLE),
+                    params![game.name, category]);
+                if let Err(e) = result {
+                    error!("Error inserting row in the game categories db: {}", e)
+                }
+            }
+
+            if let Some(metadata) = &game.metadata {
+                let result = tx.execute("INSERT INTO game_metadata (game_name, driver_status, emulation_status, players, orientation, display_type)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+                    params![game.name, metadata.driver_status, metadata.emulation_status, metadata.players, metadata.orientation, metadata.display_type]);
+                if let Err(e) = result {
+                    error!("Error inserting the metadata of the game `{}`: {}", game.name, e);
+                }
+            }
         }
 
         for rom_data in rom_buffer {
             let rom_row_id = rom_data.1;
             let rom = rom_data.0;
 
+            if let Some((conflicts_with, conflicts_with_info)) = find_rom_conflict(&tx, rom)? {
+                let (game_name, rom_name) = game_rom_buffer.iter().find_map(|(game_name, items)| {
+                    items.iter().find(|item| item.id == *rom_row_id).map(|item| (game_name.to_owned(), item.name.to_owned()))
+                }).unwrap_or_default();
+                tx.execute(&format!("INSERT INTO {} (rom_id, game_name, name, sha1, crc, size, conflicts_with) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7);", ROM_CONFLICTS_TABLE),
+                    params![rom_row_id, game_name, rom_name, rom.sha1, rom.crc, rom.size, conflicts_with])?;
+                self.rom_conflicts.push(RomConflict {
+                    game_name,
+                    rom_name,
+                    rom_id: *rom_row_id,
+                    info: rom.to_owned(),
+                    conflicts_with,
+                    conflicts_with_info,
+                });
+            }
+
             let result = tx.execute(
                 "INSERT INTO roms (id, sha1, md5, crc, size) VALUES (?1, ?2, ?3, ?4, ?5);",
                 params![ rom_row_id, rom.sha1, rom.md5, rom.crc, rom.size ]);
@@ -427,8 +591,8 @@ impl <'d> DBWriter<'d> {
             let rom_id_names = game_roms.1;
             for rom_id_name in rom_id_names {
                 let result = tx.execute(
-                    "INSERT INTO game_roms (game_name, rom_id, name, status) VALUES (?1, ?2, ?3, ?4);",
-                    params![ game_name, rom_id_name.id, rom_id_name.name, rom_id_name.status ] );
+                    "INSERT INTO game_roms (game_name, rom_id, name, status, bios, extra_attrs) VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+                    params![ game_name, rom_id_name.id, rom_id_name.name, rom_id_name.status, rom_id_name.bios, rom_id_name.extra_attrs ] );
                 match result {
                     Ok(_n) => { debug!("Inserted rom {} with id {} to the game {}", rom_id_name.name, rom_id_name.id, game_name) }
                     Err(e) => { error!("Error adding rom `{}` to the game {}: {}", rom_id_name.name, "", e) }
@@ -514,7 +678,7 @@ impl <'d> DBWriter<'d> {
         Ok(Vec::from_iter(rows))
     }
 
-    fn add_game(&mut self, game: Rc<Game>) -> Result<()> {
+    fn add_game(&mut self, game: Arc<Game>) -> Result<()> {
         let game_name = game.name.to_owned();
         self.buffer.add_game(game_name, game); 
 
@@ -556,16 +720,26 @@ impl <'d> DBWriter<'d> {
 
 impl <'d> DataWriter for DBWriter<'d> {
     fn init(&self) -> Result<()> {
-        self.create_schema()
+        if self.append {
+            self.conn.execute("UPDATE schema_info SET value = ?1 WHERE key = ?2;", params![IMPORT_STATUS_IN_PROGRESS, IMPORT_STATUS_KEY])?;
+            Ok(())
+        } else {
+            self.create_schema()
+        }
     }
     
     fn on_new_entry(&mut self, game: Game, roms: Vec<DataFile>, disks: Vec<GameDisk>, samples: Vec<String>, device_refs: Vec<String>) -> Result<()> {
-        let game_ref = Rc::new(game);
+        // There are no namespaces, the game from the first DAT is kept
+        if self.append && self.is_game_written(&game.name)? {
+            self.skipped_games.push(game.name);
+            return Ok(());
+        }
+        let game_ref = Arc::new(game);
 
         let game_name = &game_ref.name;
         let sample = game_ref.sample_of.as_ref();
 
-        self.add_game(Rc::clone(&game_ref))?;
+        self.add_game(Arc::clone(&game_ref))?;
         self.add_roms_for_game(roms, game_name)?;
         if let Some(sample_name) = sample {
             self.add_samples(samples, sample_name)?;
@@ -577,27 +751,55 @@ impl <'d> DataWriter for DBWriter<'d> {
     }
 
     fn finish(&mut self) -> Result<()> {
+        self.finish_with_progress(&mut |_done, _total| {})
+    }
+
+    fn finish_with_progress(&mut self, on_progress: &mut dyn FnMut(u64, u64)) -> Result<()> {
         self.write_buffer()?;
         let roms_from_parents = self.get_roms_from_parents()?;
-
-        let tx = self.conn.transaction()?;
-        for item in roms_from_parents {
-            let game_name = item.0;
-            let rom_id = item.1;
-            let parent = item.2;
-
-            let result = tx.execute("UPDATE game_roms SET parent = ?1
-                WHERE game_roms.game_name = ?2 AND game_roms.rom_id = ?3;", params![parent, game_name, rom_id])?;
-            if result > 1 {
-                debug!("Updated {} rows, should be only 1 for game {}, rom_id {}, with parent {}, unless is a 'nodump'", result, game_name, rom_id, parent);
+        let total = roms_from_parents.len() as u64;
+        on_progress(0, total);
+
+        // Committed in chunks so a big DAT doesn't keep a single huge transaction
+        let mut done = 0;
+        for chunk in roms_from_parents.chunks(FINISH_CHUNK_SIZE) {
+            let tx = self.conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached("UPDATE game_roms SET parent = ?1
+                    WHERE game_roms.game_name = ?2 AND game_roms.rom_id = ?3;")?;
+                for (game_name, rom_id, parent) in chunk {
+                    let result = stmt.execute(params![parent, game_name, rom_id])?;
+                    if result > 1 {
+                        debug!("Updated {} rows, should be only 1 for game {}, rom_id {}, with parent {}, unless is a 'nodump'", result, game_name, rom_id, parent);
+                    }
+                }
             }
+            tx.commit()?;
+            done += chunk.len() as u64;
+            on_progress(done, total);
         }
+
+        let tx = self.conn.transaction()?;
+        tx.execute("UPDATE schema_info SET value = ?1 WHERE key = ?2;", params![IMPORT_STATUS_COMPLETE, IMPORT_STATUS_KEY])?;
+        tx.execute("INSERT OR REPLACE INTO schema_info (key, value) VALUES (?1, ?2);", params![IMPORT_DATE_KEY, Utc::now().to_rfc3339()])?;
         tx.commit()?;
 
         Ok(())
     }
 
+    fn take_rom_conflicts(&mut self) -> Vec<RomConflict> {
+        std::mem::take(&mut self.rom_conflicts)
+    }
+
+    fn take_skipped_games(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.skipped_games)
+    }
+
     fn on_dat_info(&mut self, dat_info: crate::data::models::dat_info::DatInfo) -> Result<()> {
+        // The database keeps the info of the first DAT imported
+        if self.append {
+            return Ok(());
+        }
         let tx = self.conn.transaction()?;
         // We insert the common fields
         if !dat_info.name.is_empty() {
@@ -625,9 +827,347 @@ impl <'d> DataWriter for DBWriter<'d> {
     }
 }
 
+/// Finds a rom already written with the same sha1, but a different size or crc. Unknown sizes or crcs are not a conflict
+fn find_rom_conflict(tx: &Transaction, rom: &DataFileInfo) -> Result<Option<(u32, DataFileInfo)>> {
+    let sha1 = match &rom.sha1 {
+        Some(sha1) => sha1,
+        None => return Ok(None),
+    };
+
+    let conflict = tx.query_row("SELECT id, md5, crc, size FROM roms WHERE sha1 = ?1 AND (size != ?2 OR crc != ?3) LIMIT 1;",
+        params![sha1, rom.size, rom.crc], |row| {
+            let mut info = DataFileInfo::new(rom.file_type.clone());
+            info.sha1 = Some(sha1.to_owned());
+            info.md5 = row.get(1)?;
+            info.crc = row.get(2)?;
+            info.size = row.get(3)?;
+            Ok((row.get(0)?, info))
+        }).optional()?;
+
+    Ok(conflict)
+}
+
+/// Databases imported before the overrides existed don't have the table
+fn create_table_mode_overrides_if_not_exists(conn: &Connection) -> Result<()> {
+    conn.execute(
+        &format!("CREATE TABLE IF NOT EXISTS {} (
+            game_name   TEXT PRIMARY KEY,
+            mode        TEXT NOT NULL);", MODE_OVERRIDES_TABLE),
+        params![])?;
+
+    Ok(())
+}
+
+fn check_game_exists(conn: &Connection, game_name: &str) -> Result<()> {
+    let games: u32 = conn.query_row("SELECT COUNT(*) FROM games WHERE name = ?1;", params![game_name], |row| row.get(0))?;
+    if games == 0 {
+        return err!(RomstError::GenericError { message: format!("Game {} not found", game_name) });
+    }
+
+    Ok(())
+}
+
+/// Sets the mode a set is always checked with, whatever the mode of the check is. Without mode the override is removed
+pub fn write_mode_override(conn: &Connection, game_name: &str, rom_mode: Option<RomsetMode>) -> Result<()> {
+    check_game_exists(conn, game_name)?;
+
+    create_table_mode_overrides_if_not_exists(conn)?;
+    match rom_mode {
+        Some(rom_mode) => {
+            conn.execute(&format!("INSERT OR REPLACE INTO {} (game_name, mode) VALUES (?1, ?2);", MODE_OVERRIDES_TABLE), params![game_name, rom_mode.get_key()])?;
+        }
+        None => {
+            conn.execute(&format!("DELETE FROM {} WHERE game_name = ?1;", MODE_OVERRIDES_TABLE), params![game_name])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Databases imported before the tags and notes existed don't have the tables
+fn create_tables_user_data_if_not_exists(conn: &Connection) -> Result<()> {
+    conn.execute(
+        &format!("CREATE TABLE IF NOT EXISTS {} (
+            game_name   TEXT NOT NULL,
+            tag         TEXT NOT NULL,
+            PRIMARY KEY (game_name, tag));", SET_TAGS_TABLE),
+        params![])?;
+    conn.execute(
+        &format!("CREATE TABLE IF NOT EXISTS {} (
+            game_name   TEXT PRIMARY KEY,
+            note        TEXT NOT NULL);", SET_NOTES_TABLE),
+        params![])?;
+    // A rom ignored by sha1 has no set and name, and one ignored in a set has no sha1
+    conn.execute(
+        &format!("CREATE TABLE IF NOT EXISTS {} (
+            sha1        TEXT NOT NULL DEFAULT '',
+            game_name   TEXT NOT NULL DEFAULT '',
+            rom_name    TEXT NOT NULL DEFAULT '',
+            PRIMARY KEY (sha1, game_name, rom_name));", IGNORED_ROMS_TABLE),
+        params![])?;
+
+    Ok(())
+}
+
+/// A tag is a single word or a few words, without commas as the UIs use them to separate the tags
+fn check_tag(tag: &str) -> Result<()> {
+    if tag.trim().is_empty() || tag.contains(',') {
+        return err!(RomstError::GenericError { message: format!("Non valid tag `{}`, it can't be empty or contain commas", tag) });
+    }
+
+    Ok(())
+}
+
+/// Adds the tags to the set, the tags it already has are kept
+pub fn add_set_tags(conn: &Connection, game_name: &str, tags: &[String]) -> Result<()> {
+    check_game_exists(conn, game_name)?;
+    for tag in tags {
+        check_tag(tag)?;
+    }
+
+    create_tables_user_data_if_not_exists(conn)?;
+    for tag in tags {
+        conn.execute(&format!("INSERT OR IGNORE INTO {} (game_name, tag) VALUES (?1, ?2);", SET_TAGS_TABLE), params![game_name, tag.trim()])?;
+    }
+
+    Ok(())
+}
+
+/// Removes the tags from the set, without tags all of them are removed
+pub fn remove_set_tags(conn: &Connection, game_name: &str, tags: &[String]) -> Result<()> {
+    check_game_exists(conn, game_name)?;
+
+    create_tables_user_data_if_not_exists(conn)?;
+    if tags.is_empty() {
+        conn.execute(&format!("DELETE FROM {} WHERE game_name = ?1;", SET_TAGS_TABLE), params![game_name])?;
+    }
+    for tag in tags {
+        conn.execute(&format!("DELETE FROM {} WHERE game_name = ?1 AND tag = ?2;", SET_TAGS_TABLE), params![game_name, tag.trim()])?;
+    }
+
+    Ok(())
+}
+
+/// Attaches a free text note to the set, replacing the previous one. Without note, or with an empty one, it's removed
+pub fn write_set_note(conn: &Connection, game_name: &str, note: Option<&str>) -> Result<()> {
+    check_game_exists(conn, game_name)?;
+
+    create_tables_user_data_if_not_exists(conn)?;
+    match note.map(|note| note.trim()).filter(|note| !note.is_empty()) {
+        Some(note) => {
+            conn.execute(&format!("INSERT OR REPLACE INTO {} (game_name, note) VALUES (?1, ?2);", SET_NOTES_TABLE), params![game_name, note])?;
+        }
+        None => {
+            conn.execute(&format!("DELETE FROM {} WHERE game_name = ?1;", SET_NOTES_TABLE), params![game_name])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds the roms to the ignore list, the sets of the roms ignored by name must exist
+pub fn add_ignored_roms(conn: &Connection, ignored_roms: &IgnoredRoms) -> Result<()> {
+    for game_name in ignored_roms.roms.keys() {
+        check_game_exists(conn, game_name)?;
+    }
+
+    create_tables_user_data_if_not_exists(conn)?;
+    for sha1 in &ignored_roms.sha1s {
+        conn.execute(&format!("INSERT OR IGNORE INTO {} (sha1) VALUES (?1);", IGNORED_ROMS_TABLE), params![sha1])?;
+    }
+    for (game_name, rom_names) in &ignored_roms.roms {
+        for rom_name in rom_names {
+            conn.execute(&format!("INSERT OR IGNORE INTO {} (game_name, rom_name) VALUES (?1, ?2);", IGNORED_ROMS_TABLE), params![game_name, rom_name])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Takes the roms out of the ignore list, so they are required again
+pub fn remove_ignored_roms(conn: &Connection, ignored_roms: &IgnoredRoms) -> Result<()> {
+    create_tables_user_data_if_not_exists(conn)?;
+    for sha1 in &ignored_roms.sha1s {
+        conn.execute(&format!("DELETE FROM {} WHERE sha1 = ?1;", IGNORED_ROMS_TABLE), params![sha1])?;
+    }
+    for (game_name, rom_names) in &ignored_roms.roms {
+        for rom_name in rom_names {
+            conn.execute(&format!("DELETE FROM {} WHERE game_name = ?1 AND rom_name = ?2;", IGNORED_ROMS_TABLE), params![game_name, rom_name])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies the tags, notes and ignored roms from the database imported before, so importing a DAT again keeps them.
+/// Only the ones of the sets still in the new database are copied, returns how many were copied
+pub fn copy_user_data(conn: &Connection, previous_db_file: &str) -> Result<usize> {
+    create_tables_user_data_if_not_exists(conn)?;
+    conn.execute("ATTACH DATABASE ?1 AS previous;", params![previous_db_file])?;
+    let copied = copy_user_data_from_attached(conn);
+    conn.execute("DETACH DATABASE previous;", params![])?;
+
+    copied
+}
+
+fn copy_user_data_from_attached(conn: &Connection) -> Result<usize> {
+    let mut copied = 0;
+    for table in &[SET_TAGS_TABLE, SET_NOTES_TABLE] {
+        let tables: u32 = conn.query_row("SELECT COUNT(*) FROM previous.sqlite_master WHERE type = 'table' AND name = ?1;", params![table], |row| row.get(0))?;
+        if tables > 0 {
+            copied += conn.execute(&format!("INSERT OR IGNORE INTO main.{0} SELECT * FROM previous.{0} WHERE game_name IN (SELECT name FROM main.games);", table), params![])?;
+        }
+    }
+    let tables: u32 = conn.query_row("SELECT COUNT(*) FROM previous.sqlite_master WHERE type = 'table' AND name = ?1;", params![IGNORED_ROMS_TABLE], |row| row.get(0))?;
+    if tables > 0 {
+        // The roms ignored by sha1 are kept whatever sets there are
+        copied += conn.execute(&format!("INSERT OR IGNORE INTO main.{0} SELECT * FROM previous.{0} WHERE game_name = '' OR game_name IN (SELECT name FROM main.games);", IGNORED_ROMS_TABLE), params![])?;
+    }
+
+    Ok(copied)
+}
+
+/// Removes the games not matching the filter, but keeps the parents, bios and devices the remaining games depend on.
+/// The roms, disks and samples no game uses anymore are removed too. Returns the number of games removed
+pub fn retain_games(conn: &mut Connection, game_filter: &GameFilter) -> Result<u32> {
+    let tx = conn.transaction()?;
+
+    let mut dependencies: HashMap<String, Vec<String>> = HashMap::new();
+    let mut pending = vec![];
+    {
+        let mut stmt = tx.prepare(&format!("SELECT name, clone_of, rom_of, source_file, category FROM games
+            LEFT JOIN {} ON game_name = name;", GAME_CATEGORIES_TABLE))?;
+        let mut rows = stmt.query(params![])?;
+        while let Some(row) = rows.next()? {
+            let mut game = Game::new(row.get(0)?);
+            game.clone_of = row.get(1)?;
+            game.rom_of = row.get(2)?;
+            game.source_file = row.get(3)?;
+            game.category = row.get(4)?;
+
+            let game_dependencies = game.clone_of.iter().chain(game.rom_of.iter()).cloned().collect();
+            if game_filter.matches(&game) {
+                pending.push(game.name.to_owned());
+            }
+            dependencies.insert(game.name, game_dependencies);
+        }
+
+        let mut stmt = tx.prepare("SELECT game_name, device_ref FROM devices;")?;
+        let mut rows = stmt.query(params![])?;
+        while let Some(row) = rows.next()? {
+            let game_name: String = row.get(0)?;
+            dependencies.entry(game_name).or_default().push(row.get(1)?);
+        }
+    }
+
+    let mut kept = HashSet::new();
+    while let Some(game_name) = pending.pop() {
+        if let Some(game_dependencies) = dependencies.get(&game_name) {
+            pending.extend(game_dependencies.iter().filter(|dependency| !kept.contains(*dependency)).cloned());
+        }
+        kept.insert(game_name);
+    }
+
+    let mut removed = 0;
+    for game_name in dependencies.keys().filter(|game_name| !kept.contains(*game_name)) {
+        tx.execute("DELETE FROM games WHERE name = ?1;", params![game_name])?;
+        for table in ["game_roms", "game_disks", "devices", "game_metadata", GAME_CATEGORIES_TABLE, ROM_CONFLICTS_TABLE, MODE_OVERRIDES_TABLE] {
+            tx.execute(&format!("DELETE FROM {} WHERE game_name = ?1;", table), params![game_name])?;
+        }
+        removed += 1;
+    }
+
+    tx.execute("DELETE FROM roms WHERE id NOT IN (SELECT rom_id FROM game_roms);", params![])?;
+    tx.execute("DELETE FROM disks WHERE id NOT IN (SELECT disk_id FROM game_disks);", params![])?;
+    tx.execute("DELETE FROM samples WHERE sample_set NOT IN (SELECT sample_of FROM games WHERE sample_of IS NOT NULL);", params![])?;
+    tx.commit()?;
+
+    debug!("Removed {} games not matching the filter", removed);
+    Ok(removed)
+}
+
+fn create_table_check_status_if_not_exists(conn: &Connection) -> Result<()> {
+    conn.execute(
+        &format!("CREATE TABLE IF NOT EXISTS {} (
+            root_directory      TEXT NOT NULL,
+            game_name           TEXT NOT NULL,
+            status              TEXT NOT NULL,
+            checked_at          TEXT,
+            directory_modified  INTEGER,
+            PRIMARY KEY (root_directory, game_name));", CHECK_STATUS_TABLE),
+        params![])?;
+
+    // The statuses stored before the check time was kept don't have the columns
+    let columns = conn.prepare(&format!("PRAGMA table_info({});", CHECK_STATUS_TABLE))?
+        .query_map(params![], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<HashSet<_>>>()?;
+    for (column, column_type) in &[("checked_at", "TEXT"), ("directory_modified", "INTEGER")] {
+        if !columns.contains(*column) {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {};", CHECK_STATUS_TABLE, column, column_type), params![])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The changes to the schema made after the first version, the one at `i` takes a database from version `i + 1` to `i + 2`
+const SCHEMA_MIGRATIONS: &[&str] = &[];
+
+/// Brings a database written by an older version up to `SCHEMA_VERSION`, adding the tables created after it was
+/// imported. Returns the version the database had, 0 if it's older than the `schema_info` table
+pub fn migrate_schema(conn: &mut Connection) -> Result<u32> {
+    let tx = conn.transaction()?;
+    let has_schema_info: u32 = tx.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'schema_info';", params![], |row| row.get(0))?;
+    let from_version = if has_schema_info == 0 {
+        // Databases older than the table were always imported completely
+        tx.execute("CREATE TABLE schema_info (key TEXT PRIMARY KEY, value TEXT);", params![])?;
+        tx.execute("INSERT INTO schema_info (key, value) VALUES (?1, ?2);", params![IMPORT_STATUS_KEY, IMPORT_STATUS_COMPLETE])?;
+        0
+    } else {
+        let version: Option<String> = tx.query_row("SELECT value FROM schema_info WHERE key = ?1;", params![SCHEMA_VERSION_KEY], |row| row.get(0)).optional()?;
+        version.and_then(|version| version.parse().ok()).unwrap_or(0)
+    };
+    if from_version > SCHEMA_VERSION {
+        return err!(RomstError::GenericError { message: format!("The database has the schema version {}, newer than the {} this version of romst supports", from_version, SCHEMA_VERSION) });
+    }
+
+    for migration in SCHEMA_MIGRATIONS.iter().skip(from_version.saturating_sub(1) as usize) {
+        tx.execute_batch(migration)?;
+    }
+    create_table_mode_overrides_if_not_exists(&tx)?;
+    create_tables_user_data_if_not_exists(&tx)?;
+    create_table_check_status_if_not_exists(&tx)?;
+    tx.execute("INSERT OR REPLACE INTO schema_info (key, value) VALUES (?1, ?2);", params![SCHEMA_VERSION_KEY, SCHEMA_VERSION.to_string()])?;
+    tx.commit()?;
+
+    Ok(from_version)
+}
+
+/// Replaces the statuses of the sets of the last check of the directory, along with the time the directory was
+/// modified so the statuses can be discarded once it changes
+pub fn write_check_statuses(conn: &mut Connection, root_directory: &str, directory_modified: Option<i64>, statuses: &BTreeMap<String, SetStatus>) -> Result<()> {
+    create_table_check_status_if_not_exists(conn)?;
+    let checked_at = Utc::now().to_rfc3339();
+    let tx = conn.transaction()?;
+    tx.execute(&format!("DELETE FROM {} WHERE root_directory = ?1;", CHECK_STATUS_TABLE), params![root_directory])?;
+    {
+        let mut stmt = tx.prepare(&format!("INSERT INTO {} (root_directory, game_name, status, checked_at, directory_modified) VALUES (?1, ?2, ?3, ?4, ?5);", CHECK_STATUS_TABLE))?;
+        for (game_name, status) in statuses {
+            stmt.execute(params![root_directory, game_name, status.get_key(), checked_at, directory_modified])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::IdsCounter;
+    use std::{env, fs};
+
+    use crate::{ImportOptions, Romst, filesystem::paths, sysout::DatImporterReporterSysOut};
+
+    use super::*;
 
     #[test]
     fn test_counter() {
@@ -652,4 +1192,184 @@ mod tests {
             assert_eq!(i as u32, nums[i])
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn keeps_the_filtered_games_and_their_dependencies() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_retain_games_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+
+        let mut game_filter = GameFilter::default();
+        game_filter.add_include("game1a")?;
+        game_filter.add_include("game2")?;
+        let options = ImportOptions { overwrite: true, game_filter, ..Default::default() };
+        let import_report = Romst::import_dat("testdata/test.dat", &db_file, options, None::<DatImporterReporterSysOut>)?;
+
+        let conn = Connection::open(&db_file)?;
+        let mut stmt = conn.prepare("SELECT name FROM games ORDER BY name;")?;
+        let games = stmt.query_map(params![], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+        assert_eq!(vec!["device1", "deviceref", "game1", "game1a", "game2", "screen"], games);
+        assert_eq!(4, import_report.excluded);
+
+        let orphan_roms: u32 = conn.query_row("SELECT COUNT(*) FROM roms WHERE id NOT IN (SELECT rom_id FROM game_roms);", params![], |row| row.get(0))?;
+        assert_eq!(0, orphan_roms);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_tags_and_notes_importing_again() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_user_data_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        let options = || ImportOptions { overwrite: true, ..Default::default() };
+
+        Romst::import_dat("testdata/test.dat", &db_file, options(), None::<DatImporterReporterSysOut>)?;
+        Romst::add_set_tags(db_file.as_str(), "game1", &["favorite".to_string(), "broken-dump".to_string()])?;
+        Romst::remove_set_tags(db_file.as_str(), "game1", &["broken-dump".to_string()])?;
+        Romst::set_set_note(db_file.as_str(), "game2", Some("Needs a redump"))?;
+        assert!(Romst::add_set_tags(db_file.as_str(), "game2", &["bad,tag".to_string()]).is_err());
+        assert!(Romst::add_set_tags(db_file.as_str(), "nogame", &["favorite".to_string()]).is_err());
+
+        Romst::import_dat("testdata/test.dat", &db_file, options(), None::<DatImporterReporterSysOut>)?;
+        let user_data = Romst::get_user_data(db_file.as_str(), None)?;
+        assert_eq!(vec!["favorite"], user_data.sets["game1"].tags.iter().collect::<Vec<_>>());
+        assert_eq!(Some("Needs a redump".to_string()), user_data.sets["game2"].note);
+        assert_eq!(vec!["game1"], Romst::get_user_data(db_file.as_str(), Some("favorite"))?.sets.keys().collect::<Vec<_>>());
+
+        let mut game_filter = GameFilter::default();
+        game_filter.add_include("tag:favorite")?;
+        let import = Romst::import_dat("testdata/test.dat", &db_file, ImportOptions { game_filter, ..options() }, None::<DatImporterReporterSysOut>);
+        assert!(import.is_err());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_ignored_roms_importing_again() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_ignored_roms_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        let options = || ImportOptions { overwrite: true, ..Default::default() };
+
+        Romst::import_dat("testdata/test.dat", &db_file, options(), None::<DatImporterReporterSysOut>)?;
+        let mut ignored_roms = IgnoredRoms::default();
+        ignored_roms.add_sha1("0123456789abcdef0123456789abcdef01234567")?;
+        ignored_roms.add_rom("game1/rom1.rom")?;
+        ignored_roms.add_rom("game2/rom3.rom")?;
+        Romst::add_ignored_roms(db_file.as_str(), &ignored_roms)?;
+        let mut removed = IgnoredRoms::default();
+        removed.add_rom("game2/rom3.rom")?;
+        Romst::remove_ignored_roms(db_file.as_str(), &removed)?;
+        let mut unknown = IgnoredRoms::default();
+        unknown.add_rom("nogame/rom1.rom")?;
+        assert!(Romst::add_ignored_roms(db_file.as_str(), &unknown).is_err());
+
+        Romst::import_dat("testdata/test.dat", &db_file, options(), None::<DatImporterReporterSysOut>)?;
+        let stored = Romst::get_ignored_roms(db_file.as_str())?;
+        assert_eq!(vec!["0123456789abcdef0123456789abcdef01234567"], stored.sha1s.iter().collect::<Vec<_>>());
+        assert_eq!(vec!["game1"], stored.roms.keys().collect::<Vec<_>>());
+        assert!(stored.roms["game1"].contains("rom1.rom"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn appends_the_games_of_several_dats() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_import_dats_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        let extra_dat = dir.join("extra.dat").to_string_lossy().to_string();
+        fs::write(&extra_dat, r#"<?xml version="1.0"?>
+<datafile>
+    <header><name>Extra</name></header>
+    <game name="game1"><rom name="other.rom" size="4" crc="11111111"/></game>
+    <game name="extra"><rom name="extra.rom" size="4" crc="22222222" sha1="2222222222222222222222222222222222222222"/></game>
+</datafile>"#)?;
+
+        let before = Romst::import_dat("testdata/test.dat", &db_file, ImportOptions { overwrite: true, ..Default::default() }, None::<DatImporterReporterSysOut>)?;
+        let conn = Connection::open(&db_file)?;
+        let count_games = |conn: &Connection| conn.query_row("SELECT COUNT(*) FROM games;", params![], |row| row.get::<_, u32>(0));
+        let games_before = count_games(&conn)?;
+        drop(conn);
+
+        let inputs = ["testdata/test.dat".to_string(), extra_dat];
+        let report = Romst::import_dats(&inputs, db_file.clone(), ImportOptions { overwrite: true, ..Default::default() }, None::<DatImporterReporterSysOut>)?;
+        assert_eq!(2, report.dats.len());
+        assert_eq!(before.entries, report.dats[0].report.entries);
+        assert_eq!(1, report.dats[1].report.warnings);
+
+        let conn = Connection::open(&db_file)?;
+        assert_eq!(games_before + 1, count_games(&conn)?);
+        let game1_roms: Vec<String> = conn.prepare("SELECT name FROM game_roms WHERE game_name = 'game1';")?
+            .query_map(params![], |row| row.get(0))?.collect::<Result<_, _>>()?;
+        assert!(!game1_roms.contains(&"other.rom".to_string()));
+        let extra_rom: String = conn.query_row("SELECT roms.sha1 FROM game_roms JOIN roms ON roms.id = game_roms.rom_id WHERE game_name = 'extra';", params![], |row| row.get(0))?;
+        assert_eq!("2222222222222222222222222222222222222222", extra_rom);
+        let dat_names: u32 = conn.query_row("SELECT COUNT(*) FROM info WHERE key = 'name';", params![], |row| row.get(0))?;
+        assert_eq!(1, dat_names);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn stores_the_statuses_of_the_last_check() -> Result<()> {
+        let db_file = env::temp_dir().join(format!("romst_check_status_{}.rst", std::process::id()));
+        let statuses: BTreeMap<String, SetStatus> = vec![
+            ("game1".to_string(), SetStatus::COMPLETE),
+            ("game2".to_string(), SetStatus::INCOMPLETE),
+        ].into_iter().collect();
+
+        let mut conn = Connection::open(&db_file)?;
+        write_check_statuses(&mut conn, "/roms", Some(1000), &statuses)?;
+        write_check_statuses(&mut conn, "/other", None, &BTreeMap::new())?;
+        let complete = DBReader::from_connection(conn).get_last_complete_sets("/roms");
+        fs::remove_file(&db_file)?;
+
+        assert_eq!(vec!["game1".to_string()], complete?.into_iter().collect::<Vec<_>>());
+        Ok(())
+    }
+    #[test]
+    fn keeps_the_last_checks_until_the_directory_changes() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_last_checks_{}", std::process::id()));
+        let roms_dir = dir.join("roms");
+        fs::create_dir_all(&roms_dir)?;
+        let roms_directory = roms_dir.to_string_lossy().to_string();
+        let statuses: BTreeMap<String, SetStatus> = vec![("game1".to_string(), SetStatus::FIXEABLE)].into_iter().collect();
+
+        // A table from before the check time was kept
+        let mut conn = Connection::open(dir.join("test.rst"))?;
+        conn.execute_batch(&format!("CREATE TABLE {} (root_directory TEXT NOT NULL, game_name TEXT NOT NULL, status TEXT NOT NULL,
+            PRIMARY KEY (root_directory, game_name)); INSERT INTO {0} VALUES ('/old', 'game2', 'complete');", CHECK_STATUS_TABLE))?;
+        write_check_statuses(&mut conn, &roms_directory, paths::get_modified_millis(&roms_dir), &statuses)?;
+
+        let reader = DBReader::from_connection(conn);
+        let mut last_checks = reader.get_last_checks()?;
+        assert!(!last_checks.sets.contains_key("game2"));
+        last_checks.retain_current();
+        let last_check = last_checks.get_last_check("game1").unwrap();
+        assert_eq!(SetStatus::FIXEABLE, last_check.status);
+        assert_eq!(roms_directory, last_check.root_directory);
+
+        // Adding a file changes the time of the directory, tried until the clock moves on
+        for i in 0..100 {
+            fs::write(roms_dir.join(format!("game{}.zip", i)), "")?;
+            if paths::get_modified_millis(&roms_dir) != last_check.directory_modified {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let mut last_checks = reader.get_last_checks()?;
+        last_checks.retain_current();
+        assert!(last_checks.get_last_check("game1").is_none());
+
+        drop(reader);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
+
@@ -34,6 +34,18 @@ pub enum RomstError {
         message: String
     },
 
+    #[error("The database `{path}` is busy with another operation, try again once it finishes")]
+    DatabaseBusy {
+        path: String
+    },
+
+}
+
+impl RomstError {
+    /// The operation failed but can be run again, like when the database was locked by another operation
+    pub fn is_retriable(&self) -> bool {
+        matches!(self, RomstError::DatabaseBusy { .. })
+    }
 }
 
 #[derive(Error, Debug)]
@@ -49,4 +61,28 @@ pub enum RomstIOError {
 
     #[error("File not found {0}")]
     FileNotFound(String),
-}
\ No newline at end of file
+}
+impl RomstIOError {
+    /// The read failed for a reason that may be gone when reading again, like a network share dropping for a moment
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RomstIOError::Io { source } => matches!(source.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe)
+                || source.raw_os_error().map(is_transient_os_error).unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+/// The errors of the system the network filesystems return when the server doesn't answer, besides the generic EIO
+#[cfg(target_os = "linux")]
+fn is_transient_os_error(code: i32) -> bool {
+    [libc::EIO, libc::EAGAIN, libc::ETIMEDOUT, libc::ESTALE, libc::EHOSTDOWN, libc::EHOSTUNREACH, libc::ENETDOWN,
+        libc::ENETUNREACH, libc::ENETRESET, libc::ECONNRESET, libc::ECONNABORTED].contains(&code)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_transient_os_error(_code: i32) -> bool {
+    false
+}
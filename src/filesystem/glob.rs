@@ -0,0 +1,132 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Result;
+
+/// If the path has any of the `*`, `?` or `[` wildcards
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Finds the paths matching the pattern, sorted. The wildcards can be in any component of the path,
+/// but they don't cross directories, so `roms/*/*.zip` only looks one level deep
+pub fn glob_paths(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut candidates = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let name = match component {
+            Component::Normal(name) => name.to_string_lossy(),
+            _ => {
+                candidates.iter_mut().for_each(|candidate| candidate.push(component));
+                continue;
+            }
+        };
+
+        if !is_glob(&name) {
+            candidates.iter_mut().for_each(|candidate| candidate.push(name.as_ref()));
+            continue;
+        }
+
+        let mut matching = vec![];
+        for candidate in candidates {
+            let directory = if candidate.as_os_str().is_empty() { Path::new(".") } else { candidate.as_path() };
+            if !directory.is_dir() {
+                continue;
+            }
+            for entry in directory.read_dir()? {
+                let file_name = entry?.file_name();
+                let file_name = file_name.to_string_lossy();
+                // Hidden files only match if the pattern explicitly starts with a dot
+                if file_name.starts_with('.') && !name.starts_with('.') {
+                    continue;
+                }
+                if matches(&name, &file_name) {
+                    matching.push(candidate.join(file_name.as_ref()));
+                }
+            }
+        }
+        candidates = matching;
+    }
+
+    let mut paths = candidates.into_iter().filter(|path| path.exists()).collect::<Vec<_>>();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Matches a file name against a pattern, `*` is any sequence of characters, `?` any single character,
+/// and `[...]` any of the characters or ranges inside, or none of them if it starts with `!`
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    matches_from(&pattern, &name)
+}
+
+fn matches_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| matches_from(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && matches_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let end = match pattern.iter().skip(2).position(|c| *c == ']') {
+                Some(end) => end + 2,
+                // Not a class, just a bracket
+                None => return name.first() == Some(&'[') && matches_from(&pattern[1..], &name[1..]),
+            };
+            match name.first() {
+                Some(c) if matches_class(&pattern[1..end], *c) => matches_from(&pattern[end + 1..], &name[1..]),
+                _ => false,
+            }
+        },
+        Some(c) => name.first() == Some(c) && matches_from(&pattern[1..], &name[1..]),
+    }
+}
+
+fn matches_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found = found || (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found = found || class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_patterns() {
+        assert!(matches("*.zip", "game1.zip"));
+        assert!(!matches("*.zip", "game1.7z"));
+        assert!(matches("game?.zip", "game1.zip"));
+        assert!(!matches("game?.zip", "game1a.zip"));
+        assert!(matches("game[1-3].zip", "game2.zip"));
+        assert!(!matches("game[!1-3].zip", "game2.zip"));
+        assert!(matches("game[!1-3]*", "game5"));
+        assert!(matches("*", ""));
+        assert!(matches("[abc", "[abc"));
+    }
+
+    #[test]
+    fn finds_glob_paths() -> Result<()> {
+        let paths = glob_paths("testdata/split/game?.zip")?;
+        let names = paths.iter().map(|path| path.file_name().unwrap().to_string_lossy().to_string()).collect::<Vec<_>>();
+        assert_eq!(vec!["game1.zip", "game2.zip", "game3.zip", "game4.zip", "game5.zip"], names);
+        assert_eq!(Path::new("testdata").join("split").join("game1.zip"), paths[0]);
+
+        let paths = glob_paths("testdata/*/game4.zip")?;
+        assert_eq!(vec![Path::new("testdata/single/game4.zip"), Path::new("testdata/split/game4.zip")], paths);
+
+        assert!(glob_paths("testdata/none/*.zip")?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+//! The digests the files are identified with. The hashers wrap a `HashBackend`, so the implementation can be swapped
+//! without touching the code that hashes the files.
+use sha1::digest::{Digest, FixedOutputReset};
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Hashes the whole data at once, to the lowercase hex string the DATs use
+pub trait HashBackend {
+    fn get_hash(&mut self, data: &[u8]) -> String;
+}
+
+/// The RustCrypto digests, `sha-1` detects the SHA extensions of the CPU at runtime and uses them when available
+pub struct RustCryptoBackend<D> {
+    digest: D,
+}
+
+impl<D> Default for RustCryptoBackend<D> where D: Digest {
+    fn default() -> Self {
+        Self { digest: D::new() }
+    }
+}
+
+impl<D> HashBackend for RustCryptoBackend<D> where D: Digest + FixedOutputReset {
+    fn get_hash(&mut self, data: &[u8]) -> String {
+        Digest::update(&mut self.digest, data);
+        to_hex(&Digest::finalize_reset(&mut self.digest))
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        hex.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+        hex.push(HEX_DIGITS[(byte & 0x0f) as usize] as char);
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filesystem::{md5::MD5Hasher, sha1::SHA1Hasher};
+    use super::*;
+
+    #[test]
+    fn hashes_to_lowercase_hex() {
+        let mut sha1 = SHA1Hasher::new();
+        assert_eq!("a9993e364706816aba3e25717850c26c9cd0d89d", sha1.get_hash(b"abc"));
+        // The hasher is reset after each hash
+        assert_eq!("a9993e364706816aba3e25717850c26c9cd0d89d", sha1.get_hash(b"abc"));
+        assert_eq!("900150983cd24fb0d6963f7d28e17f72", MD5Hasher::new().get_hash(b"abc"));
+        assert_eq!("00ff10", to_hex(&[0x00, 0xff, 0x10]));
+    }
+}
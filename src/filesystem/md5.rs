@@ -1,15 +1,20 @@
-use md5::{Digest, Md5};
+use md5::Md5;
 
-pub struct MD5Hasher {
-    hasher: Md5,
+use super::hash::{HashBackend, RustCryptoBackend};
+
+pub struct MD5Hasher<B = RustCryptoBackend<Md5>> {
+    backend: B,
 }
 
 impl MD5Hasher {
-    pub fn new() -> Self { Self { hasher: <Md5 as md5::Digest>::new() } }
+    pub fn new() -> Self { Self::with_backend(RustCryptoBackend::default()) }
+}
+
+impl<B> MD5Hasher<B> where B: HashBackend {
+    /// Hashes with another implementation of MD5
+    pub fn with_backend(backend: B) -> Self { Self { backend } }
+
     pub fn get_hash(&mut self, data: &[u8]) -> String {
-        self.hasher.update(data);
-        let hash = self.hasher.finalize_reset();
-        format!("{:x}", hash)
+        self.backend.get_hash(data)
     }
 }
-
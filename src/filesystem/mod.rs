@@ -1,15 +1,22 @@
-mod sha1;
-mod md5;
+pub mod hash;
+pub(crate) mod sha1;
+pub(crate) mod md5;
+pub(crate) mod glob;
+pub(crate) mod throttle;
+pub(crate) mod paths;
+#[cfg(feature = "rar")]
+pub(crate) mod rar;
 
 use anyhow::Result;
 use data::models::file::FileType;
 use zip::{ZipArchive, result::ZipError};
-use std::{fs::File, io::BufReader, path::Path};
+use std::{fmt::Display, fs::File, io::{self, BufReader, Read, Seek, SeekFrom}, path::{Path, PathBuf}};
 use bitflags::bitflags;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::{data::{self, models::{file::{DataFile, DataFileInfo}, game::Game, set::GameSet}}, error::RomstIOError};
+use crate::{data::{self, models::{archive::{ArchiveFormat, ArchiveInfo, ArchiveIssue}, file::{DataFile, DataFileInfo}, game::Game, set::GameSet}}, error::RomstIOError};
 
-use self::{md5::MD5Hasher, sha1::SHA1Hasher};
+use self::{md5::MD5Hasher, sha1::SHA1Hasher, throttle::{Throttle, ThrottledReader}};
 
 bitflags! {
     pub struct FileChecks: u32 {
@@ -21,9 +28,68 @@ bitflags! {
     }
 }
 
+impl FileChecks {
+    /// The names stored in the reports, in the same order they are shown
+    const NAMES: [(FileChecks, &'static str); 4] = [(FileChecks::SHA1, "sha1"), (FileChecks::MD5, "md5"), (FileChecks::CRC, "crc"), (FileChecks::SIZE, "size")];
+
+    /// Without sha1 nor md5 the roms are only told apart by the crc and size, two different roms can match
+    pub fn is_weak(&self) -> bool {
+        !self.intersects(FileChecks::SHA1 | FileChecks::MD5)
+    }
+
+    fn get_names(&self) -> Vec<&'static str> {
+        FileChecks::NAMES.iter()
+            .filter(|(check, _name)| self.contains(*check))
+            .map(|(_check, name)| *name)
+            .collect()
+    }
+}
+
+impl Display for FileChecks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self.get_names();
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(", "))
+        }
+    }
+}
+
+impl Serialize for FileChecks {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.collect_seq(self.get_names())
+    }
+}
+
+impl<'de> Deserialize<'de> for FileChecks {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        let mut file_checks = FileChecks::empty();
+        for name in Vec::<String>::deserialize(deserializer)? {
+            match FileChecks::NAMES.iter().find(|(_check, check_name)| *check_name == name) {
+                Some((check, _name)) => file_checks |= *check,
+                None => return Err(serde::de::Error::unknown_variant(&name, &["sha1", "md5", "crc", "size"])),
+            }
+        }
+        Ok(file_checks)
+    }
+}
+
+const TORRENTZIP_PREFIX: &str = "TORRENTZIPPED-";
+const RVZSTD_PREFIX: &str = "RVZSTD-";
+const LOCAL_FILE_HEADER_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x05, 0x06];
+const END_OF_CENTRAL_DIRECTORY_SIZE: u64 = 22;
+const CHD_TAG: &[u8; 8] = b"MComprHD";
+const CHD_V5_HEADER_SIZE: usize = 124;
+
 pub struct FileReader {
     sha1_hasher: SHA1Hasher,
     md5_hasher: MD5Hasher,
+    throttle: Option<Throttle>,
+    /// The info of the last rar archive read, so it's not extracted again to get it
+    #[cfg(feature = "rar")]
+    rar_info: Option<(PathBuf, ArchiveInfo)>,
 }
 
 impl FileReader {
@@ -31,84 +97,443 @@ impl FileReader {
         Self { 
             sha1_hasher: SHA1Hasher::new(),
             md5_hasher: MD5Hasher::new(),
+            throttle: None,
+            #[cfg(feature = "rar")]
+            rar_info: None,
         } 
     }
 
+    /// The files are read through the throttle, sharing its bandwidth with the other readers using it
+    pub(crate) fn set_throttle(&mut self, throttle: Throttle) {
+        self.throttle = Some(throttle);
+    }
+
+    fn open(&self, file_path: &impl AsRef<Path>) -> io::Result<ThrottledReader<File>> {
+        Ok(ThrottledReader::new(File::open(paths::to_long_path(file_path))?, self.throttle.clone()))
+    }
+
+    fn read(&self, file_path: &impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        self.open(file_path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
     pub fn build_game_set(&mut self, file_path: &impl AsRef<Path>, file_checks: FileChecks) -> Result<GameSet, RomstIOError> {
         let no_path = Path::new(file_path.as_ref()).with_extension("");
         let base_file_name = no_path.file_name();
 
         let game_name = match base_file_name {
-            Some(str_name) => {
-                str_name.to_str().unwrap_or_default()
+            Some(str_name) => paths::os_str_to_key(str_name),
+            None => String::new(),
+        };
+
+        let game = Game::new(game_name.trim().to_string());
+
+        #[cfg(feature = "rar")]
+        if rar::is_rar(file_path) {
+            let files = self.read_rar(file_path, file_checks)?;
+            return Ok(game_set_from_files(game, files));
+        }
+
+        let mut files = vec![];
+        if file_path.as_ref().is_dir() {
+            files = self.read_directory(file_path, file_checks)?;
+        } else {
+            let file = self.open(file_path)?;
+            let reader = BufReader::new(file);
+
+            match ZipArchive::new(reader) {
+                Ok(mut archive) => {
+                    for i in 0..archive.len() {
+                        let mut f = archive.by_index(i).map_err(|err| { RomstIOError::Io{ source: err.into() } })?;
+                        // The folders are only part of the entry names
+                        if f.is_dir() {
+                            continue;
+                        }
+                        let mut writer = vec![];
+                        std::io::copy(&mut f, &mut writer)?;
+
+                        let data_file = self.data_file_from_bytes(f.name(), &writer, f.crc32(), file_checks);
+                        files.push(data_file);
+                    }
+                },
+                Err(ZipError::InvalidArchive(_e)) => {
+                    let file_name = file_path.as_ref().to_path_buf().into_os_string().into_string().unwrap_or_else(|ref osstring| {
+                        osstring.to_string_lossy().to_string()
+                    });
+                    return Err(RomstIOError::NotValidFileError(file_name, FileType::Rom))
+                },
+                Err(ZipError::FileNotFound) => {
+                    let file_name = file_path.as_ref().to_path_buf().into_os_string().into_string().unwrap_or_else(|ref osstring| {
+                        osstring.to_string_lossy().to_string()
+                    });
+                    return Err(RomstIOError::FileNotFound(file_name))
+                },
+                Err(e) => { return Err(RomstIOError::Io{ source: e.into() }) }
             }
-            None => { "" }
-        }.trim();
+        }
 
-        let game = Game::new(game_name.to_string());
+        Ok(game_set_from_files(game, files))
+    }
 
-        let use_sha1 = file_checks.contains(FileChecks::SHA1);
-        let use_md5 = file_checks.contains(FileChecks::MD5);
-        let use_crc = file_checks.contains(FileChecks::CRC);
-        let use_size = file_checks.contains(FileChecks::SIZE);
+    /// Reads the entries of a rar archive, extracting it with an external command as there's no reader for the format
+    #[cfg(feature = "rar")]
+    fn read_rar(&mut self, file_path: &impl AsRef<Path>, file_checks: FileChecks) -> Result<Vec<DataFile>, RomstIOError> {
+        let extracted = rar::ExtractedRar::extract(file_path)?;
+        let mut files = vec![];
+        let mut total_size = 0;
+        for (name, path) in extracted.get_files()? {
+            let bytes = self.read(&path)?;
+            total_size += bytes.len() as u64;
+            files.push(self.data_file_from_bytes(&name, &bytes, crc32fast::hash(&bytes), file_checks));
+        }
 
-        let mut roms = vec![];
-        let file = File::open(&file_path)?;
-        let reader = BufReader::new(file);
+        let archive_info = ArchiveInfo::new(ArchiveFormat::Rar, files.len(), total_size, String::new());
+        self.rar_info = Some((file_path.as_ref().to_path_buf(), archive_info));
 
-        match ZipArchive::new(reader) {
+        Ok(files)
+    }
+
+    /// Reads the files in a directory, the disks only have their header read, the sha1 is taken from it
+    fn read_directory(&mut self, dir_path: &impl AsRef<Path>, file_checks: FileChecks) -> Result<Vec<DataFile>, RomstIOError> {
+        let mut paths = paths::to_long_path(dir_path).read_dir()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut files = vec![];
+        for path in paths {
+            files.push(self.read_file(&path, file_checks)?);
+        }
+
+        Ok(files)
+    }
+
+    /// Builds a set from loose files, each file is a rom named as the file
+    pub fn build_loose_game_set(&mut self, set_name: &str, file_paths: &[PathBuf], file_checks: FileChecks) -> Result<GameSet, RomstIOError> {
+        let mut files = vec![];
+        for path in file_paths {
+            files.push(self.read_file(path, file_checks)?);
+        }
+
+        Ok(GameSet::new(Game::new(set_name.to_string()), files, vec![], vec![], vec![]))
+    }
+
+    /// Reads a file outside an archive, the disks are identified by the sha1 in their header
+    fn read_file(&mut self, path: &impl AsRef<Path>, file_checks: FileChecks) -> Result<DataFile, RomstIOError> {
+        let name = paths::get_file_name_key(path);
+        let data_file = match FileType::from_file_name(&name) {
+            FileType::Disk => {
+                let mut header = vec![0; CHD_V5_HEADER_SIZE];
+                let mut file = self.open(path)?;
+                let read = file.read(&mut header)?;
+                header.truncate(read);
+                let mut info = DataFileInfo::new(FileType::Disk);
+                // A file without a CHD header is hashed whole
+                info.sha1 = match get_chd_sha1(&header) {
+                    Some(sha1) => Some(sha1),
+                    None => Some(self.sha1_hasher.get_hash(&self.read(path)?)),
+                };
+                DataFile::new(name, info)
+            },
+            _ => {
+                let bytes = self.read(path)?;
+                self.data_file_from_bytes(&name, &bytes, crc32fast::hash(&bytes), file_checks)
+            }
+        };
+
+        Ok(data_file)
+    }
+
+    fn data_file_from_bytes(&mut self, name: &str, bytes: &[u8], crc32: u32, file_checks: FileChecks) -> DataFile {
+        let file_type = FileType::from_file_name(name);
+        if file_type == FileType::Disk {
+            let mut info = DataFileInfo::new(file_type);
+            info.sha1 = get_chd_sha1(bytes);
+            return DataFile::new(name.trim(), info);
+        }
+
+        let sha1 = if file_checks.contains(FileChecks::SHA1) { Some(self.sha1_hasher.get_hash(bytes)) } else { None };
+        let md5 = if file_checks.contains(FileChecks::MD5) { Some(self.md5_hasher.get_hash(bytes)) } else { None };
+        let size = if file_checks.contains(FileChecks::SIZE) { Some(bytes.len() as u32) } else { None };
+        let crc = if file_checks.contains(FileChecks::CRC) { 
+            let crc32 = format!("{:01$x}", crc32, 8);
+            Some(crc32)
+        } else { None };
+
+        DataFile {
+            name: name.trim().to_string(),
+            info: DataFileInfo {
+                file_type,
+                sha1,
+                md5,
+                crc,
+                size,
+            },
+            status: None,
+            bios: None,
+            extra_attrs: None,
+        }
+    }
+
+    /// Extracts every entry of the archive to check its data against the crc of its header. If the central directory
+    /// is broken, the local headers are counted to know how many entries can be salvaged. Directories have no issues
+    pub fn verify_archive(&self, file_path: &impl AsRef<Path>) -> Result<Vec<ArchiveIssue>, RomstIOError> {
+        let file_path = file_path.as_ref();
+        if file_path.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut issues = vec![];
+        match ZipArchive::new(BufReader::new(self.open(&file_path)?)) {
             Ok(mut archive) => {
                 for i in 0..archive.len() {
-                    let mut f = archive.by_index(i).map_err(|err| { RomstIOError::Io{ source: err.into() } })?;
-                    let mut writer = vec![];
-                    std::io::copy(&mut f, &mut writer)?;
-
-                    let sha1 =  if use_sha1 { Some(self.sha1_hasher.get_hash(&writer)) } else { None };
-                    let md5 =  if use_md5 { Some(self.md5_hasher.get_hash(&writer)) } else { None };
-                    let size = if use_size { Some(f.size() as u32) } else { None };
-                    let crc = if use_crc { 
-                        let crc32 = format!("{:01$x}", f.crc32(), 8);
-                        Some(crc32)
-                    } else { None };
-
-                    let rom = DataFile {
-                        name: f.name().trim().to_string(),
-                        info: DataFileInfo {
-                            file_type: FileType::Rom,
-                            sha1,
-                            md5,
-                            crc,
-                            size,
-                        },
-                        status: None
+                    let mut entry = match archive.by_index(i) {
+                        Ok(entry) => entry,
+                        Err(e) => {
+                            issues.push(ArchiveIssue::UnreadableEntry { entry: format!("#{}", i), message: e.to_string() });
+                            continue;
+                        }
+                    };
+                    if entry.is_dir() {
+                        continue;
+                    }
+
+                    let expected = entry.crc32();
+                    let mut hasher = crc32fast::Hasher::new();
+                    let mut buffer = [0; 64 * 1024];
+                    // The zip reader fails at the end of the entry when the crc doesn't match
+                    let read_error = loop {
+                        match entry.read(&mut buffer) {
+                            Ok(0) => break None,
+                            Ok(read) => hasher.update(&buffer[..read]),
+                            Err(e) => break Some(e),
+                        }
                     };
-                    
-                    roms.push(rom);
+                    let found = hasher.finalize();
+                    if found != expected {
+                        issues.push(ArchiveIssue::CrcMismatch { entry: entry.name().to_string(), expected: format!("{:08x}", expected), found: format!("{:08x}", found) });
+                    } else if let Some(e) = read_error {
+                        issues.push(ArchiveIssue::UnreadableEntry { entry: entry.name().to_string(), message: e.to_string() });
+                    }
                 }
-            },
+            }
+            Err(ZipError::InvalidArchive(_e)) => {
+                let bytes = self.read(&file_path)?;
+                let salvageable_entries = bytes.windows(LOCAL_FILE_HEADER_SIGNATURE.len())
+                    .filter(|window| *window == LOCAL_FILE_HEADER_SIGNATURE)
+                    .count();
+                // Without local headers it's not a zip file, it's reported as not valid when reading it
+                if salvageable_entries > 0 {
+                    issues.push(ArchiveIssue::BrokenCentralDirectory { salvageable_entries });
+                }
+            }
+            Err(e) => return Err(RomstIOError::Io{ source: e.into() }),
+        }
+
+        Ok(issues)
+    }
+
+    /// Reads the archive level metadata, only the central directory is read, the files are not extracted
+    pub fn get_archive_info(&self, file_path: &impl AsRef<Path>) -> Result<ArchiveInfo, RomstIOError> {
+        #[cfg(feature = "rar")]
+        if rar::is_rar(file_path) {
+            return match &self.rar_info {
+                Some((path, archive_info)) if path == file_path.as_ref() => Ok(archive_info.to_owned()),
+                _ => {
+                    let extracted = rar::ExtractedRar::extract(file_path)?;
+                    let files = extracted.get_files()?;
+                    let total_size = files.iter().map(|(_name, path)| std::fs::metadata(path).map(|metadata| metadata.len())).sum::<io::Result<u64>>()?;
+                    Ok(ArchiveInfo::new(ArchiveFormat::Rar, files.len(), total_size, String::new()))
+                }
+            };
+        }
+
+        let file = self.open(file_path)?;
+        let reader = BufReader::new(file);
+
+        let mut archive = match ZipArchive::new(reader) {
+            Ok(archive) => archive,
             Err(ZipError::InvalidArchive(_e)) => {
-                let file_name = file_path.as_ref().to_path_buf().into_os_string().into_string().unwrap_or_else(|ref osstring| {
-                    osstring.to_string_lossy().to_string()
-                });
+                let file_name = file_path.as_ref().to_string_lossy().to_string();
                 return Err(RomstIOError::NotValidFileError(file_name, FileType::Rom))
             },
-            Err(ZipError::FileNotFound) => {
-                let file_name = file_path.as_ref().to_path_buf().into_os_string().into_string().unwrap_or_else(|ref osstring| {
-                    osstring.to_string_lossy().to_string()
-                });
-                return Err(RomstIOError::FileNotFound(file_name))
+            Err(e) => { return Err(RomstIOError::Io{ source: e.into() }) }
+        };
+
+        let comment = String::from_utf8_lossy(archive.comment()).to_string();
+        let entries = archive.len();
+        let mut total_size = 0;
+        let mut times = vec![];
+        for i in 0..entries {
+            let f = archive.by_index_raw(i).map_err(|err| { RomstIOError::Io{ source: err.into() } })?;
+            total_size += f.size();
+            let time = f.last_modified();
+            times.push(format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", time.year(), time.month(), time.day(), time.hour(), time.minute(), time.second()));
+        }
+
+        let format = get_archive_format(file_path, &comment)?;
+
+        Ok(ArchiveInfo::new(format, entries, total_size, comment).with_entry_times(times))
+    }
+
+    /// The entries of a zip with the name, size and crc its central directory lists for them, the files are not
+    /// extracted so their data is not verified
+    pub fn read_zip_directory(&self, file_path: &impl AsRef<Path>) -> Result<Vec<DataFile>, RomstIOError> {
+        let file = self.open(file_path)?;
+        let mut archive = match ZipArchive::new(BufReader::new(file)) {
+            Ok(archive) => archive,
+            Err(ZipError::InvalidArchive(_e)) => {
+                let file_name = file_path.as_ref().to_string_lossy().to_string();
+                return Err(RomstIOError::NotValidFileError(file_name, FileType::Rom))
             },
             Err(e) => { return Err(RomstIOError::Io{ source: e.into() }) }
+        };
+
+        let mut files = vec![];
+        for i in 0..archive.len() {
+            let f = archive.by_index_raw(i).map_err(|err| { RomstIOError::Io{ source: err.into() } })?;
+            if f.is_dir() {
+                continue;
+            }
+            let mut info = DataFileInfo::new(FileType::from_file_name(f.name()));
+            info.size = Some(f.size() as u32);
+            info.crc = Some(format!("{:08x}", f.crc32()));
+            files.push(DataFile::new(f.name().trim(), info));
         }
 
-        let game_set = GameSet::new(game, roms, vec![], vec![], vec![]);
+        Ok(files)
+    }
+}
 
-        Ok(game_set)
+/// A folder with disks, named as the set they belong to, like the `setname/diskname.chd` layout of the CHD collections
+pub(crate) fn is_disk_folder(path: &impl AsRef<Path>) -> bool {
+    match path.as_ref().read_dir() {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .any(|path| path.is_file() && FileType::from_file_name(&path.to_string_lossy()) == FileType::Disk),
+        Err(_) => false,
     }
 }
 
+/// The zip a file is part of when the archive is split in several files, the parts are named `game.z01`, `game.z02`...
+/// and the last one is `game.zip`
+pub(crate) fn get_split_archive_of_part(path: &impl AsRef<Path>) -> Option<PathBuf> {
+    let extension = path.as_ref().extension()?.to_str()?;
+    let number = extension.strip_prefix('z').or_else(|| extension.strip_prefix('Z'))?;
+    if number.len() >= 2 && number.chars().all(|c| c.is_ascii_digit()) {
+        Some(path.as_ref().with_extension("zip"))
+    } else {
+        None
+    }
+}
+
+fn game_set_from_files(game: Game, files: Vec<DataFile>) -> GameSet {
+    let mut roms = vec![];
+    let mut samples = vec![];
+    let mut disks = vec![];
+    for file in files {
+        match file.info.file_type {
+            FileType::Rom => roms.push(file),
+            FileType::Disk => disks.push(file),
+            FileType::Sample => samples.push(file),
+        }
+    }
+
+    GameSet::new(game, roms, samples, disks, vec![])
+}
+
+/// The last part of a split zip has the end of its central directory in a disk other than the first one
+pub(crate) fn is_split_archive(file_path: &impl AsRef<Path>) -> io::Result<bool> {
+    let mut file = File::open(paths::to_long_path(file_path))?;
+    match read_end_of_central_directory(&mut file)? {
+        Some(record) => Ok(u16::from_le_bytes([record[4], record[5]]) != 0),
+        None => Ok(false),
+    }
+}
+
+/// The sha1 of a disk is the one stored in the CHD header, its position depends on the version
+fn get_chd_sha1(header: &[u8]) -> Option<String> {
+    if header.len() < 16 || &header[0..8] != CHD_TAG {
+        return None;
+    }
+    let version = u32::from_be_bytes([header[12], header[13], header[14], header[15]]);
+    let offset = match version {
+        3 => 0x50,
+        4 => 0x30,
+        5 => 0x54,
+        _ => return None,
+    };
+
+    header.get(offset..offset + 20).map(|sha1| {
+        sha1.iter().map(|byte| format!("{:02x}", byte)).collect()
+    })
+}
+
+/// A zip is canonical when its comment has the format prefix followed by the crc32 of the central directory
+fn get_archive_format(file_path: &impl AsRef<Path>, comment: &str) -> io::Result<ArchiveFormat> {
+    let (format, crc) = if let Some(crc) = comment.strip_prefix(TORRENTZIP_PREFIX) {
+        (ArchiveFormat::TorrentZip, crc)
+    } else if let Some(crc) = comment.strip_prefix(RVZSTD_PREFIX) {
+        (ArchiveFormat::RvZstd, crc)
+    } else {
+        return Ok(ArchiveFormat::NonCanonical);
+    };
+
+    let expected_crc = match u32::from_str_radix(crc.trim(), 16) {
+        Ok(crc) => crc,
+        Err(_) => return Ok(ArchiveFormat::NonCanonical),
+    };
+
+    match read_central_directory(file_path)? {
+        Some(central_directory) if crc32fast::hash(&central_directory) == expected_crc => Ok(format),
+        _ => Ok(ArchiveFormat::NonCanonical),
+    }
+}
+
+fn read_end_of_central_directory(file: &mut File) -> io::Result<Option<Vec<u8>>> {
+    let file_len = file.metadata()?.len();
+    // The end of central directory record is at the end of the file, followed by a comment of up to 65535 bytes
+    let tail_len = file_len.min(END_OF_CENTRAL_DIRECTORY_SIZE + u16::MAX as u64);
+    file.seek(SeekFrom::End(-(tail_len as i64)))?;
+    let mut tail = vec![0; tail_len as usize];
+    file.read_exact(&mut tail)?;
+
+    match tail.windows(4).rposition(|window| window == END_OF_CENTRAL_DIRECTORY_SIGNATURE) {
+        Some(position) if position + END_OF_CENTRAL_DIRECTORY_SIZE as usize <= tail.len() => {
+            Ok(Some(tail[position..position + END_OF_CENTRAL_DIRECTORY_SIZE as usize].to_vec()))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn read_central_directory(file_path: &impl AsRef<Path>) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(paths::to_long_path(file_path))?;
+    let file_len = file.metadata()?.len();
+    let record = match read_end_of_central_directory(&mut file)? {
+        Some(record) => record,
+        None => return Ok(None),
+    };
+    let read_u32 = |offset: usize| {
+        u32::from_le_bytes([record[offset], record[offset + 1], record[offset + 2], record[offset + 3]])
+    };
+    let central_directory_size = read_u32(12);
+    let central_directory_offset = read_u32(16);
+
+    if central_directory_offset as u64 + central_directory_size as u64 > file_len {
+        return Ok(None);
+    }
+
+    file.seek(SeekFrom::Start(central_directory_offset as u64))?;
+    let mut central_directory = vec![0; central_directory_size as usize];
+    file.read_exact(&mut central_directory)?;
+
+    Ok(Some(central_directory))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{ffi::OsString, io::Write};
     use super::*;
 
     #[test]
@@ -130,4 +555,208 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reads_the_zip_directory() -> Result<()> {
+        let file_reader = FileReader::new();
+        let file_path = Path::new("testdata").join("split").join("game1.zip");
+        let entries = file_reader.read_zip_directory(&file_path)?;
+
+        assert_eq!(4, entries.len());
+        let binfil = entries.iter().find(|entry| entry.name == "binfil1.bin").unwrap();
+        assert_eq!(Some("1b736d41".to_string()), binfil.info.crc);
+        assert_eq!(Some(65536), binfil.info.size);
+        assert_eq!(None, binfil.info.sha1);
+
+        assert!(file_reader.read_zip_directory(&Path::new("testdata").join("wrong").join("info.txt")).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "rar")]
+    #[test]
+    fn reads_rar_archives() -> Result<()> {
+        let mut file_reader = FileReader::new();
+        let zip_set = file_reader.build_game_set(&Path::new("testdata").join("split").join("game1.zip"), FileChecks::ALL)?;
+        let file_path = Path::new("testdata").join("rar").join("game1.rar");
+        let rar_set = file_reader.build_game_set(&file_path, FileChecks::ALL)?;
+
+        assert_eq!("game1", rar_set.game.name);
+        let mut zip_roms = zip_set.roms;
+        zip_roms.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(zip_roms, rar_set.roms);
+
+        let archive_info = file_reader.get_archive_info(&file_path)?;
+        assert_eq!(ArchiveInfo::new(ArchiveFormat::Rar, 4, 71680, String::new()), archive_info);
+        assert_eq!(archive_info, FileReader::new().get_archive_info(&file_path)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn detects_torrentzip_archives() -> Result<()> {
+        let file_reader = FileReader::new();
+        let file_path = Path::new("testdata").join("torrentzip").join("game9.zip");
+        let archive_info = file_reader.get_archive_info(&file_path)?;
+
+        assert_eq!(ArchiveFormat::TorrentZip, archive_info.format);
+        assert_eq!(1, archive_info.entries);
+        assert_eq!(260, archive_info.total_size);
+        // TorrentZip sets the same time to every entry
+        assert_eq!(Some("1996-12-24 23:32:00"), archive_info.oldest_entry.as_deref());
+        assert_eq!(Some("1996-12-24 23:32:00"), archive_info.newest_entry.as_deref());
+
+        let file_path = Path::new("testdata").join("split").join("game1.zip");
+        let archive_info = file_reader.get_archive_info(&file_path)?;
+        assert_eq!(ArchiveFormat::NonCanonical, archive_info.format);
+        assert_eq!(4, archive_info.entries);
+
+        Ok(())
+    }
+
+    #[test]
+    fn records_the_entry_times() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("romst_entry_times_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let file_path = dir.join("game1.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&file_path)?);
+        for (name, (year, month, day)) in [("rom2.bin", (2001, 2, 3)), ("rom1.bin", (1999, 12, 31)), ("rom3.bin", (2000, 6, 15))] {
+            let time = zip::DateTime::from_date_and_time(year, month, day, 10, 20, 30).unwrap();
+            zip.start_file(name, zip::write::FileOptions::default().last_modified_time(time))?;
+            zip.write_all(name.as_bytes())?;
+        }
+        zip.finish()?;
+
+        let archive_info = FileReader::new().get_archive_info(&file_path)?;
+        assert_eq!(Some("1999-12-31 10:20:30"), archive_info.oldest_entry.as_deref());
+        assert_eq!(Some("2001-02-03 10:20:30"), archive_info.newest_entry.as_deref());
+        assert_eq!("[Non canonical] entries: 3, size: 24 bytes, modified: 1999-12-31 10:20:30 to 2001-02-03 10:20:30", archive_info.to_string());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn detects_split_archives() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("romst_split_archive_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("game1.z01"), [0x50, 0x4b, 0x07, 0x08])?;
+        // An end of central directory record in the second disk, with the central directory in the first one
+        let mut record = END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_vec();
+        record.extend([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        std::fs::write(dir.join("game1.zip"), &record)?;
+
+        assert_eq!(Some(dir.join("game1.zip")), get_split_archive_of_part(&dir.join("game1.z01")));
+        assert_eq!(Some(dir.join("game1.zip")), get_split_archive_of_part(&dir.join("game1.Z12")));
+        assert_eq!(None, get_split_archive_of_part(&dir.join("game1.zip")));
+        assert_eq!(None, get_split_archive_of_part(&dir.join("game1.zst")));
+        assert!(is_split_archive(&dir.join("game1.zip"))?);
+        assert!(!is_split_archive(&Path::new("testdata").join("split").join("game1.zip"))?);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reads_disks_and_samples_from_directories() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("romst_disks_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let game_dir = dir.join("game1");
+        std::fs::create_dir_all(&game_dir)?;
+
+        let mut header = vec![0u8; CHD_V5_HEADER_SIZE];
+        header[0..8].copy_from_slice(CHD_TAG);
+        header[12..16].copy_from_slice(&5u32.to_be_bytes());
+        for (i, byte) in header[0x54..0x54 + 20].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        std::fs::write(game_dir.join("disk1.chd"), &header)?;
+        std::fs::write(game_dir.join("sample1.wav"), b"sample")?;
+        std::fs::write(game_dir.join("rom1.bin"), b"rom")?;
+
+        let mut file_reader = FileReader::new();
+        let game_set = file_reader.build_game_set(&game_dir, FileChecks::ALL);
+        std::fs::remove_dir_all(&dir)?;
+        let game_set = game_set?;
+
+        assert_eq!("game1", game_set.game.name);
+        assert_eq!(1, game_set.disks.len());
+        assert_eq!(FileType::Disk, game_set.disks[0].info.file_type);
+        assert_eq!(Some("000102030405060708090a0b0c0d0e0f10111213".to_string()), game_set.disks[0].info.sha1);
+        assert_eq!(1, game_set.samples.len());
+        assert_eq!(FileType::Sample, game_set.samples[0].info.file_type);
+        assert_eq!(1, game_set.roms.len());
+        assert_eq!("rom1.bin", game_set.roms[0].name);
+        assert_eq!(Some(format!("{:08x}", crc32fast::hash(b"rom"))), game_set.roms[0].info.crc);
+
+        Ok(())
+    }
+
+    #[test]
+    fn verifies_the_archives() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("romst_verify_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+
+        let zip_path = dir.join("game1.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&zip_path)?);
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("rom1.bin", options)?;
+        std::io::Write::write_all(&mut writer, b"rom one data")?;
+        writer.start_file("rom2.bin", options)?;
+        std::io::Write::write_all(&mut writer, b"rom two data")?;
+        writer.finish()?;
+
+        let file_reader = FileReader::new();
+        let valid = file_reader.verify_archive(&zip_path);
+
+        let mut bytes = std::fs::read(&zip_path)?;
+        let data_at = bytes.windows(12).position(|window| window == b"rom two data").unwrap();
+        bytes[data_at] = b'R';
+        std::fs::write(&zip_path, &bytes)?;
+        let corrupt_entry = file_reader.verify_archive(&zip_path);
+
+        let central_directory_at = bytes.windows(4).position(|window| window == [0x50, 0x4b, 0x01, 0x02]).unwrap();
+        std::fs::write(&zip_path, &bytes[..central_directory_at])?;
+        let broken_directory = file_reader.verify_archive(&zip_path);
+        std::fs::remove_dir_all(&dir)?;
+
+        assert!(valid?.is_empty());
+        assert_eq!(vec![ArchiveIssue::CrcMismatch {
+            entry: "rom2.bin".to_string(),
+            expected: format!("{:08x}", crc32fast::hash(b"rom two data")),
+            found: format!("{:08x}", crc32fast::hash(b"Rom two data")),
+        }], corrupt_entry?);
+        assert_eq!(vec![ArchiveIssue::BrokenCentralDirectory { salvageable_entries: 2 }], broken_directory?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn names_the_sets_with_non_ascii_names() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("romst_non_ascii_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        // Deep enough to go over the MAX_PATH of Windows
+        let dir = (0..8).fold(root.clone(), |dir, level| dir.join(format!("ディレクトリ_{}_{}", level, "x".repeat(24))));
+        std::fs::create_dir_all(paths::to_long_path(&dir))?;
+
+        let mut names = vec![ OsString::from("ストリートファイター (Japan)"), OsString::from("Spieß") ];
+        #[cfg(unix)]
+        names.push(std::os::unix::ffi::OsStringExt::from_vec(b"Latin1 \xe9t\xe9".to_vec()));
+
+        let mut file_reader = FileReader::new();
+        for name in names {
+            let key = paths::os_str_to_key(&name);
+            let zip_path = paths::join_key(&dir, &format!("{}.zip", key));
+            std::fs::copy(Path::new("testdata").join("split").join("game1.zip"), &zip_path)?;
+
+            let game_set = file_reader.build_game_set(&zip_path, FileChecks::ALL)?;
+            assert_eq!(key, game_set.game.name);
+            assert_eq!(name, paths::key_to_os_string(&game_set.game.name));
+            assert_eq!(4, game_set.roms.len());
+        }
+
+        std::fs::remove_dir_all(paths::to_long_path(&root))?;
+        Ok(())
+    }
+}
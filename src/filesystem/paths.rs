@@ -0,0 +1,298 @@
+use std::{env, ffi::{OsStr, OsString}, fs, io, path::{Component, Path, PathBuf}, time::UNIX_EPOCH};
+
+/// The names that aren't valid unicode are keyed escaping each invalid byte on Unix, or each unpaired surrogate on Windows,
+/// as a private use character from this one on. No real name uses them, so the key can be turned back into the name
+const ESCAPE_BASE: u32 = 0xF0000;
+/// The escapes go up to the unpaired surrogates of Windows, the bytes of Unix only take the first 256
+const ESCAPE_RANGE: u32 = 0x800;
+/// The paths from this length on need the long path prefix on Windows
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// The absolute path without `.` and `..` components or trailing slashes, so the same file given as `./roms/x.zip`,
+/// `roms/x.zip` or `/home/user/roms/x.zip` is a single path. A directory is canonicalized, but a file keeps its own
+/// name even if it's a link, as the name is what identifies its set
+pub fn normalize_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().map(|current_dir| current_dir.join(path)).unwrap_or_else(|_| path.to_path_buf())
+    };
+    let lexical = normalize_lexically(&absolute);
+
+    if lexical.is_dir() {
+        return fs::canonicalize(&lexical).unwrap_or(lexical);
+    }
+    match (lexical.parent(), lexical.file_name()) {
+        (Some(parent), Some(file_name)) if parent.is_dir() => match fs::canonicalize(parent) {
+            Ok(parent) => parent.join(file_name),
+            Err(_) => lexical,
+        },
+        _ => lexical,
+    }
+}
+
+/// The normalized path of a directory as the string stored in the reports and plans
+pub fn normalize_directory(path: impl AsRef<Path>) -> String {
+    normalize_path(path).to_string_lossy().to_string()
+}
+
+/// The key of a file in the reports, its name without the directories it was found in or trailing slashes
+pub fn normalize_file_key(file: &str) -> String {
+    Path::new(file.trim_end_matches(['/', '\\'])).file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_else(|| file.to_string())
+}
+
+/// When the file was last modified, in milliseconds since the epoch. Adding, removing or renaming a file in a directory
+/// changes the time of the directory
+pub fn get_modified_millis(path: impl AsRef<Path>) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64)
+}
+
+/// Copies the directory with all its files and folders
+pub fn copy_directory(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    fs::create_dir_all(&to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.as_ref().join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_directory(entry.path(), target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// The free space for the user in the disk of the path, or of its closest parent that exists
+#[cfg(target_os = "linux")]
+pub fn get_available_space(path: impl AsRef<Path>) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let absolute = normalize_path(path);
+    let existing = absolute.ancestors().find(|ancestor| ancestor.exists())?;
+    let c_path = CString::new(existing.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } == 0 {
+        Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_available_space(_path: impl AsRef<Path>) -> Option<u64> {
+    None
+}
+
+/// The key of a file name in the reports. The names that are valid unicode are kept as they are, the others are
+/// escaped instead of replacing the invalid parts, so two different names never get the same key
+pub fn os_str_to_key(name: &OsStr) -> String {
+    match name.to_str() {
+        Some(name) => name.to_string(),
+        None => escape_os_str(name),
+    }
+}
+
+/// The file name of a key from `os_str_to_key`
+pub fn key_to_os_string(key: &str) -> OsString {
+    if key.chars().any(is_escape) {
+        unescape_key(key)
+    } else {
+        OsString::from(key)
+    }
+}
+
+/// The key of the file name of the path, empty if it has none
+pub fn get_file_name_key(path: impl AsRef<Path>) -> String {
+    path.as_ref().file_name().map(os_str_to_key).unwrap_or_default()
+}
+
+/// The path of the file with the key in the directory, ready to be opened
+pub fn join_key(directory: impl AsRef<Path>, key: &str) -> PathBuf {
+    to_long_path(directory.as_ref().join(key_to_os_string(key)))
+}
+
+/// The path with the `\\?\` prefix on Windows if it's longer than `MAX_PATH`, so it can be opened. The prefix turns
+/// off the normalization of the path, so it's made absolute and normalized first. The other systems don't need it
+#[cfg(windows)]
+pub fn to_long_path(path: impl AsRef<Path>) -> PathBuf {
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+    let path = path.as_ref();
+    if path.as_os_str().encode_wide().count() < MAX_PATH {
+        return path.to_path_buf();
+    }
+    let absolute = if path.is_absolute() { path.to_path_buf() } else { env::current_dir().map(|current_dir| current_dir.join(path)).unwrap_or_else(|_| path.to_path_buf()) };
+    let wide = normalize_lexically(&absolute).as_os_str().encode_wide()
+        .map(|c| if c == u16::from(b'/') { u16::from(b'\\') } else { c })
+        .collect::<Vec<_>>();
+    let text = String::from_utf16_lossy(&wide);
+    let long_path = if text.starts_with(r"\\?\") || text.starts_with(r"\\.\") {
+        wide
+    } else if text.starts_with(r"\\") {
+        // A network share, `\\server\share` becomes `\\?\UNC\server\share`
+        r"\\?\UNC".encode_utf16().chain(wide[1..].iter().copied()).collect()
+    } else {
+        r"\\?\".encode_utf16().chain(wide.iter().copied()).collect()
+    };
+    PathBuf::from(OsString::from_wide(&long_path))
+}
+
+#[cfg(not(windows))]
+pub fn to_long_path(path: impl AsRef<Path>) -> PathBuf {
+    path.as_ref().to_path_buf()
+}
+
+fn is_escape(c: char) -> bool {
+    (ESCAPE_BASE..ESCAPE_BASE + ESCAPE_RANGE).contains(&(c as u32))
+}
+
+fn escape(offset: u32) -> char {
+    char::from_u32(ESCAPE_BASE + offset).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+#[cfg(unix)]
+fn escape_os_str(name: &OsStr) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut key = String::new();
+    let mut bytes = name.as_bytes();
+    while !bytes.is_empty() {
+        match std::str::from_utf8(bytes) {
+            Ok(valid) => {
+                key.push_str(valid);
+                break;
+            }
+            Err(e) => {
+                let (valid, invalid) = bytes.split_at(e.valid_up_to());
+                key.push_str(&String::from_utf8_lossy(valid));
+                let invalid_len = e.error_len().unwrap_or(invalid.len());
+                key.extend(invalid[..invalid_len].iter().map(|byte| escape(u32::from(*byte))));
+                bytes = &invalid[invalid_len..];
+            }
+        }
+    }
+    key
+}
+
+#[cfg(unix)]
+fn unescape_key(key: &str) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+
+    let mut bytes = Vec::with_capacity(key.len());
+    for c in key.chars() {
+        if is_escape(c) && (c as u32) - ESCAPE_BASE < 0x100 {
+            bytes.push(((c as u32) - ESCAPE_BASE) as u8);
+        } else {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+    }
+    OsString::from_vec(bytes)
+}
+
+#[cfg(windows)]
+fn escape_os_str(name: &OsStr) -> String {
+    use std::os::windows::ffi::OsStrExt;
+
+    char::decode_utf16(name.encode_wide())
+        .map(|c| c.unwrap_or_else(|e| escape(u32::from(e.unpaired_surrogate()) - 0xD800)))
+        .collect()
+}
+
+#[cfg(windows)]
+fn unescape_key(key: &str) -> OsString {
+    use std::os::windows::ffi::OsStringExt;
+
+    let mut wide = Vec::with_capacity(key.len());
+    for c in key.chars() {
+        if is_escape(c) {
+            wide.push((0xD800 + (c as u32) - ESCAPE_BASE) as u16);
+        } else {
+            wide.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+        }
+    }
+    OsString::from_wide(&wide)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn escape_os_str(name: &OsStr) -> String {
+    name.to_string_lossy().to_string()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn unescape_key(key: &str) -> OsString {
+    OsString::from(key)
+}
+
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_the_same_file_given_differently() {
+        let absolute = normalize_path(env::current_dir().unwrap().join("testdata/split/game1.zip"));
+
+        assert_eq!(absolute, normalize_path("testdata/split/game1.zip"));
+        assert_eq!(absolute, normalize_path("./testdata/split/game1.zip"));
+        assert_eq!(absolute, normalize_path("testdata/merged/../split/game1.zip"));
+        assert_eq!(normalize_path("testdata/split"), normalize_path("testdata/split/"));
+    }
+
+    #[test]
+    fn normalizes_paths_that_dont_exist() {
+        assert_eq!(PathBuf::from("/roms/game1.zip"), normalize_path("/roms/./extra/../game1.zip"));
+        assert_eq!("/roms", normalize_directory("/roms/"));
+    }
+
+    #[test]
+    fn keys_non_ascii_names_as_they_are() {
+        for name in ["ストリートファイター.zip", "Spieß (Deutschland).zip", "игра.7z"] {
+            assert_eq!(name, os_str_to_key(OsStr::new(name)));
+            assert_eq!(OsStr::new(name), key_to_os_string(name));
+        }
+        assert_eq!("ストリートファイター.zip", get_file_name_key("roms/ストリートファイター.zip"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keys_names_that_are_not_unicode_without_losing_them() {
+        use std::os::unix::ffi::OsStrExt;
+
+        // Latin-1 names, the same set named in two encodings can't get the same key
+        let latin1 = OsStr::from_bytes(b"Spie\xdf.zip");
+        let other = OsStr::from_bytes(b"Spie\xfe.zip");
+        let key = os_str_to_key(latin1);
+        assert!(key.starts_with("Spie") && key.ends_with(".zip"));
+        assert_ne!(key, os_str_to_key(other));
+        assert_eq!(latin1, key_to_os_string(&key));
+        assert_eq!(PathBuf::from("/roms").join(latin1), join_key("/roms", &key));
+    }
+
+    #[test]
+    fn keys_files_by_name() {
+        assert_eq!("game1.zip", normalize_file_key("./roms/game1.zip"));
+        assert_eq!("game1.zip", normalize_file_key("/home/user/roms/game1.zip"));
+        assert_eq!("game5", normalize_file_key("roms/game5/"));
+        assert_eq!("game1.zip", normalize_file_key("game1.zip"));
+    }
+}
@@ -0,0 +1,78 @@
+use std::{env, ffi::OsString, fs, io, path::{MAIN_SEPARATOR, Path, PathBuf}, process::{self, Command}, sync::atomic::{AtomicU32, Ordering}};
+
+use log::{debug, warn};
+
+use crate::{data::models::file::FileType, error::RomstIOError};
+
+static EXTRACT_COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// If the file is a rar archive by its extension
+pub(crate) fn is_rar(file_path: &impl AsRef<Path>) -> bool {
+    file_path.as_ref().extension()
+        .map(|extension| extension.eq_ignore_ascii_case("rar"))
+        .unwrap_or(false)
+}
+
+/// A rar archive extracted to a temporary directory, the directory is removed when dropped
+pub(crate) struct ExtractedRar {
+    directory: PathBuf,
+}
+
+impl ExtractedRar {
+    /// Extracts the archive with the `unrar` command, or with `bsdtar` if `unrar` is not installed
+    pub fn extract(file_path: &impl AsRef<Path>) -> Result<Self, RomstIOError> {
+        let file_path = file_path.as_ref();
+        let directory = env::temp_dir().join(format!("romst_rar_{}_{}", process::id(), EXTRACT_COUNT.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&directory)?;
+        let extracted = ExtractedRar { directory };
+
+        // `unrar` needs the trailing separator to take the destination as a directory
+        let mut destination = OsString::from(extracted.directory.as_os_str());
+        destination.push(MAIN_SEPARATOR.to_string());
+        let output = match Command::new("unrar").args(["x", "-y", "-idq", "-p-"]).arg(file_path).arg(&destination).output() {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                debug!("`unrar` not found, extracting {} with `bsdtar`", file_path.to_string_lossy());
+                Command::new("bsdtar").arg("-xf").arg(file_path).arg("-C").arg(&extracted.directory).output()
+            }
+            output => output,
+        };
+        let output = output.map_err(|e| io::Error::new(e.kind(), format!("Couldn't run `unrar` or `bsdtar` to read the rar archive: {}", e)))?;
+        if !output.status.success() {
+            debug!("Error extracting {}: {}", file_path.to_string_lossy(), String::from_utf8_lossy(&output.stderr).trim());
+            return Err(RomstIOError::NotValidFileError(file_path.to_string_lossy().to_string(), FileType::Rom));
+        }
+
+        Ok(extracted)
+    }
+
+    /// The extracted files sorted by their names in the archive, the folders are part of the names as in zip archives
+    pub fn get_files(&self) -> io::Result<Vec<(String, PathBuf)>> {
+        let mut files = vec![];
+        let mut directories = vec![self.directory.to_path_buf()];
+        while let Some(directory) = directories.pop() {
+            for entry in directory.read_dir()? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    directories.push(path);
+                } else if let Ok(relative) = path.strip_prefix(&self.directory) {
+                    let name = relative.components()
+                        .map(|component| component.as_os_str().to_string_lossy().to_string())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    files.push((name, path));
+                }
+            }
+        }
+        files.sort();
+
+        Ok(files)
+    }
+}
+
+impl Drop for ExtractedRar {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.directory) {
+            warn!("Error removing the extracted rar at `{}`: {}", self.directory.to_string_lossy(), e);
+        }
+    }
+}
@@ -1,14 +1,20 @@
-use sha1::{Digest, Sha1};
+use sha1::Sha1;
 
-pub struct SHA1Hasher {
-    hasher: Sha1,
+use super::hash::{HashBackend, RustCryptoBackend};
+
+pub struct SHA1Hasher<B = RustCryptoBackend<Sha1>> {
+    backend: B,
 }
 
 impl SHA1Hasher {
-    pub fn new() -> Self { Self { hasher: <Sha1 as md5::Digest>::new() } }
+    pub fn new() -> Self { Self::with_backend(RustCryptoBackend::default()) }
+}
+
+impl<B> SHA1Hasher<B> where B: HashBackend {
+    /// Hashes with another implementation of SHA1
+    pub fn with_backend(backend: B) -> Self { Self { backend } }
+
     pub fn get_hash(&mut self, data: &[u8]) -> String {
-        self.hasher.update(data);
-        let hash = self.hasher.finalize_reset();
-        format!("{:x}", hash)
+        self.backend.get_hash(data)
     }
 }
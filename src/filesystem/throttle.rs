@@ -0,0 +1,108 @@
+use std::{io::{self, Read, Seek, SeekFrom}, sync::{Arc, Mutex}, thread, time::{Duration, Instant}};
+
+/// A read bandwidth limit shared by all the readers cloned from it, so several workers together don't go over it
+#[derive(Debug, Clone)]
+pub(crate) struct Throttle {
+    bytes_per_second: u64,
+    next_read: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self { bytes_per_second: bytes_per_second.max(1), next_read: Arc::new(Mutex::new(None)) }
+    }
+
+    /// Books the time the bytes read take at the limit after the previous reads, and waits until it's over
+    fn consume(&self, bytes: usize) {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+        let wait_until = {
+            let mut next_read = match self.next_read.lock() {
+                Ok(next_read) => next_read,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            let now = Instant::now();
+            let start = next_read.filter(|next| *next > now).unwrap_or(now);
+            *next_read = Some(start + cost);
+            start + cost
+        };
+
+        let now = Instant::now();
+        if wait_until > now {
+            thread::sleep(wait_until - now);
+        }
+    }
+}
+
+/// Reads through a throttle, without one it reads as the inner reader does
+pub(crate) struct ThrottledReader<R> {
+    inner: R,
+    throttle: Option<Throttle>,
+}
+
+impl<R> ThrottledReader<R> {
+    pub fn new(inner: R, throttle: Option<Throttle>) -> Self { Self { inner, throttle } }
+}
+
+impl<R: Read> Read for ThrottledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(throttle) = &self.throttle {
+            throttle.consume(read);
+        }
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for ThrottledReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+pub(crate) fn is_io_priority_supported() -> bool {
+    cfg!(target_os = "linux")
+}
+
+/// Moves the current thread to the idle IO class, so it only reads from the disk when nothing else does
+#[cfg(target_os = "linux")]
+pub(crate) fn lower_io_priority() -> io::Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+
+    // With the id 0 it applies to the calling thread only
+    let result = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT) };
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn lower_io_priority() -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_the_read_bandwidth() -> io::Result<()> {
+        let bytes = vec![0u8; 4000];
+        let throttle = Throttle::new(20000);
+        // Two readers sharing the throttle, 8000 bytes at 20000 per second take at least 0.4 seconds
+        let mut first = ThrottledReader::new(&bytes[..], Some(throttle.clone()));
+        let mut second = ThrottledReader::new(&bytes[..], Some(throttle));
+
+        let start = Instant::now();
+        let mut read = vec![];
+        first.read_to_end(&mut read)?;
+        second.read_to_end(&mut read)?;
+
+        assert_eq!(read.len(), 8000);
+        assert!(start.elapsed() >= Duration::from_millis(400));
+        Ok(())
+    }
+}
@@ -1,31 +1,38 @@
+// The tests older than the clippy checks keep their style
+#![cfg_attr(test, allow(clippy::cmp_owned, clippy::extra_unused_lifetimes, clippy::len_zero, clippy::needless_borrows_for_generic_args,
+    clippy::needless_lifetimes, clippy::needless_range_loop, clippy::too_many_arguments, clippy::unnecessary_get_then_check, clippy::unnecessary_to_owned))]
+
 pub mod data;
-mod error;
+pub mod error;
 mod filesystem;
 mod macros;
 pub mod sysout;
+#[cfg(all(feature = "server", unix))]
+pub mod server;
+pub mod ui_core;
+
+pub use filesystem::hash;
 
-use console::Style;
-use data::{importer::{DatImporter, DatImporterReporter}, models::set::GameSet, reader::{DataReader, RomSearch, SetDependencies, sqlite::{DBReader, DBReport}}, reporter::{ReportReporter, Reporter, scan_report::ScanReport}, writer::sqlite::DBWriter};
-use log::{info, error};
+use data::{aliases::SetAliases, analysis::FixableAnalysis, backup::{Backup, BackupManifest}, portable::{self, BundleExportReport, BundleImportReport}, bundle::{BundleOptions, BundlePlan}, connection, cleaner::CleanPlan, exporter::{self, DatExportReport}, filter::GameFilter, fixer::FixPlan, sanitize::NamePolicy, ignored_roms::IgnoredRoms, journal::{ExecutionProgress, ResumeJournal}, migration::MigrationPlan, priority::SetPriority, rebuilder::RebuildPlan, pool::{PoolAddReport, PoolBuildReport, RomPool}, importer::{CombinedImportReporter, DatImportReport, DatImporter, DatImporterReporter, ImportReport, MultiImportReport, validation::{DatValidation, ValidationWriter}}, models::{dat_info::Packing, file::DataFile, set::GameSet}, reader::{DataReader, RomFilter, RomFindReport, RomSearch, SetDependencies, SharedRoms, index::{RomIndex, RomIndexInfo}, sqlite::{BrowseGrouping, BrowseReport, DBReader, DBReport, DbHandle, DedupeStats, DumpQualityReport, LastCheck, SetDumpQuality, SetLastChecks, SetModeOverrides, SetUserData, SizeOrder, SizesReport}}, reporter::{self, CancellationToken, report_file::{self, ReportFileReporter}, formatter::{EnglishFormatter, ReportFormatter}, ReportReporter, Reporter, SetReportListener, diff::ReportDiff, miss_list::{self, MissListFormat}, mode_detection::{ModeDetection, ModeMismatch}, ownership::SetOwnership, sample_audit::AuditSample, scan_report::{REPORT_SCHEMA_VERSION, ScanReport, SetReport, SetStatus}, summary::{CheckSummary, MissingSets}}, writer::{maintenance::{self, VacuumReport}, references::{self, ReferenceFix, ReferencesReport}, sqlite::{DBWriter, add_ignored_roms, add_set_tags, copy_user_data, remove_ignored_roms, remove_set_tags, retain_games, write_check_statuses, write_mode_override, write_set_note}}};
+use log::{info, error, warn};
 use rusqlite::{Connection, OpenFlags};
-use std::{fmt::Display, fs::File, io::Write, path::Path, str::FromStr};
+use filesystem::paths;
+use std::{cell::RefCell, collections::BTreeMap, fmt::Display, fs, path::Path, rc::Rc, str::FromStr, time::Duration};
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, anyhow};
 
 pub const DEFAULT_WRITE_BUFFER_SIZE: u16 = 5000;
+pub const INDEX_EXTENSION: &str = "idx";
+pub const DEFAULT_MODE_DETECTION_SAMPLE: usize = 200;
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Default)]
 pub enum RomsetMode {
     Merged,
+    #[default]
     NonMerged,
     Split,
 }
 
-impl Default for RomsetMode {
-    fn default() -> Self {
-        RomsetMode::NonMerged
-    }
-}
 
 impl Display for RomsetMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -43,6 +50,17 @@ impl Display for RomsetMode {
     }
 }
 
+impl RomsetMode {
+    /// The name used in the arguments and stored in the database, parsed back with `from_str`
+    pub fn get_key(&self) -> &'static str {
+        match self {
+            RomsetMode::Merged => "merged",
+            RomsetMode::NonMerged => "non-merged",
+            RomsetMode::Split => "split",
+        }
+    }
+}
+
 impl FromStr for RomsetMode {
     type Err = anyhow::Error;
 
@@ -56,17 +74,317 @@ impl FromStr for RomsetMode {
     }
 }
 
+/// How a DAT is imported into a database
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Replaces the database if it already exists
+    pub overwrite: bool,
+    /// Builds the rom index after the import
+    pub build_index: bool,
+    /// With more than one thread the entries are written while the DAT is parsed
+    pub threads: usize,
+    /// Adds the driver status, players and orientation of the games
+    pub extended_metadata: bool,
+    /// The import fails once more than these entries couldn't be read, `None` never fails because of them
+    pub max_errors: Option<u32>,
+    /// Only the games matching the filter are kept, with the parents, bios and devices they depend on
+    pub game_filter: GameFilter,
+    /// Compacts the database at the end, it reclaims the space of the games removed by the filter
+    pub vacuum: bool,
+    /// How the parents, bios and devices referenced but not imported are fixed, `None` only lists them
+    pub fix_references: Option<ReferenceFix>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions { overwrite: false, build_index: false, threads: 1, extended_metadata: false, max_errors: None, game_filter: GameFilter::default(), vacuum: false, fix_references: None }
+    }
+}
+
+/// Which bios variants are required when checking a set, roms without a `bios` attribute are always required
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum BiosSelection {
+    #[default]
+    All,
+    NoBios,
+    Variant(String),
+}
+
+impl Display for BiosSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BiosSelection::All => write!(f, "All"),
+            BiosSelection::NoBios => write!(f, "None"),
+            BiosSelection::Variant(bios) => write!(f, "{}", bios),
+        }
+    }
+}
+
+impl FromStr for BiosSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "" => Err(anyhow!("Non valid bios selection, can be either `all`, `none` or the name of a bios")),
+            "all" => Ok(BiosSelection::All),
+            "none" => Ok(BiosSelection::NoBios),
+            bios => Ok(BiosSelection::Variant(bios.to_string())),
+        }
+    }
+}
+
+impl BiosSelection {
+    pub fn is_required(&self, rom: &DataFile) -> bool {
+        match (&rom.bios, self) {
+            (None, _) => true,
+            (Some(_), BiosSelection::All) => true,
+            (Some(_), BiosSelection::NoBios) => false,
+            (Some(bios), BiosSelection::Variant(selected)) => bios == selected,
+        }
+    }
+}
+
+/// How the paths of the entries inside an archive are compared with the rom names of the DAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum EntryPaths {
+    /// The whole path, `cpu/rom.bin`, a rom in the wrong folder is reported to be renamed
+    #[default]
+    FullPath,
+    /// Only the file name, `rom.bin`, the folders inside the archive are ignored
+    FileName,
+}
+
+impl Display for EntryPaths {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EntryPaths::FullPath => write!(f, "full"),
+            EntryPaths::FileName => write!(f, "file-name"),
+        }
+    }
+}
+
+impl FromStr for EntryPaths {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "full" => Ok(EntryPaths::FullPath),
+            "file-name" => Ok(EntryPaths::FileName),
+            _ => Err(anyhow!("Non valid entry paths, can be either `full` or `file-name`")),
+        }
+    }
+}
+
+/// How the loose files, the ones outside an archive, are checked. The files of a multi-file game, like a `.cue` with
+/// its `.bin` tracks, are grouped into one set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum LooseFiles {
+    /// They are not read, only the archives and the disk folders are sets
+    #[default]
+    Ignore,
+    /// The files with the same name, without the extension and the ` (Track N)` suffix, are a set
+    Basename,
+    /// The files are grouped by the game of the DAT with a rom of the same name, or by their basename if there's no single game
+    Dat,
+}
+
+impl Display for LooseFiles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LooseFiles::Ignore => write!(f, "ignore"),
+            LooseFiles::Basename => write!(f, "basename"),
+            LooseFiles::Dat => write!(f, "dat"),
+        }
+    }
+}
+
+impl FromStr for LooseFiles {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "ignore" => Ok(LooseFiles::Ignore),
+            "basename" => Ok(LooseFiles::Basename),
+            "dat" => Ok(LooseFiles::Dat),
+            _ => Err(anyhow!("Non valid loose files grouping, can be either `ignore`, `basename` or `dat`")),
+        }
+    }
+}
+
+/// How much a scan can use the disk, so it doesn't saturate it while scanning on a NAS or while gaming
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IoLimits {
+    /// The read bandwidth of all the scan workers together, in MB/s. Without it they read as fast as the disk can
+    pub max_megabytes_per_second: Option<f64>,
+    /// Lowers the IO priority of the scan workers, only supported in Linux
+    pub low_priority: bool,
+}
+
+impl IoLimits {
+    pub fn new(max_megabytes_per_second: Option<f64>, low_priority: bool) -> Self { Self { max_megabytes_per_second, low_priority } }
+
+    pub(crate) fn get_bytes_per_second(&self) -> Option<u64> {
+        self.max_megabytes_per_second
+            .filter(|megabytes| *megabytes > 0.0)
+            .map(|megabytes| (megabytes * 1024.0 * 1024.0) as u64)
+    }
+}
+
+/// How the scan workers read again the files failing with a transient IO error, like a network share not answering
+/// for a moment, before reporting them as errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IoRetry {
+    /// The times a file is read again, with 0 it's never read again
+    pub retries: u32,
+    /// The wait before reading again the first time in milliseconds, it doubles on each retry
+    pub backoff_millis: u64,
+}
+
+impl Default for IoRetry {
+    fn default() -> Self {
+        IoRetry { retries: 2, backoff_millis: 500 }
+    }
+}
+
+impl IoRetry {
+    pub fn new(retries: u32, backoff_millis: u64) -> Self { Self { retries, backoff_millis } }
+
+    /// The wait before the retry, starting with 0
+    pub(crate) fn get_backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.backoff_millis.saturating_mul(1 << retry.min(16)))
+    }
+}
+
+impl EntryPaths {
+    /// If the name of an entry matches the name of a rom, the folders can be separated with either `/` or `\`
+    pub fn is_same_name(&self, entry_name: &str, rom_name: &str) -> bool {
+        let entry_name = entry_name.trim().replace('\\', "/");
+        let rom_name = rom_name.trim().replace('\\', "/");
+        match self {
+            EntryPaths::FullPath => entry_name == rom_name,
+            EntryPaths::FileName => entry_name.rsplit('/').next() == rom_name.rsplit('/').next(),
+        }
+    }
+}
+
+/// How the files are checked against the sets. They can be stored, the options missing when read back keep their default
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanOptions {
+    pub rom_mode: RomsetMode,
+    /// Roms from bios variants not selected are not reported as missing
+    pub bios: BiosSelection,
+    /// Only the sets of the games matching the filter are reported
+    pub game_filter: GameFilter,
+    /// Whether the folders of the entries inside the archives are compared with the DAT
+    pub entry_paths: EntryPaths,
+    /// Extracts the whole archives to verify their structure and the crc of the entries, the corrupt archives are reported
+    pub deep_verify: bool,
+    /// The zips named as a set whose directory lists the names, sizes and crcs of the roms of the set, and nothing else,
+    /// are accepted without extracting them, only the rest are hashed. A zip with a right directory and corrupt data is
+    /// not detected, so it's meant to check again a collection already verified
+    pub quick_check: bool,
+    /// Limits the read bandwidth and the IO priority of the workers reading the files
+    pub io_limits: IoLimits,
+    /// Whether the loose files are read, and how they are grouped into sets
+    pub loose_files: LooseFiles,
+    /// The files named as an alias of a set belong to it, and are reported to be renamed to the name of the set
+    pub set_aliases: SetAliases,
+    /// The entries named as the policy writes the roms are taken as named as in the DAT, so a rebuild with the policy
+    /// isn't reported to be renamed
+    pub name_policy: NamePolicy,
+    /// How the files failing with a transient IO error are read again
+    pub io_retry: IoRetry,
+    /// Hashes only a random sample of the zips named as a set, the rest are screened as with a quick check. The report
+    /// estimates from the sample how many of the zips screened don't match their directory
+    pub sample: Option<AuditSample>,
+}
+
+impl ScanOptions {
+    pub fn new(rom_mode: RomsetMode) -> Self { Self { rom_mode, ..Default::default() } }
+
+    pub fn bios(mut self, bios: BiosSelection) -> Self {
+        self.bios = bios;
+        self
+    }
+
+    pub fn game_filter(mut self, game_filter: GameFilter) -> Self {
+        self.game_filter = game_filter;
+        self
+    }
+
+    pub fn entry_paths(mut self, entry_paths: EntryPaths) -> Self {
+        self.entry_paths = entry_paths;
+        self
+    }
+
+    pub fn deep_verify(mut self, deep_verify: bool) -> Self {
+        self.deep_verify = deep_verify;
+        self
+    }
+
+    pub fn quick_check(mut self, quick_check: bool) -> Self {
+        self.quick_check = quick_check;
+        self
+    }
+
+    pub fn io_limits(mut self, io_limits: IoLimits) -> Self {
+        self.io_limits = io_limits;
+        self
+    }
+
+    pub fn loose_files(mut self, loose_files: LooseFiles) -> Self {
+        self.loose_files = loose_files;
+        self
+    }
+
+    pub fn set_aliases(mut self, set_aliases: SetAliases) -> Self {
+        self.set_aliases = set_aliases;
+        self
+    }
+
+    pub fn name_policy(mut self, name_policy: NamePolicy) -> Self {
+        self.name_policy = name_policy;
+        self
+    }
+
+    pub fn io_retry(mut self, io_retry: IoRetry) -> Self {
+        self.io_retry = io_retry;
+        self
+    }
+
+    pub fn sample(mut self, sample: Option<AuditSample>) -> Self {
+        self.sample = sample;
+        self
+    }
+}
+
 pub struct Romst {
 
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GameSetsInfo {
-    pub game_sets: Vec<GameSet>
+    pub game_sets: Vec<GameSet>,
+    /// The statuses of the sets in the last checks of the directories not modified since
+    #[serde(default)]
+    pub last_checks: BTreeMap<String, Vec<LastCheck>>,
+    /// What a check found of the roms of each set, when a report is added
+    #[serde(default)]
+    pub ownership: BTreeMap<String, SetOwnership>,
 }
 
 impl GameSetsInfo {
-    pub fn new(game_sets: Vec<GameSet>) -> Self { Self { game_sets } }
+    pub fn new(game_sets: Vec<GameSet>) -> Self { Self { game_sets, last_checks: BTreeMap::new(), ownership: BTreeMap::new() } }
+
+    /// Adds where the check found the roms of each set, the sets should be in the mode of the check
+    pub fn add_report(&mut self, report: &ScanReport) {
+        for game_set in &self.game_sets {
+            self.ownership.insert(game_set.game.name.to_string(), SetOwnership::new(game_set, report));
+        }
+    }
 }
 
 
@@ -74,6 +392,12 @@ impl Display for GameSetsInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for game_set in &self.game_sets {
             writeln!(f, "{}", game_set)?;
+            if let Some(last_checks) = self.last_checks.get(&game_set.game.name) {
+                EnglishFormatter.write_last_checks(f, last_checks)?;
+            }
+            if let Some(ownership) = self.ownership.get(&game_set.game.name) {
+                EnglishFormatter.write_set_ownership(f, ownership)?;
+            }
         };
         Ok(())
     }
@@ -81,51 +405,364 @@ impl Display for GameSetsInfo {
 
 impl Romst {
     fn get_rw_connection(db_file: &str) -> Result<Connection> {
-        let db_path = Path::new(db_file);
-        let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
-        Ok(conn)
+        connection::open_read_write(db_file).map_err(|e| connection::retriable_if_busy(e, db_file))
     }
 
-    fn get_r_connection(db_file: &str) -> Result<Connection> {
-        let db_path = Path::new(db_file);
-        if !db_path.exists() {
+    /// A connection to write in a database already imported, without creating it
+    fn get_existing_rw_connection(db_file: &str) -> Result<Connection> {
+        if !Path::new(db_file).exists() {
             return Err(anyhow!("No Database found at `{}`", db_file));
         }
-        let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-        Ok(conn)
+        Romst::get_rw_connection(db_file)
     }
 
     pub fn get_data_reader<S>(db_file: S) -> Result<DBReader> where S: AsRef<str>{
-        let conn = Romst::get_r_connection(db_file.as_ref())?;
+        let mut reader = DBReader::from_path(db_file.as_ref())?;
+        if !reader.is_import_complete().map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))? {
+            return Err(anyhow!("The database `{}` was not fully imported, import the DAT file again.", db_file.as_ref()));
+        }
+
+        let index_file = Romst::get_index_file(db_file.as_ref());
+        if Path::new(&index_file).exists() {
+            match RomIndex::load(&index_file) {
+                Ok(index) if index.get_fingerprint() == reader.get_db_fingerprint()? => {
+                    info!("Using the rom index `{}`", index_file);
+                    reader.set_index(index);
+                }
+                Ok(_) => warn!("The rom index `{}` is outdated, build it again", index_file),
+                Err(e) => warn!("Error loading the rom index `{}`: {}", index_file, e),
+            }
+        }
+        Ok(reader)
+    }
+
+    /// Imports the DAT into a database in memory, to check the files against a DAT once without importing it
+    /// Opens the database with `get_data_reader`, along with its path, the name and version of its DAT and its counts
+    pub fn open<S>(db_file: S) -> Result<DbHandle> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file.as_ref())?;
+        DbHandle::new(db_file.as_ref(), reader)
+    }
+
+    pub fn get_data_reader_from_dat<S>(dat_file: S) -> Result<DBReader> where S: AsRef<str> {
+        let mut conn = Connection::open_in_memory_with_flags(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)?;
+        let db_writer = DBWriter::from_connection(&mut conn, DEFAULT_WRITE_BUFFER_SIZE);
+        let import_report = DatImporter::from_path(&dat_file.as_ref().to_string(), db_writer)?.load_dat()?;
+        if import_report.has_issues() {
+            warn!("The DAT `{}` was read with {} warnings and {} errors, import it to see them", dat_file.as_ref(), import_report.warnings, import_report.errors);
+        }
+
         Ok(DBReader::from_connection(conn))
     }
 
-    pub fn get_data_writer(conn: &mut Connection) -> Result<DBWriter> {
+    /// The rom index is stored next to the database
+    pub fn get_index_file<S>(db_file: S) -> String where S: AsRef<str> {
+        format!("{}.{}", db_file.as_ref(), INDEX_EXTENSION)
+    }
+
+    /// Builds the rom index for the database, used to check files without querying the database for each one
+    pub fn build_index<S>(db_file: S) -> Result<RomIndexInfo> where S: AsRef<str> {
+        let reader = DBReader::from_path(db_file.as_ref())?;
+        if !reader.is_import_complete()? {
+            return Err(anyhow!("The database `{}` was not fully imported, import the DAT file again.", db_file.as_ref()));
+        }
+
+        let index = reader.build_index()?;
+        index.write(&Romst::get_index_file(db_file))?;
+        Ok(index.get_info())
+    }
+
+    /// Forces the mode a set is always checked with, or removes the override if there's no mode. Returns all the overrides
+    pub fn set_mode_override<S>(db_file: S, game_name: S, rom_mode: Option<RomsetMode>) -> Result<SetModeOverrides> where S: AsRef<str> {
+        let conn = Romst::get_existing_rw_connection(db_file.as_ref())?;
+        write_mode_override(&conn, game_name.as_ref(), rom_mode).map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))?;
+
+        Romst::get_mode_overrides(db_file)
+    }
+
+    /// Compacts the database, returns its size before and after
+    pub fn vacuum_db<S>(db_file: S) -> Result<VacuumReport> where S: AsRef<str> {
+        let conn = Romst::get_existing_rw_connection(db_file.as_ref())?;
+        maintenance::vacuum(&conn).map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))
+    }
+
+    /// Lists the clones, bios and devices referencing games that aren't in the database, and fixes them if asked
+    pub fn verify_db<S>(db_file: S, fix: Option<ReferenceFix>) -> Result<ReferencesReport> where S: AsRef<str> {
+        let mut conn = Romst::get_existing_rw_connection(db_file.as_ref())?;
+        references::verify_references(&mut conn, fix).map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))
+    }
+
+    /// Packs the database into a bundle to take it to another machine
+    pub fn export_db<S>(db_file: S, bundle_file: S) -> Result<BundleExportReport> where S: AsRef<str> {
+        portable::export_bundle(db_file.as_ref(), bundle_file.as_ref()).map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))
+    }
+
+    /// Unpacks the database of a bundle, migrating it if it was written with an older schema
+    pub fn import_db<S>(bundle_file: S, db_file: S, overwrite: bool) -> Result<BundleImportReport> where S: AsRef<str> {
+        let report = portable::import_bundle(bundle_file.as_ref(), db_file.as_ref(), overwrite)?;
+        // The rom index of the database replaced doesn't belong to this one
+        let index_file = Romst::get_index_file(db_file.as_ref());
+        if Path::new(&index_file).exists() {
+            fs::remove_file(&index_file)?;
+        }
+        Ok(report)
+    }
+
+    pub fn get_mode_overrides<S>(db_file: S) -> Result<SetModeOverrides> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        reader.get_mode_overrides()
+    }
+
+    /// Adds the roms to the ignore list, so they are not required to complete their sets. Returns the whole list
+    pub fn add_ignored_roms<S>(db_file: S, ignored_roms: &IgnoredRoms) -> Result<IgnoredRoms> where S: AsRef<str> {
+        let conn = Romst::get_existing_rw_connection(db_file.as_ref())?;
+        add_ignored_roms(&conn, ignored_roms).map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))?;
+
+        Romst::get_ignored_roms(db_file)
+    }
+
+    /// Takes the roms out of the ignore list. Returns the whole list
+    pub fn remove_ignored_roms<S>(db_file: S, ignored_roms: &IgnoredRoms) -> Result<IgnoredRoms> where S: AsRef<str> {
+        let conn = Romst::get_existing_rw_connection(db_file.as_ref())?;
+        remove_ignored_roms(&conn, ignored_roms).map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))?;
+
+        Romst::get_ignored_roms(db_file)
+    }
+
+    pub fn get_ignored_roms<S>(db_file: S) -> Result<IgnoredRoms> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        reader.get_ignored_roms()
+    }
+
+    /// Tags the set, returns the tags and notes of all the sets
+    pub fn add_set_tags<S>(db_file: S, game_name: S, tags: &[String]) -> Result<SetUserData> where S: AsRef<str> {
+        let conn = Romst::get_existing_rw_connection(db_file.as_ref())?;
+        add_set_tags(&conn, game_name.as_ref(), tags).map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))?;
+
+        Romst::get_user_data(db_file, None)
+    }
+
+    /// Removes the tags of the set, all of them if there are no tags. Returns the tags and notes of all the sets
+    pub fn remove_set_tags<S>(db_file: S, game_name: S, tags: &[String]) -> Result<SetUserData> where S: AsRef<str> {
+        let conn = Romst::get_existing_rw_connection(db_file.as_ref())?;
+        remove_set_tags(&conn, game_name.as_ref(), tags).map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))?;
+
+        Romst::get_user_data(db_file, None)
+    }
+
+    /// Attaches a note to the set, or removes it if there's no note. Returns the tags and notes of all the sets
+    pub fn set_set_note<S>(db_file: S, game_name: S, note: Option<&str>) -> Result<SetUserData> where S: AsRef<str> {
+        let conn = Romst::get_existing_rw_connection(db_file.as_ref())?;
+        write_set_note(&conn, game_name.as_ref(), note).map_err(|e| connection::retriable_if_busy(e, db_file.as_ref()))?;
+
+        Romst::get_user_data(db_file, None)
+    }
+
+    /// The tags and notes of the sets, only the sets with the tag if there's one
+    pub fn get_user_data<S>(db_file: S, tag: Option<&str>) -> Result<SetUserData> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        let mut user_data = reader.get_user_data()?;
+        if let Some(tag) = tag {
+            user_data.retain_tag(tag);
+        }
+        Ok(user_data)
+    }
+
+    /// The tag rules of the filter match the sets with the tags in the database
+    fn load_filter_tags(reader: &DBReader, game_filter: &mut GameFilter) -> Result<()> {
+        if game_filter.uses_tags() {
+            game_filter.set_tags(reader.get_user_data()?.get_tags());
+        }
+        Ok(())
+    }
+
+    pub fn get_data_writer(conn: &mut Connection) -> Result<DBWriter<'_>> {
         Ok(DBWriter::from_connection(conn, 500))
     }
 
-    pub fn import_dat<R, S>(input: S, output_file: S, overwrite: bool, reporter: Option<R>) -> Result<()> where R: DatImporterReporter + 'static, S: AsRef<str> {
-        println!("Loading file: {}", Style::new().bold().apply_to(input.as_ref()));
-        println!("Output: {}", Style::new().bold().apply_to(output_file.as_ref()));
+    /// Imports the DAT into a new database, the entries that can't be read are skipped and listed in the returned report
+    pub fn import_dat<R, S>(input: S, output_file: S, options: ImportOptions, reporter: Option<R>) -> Result<ImportReport> where R: DatImporterReporter + 'static, S: AsRef<str> {
+        Romst::write_import(output_file.as_ref(), &options, |tmp_file| {
+            let mut import_report = Romst::import_dat_to(input.as_ref(), tmp_file, &options, false, reporter)?;
+            let (excluded, references, vacuum) = Romst::finish_import(tmp_file, &options)?;
+            import_report.excluded = excluded;
+            import_report.references = references;
+            import_report.vacuum = vacuum;
+            Ok(import_report)
+        })
+    }
+
+    /// Imports several DATs one after another into a new database, a directory adds all the DATs in it. The games of a DAT
+    /// already imported from a previous one are skipped, as there are no namespaces the first one is kept
+    pub fn import_dats<R, S>(inputs: &[S], output_file: S, options: ImportOptions, reporter: Option<R>) -> Result<MultiImportReport> where R: DatImporterReporter + 'static, S: AsRef<str> {
+        let dat_files = Romst::get_dat_files(inputs)?;
+        let sizes = dat_files.iter().map(|dat_file| Ok(fs::metadata(dat_file)?.len())).collect::<Result<Vec<u64>>>()?;
+        let reporter = reporter.map(|mut reporter| {
+            reporter.set_total_bytes(sizes.iter().sum());
+            Rc::new(RefCell::new(reporter))
+        });
 
-        let db_path = Path::new(output_file.as_ref());
-        if !overwrite && db_path.exists() {
-            return Err(anyhow!("Destination file `{}` already exists, choose another output or rename the file.", output_file.as_ref()));
+        Romst::write_import(output_file.as_ref(), &options, |tmp_file| {
+            let mut multi_report = MultiImportReport::default();
+            let mut offset = 0;
+            for (i, (dat_file, size)) in dat_files.iter().zip(sizes.iter()).enumerate() {
+                info!("Importing `{}`", dat_file);
+                let dat_reporter = reporter.as_ref().map(|reporter| CombinedImportReporter::new(Rc::clone(reporter), offset, i == dat_files.len() - 1));
+                let report = Romst::import_dat_to(dat_file, tmp_file, &options, i > 0, dat_reporter)?;
+                multi_report.dats.push(DatImportReport { source: dat_file.to_owned(), report });
+                offset += size;
+            }
+            let (excluded, references, vacuum) = Romst::finish_import(tmp_file, &options)?;
+            multi_report.excluded = excluded;
+            multi_report.references = references;
+            multi_report.vacuum = vacuum;
+            Ok(multi_report)
+        })
+    }
+
+    /// Reads the DATs as an import would, without writing anything, to find the malformed entries, the duplicated sets and
+    /// the roms without hashes
+    pub fn validate_dats<R, S>(inputs: &[S], reporter: Option<R>) -> Result<DatValidation> where R: DatImporterReporter + 'static, S: AsRef<str> {
+        let dat_files = Romst::get_dat_files(inputs)?;
+        let sizes = dat_files.iter().map(|dat_file| Ok(fs::metadata(dat_file)?.len())).collect::<Result<Vec<u64>>>()?;
+        let reporter = reporter.map(|mut reporter| {
+            reporter.set_total_bytes(sizes.iter().sum());
+            Rc::new(RefCell::new(reporter))
+        });
+
+        let mut writer = ValidationWriter::default();
+        let mut dats = vec![];
+        let mut offset = 0;
+        for (i, (dat_file, size)) in dat_files.iter().zip(sizes.iter()).enumerate() {
+            info!("Validating `{}`", dat_file);
+            let mut dat_importer = DatImporter::from_path(dat_file, writer)?;
+            if let Some(reporter) = reporter.as_ref() {
+                dat_importer.set_reporter(CombinedImportReporter::new(Rc::clone(reporter), offset, i == dat_files.len() - 1));
+            }
+            let report = dat_importer.load_dat()?;
+            dats.push(DatImportReport { source: dat_file.to_owned(), report });
+            writer = dat_importer.into_writer().ok_or_else(|| anyhow!("The DAT writer is not available"))?;
+            offset += size;
         }
+        Ok(writer.into_validation(dats))
+    }
 
-        let mut conn = Romst::get_rw_connection(output_file.as_ref())?;
-        let db_writer = DBWriter::from_connection(&mut conn, DEFAULT_WRITE_BUFFER_SIZE);
-        let mut dat_importer = DatImporter::from_path(&input.as_ref().to_string(), db_writer)?;
+    /// The DAT files to import, the directories are replaced by the `.dat` and `.xml` files in them, sorted by name
+    fn get_dat_files<S>(inputs: &[S]) -> Result<Vec<String>> where S: AsRef<str> {
+        let mut dat_files = vec![];
+        for input in inputs {
+            let path = Path::new(input.as_ref());
+            if path.is_dir() {
+                let mut dir_files = fs::read_dir(path)?.map(|entry| Ok(entry?.path())).collect::<Result<Vec<_>>>()?
+                    .into_iter()
+                    .filter(|file| file.is_file() && file.extension()
+                        .map(|extension| extension.eq_ignore_ascii_case("dat") || extension.eq_ignore_ascii_case("xml"))
+                        .unwrap_or(false))
+                    .map(|file| file.to_string_lossy().to_string())
+                    .collect::<Vec<_>>();
+                dir_files.sort();
+                dat_files.extend(dir_files);
+            } else {
+                dat_files.push(input.as_ref().to_string());
+            }
+        }
+
+        if dat_files.is_empty() {
+            return Err(anyhow!("No DAT files found to import."));
+        }
+        Ok(dat_files)
+    }
+
+    /// Runs the import into a temporary file, and only moves it to the destination when the import finishes
+    fn write_import<T, F>(output_file: &str, options: &ImportOptions, import: F) -> Result<T> where F: FnOnce(&str) -> Result<T> {
+        let db_path = Path::new(output_file);
+        if !options.overwrite && db_path.exists() {
+            return Err(anyhow!("Destination file `{}` already exists, choose another output or rename the file.", output_file));
+        }
+        if options.game_filter.uses_tags() {
+            return Err(anyhow!("The games can't be filtered by tag when importing, the tags are given to the sets once imported."));
+        }
+
+        let tmp_file = format!("{}.tmp", output_file);
+        let tmp_path = Path::new(&tmp_file);
+        if tmp_path.exists() {
+            fs::remove_file(tmp_path)?;
+        }
+        // A journal left by an interrupted import would be replayed into the new database
+        for journal_file in [format!("{}-wal", tmp_file), format!("{}-shm", tmp_file)] {
+            if Path::new(&journal_file).exists() {
+                fs::remove_file(&journal_file)?;
+            }
+        }
+
+        match import(&tmp_file) {
+            Ok(import_report) => {
+                info!("Parsing complete");
+                if db_path.exists() {
+                    let conn = Romst::get_rw_connection(&tmp_file)?;
+                    let copied = copy_user_data(&conn, output_file)?;
+                    info!("Kept {} tags and notes from the previous database", copied);
+                }
+                fs::rename(tmp_path, db_path)?;
+                // An index from a previous import doesn't match the new database, it's kept if the import fails
+                let index_file = Romst::get_index_file(output_file);
+                if Path::new(&index_file).exists() {
+                    fs::remove_file(&index_file)?;
+                }
+                if options.build_index {
+                    Romst::build_index(output_file)?;
+                }
+                Ok(import_report)
+            }
+            Err(e) => {
+                error!("Error parsing file: {}", e);
+                if let Err(remove_error) = fs::remove_file(tmp_path) {
+                    error!("Error removing the temporary file `{}`: {}", tmp_file, remove_error);
+                }
+                Err(connection::retriable_if_busy(e, output_file))
+            }
+        }
+    }
+
+    /// Downloads a DAT, plain, gzipped or zipped, into a temporary directory that is removed when the returned value is dropped.
+    /// Import it with `import_dat` using the path of the returned `RemoteDat`
+    #[cfg(feature = "net")]
+    pub fn fetch_dat<S>(url: S, expected_sha1: Option<&str>) -> Result<data::importer::remote::RemoteDat> where S: AsRef<str> {
+        data::importer::remote::RemoteDat::fetch(url.as_ref(), expected_sha1)
+    }
+
+    /// Imports the DAT into the database, with `append` the entries are added to the ones of the DATs imported before
+    fn import_dat_to<R>(input: &str, output_file: &str, options: &ImportOptions, append: bool, reporter: Option<R>) -> Result<ImportReport> where R: DatImporterReporter + 'static {
+        let mut conn = Romst::get_rw_connection(output_file)?;
+        let db_writer = if append {
+            DBWriter::appending(&mut conn, DEFAULT_WRITE_BUFFER_SIZE)?
+        } else {
+            DBWriter::from_connection(&mut conn, DEFAULT_WRITE_BUFFER_SIZE)
+        };
+        let mut dat_importer = DatImporter::from_path(&input.to_string(), db_writer)?;
+        dat_importer.set_extended_metadata(options.extended_metadata);
+        dat_importer.set_max_errors(options.max_errors);
         if let Some(r) = reporter {
             dat_importer.set_reporter(r);
         }
 
-        match dat_importer.load_dat() {
-            Ok(_) => info!("Parsing complete"),
-            Err(e) => error!("Error parsing file: {}", e)
-        };
+        if options.threads > 1 {
+            dat_importer.load_dat_threaded()
+        } else {
+            dat_importer.load_dat()
+        }
+    }
 
-        Ok(())
+    /// Removes the games excluded by the filter, looks for the references to games not imported and compacts the database
+    /// if asked, once all the DATs are imported
+    fn finish_import(output_file: &str, options: &ImportOptions) -> Result<(u32, Option<ReferencesReport>, Option<VacuumReport>)> {
+        let mut conn = Romst::get_rw_connection(output_file)?;
+        let excluded = if options.game_filter.is_empty() { 0 } else { retain_games(&mut conn, &options.game_filter)? };
+        let references = Some(references::verify_references(&mut conn, options.fix_references)?).filter(|report| !report.is_empty());
+        if let Some(report) = &references {
+            warn!("{} references to games not in the database", report.dangling.len());
+        }
+        let vacuum = if options.vacuum { Some(maintenance::vacuum(&conn)?) } else { None };
+        Ok((excluded, references, vacuum))
     }
 
     // Returns a list of the games and their description
@@ -142,32 +779,46 @@ impl Romst {
     pub fn get_sets_info<S>(db_file: S, game_names: Vec<S>, rom_mode: RomsetMode) -> Result<GameSetsInfo> where S: AsRef<str> {
         let mut games =  vec![];
         let reader = Romst::get_data_reader(db_file)?;
+        let game_names = game_names.iter().map(|game_name| game_name.as_ref().to_string()).collect::<Vec<_>>();
+        let mut romsets_roms = reader.get_romsets_roms(&game_names, rom_mode)?;
         for game_name in game_names {
-            let roms = reader.get_romset_roms(game_name.as_ref(), rom_mode)?.into_iter().map(|db_rom| {
+            let roms = romsets_roms.remove(&game_name).unwrap_or_default().into_iter().map(|db_rom| {
                 db_rom.file
             }).collect();
-            let device_refs = reader.get_devices_for_game(game_name.as_ref())?;
-            match reader.get_game(game_name.as_ref()) {
+            let device_refs = reader.get_devices_for_game(game_name.as_str())?;
+            match reader.get_game(game_name.as_str()) {
                 Some(game) => {
                     games.push(GameSet::new(game, roms, vec![], vec![], device_refs.dependencies));
                 }
                 None => {
-                    error!("Game {} not found", game_name.as_ref())
+                    error!("Game {} not found", game_name)
                 }
             }
         }
 
-        Ok(GameSetsInfo::new(games))
+        let mut last_checks = reader.get_last_checks()?;
+        last_checks.sets.retain(|set_name, _| games.iter().any(|game_set| game_set.game.name == *set_name));
+        last_checks.retain_current();
+
+        Ok(GameSetsInfo { game_sets: games, last_checks: last_checks.sets, ownership: BTreeMap::new() })
     }
 
+    /// The sets using a rom of a set, with the name the rom has in each of them, the set itself included
     pub fn get_rom_usage<S>(db_file: S, game_name: S, rom_name: S, rom_mode: RomsetMode) -> Result<RomSearch> where S: AsRef<str> {
         let reader = Romst::get_data_reader(db_file)?;
         reader.get_rom_usage(game_name.as_ref(), rom_name.as_ref(), rom_mode)
     }
 
-    pub fn get_romset_shared_roms<S>(db_file: S, game_name: S, rom_mode: RomsetMode) -> Result<RomSearch> where S: AsRef<str> {
+    /// The other sets that have any of the roms of a set, with the names of the roms shared as they are in the set
+    pub fn get_romset_shared_roms<S>(db_file: S, game_name: S, rom_mode: RomsetMode) -> Result<SharedRoms> where S: AsRef<str> {
         let reader = Romst::get_data_reader(db_file)?;
-        reader.get_romset_shared_roms(game_name.as_ref(), rom_mode)
+        let rom_search = reader.get_romset_shared_roms(game_name.as_ref(), rom_mode)?;
+        Ok(SharedRoms::from_search(game_name, &rom_search))
+    }
+
+    pub fn get_sets_using_rom_id<S>(db_file: S, rom_id: u32) -> Result<RomSearch> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        reader.get_sets_using_rom_id(rom_id)
     }
 
     pub fn get_romset_dependencies<S>(db_file: S, game_name: S, rom_mode: RomsetMode) -> Result<SetDependencies> where S: AsRef<str> {
@@ -180,30 +831,349 @@ impl Romst {
         reader.get_db_info()
     }
 
-    pub fn get_report<R, S>(db_file: S, file_paths: Vec<impl AsRef<Path>>, rom_mode: RomsetMode, progress_reporter: Option<R>) -> Result<ScanReport> where R: ReportReporter + 'static, S: AsRef<str> {
+    /// The dump quality of the database, and of each of the sets requested
+    pub fn get_dump_quality<S>(db_file: S, game_names: Vec<S>, rom_mode: RomsetMode) -> Result<DumpQualityReport> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        let database = reader.get_dump_quality()?;
+        let mut sets = vec![];
+        for game_name in game_names {
+            let game_set = reader.get_set_info(game_name.as_ref(), rom_mode)?;
+            sets.push(SetDumpQuality { name: game_set.game.name.to_owned(), dump_quality: game_set.get_dump_quality() });
+        }
+
+        Ok(DumpQualityReport { database, sets })
+    }
+
+    /// Writes the database as a DAT, or as a DAT for each group in the output directory when split
+    pub fn export_dat<S>(db_file: S, output: S, split_by: Option<BrowseGrouping>) -> Result<DatExportReport> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        exporter::export_dat(&reader, &output.as_ref(), split_by)
+    }
+
+    /// The roms of the database matching the filter, with the sets using them. The filter needs at least a predicate
+    pub fn find_roms<S>(db_file: S, filter: RomFilter) -> Result<RomFindReport> where S: AsRef<str> {
+        if filter.is_empty() {
+            return Err(anyhow!("Set at least a hash prefix or a size to find the roms"));
+        }
+        let reader = Romst::get_data_reader(db_file)?;
+        let roms = reader.find_roms(&filter)?;
+        Ok(RomFindReport { filter, roms })
+    }
+
+    /// How many rom bytes each romset mode stores, and how much merging saves
+    pub fn get_dedupe_stats<S>(db_file: S) -> Result<DedupeStats> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        reader.get_dedupe_stats()
+    }
+
+    /// The sets storing the most, or the least, rom bytes in the mode, and the roms with the most, or the least, bytes
+    pub fn get_sizes<S>(db_file: S, rom_mode: RomsetMode, order: SizeOrder, limit: u32) -> Result<SizesReport> where S: AsRef<str> {
         let reader = Romst::get_data_reader(db_file)?;
+        reader.get_sizes(rom_mode, order, limit)
+    }
+
+    pub fn browse_sets<S>(db_file: S, grouping: BrowseGrouping) -> Result<BrowseReport> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        let groups = reader.get_sets_grouped_by(grouping, RomsetMode::NonMerged)?;
+        Ok(BrowseReport { grouping, groups })
+    }
+
+    /// Checks the files against the sets of the reader, either from a database with `get_data_reader` or from a DAT with `get_data_reader_from_dat`.
+    /// It blocks on a runtime of its own, so it fails if called from within a tokio runtime, use `get_report_async` there
+    pub fn get_report<R>(reader: DBReader, file_paths: Vec<impl AsRef<Path>>, options: ScanOptions, cancellation: CancellationToken, progress_reporter: Option<R>) -> Result<ScanReport> where R: ReportReporter + Send + 'static {
+        Romst::get_report_streaming(reader, file_paths, options, cancellation, progress_reporter, |_file_name: &str, _set_report: &SetReport| {})
+    }
+
+    /// Same as `get_report`, but the listener receives the set reports as the files are processed
+    pub fn get_report_streaming<R, L>(reader: DBReader, file_paths: Vec<impl AsRef<Path>>, options: ScanOptions, cancellation: CancellationToken, progress_reporter: Option<R>, set_listener: L) -> Result<ScanReport> where R: ReportReporter + Send + 'static, L: SetReportListener + Send + 'static {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err(anyhow!("The check can't block within a tokio runtime, use `get_report_async` instead"));
+        }
+
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?
+            .block_on(Romst::get_report_streaming_async(reader, file_paths, options, cancellation, progress_reporter, set_listener))
+    }
+
+    /// Checks again the files of the report that failed with a transient error, reading them from the directory, and
+    /// adds them to the report. The files failing again keep their errors
+    pub fn retry_errors<R>(reader: DBReader, mut report: ScanReport, directory: impl AsRef<Path>, options: ScanOptions, cancellation: CancellationToken, progress_reporter: Option<R>) -> Result<ScanReport> where R: ReportReporter + Send + 'static {
+        if report.get_rom_mode() != options.rom_mode {
+            return Err(anyhow!("The report is checked as {}, the errors can't be checked again as {}", report.get_rom_mode(), options.rom_mode));
+        }
+        let file_paths = report.get_retriable_files().into_iter().map(|file| directory.as_ref().join(file)).collect::<Vec<_>>();
+        if file_paths.is_empty() {
+            return Ok(report);
+        }
+
+        let retried = Romst::get_report(reader, file_paths, options, cancellation, progress_reporter)?;
+        report.merge_retried(retried)?;
+        Ok(report)
+    }
+
+    /// Same as `get_report`, but runs on the runtime of the caller. The runtime needs the IO and time drivers enabled, the
+    /// files are read in tasks spawned on it, so they are only read in parallel on a multi thread runtime
+    pub async fn get_report_async<R>(reader: DBReader, file_paths: Vec<impl AsRef<Path>>, options: ScanOptions, cancellation: CancellationToken, progress_reporter: Option<R>) -> Result<ScanReport> where R: ReportReporter + Send + 'static {
+        Romst::get_report_streaming_async(reader, file_paths, options, cancellation, progress_reporter, |_file_name: &str, _set_report: &SetReport| {}).await
+    }
+
+    /// Same as `get_report_streaming`, but runs on the runtime of the caller as `get_report_async`
+    pub async fn get_report_streaming_async<R, L>(reader: DBReader, file_paths: Vec<impl AsRef<Path>>, options: ScanOptions, cancellation: CancellationToken, progress_reporter: Option<R>, set_listener: L) -> Result<ScanReport> where R: ReportReporter + Send + 'static, L: SetReportListener + Send + 'static {
+        let mut options = options;
+        Romst::load_filter_tags(&reader, &mut options.game_filter)?;
 
         let mut reporter = Reporter::new(reader);
+        reporter.set_cancellation_token(cancellation);
+        reporter.add_set_listener(set_listener);
         if let Some(progress_reporter) = progress_reporter {
             reporter.add_reporter(progress_reporter);
         }
 
-        let report = tokio::runtime::Builder::new_multi_thread()
-            .enable_all()
-            .build()
-            .unwrap()
-            .block_on(async { 
-                reporter.check(file_paths, rom_mode).await
-             });
-        report
+        reporter.check(file_paths, options).await
+    }
+
+    /// Compares a sample of the files with the roms each mode expects, and returns the mode that fits best
+    pub fn detect_romset_mode(reader: &DBReader, file_paths: Vec<impl AsRef<Path>>, sample_size: usize) -> Result<ModeDetection> {
+        reporter::detect_romset_mode(reader, file_paths, sample_size)
+    }
+
+    /// Whether the mode asked is not the one the DAT of the database is meant to be used in
+    pub fn get_mode_mismatch<S>(db_file: S, rom_mode: RomsetMode) -> Result<Option<ModeMismatch>> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        ModeMismatch::check(&reader, rom_mode)
+    }
+
+    /// Checks one archive against one set, without looking for its roms in other sets, a lighter check for a single set
+    pub fn check_set<S>(db_file: S, set_name: S, file_path: impl AsRef<Path>, options: ScanOptions) -> Result<SetReport> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        let mut reporter = Reporter::new(reader);
+        reporter.check_set(set_name.as_ref(), &file_path, options)
+    }
+
+    /// Reduces the report to a summary, with the sets broken since the last check of the same directory. The statuses of
+    /// the sets are stored in the database to compare them in the next check, unless the check was interrupted
+    pub fn summarize_report<S>(db_file: S, report: ScanReport) -> Result<CheckSummary> where S: AsRef<str> {
+        let root_directory = report.get_root_directory().unwrap_or_default().to_string();
+        let last_complete = Romst::get_data_reader(db_file.as_ref())?.get_last_complete_sets(&root_directory)?;
+        let statuses = report.get_set_statuses();
+        let summary = CheckSummary::new(&report, &statuses, &last_complete);
+        drop(report);
+
+        if summary.unscanned == 0 {
+            Romst::write_check_statuses(db_file.as_ref(), &root_directory, &statuses)?;
+        }
+
+        Ok(summary)
+    }
+
+    /// Stores the statuses of the sets in the database, so they are known without checking again until the directory
+    /// is modified or the DAT imported again. The statuses of an interrupted check are not stored
+    pub fn store_set_statuses<S>(db_file: S, report: &ScanReport) -> Result<()> where S: AsRef<str> {
+        if report.is_interrupted() {
+            return Ok(());
+        }
+        let root_directory = report.get_root_directory().unwrap_or_default();
+        Romst::write_check_statuses(db_file.as_ref(), root_directory, &report.get_set_statuses())
+    }
+
+    fn write_check_statuses(db_file: &str, root_directory: &str, statuses: &BTreeMap<String, SetStatus>) -> Result<()> {
+        let directory_modified = if root_directory.is_empty() { None } else { paths::get_modified_millis(root_directory) };
+        let mut conn = Romst::get_existing_rw_connection(db_file)?;
+        write_check_statuses(&mut conn, root_directory, directory_modified, statuses).map_err(|e| connection::retriable_if_busy(e, db_file))
+    }
+
+    /// The statuses of the sets in the last checks, without the ones of the directories modified since
+    pub fn get_last_checks<S>(db_file: S) -> Result<SetLastChecks> where S: AsRef<str> {
+        let mut last_checks = Romst::get_data_reader(db_file)?.get_last_checks()?;
+        last_checks.retain_current();
+        Ok(last_checks)
     }
 
     pub fn save_report<S>(output_file: S, report: ScanReport) -> Result<()> where S: AsRef<str> {
-        let encoded: Vec<u8> = bincode::serialize(&report)?;
-        let mut file = File::create(output_file.as_ref())?;
-        file.write_all(&encoded)?;
+        Romst::save_report_with_progress(output_file, report, None)
+    }
 
-        Ok(())
+    /// Saves the report compressed, the reporter gets the bytes of the report written
+    pub fn save_report_with_progress<S>(output_file: S, report: ScanReport, reporter: Option<&mut dyn ReportFileReporter>) -> Result<()> where S: AsRef<str> {
+        report_file::write_report(Path::new(output_file.as_ref()), &report, reporter)
+    }
+
+    pub fn load_report<S>(report_file: S) -> Result<ScanReport> where S: AsRef<str> {
+        Romst::load_report_with_progress(report_file, None)
+    }
+
+    /// Loads a report, compressed or saved before the reports were compressed. The reporter gets the bytes of the file read
+    pub fn load_report_with_progress<S>(report_file: S, reporter: Option<&mut dyn ReportFileReporter>) -> Result<ScanReport> where S: AsRef<str> {
+        let report = report_file::read_report(Path::new(report_file.as_ref()), reporter)
+            .map_err(|e| anyhow!("The report `{}` can't be read, it may be from another version, check again.\n{}", report_file.as_ref(), e))?;
+        if report.get_schema_version() != REPORT_SCHEMA_VERSION {
+            return Err(anyhow!("The report `{}` is from another version, check again.", report_file.as_ref()));
+        }
+
+        Ok(report)
+    }
+
+    /// The sets of the database in the scope that the report is missing, the sets outside the scope don't count
+    pub fn get_missing_sets<S>(db_file: S, report_file: S, mut scope: GameFilter) -> Result<MissingSets> where S: AsRef<str> {
+        let report = Romst::load_report(report_file)?;
+        let reader = Romst::get_data_reader(db_file)?;
+        Romst::load_filter_tags(&reader, &mut scope)?;
+        reader.get_missing_sets(&report, &scope)
+    }
+
+    /// Merges the reports of several scans in a single one, the reports are merged in order into the first one
+    pub fn merge_reports<S>(report_files: &[S], output_file: S, mut reporter: Option<&mut dyn ReportFileReporter>) -> Result<()> where S: AsRef<str> {
+        let mut report_files = report_files.iter();
+        let mut report = match report_files.next() {
+            Some(report_file) => Romst::load_report_with_progress(report_file, reporter.as_mut().map(|reporter| &mut **reporter as &mut dyn ReportFileReporter))?,
+            None => return Err(anyhow!("There are no reports to merge")),
+        };
+        for report_file in report_files {
+            report.merge(Romst::load_report_with_progress(report_file, reporter.as_mut().map(|reporter| &mut **reporter as &mut dyn ReportFileReporter))?)
+                .map_err(|e| anyhow!("Error merging the report `{}`: {}", report_file.as_ref(), e))?;
+        }
+        Romst::save_report_with_progress(output_file, report, reporter)
+    }
+
+    /// The sets whose status changed from the older report to the newer one
+    pub fn diff_reports<S>(before_file: S, after_file: S) -> Result<ReportDiff> where S: AsRef<str> {
+        let before = Romst::load_report(before_file)?;
+        let after = Romst::load_report(after_file)?;
+        Ok(ReportDiff::new(&before, &after))
     }
 
+    /// Renders the missing roms of a report in a format that other rom managers can import
+    pub fn get_miss_list<S>(report_file: S, format: MissListFormat) -> Result<String> where S: AsRef<str> {
+        let report = Romst::load_report(report_file)?;
+        let mut miss_list = String::new();
+        miss_list::write_miss_list(&mut miss_list, &report, format)?;
+
+        Ok(miss_list)
+    }
+
+    /// Moves the spare and unknown files from a report to the target directory, unless it's a dry run.
+    /// Returns the clean plan.
+    pub fn clean<S>(report_file: S, source_directory: Option<S>, target_directory: S, dry_run: bool) -> Result<CleanPlan> where S: AsRef<str> {
+        let report = Romst::load_report(report_file)?;
+        let plan = CleanPlan::from_report(&report, source_directory, target_directory)?;
+        if !dry_run {
+            plan.execute()?;
+        }
+
+        Ok(plan)
+    }
+
+    /// Renames and copies the roms of the fixeable sets from a report, unless it's a dry run. Returns the fix plan.
+    /// The sets in the priority list are fixed first, and the sets done are recorded in a journal next to the report,
+    /// so a run cancelled or failed continues with the remaining sets when run again. Unless `skip_space_check` is set, it
+    /// fails before changing anything if the directory may not have space for it. The archives are backed up before they
+    /// are changed, in a folder next to the report, and can be restored with `restore_backup`
+    pub fn fix<S>(report_file: S, priority: &SetPriority, dry_run: bool, skip_space_check: bool, cancellation: &CancellationToken) -> Result<FixPlan> where S: AsRef<str> {
+        let report = Romst::load_report(report_file.as_ref())?;
+        let mut plan = report.build_fix_plan();
+        plan.prioritize(priority);
+        plan.read_available_space();
+        if !dry_run {
+            if !skip_space_check {
+                plan.space.check(plan.get_root_directory().unwrap_or_default())?;
+            }
+            let mut journal = ResumeJournal::open(ResumeJournal::get_journal_file(report_file.as_ref(), "fix"), report.get_date_time_string())?;
+            let mut backup = Backup::new(Backup::get_backup_root(report_file.as_ref(), "fix"));
+            let progress = plan.execute_resumable(&mut journal, &mut backup, cancellation);
+            plan.progress = Some(Romst::with_backup_manifest(progress, &backup)?);
+        }
+
+        Ok(plan)
+    }
+
+    /// Reads the list of the sets to fix or rebuild first, the sets of the tags in it are looked up in the database
+    pub fn get_set_priority<S>(priority_file: S, db_file: Option<S>) -> Result<SetPriority> where S: AsRef<str> {
+        let user_data = db_file.map(|db_file| Romst::get_user_data(db_file, None)).transpose()?;
+        SetPriority::from_file(priority_file.as_ref(), user_data.as_ref())
+    }
+
+    /// Lists the sets of a report that can be fixed with the roms already in the collection, with their moves together
+    pub fn analyze_fixable<S>(report_file: S) -> Result<FixableAnalysis> where S: AsRef<str> {
+        let report = Romst::load_report(report_file)?;
+        Ok(FixableAnalysis::from_report(&report))
+    }
+
+    /// Rebuilds the sets of a report into new archives in the target directory, with `dry_run` only the plan is built.
+    /// The time is estimated copying at `throughput` megabytes per second. Without a packing, the one the DAT expects is used
+    /// The sets are rebuilt in the order of the priority list and recorded in a journal next to the report, and the free
+    /// space of the target directory is checked first and the archives replaced are backed up, as in `fix`
+    #[allow(clippy::too_many_arguments)]
+    pub fn rebuild<S>(report_file: S, target_directory: S, throughput: f64, packing: Option<Packing>, name_policy: NamePolicy, priority: &SetPriority, dry_run: bool, skip_space_check: bool, cancellation: &CancellationToken) -> Result<RebuildPlan> where S: AsRef<str> {
+        let report = Romst::load_report(report_file.as_ref())?;
+        let mut plan = report.build_rebuild_plan(target_directory, throughput, packing);
+        plan.sanitize_names(name_policy);
+        plan.prioritize(priority);
+        plan.read_available_space();
+        if !dry_run {
+            if !skip_space_check {
+                plan.space.check(plan.get_target_directory())?;
+            }
+            let mut journal = ResumeJournal::open(ResumeJournal::get_journal_file(report_file.as_ref(), "rebuild"), report.get_date_time_string())?;
+            let mut backup = Backup::new(Backup::get_backup_root(report_file.as_ref(), "rebuild"));
+            let progress = plan.execute_resumable(&mut journal, &mut backup, cancellation);
+            plan.progress = Some(Romst::with_backup_manifest(progress, &backup)?);
+        }
+
+        Ok(plan)
+    }
+
+    /// A run that failed tells where the archives it changed are backed up
+    fn with_backup_manifest(progress: Result<ExecutionProgress>, backup: &Backup) -> Result<ExecutionProgress> {
+        match (progress, backup.get_manifest_file()) {
+            (Err(e), Some(manifest_file)) => Err(anyhow!("{}\nThe archives changed are backed up, the manifest to restore them is `{}`", e, manifest_file.to_string_lossy())),
+            (progress, _) => progress,
+        }
+    }
+
+    /// Puts back the archives changed by a fix or a rebuild as they were before, from the manifest of its backup
+    pub fn restore_backup<S>(manifest_file: S) -> Result<BackupManifest> where S: AsRef<str> {
+        Backup::restore(manifest_file.as_ref())
+    }
+
+    /// Plans the move of a collection to a new version of its DAT from a check against the new database, the old
+    /// database tells the sets that were renamed. Unless it's a dry run, the sets are rebuilt into the target directory
+    pub fn migrate<S>(from_db: S, report: &ScanReport, target_directory: S, packing: Option<Packing>, dry_run: bool, cancellation: &CancellationToken) -> Result<MigrationPlan> where S: AsRef<str> {
+        let old_sets = Romst::get_data_reader(from_db)?.get_game_list(report.get_rom_mode())?.into_iter().map(|(set_name, _)| set_name).collect();
+        let mut plan = MigrationPlan::from_report(report, &old_sets, target_directory, packing);
+        if !dry_run {
+            plan.execute(cancellation)?;
+        }
+
+        Ok(plan)
+    }
+
+    /// Gathers the set with the sets, disks and samples it needs in the mode into the target directory, what isn't in
+    /// the source directory is listed in the plan. Nothing is written if it's a dry run
+    pub fn bundle<S>(db_file: S, game_name: S, options: BundleOptions, dry_run: bool) -> Result<BundlePlan> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        let mut plan = BundlePlan::new(&reader, game_name.as_ref(), &options)?;
+        if !dry_run {
+            plan.execute(options.zip)?;
+        }
+
+        Ok(plan)
+    }
+
+    pub fn undo_clean<S>(target_directory: S) -> Result<CleanPlan> where S: AsRef<str> {
+        CleanPlan::undo(target_directory)
+    }
+
+    /// Stores the roms of the files in the pool, compressed with zstd if `compress` is set
+    pub fn pool_add<S>(pool_directory: S, file_paths: Vec<impl AsRef<Path>>, compress: bool) -> Result<PoolAddReport> where S: AsRef<str> {
+        let mut pool = RomPool::open(&pool_directory.as_ref())?;
+        pool.add_files(file_paths, compress)
+    }
+
+    /// Writes the sets in the target directory in the mode, with the roms from the pool
+    pub fn pool_build<S>(db_file: S, pool_directory: S, game_names: Vec<S>, rom_mode: RomsetMode, target_directory: S) -> Result<PoolBuildReport> where S: AsRef<str> {
+        let reader = Romst::get_data_reader(db_file)?;
+        let pool = RomPool::open(&pool_directory.as_ref())?;
+        pool.build_sets(&reader, game_names, rom_mode, &target_directory.as_ref())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,509 @@
+//! A JSON-RPC 2.0 server listening on a Unix socket, so other programs can import, check and get info without parsing
+//! the output of the command line. Every request, response and notification is a JSON object in a single line.
+//! While a request runs its progress is sent in `progress` notifications with the id of the request, a check also
+//! sends a `set_report` notification each time a set is updated.
+use std::{fmt::Display, fs, io::{BufRead, BufReader, Write}, os::unix::net::{UnixListener, UnixStream}, path::{Path, PathBuf}, str::FromStr, sync::{Arc, Mutex}, thread};
+
+use anyhow::Result;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::{BiosSelection, EntryPaths, ImportOptions, RomsetMode, Romst, ScanOptions, data::{importer::{DatImporterReporter, ImportThroughput}, reporter::{CancellationToken, ReportReporter, scan_report::{SetReport, SetStatus}}}, err, error::RomstError};
+
+const JSONRPC_VERSION: &str = "2.0";
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The operation failed, like when the database doesn't exist
+const OPERATION_ERROR: i64 = -32000;
+
+pub struct Server {
+    listener: UnixListener,
+    socket_path: PathBuf,
+}
+
+impl Server {
+    /// Listens on the socket. A socket left by a server that is not running anymore is replaced
+    pub fn bind(socket_path: impl AsRef<Path>) -> Result<Self> {
+        let socket_path = socket_path.as_ref();
+        if socket_path.exists() {
+            if UnixStream::connect(socket_path).is_ok() {
+                return err!(RomstError::GenericError { message: format!("There is a server listening at `{}` already", socket_path.to_string_lossy()) });
+            }
+            fs::remove_file(socket_path)?;
+        }
+
+        let listener = UnixListener::bind(socket_path)?;
+        Ok(Self { listener, socket_path: socket_path.to_path_buf() })
+    }
+
+    /// Serves the clients until the process ends, each client and each of its requests run in their own thread
+    pub fn serve(&self) -> Result<()> {
+        info!("Listening at {}", self.socket_path.to_string_lossy());
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    thread::spawn(move || {
+                        if let Err(e) = serve_client(stream) {
+                            error!("ERROR serving the client: {}", e);
+                        }
+                    });
+                }
+                Err(e) => error!("ERROR accepting a client: {}", e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.socket_path) {
+            debug!("Can't remove the socket {}: {}", self.socket_path.to_string_lossy(), e);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new<S>(code: i64, message: S) -> Self where S: Into<String> { Self { code, message: message.into() } }
+}
+
+/// The writing half of the connection with a client, shared by the requests running for it
+#[derive(Debug, Clone)]
+struct Client {
+    stream: Arc<Mutex<UnixStream>>,
+}
+
+impl Client {
+    fn send(&self, message: Value) {
+        let mut stream = match self.stream.lock() {
+            Ok(stream) => stream,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Err(e) = writeln!(stream, "{}", message) {
+            debug!("Can't write to the client: {}", e);
+        }
+    }
+
+    fn respond(&self, id: Value, result: Result<Value, RpcError>) {
+        match result {
+            Ok(result) => self.send(json!({ "jsonrpc": JSONRPC_VERSION, "id": id, "result": result })),
+            Err(error) => self.send(json!({ "jsonrpc": JSONRPC_VERSION, "id": id, "error": error })),
+        }
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": JSONRPC_VERSION, "method": method, "params": params }));
+    }
+}
+
+fn serve_client(stream: UnixStream) -> Result<()> {
+    let client = Client { stream: Arc::new(Mutex::new(stream.try_clone()?)) };
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            handle_line(&client, &line);
+        }
+    }
+    Ok(())
+}
+
+/// Requests without id are notifications, they run but get no response
+fn handle_line(client: &Client, line: &str) {
+    let request = match serde_json::from_str::<Value>(line) {
+        Ok(value) => value,
+        Err(e) => return client.respond(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))),
+    };
+    let request = match serde_json::from_value::<Request>(request) {
+        Ok(request) if request.jsonrpc == JSONRPC_VERSION => request,
+        Ok(request) => return client.respond(request.id.unwrap_or_default(), Err(RpcError::new(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported"))),
+        Err(e) => return client.respond(Value::Null, Err(RpcError::new(INVALID_REQUEST, e.to_string()))),
+    };
+
+    let client = client.clone();
+    thread::spawn(move || {
+        let id = request.id.clone().unwrap_or_default();
+        let result = handle_request(&client, &id, &request.method, request.params);
+        if let Some(id) = request.id {
+            client.respond(id, result);
+        }
+    });
+}
+
+fn handle_request(client: &Client, id: &Value, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "import" => import(client, id, parse_params(params)?),
+        "check" => check(client, id, parse_params(params)?),
+        "info.data" => {
+            let params: DbParams = parse_params(params)?;
+            to_result(Romst::get_db_info(params.db))
+        }
+        "info.set" => {
+            let params: SetParams = parse_params(params)?;
+            let rom_mode = parse_or_default(&params.mode)?;
+            to_result(Romst::get_sets_info(params.db, params.games, rom_mode))
+        }
+        "info.romusage" => {
+            let params: RomUsageParams = parse_params(params)?;
+            let rom_mode = parse_or_default(&params.mode)?;
+            to_result(Romst::get_rom_usage(params.db, params.game, params.rom, rom_mode))
+        }
+        "info.sharedroms" => {
+            let params: SharedRomsParams = parse_params(params)?;
+            let rom_mode = parse_or_default(&params.mode)?;
+            to_result(Romst::get_romset_shared_roms(params.db, params.game, rom_mode))
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method `{}` not found", method))),
+    }
+}
+
+fn parse_params<T>(params: Value) -> Result<T, RpcError> where T: DeserializeOwned {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+/// The values like the romset mode are sent as in the command line, `non-merged` or `file-name`
+fn parse_or_default<T>(value: &Option<String>) -> Result<T, RpcError> where T: FromStr + Default, T::Err: Display {
+    match value {
+        Some(value) => value.parse().map_err(|e: T::Err| RpcError::new(INVALID_PARAMS, e.to_string())),
+        None => Ok(T::default()),
+    }
+}
+
+fn to_result<T>(result: Result<T>) -> Result<Value, RpcError> where T: Serialize {
+    match result {
+        Ok(value) => serde_json::to_value(value).map_err(|e| RpcError::new(OPERATION_ERROR, e.to_string())),
+        Err(e) => Err(RpcError::new(OPERATION_ERROR, e.to_string())),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DbParams {
+    db: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetParams {
+    db: String,
+    games: Vec<String>,
+    #[serde(default)]
+    mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RomUsageParams {
+    db: String,
+    game: String,
+    rom: String,
+    #[serde(default)]
+    mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SharedRomsParams {
+    db: String,
+    game: String,
+    #[serde(default)]
+    mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportParams {
+    source: String,
+    db: String,
+    #[serde(default)]
+    overwrite: bool,
+    #[serde(default)]
+    index: bool,
+    #[serde(default)]
+    extended_metadata: bool,
+    #[serde(default)]
+    threads: Option<usize>,
+}
+
+fn import(client: &Client, id: &Value, params: ImportParams) -> Result<Value, RpcError> {
+    let options = ImportOptions {
+        overwrite: params.overwrite,
+        build_index: params.index,
+        threads: params.threads.unwrap_or(1),
+        extended_metadata: params.extended_metadata,
+        ..ImportOptions::default()
+    };
+    let reporter = DatImporterReporterRpc::new(client.clone(), id.clone());
+    to_result(Romst::import_dat(params.source, params.db, options, Some(reporter)))
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckParams {
+    db: String,
+    sources: Vec<String>,
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default)]
+    bios: Option<String>,
+    #[serde(default)]
+    entry_paths: Option<String>,
+    #[serde(default)]
+    deep_verify: bool,
+}
+
+fn check(client: &Client, id: &Value, params: CheckParams) -> Result<Value, RpcError> {
+    let rom_mode: RomsetMode = parse_or_default(&params.mode)?;
+    let bios: BiosSelection = parse_or_default(&params.bios)?;
+    let entry_paths: EntryPaths = parse_or_default(&params.entry_paths)?;
+
+    let reporter = ReportReporterRpc::new(client.clone(), id.clone());
+    let listener_client = client.clone();
+    let listener_id = id.clone();
+    let set_listener = move |file_name: &str, set_report: &SetReport| {
+        listener_client.notify("set_report", json!({ "id": listener_id, "file": file_name, "set": set_report }));
+    };
+    let options = ScanOptions::new(rom_mode).bios(bios).entry_paths(entry_paths).deep_verify(params.deep_verify);
+    let sources = params.sources;
+    to_result(Romst::get_data_reader(params.db).and_then(|reader| Romst::get_report_streaming(reader, sources, options,
+        CancellationToken::default(), Some(reporter), set_listener)))
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ImportProgress {
+    total_bytes: u64,
+    bytes: u64,
+    entries: u32,
+    finishing: bool,
+    finish_done: u64,
+    finish_total: u64,
+    finished: bool,
+}
+
+/// Sends the progress of an import, at most once for each percent of the file read
+#[derive(Debug)]
+struct DatImporterReporterRpc {
+    client: Client,
+    id: Value,
+    progress: Mutex<ImportProgress>,
+    last_percent: u64,
+}
+
+impl DatImporterReporterRpc {
+    fn new(client: Client, id: Value) -> Self { Self { client, id, progress: Mutex::new(ImportProgress::default()), last_percent: 0 } }
+
+    fn send_progress(&self) {
+        let progress = match self.progress.lock() {
+            Ok(progress) => progress,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        self.client.notify("progress", json!({ "id": self.id, "progress": *progress }));
+    }
+
+    fn update<F>(&self, update: F) where F: FnOnce(&mut ImportProgress) {
+        match self.progress.lock() {
+            Ok(mut progress) => update(&mut progress),
+            Err(poisoned) => update(&mut poisoned.into_inner()),
+        }
+    }
+}
+
+impl DatImporterReporter for DatImporterReporterRpc {
+    fn set_total_bytes(&mut self, total_bytes: u64) {
+        self.update(|progress| progress.total_bytes = total_bytes);
+        self.send_progress();
+    }
+
+    fn update_position(&mut self, bytes: u64, new_entries: u32) {
+        let mut percent = 0;
+        self.update(|progress| {
+            progress.bytes = bytes;
+            progress.entries += new_entries;
+            percent = (bytes * 100).checked_div(progress.total_bytes).unwrap_or_default();
+        });
+        if percent > self.last_percent {
+            self.last_percent = percent;
+            self.send_progress();
+        }
+    }
+
+    fn update_throughput(&mut self, _throughput: ImportThroughput) {}
+
+    fn start_finish(&self) {
+        self.update(|progress| progress.finishing = true);
+        self.send_progress();
+    }
+
+    fn update_finish_progress(&mut self, done: u64, total: u64) {
+        self.update(|progress| {
+            progress.finish_done = done;
+            progress.finish_total = total;
+        });
+        self.send_progress();
+    }
+
+    fn finish(&self) {
+        self.update(|progress| progress.finished = true);
+        self.send_progress();
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+struct CheckProgress {
+    total_files: usize,
+    current_file: String,
+    processed: usize,
+    directories: usize,
+    ignored: usize,
+    errors: usize,
+    /// The sets found so far with each status
+    complete: usize,
+    fixeable: usize,
+    incomplete: usize,
+    finished: bool,
+}
+
+impl CheckProgress {
+    fn get_set_count(&mut self, status: SetStatus) -> &mut usize {
+        match status {
+            SetStatus::COMPLETE => &mut self.complete,
+            SetStatus::FIXEABLE => &mut self.fixeable,
+            SetStatus::INCOMPLETE => &mut self.incomplete,
+        }
+    }
+}
+
+/// Sends the progress of a check each time a file is processed
+#[derive(Debug)]
+struct ReportReporterRpc {
+    client: Client,
+    id: Value,
+    progress: CheckProgress,
+}
+
+impl ReportReporterRpc {
+    fn new(client: Client, id: Value) -> Self { Self { client, id, progress: CheckProgress::default() } }
+
+    fn send_progress(&self) {
+        self.client.notify("progress", json!({ "id": self.id, "progress": self.progress }));
+    }
+}
+
+impl ReportReporter for ReportReporterRpc {
+    fn set_total_files(&mut self, total_files: usize) {
+        self.progress.total_files = total_files;
+        self.send_progress();
+    }
+
+    fn update_report_new_file(&mut self, new_file: &str) {
+        self.progress.current_file = new_file.to_string();
+    }
+
+    fn update_report_new_added_file(&mut self, new_files: usize) {
+        self.progress.processed += new_files;
+        self.send_progress();
+    }
+
+    fn update_report_directory(&mut self, new_files: usize) {
+        self.progress.directories += new_files;
+        self.send_progress();
+    }
+
+    fn update_report_ignored(&mut self, new_files: usize) {
+        self.progress.ignored += new_files;
+        self.send_progress();
+    }
+
+    fn update_report_file_error(&mut self, new_files: usize) {
+        self.progress.errors += new_files;
+        self.send_progress();
+    }
+
+    fn set_matched(&mut self, _set_name: &str) {}
+
+    fn set_completed(&mut self, _set_name: &str, status: SetStatus, previous: Option<SetStatus>) {
+        if let Some(previous) = previous {
+            *self.progress.get_set_count(previous) -= 1;
+        }
+        *self.progress.get_set_count(status) += 1;
+        self.send_progress();
+    }
+
+    fn finish(&mut self) {
+        self.progress.finished = true;
+        self.send_progress();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::BufReader};
+    use super::*;
+
+    fn request(stream: &mut UnixStream, reader: &mut BufReader<UnixStream>, request: Value) -> Result<(Value, Vec<Value>)> {
+        writeln!(stream, "{}", request)?;
+        let mut notifications = vec![];
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let message: Value = serde_json::from_str(&line)?;
+            if message.get("method").is_some() {
+                notifications.push(message);
+            } else {
+                return Ok((message, notifications));
+            }
+        }
+    }
+
+    #[test]
+    fn serves_the_requests() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_server_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let db = dir.join("test.rst").to_string_lossy().to_string();
+        let socket = dir.join("romst.sock");
+
+        let server = Server::bind(&socket)?;
+        assert!(Server::bind(&socket).is_err());
+        thread::spawn(move || server.serve());
+
+        let mut stream = UnixStream::connect(&socket)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        let (response, notifications) = request(&mut stream, &mut reader, json!({ "jsonrpc": "2.0", "id": 1, "method": "import", "params": { "source": "testdata/test.dat", "db": db } }))?;
+        assert_eq!(json!(1), response["id"]);
+        assert!(response["result"]["entries"].as_u64().unwrap_or_default() > 0);
+        assert!(notifications.iter().any(|notification| notification["params"]["progress"]["finished"] == json!(true)));
+
+        let (response, _) = request(&mut stream, &mut reader, json!({ "jsonrpc": "2.0", "id": 2, "method": "info.data", "params": { "db": db } }))?;
+        assert_eq!(json!(10), response["result"]["games"]);
+
+        let (response, notifications) = request(&mut stream, &mut reader, json!({ "jsonrpc": "2.0", "id": "check", "method": "check", "params": { "db": db, "sources": ["testdata/single"], "mode": "merged" } }))?;
+        assert!(response["result"]["sets"]["game4"].is_object());
+        assert!(notifications.iter().any(|notification| notification["method"] == json!("set_report") && notification["params"]["id"] == json!("check")));
+
+        let (response, _) = request(&mut stream, &mut reader, json!({ "jsonrpc": "2.0", "id": 3, "method": "info.set", "params": { "db": db, "games": ["game1"], "mode": "nope" } }))?;
+        assert_eq!(json!(INVALID_PARAMS), response["error"]["code"]);
+        let (response, _) = request(&mut stream, &mut reader, json!({ "jsonrpc": "2.0", "id": 4, "method": "nope" }))?;
+        assert_eq!(json!(METHOD_NOT_FOUND), response["error"]["code"]);
+        writeln!(stream, "{{ not json")?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        assert_eq!(json!(PARSE_ERROR), serde_json::from_str::<Value>(&line)?["error"]["code"]);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,18 +1,89 @@
+use std::fmt::Display;
+
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::LevelFilter;
+
+use crate::data::{importer::{DatImporterReporter, ImportThroughput}, reporter::{ReportReporter, report_file::ReportFileReporter, scan_report::SetStatus}};
+
+/// How much output is written to the console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+impl Verbosity {
+    pub fn from_flags(quiet: bool, verbose: bool) -> Self {
+        if quiet {
+            Verbosity::Quiet
+        } else if verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+
+    pub fn log_level(&self) -> LevelFilter {
+        match self {
+            Verbosity::Quiet => LevelFilter::Error,
+            Verbosity::Normal => LevelFilter::Warn,
+            Verbosity::Verbose => LevelFilter::Debug,
+        }
+    }
 
-use crate::data::{importer::DatImporterReporter, reporter::ReportReporter};
+    /// Progress is never drawn in quiet mode, or when the stdout is not a terminal
+    pub fn show_progress(&self) -> bool {
+        *self != Verbosity::Quiet && console::user_attended()
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        *self == Verbosity::Quiet
+    }
+}
+
+impl Display for Verbosity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verbosity::Quiet => write!(f, "Quiet"),
+            Verbosity::Normal => write!(f, "Normal"),
+            Verbosity::Verbose => write!(f, "Verbose"),
+        }
+    }
+}
+
+fn draw_target(verbosity: Verbosity) -> ProgressDrawTarget {
+    if verbosity.show_progress() {
+        ProgressDrawTarget::stdout()
+    } else {
+        ProgressDrawTarget::hidden()
+    }
+}
 
 #[derive(Debug)]
 pub struct DatImporterReporterSysOut {
     progress_bar: ProgressBar,
     entries: u32,
+    throughput: Option<ImportThroughput>,
 }
 
 impl DatImporterReporterSysOut {
     pub fn new() -> Self { 
+        DatImporterReporterSysOut::with_verbosity(Verbosity::default())
+    }
+
+    pub fn with_verbosity(verbosity: Verbosity) -> Self {
         let progress_bar = ProgressBar::new_spinner();
-        progress_bar.set_draw_target(ProgressDrawTarget::stdout());
-        Self { progress_bar, entries: 0 }
+        progress_bar.set_draw_target(draw_target(verbosity));
+        Self { progress_bar, entries: 0, throughput: None }
+    }
+
+    fn get_message(&self) -> String {
+        match self.throughput {
+            Some(throughput) => format!("Entries: #{} | Parsed: {:.0}/s / Written: {:.0}/s", self.entries, throughput.parse_rate(), throughput.write_rate()),
+            None => format!("Entries: #{}", self.entries),
+        }
     }
 }
 
@@ -34,7 +105,12 @@ impl DatImporterReporter for DatImporterReporterSysOut {
         self.entries += new_entries;
 
         self.progress_bar.set_position(current_bytes);
-        self.progress_bar.set_message(format!("Entries: #{}", self.entries));
+        self.progress_bar.set_message(self.get_message());
+    }
+
+    fn update_throughput(&mut self, throughput: ImportThroughput) {
+        self.throughput = Some(throughput);
+        self.progress_bar.set_message(self.get_message());
     }
 
     fn start_finish(&self) {
@@ -42,9 +118,18 @@ impl DatImporterReporter for DatImporterReporterSysOut {
         self.progress_bar.set_message("Finishing, hold on...");
     }
 
+    fn update_finish_progress(&mut self, done: u64, total: u64) {
+        if total > 0 {
+            self.progress_bar.set_message(format!("Finishing, updating parents {}/{}", done, total));
+        }
+    }
+
     fn finish(&self) {
-        self.progress_bar.set_message(format!("Entries: #{}", self.entries));
-        self.progress_bar.finish_with_message(format!("Total Entries #{}", self.entries));
+        self.progress_bar.set_message(self.get_message());
+        match self.throughput {
+            Some(throughput) => self.progress_bar.finish_with_message(format!("Total Entries #{} (parsed {:.0}/s, written {:.0}/s)", self.entries, throughput.parse_rate(), throughput.write_rate())),
+            None => self.progress_bar.finish_with_message(format!("Total Entries #{}", self.entries)),
+        }
     }
 }
 
@@ -57,21 +142,50 @@ pub struct ReportReporterSysOut {
     ignored: usize,
     error: usize,
     current_file: String,
+    sets: SetCounts,
+}
+
+/// The number of sets found in the scan with each status
+#[derive(Debug, Default)]
+struct SetCounts {
+    complete: usize,
+    fixeable: usize,
+    incomplete: usize,
+}
+
+impl SetCounts {
+    fn get_count(&mut self, status: SetStatus) -> &mut usize {
+        match status {
+            SetStatus::COMPLETE => &mut self.complete,
+            SetStatus::FIXEABLE => &mut self.fixeable,
+            SetStatus::INCOMPLETE => &mut self.incomplete,
+        }
+    }
+}
+
+impl Display for SetCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Sets: {} complete / {} fixable / {} incomplete", self.complete, self.fixeable, self.incomplete)
+    }
 }
 
 impl ReportReporterSysOut {
     pub fn new() -> Self {
-        let progress_bar = ProgressBar::new(!0);
+        ReportReporterSysOut::with_verbosity(Verbosity::default())
+    }
+
+    pub fn with_verbosity(verbosity: Verbosity) -> Self {
+        let progress_bar = ProgressBar::with_draw_target(!0, draw_target(verbosity));
         progress_bar.set_style(ProgressStyle::default_bar()
             .template("{prefix}\n{spinner:.green} [{elapsed_precise}] [{bar:40.green/blue}] {pos}% ({eta}) | {msg}")
             .progress_chars("#>-"));
         progress_bar.set_prefix("P: Processed / D: Directories / I: Ignored");
-        Self { progress_bar, total_files: !0, current_files: 0, new_files: 0, directories: 0, ignored: 0, error: 0, current_file: String::new() }
+        Self { progress_bar, total_files: !0, current_files: 0, new_files: 0, directories: 0, ignored: 0, error: 0, current_file: String::new(), sets: SetCounts::default() }
     }
 
     fn update_info_numbers(&mut self) {
         self.progress_bar.set_prefix(format!("P: Processed / D: Directories / I: Ignored | {}", self.current_file));
-        self.progress_bar.set_message(format!("P: {} / D: {} / I: {} / E: {}", self.new_files, self.directories, self.ignored, self.error));
+        self.progress_bar.set_message(format!("P: {} / D: {} / I: {} / E: {} | {}", self.new_files, self.directories, self.ignored, self.error, self.sets));
     }
 }
 
@@ -114,8 +228,53 @@ impl ReportReporter for ReportReporterSysOut {
         self.update_info_numbers();
     }
 
+    fn set_matched(&mut self, _set_name: &str) {}
+
+    fn set_completed(&mut self, _set_name: &str, status: SetStatus, previous: Option<SetStatus>) {
+        if let Some(previous) = previous {
+            *self.sets.get_count(previous) -= 1;
+        }
+        *self.sets.get_count(status) += 1;
+        self.update_info_numbers();
+    }
+
     fn finish(&mut self) {
         self.progress_bar.set_prefix("P: Processed / D: Directories / I: Ignored / E: Errors | FINISHED");
-        self.progress_bar.finish_with_message(format!("P: {} / D: {} / I: {} / E: {}", self.new_files, self.directories, self.ignored, self.error));
+        self.progress_bar.finish_with_message(format!("P: {} / D: {} / I: {} / E: {} | {}", self.new_files, self.directories, self.ignored, self.error, self.sets));
+    }
+}
+/// Shows the progress of a report being saved or loaded, a new bar for each report
+#[derive(Debug)]
+pub struct ReportFileReporterSysOut {
+    verbosity: Verbosity,
+    progress_bar: Option<ProgressBar>,
+}
+
+impl ReportFileReporterSysOut {
+    pub fn with_verbosity(verbosity: Verbosity) -> Self {
+        Self { verbosity, progress_bar: None }
+    }
+}
+
+impl ReportFileReporter for ReportFileReporterSysOut {
+    fn start(&mut self, total_bytes: u64, saving: bool) {
+        let progress_bar = ProgressBar::with_draw_target(total_bytes, draw_target(self.verbosity));
+        progress_bar.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.green/blue}] {bytes}/{total_bytes} ({eta}) | {msg}")
+            .progress_chars("#>-"));
+        progress_bar.set_message(if saving { "Saving the report" } else { "Loading the report" });
+        self.progress_bar = Some(progress_bar);
+    }
+
+    fn update_position(&mut self, bytes: u64) {
+        if let Some(progress_bar) = &self.progress_bar {
+            progress_bar.set_position(bytes);
+        }
     }
-}
\ No newline at end of file
+
+    fn finish(&mut self) {
+        if let Some(progress_bar) = self.progress_bar.take() {
+            progress_bar.finish_and_clear();
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Key bindings shared by the interactive frontends. The frontends translate their key events into a `Key`
+//! and ask the `KeyBindings` for the action, so the keys can be changed in a config file.
+use std::{collections::BTreeMap, fmt::Display, fs, iter::Peekable, path::Path, str::{Chars, FromStr}};
+
+use anyhow::Result;
+
+use crate::{err, error::RomstError};
+
+/// The key bindings config file, next to the executable. Without it the default bindings are used
+pub const DEFAULT_KEYBINDINGS_FILE: &str = "keybindings.toml";
+/// The only table of the config file
+const KEYS_TABLE: &str = "keys";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyAction {
+    Quit,
+    Help,
+    Filter,
+    NextGrouping,
+    EditTags,
+    CompareReports,
+    NextRegression,
+    PreviousRegression,
+}
+
+const ACTIONS: [KeyAction; 8] = [
+    KeyAction::Quit, KeyAction::Help, KeyAction::Filter, KeyAction::NextGrouping, KeyAction::EditTags,
+    KeyAction::CompareReports, KeyAction::NextRegression, KeyAction::PreviousRegression,
+];
+
+impl KeyAction {
+    /// The name used in the config file
+    pub fn get_key(&self) -> &'static str {
+        match self {
+            KeyAction::Quit => "quit",
+            KeyAction::Help => "help",
+            KeyAction::Filter => "filter",
+            KeyAction::NextGrouping => "next_grouping",
+            KeyAction::EditTags => "edit_tags",
+            KeyAction::CompareReports => "compare_reports",
+            KeyAction::NextRegression => "next_regression",
+            KeyAction::PreviousRegression => "previous_regression",
+        }
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            KeyAction::Quit => "Quit",
+            KeyAction::Help => "Show the key bindings",
+            KeyAction::Filter => "Filter the sets",
+            KeyAction::NextGrouping => "Change how the sets are grouped",
+            KeyAction::EditTags => "Edit the tags and note of the set",
+            KeyAction::CompareReports => "Compare a saved report with another one",
+            KeyAction::NextRegression => "Go to the next regressed set",
+            KeyAction::PreviousRegression => "Go to the previous regressed set",
+        }
+    }
+
+    fn get_default_keys(&self) -> Vec<Key> {
+        match self {
+            KeyAction::Quit => vec![Key::Esc],
+            KeyAction::Help => vec![Key::Char('?'), Key::F(1)],
+            KeyAction::Filter => vec![Key::Char('/')],
+            KeyAction::NextGrouping => vec![Key::Ctrl('g')],
+            KeyAction::EditTags => vec![Key::Ctrl('t')],
+            KeyAction::CompareReports => vec![Key::Ctrl('r')],
+            KeyAction::NextRegression => vec![Key::Ctrl('n')],
+            KeyAction::PreviousRegression => vec![Key::Ctrl('p')],
+        }
+    }
+}
+
+impl FromStr for KeyAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match ACTIONS.iter().find(|action| action.get_key() == s) {
+            Some(action) => Ok(*action),
+            None => err!(RomstError::GenericError { message: format!("Unknown action `{}`", s) }),
+        }
+    }
+}
+
+/// A key press, written in the config as the character, `ctrl+<char>`, `f<number>` or the name of the key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    F(u8),
+    Esc,
+    Enter,
+    Tab,
+    Backspace,
+}
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Key::Char(c));
+        }
+
+        let lowercase = s.to_lowercase();
+        let key = match lowercase.as_str() {
+            "esc" => Some(Key::Esc),
+            "enter" => Some(Key::Enter),
+            "tab" => Some(Key::Tab),
+            "backspace" => Some(Key::Backspace),
+            "space" => Some(Key::Char(' ')),
+            name => {
+                if let Some(c) = name.strip_prefix("ctrl+").filter(|c| c.chars().count() == 1) {
+                    c.chars().next().map(Key::Ctrl)
+                } else {
+                    name.strip_prefix('f')
+                        .and_then(|n| n.parse::<u8>().ok())
+                        .filter(|n| (1..=12).contains(n))
+                        .map(Key::F)
+                }
+            }
+        };
+
+        match key {
+            Some(key) => Ok(key),
+            None => err!(RomstError::GenericError { message: format!("Unknown key `{}`", s) }),
+        }
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Char(' ') => write!(f, "space"),
+            Key::Char(c) => write!(f, "{}", c),
+            Key::Ctrl(c) => write!(f, "ctrl+{}", c),
+            Key::F(n) => write!(f, "f{}", n),
+            Key::Esc => write!(f, "esc"),
+            Key::Enter => write!(f, "enter"),
+            Key::Tab => write!(f, "tab"),
+            Key::Backspace => write!(f, "backspace"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    bindings: BTreeMap<KeyAction, Vec<Key>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let bindings = ACTIONS.iter().map(|action| (*action, action.get_default_keys())).collect();
+        Self { bindings }
+    }
+}
+
+impl KeyBindings {
+    /// Loads the bindings from the config file, the actions not in the file keep the default keys
+    pub fn load(config_file: impl AsRef<Path>) -> Result<Self> {
+        let config_file = config_file.as_ref();
+        if !config_file.exists() {
+            return Ok(KeyBindings::default());
+        }
+
+        match fs::read_to_string(config_file) {
+            Ok(config) => KeyBindings::from_config(&config),
+            Err(e) => err!(RomstError::GenericError { message: format!("Error reading the key bindings `{}`: {}", config_file.to_string_lossy(), e) }),
+        }
+    }
+
+    /// Reads the `[keys]` table of a TOML config, with an action per line and either a key or an array of keys:
+    /// `filter = "/"` or `help = ["?", "f1"]`. The keys are basic or literal strings, and the lines can end in a comment
+    pub fn from_config(config: &str) -> Result<Self> {
+        let mut key_bindings = KeyBindings::default();
+        let mut in_keys_table = false;
+        for (number, line) in config.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(table) = line.strip_prefix('[').and_then(|table| table.strip_suffix(']')) {
+                in_keys_table = table.trim() == KEYS_TABLE;
+                continue;
+            }
+            if !in_keys_table {
+                continue;
+            }
+
+            let parse_error = |message: String| err!(RomstError::ParsingError { message: format!("Line {} of the key bindings: {}", number + 1, message) });
+            let (action, keys) = match line.split_once('=') {
+                Some((action, keys)) => (action.trim(), keys.trim()),
+                None => return parse_error(format!("expected `action = \"key\"`, found `{}`", line)),
+            };
+            let action = match action.parse::<KeyAction>() {
+                Ok(action) => action,
+                Err(e) => return parse_error(e.to_string()),
+            };
+            let keys = match parse_keys(keys) {
+                Ok(keys) => keys,
+                Err(e) => return parse_error(e.to_string()),
+            };
+            key_bindings.bindings.insert(action, keys);
+        }
+
+        key_bindings.check_conflicts()?;
+        Ok(key_bindings)
+    }
+
+    fn check_conflicts(&self) -> Result<()> {
+        let mut actions_by_key = BTreeMap::new();
+        for (action, keys) in &self.bindings {
+            for key in keys {
+                if let Some(other) = actions_by_key.insert(*key, *action) {
+                    return err!(RomstError::GenericError { message: format!("The key `{}` is bound to both `{}` and `{}`", key, other.get_key(), action.get_key()) });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_action(&self, key: &Key) -> Option<KeyAction> {
+        self.bindings.iter().find(|(_action, keys)| keys.contains(key)).map(|(action, _keys)| *action)
+    }
+
+    pub fn get_keys(&self, action: KeyAction) -> &[Key] {
+        self.bindings.get(&action).map(|keys| keys.as_slice()).unwrap_or_default()
+    }
+
+    /// The actions with their keys, in the order they are shown in the help
+    pub fn get_bindings(&self) -> Vec<(KeyAction, &[Key])> {
+        ACTIONS.iter().map(|action| (*action, self.get_keys(*action))).collect()
+    }
+}
+
+/// The line without its comment, a `#` inside a quoted key doesn't start one
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (position, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => return &line[..position],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_keys(value: &str) -> Result<Vec<Key>> {
+    let mut chars = value.chars().peekable();
+    let mut keys = vec![];
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'[') {
+        chars.next();
+        loop {
+            skip_whitespace(&mut chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                break;
+            }
+            keys.push(parse_string(&mut chars, value)?.parse::<Key>()?);
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => {}
+                Some(']') => break,
+                _ => return err!(RomstError::GenericError { message: format!("expected `,` or `]` in `{}`", value) }),
+            }
+        }
+    } else {
+        keys.push(parse_string(&mut chars, value)?.parse::<Key>()?);
+    }
+
+    skip_whitespace(&mut chars);
+    if chars.peek().is_some() {
+        return err!(RomstError::GenericError { message: format!("unexpected `{}` after the keys", chars.collect::<String>()) });
+    }
+    Ok(keys)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+        chars.next();
+    }
+}
+
+/// A basic string, with escapes, or a literal string
+fn parse_string(chars: &mut Peekable<Chars>, value: &str) -> Result<String> {
+    let quote = match chars.next() {
+        Some(quote) if quote == '"' || quote == '\'' => quote,
+        _ => return err!(RomstError::GenericError { message: format!("the keys of `{}` must be quoted", value) }),
+    };
+
+    let mut string = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == quote => return Ok(string),
+            '\\' if quote == '"' => match chars.next() {
+                Some('"') => string.push('"'),
+                Some('\\') => string.push('\\'),
+                Some('t') => string.push('\t'),
+                other => return err!(RomstError::GenericError { message: format!("unsupported escape `\\{}` in `{}`", other.map(String::from).unwrap_or_default(), value) }),
+            },
+            c => string.push(c),
+        }
+    }
+    err!(RomstError::GenericError { message: format!("the key in `{}` is not closed", value) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_key_bindings() -> Result<()> {
+        let config = "# romst keys\n[other]\nquit = \"q\"\n\n[keys]\nquit = \"ctrl+q\"\nhelp = [\"h\", \"f1\"]\n";
+        let key_bindings = KeyBindings::from_config(config)?;
+
+        assert_eq!(Some(KeyAction::Quit), key_bindings.get_action(&Key::Ctrl('q')));
+        assert_eq!(None, key_bindings.get_action(&Key::Esc));
+        assert_eq!(&[Key::Char('h'), Key::F(1)], key_bindings.get_keys(KeyAction::Help));
+        assert_eq!(Some(KeyAction::Filter), key_bindings.get_action(&Key::Char('/')));
+        assert_eq!(Some(KeyAction::NextRegression), key_bindings.get_action(&Key::Ctrl('n')));
+        assert_eq!(KeyBindings::default(), KeyBindings::load("testdata/none.toml")?);
+
+        assert!(KeyBindings::from_config("[keys]\nquit = \"/\"").is_err());
+        assert!(KeyBindings::from_config("[keys]\njump = \"j\"").is_err());
+        assert!(KeyBindings::from_config("[keys]\nquit = q").is_err());
+        assert!(KeyBindings::from_config("[keys]\nquit = \"ctrl+\"").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_comments_and_quoted_keys() -> Result<()> {
+        let config = "[keys] # the bindings\nquit = \"q\" # leave\nhelp = [\",\", '#', \"\\\"\",] # all of them\nfilter = [ ]\n";
+        let key_bindings = KeyBindings::from_config(config)?;
+
+        assert_eq!(&[Key::Char('q')], key_bindings.get_keys(KeyAction::Quit));
+        assert_eq!(&[Key::Char(','), Key::Char('#'), Key::Char('"')], key_bindings.get_keys(KeyAction::Help));
+        assert!(key_bindings.get_keys(KeyAction::Filter).is_empty());
+
+        assert!(KeyBindings::from_config("[keys]\nquit = \"q").is_err());
+        assert!(KeyBindings::from_config("[keys]\nquit = \"q\" \"w\"").is_err());
+        assert!(KeyBindings::from_config("[keys]\nhelp = [\"h\" \"f1\"]").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,465 @@
+//! UI agnostic state of the interactive frontends. The UIs send `AppMessage`s to update the `AppState`
+//! and render what it holds, so they don't need to know how the data is loaded.
+pub mod keys;
+pub mod rom_usage;
+pub mod set_details;
+
+use std::{fs, path::{Path, PathBuf}};
+
+use anyhow::Result;
+
+use crate::{RomsetMode, Romst, data::{models::set::GameSet, reader::{DataReader, sqlite::{BrowseGrouping, DBReader, DBReport, DbHandle, LastCheck, SetLastChecks, SetUserData, SetUserEntry}}, reporter::{diff::ReportDiff, scan_report::{ScanReport, SetReport}}}, err, error::RomstError};
+
+/// Where the UIs look for the databases, next to the executable
+pub const DEFAULT_DB_DIRECTORY: &str = "db";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DBEntry {
+    pub name: String,
+    pub path: String,
+}
+
+/// An entry of the sets list, the sets of a group are only loaded when it's expanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetListItem {
+    Group { name: Option<String>, sets: u32, expanded: bool },
+    Set { name: String, description: String, grouped: bool },
+}
+
+impl SetListItem {
+    pub fn is_group(&self, group: &Option<String>) -> bool {
+        matches!(self, SetListItem::Group { name, .. } if name == group)
+    }
+}
+
+/// The details of the selected set, they are still loading while a `SetDetailsLoader` gets them
+#[derive(Debug)]
+pub enum SetDetails {
+    None,
+    Loading(String),
+    Loaded(Box<GameSet>),
+}
+
+#[derive(Debug, Clone)]
+pub enum AppMessage {
+    /// Reads again the databases in the directory
+    RefreshDBs,
+    /// Loads the info of a database without opening it
+    HighlightDB(String),
+    OpenDB(String),
+    CloseDB,
+    SetRomMode(RomsetMode),
+    SetFilter(String),
+    SetGrouping(Option<BrowseGrouping>),
+    /// Cycles the grouping between driver, first letter and no grouping
+    NextGrouping,
+    /// Expands or collapses a group of the sets list
+    ToggleGroup(Option<String>),
+    SelectSet(String),
+    /// Selects a set without loading it, so the UI can show it's loading
+    LoadingSet(String),
+    /// A set loaded in the background, it's ignored if another set was selected since
+    SetLoaded(Box<GameSet>),
+    /// Replaces the tags and the note of a set, they are written in the database
+    SetUserEntry(String, SetUserEntry),
+    LoadReport(String),
+    ClearReport,
+    /// Compares a saved report with a newer one, or with the loaded report if there's no newer one
+    CompareReports(String, Option<String>),
+    ClearComparison,
+}
+
+pub struct AppState {
+    db_directory: PathBuf,
+    dbs: Vec<DBEntry>,
+    highlighted_db: Option<DBReport>,
+    selected_db: Option<DbHandle>,
+    rom_mode: RomsetMode,
+    filter: String,
+    grouping: Option<BrowseGrouping>,
+    set_list: Vec<SetListItem>,
+    selected_set: SetDetails,
+    user_data: SetUserData,
+    last_checks: SetLastChecks,
+    report: Option<ScanReport>,
+    comparison: Option<ReportDiff>,
+}
+
+impl AppState {
+    pub fn new<P>(db_directory: P) -> Self where P: AsRef<Path> {
+        Self {
+            db_directory: db_directory.as_ref().to_path_buf(),
+            dbs: vec![],
+            highlighted_db: None,
+            selected_db: None,
+            rom_mode: RomsetMode::default(),
+            filter: String::new(),
+            grouping: Some(BrowseGrouping::Driver),
+            set_list: vec![],
+            selected_set: SetDetails::None,
+            user_data: SetUserData::default(),
+            last_checks: SetLastChecks::default(),
+            report: None,
+            comparison: None,
+        }
+    }
+
+    pub fn update(&mut self, message: AppMessage) -> Result<()> {
+        match message {
+            AppMessage::RefreshDBs => self.refresh_dbs(),
+            AppMessage::HighlightDB(db_file) => {
+                self.highlighted_db = None;
+                self.highlighted_db = Some(Romst::get_db_info(db_file)?);
+                Ok(())
+            },
+            AppMessage::OpenDB(db_file) => {
+                let db_handle = Romst::open(&db_file)?;
+                self.user_data = db_handle.get_reader().get_user_data()?;
+                self.last_checks = db_handle.get_reader().get_last_checks()?;
+                self.last_checks.retain_current();
+                self.selected_db = Some(db_handle);
+                self.selected_set = SetDetails::None;
+                self.reload_set_list()
+            },
+            AppMessage::CloseDB => {
+                self.selected_db = None;
+                self.selected_set = SetDetails::None;
+                self.user_data = SetUserData::default();
+                self.last_checks = SetLastChecks::default();
+                self.set_list.clear();
+                Ok(())
+            },
+            AppMessage::SetRomMode(rom_mode) => {
+                self.rom_mode = rom_mode;
+                self.reload_set_list()
+            },
+            AppMessage::SetFilter(filter) => {
+                self.filter = filter;
+                self.reload_set_list()
+            },
+            AppMessage::SetGrouping(grouping) => {
+                self.grouping = grouping;
+                self.reload_set_list()
+            },
+            AppMessage::NextGrouping => {
+                self.grouping = match self.grouping {
+                    Some(BrowseGrouping::Driver) => Some(BrowseGrouping::Letter),
+                    Some(BrowseGrouping::Letter) => None,
+                    _ => Some(BrowseGrouping::Driver),
+                };
+                self.reload_set_list()
+            },
+            AppMessage::ToggleGroup(group) => self.toggle_group(group),
+            AppMessage::SelectSet(set_name) => {
+                let set_info = self.get_db_reader()?.get_set_info(set_name, self.rom_mode)?;
+                self.selected_set = SetDetails::Loaded(Box::new(set_info));
+                Ok(())
+            },
+            AppMessage::LoadingSet(set_name) => {
+                self.get_db_reader()?;
+                self.selected_set = SetDetails::Loading(set_name);
+                Ok(())
+            },
+            AppMessage::SetLoaded(set_info) => {
+                if matches!(&self.selected_set, SetDetails::Loading(set_name) if *set_name == set_info.game.name) {
+                    self.selected_set = SetDetails::Loaded(set_info);
+                }
+                Ok(())
+            },
+            AppMessage::SetUserEntry(set_name, entry) => self.write_user_entry(&set_name, entry),
+            AppMessage::LoadReport(report_file) => {
+                self.report = Some(Romst::load_report(report_file)?);
+                Ok(())
+            },
+            AppMessage::ClearReport => {
+                self.report = None;
+                Ok(())
+            },
+            AppMessage::CompareReports(before_file, after_file) => {
+                self.comparison = None;
+                let before = Romst::load_report(before_file)?;
+                let comparison = match (after_file, &self.report) {
+                    (Some(after_file), _) => ReportDiff::new(&before, &Romst::load_report(after_file)?),
+                    (None, Some(report)) => ReportDiff::new(&before, report),
+                    (None, None) => return err!(RomstError::GenericError { message: "There is no report loaded to compare with".to_string() }),
+                };
+                self.comparison = Some(comparison);
+                Ok(())
+            },
+            AppMessage::ClearComparison => {
+                self.comparison = None;
+                Ok(())
+            },
+        }
+    }
+
+    pub fn get_dbs(&self) -> &Vec<DBEntry> {
+        &self.dbs
+    }
+
+    pub fn get_highlighted_db(&self) -> Option<&DBReport> {
+        self.highlighted_db.as_ref()
+    }
+
+    pub fn get_selected_db(&self) -> Option<&str> {
+        self.selected_db.as_ref().map(|db_handle| db_handle.path.as_str())
+    }
+
+    /// The open database, with the metadata to show in the headers
+    pub fn get_db_handle(&self) -> Option<&DbHandle> {
+        self.selected_db.as_ref()
+    }
+
+    pub fn get_rom_mode(&self) -> RomsetMode {
+        self.rom_mode
+    }
+
+    pub fn get_filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn get_grouping(&self) -> Option<BrowseGrouping> {
+        self.grouping
+    }
+
+    pub fn get_set_list(&self) -> &Vec<SetListItem> {
+        &self.set_list
+    }
+
+    pub fn get_selected_set(&self) -> Option<&GameSet> {
+        match &self.selected_set {
+            SetDetails::Loaded(set_info) => Some(set_info),
+            _ => None,
+        }
+    }
+
+    pub fn get_set_details(&self) -> &SetDetails {
+        &self.selected_set
+    }
+
+    /// The tags and the note of the set, if it has any
+    pub fn get_set_user_entry(&self, set_name: &str) -> Option<&SetUserEntry> {
+        self.user_data.sets.get(set_name)
+    }
+
+    /// The status of the set in the last check, so it's shown without checking it again
+    pub fn get_set_last_check(&self, set_name: &str) -> Option<&LastCheck> {
+        self.last_checks.get_last_check(set_name)
+    }
+
+    pub fn get_report(&self) -> Option<&ScanReport> {
+        self.report.as_ref()
+    }
+
+    /// The sets whose status changed between the compared reports
+    pub fn get_comparison(&self) -> Option<&ReportDiff> {
+        self.comparison.as_ref()
+    }
+
+    /// The report of the selected set, if there is a report loaded
+    pub fn get_selected_set_report(&self) -> Option<&SetReport> {
+        match (&self.report, self.get_selected_set()) {
+            (Some(report), Some(set)) => report.sets.get(&set.game.name),
+            _ => None,
+        }
+    }
+
+    fn get_db_reader(&self) -> Result<&DBReader> {
+        match &self.selected_db {
+            Some(db_handle) => Ok(db_handle.get_reader()),
+            None => err!(RomstError::GenericError { message: "There is no DB open".to_string() }),
+        }
+    }
+
+    fn write_user_entry(&mut self, set_name: &str, entry: SetUserEntry) -> Result<()> {
+        let db_file = match self.get_selected_db() {
+            Some(db_file) => db_file.to_owned(),
+            None => return err!(RomstError::GenericError { message: "There is no DB open".to_string() }),
+        };
+        Romst::remove_set_tags(db_file.as_str(), set_name, &[])?;
+        Romst::add_set_tags(db_file.as_str(), set_name, &entry.tags.into_iter().collect::<Vec<_>>())?;
+        self.user_data = Romst::set_set_note(db_file.as_str(), set_name, entry.note.as_deref())?;
+        Ok(())
+    }
+
+    fn refresh_dbs(&mut self) -> Result<()> {
+        let db_path = self.db_directory.as_path();
+
+        if db_path.is_file() {
+            fs::remove_file(db_path)?;
+        };
+
+        if !db_path.exists() {
+            fs::create_dir(db_path)?;
+        };
+
+        let mut dbs = db_path.read_dir()?.filter_map(|file| {
+            let file = file.ok()?;
+            let path = file.path();
+            if path.is_file() {
+                Some(DBEntry {
+                    name: file.file_name().to_str()?.to_string(),
+                    path: path.to_str()?.to_string(),
+                })
+            } else {
+                None
+            }
+        }).collect::<Vec<_>>();
+        dbs.sort_by(|a, b| a.name.cmp(&b.name));
+        self.dbs = dbs;
+
+        Ok(())
+    }
+
+    /// Loads the sets list collapsing all the groups, a filter shows the matching sets without grouping
+    fn reload_set_list(&mut self) -> Result<()> {
+        let db_reader = match &self.selected_db {
+            Some(db_handle) => db_handle.get_reader(),
+            None => return Ok(()),
+        };
+        self.set_list = match self.grouping {
+            Some(grouping) if self.filter.is_empty() => {
+                db_reader.get_sets_grouped_by(grouping, self.rom_mode)?.into_iter().map(|group| {
+                    SetListItem::Group { name: group.name, sets: group.sets, expanded: false }
+                }).collect()
+            },
+            _ => {
+                db_reader.get_game_list(self.rom_mode)?.into_iter().filter(|set| {
+                    set.0.contains(&self.filter) || set.1.contains(&self.filter)
+                }).map(|set| {
+                    SetListItem::Set { name: set.0, description: set.1, grouped: false }
+                }).collect()
+            }
+        };
+
+        Ok(())
+    }
+
+    fn toggle_group(&mut self, group: Option<String>) -> Result<()> {
+        let grouping = match self.grouping {
+            Some(grouping) => grouping,
+            None => return Ok(()),
+        };
+        let index = match self.set_list.iter().position(|item| item.is_group(&group)) {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let sets = match &self.set_list[index] {
+            SetListItem::Group { expanded: true, .. } => vec![],
+            _ => self.get_db_reader()?.get_sets_in_group(grouping, group.as_deref(), self.rom_mode)?,
+        };
+
+        if let SetListItem::Group { expanded, .. } = &mut self.set_list[index] {
+            *expanded = !*expanded;
+        }
+        while let Some(SetListItem::Set { grouped: true, .. }) = self.set_list.get(index + 1) {
+            self.set_list.remove(index + 1);
+        }
+        let sets = sets.into_iter().map(|set| SetListItem::Set { name: set.0, description: set.1, grouped: true });
+        self.set_list.splice(index + 1..index + 1, sets);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use crate::{ImportOptions, data::models::game::Game, sysout::DatImporterReporterSysOut};
+    use super::*;
+
+    #[test]
+    fn updates_the_state_from_messages() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_app_state_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        Romst::import_dat("testdata/test.dat", &db_file, ImportOptions { overwrite: true, ..Default::default() }, None::<DatImporterReporterSysOut>)?;
+
+        let mut state = AppState::new(&dir);
+        state.update(AppMessage::RefreshDBs)?;
+        assert_eq!(vec![DBEntry { name: "test.rst".to_string(), path: db_file.clone() }], *state.get_dbs());
+        state.update(AppMessage::HighlightDB(db_file.clone()))?;
+        assert_eq!(10, state.get_highlighted_db().unwrap().games);
+        assert!(state.update(AppMessage::SelectSet("game1".to_string())).is_err());
+
+        state.update(AppMessage::OpenDB(db_file.clone()))?;
+        assert_eq!(Some(db_file.as_str()), state.get_selected_db());
+        let db_handle = state.get_db_handle().unwrap();
+        assert_eq!(10, db_handle.games);
+        assert_eq!(Some(db_file.as_str()), db_handle.get_reader().get_path());
+        assert_eq!(Some(1), db_handle.schema_version);
+        assert_eq!("MAME 0.1 (Split) - 10 sets", db_handle.get_title(RomsetMode::Split));
+        let driver = Some("gamelib.cpp".to_string());
+        assert!(state.get_set_list().contains(&SetListItem::Group { name: driver.clone(), sets: 3, expanded: false }));
+
+        state.update(AppMessage::ToggleGroup(driver.clone()))?;
+        let index = state.get_set_list().iter().position(|item| item.is_group(&driver)).unwrap();
+        let sets = state.get_set_list()[index + 1..index + 4].iter().map(|item| match item {
+            SetListItem::Set { name, grouped: true, .. } => name.as_str(),
+            _ => "",
+        }).collect::<Vec<_>>();
+        assert_eq!(vec!["game0", "game1", "game1a"], sets);
+        let len = state.get_set_list().len();
+        state.update(AppMessage::ToggleGroup(driver.clone()))?;
+        assert_eq!(len - 3, state.get_set_list().len());
+
+        state.update(AppMessage::SetFilter("game1".to_string()))?;
+        assert!(state.get_set_list().iter().all(|item| matches!(item, SetListItem::Set { name, grouped: false, .. } if name.contains("game1"))));
+        state.update(AppMessage::NextGrouping)?;
+        assert_eq!(Some(BrowseGrouping::Letter), state.get_grouping());
+
+        state.update(AppMessage::SelectSet("game1a".to_string()))?;
+        assert_eq!("game1a", state.get_selected_set().unwrap().game.name);
+        assert!(state.get_selected_set_report().is_none());
+
+        state.update(AppMessage::LoadingSet("game2".to_string()))?;
+        assert!(matches!(state.get_set_details(), SetDetails::Loading(set_name) if set_name == "game2"));
+        assert!(state.get_selected_set().is_none());
+        let stale_set = state.get_db_reader()?.get_set_info("game1", RomsetMode::default())?;
+        state.update(AppMessage::SetLoaded(Box::new(stale_set)))?;
+        assert!(state.get_selected_set().is_none());
+        let set_info = state.get_db_reader()?.get_set_info("game2", RomsetMode::default())?;
+        state.update(AppMessage::SetLoaded(Box::new(set_info)))?;
+        assert_eq!("game2", state.get_selected_set().unwrap().game.name);
+
+        let entry = SetUserEntry { tags: vec!["favorite".to_string()].into_iter().collect(), note: Some("Needs a redump".to_string()) };
+        state.update(AppMessage::SetUserEntry("game2".to_string(), entry.clone()))?;
+        assert_eq!(Some(&entry), state.get_set_user_entry("game2"));
+        assert!(state.update(AppMessage::SetUserEntry("nogame".to_string(), entry)).is_err());
+
+        state.update(AppMessage::CloseDB)?;
+        assert!(state.get_set_list().is_empty());
+        assert!(state.get_set_user_entry("game2").is_none());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn compares_the_reports() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_app_comparison_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let before_file = dir.join("before.rpt").to_string_lossy().to_string();
+        let after_file = dir.join("after.rpt").to_string_lossy().to_string();
+        let mut before = ScanReport::new(Some("/roms".to_string()), RomsetMode::NonMerged);
+        before.reference_with_game(Game::new("game1".to_string()));
+        Romst::save_report(&before_file, before)?;
+        Romst::save_report(&after_file, ScanReport::new(Some("/roms".to_string()), RomsetMode::NonMerged))?;
+
+        let mut state = AppState::new(&dir);
+        assert!(state.update(AppMessage::CompareReports(before_file.clone(), None)).is_err());
+        state.update(AppMessage::CompareReports(before_file.clone(), Some(after_file.clone())))?;
+        let regressions = state.get_comparison().unwrap().get_regressions();
+        assert_eq!(vec!["game1"], regressions.iter().map(|set| set.name.as_str()).collect::<Vec<_>>());
+
+        state.update(AppMessage::LoadReport(before_file.clone()))?;
+        state.update(AppMessage::CompareReports(before_file, None))?;
+        assert!(state.get_comparison().unwrap().sets.is_empty());
+        state.update(AppMessage::ClearComparison)?;
+        assert!(state.get_comparison().is_none());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+//! The details of a rom selected in a set, with the other sets using it. The search opens its own connection to the
+//! database so it can run in a background thread while the UI keeps browsing.
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use crate::{RomsetMode, Romst, data::models::file::DataFile};
+
+/// A rom of a set and the names it has in each of the other sets using it
+#[derive(Debug, Clone)]
+pub struct RomUsage {
+    pub set_name: String,
+    pub rom: DataFile,
+    pub used_by: BTreeMap<String, Vec<String>>,
+}
+
+impl RomUsage {
+    /// Searches the sets using the rom, a rom with no dump has nothing to search and is used by no other set
+    pub fn load(db_file: &str, set_name: &str, rom: DataFile, rom_mode: RomsetMode) -> Result<Self> {
+        let mut used_by = BTreeMap::new();
+        if rom.status.as_deref() != Some("nodump") {
+            let rom_search = Romst::get_rom_usage(db_file, set_name, rom.name.as_str(), rom_mode)?;
+            for (other_set, set_content) in rom_search.set_results.iter().filter(|(other_set, _)| *other_set != set_name) {
+                let mut names = set_content.get_roms_included().iter().map(|entry| entry.file.name.to_owned()).collect::<Vec<_>>();
+                names.sort();
+                used_by.insert(other_set.to_owned(), names);
+            }
+        }
+
+        Ok(Self { set_name: set_name.to_string(), rom, used_by })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+    use crate::{ImportOptions, data::reader::DataReader, sysout::DatImporterReporterSysOut};
+    use super::*;
+
+    #[test]
+    fn loads_the_other_sets_using_the_rom() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_rom_usage_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        Romst::import_dat("testdata/test.dat", &db_file, ImportOptions { overwrite: true, ..Default::default() }, None::<DatImporterReporterSysOut>)?;
+
+        let game_set = Romst::get_data_reader(&db_file)?.get_set_info("game1", RomsetMode::NonMerged)?;
+        let rom = game_set.roms.iter().find(|rom| rom.name == "rom1.trom").unwrap().to_owned();
+
+        let usage = RomUsage::load(&db_file, "game1", rom.clone(), RomsetMode::NonMerged)?;
+        assert_eq!(rom, usage.rom);
+        assert_eq!(Some(&vec!["rom1.trom".to_string()]), usage.used_by.get("game1a"));
+        assert!(!usage.used_by.contains_key("game1"));
+
+        let usage = RomUsage::load(&db_file, "game1", rom, RomsetMode::Merged)?;
+        assert!(usage.used_by.is_empty());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! Loads the details of the selected set in a background worker with its own connection to the database. Moving
+//! through the sets list asks for many sets in a row, the worker only loads the last one asked, so the UI never waits
+//! for the queries of the sets it already left. The last sets loaded are kept, going back to them doesn't query again.
+use std::{sync::mpsc::{self, Receiver, Sender}, thread};
+
+use anyhow::Result;
+
+use crate::{RomsetMode, Romst, data::{models::set::GameSet, reader::{DataReader, cached::CachedDataReader, sqlite::DBReader}}};
+
+#[derive(Debug, Clone)]
+pub struct SetDetailsRequest {
+    pub db_file: String,
+    pub set_name: String,
+    pub rom_mode: RomsetMode,
+}
+
+pub struct SetDetailsLoader {
+    requests: Sender<SetDetailsRequest>,
+}
+
+impl SetDetailsLoader {
+    /// Starts the worker, it calls `on_loaded` with the latest set asked each time it's loaded, and stops when the loader is dropped
+    pub fn new<F>(on_loaded: F) -> Self where F: FnMut(SetDetailsRequest, Result<GameSet>) + Send + 'static {
+        let (requests, receiver) = mpsc::channel();
+        thread::spawn(move || load_sets(receiver, on_loaded));
+        Self { requests }
+    }
+
+    /// Asks for the details of a set, the requests still waiting are discarded
+    pub fn request(&self, request: SetDetailsRequest) {
+        // The worker only stops receiving when the loader is dropped
+        let _ = self.requests.send(request);
+    }
+}
+
+fn load_sets<F>(receiver: Receiver<SetDetailsRequest>, mut on_loaded: F) where F: FnMut(SetDetailsRequest, Result<GameSet>) {
+    let mut db_reader = None;
+    let mut next = receiver.recv().ok();
+    while let Some(request) = next {
+        let request = receiver.try_iter().last().unwrap_or(request);
+        let result = load_set(&mut db_reader, &request);
+        // If another set was asked during the query this one is stale already
+        next = receiver.try_iter().last();
+        if next.is_none() {
+            on_loaded(request, result);
+            next = receiver.recv().ok();
+        }
+    }
+}
+
+/// Reuses the reader and the sets it keeps while the requests are for the same database
+fn load_set(db_reader: &mut Option<(String, CachedDataReader<DBReader>)>, request: &SetDetailsRequest) -> Result<GameSet> {
+    if !matches!(db_reader, Some((db_file, _)) if *db_file == request.db_file) {
+        *db_reader = Some((request.db_file.clone(), CachedDataReader::new(Romst::get_data_reader(&request.db_file)?)));
+    }
+    match db_reader {
+        Some((_, reader)) => reader.get_set_info(&request.set_name, request.rom_mode),
+        None => unreachable!("The reader is opened above"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, time::Duration};
+    use crate::{ImportOptions, sysout::DatImporterReporterSysOut};
+    use super::*;
+
+    #[test]
+    fn loads_the_latest_set_asked() -> Result<()> {
+        let dir = env::temp_dir().join(format!("romst_set_details_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let db_file = dir.join("test.rst").to_string_lossy().to_string();
+        Romst::import_dat("testdata/test.dat", &db_file, ImportOptions { overwrite: true, ..Default::default() }, None::<DatImporterReporterSysOut>)?;
+
+        let (sender, receiver) = mpsc::channel();
+        let loader = SetDetailsLoader::new(move |request, result: Result<GameSet>| {
+            sender.send((request.set_name, result.map(|set| set.game.name))).unwrap();
+        });
+        let request = |set_name: &str| SetDetailsRequest { db_file: db_file.clone(), set_name: set_name.to_string(), rom_mode: RomsetMode::Merged };
+
+        loader.request(request("game1"));
+        let (set_name, loaded) = receiver.recv_timeout(Duration::from_secs(10))?;
+        assert_eq!("game1", set_name);
+        assert_eq!("game1", loaded?);
+
+        // The stale sets may be skipped, but the last one is always loaded
+        for set_name in &["game0", "game1a", "game2", "game3", "game4"] {
+            loader.request(request(set_name));
+        }
+        let (_, loaded) = receiver.iter().find(|(set_name, _)| set_name == "game4").unwrap();
+        assert_eq!("game4", loaded?);
+
+        loader.request(request("unknown"));
+        let (_, loaded) = receiver.recv_timeout(Duration::from_secs(10))?;
+        assert!(loaded.is_err());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use romst::{RomsetMode, Romst, ScanOptions, data::reporter::{CancellationToken, scan_report::SetStatus}, sysout::ReportReporterSysOut};
 
 mod common;
 
@@ -6,4 +7,42 @@ mod common;
 fn empty_test() -> Result<()> {
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn checks_against_a_dat_without_importing() -> Result<()> {
+    let reader = Romst::get_data_reader_from_dat("testdata/test.dat")?;
+    let report = Romst::get_report(reader, vec!["testdata/split"], ScanOptions::new(RomsetMode::Split), CancellationToken::default(), None::<ReportReporterSysOut>)?;
+
+    assert_eq!(Some(&SetStatus::COMPLETE), report.get_set_statuses().get("game3"));
+
+    Ok(())
+}
+
+#[test]
+fn reads_the_scan_options_stored_as_json() -> Result<()> {
+    let stored = r#"{ "rom_mode": "Split", "bios": "NoBios", "game_filter": { "include": ["game3", "parent:game1"] }, "set_aliases": { "game3_fb": "game3" } }"#;
+    let options: ScanOptions = serde_json::from_str(stored)?;
+    assert_eq!(RomsetMode::Split, options.rom_mode);
+    assert!(!options.quick_check);
+    assert_eq!(options, serde_json::from_str(&serde_json::to_string(&options)?)?);
+
+    let reader = Romst::get_data_reader_from_dat("testdata/test.dat")?;
+    let report = Romst::get_report(reader, vec!["testdata/split"], options, CancellationToken::default(), None::<ReportReporterSysOut>)?;
+    assert_eq!(Some(&SetStatus::COMPLETE), report.get_set_statuses().get("game3"));
+    assert!(report.get_set_statuses().keys().all(|set_name| ["game1", "game1a", "game3"].contains(&set_name.as_str())));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn checks_on_the_runtime_of_the_caller() -> Result<()> {
+    let reader = Romst::get_data_reader_from_dat("testdata/test.dat")?;
+    let report = tokio::spawn(Romst::get_report_async(reader, vec!["testdata/split"], ScanOptions::new(RomsetMode::Split), CancellationToken::default(), None::<ReportReporterSysOut>)).await??;
+    assert_eq!(Some(&SetStatus::COMPLETE), report.get_set_statuses().get("game3"));
+
+    let reader = Romst::get_data_reader_from_dat("testdata/test.dat")?;
+    assert!(Romst::get_report(reader, vec!["testdata/split"], ScanOptions::new(RomsetMode::Split), CancellationToken::default(), None::<ReportReporterSysOut>).is_err());
+
+    Ok(())
+}